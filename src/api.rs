@@ -1,6 +1,15 @@
+pub mod anthropic;
 pub mod client;
+pub mod files;
 mod logging;
 #[cfg(test)]
 pub mod mock_client;
+pub mod ollama;
+pub mod openai;
+pub mod provider;
+pub mod raw_capture;
+pub mod runaway;
 pub mod stream;
+pub mod watchdog;
 pub use client::ApiClient;
+pub use provider::{ModelProvider, ProviderRequest};
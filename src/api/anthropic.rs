@@ -0,0 +1,207 @@
+use super::files::{files_api_enabled_for, references_uploaded_files, FILES_API_BETA};
+use super::provider::{
+    apply_sampling, map_api_request_error, set_turn_overrides, Endpoint, ModelProvider,
+    ProviderRequest,
+};
+use crate::config::ApiProtocol;
+use crate::turn_overrides::TurnOverrides;
+use crate::types::ApiMessage;
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+
+const COUNT_TOKENS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// The Anthropic Messages API, `/v1/messages`.
+pub struct AnthropicProvider {
+    endpoint: Endpoint,
+    anthropic_version: String,
+}
+
+impl AnthropicProvider {
+    pub fn new(endpoint: Endpoint, anthropic_version: String) -> Self {
+        Self {
+            endpoint,
+            anthropic_version,
+        }
+    }
+}
+
+impl AnthropicProvider {
+    /// The turn's body without the fields `/v1/messages/count_tokens`
+    /// rejects: streaming, the output limit, and sampling settings.
+    fn count_tokens_payload(&self, request: &ProviderRequest<'_>) -> Value {
+        let mut payload = self.request_payload(request);
+        if let Some(payload_object) = payload.as_object_mut() {
+            for field in ["stream", "max_tokens", "temperature", "top_p", "seed"] {
+                payload_object.remove(field);
+            }
+        }
+        payload
+    }
+}
+
+impl ModelProvider for AnthropicProvider {
+    fn protocol(&self) -> ApiProtocol {
+        ApiProtocol::AnthropicMessages
+    }
+
+    fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    fn request_url(&self) -> String {
+        self.endpoint.api_url.clone()
+    }
+
+    fn request_payload(&self, request: &ProviderRequest<'_>) -> Value {
+        let mut payload = json!({
+            "model": request.model,
+            "max_tokens": request.max_tokens,
+            "stream": true,
+            "system": request.system_prompt,
+            "messages": request.messages,
+        });
+        if let Some(tools) = request.tools {
+            let payload_object = payload
+                .as_object_mut()
+                .expect("payload must be a JSON object");
+            payload_object.insert("tool_choice".to_string(), json!({ "type": "auto" }));
+            payload_object.insert("tools".to_string(), tools.clone());
+        }
+        apply_sampling(&mut payload, &request.sampling, self.protocol());
+        self.apply_turn_overrides(&mut payload, request.overrides);
+        payload
+    }
+
+    /// A turn's temperature replaces any pinned top_p, which the Messages
+    /// API does not take alongside it, and a thinking budget turns on
+    /// extended thinking.
+    fn apply_turn_overrides(&self, payload: &mut Value, overrides: &TurnOverrides) {
+        set_turn_overrides(payload, overrides);
+        let Some(payload_object) = payload.as_object_mut() else {
+            return;
+        };
+        if overrides.temperature.is_some() {
+            payload_object.remove("top_p");
+        }
+        if let Some(budget) = overrides.thinking_budget {
+            // max_tokens must leave room for the answer after the budget.
+            let max_tokens = payload_object
+                .get("max_tokens")
+                .and_then(Value::as_u64)
+                .unwrap_or_default();
+            if max_tokens <= u64::from(budget) {
+                payload_object.insert(
+                    "max_tokens".to_string(),
+                    json!(u64::from(budget) + max_tokens),
+                );
+            }
+            // Extended thinking rejects adjusted sampling, so a temperature
+            // or top_p, pinned or set for the turn, gives way for this turn.
+            payload_object.remove("temperature");
+            payload_object.remove("top_p");
+            payload_object.insert(
+                "thinking".to_string(),
+                json!({ "type": "enabled", "budget_tokens": budget }),
+            );
+        }
+    }
+
+    fn authorize(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(api_key) = &self.endpoint.api_key {
+            request = request.header("x-api-key", api_key);
+        }
+        if !self.anthropic_version.trim().is_empty() {
+            request = request.header("anthropic-version", &self.anthropic_version);
+        }
+        request
+    }
+
+    fn turn_headers(
+        &self,
+        request: reqwest::RequestBuilder,
+        messages: &[ApiMessage],
+    ) -> reqwest::RequestBuilder {
+        if references_uploaded_files(messages) {
+            request.header("anthropic-beta", FILES_API_BETA)
+        } else {
+            request
+        }
+    }
+
+    fn supports_files_api(&self) -> bool {
+        files_api_enabled_for(&self.endpoint.api_url)
+    }
+
+    fn counts_tokens_exactly(&self) -> bool {
+        true
+    }
+
+    /// Counted exactly by `/v1/messages/count_tokens`, which is free.
+    fn count_tokens<'a>(&'a self, request: &'a ProviderRequest<'a>) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(async move {
+            let url = format!(
+                "{}/count_tokens",
+                self.endpoint.api_url.trim_end_matches('/')
+            );
+            let payload = self.count_tokens_payload(request);
+            let builder = self
+                .endpoint
+                .http
+                .post(&url)
+                .header("content-type", "application/json")
+                .json(&payload)
+                .timeout(COUNT_TOKENS_TIMEOUT);
+            let body: Value = self
+                .authorize(self.turn_headers(builder, request.messages))
+                .send()
+                .await
+                .map_err(|error| map_api_request_error(error, &url))?
+                .error_for_status()
+                .map_err(|error| map_api_request_error(error, &url))?
+                .json()
+                .await
+                .map_err(|error| map_api_request_error(error, &url))?;
+            body.get("input_tokens")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| anyhow!("token count from '{url}' returned no input_tokens"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Sampling;
+
+    #[test]
+    fn test_token_count_request_drops_sampling_settings() {
+        let provider = AnthropicProvider::new(
+            Endpoint {
+                http: reqwest::Client::new(),
+                api_url: "https://api.anthropic.com/v1/messages".to_string(),
+                api_key: None,
+            },
+            "2023-06-01".to_string(),
+        );
+        let request = ProviderRequest {
+            model: "claude-sonnet",
+            max_tokens: 1024,
+            system_prompt: "be brief",
+            messages: &[],
+            tools: None,
+            overrides: &TurnOverrides::default(),
+            sampling: Sampling {
+                temperature: None,
+                top_p: Some(0.9),
+                seed: Some(7),
+            },
+        };
+        assert_eq!(provider.request_payload(&request)["top_p"], 0.9);
+        assert_eq!(
+            provider.count_tokens_payload(&request),
+            json!({ "model": "claude-sonnet", "system": "be brief", "messages": [] })
+        );
+    }
+}
@@ -1,15 +1,24 @@
-use super::logging::{debug_payload_enabled, emit_debug_payload};
-use crate::config::Config;
-use crate::types::{ApiMessage, Content, ContentBlock};
-use crate::util::{is_local_endpoint_url, parse_bool_flag};
+#[cfg(test)]
+use super::anthropic::AnthropicProvider;
+use super::files::{files_url, multipart_body, FILES_API_BETA};
+#[cfg(test)]
+use super::provider::Endpoint;
+use super::provider::{map_api_request_error, provider_for, ModelProvider, ProviderRequest};
+use super::raw_capture::RawCapture;
+use super::stream::StreamParser;
+use crate::config::{ApiProtocol, Config, Sampling};
+use crate::language::ResponseLanguage;
+use crate::turn_overrides::TurnOverrides;
+use crate::types::ApiMessage;
+use crate::util::is_local_endpoint_url;
 use anyhow::anyhow;
 use anyhow::Result;
 use bytes::Bytes;
-use futures::{Stream, StreamExt};
+use futures::Stream;
 use serde_json::json;
 use serde_json::Value;
+use std::borrow::Cow;
 use std::pin::Pin;
-#[cfg(test)]
 use std::sync::Arc;
 
 pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>;
@@ -26,14 +35,26 @@ If native tool calls are unavailable, emit tagged tool syntax exactly:\n\
 Never claim a file was read/written/renamed/searched unless the corresponding tool call succeeded.\n\
 Do not narrate intended actions without executing the tool call.\n\
 Prefer search_files for targeted string matches and avoid full-file reads unless required.\n\
+To follow a growing log or build output, call tail_file repeatedly; it returns only the new lines.\n\
+In an unfamiliar workspace, call workspace_stats once to see languages, the largest files, and test locations instead of several list_files rounds, and tree to see how a directory is laid out.\n\
+Call repo_map for the functions and types defined in each source file; it stays current after your own file edits, so there is no need to call it again after each one.\n\
 Use list_files/search_files/read_file before saying a file is missing or present.\n\
 For edit_file, use a focused old_str snippet around the target change and avoid whole-file replacements; if an entire file rewrite is needed, use write_file instead.\n\
 For code edits, prefer this sequence: search_files -> read_file -> edit_file -> read_file (verify).\n\
 For read-only requests (show/read/list/count/status/log/diff), use read-only tools and do not call mutating tools unless the user explicitly asks for changes.\n\
 If asked what git tools are available, only list built-in git tools: git_status, git_diff, git_log, git_show, git_add, git_commit.\n\
+Before git_commit, call suggest_commit_message for a draft message, then call git_commit without a message to commit that draft.\n\
 Do not claim unsupported git tools like git_clone, git_init, git_remote, git_config, git_pull, git_push, git_branch, git_checkout, or git_stash.\n\
 Always send non-empty string paths for file tools.\n\
+Use run_tests to run the project's test suite; it returns a failure summary (test, file, message) before trimmed output.\n\
+When requirements are ambiguous and guessing would waste a turn, call ask_user with one short question, with options when the choices are clear; do not ask what the tools can find out.\n\
+Use remember for durable project facts worth keeping across sessions and recall to look them up; never store secrets.\n\
+Put throwaway artifacts (generated scripts, downloaded data) in scratch files with scratch_write and scratch_read, not in the workspace.\n\
 Avoid redundant loops: do not repeat identical read/search tool calls without new evidence.";
+const KEY_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// Appended to the system prompt when the workspace is not a git checkout
+/// and the git tools are left out of the schema.
+const NO_GIT_PROMPT: &str = "\nThis workspace is not a git repository; git tools are unavailable, so use the file tools to inspect changes.";
 
 #[cfg(test)]
 pub trait MockStreamProducer: Send + Sync {
@@ -42,39 +63,32 @@ pub trait MockStreamProducer: Send + Sync {
 
 #[derive(Clone)]
 pub struct ApiClient {
-    http: reqwest::Client,
-    api_key: Option<String>,
+    provider: Arc<dyn ModelProvider>,
     model: String,
-    api_url: String,
-    anthropic_version: String,
-    api_protocol: ApiProtocol,
+    sampling: Sampling,
     structured_tool_protocol: bool,
+    custom_tool_definitions: Vec<Value>,
+    git_tools: bool,
+    response_language: Option<ResponseLanguage>,
+    raw_capture: Option<RawCapture>,
     #[cfg(test)]
     mock_stream_producer: Option<Arc<dyn MockStreamProducer>>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ApiProtocol {
-    AnthropicMessages,
-    OpenAiChatCompletions,
-}
-
 impl ApiClient {
     pub fn new(config: &Config) -> Result<Self> {
-        let api_protocol = std::env::var("VEX_API_PROTOCOL")
-            .ok()
-            .and_then(parse_protocol)
-            .unwrap_or_else(|| infer_api_protocol(&config.api_url));
-        let structured_tool_protocol = resolve_structured_tool_protocol(&config.api_url);
+        let provider = provider_for(config);
+        let structured_tool_protocol = provider.supports_structured_tool_protocol();
 
         Ok(Self {
-            http: reqwest::Client::new(),
-            api_key: config.api_key.clone(),
+            provider,
             model: config.model.clone(),
-            api_url: config.api_url.clone(),
-            anthropic_version: config.anthropic_version.clone(),
-            api_protocol,
+            sampling: config.sampling,
             structured_tool_protocol,
+            custom_tool_definitions: Vec::new(),
+            git_tools: true,
+            response_language: ResponseLanguage::from_env(),
+            raw_capture: RawCapture::from_env(&config.working_dir),
             #[cfg(test)]
             mock_stream_producer: None,
         })
@@ -82,24 +96,156 @@ impl ApiClient {
 
     #[cfg(test)]
     pub fn new_mock(mock_producer: Arc<dyn MockStreamProducer>) -> Self {
-        Self {
+        let endpoint = Endpoint {
             http: reqwest::Client::new(),
+            api_url: "http://localhost:8000/v1/messages".to_string(),
             api_key: None,
+        };
+        Self {
+            provider: Arc::new(AnthropicProvider::new(endpoint, "2023-06-01".to_string())),
             model: "mock-model".to_string(),
-            api_url: "http://localhost:8000/v1/messages".to_string(),
-            anthropic_version: "2023-06-01".to_string(),
-            api_protocol: ApiProtocol::AnthropicMessages,
+            sampling: Sampling::default(),
             structured_tool_protocol: true,
+            custom_tool_definitions: Vec::new(),
+            git_tools: true,
+            response_language: None,
+            raw_capture: None,
             mock_stream_producer: Some(mock_producer),
         }
     }
 
+    /// Adds project tool schemas (from `.aistar/tools/`) to every request.
+    /// Definitions that reuse a built-in tool name are ignored.
+    pub fn with_custom_tool_definitions(mut self, definitions: Vec<Value>) -> Self {
+        let builtin = tool_definitions();
+        let builtin_names: Vec<&str> = builtin
+            .as_array()
+            .map(|tools| {
+                tools
+                    .iter()
+                    .filter_map(|tool| tool.get("name").and_then(Value::as_str))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.custom_tool_definitions = definitions
+            .into_iter()
+            .filter(|definition| {
+                definition
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .is_some_and(|name| !builtin_names.contains(&name))
+            })
+            .collect();
+        self
+    }
+
+    /// Leaves the git_* tools out of every request, for workspaces that are
+    /// not git checkouts, so the model does not spend rounds on failing calls.
+    pub fn with_git_tools(mut self, enabled: bool) -> Self {
+        self.git_tools = enabled;
+        self
+    }
+
+    fn request_tool_definitions(&self) -> Value {
+        let mut definitions = tool_definitions();
+        if let Some(tools) = definitions.as_array_mut() {
+            if !self.git_tools {
+                tools.retain(|tool| {
+                    !tool
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .is_some_and(|name| {
+                            name.starts_with("git_") || name == "suggest_commit_message"
+                        })
+                });
+            }
+            tools.extend(self.custom_tool_definitions.iter().cloned());
+        }
+        definitions
+    }
+
+    /// Asks for every reply in `language` (`VEX_RESPONSE_LANGUAGE`).
+    pub fn with_response_language(mut self, language: Option<ResponseLanguage>) -> Self {
+        self.response_language = language;
+        self
+    }
+
+    pub fn response_language(&self) -> Option<&ResponseLanguage> {
+        self.response_language.as_ref()
+    }
+
+    fn system_prompt(&self) -> Cow<'static, str> {
+        if self.git_tools && self.response_language.is_none() {
+            return Cow::Borrowed(SYSTEM_PROMPT);
+        }
+        let mut prompt = SYSTEM_PROMPT.to_string();
+        if !self.git_tools {
+            prompt.push_str(NO_GIT_PROMPT);
+        }
+        if let Some(language) = &self.response_language {
+            prompt.push('\n');
+            prompt.push_str(&language.directive());
+        }
+        Cow::Owned(prompt)
+    }
+
+    /// Serialized sizes of the system prompt and the tool schemas, which
+    /// every request carries.
+    /// The system prompt and the serialized tool schemas every request
+    /// carries besides the history; the schemas are empty on the text
+    /// protocol.
+    pub fn request_overhead_text(&self) -> (String, String) {
+        let tools = if self.structured_tool_protocol {
+            self.provider.tool_schemas(&self.request_tool_definitions())
+        } else {
+            Value::Null
+        };
+        let tool_schemas = match tools {
+            Value::Null => String::new(),
+            tools => tools.to_string(),
+        };
+        (self.system_prompt().into_owned(), tool_schemas)
+    }
+
     pub fn supports_structured_tool_protocol(&self) -> bool {
         self.structured_tool_protocol
     }
 
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn protocol(&self) -> ApiProtocol {
+        self.provider.protocol()
+    }
+
+    /// The reply token limit of every request (`VEX_MAX_TOKENS`).
+    pub fn max_tokens(&self) -> u32 {
+        resolve_max_tokens(self.api_url())
+    }
+
+    /// Sampling parameters pinned for the session.
+    pub fn sampling(&self) -> Sampling {
+        self.sampling
+    }
+
+    /// Pins sampling parameters for every request.
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// A parser for the streams [`Self::create_stream`] returns.
+    pub fn stream_parser(&self) -> StreamParser {
+        self.provider.stream_parser()
+    }
+
     pub fn is_local_endpoint(&self) -> bool {
-        is_local_endpoint_url(&self.api_url)
+        is_local_endpoint_url(self.api_url())
+    }
+
+    fn api_url(&self) -> &str {
+        &self.provider.endpoint().api_url
     }
 
     #[cfg(test)]
@@ -109,6 +255,16 @@ impl ApiClient {
     }
 
     pub async fn create_stream(&self, messages: &[ApiMessage]) -> Result<ByteStream> {
+        self.create_stream_with_overrides(messages, &TurnOverrides::default())
+            .await
+    }
+
+    /// Like [`Self::create_stream`], with per-turn model parameters applied.
+    pub async fn create_stream_with_overrides(
+        &self,
+        messages: &[ApiMessage],
+        overrides: &TurnOverrides,
+    ) -> Result<ByteStream> {
         #[cfg(test)]
         {
             if let Some(producer) = &self.mock_stream_producer {
@@ -116,130 +272,191 @@ impl ApiClient {
             }
         }
 
-        let request_url = self.request_url();
-        let max_tokens = resolve_max_tokens(&self.api_url);
-        let payload = match self.api_protocol {
-            ApiProtocol::AnthropicMessages => {
-                let mut payload = json!({
-                    "model": self.model,
-                    "max_tokens": max_tokens,
-                    "stream": true,
-                    "system": SYSTEM_PROMPT,
-                    "messages": messages,
-                });
-                if self.structured_tool_protocol {
-                    let payload_object = payload
-                        .as_object_mut()
-                        .expect("payload must be a JSON object");
-                    payload_object.insert("tool_choice".to_string(), json!({ "type": "auto" }));
-                    payload_object.insert("tools".to_string(), tool_definitions());
-                }
-                payload
-            }
-            ApiProtocol::OpenAiChatCompletions => {
-                let mut payload = json!({
-                    "model": self.model,
-                    "max_tokens": max_tokens,
-                    "stream": true,
-                    "messages": openai_messages(messages, SYSTEM_PROMPT),
-                });
-                if self.structured_tool_protocol {
-                    let payload_object = payload
-                        .as_object_mut()
-                        .expect("payload must be a JSON object");
-                    payload_object.insert("tool_choice".to_string(), json!("auto"));
-                    payload_object.insert("tools".to_string(), tool_definitions_openai());
-                }
-                payload
-            }
-        };
-
-        let mut request = self
-            .http
-            .post(&request_url)
-            .header("content-type", "application/json")
-            .json(&payload);
-
-        if debug_payload_enabled() {
-            emit_debug_payload(&request_url, &payload);
+        // Extended thinking drops the temperature, so it is not checked then.
+        if let (Some(temperature), None) = (overrides.temperature, overrides.thinking_budget) {
+            self.protocol().check_temperature(temperature)?;
         }
+        let tools = self.request_tools();
+        let system_prompt = self.system_prompt();
+        let request = self.provider_request(messages, &system_prompt, tools.as_ref(), overrides);
+        let stream = self.provider.create_stream(&request).await?;
+        Ok(match &self.raw_capture {
+            Some(capture) => capture.record(stream),
+            None => stream,
+        })
+    }
 
-        match self.api_protocol {
-            ApiProtocol::AnthropicMessages => {
-                if let Some(api_key) = &self.api_key {
-                    request = request.header("x-api-key", api_key);
-                }
-                if !self.anthropic_version.trim().is_empty() {
-                    request = request.header("anthropic-version", &self.anthropic_version);
-                }
-            }
-            ApiProtocol::OpenAiChatCompletions => {
-                if let Some(api_key) = &self.api_key {
-                    request = request.header("authorization", format!("Bearer {api_key}"));
-                }
+    /// Whether large attachments are uploaded through the Files API and
+    /// referenced by id instead of being outlined in the message.
+    pub fn files_api_enabled(&self) -> bool {
+        self.provider.supports_files_api()
+    }
+
+    /// Uploads `content` as a text file and returns its file id.
+    pub async fn upload_file(&self, file_name: &str, content: &str) -> Result<String> {
+        #[cfg(test)]
+        {
+            if self.mock_stream_producer.is_some() {
+                return Ok(format!("file_mock_{}", content.len()));
             }
         }
 
-        let response = request
+        let url = files_url(self.api_url())
+            .ok_or_else(|| anyhow!("'{}' has no Files API endpoint", self.api_url()))?;
+        let boundary = format!(
+            "vex-{:x}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos())
+                .unwrap_or_default()
+        );
+        let request = self
+            .provider
+            .endpoint()
+            .http
+            .post(&url)
+            .header("anthropic-beta", FILES_API_BETA)
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(multipart_body(&boundary, file_name, content.as_bytes()));
+        let response = self
+            .provider
+            .authorize(request)
             .send()
             .await
-            .map_err(|error| map_api_request_error(error, &request_url))?
+            .map_err(|error| map_api_request_error(error, &url))?
             .error_for_status()
-            .map_err(|error| map_api_request_error(error, &request_url))?;
-
-        let request_url_for_stream = request_url.clone();
-        let stream = response.bytes_stream().map(move |item| {
-            item.map_err(|error| map_api_request_error(error, &request_url_for_stream))
-        });
-        Ok(Box::pin(stream))
+            .map_err(|error| map_api_request_error(error, &url))?;
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|error| map_api_request_error(error, &url))?;
+        body.get("id")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("file upload to '{url}' returned no file id"))
     }
 
-    fn request_url(&self) -> String {
-        match self.api_protocol {
-            ApiProtocol::AnthropicMessages => self.api_url.clone(),
-            ApiProtocol::OpenAiChatCompletions => {
-                adapt_to_openai_chat_completions_url(&self.api_url)
+    /// Deletes an uploaded file.
+    pub async fn delete_file(&self, file_id: &str) -> Result<()> {
+        #[cfg(test)]
+        {
+            if self.mock_stream_producer.is_some() {
+                return Ok(());
             }
         }
+
+        let url = files_url(self.api_url())
+            .map(|base| format!("{base}/{file_id}"))
+            .ok_or_else(|| anyhow!("'{}' has no Files API endpoint", self.api_url()))?;
+        let request = self
+            .provider
+            .endpoint()
+            .http
+            .delete(&url)
+            .header("anthropic-beta", FILES_API_BETA);
+        self.provider
+            .authorize(request)
+            .timeout(KEY_CHECK_TIMEOUT)
+            .send()
+            .await
+            .map_err(|error| map_api_request_error(error, &url))?
+            .error_for_status()
+            .map_err(|error| map_api_request_error(error, &url))?;
+        Ok(())
     }
-}
 
-fn map_api_request_error(error: reqwest::Error, request_url: &str) -> anyhow::Error {
-    if error.is_connect() && is_local_endpoint_url(request_url) {
-        return anyhow!(
-            "cannot reach local API endpoint '{}': {}. Start your local server or update ANTHROPIC_API_URL.",
-            request_url,
-            error
-        );
+    /// Checks the API key with one `GET /models` request, which costs no
+    /// tokens. Fails only when the endpoint rejects the key; an unreachable
+    /// endpoint or one without a models listing is left for the first turn
+    /// to report.
+    pub async fn validate_api_key(&self) -> Result<()> {
+        if self.provider.endpoint().api_key.is_none() {
+            return Ok(());
+        }
+        let Some(models_url) = self.provider.models_url() else {
+            return Ok(());
+        };
+        let response = self
+            .provider
+            .authorize(self.provider.endpoint().http.get(&models_url))
+            .timeout(KEY_CHECK_TIMEOUT)
+            .send()
+            .await;
+        let Ok(response) = response else {
+            return Ok(());
+        };
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        match describe_key_rejection(status, &body) {
+            Some(problem) => Err(anyhow!(
+                "API key check against '{models_url}' failed (HTTP {status}): {problem}. \
+                 Set a valid ANTHROPIC_API_KEY, or fix the key in your profile, and start vex again."
+            )),
+            None => Ok(()),
+        }
     }
-    if error.is_connect() {
-        return anyhow!("cannot reach API endpoint '{}': {}", request_url, error);
+
+    /// Whether [`Self::count_tokens`] is an exact count from the backend.
+    pub fn counts_tokens_exactly(&self) -> bool {
+        self.provider.counts_tokens_exactly()
     }
-    if error.is_timeout() {
-        return anyhow!("API request to '{}' timed out: {}", request_url, error);
+
+    /// Input tokens a request with `messages` would use: counted by the
+    /// backend where it can, estimated otherwise.
+    pub async fn count_tokens(&self, messages: &[ApiMessage]) -> Result<u64> {
+        let tools = self.request_tools();
+        let system_prompt = self.system_prompt();
+        let overrides = TurnOverrides::default();
+        let request = self.provider_request(messages, &system_prompt, tools.as_ref(), &overrides);
+        self.provider.count_tokens(&request).await
     }
-    if let Some(status) = error.status() {
-        return anyhow!(
-            "API endpoint '{}' returned HTTP {}: {}",
-            request_url,
-            status,
-            error
-        );
+
+    fn request_tools(&self) -> Option<Value> {
+        self.structured_tool_protocol
+            .then(|| self.request_tool_definitions())
+    }
+
+    fn provider_request<'a>(
+        &'a self,
+        messages: &'a [ApiMessage],
+        system_prompt: &'a str,
+        tools: Option<&'a Value>,
+        overrides: &'a TurnOverrides,
+    ) -> ProviderRequest<'a> {
+        ProviderRequest {
+            model: &self.model,
+            max_tokens: self.max_tokens(),
+            system_prompt,
+            messages,
+            tools,
+            overrides,
+            sampling: self.sampling,
+        }
     }
-    anyhow!("API request to '{}' failed: {}", request_url, error)
 }
 
-fn resolve_structured_tool_protocol(api_url: &str) -> bool {
-    if let Some(value) = std::env::var("VEX_STRUCTURED_TOOL_PROTOCOL")
+/// What a key check response says about the key, when it was rejected.
+fn describe_key_rejection(status: u16, body: &str) -> Option<String> {
+    let provider_message = serde_json::from_str::<Value>(body)
         .ok()
-        .and_then(parse_bool_flag)
-    {
-        return value;
-    }
-
-    // Local endpoints default to text-protocol fallback because many local servers
-    // do not implement structured tool call blocks consistently.
-    !is_local_endpoint_url(api_url)
+        .and_then(|body| {
+            body.pointer("/error/message")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        })
+        .filter(|message| !message.trim().is_empty());
+    let problem = match status {
+        401 => "the key is invalid or has expired",
+        403 => "the key is not allowed to use this API; it may belong to a different workspace or organization",
+        _ => return None,
+    };
+    Some(match provider_message {
+        Some(message) => format!("{problem} ({message})"),
+        None => problem.to_string(),
+    })
 }
 
 fn resolve_max_tokens(api_url: &str) -> u32 {
@@ -257,174 +474,32 @@ fn resolve_max_tokens(api_url: &str) -> u32 {
     }
 }
 
-fn parse_protocol(value: String) -> Option<ApiProtocol> {
-    match value.trim().to_ascii_lowercase().as_str() {
-        "anthropic" | "anthropic_messages" | "messages" | "v1/messages" => {
-            Some(ApiProtocol::AnthropicMessages)
-        }
-        "openai" | "chat" | "chat_completions" | "openai_chat_completions" => {
-            Some(ApiProtocol::OpenAiChatCompletions)
-        }
-        _ => None,
-    }
-}
-
-fn infer_api_protocol(api_url: &str) -> ApiProtocol {
-    let normalized = api_url.trim().to_ascii_lowercase();
-    if normalized.contains("/chat/completions") || normalized.ends_with("/v1") {
-        ApiProtocol::OpenAiChatCompletions
-    } else {
-        ApiProtocol::AnthropicMessages
-    }
-}
-
-fn adapt_to_openai_chat_completions_url(api_url: &str) -> String {
-    let normalized = api_url.trim_end_matches('/');
-    if normalized.ends_with("/chat/completions") {
-        return normalized.to_string();
-    }
-    if let Some(prefix) = normalized.strip_suffix("/messages") {
-        return format!("{prefix}/chat/completions");
-    }
-    if normalized.ends_with("/v1") {
-        return format!("{normalized}/chat/completions");
-    }
-    normalized.to_string()
-}
-
-fn openai_messages(messages: &[ApiMessage], system_prompt: &str) -> Vec<Value> {
-    let mut out = Vec::with_capacity(messages.len() + 1);
-    out.push(json!({
-        "role": "system",
-        "content": system_prompt
-    }));
-
-    for message in messages {
-        append_openai_message(&mut out, message);
-    }
-
-    out
-}
-
-fn append_openai_message(out: &mut Vec<Value>, message: &ApiMessage) {
-    match (&message.role[..], &message.content) {
-        (role, Content::Text(text)) => {
-            out.push(json!({
-                "role": role,
-                "content": text
-            }));
-        }
-        ("assistant", Content::Blocks(blocks)) => {
-            let mut content = String::new();
-            let mut tool_calls = Vec::new();
-
-            for block in blocks {
-                match block {
-                    ContentBlock::Text { text } => content.push_str(text),
-                    ContentBlock::ToolUse { id, name, input } => {
-                        tool_calls.push(json!({
-                            "id": id,
-                            "type": "function",
-                            "function": {
-                                "name": name,
-                                "arguments": tool_input_to_json_string(input),
-                            }
-                        }));
-                    }
-                    ContentBlock::ToolResult { .. } => {}
-                }
-            }
-
-            let mut assistant_message = serde_json::Map::new();
-            assistant_message.insert("role".to_string(), json!("assistant"));
-            if content.is_empty() {
-                assistant_message.insert("content".to_string(), Value::Null);
-            } else {
-                assistant_message.insert("content".to_string(), Value::String(content));
-            }
-            if !tool_calls.is_empty() {
-                assistant_message.insert("tool_calls".to_string(), Value::Array(tool_calls));
-            }
-            out.push(Value::Object(assistant_message));
-        }
-        (role, Content::Blocks(blocks)) => {
-            let mut pushed = false;
-            for block in blocks {
-                match block {
-                    ContentBlock::ToolResult {
-                        tool_use_id,
-                        content,
-                        ..
-                    } => {
-                        out.push(json!({
-                            "role": "tool",
-                            "tool_call_id": tool_use_id,
-                            "content": content
-                        }));
-                        pushed = true;
-                    }
-                    ContentBlock::Text { text } => {
-                        out.push(json!({
-                            "role": role,
-                            "content": text
-                        }));
-                        pushed = true;
-                    }
-                    ContentBlock::ToolUse { .. } => {}
-                }
-            }
-
-            if !pushed {
-                out.push(json!({
-                    "role": role,
-                    "content": ""
-                }));
-            }
-        }
-    }
-}
-
-fn tool_input_to_json_string(value: &Value) -> String {
-    match value {
-        Value::String(text) => text.clone(),
-        _ => serde_json::to_string(value).unwrap_or_else(|_| "{}".to_string()),
-    }
-}
-
-fn tool_definitions_openai() -> Value {
-    let anthropic = tool_definitions();
-    let converted = anthropic
-        .as_array()
-        .map(|tools| {
-            tools
-                .iter()
-                .map(|tool| {
-                    json!({
-                        "type": "function",
-                        "function": {
-                            "name": tool.get("name").cloned().unwrap_or_else(|| json!("")),
-                            "description": tool.get("description").cloned().unwrap_or_else(|| json!("")),
-                            "parameters": tool
-                                .get("input_schema")
-                                .cloned()
-                                .unwrap_or_else(|| json!({ "type": "object" })),
-                        }
-                    })
-                })
-                .collect::<Vec<_>>()
-        })
-        .unwrap_or_default();
-    Value::Array(converted)
-}
-
 fn tool_definitions() -> serde_json::Value {
     json!([
         {
             "name": "read_file",
-            "description": "Read file content",
+            "description": "Read file content. Pass start_line and/or end_line (1-based, inclusive) to read only part of a large file, such as a section listed in an attached outline.",
             "input_schema": {
                 "type": "object",
-                "properties": { "path": { "type": "string" } },
+                "properties": {
+                    "path": { "type": "string" },
+                    "start_line": { "type": "integer", "minimum": 1 },
+                    "end_line": { "type": "integer", "minimum": 1 }
+                },
+                "required": ["path"]
+            }
+        },
+        {
+            "name": "tail_file",
+            "description": "Read only the lines appended to a log or build output file since the last tail_file call on it this session. The first call returns the file's last lines. Use instead of re-reading a growing file; pass reset to start over from its last lines.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "max_lines": { "type": "integer", "minimum": 1, "maximum": 2000 },
+                    "max_bytes": { "type": "integer", "minimum": 1, "maximum": 262144 },
+                    "reset": { "type": "boolean" }
+                },
                 "required": ["path"]
             }
         },
@@ -487,6 +562,37 @@ fn tool_definitions() -> serde_json::Value {
                 }
             }
         },
+        {
+            "name": "tree",
+            "description": "Show the directory tree under a path with file sizes and per-directory file counts, honoring .gitignore. Directories deeper than max_depth are summarized, not expanded.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "max_depth": { "type": "integer", "minimum": 1, "maximum": 12 },
+                    "max_entries": { "type": "integer", "minimum": 1, "maximum": 2000 }
+                }
+            }
+        },
+        {
+            "name": "workspace_stats",
+            "description": "Summarize the workspace in one call: file counts and lines by language, the largest files, and test/bench locations. Honors .gitignore; the result is reused for up to a minute unless a file tool changes the tree.",
+            "input_schema": {
+                "type": "object",
+                "properties": {}
+            }
+        },
+        {
+            "name": "repo_map",
+            "description": "Outline the top-level functions, types, and classes of each source file with line numbers. Built once, then updated for each file write_file, edit_file, or rename_file changes; pass refresh after changes made any other way.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Only map files under this path." },
+                    "refresh": { "type": "boolean", "description": "Rescan the whole workspace first." }
+                }
+            }
+        },
         {
             "name": "search_files",
             "description": "Search text across files and return matching lines.",
@@ -569,13 +675,102 @@ fn tool_definitions() -> serde_json::Value {
         },
         {
             "name": "git_commit",
-            "description": "Create a commit with the provided message.",
+            "description": "Create a commit with the provided message. Without a message, commits the draft from the last suggest_commit_message call; the user sees and approves it first.",
             "input_schema": {
                 "type": "object",
                 "properties": {
                     "message": { "type": "string" }
+                }
+            }
+        },
+        {
+            "name": "suggest_commit_message",
+            "description": "Draft a conventional-commit message from the staged diff (source \"staged\", the default) or from every uncommitted change (source \"session\"). Commits nothing; call git_commit without a message to commit the draft once the user approves it.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "source": { "type": "string", "enum": ["staged", "session"] },
+                    "intent": { "type": "string", "description": "What the change is for; defaults to the user's request for this turn." }
+                }
+            }
+        },
+        {
+            "name": "ask_user",
+            "description": "Ask the user a clarifying question and wait for the answer. Use it when the request is ambiguous and a wrong guess would waste work, not for facts the other tools can find. Offer options when there are a few clear choices; the user can still answer freely.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "question": { "type": "string" },
+                    "options": { "type": "array", "items": { "type": "string" }, "description": "Up to 9 suggested answers." }
+                },
+                "required": ["question"]
+            }
+        },
+        {
+            "name": "remember",
+            "description": "Store a short, durable fact about this project (conventions, decisions, gotchas) for future sessions.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "text": { "type": "string" }
+                },
+                "required": ["text"]
+            }
+        },
+        {
+            "name": "recall",
+            "description": "Look up previously remembered project facts relevant to a query.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1, "maximum": 50 }
+                },
+                "required": ["query"]
+            }
+        },
+        {
+            "name": "expand_tool_result",
+            "description": "Show the full content of an earlier tool result that was replaced in history by an 'identical to the earlier ... result' reference.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "hash": { "type": "string" }
+                },
+                "required": ["hash"]
+            }
+        },
+        {
+            "name": "scratch_write",
+            "description": "Write an intermediate file (a generated script, downloaded data, notes) to this turn's scratch directory under .aistar/sessions/ instead of the workspace. Scratch files are ignored by git and deleted when the session ends.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "content": { "type": "string" }
+                },
+                "required": ["name", "content"]
+            }
+        },
+        {
+            "name": "scratch_read",
+            "description": "Read a scratch file written earlier in this session with scratch_write.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" }
                 },
-                "required": ["message"]
+                "required": ["name"]
+            }
+        },
+        {
+            "name": "run_tests",
+            "description": "Run the project's test command (auto-detected from Cargo.toml, package.json, or pytest config, or set via VEX_TEST_COMMAND) and return a structured failure summary with trimmed output.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "filter": { "type": "string" }
+                }
             }
         }
     ])
@@ -588,26 +783,48 @@ mod tests {
 
     #[test]
     fn test_protocol_inference_defaults_to_anthropic_messages() {
-        let protocol = infer_api_protocol("http://localhost:8000/v1/messages");
+        let protocol = ApiProtocol::infer("http://localhost:8000/v1/messages");
         assert_eq!(protocol, ApiProtocol::AnthropicMessages);
     }
 
     #[test]
     fn test_protocol_inference_detects_openai_chat() {
-        let protocol = infer_api_protocol("http://localhost:8000/v1/chat/completions");
+        let protocol = ApiProtocol::infer("http://localhost:8000/v1/chat/completions");
         assert_eq!(protocol, ApiProtocol::OpenAiChatCompletions);
     }
 
     #[test]
-    fn test_openai_url_adapter_from_messages_endpoint() {
-        let adapted = adapt_to_openai_chat_completions_url("http://localhost:8000/v1/messages");
-        assert_eq!(adapted, "http://localhost:8000/v1/chat/completions");
+    fn test_git_tools_are_hidden_outside_git_workspaces() {
+        let client = ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(
+            vec![],
+        )))
+        .with_git_tools(false);
+        let names: Vec<String> = client
+            .request_tool_definitions()
+            .as_array()
+            .expect("tool definitions must be an array")
+            .iter()
+            .filter_map(|tool| tool.get("name").and_then(Value::as_str))
+            .map(ToOwned::to_owned)
+            .collect();
+        assert!(names.contains(&"read_file".to_string()));
+        assert!(!names.iter().any(|name| name.starts_with("git_")));
+        assert!(client.system_prompt().ends_with(NO_GIT_PROMPT));
     }
 
     #[test]
-    fn test_openai_url_adapter_from_v1_base_endpoint() {
-        let adapted = adapt_to_openai_chat_completions_url("http://localhost:8000/v1");
-        assert_eq!(adapted, "http://localhost:8000/v1/chat/completions");
+    fn test_key_check_reports_rejected_keys_only() {
+        let body = r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#;
+        assert_eq!(
+            describe_key_rejection(401, body).as_deref(),
+            Some("the key is invalid or has expired (invalid x-api-key)")
+        );
+        assert!(describe_key_rejection(403, "")
+            .expect("forbidden")
+            .contains("different workspace"));
+        assert_eq!(describe_key_rejection(200, "{}"), None);
+        assert_eq!(describe_key_rejection(404, ""), None);
+        assert_eq!(describe_key_rejection(529, body), None);
     }
 
     #[test]
@@ -620,11 +837,15 @@ mod tests {
     fn test_tool_definitions_cover_execute_tool_dispatch_names() {
         let expected: BTreeSet<&str> = BTreeSet::from([
             "read_file",
+            "tail_file",
             "write_file",
             "edit_file",
             "rename_file",
             "list_files",
             "list_directory",
+            "tree",
+            "workspace_stats",
+            "repo_map",
             "search_files",
             "search",
             "git_status",
@@ -633,6 +854,14 @@ mod tests {
             "git_show",
             "git_add",
             "git_commit",
+            "suggest_commit_message",
+            "remember",
+            "recall",
+            "expand_tool_result",
+            "scratch_write",
+            "scratch_read",
+            "run_tests",
+            "ask_user",
         ]);
 
         let names: BTreeSet<String> = tool_definitions()
@@ -656,7 +885,11 @@ mod tests {
             model: "mock-model".to_string(),
             api_url: "http://localhost:8000/v1/messages".to_string(),
             anthropic_version: "2023-06-01".to_string(),
+            api_protocol: None,
             working_dir: std::path::PathBuf::from("."),
+            emit_patches_dir: None,
+            check_api_key: false,
+            sampling: Sampling::default(),
         };
 
         let client = ApiClient::new(&config).expect("client should build");
@@ -673,11 +906,23 @@ mod tests {
             model: "local/llama.cpp".to_string(),
             api_url: "http://localhost:8000/v1/messages".to_string(),
             anthropic_version: "2023-06-01".to_string(),
+            api_protocol: None,
             working_dir: std::path::PathBuf::from("."),
+            emit_patches_dir: None,
+            check_api_key: false,
+            sampling: Sampling::default(),
         };
 
         let client = ApiClient::new(&config).expect("client should build");
         assert!(!client.supports_structured_tool_protocol());
+
+        // Ollama's native API returns structured tool calls, local or not.
+        let ollama = crate::config::Config {
+            api_url: "http://localhost:11434/api/chat".to_string(),
+            ..config
+        };
+        let client = ApiClient::new(&ollama).expect("client should build");
+        assert!(client.supports_structured_tool_protocol());
     }
 
     #[test]
@@ -689,7 +934,11 @@ mod tests {
             model: "claude-sonnet-4-5-20250929".to_string(),
             api_url: "https://api.anthropic.com/v1/messages".to_string(),
             anthropic_version: "2023-06-01".to_string(),
+            api_protocol: None,
             working_dir: std::path::PathBuf::from("."),
+            emit_patches_dir: None,
+            check_api_key: false,
+            sampling: Sampling::default(),
         };
 
         let client = ApiClient::new(&config).expect("client should build");
@@ -697,27 +946,49 @@ mod tests {
     }
 
     #[test]
-    fn test_openai_tool_definitions_match_base_tool_names() {
-        let base_names: BTreeSet<String> = tool_definitions()
+    fn test_custom_tool_definitions_extend_but_never_shadow_builtins() {
+        let client = ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(
+            vec![],
+        )))
+        .with_custom_tool_definitions(vec![
+            json!({ "name": "lint", "description": "custom", "input_schema": { "type": "object" } }),
+            json!({ "name": "read_file", "description": "shadow", "input_schema": { "type": "object" } }),
+        ]);
+
+        let definitions = client.request_tool_definitions();
+        let names: Vec<&str> = definitions
             .as_array()
             .expect("tool definitions must be an array")
             .iter()
-            .filter_map(|tool| tool.get("name").and_then(|value| value.as_str()))
-            .map(ToOwned::to_owned)
+            .filter_map(|tool| tool.get("name").and_then(Value::as_str))
             .collect();
+        assert_eq!(names.iter().filter(|name| **name == "read_file").count(), 1);
+        assert_eq!(names.last(), Some(&"lint"));
+    }
 
-        let openai_names: BTreeSet<String> = tool_definitions_openai()
+    #[test]
+    fn test_openai_tool_definitions_match_base_tool_names() {
+        let base_names: BTreeSet<String> = tool_definitions()
             .as_array()
-            .expect("openai tool definitions must be an array")
+            .expect("tool definitions must be an array")
             .iter()
-            .filter_map(|tool| {
-                tool.get("function")
-                    .and_then(|function| function.get("name"))
-                    .and_then(|name| name.as_str())
-            })
+            .filter_map(|tool| tool.get("name").and_then(|value| value.as_str()))
             .map(ToOwned::to_owned)
             .collect();
 
+        let openai_names: BTreeSet<String> =
+            crate::api::openai::function_tools(&tool_definitions())
+                .as_array()
+                .expect("openai tool definitions must be an array")
+                .iter()
+                .filter_map(|tool| {
+                    tool.get("function")
+                        .and_then(|function| function.get("name"))
+                        .and_then(|name| name.as_str())
+                })
+                .map(ToOwned::to_owned)
+                .collect();
+
         assert_eq!(openai_names, base_names);
     }
 
@@ -0,0 +1,176 @@
+use crate::types::{ApiMessage, Content, ContentBlock, DocumentSource};
+use crate::util::{is_local_endpoint_url, parse_bool_str};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+pub const FILES_API_ENV: &str = "VEX_FILES_API";
+/// Beta flag the Messages API needs for uploads and `file_id` references.
+pub const FILES_API_BETA: &str = "files-api-2025-04-14";
+
+/// Uploads default on for remote Messages endpoints; `VEX_FILES_API`
+/// forces them on or off.
+pub(super) fn files_api_enabled_for(api_url: &str) -> bool {
+    if files_url(api_url).is_none() {
+        return false;
+    }
+    std::env::var(FILES_API_ENV)
+        .ok()
+        .and_then(|value| parse_bool_str(&value))
+        .unwrap_or_else(|| !is_local_endpoint_url(api_url))
+}
+
+/// `.../v1/messages` -> `.../v1/files`; `None` for any other shape.
+pub(super) fn files_url(api_url: &str) -> Option<String> {
+    let base = api_url.trim().trim_end_matches('/');
+    base.strip_suffix("/messages")
+        .map(|prefix| format!("{prefix}/files"))
+}
+
+/// Whether a request carries `file_id` references and so needs the beta flag.
+pub(super) fn references_uploaded_files(messages: &[ApiMessage]) -> bool {
+    messages.iter().any(|message| match &message.content {
+        Content::Blocks(blocks) => blocks
+            .iter()
+            .any(|block| matches!(block, ContentBlock::Document { .. })),
+        Content::Text(_) => false,
+    })
+}
+
+/// A `multipart/form-data` body with a single `file` part.
+pub(super) fn multipart_body(boundary: &str, file_name: &str, content: &[u8]) -> Vec<u8> {
+    let file_name = file_name.replace(['"', '\r', '\n'], "_");
+    let mut body = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{file_name}\"\r\nContent-Type: text/plain\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(content);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body
+}
+
+/// Stands in for a document block where the file cannot be referenced: an
+/// endpoint without file references, or an upload deleted at session close.
+pub fn document_note(title: Option<&str>) -> String {
+    format!(
+        "[attached document {} is not available to this request; read it with read_file if it is still needed]",
+        title.unwrap_or("(untitled)")
+    )
+}
+
+/// A file queued to go with the next user message as an uploaded document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingDocument {
+    /// Workspace-relative path, shown to the model as the document title.
+    pub title: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadedFile {
+    pub id: String,
+    pub title: String,
+    pub bytes: usize,
+}
+
+impl UploadedFile {
+    pub fn document_block(&self) -> ContentBlock {
+        ContentBlock::Document {
+            source: DocumentSource::File {
+                file_id: self.id.clone(),
+            },
+            title: Some(self.title.clone()),
+        }
+    }
+}
+
+/// Files this session uploaded, keyed by content, so attaching the same
+/// content again reuses the earlier upload instead of sending it twice.
+#[derive(Debug, Default)]
+pub struct FileUploads {
+    by_content: HashMap<(u64, usize), UploadedFile>,
+}
+
+impl FileUploads {
+    pub fn get(&self, content: &str) -> Option<&UploadedFile> {
+        self.by_content.get(&content_key(content))
+    }
+
+    pub fn insert(&mut self, content: &str, file: UploadedFile) {
+        self.by_content.insert(content_key(content), file);
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_content.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_content.is_empty()
+    }
+
+    /// Forgets every upload and returns them for deletion.
+    pub fn drain(&mut self) -> Vec<UploadedFile> {
+        self.by_content.drain().map(|(_, file)| file).collect()
+    }
+}
+
+fn content_key(content: &str) -> (u64, usize) {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    content.hash(&mut hasher);
+    (hasher.finish(), content.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files_url_and_multipart_body() {
+        assert_eq!(
+            files_url("https://api.anthropic.com/v1/messages").as_deref(),
+            Some("https://api.anthropic.com/v1/files")
+        );
+        assert_eq!(files_url("https://example.com/v1/chat/completions"), None);
+
+        let body =
+            String::from_utf8(multipart_body("b0", "notes\".txt", b"hello")).expect("utf-8 body");
+        assert_eq!(
+            body,
+            "--b0\r\nContent-Disposition: form-data; name=\"file\"; filename=\"notes_.txt\"\r\nContent-Type: text/plain\r\n\r\nhello\r\n--b0--\r\n"
+        );
+    }
+
+    #[test]
+    fn test_uploads_are_reused_by_content() {
+        let mut uploads = FileUploads::default();
+        uploads.insert(
+            "fn main() {}",
+            UploadedFile {
+                id: "file_1".to_string(),
+                title: "src/main.rs".to_string(),
+                bytes: 12,
+            },
+        );
+        assert_eq!(
+            uploads.get("fn main() {}").map(|file| file.id.as_str()),
+            Some("file_1")
+        );
+        assert!(uploads.get("fn main() { }").is_none());
+        let serialized = serde_json::to_value(
+            uploads
+                .get("fn main() {}")
+                .expect("uploaded")
+                .document_block(),
+        )
+        .expect("serializes");
+        assert_eq!(
+            serialized,
+            serde_json::json!({
+                "type": "document",
+                "source": { "type": "file", "file_id": "file_1" },
+                "title": "src/main.rs"
+            })
+        );
+        assert_eq!(uploads.drain().len(), 1);
+        assert!(uploads.is_empty());
+    }
+}
@@ -23,7 +23,7 @@ pub fn emit_debug_payload(request_url: &str, payload: &Value) {
 pub fn emit_sse_parse_error(
     event_type: Option<&str>,
     json_data: &str,
-    parse_error: &serde_json::Error,
+    parse_error: &dyn std::fmt::Display,
 ) {
     let message = format!(
         "VEX_API ERROR sse_parse_failed error={parse_error}\nevent_type={}\ndata:\n{json_data}\n",
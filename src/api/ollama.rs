@@ -0,0 +1,398 @@
+use super::openai;
+use super::provider::{
+    apply_sampling, structured_tool_protocol_env, Endpoint, ModelProvider, ProviderRequest,
+};
+use super::stream::StreamParser;
+use crate::config::ApiProtocol;
+use crate::turn_overrides::TurnOverrides;
+use crate::types::{
+    ContentBlock, Delta, MessageDelta, StreamErrorPayload, StreamEvent, TokenUsage,
+};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+pub const OLLAMA_PORT: u16 = 11434;
+const CHAT_PATH: &str = "/api/chat";
+
+/// Ollama's native `/api/chat` endpoint for `api_url`, which may be the
+/// endpoint itself, a bare server address, or an OpenAI-compatible path on
+/// the same server.
+pub fn chat_url(api_url: &str) -> String {
+    let normalized = api_url.trim_end_matches('/');
+    if normalized.ends_with(CHAT_PATH) {
+        return normalized.to_string();
+    }
+    let base = ["/v1/chat/completions", "/v1/messages", "/v1", "/api"]
+        .iter()
+        .find_map(|suffix| normalized.strip_suffix(suffix))
+        .unwrap_or(normalized);
+    format!("{base}{CHAT_PATH}")
+}
+
+/// Whether `api_url` is Ollama's native `/api/chat` endpoint. A bare address
+/// on Ollama's port is not enough, since it also serves the
+/// OpenAI-compatible API.
+pub fn is_native_url(api_url: &str) -> bool {
+    api_url
+        .trim()
+        .trim_end_matches('/')
+        .to_ascii_lowercase()
+        .ends_with(CHAT_PATH)
+}
+
+/// Ollama's native `/api/chat`, streamed as NDJSON.
+pub struct OllamaProvider {
+    endpoint: Endpoint,
+}
+
+impl OllamaProvider {
+    pub fn new(endpoint: Endpoint) -> Self {
+        Self { endpoint }
+    }
+}
+
+impl ModelProvider for OllamaProvider {
+    fn protocol(&self) -> ApiProtocol {
+        ApiProtocol::OllamaChat
+    }
+
+    fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    fn request_url(&self) -> String {
+        chat_url(&self.endpoint.api_url)
+    }
+
+    fn request_payload(&self, request: &ProviderRequest<'_>) -> Value {
+        let mut payload = json!({
+            "model": request.model,
+            "stream": true,
+            "messages": chat_messages(openai::chat_messages(request.messages, request.system_prompt)),
+            "options": { "num_predict": request.max_tokens },
+        });
+        if let Some(tools) = request.tools {
+            payload
+                .as_object_mut()
+                .expect("payload must be a JSON object")
+                .insert("tools".to_string(), self.tool_schemas(tools));
+        }
+        apply_sampling(&mut payload, &request.sampling, self.protocol());
+        self.apply_turn_overrides(&mut payload, request.overrides);
+        payload
+    }
+
+    /// Sampling settings and the output limit go under `options`.
+    fn apply_turn_overrides(&self, payload: &mut Value, overrides: &TurnOverrides) {
+        let Some(payload_object) = payload.as_object_mut() else {
+            return;
+        };
+        if let Some(model) = &overrides.model {
+            payload_object.insert("model".to_string(), json!(model));
+        }
+        let options = payload_object.entry("options").or_insert_with(|| json!({}));
+        if let Some(max_tokens) = overrides.max_tokens {
+            options["num_predict"] = json!(max_tokens);
+        }
+        if let Some(temperature) = overrides.temperature {
+            options["temperature"] = json!(temperature);
+        }
+    }
+
+    fn tool_schemas(&self, tools: &Value) -> Value {
+        openai::function_tools(tools)
+    }
+
+    /// Ollama itself takes no key; `OLLAMA_API_KEY` is sent for a server
+    /// behind an authenticating proxy.
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.endpoint.api_key {
+            Some(api_key) => request.header("authorization", format!("Bearer {api_key}")),
+            None => request,
+        }
+    }
+
+    fn stream_parser(&self) -> StreamParser {
+        StreamParser::for_protocol(ApiProtocol::OllamaChat)
+    }
+
+    /// Tool calls come back as structured fields of the native API, so they
+    /// are on even for a local server.
+    fn supports_structured_tool_protocol(&self) -> bool {
+        structured_tool_protocol_env().unwrap_or(true)
+    }
+
+    fn models_url(&self) -> Option<String> {
+        let chat = self.request_url();
+        chat.strip_suffix(CHAT_PATH)
+            .map(|base| format!("{base}/api/tags"))
+    }
+
+    /// `models[].name` from `/api/tags`.
+    fn parse_model_list(&self, body: &Value) -> Vec<String> {
+        body.get("models")
+            .and_then(Value::as_array)
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|model| model.get("name")?.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Rewrites OpenAI chat messages into Ollama's shape: tool call arguments
+/// are JSON objects rather than strings, content is never null, and tool
+/// results name the tool they answer.
+pub fn chat_messages(openai_messages: Vec<Value>) -> Vec<Value> {
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+    openai_messages
+        .into_iter()
+        .map(|message| {
+            let Value::Object(mut message) = message else {
+                return message;
+            };
+            if message.get("content").is_none_or(Value::is_null) {
+                message.insert("content".to_string(), json!(""));
+            }
+            if let Some(Value::Array(calls)) = message.remove("tool_calls") {
+                let calls = calls
+                    .into_iter()
+                    .map(|call| {
+                        let id = call.get("id").and_then(Value::as_str).unwrap_or_default();
+                        let name = call
+                            .pointer("/function/name")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default();
+                        tool_names.insert(id.to_string(), name.to_string());
+                        let arguments = match call.pointer("/function/arguments") {
+                            Some(Value::String(text)) => serde_json::from_str(text)
+                                .unwrap_or_else(|_| Value::Object(Map::new())),
+                            Some(value) => value.clone(),
+                            None => Value::Object(Map::new()),
+                        };
+                        json!({ "function": { "name": name, "arguments": arguments } })
+                    })
+                    .collect();
+                message.insert("tool_calls".to_string(), Value::Array(calls));
+            }
+            if let Some(Value::String(id)) = message.remove("tool_call_id") {
+                if let Some(name) = tool_names.get(&id) {
+                    message.insert("tool_name".to_string(), json!(name));
+                }
+            }
+            Value::Object(message)
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaChunk {
+    #[serde(default)]
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    done_reason: Option<String>,
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    eval_count: Option<u64>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct OllamaMessage {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<OllamaToolCall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaToolCall {
+    #[serde(default)]
+    id: Option<String>,
+    function: OllamaFunction,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaFunction {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+/// Decodes Ollama's NDJSON stream, one JSON object per line. Text goes to
+/// block 0 and each tool call, which Ollama sends whole, becomes its own
+/// complete tool_use block after it.
+#[derive(Debug, Default)]
+pub struct OllamaStreamDecoder {
+    tool_blocks: usize,
+}
+
+impl OllamaStreamDecoder {
+    /// The events for one line, or `None` when it is not an Ollama chunk.
+    pub fn decode_line(&mut self, line: &str) -> Option<Vec<StreamEvent>> {
+        let chunk = serde_json::from_str::<OllamaChunk>(line).ok()?;
+        if let Some(message) = chunk.error {
+            return Some(vec![StreamEvent::Error {
+                error: StreamErrorPayload {
+                    error_type: "ollama_error".to_string(),
+                    message,
+                },
+            }]);
+        }
+
+        let mut events = Vec::new();
+        let message = chunk.message.unwrap_or_default();
+        if let Some(content) = message.content.filter(|content| !content.is_empty()) {
+            events.push(StreamEvent::ContentBlockDelta {
+                index: 0,
+                delta: Delta {
+                    delta_type: Some("text_delta".to_string()),
+                    text: Some(content),
+                    partial_json: None,
+                    thinking: None,
+                    signature: None,
+                },
+            });
+        }
+        for call in message.tool_calls {
+            self.tool_blocks += 1;
+            let index = self.tool_blocks;
+            let id = call
+                .id
+                .filter(|id| !id.is_empty())
+                .unwrap_or_else(|| format!("toolu_ollama_{index}"));
+            let arguments = match call.function.arguments {
+                Value::String(text) => text,
+                Value::Null => "{}".to_string(),
+                value => value.to_string(),
+            };
+            events.push(StreamEvent::ContentBlockStart {
+                index,
+                content_block: ContentBlock::ToolUse {
+                    id,
+                    name: call.function.name,
+                    input: Value::Object(Map::new()),
+                },
+            });
+            events.push(StreamEvent::ContentBlockDelta {
+                index,
+                delta: Delta {
+                    delta_type: Some("input_json_delta".to_string()),
+                    text: None,
+                    partial_json: Some(arguments),
+                    thinking: None,
+                    signature: None,
+                },
+            });
+            events.push(StreamEvent::ContentBlockStop { index });
+        }
+
+        if chunk.done {
+            // Report truncation the way Anthropic streams do so the
+            // conversation loop can continue the response.
+            let stop_reason = match chunk.done_reason.as_deref() {
+                Some("length") => Some("max_tokens".to_string()),
+                _ if self.tool_blocks > 0 => Some("tool_use".to_string()),
+                _ => Some("end_turn".to_string()),
+            };
+            events.push(StreamEvent::MessageDelta {
+                delta: MessageDelta { stop_reason },
+                usage: Some(TokenUsage {
+                    input_tokens: chunk.prompt_eval_count.unwrap_or_default(),
+                    output_tokens: chunk.eval_count.unwrap_or_default(),
+                }),
+            });
+            events.push(StreamEvent::MessageStop);
+        }
+        Some(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ndjson_chunks_map_onto_text_and_tool_use_blocks() {
+        let mut decoder = OllamaStreamDecoder::default();
+        let text = decoder
+            .decode_line(r#"{"model":"qwen3","message":{"role":"assistant","content":"Checking"},"done":false}"#)
+            .expect("chunk");
+        assert!(matches!(
+            &text[..],
+            [StreamEvent::ContentBlockDelta { index: 0, delta }] if delta.text.as_deref() == Some("Checking")
+        ));
+
+        let tools = decoder
+            .decode_line(r#"{"message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"read_file","arguments":{"path":"src/lib.rs"}}}]},"done":false}"#)
+            .expect("chunk");
+        match &tools[..] {
+            [StreamEvent::ContentBlockStart {
+                index: 1,
+                content_block: ContentBlock::ToolUse { id, name, .. },
+            }, StreamEvent::ContentBlockDelta { index: 1, delta }, StreamEvent::ContentBlockStop { index: 1 }] =>
+            {
+                assert_eq!(id, "toolu_ollama_1");
+                assert_eq!(name, "read_file");
+                assert_eq!(
+                    delta.partial_json.as_deref(),
+                    Some(r#"{"path":"src/lib.rs"}"#)
+                );
+            }
+            other => panic!("unexpected events: {other:?}"),
+        }
+
+        let done = decoder
+            .decode_line(r#"{"message":{"role":"assistant","content":""},"done":true,"done_reason":"stop","prompt_eval_count":26,"eval_count":9}"#)
+            .expect("chunk");
+        match &done[..] {
+            [StreamEvent::MessageDelta {
+                delta,
+                usage: Some(usage),
+            }, StreamEvent::MessageStop] => {
+                assert_eq!(delta.stop_reason.as_deref(), Some("tool_use"));
+                assert_eq!((usage.input_tokens, usage.output_tokens), (26, 9));
+            }
+            other => panic!("unexpected events: {other:?}"),
+        }
+        let error = decoder
+            .decode_line(r#"{"error":"model \"qwen3\" not found"}"#)
+            .expect("chunk");
+        assert!(matches!(&error[..], [StreamEvent::Error { .. }]));
+        assert!(decoder.decode_line("data: [DONE]").is_none());
+
+        let messages = chat_messages(vec![
+            json!({"role": "assistant", "content": null, "tool_calls": [
+                {"id": "toolu_1", "type": "function", "function": {"name": "read_file", "arguments": "{\"path\":\"a.rs\"}"}}
+            ]}),
+            json!({"role": "tool", "tool_call_id": "toolu_1", "content": "fn a() {}"}),
+        ]);
+        assert_eq!(
+            messages,
+            vec![
+                json!({"role": "assistant", "content": "", "tool_calls": [
+                    {"function": {"name": "read_file", "arguments": {"path": "a.rs"}}}
+                ]}),
+                json!({"role": "tool", "tool_name": "read_file", "content": "fn a() {}"}),
+            ]
+        );
+        assert_eq!(
+            chat_url("http://localhost:11434/v1/chat/completions"),
+            "http://localhost:11434/api/chat"
+        );
+        assert_eq!(
+            chat_url("http://gpu-box:11434/"),
+            "http://gpu-box:11434/api/chat"
+        );
+        assert!(!is_native_url("http://localhost:11434"));
+        assert!(is_native_url("https://ollama.internal/api/chat"));
+        assert!(!is_native_url("http://localhost:11434/v1/chat/completions"));
+    }
+}
@@ -0,0 +1,239 @@
+use super::files::document_note;
+use super::provider::{apply_sampling, Endpoint, ModelProvider, ProviderRequest};
+use crate::config::ApiProtocol;
+use crate::types::{ApiMessage, Content, ContentBlock};
+use serde_json::{json, Value};
+
+/// OpenAI-compatible `/v1/chat/completions`, as served by OpenAI, vLLM,
+/// and LM Studio.
+pub struct OpenAiProvider {
+    endpoint: Endpoint,
+}
+
+impl OpenAiProvider {
+    pub fn new(endpoint: Endpoint) -> Self {
+        Self { endpoint }
+    }
+}
+
+impl ModelProvider for OpenAiProvider {
+    fn protocol(&self) -> ApiProtocol {
+        ApiProtocol::OpenAiChatCompletions
+    }
+
+    fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    fn request_url(&self) -> String {
+        chat_completions_url(&self.endpoint.api_url)
+    }
+
+    fn request_payload(&self, request: &ProviderRequest<'_>) -> Value {
+        let mut payload = json!({
+            "model": request.model,
+            "max_tokens": request.max_tokens,
+            "stream": true,
+            "messages": chat_messages(request.messages, request.system_prompt),
+        });
+        if let Some(tools) = request.tools {
+            let payload_object = payload
+                .as_object_mut()
+                .expect("payload must be a JSON object");
+            payload_object.insert("tool_choice".to_string(), json!("auto"));
+            payload_object.insert("tools".to_string(), self.tool_schemas(tools));
+        }
+        apply_sampling(&mut payload, &request.sampling, self.protocol());
+        self.apply_turn_overrides(&mut payload, request.overrides);
+        payload
+    }
+
+    fn tool_schemas(&self, tools: &Value) -> Value {
+        function_tools(tools)
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.endpoint.api_key {
+            Some(api_key) => request.header("authorization", format!("Bearer {api_key}")),
+            None => request,
+        }
+    }
+}
+
+/// The chat completions endpoint for `api_url`.
+pub(super) fn chat_completions_url(api_url: &str) -> String {
+    let normalized = api_url.trim_end_matches('/');
+    if normalized.ends_with("/chat/completions") {
+        return normalized.to_string();
+    }
+    if let Some(prefix) = normalized.strip_suffix("/messages") {
+        return format!("{prefix}/chat/completions");
+    }
+    if normalized.ends_with("/v1") {
+        return format!("{normalized}/chat/completions");
+    }
+    // A bare server address, such as `http://localhost:11434`.
+    if reqwest::Url::parse(normalized).is_ok_and(|url| url.path() == "/") {
+        return format!("{normalized}/v1/chat/completions");
+    }
+    normalized.to_string()
+}
+
+pub(super) fn chat_messages(messages: &[ApiMessage], system_prompt: &str) -> Vec<Value> {
+    let mut out = Vec::with_capacity(messages.len() + 1);
+    out.push(json!({
+        "role": "system",
+        "content": system_prompt
+    }));
+
+    for message in messages {
+        append_message(&mut out, message);
+    }
+
+    out
+}
+
+fn append_message(out: &mut Vec<Value>, message: &ApiMessage) {
+    match (&message.role[..], &message.content) {
+        (role, Content::Text(text)) => {
+            out.push(json!({
+                "role": role,
+                "content": text
+            }));
+        }
+        ("assistant", Content::Blocks(blocks)) => {
+            let mut content = String::new();
+            let mut tool_calls = Vec::new();
+
+            for block in blocks {
+                match block {
+                    ContentBlock::Text { text } => content.push_str(text),
+                    ContentBlock::ToolUse { id, name, input } => {
+                        tool_calls.push(json!({
+                            "id": id,
+                            "type": "function",
+                            "function": {
+                                "name": name,
+                                "arguments": tool_input_to_json_string(input),
+                            }
+                        }));
+                    }
+                    // Chat Completions has no thinking blocks to send back.
+                    ContentBlock::ToolResult { .. }
+                    | ContentBlock::Document { .. }
+                    | ContentBlock::Thinking { .. }
+                    | ContentBlock::RedactedThinking { .. } => {}
+                }
+            }
+
+            let mut assistant_message = serde_json::Map::new();
+            assistant_message.insert("role".to_string(), json!("assistant"));
+            if content.is_empty() {
+                assistant_message.insert("content".to_string(), Value::Null);
+            } else {
+                assistant_message.insert("content".to_string(), Value::String(content));
+            }
+            if !tool_calls.is_empty() {
+                assistant_message.insert("tool_calls".to_string(), Value::Array(tool_calls));
+            }
+            out.push(Value::Object(assistant_message));
+        }
+        (role, Content::Blocks(blocks)) => {
+            let mut pushed = false;
+            for block in blocks {
+                match block {
+                    ContentBlock::ToolResult {
+                        tool_use_id,
+                        content,
+                        ..
+                    } => {
+                        out.push(json!({
+                            "role": "tool",
+                            "tool_call_id": tool_use_id,
+                            "content": content
+                        }));
+                        pushed = true;
+                    }
+                    ContentBlock::Text { text } => {
+                        out.push(json!({
+                            "role": role,
+                            "content": text
+                        }));
+                        pushed = true;
+                    }
+                    ContentBlock::Document { title, .. } => {
+                        // Chat Completions has no file references; the note
+                        // tells the model to read the file itself.
+                        out.push(json!({
+                            "role": role,
+                            "content": document_note(title.as_deref())
+                        }));
+                        pushed = true;
+                    }
+                    ContentBlock::ToolUse { .. }
+                    | ContentBlock::Thinking { .. }
+                    | ContentBlock::RedactedThinking { .. } => {}
+                }
+            }
+
+            if !pushed {
+                out.push(json!({
+                    "role": role,
+                    "content": ""
+                }));
+            }
+        }
+    }
+}
+
+fn tool_input_to_json_string(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        _ => serde_json::to_string(value).unwrap_or_else(|_| "{}".to_string()),
+    }
+}
+
+/// Anthropic-format tool schemas as chat completions `function` tools.
+pub(super) fn function_tools(anthropic: &Value) -> Value {
+    let converted = anthropic
+        .as_array()
+        .map(|tools| {
+            tools
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": tool.get("name").cloned().unwrap_or_else(|| json!("")),
+                            "description": tool.get("description").cloned().unwrap_or_else(|| json!("")),
+                            "parameters": tool
+                                .get("input_schema")
+                                .cloned()
+                                .unwrap_or_else(|| json!({ "type": "object" })),
+                        }
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    Value::Array(converted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_url_adapter_from_messages_endpoint() {
+        let adapted = chat_completions_url("http://localhost:8000/v1/messages");
+        assert_eq!(adapted, "http://localhost:8000/v1/chat/completions");
+    }
+
+    #[test]
+    fn test_openai_url_adapter_from_v1_base_endpoint() {
+        let adapted = chat_completions_url("http://localhost:8000/v1");
+        assert_eq!(adapted, "http://localhost:8000/v1/chat/completions");
+        let adapted = chat_completions_url("http://localhost:11434/");
+        assert_eq!(adapted, "http://localhost:11434/v1/chat/completions");
+    }
+}
@@ -0,0 +1,470 @@
+use super::anthropic::AnthropicProvider;
+use super::client::ByteStream;
+use super::logging::{debug_payload_enabled, emit_debug_payload};
+use super::ollama::OllamaProvider;
+use super::openai::OpenAiProvider;
+use super::stream::StreamParser;
+use crate::config::{ApiProtocol, Config, Sampling};
+use crate::turn_overrides::TurnOverrides;
+use crate::types::ApiMessage;
+use crate::util::{is_local_endpoint_url, parse_bool_flag};
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Rough size of a token, for backends that cannot count them.
+const ESTIMATED_CHARS_PER_TOKEN: usize = 4;
+const MODEL_LIST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// One turn's request as every backend sees it, before it is put in the
+/// backend's wire format.
+#[derive(Debug, Clone, Copy)]
+pub struct ProviderRequest<'a> {
+    pub model: &'a str,
+    pub max_tokens: u32,
+    pub system_prompt: &'a str,
+    pub messages: &'a [ApiMessage],
+    /// Anthropic-format tool schemas; `None` when tools are called through
+    /// the text syntax instead.
+    pub tools: Option<&'a Value>,
+    pub overrides: &'a TurnOverrides,
+    pub sampling: Sampling,
+}
+
+/// The server a provider talks to.
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    pub http: reqwest::Client,
+    pub api_url: String,
+    pub api_key: Option<String>,
+}
+
+impl Endpoint {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_url: config.api_url.clone(),
+            api_key: config.api_key.clone(),
+        }
+    }
+}
+
+/// A model backend. `ApiClient` assembles each turn the same way for every
+/// backend and leaves the wire format, credentials, stream decoding, and
+/// model listing to its provider, so a new backend implements this trait
+/// and is named in [`provider_for`]; the conversation loop is unchanged.
+pub trait ModelProvider: Send + Sync {
+    fn protocol(&self) -> ApiProtocol;
+
+    fn endpoint(&self) -> &Endpoint;
+
+    /// Where turns are posted.
+    fn request_url(&self) -> String;
+
+    fn request_payload(&self, request: &ProviderRequest<'_>) -> Value;
+
+    /// Anthropic-format tool schemas in the shape the backend takes.
+    fn tool_schemas(&self, tools: &Value) -> Value {
+        tools.clone()
+    }
+
+    /// Adds credentials to any request sent to this backend.
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder;
+
+    /// Headers a turn needs beyond credentials.
+    fn turn_headers(
+        &self,
+        request: reqwest::RequestBuilder,
+        _messages: &[ApiMessage],
+    ) -> reqwest::RequestBuilder {
+        request
+    }
+
+    /// A parser for the body [`Self::create_stream`] returns.
+    fn stream_parser(&self) -> StreamParser {
+        StreamParser::new()
+    }
+
+    /// Whether tools are sent as native tool schemas and calls come back as
+    /// tool blocks, rather than through the text syntax.
+    fn supports_structured_tool_protocol(&self) -> bool {
+        resolve_structured_tool_protocol(&self.endpoint().api_url)
+    }
+
+    /// Whether large attachments can go through the Files API.
+    fn supports_files_api(&self) -> bool {
+        false
+    }
+
+    /// The backend's model listing, when it has one.
+    fn models_url(&self) -> Option<String> {
+        models_url(&self.request_url())
+    }
+
+    /// Model names from a [`Self::models_url`] response.
+    fn parse_model_list(&self, body: &Value) -> Vec<String> {
+        body.get("data")
+            .and_then(Value::as_array)
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|model| model.get("id")?.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Sends a turn and returns the streamed response body.
+    fn create_stream<'a>(
+        &'a self,
+        request: &'a ProviderRequest<'a>,
+    ) -> BoxFuture<'a, Result<ByteStream>> {
+        Box::pin(async move {
+            let request_url = self.request_url();
+            let payload = self.request_payload(request);
+            if debug_payload_enabled() {
+                emit_debug_payload(&request_url, &payload);
+            }
+            let builder = self
+                .endpoint()
+                .http
+                .post(&request_url)
+                .header("content-type", "application/json")
+                .json(&payload);
+            let response = self
+                .authorize(self.turn_headers(builder, request.messages))
+                .send()
+                .await
+                .map_err(|error| map_api_request_error(error, &request_url))?
+                .error_for_status()
+                .map_err(|error| map_api_request_error(error, &request_url))?;
+            let stream: ByteStream =
+                Box::pin(response.bytes_stream().map(move |item| {
+                    item.map_err(|error| map_api_request_error(error, &request_url))
+                }));
+            Ok(stream)
+        })
+    }
+
+    /// The models the endpoint serves.
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let url = self
+                .models_url()
+                .ok_or_else(|| anyhow!("'{}' has no model listing", self.endpoint().api_url))?;
+            let body: Value = self
+                .authorize(self.endpoint().http.get(&url))
+                .timeout(MODEL_LIST_TIMEOUT)
+                .send()
+                .await
+                .map_err(|error| map_api_request_error(error, &url))?
+                .error_for_status()
+                .map_err(|error| map_api_request_error(error, &url))?
+                .json()
+                .await
+                .map_err(|error| map_api_request_error(error, &url))?;
+            Ok(self.parse_model_list(&body))
+        })
+    }
+
+    /// Applies per-turn overrides to a body from [`Self::request_payload`].
+    /// Extended thinking is a Messages API feature, so by default a thinking
+    /// budget is dropped.
+    fn apply_turn_overrides(&self, payload: &mut Value, overrides: &TurnOverrides) {
+        set_turn_overrides(payload, overrides);
+    }
+
+    /// Whether [`Self::count_tokens`] asks the backend rather than estimating.
+    fn counts_tokens_exactly(&self) -> bool {
+        false
+    }
+
+    /// Input tokens the request would use. Estimated from its size unless
+    /// the backend can count them.
+    fn count_tokens<'a>(&'a self, request: &'a ProviderRequest<'a>) -> BoxFuture<'a, Result<u64>> {
+        let chars = self.request_payload(request).to_string().len();
+        Box::pin(async move { Ok(chars.div_ceil(ESTIMATED_CHARS_PER_TOKEN) as u64) })
+    }
+}
+
+/// The provider for the configured protocol.
+pub fn provider_for(config: &Config) -> Arc<dyn ModelProvider> {
+    let endpoint = Endpoint::from_config(config);
+    match config.protocol() {
+        ApiProtocol::AnthropicMessages => Arc::new(AnthropicProvider::new(
+            endpoint,
+            config.anthropic_version.clone(),
+        )),
+        ApiProtocol::OpenAiChatCompletions => Arc::new(OpenAiProvider::new(endpoint)),
+        ApiProtocol::OllamaChat => Arc::new(OllamaProvider::new(endpoint)),
+    }
+}
+
+pub(super) fn map_api_request_error(error: reqwest::Error, request_url: &str) -> anyhow::Error {
+    if error.is_connect() && is_local_endpoint_url(request_url) {
+        return anyhow!(
+            "cannot reach local API endpoint '{}': {}. Start your local server or update ANTHROPIC_API_URL.",
+            request_url,
+            error
+        );
+    }
+    if error.is_connect() {
+        return anyhow!("cannot reach API endpoint '{}': {}", request_url, error);
+    }
+    if error.is_timeout() {
+        return anyhow!("API request to '{}' timed out: {}", request_url, error);
+    }
+    if let Some(status) = error.status() {
+        return anyhow!(
+            "API endpoint '{}' returned HTTP {}: {}",
+            request_url,
+            status,
+            error
+        );
+    }
+    anyhow!("API request to '{}' failed: {}", request_url, error)
+}
+
+/// The models listing next to a messages or chat completions endpoint.
+pub(super) fn models_url(request_url: &str) -> Option<String> {
+    let normalized = request_url.trim_end_matches('/');
+    normalized
+        .strip_suffix("/messages")
+        .or_else(|| normalized.strip_suffix("/chat/completions"))
+        .map(|prefix| format!("{prefix}/models"))
+}
+
+/// `VEX_STRUCTURED_TOOL_PROTOCOL`, when set.
+pub(super) fn structured_tool_protocol_env() -> Option<bool> {
+    std::env::var("VEX_STRUCTURED_TOOL_PROTOCOL")
+        .ok()
+        .and_then(parse_bool_flag)
+}
+
+/// `VEX_STRUCTURED_TOOL_PROTOCOL`, or on for remote endpoints.
+pub(super) fn resolve_structured_tool_protocol(api_url: &str) -> bool {
+    if let Some(value) = structured_tool_protocol_env() {
+        return value;
+    }
+
+    // Local endpoints default to text-protocol fallback because many local servers
+    // do not implement structured tool call blocks consistently.
+    !is_local_endpoint_url(api_url)
+}
+
+/// Applies the session's pinned sampling parameters to a request body,
+/// before any per-turn override. The Messages API has no seed, so it is
+/// sent only to chat completions and Ollama endpoints, and takes a
+/// temperature or a top_p but not both, so a pinned temperature wins there.
+pub(super) fn apply_sampling(payload: &mut Value, sampling: &Sampling, protocol: ApiProtocol) {
+    let Some(payload_object) = payload.as_object_mut() else {
+        return;
+    };
+    if sampling.is_empty() {
+        return;
+    }
+    let target = if protocol == ApiProtocol::OllamaChat {
+        match payload_object
+            .entry("options")
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+        {
+            Some(options) => options,
+            None => return,
+        }
+    } else {
+        payload_object
+    };
+    if let Some(temperature) = sampling.temperature {
+        target.insert("temperature".to_string(), json!(temperature));
+    }
+    let anthropic = protocol == ApiProtocol::AnthropicMessages;
+    if let (Some(top_p), false) = (sampling.top_p, anthropic && sampling.temperature.is_some()) {
+        target.insert("top_p".to_string(), json!(top_p));
+    }
+    if let (Some(seed), false) = (sampling.seed, protocol == ApiProtocol::AnthropicMessages) {
+        target.insert("seed".to_string(), json!(seed));
+    }
+}
+
+/// Sets a turn's model, max_tokens, and temperature at the top level of a
+/// request body, where the Messages and chat completions APIs take them.
+pub(super) fn set_turn_overrides(payload: &mut Value, overrides: &TurnOverrides) {
+    let Some(payload_object) = payload.as_object_mut() else {
+        return;
+    };
+    if let Some(model) = &overrides.model {
+        payload_object.insert("model".to_string(), json!(model));
+    }
+    if let Some(max_tokens) = overrides.max_tokens {
+        payload_object.insert("max_tokens".to_string(), json!(max_tokens));
+    }
+    if let Some(temperature) = overrides.temperature {
+        payload_object.insert("temperature".to_string(), json!(temperature));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(api_url: &str) -> Config {
+        Config {
+            api_key: Some("test-key".to_string()),
+            model: "test-model".to_string(),
+            api_url: api_url.to_string(),
+            anthropic_version: "2023-06-01".to_string(),
+            api_protocol: None,
+            working_dir: std::path::PathBuf::from("."),
+            emit_patches_dir: None,
+            check_api_key: false,
+            sampling: Sampling::default(),
+        }
+    }
+
+    fn providers() -> [Arc<dyn ModelProvider>; 3] {
+        [
+            "https://api.anthropic.com/v1/messages",
+            "https://api.openai.com/v1/chat/completions",
+            "http://localhost:11434/api/chat",
+        ]
+        .map(|api_url| provider_for(&config(api_url)))
+    }
+
+    #[test]
+    fn test_each_protocol_gets_its_own_provider() {
+        let cases = [
+            (
+                "https://api.anthropic.com/v1/messages",
+                ApiProtocol::AnthropicMessages,
+                Some("https://api.anthropic.com/v1/models"),
+            ),
+            (
+                "https://api.openai.com/v1/chat/completions/",
+                ApiProtocol::OpenAiChatCompletions,
+                Some("https://api.openai.com/v1/models"),
+            ),
+            (
+                "http://localhost:11434/api/chat",
+                ApiProtocol::OllamaChat,
+                Some("http://localhost:11434/api/tags"),
+            ),
+        ];
+        for (api_url, protocol, models) in cases {
+            let provider = provider_for(&config(api_url));
+            assert_eq!(provider.protocol(), protocol, "{api_url}");
+            assert_eq!(provider.models_url().as_deref(), models, "{api_url}");
+        }
+        assert_eq!(models_url("https://proxy.example/generate"), None);
+
+        let ollama = provider_for(&config("http://localhost:11434/api/chat"));
+        let tags = json!({"models": [{"name": "qwen3:8b"}, {"name": "llama3.2"}]});
+        assert_eq!(ollama.parse_model_list(&tags), vec!["qwen3:8b", "llama3.2"]);
+        let openai = provider_for(&config("https://api.openai.com/v1/chat/completions"));
+        let data = json!({"data": [{"id": "gpt-4o"}]});
+        assert_eq!(openai.parse_model_list(&data), vec!["gpt-4o"]);
+    }
+
+    #[test]
+    fn test_turn_overrides_replace_request_parameters() {
+        let [anthropic, openai, ollama] = providers();
+        let overrides = TurnOverrides {
+            model: Some("claude-opus-4-1-20250805".to_string()),
+            thinking_budget: Some(2048),
+            temperature: Some(0.2),
+            ..TurnOverrides::default()
+        };
+        let mut payload = json!({ "model": "claude-sonnet", "max_tokens": 1024 });
+        anthropic.apply_turn_overrides(&mut payload, &overrides);
+        assert_eq!(
+            payload,
+            json!({
+                "model": "claude-opus-4-1-20250805",
+                "max_tokens": 3072,
+                "thinking": { "type": "enabled", "budget_tokens": 2048 },
+            })
+        );
+
+        let mut payload = json!({ "model": "local/qwen", "max_tokens": 1024 });
+        openai.apply_turn_overrides(&mut payload, &overrides);
+        assert!(payload.get("thinking").is_none());
+        assert_eq!(payload["max_tokens"], 1024);
+
+        let mut payload = json!({ "model": "qwen3", "options": { "num_predict": 1024 } });
+        ollama.apply_turn_overrides(&mut payload, &overrides);
+        assert_eq!(
+            payload,
+            json!({
+                "model": "claude-opus-4-1-20250805",
+                "options": { "num_predict": 1024, "temperature": 0.2 },
+            })
+        );
+    }
+
+    #[test]
+    fn test_pinned_sampling_fits_each_protocol() {
+        let [anthropic, openai, _] = providers();
+        let sampling = Sampling {
+            temperature: Some(0.0),
+            top_p: Some(0.9),
+            seed: Some(7),
+        };
+        let mut payload = json!({ "model": "claude-sonnet", "max_tokens": 1024 });
+        apply_sampling(&mut payload, &sampling, ApiProtocol::AnthropicMessages);
+        assert_eq!(
+            payload,
+            json!({ "model": "claude-sonnet", "max_tokens": 1024, "temperature": 0.0 })
+        );
+        let top_p_only = Sampling {
+            temperature: None,
+            ..sampling
+        };
+        let mut top_p_payload = json!({ "model": "claude-sonnet" });
+        apply_sampling(
+            &mut top_p_payload,
+            &top_p_only,
+            ApiProtocol::AnthropicMessages,
+        );
+        assert_eq!(
+            top_p_payload,
+            json!({ "model": "claude-sonnet", "top_p": 0.9 })
+        );
+        let temperature = TurnOverrides {
+            temperature: Some(0.5),
+            ..TurnOverrides::default()
+        };
+        anthropic.apply_turn_overrides(&mut top_p_payload, &temperature);
+        assert_eq!(
+            top_p_payload,
+            json!({ "model": "claude-sonnet", "temperature": 0.5 })
+        );
+        let thinking = TurnOverrides {
+            thinking_budget: Some(2048),
+            ..TurnOverrides::default()
+        };
+        anthropic.apply_turn_overrides(&mut payload, &thinking);
+        assert!(payload.get("temperature").is_none());
+        assert!(payload.get("top_p").is_none());
+
+        let mut payload = json!({ "model": "gpt-4o" });
+        apply_sampling(&mut payload, &sampling, ApiProtocol::OpenAiChatCompletions);
+        let overrides = TurnOverrides {
+            temperature: Some(0.7),
+            ..TurnOverrides::default()
+        };
+        openai.apply_turn_overrides(&mut payload, &overrides);
+        assert_eq!(
+            payload,
+            json!({ "model": "gpt-4o", "temperature": 0.7, "top_p": 0.9, "seed": 7 })
+        );
+
+        let mut payload = json!({ "model": "qwen3", "options": { "num_predict": 1024 } });
+        apply_sampling(&mut payload, &sampling, ApiProtocol::OllamaChat);
+        assert_eq!(
+            payload["options"],
+            json!({ "num_predict": 1024, "temperature": 0.0, "top_p": 0.9, "seed": 7 })
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use super::client::ByteStream;
+use crate::state_dirs::StateDirs;
+use crate::util::parse_bool_str;
+use anyhow::{bail, Context, Result};
+use futures::StreamExt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const RAW_STREAM_ENV: &str = "VEX_RAW_STREAM";
+/// Older captures are deleted once the directory holds this many.
+const MAX_CAPTURES: usize = 200;
+const CHUNKS_SUFFIX: &str = ".chunks";
+
+static NEXT_CAPTURE: AtomicU64 = AtomicU64::new(0);
+
+/// Opt-in recorder of the raw SSE bytes of every response stream, for
+/// reproducing parse bugs with `vex debug-parse`. Each request writes
+/// `.aistar/raw/<millis>-<n>.sse` with the bytes exactly as received, and
+/// `<name>.sse.chunks` with the size of each network read, so a replay
+/// splits the stream at the same points.
+#[derive(Debug, Clone)]
+pub struct RawCapture {
+    dir: PathBuf,
+}
+
+impl RawCapture {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// `.aistar/raw/` in `working_dir` when `VEX_RAW_STREAM` is on.
+    pub fn from_env(working_dir: &Path) -> Option<Self> {
+        let enabled = std::env::var(RAW_STREAM_ENV)
+            .ok()
+            .and_then(|value| parse_bool_str(&value))
+            .unwrap_or(false);
+        enabled.then(|| Self::new(StateDirs::for_workspace(working_dir).raw_dir()))
+    }
+
+    /// Tees `stream` into a new capture. When the capture files cannot be
+    /// created the stream is returned unrecorded; a failed write stops
+    /// recording but never fails the request.
+    pub fn record(&self, stream: ByteStream) -> ByteStream {
+        let Ok((mut body, mut chunks)) = self.create_files() else {
+            return stream;
+        };
+        let mut recording = true;
+        Box::pin(stream.map(move |item| {
+            if let (true, Ok(bytes)) = (recording, &item) {
+                recording =
+                    body.write_all(bytes).is_ok() && writeln!(chunks, "{}", bytes.len()).is_ok();
+            }
+            item
+        }))
+    }
+
+    fn create_files(&self) -> Result<(File, File)> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        self.prune();
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default();
+        let sequence = NEXT_CAPTURE.fetch_add(1, Ordering::Relaxed);
+        let body_path = self.dir.join(format!("{millis}-{sequence}.sse"));
+        let chunks_path = chunks_path(&body_path);
+        let body = File::create(&body_path)
+            .with_context(|| format!("failed to create {}", body_path.display()))?;
+        let chunks = File::create(&chunks_path)
+            .with_context(|| format!("failed to create {}", chunks_path.display()))?;
+        Ok((body, chunks))
+    }
+
+    /// Keeps the newest captures, leaving room for the one being created.
+    fn prune(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut captures: Vec<(u128, u64, PathBuf)> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter_map(|path| {
+                let stem = path.file_name()?.to_str()?.strip_suffix(".sse")?;
+                let (millis, sequence) = stem.split_once('-')?;
+                Some((millis.parse().ok()?, sequence.parse().ok()?, path))
+            })
+            .collect();
+        if captures.len() < MAX_CAPTURES {
+            return;
+        }
+        captures.sort();
+        let excess = captures.len() + 1 - MAX_CAPTURES;
+        for (_, _, path) in captures.into_iter().take(excess) {
+            let _ = fs::remove_file(chunks_path(&path));
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn chunks_path(body_path: &Path) -> PathBuf {
+    let mut name = body_path.as_os_str().to_os_string();
+    name.push(CHUNKS_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// The chunks of a capture in the order they arrived. Without a `.chunks`
+/// file (for example a hand-written `.sse` file) the body is one chunk.
+pub fn read_capture(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let body = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let sizes_path = chunks_path(path);
+    let Ok(sizes) = fs::read_to_string(&sizes_path) else {
+        return Ok(vec![body]);
+    };
+    let sizes = sizes
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.trim().parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("{} is not a list of chunk sizes", sizes_path.display()))?;
+    let total: usize = sizes.iter().sum();
+    if total != body.len() {
+        bail!(
+            "{} lists {total} bytes but {} holds {}",
+            sizes_path.display(),
+            path.display(),
+            body.len()
+        );
+    }
+    let mut rest = body.as_slice();
+    let mut chunks = Vec::with_capacity(sizes.len());
+    for size in sizes {
+        let (chunk, tail) = rest.split_at(size);
+        chunks.push(chunk.to_vec());
+        rest = tail;
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use futures::stream;
+
+    #[tokio::test]
+    async fn test_recorded_stream_replays_with_the_same_chunk_boundaries() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let capture = RawCapture::new(dir.path().join("raw"));
+        // The split lands inside a multi-byte character.
+        let text = "data: {\"t\":\"é\"}\n\n";
+        let split = text.find('é').unwrap_or_default() + 1;
+        let parts: Vec<Result<Bytes>> = vec![
+            Ok(Bytes::copy_from_slice(&text.as_bytes()[..split])),
+            Ok(Bytes::copy_from_slice(&text.as_bytes()[split..])),
+        ];
+        let recorded: Vec<Bytes> = capture
+            .record(Box::pin(stream::iter(parts)))
+            .map(|item| item.expect("chunk"))
+            .collect()
+            .await;
+        assert_eq!(recorded.len(), 2);
+
+        let file = fs::read_dir(dir.path().join("raw"))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .find(|path| path.extension().is_some_and(|ext| ext == "sse"))
+            .expect("capture written");
+        let chunks = read_capture(&file)?;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].len(), split);
+        assert_eq!(chunks.concat(), text.as_bytes());
+
+        fs::remove_file(chunks_path(&file))?;
+        assert_eq!(read_capture(&file)?, vec![text.as_bytes().to_vec()]);
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet};
+
+/// Trailing text examined for repetition.
+const WINDOW_BYTES: usize = 8 * 1024;
+const CHECK_EVERY_BYTES: usize = 1024;
+/// Longest unit checked for verbatim repetition, e.g. `"the "` or `"....."`.
+const MAX_PERIOD_BYTES: usize = 256;
+/// Lines shorter than this (`}`, blank lines) repeat in ordinary output.
+const MIN_COUNTED_LINE_CHARS: usize = 4;
+const MIN_COUNTED_LINES: usize = 24;
+const MAX_REPEATED_LINE_RATIO: f64 = 0.9;
+
+/// Where a runaway text block should be cut and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunawayCut {
+    /// Bytes of the block to keep.
+    pub keep_bytes: usize,
+    /// Bytes of the block received so far.
+    pub block_bytes: usize,
+    /// Completes "the response ...".
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+struct BlockProgress {
+    bytes: usize,
+    tail: String,
+    checked_at: usize,
+}
+
+/// Watches streamed text blocks for output a misbehaving model will not
+/// stop on its own: a block larger than the byte budget, or a tail that is
+/// one phrase or a few lines repeated over and over.
+#[derive(Debug)]
+pub struct RunawayDetector {
+    max_block_bytes: usize,
+    blocks: HashMap<usize, BlockProgress>,
+}
+
+impl RunawayDetector {
+    pub fn new(max_block_bytes: usize) -> Self {
+        Self {
+            max_block_bytes,
+            blocks: HashMap::new(),
+        }
+    }
+
+    /// Records `delta` as appended to block `index`. Returns a cut once the
+    /// block has run away; later deltas for it should be dropped.
+    pub fn observe(&mut self, index: usize, delta: &str) -> Option<RunawayCut> {
+        let block = self.blocks.entry(index).or_default();
+        block.bytes += delta.len();
+        block.tail.push_str(delta);
+        if block.tail.len() > 2 * WINDOW_BYTES {
+            let start = ceil_char_boundary(&block.tail, block.tail.len() - WINDOW_BYTES);
+            block.tail.drain(..start);
+        }
+
+        if block.bytes > self.max_block_bytes {
+            return Some(RunawayCut {
+                keep_bytes: self.max_block_bytes,
+                block_bytes: block.bytes,
+                reason: format!(
+                    "passed the {} KiB limit for one block",
+                    self.max_block_bytes / 1024
+                ),
+            });
+        }
+        if block.bytes - block.checked_at < CHECK_EVERY_BYTES || block.tail.len() < WINDOW_BYTES {
+            return None;
+        }
+        block.checked_at = block.bytes;
+        let window_start = ceil_char_boundary(&block.tail, block.tail.len() - WINDOW_BYTES);
+        let window = &block.tail[window_start..];
+        let (keep_in_window, reason) = repeated_run(window)?;
+        Some(RunawayCut {
+            keep_bytes: block.bytes - window.len() + keep_in_window,
+            block_bytes: block.bytes,
+            reason,
+        })
+    }
+}
+
+/// Bytes of `window` to keep, up to the first repeat, when the window is a
+/// run of repeated text.
+fn repeated_run(window: &str) -> Option<(usize, String)> {
+    let bytes = window.as_bytes();
+    for period in 1..=MAX_PERIOD_BYTES.min(bytes.len() / 2) {
+        if bytes[period..] == bytes[..bytes.len() - period] {
+            return Some((
+                ceil_char_boundary(window, period),
+                format!("repeated the same {period}-byte sequence"),
+            ));
+        }
+    }
+
+    // The first line is usually cut mid-way by the window edge.
+    let first_break = window.find('\n')? + 1;
+    let mut seen = HashSet::new();
+    let mut counted = 0usize;
+    let mut repeated = 0usize;
+    let mut distinct_prefix_end = None;
+    let mut offset = first_break;
+    for line in window[first_break..].split_inclusive('\n') {
+        let end = offset + line.len();
+        offset = end;
+        if line.trim().chars().count() < MIN_COUNTED_LINE_CHARS {
+            continue;
+        }
+        counted += 1;
+        if seen.insert(line.trim()) {
+            continue;
+        }
+        repeated += 1;
+        distinct_prefix_end.get_or_insert(end - line.len());
+    }
+    if counted < MIN_COUNTED_LINES {
+        return None;
+    }
+    let ratio = repeated as f64 / counted as f64;
+    (ratio >= MAX_REPEATED_LINE_RATIO).then(|| {
+        (
+            distinct_prefix_end.unwrap_or(window.len()),
+            format!(
+                "repeated earlier lines in {:.0}% of its last {counted} lines",
+                ratio * 100.0
+            ),
+        )
+    })
+}
+
+/// Smallest char boundary at or after `index`.
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while index < text.len() && !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index.min(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(detector: &mut RunawayDetector, text: &str) -> Option<RunawayCut> {
+        text.as_bytes()
+            .chunks(100)
+            .map(|chunk| std::str::from_utf8(chunk).expect("ascii"))
+            .find_map(|chunk| detector.observe(0, chunk))
+    }
+
+    #[test]
+    fn test_repeated_phrase_and_lines_are_cut_after_the_first_copy() {
+        let intro = "Here is the plan for the refactor.\n".repeat(3);
+        let mut detector = RunawayDetector::new(1024 * 1024);
+        let cut = feed(
+            &mut detector,
+            &format!("{intro}{}", "I will now ".repeat(2000)),
+        )
+        .expect("phrase loop");
+        assert_eq!(cut.reason, "repeated the same 11-byte sequence");
+        assert!(cut.keep_bytes < cut.block_bytes);
+        assert!(cut.keep_bytes <= intro.len() + 2 * 1024);
+
+        let mut detector = RunawayDetector::new(1024 * 1024);
+        let looping: String = (0..2000u32)
+            .map(|step| {
+                format!(
+                    "Checking the file again (attempt {}).\n",
+                    step.count_ones() % 3
+                )
+            })
+            .collect();
+        let cut = feed(&mut detector, &looping).expect("line loop");
+        assert!(
+            cut.reason.starts_with("repeated earlier lines in"),
+            "{}",
+            cut.reason
+        );
+    }
+
+    #[test]
+    fn test_varied_text_passes_until_the_byte_budget() {
+        let varied: String = (0..4000)
+            .map(|step| format!("line {step} explains step {}\n", step * 7))
+            .collect();
+        let mut detector = RunawayDetector::new(1024 * 1024);
+        assert_eq!(feed(&mut detector, &varied), None);
+
+        let mut detector = RunawayDetector::new(32 * 1024);
+        let cut = feed(&mut detector, &varied).expect("over budget");
+        assert_eq!(cut.keep_bytes, 32 * 1024);
+        assert_eq!(cut.reason, "passed the 32 KiB limit for one block");
+    }
+}
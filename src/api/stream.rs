@@ -1,12 +1,83 @@
 use super::logging::emit_sse_parse_error;
-use crate::types::{ContentBlock, Delta, StreamEvent};
+use super::ollama::OllamaStreamDecoder;
+use crate::config::ApiProtocol;
+use crate::types::{ContentBlock, Delta, MessageDelta, StreamErrorPayload, StreamEvent};
 use anyhow::Result;
 use serde::Deserialize;
+use std::fmt;
+
+const MAX_BUFFER_SIZE: usize = 1024 * 1024; // 1MB limit
+const MAX_CONSECUTIVE_MALFORMED_FRAMES: usize = 8;
+
+/// Typed stream failures so callers can tell provider errors (some of which
+/// are retryable) apart from a broken stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamError {
+    /// The provider sent an `error` event mid-stream.
+    Provider { kind: String, message: String },
+    /// A single frame grew past the buffer limit without a delimiter.
+    BufferOverflow { limit: usize },
+    /// Too many frames in a row could not be decoded.
+    MalformedStream { consecutive_frames: usize },
+    /// No bytes arrived for `silent_secs` and the request was not retried.
+    Stalled { silent_secs: u64 },
+}
+
+impl StreamError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            StreamError::Provider { kind, .. }
+                if matches!(kind.as_str(), "overloaded_error" | "api_error" | "rate_limit_error")
+        )
+    }
+}
+
+impl From<StreamErrorPayload> for StreamError {
+    fn from(payload: StreamErrorPayload) -> Self {
+        StreamError::Provider {
+            kind: if payload.error_type.is_empty() {
+                "unknown_error".to_string()
+            } else {
+                payload.error_type
+            },
+            message: payload.message,
+        }
+    }
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Provider { kind, message } => {
+                write!(f, "Provider stream error ({kind}): {message}")
+            }
+            StreamError::BufferOverflow { limit } => {
+                write!(f, "Stream buffer limit exceeded ({limit} bytes)")
+            }
+            StreamError::MalformedStream { consecutive_frames } => write!(
+                f,
+                "Stream aborted after {consecutive_frames} consecutive malformed frames"
+            ),
+            StreamError::Stalled { silent_secs } => write!(
+                f,
+                "Stream stalled: no data from the provider for {silent_secs}s"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
 
 #[derive(Default)]
 pub struct StreamParser {
     buffer: Vec<u8>,
     openai_tools: Vec<OpenAiToolState>,
+    /// Set for Ollama's native API, which streams NDJSON instead of SSE.
+    ollama: Option<OllamaStreamDecoder>,
+    consecutive_malformed_frames: usize,
+    /// Skipped-frame notes kept for `vex debug-parse`; `None` outside it.
+    diagnostics: Option<Vec<String>>,
 }
 
 #[derive(Default, Clone)]
@@ -22,6 +93,17 @@ struct OpenAiToolState {
 struct OpenAiChunk {
     #[serde(default)]
     choices: Vec<OpenAiChoice>,
+    #[serde(default)]
+    error: Option<OpenAiError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiError {
+    #[serde(default)]
+    message: String,
+    #[serde(rename = "type")]
+    #[serde(default)]
+    error_type: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -63,12 +145,47 @@ impl StreamParser {
         Self::default()
     }
 
+    /// A parser for the stream format `protocol` responds with.
+    pub fn for_protocol(protocol: ApiProtocol) -> Self {
+        Self {
+            ollama: (protocol == ApiProtocol::OllamaChat).then(OllamaStreamDecoder::default),
+            ..Self::default()
+        }
+    }
+
+    /// Also keeps a note for every frame the parser skips, drained with
+    /// [`Self::take_diagnostics`].
+    pub fn with_diagnostics(mut self) -> Self {
+        self.diagnostics = Some(Vec::new());
+        self
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<String> {
+        self.diagnostics
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Bytes received after the last complete frame.
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Splits `chunk` into SSE frames and decodes them. Pings and provider
+    /// `error` events come back as typed events; an undecodable frame is logged
+    /// and skipped, and only a run of them fails the stream.
     pub fn process(&mut self, chunk: &[u8]) -> Result<Vec<StreamEvent>> {
-        const MAX_BUFFER_SIZE: usize = 1024 * 1024; // 1MB limit
         if self.buffer.len() + chunk.len() > MAX_BUFFER_SIZE {
-            anyhow::bail!("Stream buffer limit exceeded");
+            return Err(StreamError::BufferOverflow {
+                limit: MAX_BUFFER_SIZE,
+            }
+            .into());
         }
         self.buffer.extend_from_slice(chunk);
+        if self.ollama.is_some() {
+            return self.process_ndjson();
+        }
 
         let mut events = Vec::new();
 
@@ -77,7 +194,15 @@ impl StreamParser {
             let frame_bytes = self.buffer[..pos].to_vec();
             self.buffer.drain(..end);
 
-            let frame_text = String::from_utf8(frame_bytes)?;
+            let frame_text = match String::from_utf8(frame_bytes) {
+                Ok(text) => text,
+                Err(utf8_error) => {
+                    let lossy = String::from_utf8_lossy(utf8_error.as_bytes()).to_string();
+                    self.note_malformed_frame(None, &lossy, &utf8_error);
+                    self.record_malformed_frame()?;
+                    continue;
+                }
+            };
 
             let mut event_type = None;
             let mut data_lines = Vec::new();
@@ -93,27 +218,29 @@ impl StreamParser {
                 }
             }
 
+            if event_type.as_deref() == Some("ping") {
+                events.push(StreamEvent::Ping);
+                continue;
+            }
+
             if !data_lines.is_empty() {
                 let json_data = data_lines.join("\n");
-                let should_parse = if json_data == "[DONE]" {
-                    true
-                } else {
-                    event_type.as_deref().is_none_or(|ty| ty != "ping")
-                };
-
-                if should_parse {
-                    match serde_json::from_str::<StreamEvent>(&json_data) {
-                        Ok(evt) => events.push(evt),
-                        Err(anthropic_error) => {
-                            if let Some(openai_events) = self.parse_openai_chunk(&json_data) {
-                                events.extend(openai_events);
-                            } else {
-                                emit_sse_parse_error(
-                                    event_type.as_deref(),
-                                    &json_data,
-                                    &anthropic_error,
-                                );
-                            }
+                match serde_json::from_str::<StreamEvent>(&json_data) {
+                    Ok(evt) => {
+                        self.consecutive_malformed_frames = 0;
+                        events.push(evt);
+                    }
+                    Err(anthropic_error) => {
+                        if let Some(openai_events) = self.parse_openai_chunk(&json_data) {
+                            self.consecutive_malformed_frames = 0;
+                            events.extend(openai_events);
+                        } else {
+                            self.note_malformed_frame(
+                                event_type.as_deref(),
+                                &json_data,
+                                &anthropic_error,
+                            );
+                            self.record_malformed_frame()?;
                         }
                     }
                 }
@@ -123,6 +250,60 @@ impl StreamParser {
         Ok(events)
     }
 
+    /// Decodes every complete line of an Ollama NDJSON stream.
+    fn process_ndjson(&mut self) -> Result<Vec<StreamEvent>> {
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line_bytes: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line_bytes);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let decoded = self
+                .ollama
+                .as_mut()
+                .and_then(|decoder| decoder.decode_line(line));
+            match decoded {
+                Some(decoded) => {
+                    self.consecutive_malformed_frames = 0;
+                    events.extend(decoded);
+                }
+                None => {
+                    self.note_malformed_frame(None, line, &"not an Ollama chat chunk");
+                    self.record_malformed_frame()?;
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    fn note_malformed_frame(
+        &mut self,
+        event_type: Option<&str>,
+        data: &str,
+        error: &dyn fmt::Display,
+    ) {
+        match &mut self.diagnostics {
+            Some(notes) => notes.push(format!(
+                "skipped frame (event {}): {error}: {data}",
+                event_type.unwrap_or("<none>")
+            )),
+            None => emit_sse_parse_error(event_type, data, error),
+        }
+    }
+
+    fn record_malformed_frame(&mut self) -> Result<()> {
+        self.consecutive_malformed_frames += 1;
+        if self.consecutive_malformed_frames >= MAX_CONSECUTIVE_MALFORMED_FRAMES {
+            return Err(StreamError::MalformedStream {
+                consecutive_frames: self.consecutive_malformed_frames,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
     fn find_delimiter(&self) -> Option<(usize, usize)> {
         if let Some(pos) = self.buffer.windows(2).position(|w| w == b"\n\n") {
             return Some((pos, 2));
@@ -141,6 +322,14 @@ impl StreamParser {
         }
 
         let chunk = serde_json::from_str::<OpenAiChunk>(json_data).ok()?;
+        if let Some(error) = chunk.error {
+            return Some(vec![StreamEvent::Error {
+                error: StreamErrorPayload {
+                    error_type: error.error_type.unwrap_or_default(),
+                    message: error.message,
+                },
+            }]);
+        }
         if chunk.choices.is_empty() {
             return Some(Vec::new());
         }
@@ -154,6 +343,8 @@ impl StreamParser {
                         delta_type: Some("text_delta".to_string()),
                         text: Some(content),
                         partial_json: None,
+                        thinking: None,
+                        signature: None,
                     },
                 });
             }
@@ -164,8 +355,18 @@ impl StreamParser {
                 }
             }
 
-            if choice.finish_reason.is_some() {
+            if let Some(finish_reason) = choice.finish_reason {
                 self.close_openai_tool_blocks(&mut events);
+                // Report truncation the way Anthropic streams do so the
+                // conversation loop can continue the response.
+                if finish_reason == "length" {
+                    events.push(StreamEvent::MessageDelta {
+                        delta: MessageDelta {
+                            stop_reason: Some("max_tokens".to_string()),
+                        },
+                        usage: None,
+                    });
+                }
             }
         }
 
@@ -223,6 +424,8 @@ impl StreamParser {
                     delta_type: Some("input_json_delta".to_string()),
                     text: None,
                     partial_json: Some(partial_json),
+                    thinking: None,
+                    signature: None,
                 },
             });
         }
@@ -0,0 +1,169 @@
+use futures::{Stream, StreamExt};
+use std::time::Duration;
+
+pub const STREAM_STALL_SECS_ENV: &str = "VEX_STREAM_STALL_SECS";
+pub const STREAM_STALL_POLICY_ENV: &str = "VEX_STREAM_STALL_POLICY";
+const DEFAULT_STALL_SECS: u64 = 30;
+const MAX_STALL_SECS: u64 = 3_600;
+/// Times one turn sends a stalled request again before failing.
+pub const MAX_STALL_RETRIES: usize = 2;
+
+/// What to do with a stream that stays silent past the grace period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallPolicy {
+    /// Send the request again when nothing from it was shown yet;
+    /// otherwise fail the turn.
+    Retry,
+    /// Fail the turn.
+    Abort,
+}
+
+/// What a stall led to, as reported to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallAction {
+    /// Still waiting; the request is retried or aborted if silence continues.
+    Waiting,
+    Retrying,
+    Aborting,
+}
+
+impl StallAction {
+    /// Completes "stream stalled for 30s; ...".
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Waiting => "still waiting",
+            Self::Retrying => "sending the request again",
+            Self::Aborting => "giving up on this turn",
+        }
+    }
+}
+
+/// What the watchdog saw while waiting for the next chunk.
+#[derive(Debug)]
+pub enum StreamPoll<T> {
+    Item(T),
+    Ended,
+    /// The stream was silent for this long, twice the stall threshold.
+    Stalled(Duration),
+}
+
+/// Watches a response stream for silence. After the threshold with no
+/// bytes it reports the stall; after twice the threshold it gives up so the
+/// caller can retry or abort instead of waiting forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamWatchdog {
+    stall_after: Option<Duration>,
+    policy: StallPolicy,
+}
+
+impl StreamWatchdog {
+    /// `stall_after` of `None` waits indefinitely.
+    pub fn new(stall_after: Option<Duration>, policy: StallPolicy) -> Self {
+        Self {
+            stall_after,
+            policy,
+        }
+    }
+
+    /// `VEX_STREAM_STALL_SECS` sets the threshold (30 by default, `0` turns
+    /// the watchdog off) and `VEX_STREAM_STALL_POLICY=abort` fails stalled
+    /// turns instead of retrying them.
+    pub fn from_env() -> Self {
+        let secs = std::env::var(STREAM_STALL_SECS_ENV)
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map_or(DEFAULT_STALL_SECS, |secs| secs.min(MAX_STALL_SECS));
+        let policy = match std::env::var(STREAM_STALL_POLICY_ENV)
+            .ok()
+            .as_deref()
+            .map(str::trim)
+        {
+            Some("abort") => StallPolicy::Abort,
+            _ => StallPolicy::Retry,
+        };
+        Self::new((secs > 0).then(|| Duration::from_secs(secs)), policy)
+    }
+
+    pub fn policy(&self) -> StallPolicy {
+        self.policy
+    }
+
+    /// The next item of `stream`. `on_stall` is called once when the stream
+    /// has been silent for the threshold.
+    pub async fn next<S>(
+        &self,
+        stream: &mut S,
+        on_stall: impl FnOnce(Duration),
+    ) -> StreamPoll<S::Item>
+    where
+        S: Stream + Unpin,
+    {
+        let Some(stall_after) = self.stall_after else {
+            return stream
+                .next()
+                .await
+                .map_or(StreamPoll::Ended, StreamPoll::Item);
+        };
+        let item = match tokio::time::timeout(stall_after, stream.next()).await {
+            Ok(item) => item,
+            Err(_) => {
+                on_stall(stall_after);
+                match tokio::time::timeout(stall_after, stream.next()).await {
+                    Ok(item) => item,
+                    Err(_) => return StreamPoll::Stalled(stall_after * 2),
+                }
+            }
+        };
+        item.map_or(StreamPoll::Ended, StreamPoll::Item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    #[tokio::test]
+    async fn test_watchdog_reports_then_gives_up_on_a_silent_stream() {
+        let watchdog = StreamWatchdog::new(Some(Duration::from_millis(20)), StallPolicy::Retry);
+        let mut reported = None;
+        let mut silent = stream::pending::<u8>();
+        match watchdog
+            .next(&mut silent, |after| reported = Some(after))
+            .await
+        {
+            StreamPoll::Stalled(silence) => assert_eq!(silence, Duration::from_millis(40)),
+            other => panic!("expected a stall, got {other:?}"),
+        }
+        assert_eq!(reported, Some(Duration::from_millis(20)));
+
+        let mut slow = stream::once(async {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            7u8
+        })
+        .boxed();
+        let mut reported = false;
+        assert!(matches!(
+            watchdog.next(&mut slow, |_| reported = true).await,
+            StreamPoll::Item(7)
+        ));
+        assert!(
+            reported,
+            "a chunk after the threshold still reports the stall"
+        );
+        assert!(matches!(
+            watchdog
+                .next(&mut slow, |_| panic!("ended streams do not stall"))
+                .await,
+            StreamPoll::Ended
+        ));
+
+        let off = StreamWatchdog::new(None, StallPolicy::Abort);
+        let mut chunks = stream::iter([1u8]);
+        assert!(matches!(
+            off.next(&mut chunks, |_| {}).await,
+            StreamPoll::Item(1)
+        ));
+        assert_eq!(off.policy(), StallPolicy::Abort);
+    }
+}
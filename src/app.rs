@@ -1,21 +1,67 @@
 use crate::api::ApiClient;
-use crate::config::Config;
+use crate::attachments::{
+    AttachmentBody, AttachmentManager, DEFAULT_ADD_MAX_KB, DEFAULT_ATTACHMENT_BUDGET_CHARS,
+};
+use crate::audit::{format_utc, render_audit_records, AuditLog};
+use crate::claims::{claim_annotations, claim_check_enabled_from_env, last_turn_changes};
+use crate::clipboard::{
+    copy_with_command, osc52_sequence, parse_register, ClipboardTarget, RegisterKind, Registers,
+};
+use crate::config::{ApiProtocol, Config, Sampling};
+use crate::edit_diff::{format_edit_hunks, format_unified_file_diff, DiffContext, DiffSurface};
+use crate::follow_ups::{follow_ups_enabled_from_env, suggest_follow_ups};
+use crate::hooks::LifecycleHooks;
+use crate::notifier::Notifier;
+use crate::presets::{Preset, PresetStore};
+use crate::profiles::{active_profile_name, list_profiles, profiles_dir, Profile};
+use crate::rebase_assist::{RebaseAction, RebaseAssist, RebaseOutcome, RebaseStep};
+use crate::releases::{releases_url, ReleaseCheck};
+use crate::runtime::autosave::SessionAutosave;
 use crate::runtime::context::RuntimeContext;
 use crate::runtime::frontend::{ScrollAction, ScrollTarget, UserInputEvent};
 use crate::runtime::mode::RuntimeMode;
 use crate::runtime::policy::sanitize_assistant_text;
 use crate::runtime::r#loop::Runtime;
 use crate::runtime::UiUpdate;
-use crate::state::{ConversationManager, StreamBlock, ToolApprovalRequest};
-use crate::tools::ToolOperator;
+use crate::sandbox::{active_backend, SandboxPolicy};
+use crate::share::{default_bundle_path, BundleMetadata, SessionBundle};
+use crate::speech::{Speaker, SpeechStream, SpeechTarget};
+use crate::state::{
+    ApprovalTier, ConversationManager, SavedSessionSummary, SessionStore, StreamBlock,
+    ToolApprovalDecision, ToolApprovalRequest, ToolClass, ToolStatus, UserQuestionRequest,
+};
+use crate::tags::{matches_tag_filter, messages_tagged, parse_tags, summarize_tags};
+use crate::tool_keys::{first_str, NEW_TEXT_KEYS, OLD_TEXT_KEYS, PATH_KEYS};
+use crate::tool_preview::{preview_tool_input, LiveToolInput, ToolPreviewStyle};
+use crate::tools::{
+    file_locks_enabled_from_env, format_bytes, fuzzy_edit_enabled_from_env, git_repository_found,
+    render_memory_entries, result_dedup_enabled_from_env, symbol_context_enabled_from_env,
+    FileSnapshots, MemoryStore, ToolOperator, Vcs,
+};
+use crate::turn_overrides::TurnOverrides;
+use crate::ui::context_view::render_context_breakdown;
+use crate::ui::diff_style::{DiffPalette, DiffStyle};
+use crate::ui::help::{help_lines, HelpLine};
+use crate::ui::inline_images::InlineImage;
+use crate::ui::input_metrics::{truncate_with_ellipsis, Ellipsis};
+use crate::ui::plain::SeparatorStyle;
+use crate::ui::prompt_history::{
+    prompt_history_rows, PromptHistory, PromptHistoryRow, PromptOutcome,
+};
 use crate::ui::render::history_visual_line_count;
 #[cfg(test)]
-use crate::ui::render::input_visual_rows;
+use crate::ui::render::{input_visual_rows, MAX_INPUT_PANE_ROWS};
+use crate::ui::thinking::{ThinkingBlock, ThinkingDisplay};
+use crate::ui::transcript_spill::TranscriptSpill;
+use crate::ui::typewriter::Typewriter;
+use crate::usage::UsageLog;
+use crate::util::{format_elapsed, parse_bool_str};
 use anyhow::Result;
 #[cfg(test)]
 use crossterm::event::{Event, KeyCode, KeyModifiers};
-use std::cell::Cell;
-#[cfg(test)]
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
@@ -23,7 +69,22 @@ use tokio_util::sync::CancellationToken;
 struct PendingApproval {
     tool_name: String,
     input_preview: String,
-    response_tx: tokio::sync::oneshot::Sender<bool>,
+    class: ToolClass,
+    paths: Vec<String>,
+    tier: ApprovalTier,
+    response_tx: tokio::sync::oneshot::Sender<ToolApprovalDecision>,
+    reason_entry: bool,
+}
+
+/// The tool call waiting on the approval overlay.
+pub struct ToolApprovalOverlay<'a> {
+    pub tool_name: &'a str,
+    pub input_preview: &'a str,
+    pub class: ToolClass,
+    pub paths: &'a [String],
+    pub auto_approve_enabled: bool,
+    pub reason_entry: bool,
+    pub tier: &'a ApprovalTier,
 }
 
 struct PendingPatchApproval {
@@ -32,15 +93,50 @@ struct PendingPatchApproval {
     response_tx: Option<tokio::sync::oneshot::Sender<bool>>,
 }
 
+/// An ask_user question waiting for the next line the user submits.
+struct PendingQuestion {
+    options: Vec<String>,
+    response_tx: tokio::sync::oneshot::Sender<Option<String>>,
+}
+
+/// Files `/add` names when it leaves some out; the rest are counted.
+const MAX_LISTED_ADD_SKIPS: usize = 5;
+
+/// Skips a pending ask_user question without answering it.
+const SKIP_QUESTION_COMMAND: &str = "/skip";
+
+/// A paste with more lines than this asks before it lands in the input.
+const DEFAULT_PASTE_CONFIRM_LINES: usize = 200;
+/// Characters per line of threshold, so one huge line also asks.
+const PASTE_CONFIRM_CHARS_PER_LINE: usize = 100;
+const PASTE_CONFIRM_LINES_ENV: &str = "VEX_PASTE_CONFIRM_LINES";
+
+/// A paste held back from the input until the user picks where it goes.
+struct PendingPaste {
+    text: String,
+    lines: usize,
+    chars: usize,
+}
+
 const DEFAULT_MAX_HISTORY_LINES: usize = 2000;
+const DEFAULT_AUDIT_VIEW_COUNT: usize = 20;
+/// Images the transcript remembers for inline drawing; older ones stay
+/// placeholders.
+const MAX_INLINE_IMAGES: usize = 32;
 const MAX_HISTORY_LINES_ENV: &str = "VEX_MAX_HISTORY_LINES";
 const HISTORY_CONTENT_WIDTH_FALLBACK: usize = usize::MAX;
-#[cfg(test)]
-const MAX_INPUT_PANE_ROWS: usize = 6;
+const FOOTER_SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const FOOTER_SPINNER_FRAME_MS: u128 = 100;
+/// Body lines shown while an edit_file or write_file input streams in.
+const LIVE_PREVIEW_MAX_LINES: usize = 40;
+/// Display cells of a tool call summary in approval notes and the queue.
+const APPROVAL_SUMMARY_MAX_WIDTH: usize = 160;
 
 struct HistoryState {
     lines: Vec<String>,
+    dropped_lines: usize,
     turn_in_progress: bool,
+    turn_started_at: Option<Instant>,
     cancel_pending: bool,
     active_assistant_index: Option<usize>,
     scroll_offset: usize,
@@ -51,7 +147,9 @@ impl Default for HistoryState {
     fn default() -> Self {
         Self {
             lines: Vec::new(),
+            dropped_lines: 0,
             turn_in_progress: false,
+            turn_started_at: None,
             cancel_pending: false,
             active_assistant_index: None,
             scroll_offset: 0,
@@ -60,20 +158,138 @@ impl Default for HistoryState {
     }
 }
 
+/// `/history` picker. While `filtering`, typed characters edit the query;
+/// otherwise they are picker actions.
+#[derive(Default)]
+struct HistoryPicker {
+    query: String,
+    filtering: bool,
+    selected: usize,
+}
+
+/// Thinking blocks shown in the transcript, plus the one currently streaming.
+#[derive(Default)]
+struct ThinkingState {
+    blocks: Vec<ThinkingBlock>,
+    default_display: ThinkingDisplay,
+    /// Stream block index of the thinking block being received.
+    stream_index: Option<usize>,
+    /// Entry in `blocks` that receives the streaming text.
+    active: Option<usize>,
+}
+
+struct PresetPicker {
+    presets: Vec<Preset>,
+    selected: usize,
+}
+
+/// `/sessions` picker over the saved sessions, narrowed to those with a tag
+/// starting with `tag`. While `filtering`, typed characters edit the tag.
+struct SessionPicker {
+    sessions: Vec<SavedSessionSummary>,
+    current: Option<String>,
+    tag: String,
+    filtering: bool,
+    selected: usize,
+}
+
+impl SessionPicker {
+    fn matches(&self) -> Vec<&SavedSessionSummary> {
+        self.sessions
+            .iter()
+            .filter(|session| matches_tag_filter(&session.tags, &self.tag))
+            .collect()
+    }
+}
+
+/// Help screen. Typed characters filter it; arrows scroll.
+#[derive(Default)]
+struct HelpView {
+    query: String,
+    scroll: usize,
+}
+
 #[derive(Default)]
 struct OverlayState {
     pending_approval: Option<PendingApproval>,
     pending_patch_approval: Option<PendingPatchApproval>,
+    pending_paste: Option<PendingPaste>,
+    pending_question: Option<PendingQuestion>,
+    history_picker: Option<HistoryPicker>,
+    preset_picker: Option<PresetPicker>,
+    session_picker: Option<SessionPicker>,
+    help: Option<HelpView>,
     auto_approve_session: bool,
 }
 
+/// An edit_file or write_file call whose input is still streaming, shown as
+/// trailing history lines that are redrawn as the input grows.
+struct LiveToolPreview {
+    index: usize,
+    input: LiveToolInput,
+    /// Position of the first preview line, counting lines the history cap
+    /// has dropped.
+    first_line: Option<usize>,
+    shown: Vec<String>,
+}
+
 pub struct TuiMode {
     history_state: HistoryState,
     overlay_state: OverlayState,
     history_line_cap: usize,
+    /// Lines the cap pushed out, paged back in when scrolling up.
+    transcript_spill: TranscriptSpill,
+    /// Paged-in lines kept over the cap until the view returns to the bottom.
+    paged_in_lines: usize,
     repo_label: String,
+    vcs: Vcs,
+    workspace_root: PathBuf,
     history_content_width: Cell<usize>,
     active_stream_blocks: std::collections::HashMap<usize, StreamBlock>,
+    live_tool_preview: Option<LiveToolPreview>,
+    prompt_history: PromptHistory,
+    editor_prefill: RefCell<Option<String>>,
+    /// Confirmed paste text for the frontend to insert at the cursor.
+    paste_insert: RefCell<Option<String>>,
+    paste_confirm_lines: usize,
+    registers: Registers,
+    /// `name primary-arg` labels of this turn's tool calls, by call id.
+    tool_call_labels: std::collections::HashMap<String, (String, String)>,
+    clipboard: ClipboardTarget,
+    /// OSC 52 sequence for the frontend to write to the terminal.
+    clipboard_escape: RefCell<Option<String>>,
+    thinking: ThinkingState,
+    attachments: AttachmentManager,
+    config_highlights: Vec<(String, String)>,
+    bundle_metadata: BundleMetadata,
+    api_protocol: ApiProtocol,
+    speech: Option<SpeechStream>,
+    diff_context: DiffContext,
+    diff_style: DiffStyle,
+    release_check: ReleaseCheck,
+    /// Whether the release check was running at the last idle tick.
+    release_check_was_fetching: Cell<bool>,
+    profile: Option<Profile>,
+    turn_separators: SeparatorStyle,
+    /// Reserve transcript rows under image placeholders for the frontend
+    /// to draw the image over.
+    inline_image_rows: bool,
+    inline_images: Vec<InlineImage>,
+    separator_turn: usize,
+    response_separator_pending: bool,
+    /// Checks the response's claims against the turn's file changes.
+    claim_check: bool,
+    follow_ups_enabled: bool,
+    /// Queues response text and releases it a few characters per frame.
+    typewriter: Option<Typewriter>,
+    /// Suggested next prompts from the last turn, shown above the input.
+    follow_ups: Vec<String>,
+    /// Messages typed while a turn runs, sent in order as each turn ends.
+    queued_messages: VecDeque<String>,
+    /// The rebase `/rebase-assist` is walking through, if any.
+    rebase_assist: Option<RebaseAssist>,
+    /// The `/rebase-assist` git step running on its own thread, if any.
+    rebase_job: Option<std::sync::mpsc::Receiver<RebaseOutcome>>,
     pending_quit: bool,
     quit_requested: bool,
 }
@@ -84,1580 +300,5534 @@ impl TuiMode {
             history_state: HistoryState::default(),
             overlay_state: OverlayState::default(),
             history_line_cap: resolve_history_line_cap(),
+            transcript_spill: TranscriptSpill::from_env(),
+            paged_in_lines: 0,
             repo_label: resolve_repo_label(),
+            vcs: Vcs::detect(&std::env::current_dir().unwrap_or_default()),
+            workspace_root: std::env::current_dir().unwrap_or_default(),
             history_content_width: Cell::new(HISTORY_CONTENT_WIDTH_FALLBACK),
             active_stream_blocks: std::collections::HashMap::new(),
+            live_tool_preview: None,
+            prompt_history: PromptHistory::default(),
+            editor_prefill: RefCell::new(None),
+            registers: Registers::default(),
+            tool_call_labels: std::collections::HashMap::new(),
+            clipboard: ClipboardTarget::Terminal,
+            clipboard_escape: RefCell::new(None),
+            paste_insert: RefCell::new(None),
+            paste_confirm_lines: resolve_paste_confirm_lines(),
+            thinking: ThinkingState {
+                default_display: ThinkingDisplay::from_env(),
+                ..ThinkingState::default()
+            },
+            attachments: AttachmentManager::new(DEFAULT_ATTACHMENT_BUDGET_CHARS),
+            config_highlights: Vec::new(),
+            bundle_metadata: BundleMetadata::default(),
+            api_protocol: ApiProtocol::AnthropicMessages,
+            speech: None,
+            diff_context: DiffContext::default(),
+            diff_style: DiffStyle::default(),
+            release_check: ReleaseCheck::default(),
+            release_check_was_fetching: Cell::new(false),
+            profile: None,
+            turn_separators: SeparatorStyle::Off,
+            inline_image_rows: false,
+            inline_images: Vec::new(),
+            separator_turn: 0,
+            response_separator_pending: false,
+            claim_check: claim_check_enabled_from_env(),
+            follow_ups_enabled: follow_ups_enabled_from_env(),
+            typewriter: Typewriter::from_env(),
+            follow_ups: Vec::new(),
+            queued_messages: VecDeque::new(),
+            rebase_assist: None,
+            rebase_job: None,
             pending_quit: false,
             quit_requested: false,
         }
     }
 
-    fn mode_status_label(&self) -> &'static str {
-        if self.overlay_active() {
-            "overlay"
-        } else if self.pending_quit {
-            "quit-arm"
-        } else if self.history_state.cancel_pending {
-            "cancelling"
-        } else if self.history_state.turn_in_progress {
-            "streaming"
-        } else {
-            "ready"
-        }
+    /// Smooths streamed response text into a steady flow, or shows each
+    /// delta as it arrives when `None`.
+    pub fn with_typewriter(mut self, typewriter: Option<Typewriter>) -> Self {
+        self.typewriter = typewriter;
+        self
     }
 
-    fn approval_status_label(&self) -> &'static str {
-        if self.overlay_active() {
-            "pending"
-        } else if self.overlay_state.auto_approve_session {
-            "auto"
-        } else {
-            "none"
-        }
+    /// Marks each prompt and response with a separator line. Only plain
+    /// output turns this on; the TUI has its own layout.
+    pub fn set_turn_separators(&mut self, style: SeparatorStyle) {
+        self.turn_separators = style;
     }
 
-    pub fn status_line(&self) -> String {
-        let history_rows =
-            history_visual_line_count(&self.history_state.lines, self.history_content_width.get());
-        format!(
-            "mode:{} approval:{} history:{} repo:{}",
-            self.mode_status_label(),
-            self.approval_status_label(),
-            history_rows,
-            self.repo_label
-        )
+    /// Leaves room under image placeholders for the frontend to draw the
+    /// image. Only a frontend that can draw images turns this on.
+    pub fn set_inline_images(&mut self, enabled: bool) {
+        self.inline_image_rows = enabled;
     }
 
-    pub fn overlay_active(&self) -> bool {
-        self.overlay_state.pending_approval.is_some()
-            || self.overlay_state.pending_patch_approval.is_some()
+    /// Images shown in the transcript, oldest first.
+    pub fn inline_images(&self) -> &[InlineImage] {
+        &self.inline_images
     }
 
-    fn patch_overlay_active(&self) -> bool {
-        self.overlay_state.pending_patch_approval.is_some()
+    pub fn with_workspace_root(mut self, workspace_root: PathBuf) -> Self {
+        self.vcs = Vcs::detect(&workspace_root);
+        self.workspace_root = workspace_root;
+        self
     }
 
-    pub fn history_lines(&self) -> &[String] {
-        &self.history_state.lines
+    /// Label/value pairs shown under "Current config" on the help screen.
+    pub fn with_config_highlights(mut self, highlights: Vec<(String, String)>) -> Self {
+        self.config_highlights = highlights;
+        self
     }
 
-    pub fn active_assistant_index(&self) -> Option<usize> {
-        self.history_state.active_assistant_index
+    pub fn with_bundle_metadata(mut self, metadata: BundleMetadata) -> Self {
+        self.bundle_metadata = metadata;
+        self
     }
 
-    pub fn history_scroll_offset(&self) -> usize {
-        self.history_state.scroll_offset
+    /// The endpoint's protocol, which decides what a `/think` turn drops.
+    pub fn with_api_protocol(mut self, protocol: ApiProtocol) -> Self {
+        self.api_protocol = protocol;
+        self
     }
 
-    pub fn quit_requested(&self) -> bool {
-        self.quit_requested
+    /// Names the pinned sampling a `/think` turn does not send: the Messages
+    /// API takes neither temperature nor top_p with extended thinking.
+    fn dropped_sampling_note(&self, overrides: &TurnOverrides) -> String {
+        if overrides.thinking_budget.is_none()
+            || self.api_protocol != ApiProtocol::AnthropicMessages
+        {
+            return String::new();
+        }
+        let pinned = Sampling {
+            seed: None,
+            ..self.bundle_metadata.sampling
+        };
+        if pinned.is_empty() {
+            return String::new();
+        }
+        format!("; pinned {} dropped: thinking", pinned.describe())
     }
 
-    pub fn pending_patch_overlay(&self) -> Option<(&str, usize)> {
-        self.overlay_state
-            .pending_patch_approval
-            .as_ref()
-            .map(|pending| (pending.patch_preview.as_str(), pending.scroll_offset))
+    /// Speaks response text (not thinking or tool output) as it streams.
+    pub fn with_speaker(mut self, speaker: Speaker) -> Self {
+        self.speech = Some(SpeechStream::new(speaker));
+        self
     }
 
-    pub fn pending_tool_overlay(&self) -> Option<(&str, &str, bool)> {
-        self.overlay_state.pending_approval.as_ref().map(|pending| {
-            (
-                pending.tool_name.as_str(),
-                pending.input_preview.as_str(),
-                self.overlay_state.auto_approve_session,
-            )
-        })
+    pub fn with_diff_style(mut self, style: DiffStyle) -> Self {
+        self.diff_style = style;
+        self
     }
 
-    pub fn set_history_content_width(&self, width: usize) {
-        self.history_content_width.set(width.max(1));
+    pub fn workspace_root(&self) -> &Path {
+        &self.workspace_root
     }
 
-    fn resolve_pending_approval(&mut self, approved: bool) {
-        if let Some(pending) = self.overlay_state.pending_approval.take() {
-            let _ = pending.response_tx.send(approved);
-        }
+    pub fn diff_style(&self) -> DiffStyle {
+        self.diff_style
     }
 
-    fn handle_approval_input(&mut self, input: &str) {
-        let normalized = input.trim().to_lowercase();
-        let context = self
-            .overlay_state
-            .pending_approval
-            .as_ref()
-            .map(|p| summarize_tool_approval_context(&p.tool_name, &p.input_preview))
-            .unwrap_or_else(|| "unknown".to_string());
-        match normalized.as_str() {
-            "1" | "y" | "yes" => {
-                self.push_history_line(format!("[tool approval accepted once: {context}]"));
-                self.resolve_pending_approval(true);
+    /// Diff context settings, shared with the tool operator so
+    /// `/diffcontext` also changes approval previews and edit results.
+    pub fn with_diff_context(mut self, context: DiffContext) -> Self {
+        self.diff_context = context;
+        self
+    }
+
+    /// The release check behind the status-bar update notice and
+    /// `/release-notes`.
+    pub fn with_release_check(mut self, check: ReleaseCheck) -> Self {
+        self.release_check = check;
+        self
+    }
+
+    /// The named profile this session started with, shown by `/profile`.
+    pub fn with_profile(mut self, profile: Option<Profile>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Files larger than `budget_chars` are attached as outlines.
+    pub fn with_attachment_budget(mut self, budget_chars: usize) -> Self {
+        self.attachments = AttachmentManager::new(budget_chars);
+        self
+    }
+
+    /// Files over the attachment budget are uploaded through the provider's
+    /// Files API instead of being outlined. Set after the budget.
+    pub fn with_file_uploads(mut self, enabled: bool) -> Self {
+        self.attachments =
+            AttachmentManager::new(self.attachments.budget_chars()).with_uploads(enabled);
+        self
+    }
+
+    /// Handles slash commands that are answered locally instead of being sent
+    /// to the model. Returns true when `input` was consumed.
+    fn handle_local_command(&mut self, input: &str, ctx: &RuntimeContext) -> bool {
+        let trimmed = input.trim();
+        if let Some(query) = trimmed.strip_prefix("/history") {
+            if query.is_empty() || query.starts_with(char::is_whitespace) {
+                self.open_history_picker(query.trim());
+                return true;
             }
-            "2" | "a" | "always" => {
-                self.overlay_state.auto_approve_session = true;
-                self.push_history_line(format!("[tool approval enabled for session: {context}]"));
-                self.resolve_pending_approval(true);
+        }
+        if let Some(args) = trimmed.strip_prefix("/thinking") {
+            if args.is_empty() || args.starts_with(char::is_whitespace) {
+                self.handle_thinking_command(args.trim());
+                return true;
             }
-            "3" | "n" | "no" | "esc" => {
-                self.push_history_line(format!("[tool approval denied: {context}]"));
-                self.resolve_pending_approval(false);
+        }
+        if let Some(args) = trimmed.strip_prefix("/attach") {
+            if args.is_empty() || args.starts_with(char::is_whitespace) {
+                self.handle_attach_command(args.trim());
+                return true;
             }
-            _ => {
-                self.push_history_line("[invalid selection, expected 1/2/3]".to_string());
+        }
+        if let Some(args) = trimmed.strip_prefix("/add") {
+            if args.is_empty() || args.starts_with(char::is_whitespace) {
+                self.handle_add_command(args.trim());
+                return true;
+            }
+        }
+        if let Some(args) = trimmed.strip_prefix("/copy ") {
+            self.handle_copy_command(args.trim());
+            return true;
+        }
+        if let Some(args) = trimmed.strip_prefix("/diffcontext") {
+            if args.is_empty() || args.starts_with(char::is_whitespace) {
+                self.handle_diff_context_command(args.trim());
+                return true;
+            }
+        }
+        if let Some(args) = trimmed.strip_prefix("/diffstyle") {
+            if args.is_empty() || args.starts_with(char::is_whitespace) {
+                self.handle_diff_style_command(args.trim());
+                return true;
+            }
+        }
+        if let Some(path) = trimmed.strip_prefix("/share") {
+            if path.is_empty() || path.starts_with(char::is_whitespace) {
+                self.share_session(path.trim(), ctx);
+                return true;
+            }
+        }
+        if let Some(args) = trimmed.strip_prefix("/followup") {
+            if args.is_empty() || args.starts_with(char::is_whitespace) {
+                self.insert_follow_up(args.trim());
+                return true;
+            }
+        }
+        if let Some(args) = trimmed.strip_prefix("/tag") {
+            if args.is_empty() || args.starts_with(char::is_whitespace) {
+                self.handle_tag_command(args.trim(), ctx);
+                return true;
+            }
+        }
+        if let Some(tag) = trimmed.strip_prefix("/sessions") {
+            if tag.is_empty() || tag.starts_with(char::is_whitespace) {
+                self.open_session_picker(tag.trim(), ctx);
+                return true;
+            }
+        }
+        if let Some(id) = trimmed.strip_prefix("/changes") {
+            if id.is_empty() || id.starts_with(char::is_whitespace) {
+                self.show_turn_changes(id.trim(), ctx);
+                return true;
+            }
+        }
+        if let Some(id) = trimmed.strip_prefix("/resume") {
+            if id.is_empty() || id.starts_with(char::is_whitespace) {
+                self.handle_resume_command(id.trim(), ctx);
+                return true;
+            }
+        }
+        if let Some(args) = trimmed.strip_prefix("/rebase-assist") {
+            if args.is_empty() || args.starts_with(char::is_whitespace) {
+                self.handle_rebase_assist_command(args.trim());
+                return true;
+            }
+        }
+        if let Some(args) = trimmed.strip_prefix("/memories") {
+            if args.is_empty() || args.starts_with(char::is_whitespace) {
+                self.handle_memories_command(args.trim());
+                return true;
+            }
+        }
+        if let Some(count) = trimmed.strip_prefix("/audit") {
+            if count.is_empty() || count.starts_with(char::is_whitespace) {
+                self.show_audit_log(count.trim());
+                return true;
+            }
+        }
+        match trimmed {
+            "/undo" => {
+                self.undo_last_change(ctx);
+                true
+            }
+            "/profile" => {
+                self.show_profile();
+                true
+            }
+            "/copy" => {
+                self.handle_copy_command("");
+                true
+            }
+            "/context" => {
+                self.show_context_breakdown(ctx);
+                true
+            }
+            "/release-notes" => {
+                self.show_release_notes();
+                true
+            }
+            "/toolpool" => {
+                self.push_history_line(format!("[{}]", ctx.tool_pool_stats().summary()));
+                true
+            }
+            "/preset" | "/presets" => {
+                self.open_preset_picker();
+                true
+            }
+            "/help" | "/commands" => {
+                self.overlay_state.help = Some(HelpView::default());
+                true
             }
+            _ => false,
         }
     }
 
-    fn resolve_pending_patch_approval(&mut self, approved: bool) {
-        if let Some(mut pending) = self.overlay_state.pending_patch_approval.take() {
-            if let Some(tx) = pending.response_tx.take() {
-                let _ = tx.send(approved);
+    fn show_context_breakdown(&mut self, ctx: &RuntimeContext) {
+        let budget = self.attachments.budget_chars();
+        let attachments: Vec<(String, usize)> = self
+            .attachments
+            .pending()
+            .iter()
+            .map(|attachment| (attachment.path.clone(), attachment.context_tokens(budget)))
+            .collect();
+        let breakdown = ctx.conversation_reader().context_breakdown();
+        for line in render_context_breakdown(&breakdown, &attachments) {
+            self.push_history_line(line);
+        }
+    }
+
+    /// Shows the latest release's notes, fetching them first when no check
+    /// has run yet.
+    fn show_release_notes(&mut self) {
+        if let Some(release) = self.release_check.latest() {
+            for line in release.notes_lines() {
+                self.push_history_line(line);
             }
-            let decision = if approved { "accepted" } else { "denied" };
-            self.push_history_line(format!("[patch approval {decision}]"));
+            return;
         }
+        let started = !self.release_check.is_fetching();
+        self.release_check.fetch_in_background();
+        let line = if started || self.release_check.is_fetching() {
+            format!(
+                "[fetching release notes from {}; run /release-notes again in a moment]",
+                releases_url()
+            )
+        } else {
+            "[release notes are unavailable; check the network or VEX_RELEASES_URL]".to_string()
+        };
+        self.push_history_line(line);
     }
 
-    fn apply_patch_overlay_scroll_action(&mut self, action: ScrollAction) {
-        if let Some(pending) = self.overlay_state.pending_patch_approval.as_mut() {
-            let max = pending.patch_preview.lines().count().saturating_sub(1);
-            match action {
-                ScrollAction::LineUp => {
-                    pending.scroll_offset = pending.scroll_offset.saturating_sub(1);
-                }
-                ScrollAction::LineDown => {
-                    pending.scroll_offset = pending.scroll_offset.saturating_add(1).min(max);
+    /// `/diffcontext` shows the context lines per diff surface,
+    /// `/diffcontext <n>` sets all surfaces, and
+    /// `/diffcontext <preview|history|transcript> <n>` sets one.
+    fn handle_diff_context_command(&mut self, args: &str) {
+        let words: Vec<&str> = args.split_whitespace().collect();
+        let (surfaces, count) = match words.as_slice() {
+            [] => {
+                let line = format!("[diff context: {}]", self.diff_context.describe());
+                self.push_history_line(line);
+                return;
+            }
+            [count] => (DiffSurface::ALL.to_vec(), *count),
+            [surface, count] => match DiffSurface::parse(surface) {
+                Some(surface) => (vec![surface], *count),
+                None => {
+                    self.push_history_line(format!(
+                        "[error] unknown diff surface `{surface}`; use preview, history, or transcript"
+                    ));
+                    return;
                 }
-                ScrollAction::PageUp(step) => {
-                    pending.scroll_offset = pending.scroll_offset.saturating_sub(step.max(1));
+            },
+            _ => {
+                self.push_history_line(
+                    "[error] usage: /diffcontext [preview|history|transcript] [lines]".to_string(),
+                );
+                return;
+            }
+        };
+        let Ok(lines) = count.parse::<usize>() else {
+            self.push_history_line(format!(
+                "[error] /diffcontext expects a line count, got `{count}`"
+            ));
+            return;
+        };
+        for surface in surfaces {
+            self.diff_context.set(surface, lines);
+        }
+        let line = format!("[diff context: {}]", self.diff_context.describe());
+        self.push_history_line(line);
+    }
+
+    /// `/diffstyle` shows the diff style, `/diffstyle <default|colorblind|mono>`
+    /// picks a palette, and `/diffstyle <glyphs|tint> <on|off>` toggles
+    /// marker emphasis or background tints.
+    fn handle_diff_style_command(&mut self, args: &str) {
+        let words: Vec<&str> = args.split_whitespace().collect();
+        let updated = match words.as_slice() {
+            [] => Ok(()),
+            [palette] => DiffPalette::parse(palette)
+                .map(|palette| self.diff_style.palette = palette)
+                .ok_or_else(|| {
+                    format!("unknown diff palette `{palette}`; use default, colorblind, or mono")
+                }),
+            [setting, value] => {
+                match (setting.to_ascii_lowercase().as_str(), parse_bool_str(value)) {
+                    ("glyphs", Some(enabled)) => {
+                        self.diff_style.glyphs = enabled;
+                        Ok(())
+                    }
+                    ("tint", Some(enabled)) => {
+                        self.diff_style.tint = enabled;
+                        Ok(())
+                    }
+                    _ => Err(
+                        "usage: /diffstyle [default|colorblind|mono] | [glyphs|tint] [on|off]"
+                            .to_string(),
+                    ),
                 }
-                ScrollAction::PageDown(step) => {
-                    pending.scroll_offset =
-                        pending.scroll_offset.saturating_add(step.max(1)).min(max);
+            }
+            _ => Err(
+                "usage: /diffstyle [default|colorblind|mono] | [glyphs|tint] [on|off]".to_string(),
+            ),
+        };
+        let line = match updated {
+            Ok(()) => format!("[diff style: {}]", self.diff_style.describe()),
+            Err(error) => format!("[error] {error}"),
+        };
+        self.push_history_line(line);
+    }
+
+    /// Echoes a completed edit_file call as a diff in the transcript.
+    fn push_edit_diff(&mut self, input: &serde_json::Value) {
+        let text = |keys: &[&str]| first_str(input, keys).unwrap_or_default().to_string();
+        let path = text(&PATH_KEYS);
+        let old_str = text(&OLD_TEXT_KEYS);
+        let new_str = text(&NEW_TEXT_KEYS);
+        let context_lines = self.diff_context.get(DiffSurface::Transcript);
+        if let Some(diff) = self.edit_file_diff(&path, &old_str, &new_str, context_lines) {
+            self.registers
+                .push(RegisterKind::Diff, format!("edit_file {path}"), diff);
+        }
+        self.push_history_line(format!("[edited {path}]"));
+        for line in format_edit_hunks(&old_str, &new_str, "  ", context_lines).lines() {
+            self.push_history_line(line.to_string());
+        }
+        // Later response text starts below the diff.
+        self.history_state.active_assistant_index = None;
+    }
+
+    /// A `git apply`-able diff of an edit that replaced `old_str` with
+    /// `new_str` in `path`. The file is read back so the hunks carry its real
+    /// line numbers; the other side is rebuilt from the snippets. `None` when
+    /// the file does not show the edit in exactly one place.
+    fn edit_file_diff(
+        &self,
+        path: &str,
+        old_str: &str,
+        new_str: &str,
+        context_lines: usize,
+    ) -> Option<String> {
+        let content = std::fs::read_to_string(self.workspace_root.join(path)).ok()?;
+        let (before, after) = if !new_str.is_empty() && content.matches(new_str).count() == 1 {
+            (content.replacen(new_str, old_str, 1), content)
+        } else if !old_str.is_empty() && content.matches(old_str).count() == 1 {
+            // Patch-output mode leaves the file as it was.
+            let after = content.replacen(old_str, new_str, 1);
+            (content, after)
+        } else {
+            return None;
+        };
+        Some(format_unified_file_diff(
+            path,
+            Some(&before),
+            Some(&after),
+            context_lines,
+        ))
+    }
+
+    /// Keeps successful tool results, and the diffs among them, for `/copy`.
+    fn record_tool_output(&mut self, block: &StreamBlock) {
+        match block {
+            StreamBlock::ToolCall {
+                id, name, input, ..
+            } => {
+                // Later starts for the same call carry the finished input.
+                let label = match tool_input_subject(input) {
+                    Some(subject) => format!("{name} {subject}"),
+                    None => name.clone(),
+                };
+                self.tool_call_labels
+                    .insert(id.clone(), (name.clone(), label));
+            }
+            StreamBlock::ToolResult {
+                tool_call_id,
+                output,
+                is_error,
+            } => {
+                let Some((name, label)) = self.tool_call_labels.remove(tool_call_id) else {
+                    return;
+                };
+                if *is_error || output.trim().is_empty() {
+                    return;
                 }
-                ScrollAction::Home => {
-                    pending.scroll_offset = 0;
+                // Tool output is trimmed, but `git apply` needs the final
+                // newline; "OK" means there was no diff.
+                if name == "git_diff" && output.starts_with("diff --git ") {
+                    self.registers
+                        .push(RegisterKind::Diff, label.clone(), format!("{output}\n"));
                 }
-                ScrollAction::End => {
-                    pending.scroll_offset = max;
+                // The box-drawn tree orients the reader as much as the model.
+                if name == "tree" {
+                    for line in output.lines() {
+                        self.push_history_line(format!("  {line}"));
+                    }
                 }
+                self.registers
+                    .push(RegisterKind::Result, label, output.clone());
             }
+            _ => {}
         }
     }
 
-    fn handle_patch_overlay_input(&mut self, input: &str) {
-        if self.overlay_state.pending_patch_approval.is_none() {
+    /// `/copy` lists the copyable tool outputs; `/copy result 2` or
+    /// `/copy diff last` copies one to the clipboard.
+    fn handle_copy_command(&mut self, args: &str) {
+        if args.is_empty() {
+            let mut lines = Vec::new();
+            for kind in [RegisterKind::Result, RegisterKind::Diff] {
+                for (index, register) in self.registers.iter(kind).enumerate() {
+                    lines.push(format!(
+                        "  {} {}: {}",
+                        kind.name(),
+                        index + 1,
+                        register.summary()
+                    ));
+                }
+            }
+            if lines.is_empty() {
+                self.push_history_line(
+                    "[nothing to copy yet; tool results and diffs are kept as they arrive]"
+                        .to_string(),
+                );
+                return;
+            }
+            self.push_history_line(
+                "[copyable outputs, newest first; /copy result N or /copy diff N]".to_string(),
+            );
+            for line in lines {
+                self.push_history_line(line);
+            }
             return;
         }
+        let Some((kind, number)) = parse_register(args) else {
+            self.push_history_line("[error] usage: /copy [result|diff] [N|last]".to_string());
+            return;
+        };
+        let Some(register) = self.registers.get(kind, number) else {
+            self.push_history_line(format!("[error] no {} {number} to copy", kind.name()));
+            return;
+        };
+        let summary = register.summary();
+        let copied = match &self.clipboard {
+            ClipboardTarget::Command(command) => copy_with_command(command, &register.text),
+            ClipboardTarget::Terminal => {
+                *self.clipboard_escape.borrow_mut() = Some(osc52_sequence(&register.text));
+                Ok(())
+            }
+        };
+        let line = match copied {
+            Ok(()) => format!("[copied {} {number}: {summary}]", kind.name()),
+            Err(error) => format!("[error] {error:#}"),
+        };
+        self.push_history_line(line);
+    }
 
-        let normalized = input.trim().to_lowercase();
-        match normalized.as_str() {
-            "1" | "y" | "yes" => self.resolve_pending_patch_approval(true),
-            "3" | "n" | "no" | "esc" => self.resolve_pending_patch_approval(false),
-            _ => {}
+    /// Puts the pending response separator above the first response output,
+    /// reusing the empty line waiting for response text when it is last.
+    fn push_response_separator(&mut self) {
+        if !std::mem::take(&mut self.response_separator_pending) {
+            return;
+        }
+        let Some(separator) = self.turn_separators.line(self.separator_turn, "vex") else {
+            return;
+        };
+        let last = self.history_state.lines.len().checked_sub(1);
+        match self.history_state.active_assistant_index {
+            Some(idx) if Some(idx) == last && self.history_state.lines[idx].is_empty() => {
+                self.history_state.lines[idx] = separator;
+                self.push_history_line(String::new());
+                self.history_state.active_assistant_index =
+                    Some(self.history_state.lines.len() - 1);
+            }
+            _ => self.push_history_line(separator),
         }
     }
 
-    fn push_history_line(&mut self, line: String) {
-        self.history_state.lines.push(line);
-        self.enforce_history_cap();
-        if self.history_state.auto_follow {
-            self.set_scroll_to_bottom();
-        } else {
-            self.clamp_scroll_offset();
-        }
+    fn start_live_tool_preview(&mut self, index: usize, tool_name: &str) {
+        self.end_live_tool_preview();
+        self.live_tool_preview = LiveToolInput::new(tool_name).map(|input| LiveToolPreview {
+            index,
+            input,
+            first_line: None,
+            shown: Vec::new(),
+        });
     }
 
-    fn enforce_history_cap(&mut self) {
-        let cap = self.history_line_cap;
-        if self.history_state.lines.len() <= cap {
+    fn update_live_tool_preview(&mut self, index: usize, delta: &str) {
+        let Some(preview) = self
+            .live_tool_preview
+            .as_mut()
+            .filter(|preview| preview.index == index)
+        else {
+            return;
+        };
+        preview.input.push(delta);
+        let lines = preview.input.preview_lines(LIVE_PREVIEW_MAX_LINES);
+        // Past the cap most deltas change nothing on screen.
+        if preview.first_line.is_some() && lines == preview.shown {
             return;
         }
-
-        let excess = self.history_state.lines.len() - cap;
-        self.history_state.lines.drain(..excess);
-        self.history_state.active_assistant_index = self
-            .history_state
-            .active_assistant_index
-            .and_then(|idx| idx.checked_sub(excess));
-        self.history_state.scroll_offset = self.history_state.scroll_offset.saturating_sub(excess);
-        self.clamp_scroll_offset();
+        self.remove_live_preview_lines();
+        let first_line = self.history_state.dropped_lines + self.history_state.lines.len();
+        for line in &lines {
+            self.push_history_line(line.clone());
+        }
+        if let Some(preview) = self.live_tool_preview.as_mut() {
+            preview.first_line = Some(first_line);
+            preview.shown = lines;
+        }
+        self.history_state.active_assistant_index = None;
     }
 
-    fn max_scroll_offset(&self) -> usize {
-        history_visual_line_count(&self.history_state.lines, self.history_content_width.get())
-            .saturating_sub(1)
+    /// Drops the preview once the input has fully arrived; approval and the
+    /// finished edit show the final version.
+    fn end_live_tool_preview(&mut self) {
+        self.remove_live_preview_lines();
+        self.live_tool_preview = None;
     }
 
-    fn set_scroll_to_bottom(&mut self) {
-        self.history_state.scroll_offset = self.max_scroll_offset();
+    /// Removes the preview's lines while they are still the newest history;
+    /// lines pushed after them leave them in place.
+    fn remove_live_preview_lines(&mut self) {
+        let Some(preview) = self.live_tool_preview.as_mut() else {
+            return;
+        };
+        let start = preview
+            .first_line
+            .take()
+            .and_then(|first| first.checked_sub(self.history_state.dropped_lines));
+        let line_count = std::mem::take(&mut preview.shown).len();
+        if let Some(start) = start {
+            if start + line_count == self.history_state.lines.len() {
+                self.history_state.lines.truncate(start);
+                self.clamp_scroll_offset();
+            }
+        }
     }
 
-    fn clamp_scroll_offset(&mut self) {
-        let max = self.max_scroll_offset();
-        self.history_state.scroll_offset = self.history_state.scroll_offset.min(max);
+    /// `/undo` reverses the newest file change recorded by the tool
+    /// operator's snapshots, which are kept only outside version control.
+    fn undo_last_change(&mut self, ctx: &RuntimeContext) {
+        if self.history_state.turn_in_progress {
+            self.push_history_line("[busy - /undo waits for the turn to finish]".to_string());
+            return;
+        }
+        if self.vcs != Vcs::None {
+            self.push_history_line(format!(
+                "[no undo snapshots in a {} workspace; use {} to revert changes]",
+                self.vcs.label(),
+                self.vcs.label()
+            ));
+            return;
+        }
+        match FileSnapshots::for_workspace(&self.workspace_root).undo_last() {
+            Ok(Some(snapshot)) => {
+                ctx.invalidate_workspace_caches();
+                self.push_history_line(format!("[undo: {}]", snapshot.summary()));
+            }
+            Ok(None) => self.push_history_line("[nothing to undo]".to_string()),
+            Err(error) => self.push_history_line(format!("[error] {error:#}")),
+        }
     }
 
-    fn apply_page_up(&mut self, page_step: usize) {
-        self.history_state.scroll_offset = self
-            .history_state
-            .scroll_offset
-            .saturating_sub(page_step.max(1));
-        self.history_state.auto_follow = false;
+    /// `/profile` shows the active profile's settings, or the profiles
+    /// available to start with.
+    fn show_profile(&mut self) {
+        let Some(profile) = &self.profile else {
+            let available = profiles_dir()
+                .map(|dir| list_profiles(&dir))
+                .unwrap_or_default();
+            let line = if available.is_empty() {
+                "[no profile active; start with `vex --profile <name>`]".to_string()
+            } else {
+                format!(
+                    "[no profile active; start with `vex --profile <name>` (available: {})]",
+                    available.join(", ")
+                )
+            };
+            self.push_history_line(line);
+            return;
+        };
+        let mut lines = vec![format!(
+            "[profile {} from {}]",
+            profile.name,
+            profile.path.display()
+        )];
+        lines.extend(
+            profile
+                .describe_settings()
+                .into_iter()
+                .map(|setting| format!("  {setting}")),
+        );
+        for line in lines {
+            self.push_history_line(line);
+        }
     }
 
-    fn apply_page_down(&mut self, page_step: usize) {
-        let max = self.max_scroll_offset();
-        self.history_state.scroll_offset = self
-            .history_state
-            .scroll_offset
-            .saturating_add(page_step.max(1))
-            .min(max);
-        self.history_state.auto_follow = self.history_state.scroll_offset >= max;
+    /// `/followup <n>` (Alt+n) inserts the nth suggested follow-up at the
+    /// input cursor.
+    fn insert_follow_up(&mut self, args: &str) {
+        let suggestion = args
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| index.checked_sub(1))
+            .and_then(|index| self.follow_ups.get(index))
+            .cloned();
+        match suggestion {
+            Some(suggestion) => *self.paste_insert.borrow_mut() = Some(suggestion),
+            None if self.follow_ups.is_empty() => {
+                self.push_history_line("[no follow-up suggestions for the last turn]".to_string())
+            }
+            None => {
+                self.push_history_line(format!("[usage: /followup <1-{}>]", self.follow_ups.len()))
+            }
+        }
     }
 
-    fn apply_home(&mut self) {
-        self.history_state.scroll_offset = 0;
-        self.history_state.auto_follow = false;
+    /// Follow-ups suggested after the last turn, in Alt+digit order.
+    pub fn follow_ups(&self) -> &[String] {
+        &self.follow_ups
     }
 
-    fn apply_end(&mut self) {
-        self.set_scroll_to_bottom();
-        self.history_state.auto_follow = true;
+    /// `/tag <tag>...` tags the running or last turn; `/tag` lists the
+    /// session's tags.
+    fn handle_tag_command(&mut self, args: &str, ctx: &RuntimeContext) {
+        let reader = ctx.conversation_reader();
+        if args.is_empty() {
+            let tags = reader.turn_tags();
+            if tags.is_empty() {
+                self.push_history_line(
+                    "[no tags yet; /tag <tag> labels the current turn]".to_string(),
+                );
+            }
+            for line in summarize_tags(&tags) {
+                self.push_history_line(format!("[tag] {line}"));
+            }
+            return;
+        }
+        let line = match parse_tags(args) {
+            Ok(tags) => match reader.tag_turn(&tags) {
+                Some(turn) => format!("[tagged turn {turn}: {}]", tags.join(", ")),
+                None => "[nothing to tag yet; send a message first]".to_string(),
+            },
+            Err(error) => format!("[error] {error}"),
+        };
+        self.push_history_line(line);
     }
 
-    fn apply_history_scroll_action(&mut self, action: ScrollAction) {
-        match action {
-            ScrollAction::LineUp => self.apply_page_up(1),
-            ScrollAction::LineDown => self.apply_page_down(1),
-            ScrollAction::PageUp(step) => self.apply_page_up(step),
-            ScrollAction::PageDown(step) => self.apply_page_down(step),
-            ScrollAction::Home => self.apply_home(),
-            ScrollAction::End => self.apply_end(),
+    /// `/share [--tag <tag>] [path]` writes a redacted session bundle, by
+    /// default under `.aistar/shares/`. With `--tag` the bundle holds only
+    /// the turns carrying that tag.
+    fn share_session(&mut self, args: &str, ctx: &RuntimeContext) {
+        let reader = ctx.conversation_reader();
+        if reader.message_count() == 0 {
+            self.push_history_line("[nothing to share yet]".to_string());
+            return;
+        }
+        let (tag, path) = match args.strip_prefix("--tag") {
+            Some(rest) => {
+                let mut words = rest.split_whitespace();
+                let Some(tag) = words.next() else {
+                    self.push_history_line("[usage: /share --tag <tag> [path]]".to_string());
+                    return;
+                };
+                (
+                    Some(tag.trim_start_matches('#').to_lowercase()),
+                    words.next().unwrap_or(""),
+                )
+            }
+            None => (None, args),
+        };
+        let turn_tags = reader.turn_tags();
+        let messages = match &tag {
+            Some(tag) => {
+                let messages =
+                    messages_tagged(&reader.messages(), reader.turn_count(), &turn_tags, tag);
+                if messages.is_empty() {
+                    self.push_history_line(format!(
+                        "[no turns tagged '{tag}' are still in history]"
+                    ));
+                    return;
+                }
+                messages
+            }
+            None => reader.messages(),
+        };
+        let turn_tags = turn_tags
+            .into_iter()
+            .filter(|turn_tag| tag.as_ref().is_none_or(|tag| turn_tag.tag == *tag))
+            .collect();
+        let path = if path.is_empty() {
+            default_bundle_path(&self.workspace_root)
+        } else {
+            self.workspace_root.join(path)
+        };
+        let result = SessionBundle::capture(
+            self.bundle_metadata.clone(),
+            &self.workspace_root,
+            messages,
+            reader.token_usage(),
+        )
+        .and_then(|bundle| bundle.with_turn_tags(turn_tags).write(&path));
+        match result {
+            Ok(()) => self.push_history_line(format!(
+                "[session bundle written to {}; view it with `vex open-bundle <file>`]",
+                path.display()
+            )),
+            Err(error) => self.push_history_line(format!("[error] {error}")),
         }
     }
-}
 
-fn resolve_history_line_cap() -> usize {
-    std::env::var(MAX_HISTORY_LINES_ENV)
-        .ok()
-        .and_then(|value| value.trim().parse::<usize>().ok())
-        .filter(|cap| *cap > 0)
-        .unwrap_or(DEFAULT_MAX_HISTORY_LINES)
-}
+    /// `/attach <path>` queues a file for the next message, `/attach` lists
+    /// the queue, and `/attach clear` empties it.
+    fn handle_attach_command(&mut self, args: &str) {
+        match args {
+            "" if self.attachments.pending().is_empty() => {
+                self.push_history_line("[no attachments queued; /attach <path>]".to_string());
+            }
+            "" => {
+                let summaries: Vec<String> = self
+                    .attachments
+                    .pending()
+                    .iter()
+                    .map(|attachment| format!("[queued attachment: {}]", attachment.summary()))
+                    .collect();
+                for summary in summaries {
+                    self.push_history_line(summary);
+                }
+            }
+            "clear" => {
+                self.attachments.clear();
+                self.push_history_line("[attachments cleared]".to_string());
+            }
+            path => match self.attachments.attach(&self.workspace_root, path) {
+                Ok(attachment) => {
+                    let line = format!("[attached: {}]", attachment.summary());
+                    let image = match attachment.body {
+                        AttachmentBody::Image(info) => Some(InlineImage::new(
+                            self.workspace_root.join(&attachment.path),
+                            attachment.path.clone(),
+                            info,
+                        )),
+                        _ => None,
+                    };
+                    self.push_history_line(line);
+                    if let Some(image) = image {
+                        self.push_inline_image(image);
+                    }
+                }
+                Err(error) => self.push_history_line(format!("[error] {error}")),
+            },
+        }
+    }
 
-fn resolve_repo_label() -> String {
-    std::env::var("VEX_REPO_LABEL")
-        .ok()
-        .map(|value| value.trim().to_string())
-        .filter(|value| !value.is_empty())
-        .or_else(|| {
-            std::env::current_dir()
-                .ok()
-                .and_then(|path| {
-                    path.file_name()
-                        .map(|name| name.to_string_lossy().into_owned())
-                })
-                .filter(|name| !name.trim().is_empty())
-        })
-        .unwrap_or_else(|| "workspace".to_string())
-}
+    /// `/add <glob>... [--max-kb <n>]` attaches every matching workspace file
+    /// up to a total size and reports what the next message will carry.
+    fn handle_add_command(&mut self, args: &str) {
+        let mut patterns = Vec::new();
+        let mut max_kb = DEFAULT_ADD_MAX_KB;
+        let mut words = args.split_whitespace();
+        while let Some(word) = words.next() {
+            if word != "--max-kb" {
+                patterns.push(word);
+                continue;
+            }
+            match words.next().and_then(|value| value.parse::<u64>().ok()) {
+                Some(kb) if kb > 0 => max_kb = kb,
+                _ => {
+                    self.push_history_line("[error] --max-kb expects a size in KiB".to_string());
+                    return;
+                }
+            }
+        }
+        let added = match self.attachments.attach_glob(
+            &self.workspace_root,
+            &patterns,
+            max_kb.saturating_mul(1024),
+        ) {
+            Ok(added) => added,
+            Err(error) => {
+                self.push_history_line(format!("[error] {error}"));
+                return;
+            }
+        };
+        let mut lines = vec![format!(
+            "[added {} {}: {} on disk, about {} tokens in the next message]",
+            added.attached.len(),
+            if added.attached.len() == 1 {
+                "file"
+            } else {
+                "files"
+            },
+            format_bytes(added.bytes),
+            added.context_tokens
+        )];
+        for attachment in self.attachments.pending() {
+            if added.attached.contains(&attachment.path) {
+                lines.push(format!("  {}", attachment.summary()));
+            }
+        }
+        for skipped in added.skipped.iter().take(MAX_LISTED_ADD_SKIPS) {
+            lines.push(format!("  skipped {skipped}"));
+        }
+        if added.skipped.len() > MAX_LISTED_ADD_SKIPS {
+            lines.push(format!(
+                "  ... {} more skipped",
+                added.skipped.len() - MAX_LISTED_ADD_SKIPS
+            ));
+        }
+        self.push_history_line(lines.join("\n"));
+    }
 
-#[cfg(test)]
-fn input_rows_for_buffer(input: &str, width: usize) -> u16 {
-    input_visual_rows(input, width).clamp(1, MAX_INPUT_PANE_ROWS) as u16
-}
+    pub fn help_active(&self) -> bool {
+        self.overlay_state.help.is_some()
+    }
 
-#[cfg(test)]
-struct RenderGuard {
-    dirty: bool,
-    cursor_tick: Duration,
-    status_tick: Duration,
-    last_draw_at: Instant,
-    last_render_state_hash: Option<u64>,
-}
+    /// Filter query, matching help lines, and scroll offset of the open help
+    /// screen.
+    pub fn help_overlay(&self) -> Option<(&str, Vec<HelpLine>, usize)> {
+        let help = self.overlay_state.help.as_ref()?;
+        let lines = help_lines(&help.query, &self.config_highlights);
+        let scroll = help.scroll.min(lines.len().saturating_sub(1));
+        Some((help.query.as_str(), lines, scroll))
+    }
 
-#[cfg(test)]
-impl RenderGuard {
-    fn with_intervals(cursor_tick: Duration, status_tick: Duration, now: Instant) -> Self {
-        Self {
-            dirty: true,
-            cursor_tick,
-            status_tick,
-            last_draw_at: now,
-            last_render_state_hash: None,
+    /// Single characters extend the filter and longer text (a pasted or
+    /// line-mode query) replaces it; `enter` and `esc` close the screen.
+    fn handle_help_input(&mut self, input: &str) {
+        let Some(help) = self.overlay_state.help.as_mut() else {
+            return;
+        };
+        match input {
+            "enter" | "esc" => self.overlay_state.help = None,
+            "backspace" => {
+                help.query.pop();
+                help.scroll = 0;
+            }
+            text if text.chars().count() == 1 => {
+                help.query.push_str(text);
+                help.scroll = 0;
+            }
+            text => {
+                help.query = text.trim().to_string();
+                help.scroll = 0;
+            }
         }
     }
 
-    fn poll_timeout(&self) -> Duration {
-        self.cursor_tick.min(self.status_tick)
+    fn apply_help_scroll_action(&mut self, action: ScrollAction) {
+        let line_count = self
+            .help_overlay()
+            .map(|(_, lines, _)| lines.len())
+            .unwrap_or_default();
+        let Some(help) = self.overlay_state.help.as_mut() else {
+            return;
+        };
+        let max = line_count.saturating_sub(1);
+        let scroll = help.scroll.min(max);
+        help.scroll = match action {
+            ScrollAction::LineUp => scroll.saturating_sub(1),
+            ScrollAction::LineDown => scroll.saturating_add(1).min(max),
+            ScrollAction::PageUp(step) => scroll.saturating_sub(step.max(1)),
+            ScrollAction::PageDown(step) => scroll.saturating_add(step.max(1)).min(max),
+            ScrollAction::Home => 0,
+            ScrollAction::End => max,
+        };
     }
 
-    fn should_draw(&mut self, now: Instant, state_hash: u64) -> bool {
-        if self.last_render_state_hash != Some(state_hash) {
-            self.dirty = true;
+    fn open_history_picker(&mut self, query: &str) {
+        if self.prompt_history.is_empty() {
+            self.push_history_line("[no prompts in history yet]".to_string());
+            return;
         }
+        self.overlay_state.history_picker = Some(HistoryPicker {
+            query: query.to_string(),
+            filtering: false,
+            selected: 0,
+        });
+    }
 
-        if self.dirty || now.saturating_duration_since(self.last_draw_at) >= self.poll_timeout() {
-            self.dirty = false;
-            self.last_draw_at = now;
-            self.last_render_state_hash = Some(state_hash);
-            true
-        } else {
-            false
-        }
+    pub fn history_picker_active(&self) -> bool {
+        self.overlay_state.history_picker.is_some()
     }
-}
 
-impl Default for TuiMode {
-    fn default() -> Self {
-        Self::new()
+    /// Query, filter-entry flag, matching rows, and selected row of the open
+    /// `/history` picker.
+    pub fn history_picker_overlay(&self) -> Option<(&str, bool, Vec<PromptHistoryRow>, usize)> {
+        let picker = self.overlay_state.history_picker.as_ref()?;
+        let rows = prompt_history_rows(
+            &self.prompt_history,
+            &self.prompt_history.filter(&picker.query),
+        );
+        let selected = picker.selected.min(rows.len().saturating_sub(1));
+        Some((picker.query.as_str(), picker.filtering, rows, selected))
     }
-}
 
-impl RuntimeMode for TuiMode {
-    fn on_frontend_event(&mut self, event: UserInputEvent, ctx: &mut RuntimeContext) {
-        match event {
-            UserInputEvent::Text(input) => self.on_user_input(input, ctx),
-            UserInputEvent::Interrupt => self.on_interrupt(ctx),
-            UserInputEvent::Scroll { target, action } => {
-                if self.overlay_active() {
-                    if target == ScrollTarget::Overlay {
-                        self.apply_patch_overlay_scroll_action(action);
-                    }
-                } else if target == ScrollTarget::History {
-                    self.apply_history_scroll_action(action);
-                }
-            }
-        }
+    /// Text queued for the input editor by the `/history` picker's edit
+    /// action; the frontend takes it on its next render.
+    pub fn take_editor_prefill(&self) -> Option<String> {
+        self.editor_prefill.borrow_mut().take()
     }
 
-    fn on_user_input(&mut self, input: String, ctx: &mut RuntimeContext) {
-        if self.overlay_active() {
-            if self.patch_overlay_active() {
-                self.handle_patch_overlay_input(&input);
-            } else {
-                self.handle_approval_input(&input);
-            }
-            return;
-        }
+    /// Text to insert at the input cursor after a large paste was confirmed.
+    pub fn take_paste_insert(&self) -> Option<String> {
+        self.paste_insert.borrow_mut().take()
+    }
 
-        if self.history_state.turn_in_progress {
-            if self.history_state.cancel_pending {
-                self.push_history_line(
-                    "[busy - cancelling current turn, input discarded]".to_string(),
-                );
-            } else {
-                self.push_history_line("[busy - turn in progress, input discarded]".to_string());
-            }
-            return;
-        }
+    /// Pastes over `lines` lines (or `lines * 100` characters) ask before
+    /// they reach the input; 0 never asks.
+    pub fn with_paste_confirm_lines(mut self, lines: usize) -> Self {
+        self.paste_confirm_lines = lines;
+        self
+    }
 
-        self.pending_quit = false;
-        self.quit_requested = false;
-        self.history_state.cancel_pending = false;
-        self.push_history_line(format!("> {input}"));
-        self.push_history_line(String::new());
-        self.history_state.active_assistant_index = Some(self.history_state.lines.len() - 1);
-        self.history_state.turn_in_progress = true;
-        ctx.start_turn(input);
+    /// Where `/copy` sends tool outputs.
+    pub fn with_clipboard(mut self, target: ClipboardTarget) -> Self {
+        self.clipboard = target;
+        self
     }
 
-    fn on_model_update(&mut self, update: UiUpdate, _ctx: &mut RuntimeContext) {
-        match update {
-            UiUpdate::StreamDelta(text) => {
-                if self.history_state.cancel_pending {
-                    return;
+    /// The clipboard escape queued by `/copy`, which the frontend writes to
+    /// the terminal on its next render.
+    pub fn take_clipboard_escape(&self) -> Option<String> {
+        self.clipboard_escape.borrow_mut().take()
+    }
+
+    /// Whether `text` is large enough that the frontend should send it as
+    /// [`UserInputEvent::LargePaste`] instead of inserting it.
+    pub fn paste_needs_confirmation(&self, text: &str) -> bool {
+        self.paste_confirm_lines > 0
+            && (text.lines().count() > self.paste_confirm_lines
+                || text.chars().count()
+                    > self
+                        .paste_confirm_lines
+                        .saturating_mul(PASTE_CONFIRM_CHARS_PER_LINE))
+    }
+
+    /// Line count, character count, and text of a paste awaiting
+    /// confirmation.
+    pub fn pending_paste_overlay(&self) -> Option<(usize, usize, &str)> {
+        self.overlay_state
+            .pending_paste
+            .as_ref()
+            .map(|paste| (paste.lines, paste.chars, paste.text.as_str()))
+    }
+
+    fn selected_history_prompt(&self) -> Option<String> {
+        let picker = self.overlay_state.history_picker.as_ref()?;
+        let matches = self.prompt_history.filter(&picker.query);
+        let index = *matches.get(picker.selected.min(matches.len().saturating_sub(1)))?;
+        Some(self.prompt_history.entries()[index].text.clone())
+    }
+
+    /// Picker keys arrive as single characters plus `enter`, `backspace`, and
+    /// `esc`.
+    fn handle_history_picker_input(&mut self, input: &str, ctx: &mut RuntimeContext) {
+        let Some(picker) = self.overlay_state.history_picker.as_mut() else {
+            return;
+        };
+        if picker.filtering {
+            match input {
+                "enter" | "esc" => picker.filtering = false,
+                "backspace" => {
+                    picker.query.pop();
+                    picker.selected = 0;
                 }
-                let idx = match self.history_state.active_assistant_index {
-                    Some(idx) => idx,
-                    None => {
-                        if !self.history_state.turn_in_progress {
-                            return;
-                        }
-                        self.push_history_line(String::new());
-                        let idx = self.history_state.lines.len() - 1;
-                        self.history_state.active_assistant_index = Some(idx);
-                        idx
-                    }
-                };
-                if let Some(line) = self.history_state.lines.get_mut(idx) {
-                    line.push_str(&text);
-                    *line = sanitize_assistant_text(line);
+                text => {
+                    picker.query.push_str(text);
+                    picker.selected = 0;
                 }
-                if self.history_state.auto_follow {
-                    self.set_scroll_to_bottom();
+            }
+            return;
+        }
+        match input {
+            "/" => picker.filtering = true,
+            "backspace" => {
+                picker.query.pop();
+                picker.selected = 0;
+            }
+            "esc" | "q" => self.overlay_state.history_picker = None,
+            "e" => {
+                if let Some(prompt) = self.selected_history_prompt() {
+                    *self.editor_prefill.borrow_mut() = Some(prompt);
                 }
+                self.overlay_state.history_picker = None;
             }
-            UiUpdate::StreamBlockStart { index, block } => {
-                self.active_stream_blocks.insert(index, block);
+            "enter" => {
+                let prompt = self.selected_history_prompt();
+                self.overlay_state.history_picker = None;
+                if let Some(prompt) = prompt {
+                    self.on_user_input(prompt, ctx);
+                }
             }
-            UiUpdate::StreamBlockDelta { index, delta } => {
-                if let Some(block) = self.active_stream_blocks.get_mut(&index) {
-                    match block {
-                        StreamBlock::Thinking { content, .. } => content.push_str(&delta),
-                        StreamBlock::FinalText { content } => content.push_str(&delta),
-                        StreamBlock::ToolCall { .. } | StreamBlock::ToolResult { .. } => {}
-                    }
-                }
-            }
-            UiUpdate::StreamBlockComplete { index } => {
-                self.active_stream_blocks.remove(&index);
+            _ => {}
+        }
+    }
+
+    fn apply_history_picker_scroll_action(&mut self, action: ScrollAction) {
+        let match_count = self
+            .overlay_state
+            .history_picker
+            .as_ref()
+            .map(|picker| self.prompt_history.filter(&picker.query).len())
+            .unwrap_or_default();
+        let Some(picker) = self.overlay_state.history_picker.as_mut() else {
+            return;
+        };
+        let max = match_count.saturating_sub(1);
+        let selected = picker.selected.min(max);
+        picker.selected = match action {
+            ScrollAction::LineUp => selected.saturating_sub(1),
+            ScrollAction::LineDown => selected.saturating_add(1).min(max),
+            ScrollAction::PageUp(step) => selected.saturating_sub(step.max(1)),
+            ScrollAction::PageDown(step) => selected.saturating_add(step.max(1)).min(max),
+            ScrollAction::Home => 0,
+            ScrollAction::End => max,
+        };
+    }
+
+    /// Opens the preset picker when the workspace defines any presets, so a
+    /// new session can start from a team template.
+    pub fn with_startup_presets(mut self) -> Self {
+        if let Ok(presets) = PresetStore::for_workspace(&self.workspace_root).load() {
+            if !presets.is_empty() {
+                self.overlay_state.preset_picker = Some(PresetPicker {
+                    presets,
+                    selected: 0,
+                });
             }
-            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
-                tool_name,
-                input_preview,
-                response_tx,
-            }) => {
-                if self.history_state.cancel_pending {
-                    let _ = response_tx.send(false);
-                    return;
-                }
-                if self.overlay_state.auto_approve_session {
-                    let _ = response_tx.send(true);
-                    self.push_history_line(format!("[auto-approved tool: {tool_name} session]"));
-                    return;
-                }
+        }
+        self
+    }
 
-                self.resolve_pending_approval(false);
-                self.resolve_pending_patch_approval(false);
-                let summary = summarize_tool_approval_context(&tool_name, &input_preview);
-                self.push_history_line(format!("[tool approval requested: {summary}]"));
-                self.overlay_state.pending_approval = Some(PendingApproval {
-                    tool_name,
-                    input_preview,
-                    response_tx,
+    /// Opens the picker over `.aistar/presets/*.md`; the chosen template is
+    /// loaded into the input so it can be completed before sending.
+    fn open_preset_picker(&mut self) {
+        let store = PresetStore::for_workspace(&self.workspace_root);
+        match store.load() {
+            Ok(presets) if presets.is_empty() => {
+                self.push_history_line(format!("[no prompt presets in {}]", store.dir().display()))
+            }
+            Ok(presets) => {
+                self.overlay_state.preset_picker = Some(PresetPicker {
+                    presets,
+                    selected: 0,
                 });
             }
-            UiUpdate::TurnComplete => {
-                self.resolve_pending_approval(false);
-                self.resolve_pending_patch_approval(false);
-                self.active_stream_blocks.clear();
-                self.history_state.cancel_pending = false;
-                self.history_state.turn_in_progress = false;
-                self.history_state.active_assistant_index = None;
-                if self.history_state.auto_follow {
-                    self.set_scroll_to_bottom();
-                } else {
-                    self.clamp_scroll_offset();
+            Err(error) => self.push_history_line(format!("[error] {error}")),
+        }
+    }
+
+    pub fn preset_picker_active(&self) -> bool {
+        self.overlay_state.preset_picker.is_some()
+    }
+
+    /// Presets and selected row of the open preset picker.
+    pub fn preset_picker_overlay(&self) -> Option<(&[Preset], usize)> {
+        let picker = self.overlay_state.preset_picker.as_ref()?;
+        Some((&picker.presets, picker.selected))
+    }
+
+    fn handle_preset_picker_input(&mut self, input: &str) {
+        match input {
+            "esc" | "q" => self.overlay_state.preset_picker = None,
+            "enter" => {
+                if let Some(picker) = self.overlay_state.preset_picker.take() {
+                    if let Some(preset) = picker.presets.get(picker.selected) {
+                        *self.editor_prefill.borrow_mut() = Some(preset.template.clone());
+                        self.push_history_line(format!("[preset loaded: {}]", preset.name));
+                    }
                 }
             }
-            UiUpdate::Error(msg) => {
-                self.resolve_pending_approval(false);
-                self.resolve_pending_patch_approval(false);
-                self.active_stream_blocks.clear();
-                self.history_state.cancel_pending = false;
-                self.push_history_line(format!("[error] {msg}"));
-                self.history_state.turn_in_progress = false;
-                self.history_state.active_assistant_index = None;
+            _ => {}
+        }
+    }
+
+    fn apply_preset_picker_scroll_action(&mut self, action: ScrollAction) {
+        let Some(picker) = self.overlay_state.preset_picker.as_mut() else {
+            return;
+        };
+        let max = picker.presets.len().saturating_sub(1);
+        picker.selected = match action {
+            ScrollAction::LineUp => picker.selected.saturating_sub(1),
+            ScrollAction::LineDown => picker.selected.saturating_add(1).min(max),
+            ScrollAction::PageUp(step) => picker.selected.saturating_sub(step.max(1)),
+            ScrollAction::PageDown(step) => picker.selected.saturating_add(step.max(1)).min(max),
+            ScrollAction::Home => 0,
+            ScrollAction::End => max,
+        };
+    }
+
+    /// `/memories` lists the project memories; `/memories forget <id>`
+    /// deletes one.
+    fn handle_memories_command(&mut self, args: &str) {
+        let store = MemoryStore::for_workspace(&self.workspace_root);
+        if args.is_empty() {
+            self.show_memories(&store);
+            return;
+        }
+        let id = args
+            .strip_prefix("forget")
+            .filter(|id| id.is_empty() || id.starts_with(char::is_whitespace))
+            .map(|id| id.trim().trim_start_matches('#'));
+        let Some(id) = id.and_then(|id| id.parse::<u64>().ok()) else {
+            self.push_history_line("[usage: /memories [forget <id>]]".to_string());
+            return;
+        };
+        match store.forget(id) {
+            Ok(Some(entry)) => {
+                self.push_history_line(format!("[forgot memory #{}: {}]", entry.id, entry.text))
             }
+            Ok(None) => self.push_history_line(format!("[no memory #{id}]")),
+            Err(error) => self.push_history_line(format!("[error] {error}")),
         }
     }
 
-    fn on_interrupt(&mut self, ctx: &mut RuntimeContext) {
-        if self.history_state.turn_in_progress {
-            if self.history_state.cancel_pending {
+    fn show_memories(&mut self, store: &MemoryStore) {
+        match store.load() {
+            Ok(entries) if entries.is_empty() => {
+                self.push_history_line("[no project memories stored]".to_string());
+            }
+            Ok(entries) => {
+                self.push_history_line(format!(
+                    "[project memories: {} in {}]\n{}",
+                    entries.len(),
+                    store.path().display(),
+                    render_memory_entries(&entries)
+                ));
+            }
+            Err(error) => self.push_history_line(format!("[error] {error}")),
+        }
+    }
+
+    /// `/thinking <expand|collapse|hide|toggle> [last|all]` re-renders thinking
+    /// blocks from their stored content.
+    fn handle_thinking_command(&mut self, args: &str) {
+        let mut words = args.split_whitespace();
+        let action = words.next().unwrap_or_default();
+        let scope = words.next().unwrap_or("last");
+        let display = match action {
+            "toggle" => None,
+            other => match ThinkingDisplay::parse(other) {
+                Some(display) => Some(display),
+                None => {
+                    self.push_history_line(
+                        "[error] usage: /thinking <expand|collapse|hide|toggle> [last|all]"
+                            .to_string(),
+                    );
+                    return;
+                }
+            },
+        };
+        let targets = match scope {
+            "last" => self.thinking.blocks.len().saturating_sub(1)..self.thinking.blocks.len(),
+            "all" => 0..self.thinking.blocks.len(),
+            _ => {
+                self.push_history_line("[error] /thinking scope must be last or all".to_string());
                 return;
             }
-            ctx.cancel_turn();
-            self.resolve_pending_approval(false);
-            self.resolve_pending_patch_approval(false);
-            self.history_state.cancel_pending = true;
-            self.push_history_line("[turn cancellation requested]".to_string());
-            self.pending_quit = false;
-            self.quit_requested = false;
+        };
+        if targets.is_empty() {
+            self.push_history_line("[no thinking blocks yet]".to_string());
             return;
         }
-
-        if self.pending_quit {
-            self.quit_requested = true;
-        } else {
-            self.pending_quit = true;
-            self.push_history_line("[press Ctrl+C again to exit]".to_string());
+        let fallback = self.thinking.default_display;
+        for index in targets {
+            let block = &mut self.thinking.blocks[index];
+            match display {
+                Some(display) => block.display = display,
+                None => block.toggle(fallback),
+            }
+            self.rerender_thinking_block(index);
         }
+        self.clamp_scroll_offset();
     }
 
-    fn is_turn_in_progress(&self) -> bool {
-        self.history_state.turn_in_progress
+    fn rerender_thinking_block(&mut self, index: usize) {
+        let Some(block) = self.thinking.blocks.get(index) else {
+            return;
+        };
+        if let Some(line) = self.history_state.lines.get_mut(block.line) {
+            *line = block.render();
+        }
     }
-}
-
-fn summarize_tool_approval_context(tool_name: &str, input_preview: &str) -> String {
-    let mut path: Option<&str> = None;
-    let mut summary_line: Option<&str> = None;
 
-    for line in input_preview.lines().take(8) {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            continue;
-        }
-        if path.is_none() && trimmed.starts_with("path:") {
-            path = Some(trimmed);
-            continue;
+    /// Appends streamed response text to the active assistant line.
+    fn append_response_text(&mut self, text: &str) {
+        let idx = match self.history_state.active_assistant_index {
+            Some(idx) => idx,
+            None => {
+                if !self.history_state.turn_in_progress {
+                    return;
+                }
+                self.push_history_line(String::new());
+                let idx = self.history_state.lines.len() - 1;
+                self.history_state.active_assistant_index = Some(idx);
+                idx
+            }
+        };
+        if let Some(line) = self.history_state.lines.get_mut(idx) {
+            let spoken_len = line.len();
+            line.push_str(text);
+            *line = sanitize_assistant_text(line);
+            if let Some(speech) = self.speech.as_mut() {
+                // Sanitizing can rewrite earlier text; only speak growth.
+                if line.is_char_boundary(spoken_len) {
+                    speech.push(&line[spoken_len..]);
+                }
+            }
         }
-        if summary_line.is_none()
-            && (trimmed.starts_with("change:") || trimmed.starts_with("content:"))
-        {
-            summary_line = Some(trimmed);
-            continue;
+        self.report_speech_failure();
+        if self.history_state.auto_follow {
+            self.set_scroll_to_bottom();
         }
-        if summary_line.is_none() {
-            summary_line = Some(trimmed);
+    }
+
+    fn flush_typewriter(&mut self) {
+        if let Some(text) = self.typewriter.as_mut().and_then(Typewriter::flush) {
+            self.append_response_text(&text);
         }
     }
 
-    match (path, summary_line) {
-        (Some(path), Some(summary)) => format!("{tool_name} {path} {summary}"),
-        (Some(path), None) => format!("{tool_name} {path}"),
-        (None, Some(summary)) => format!("{tool_name} {summary}"),
-        (None, None) => tool_name.to_string(),
+    fn append_thinking_delta(&mut self, text: &str) {
+        let index = match self.thinking.active {
+            Some(index) => index,
+            None => {
+                // Thinking gets its own line; reuse the empty line reserved
+                // for the turn's first output when nothing streamed yet.
+                let line = match self.history_state.active_assistant_index {
+                    Some(idx)
+                        if self
+                            .history_state
+                            .lines
+                            .get(idx)
+                            .is_some_and(|line| line.is_empty()) =>
+                    {
+                        idx
+                    }
+                    _ => {
+                        self.push_history_line(String::new());
+                        self.history_state.lines.len() - 1
+                    }
+                };
+                self.history_state.active_assistant_index = None;
+                self.thinking
+                    .blocks
+                    .push(ThinkingBlock::new(line, self.thinking.default_display));
+                let index = self.thinking.blocks.len() - 1;
+                self.thinking.active = Some(index);
+                index
+            }
+        };
+        let block = &mut self.thinking.blocks[index];
+        block.content.push_str(text);
+        block.content = sanitize_assistant_text(&block.content);
+        self.rerender_thinking_block(index);
     }
-}
 
-#[cfg(test)]
-fn overlay_event_to_user_input(event: Event) -> Option<UserInputEvent> {
-    match event {
-        Event::Key(key) => match key.code {
-            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                Some(UserInputEvent::Interrupt)
+    fn end_thinking_stream(&mut self) {
+        self.thinking.stream_index = None;
+        self.thinking.active = None;
+    }
+
+    /// `/changes` lists what each turn of this conversation changed;
+    /// `/changes <n|id>` does the same for a saved session, by its number in
+    /// the `/resume` list or by id, from the session index.
+    fn show_turn_changes(&mut self, id: &str, ctx: &RuntimeContext) {
+        let (label, changes) = if id.is_empty() {
+            (
+                "this session".to_string(),
+                ctx.conversation_reader().turn_changes(),
+            )
+        } else {
+            let summary = match id.parse::<usize>() {
+                Ok(number) => ctx.saved_sessions().and_then(|sessions| {
+                    number
+                        .checked_sub(1)
+                        .and_then(|index| sessions.into_iter().nth(index))
+                        .ok_or_else(|| anyhow::anyhow!("no saved session {number}"))
+                }),
+                Err(_) => ctx.saved_session_summary(id),
+            };
+            match summary {
+                Ok(summary) => (format!("session {}", summary.id), summary.turn_changes),
+                Err(error) => {
+                    self.push_history_line(format!("[error] {error}"));
+                    return;
+                }
             }
-            KeyCode::Esc => Some(UserInputEvent::Text("esc".to_string())),
-            KeyCode::Char(ch)
-                if !key.modifiers.contains(KeyModifiers::CONTROL)
-                    && !key.modifiers.contains(KeyModifiers::ALT) =>
-            {
-                Some(UserInputEvent::Text(ch.to_string()))
+        };
+        if changes.is_empty() {
+            self.push_history_line(format!("[no file changes or commands in {label}]"));
+            return;
+        }
+        let mut lines = vec![format!("[changes in {label}, oldest first]")];
+        lines.extend(changes.iter().flat_map(|summary| summary.history_lines()));
+        self.push_history_line(lines.join("\n"));
+    }
+
+    /// `/resume` lists saved sessions and `/resume #tag` those tagged with
+    /// something starting with `tag`; `/resume <n|id>` reloads one by its
+    /// number in the full list or by id.
+    fn handle_resume_command(&mut self, id: &str, ctx: &RuntimeContext) {
+        let sessions = match ctx.saved_sessions() {
+            Ok(sessions) => sessions,
+            Err(error) => {
+                self.push_history_line(format!("[error] {error}"));
+                return;
             }
-            _ => None,
-        },
-        Event::Paste(text) => {
-            let trimmed = text.trim();
-            if trimmed.is_empty() {
-                None
-            } else {
-                Some(UserInputEvent::Text(trimmed.to_string()))
+        };
+        if id.is_empty() || id.starts_with('#') {
+            if sessions.is_empty() {
+                self.push_history_line("[no saved sessions]".to_string());
+                return;
+            }
+            let current = ctx.current_session_id();
+            let mut lines = vec!["[saved sessions; /resume <n|id> to reload one]".to_string()];
+            for (index, session) in sessions.iter().enumerate() {
+                if matches_tag_filter(&session.tags, id) {
+                    lines.push(format!(
+                        "{:>3}. {}",
+                        index + 1,
+                        saved_session_row(session, current.as_deref())
+                    ));
+                }
+            }
+            if lines.len() == 1 {
+                lines[0] = format!("[no saved sessions tagged {id}*]");
             }
+            self.push_history_line(lines.join("\n"));
+            return;
         }
-        _ => None,
+        let id = match id.parse::<usize>() {
+            Ok(number) if (1..=sessions.len()).contains(&number) => sessions[number - 1].id.clone(),
+            _ => id.to_string(),
+        };
+        self.resume_saved_session(&id, ctx);
     }
-}
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-#[cfg(test)]
-enum RenderPass {
-    Header,
-    History,
-    Input,
-    Overlay,
-}
 
-#[cfg(test)]
-fn render_pass_order(mode: &TuiMode) -> Vec<RenderPass> {
-    let mut order = vec![RenderPass::Header, RenderPass::History, RenderPass::Input];
-    if mode.overlay_active() {
-        order.push(RenderPass::Overlay);
+    fn resume_saved_session(&mut self, id: &str, ctx: &RuntimeContext) {
+        match ctx.resume_session(id) {
+            Ok(saved) => {
+                let mut lines = vec![format!(
+                    "[resumed session {} ({} turns)]",
+                    saved.id, saved.turn_count
+                )];
+                let current = self.bundle_metadata.sampling;
+                if saved.sampling != current {
+                    let saved_pin = if saved.sampling.is_empty() {
+                        "was not pinned".to_string()
+                    } else {
+                        format!("was pinned to {}", saved.sampling.describe())
+                    };
+                    let current_pin = if current.is_empty() {
+                        "this run is not".to_string()
+                    } else {
+                        format!("this run is pinned to {}", current.describe())
+                    };
+                    lines.push(format!("[the saved session {saved_pin}; {current_pin}]"));
+                }
+                lines.extend(saved.recap_lines());
+                self.push_history_line(lines.join("\n"));
+            }
+            Err(error) => self.push_history_line(format!("[error] {error}")),
+        }
     }
-    order
-}
 
-pub fn build_runtime(config: Config) -> Result<(Runtime<TuiMode>, RuntimeContext)> {
-    let client = ApiClient::new(&config)?;
-    let operator = ToolOperator::new(config.working_dir.clone());
-    let conversation = ConversationManager::new(client, operator);
-
-    let (update_tx, update_rx) = mpsc::unbounded_channel::<UiUpdate>();
-    let ctx = RuntimeContext::new(conversation, update_tx, CancellationToken::new());
+    /// `/sessions [tag]` opens the saved-session picker, narrowed to
+    /// sessions with a tag starting with `tag`.
+    fn open_session_picker(&mut self, tag: &str, ctx: &RuntimeContext) {
+        match ctx.saved_sessions() {
+            Ok(sessions) if sessions.is_empty() => {
+                self.push_history_line("[no saved sessions]".to_string())
+            }
+            Ok(sessions) => {
+                self.overlay_state.session_picker = Some(SessionPicker {
+                    sessions,
+                    current: ctx.current_session_id(),
+                    tag: tag.trim_start_matches('#').to_string(),
+                    filtering: false,
+                    selected: 0,
+                });
+            }
+            Err(error) => self.push_history_line(format!("[error] {error}")),
+        }
+    }
 
-    let mode = TuiMode::new();
-    let runtime = Runtime::new(mode, update_rx);
-    Ok((runtime, ctx))
-}
+    pub fn session_picker_active(&self) -> bool {
+        self.overlay_state.session_picker.is_some()
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::api::{mock_client::MockApiClient, ApiClient};
-    use crate::ui::editor::{InputAction, InputEditor};
-    use crossterm::event::KeyEvent;
-    use futures::FutureExt;
-    use std::collections::HashMap;
-    use std::sync::Arc;
+    /// Tag filter, filter-entry flag, matching rows, and selected row of the
+    /// open `/sessions` picker.
+    pub fn session_picker_overlay(&self) -> Option<(&str, bool, Vec<String>, usize)> {
+        let picker = self.overlay_state.session_picker.as_ref()?;
+        let rows: Vec<String> = picker
+            .matches()
+            .into_iter()
+            .map(|session| saved_session_row(session, picker.current.as_deref()))
+            .collect();
+        let selected = picker.selected.min(rows.len().saturating_sub(1));
+        Some((picker.tag.as_str(), picker.filtering, rows, selected))
+    }
 
-    fn setup_ctx() -> RuntimeContext {
-        let (tx, _rx) = mpsc::unbounded_channel::<UiUpdate>();
-        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(vec![])));
-        let conversation = ConversationManager::new_mock(client, HashMap::new());
-        RuntimeContext::new(conversation, tx, CancellationToken::new())
+    /// Keys as for the `/history` picker: `/` edits the tag filter and
+    /// `enter` resumes the selected session.
+    fn handle_session_picker_input(&mut self, input: &str, ctx: &RuntimeContext) {
+        let Some(picker) = self.overlay_state.session_picker.as_mut() else {
+            return;
+        };
+        if picker.filtering {
+            match input {
+                "enter" | "esc" => picker.filtering = false,
+                "backspace" => {
+                    picker.tag.pop();
+                    picker.selected = 0;
+                }
+                text => {
+                    picker.tag.push_str(text);
+                    picker.selected = 0;
+                }
+            }
+            return;
+        }
+        match input {
+            "/" => picker.filtering = true,
+            "backspace" => {
+                picker.tag.pop();
+                picker.selected = 0;
+            }
+            "esc" | "q" => self.overlay_state.session_picker = None,
+            "enter" => {
+                let matches = picker.matches();
+                let id = matches
+                    .get(picker.selected.min(matches.len().saturating_sub(1)))
+                    .map(|session| session.id.clone());
+                self.overlay_state.session_picker = None;
+                if let Some(id) = id {
+                    self.resume_saved_session(&id, ctx);
+                }
+            }
+            _ => {}
+        }
     }
 
-    #[tokio::test]
-    async fn test_ref_03_tui_mode_overlay_blocks_input() {
-        let mut ctx = setup_ctx();
-        let mut mode = TuiMode::new();
+    fn apply_session_picker_scroll_action(&mut self, action: ScrollAction) {
+        let Some(picker) = self.overlay_state.session_picker.as_mut() else {
+            return;
+        };
+        let max = picker.matches().len().saturating_sub(1);
+        let selected = picker.selected.min(max);
+        picker.selected = match action {
+            ScrollAction::LineUp => selected.saturating_sub(1),
+            ScrollAction::LineDown => selected.saturating_add(1).min(max),
+            ScrollAction::PageUp(step) => selected.saturating_sub(step.max(1)),
+            ScrollAction::PageDown(step) => selected.saturating_add(step.max(1)).min(max),
+            ScrollAction::Home => 0,
+            ScrollAction::End => max,
+        };
+    }
 
-        let (response_tx, _rx) = tokio::sync::oneshot::channel::<bool>();
-        mode.on_model_update(
+    /// `/rebase-assist [upstream|continue|abort]` walks through a rebase:
+    /// the conflicts of each stop go to the model, and the rebase continues
+    /// once the user has reviewed its edits. Git runs off the UI thread;
+    /// [`Self::finish_rebase_job`] picks up the result.
+    fn handle_rebase_assist_command(&mut self, args: &str) {
+        if self.history_state.turn_in_progress {
+            self.push_history_line("[rebase-assist: wait for the current turn]".to_string());
+            return;
+        }
+        if self.rebase_job.is_some() {
+            self.push_history_line("[rebase-assist: git is still running]".to_string());
+            return;
+        }
+        let (assist, action) = match args {
+            "abort" => (
+                self.rebase_assist
+                    .take()
+                    .unwrap_or_else(|| RebaseAssist::new(self.workspace_root.clone())),
+                RebaseAction::Abort,
+            ),
+            "continue" => match self.rebase_assist.take() {
+                Some(assist) => (assist, RebaseAction::Continue),
+                None => {
+                    self.push_history_line(
+                        "[rebase-assist: not active; /rebase-assist [upstream] starts it]"
+                            .to_string(),
+                    );
+                    return;
+                }
+            },
+            upstream => (
+                RebaseAssist::new(self.workspace_root.clone()),
+                RebaseAction::Start(upstream.to_string()),
+            ),
+        };
+        self.rebase_job = Some(assist.spawn(action));
+    }
+
+    /// Reports a finished `/rebase-assist` git step, and sends the model the
+    /// conflicts to resolve when the rebase stopped on some.
+    fn finish_rebase_job(&mut self, ctx: &mut RuntimeContext) -> bool {
+        let Some(job) = self.rebase_job.as_ref() else {
+            return false;
+        };
+        let outcome = match job.try_recv() {
+            Ok(outcome) => outcome,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return false,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                self.rebase_job = None;
+                self.push_history_line(
+                    "[rebase-assist: git step stopped unexpectedly]".to_string(),
+                );
+                return true;
+            }
+        };
+        self.rebase_job = None;
+        let RebaseOutcome {
+            assist,
+            action,
+            step,
+        } = outcome;
+        match step {
+            Ok(RebaseStep::Conflicts {
+                progress,
+                files,
+                prompt,
+            }) => {
+                self.rebase_assist = Some(assist);
+                self.push_history_line(format!(
+                    "[rebase-assist: {} has conflicts in {}; asking the model to resolve them]",
+                    progress.describe(),
+                    files.join(", ")
+                ));
+                self.on_user_input(prompt, ctx);
+            }
+            Ok(RebaseStep::Stopped(progress)) => {
+                self.rebase_assist = Some(assist);
+                self.push_history_line(format!(
+                    "[rebase-assist: stopped at {} without conflicts; /rebase-assist continue when ready]",
+                    progress.describe()
+                ));
+            }
+            Ok(RebaseStep::Finished) => {
+                self.push_history_line("[rebase-assist: rebase complete]".to_string());
+            }
+            Ok(RebaseStep::Aborted) => {
+                self.push_history_line("[rebase-assist: rebase aborted]".to_string());
+            }
+            Err(error) => {
+                match action {
+                    RebaseAction::Continue => self.rebase_assist = Some(assist),
+                    RebaseAction::Abort => {
+                        self.push_history_line(format!("[error] {error}"));
+                        return true;
+                    }
+                    RebaseAction::Start(_) => {}
+                }
+                self.push_history_line(format!("[rebase-assist] {error}"));
+            }
+        }
+        true
+    }
+
+    /// After the model's resolution turn, asks the user to review the edits
+    /// before the rebase continues.
+    fn review_rebase_resolution(&mut self) {
+        let Some(assist) = self.rebase_assist.as_ref() else {
+            return;
+        };
+        if assist.files().is_empty() {
+            return;
+        }
+        let unresolved = assist.unresolved();
+        let line = if unresolved.is_empty() {
+            format!(
+                "[rebase-assist: resolved {}; review the edits, then /rebase-assist continue to stage them and continue, or /rebase-assist abort]",
+                assist.files().join(", ")
+            )
+        } else {
+            format!(
+                "[rebase-assist: {} still has conflict markers; resolve them, then /rebase-assist continue]",
+                unresolved.join(", ")
+            )
+        };
+        self.push_history_line(line);
+    }
+
+    /// `/audit [n]` lists the newest `n` audit records, 20 by default.
+    fn show_audit_log(&mut self, count: &str) {
+        let Some(log) = AuditLog::from_env(&self.workspace_root) else {
+            self.push_history_line(
+                "[audit log is off; set VEX_AUDIT_LOG=on to record tool calls]".to_string(),
+            );
+            return;
+        };
+        let count = match count {
+            "" => DEFAULT_AUDIT_VIEW_COUNT,
+            value => match value.parse::<usize>() {
+                Ok(count) if count > 0 => count,
+                _ => {
+                    self.push_history_line("[error] usage: /audit [count]".to_string());
+                    return;
+                }
+            },
+        };
+        match log.read() {
+            Ok(records) if records.is_empty() => {
+                self.push_history_line(format!(
+                    "[no tool calls recorded in {}]",
+                    log.path().display()
+                ));
+            }
+            Ok(records) => {
+                let shown = &records[records.len().saturating_sub(count)..];
+                self.push_history_line(format!(
+                    "[audit log: last {} of {} in {}]\n{}",
+                    shown.len(),
+                    records.len(),
+                    log.path().display(),
+                    render_audit_records(shown)
+                ));
+            }
+            Err(error) => self.push_history_line(format!("[error] {error}")),
+        }
+    }
+
+    fn mode_status_label(&self) -> &'static str {
+        if self.overlay_active() {
+            "overlay"
+        } else if self.pending_quit {
+            "quit-arm"
+        } else if self.history_state.cancel_pending {
+            "cancelling"
+        } else if self.history_state.turn_in_progress {
+            "streaming"
+        } else {
+            "ready"
+        }
+    }
+
+    fn approval_status_label(&self) -> &'static str {
+        if self.overlay_active() {
+            "pending"
+        } else if self.overlay_state.auto_approve_session {
+            "auto"
+        } else {
+            "none"
+        }
+    }
+
+    pub fn status_line(&self) -> String {
+        let history_rows =
+            history_visual_line_count(&self.history_state.lines, self.history_content_width.get());
+        let mut line = format!(
+            "mode:{} approval:{} history:{} repo:{} vcs:{}",
+            self.mode_status_label(),
+            self.approval_status_label(),
+            history_rows,
+            self.repo_label,
+            self.vcs.label()
+        );
+        if let Some(version) = self.release_check.available_update() {
+            line.push_str(&format!(" update:v{version}"));
+        }
+        line
+    }
+
+    /// Compact status for the right of the input footer: a spinner and the
+    /// elapsed time while a turn runs, open approval prompts, and messages
+    /// queued for after the turn. Empty when idle.
+    pub fn footer_widgets(&self, now: Instant) -> String {
+        let mut widgets = Vec::new();
+        if self.history_state.turn_in_progress {
+            let elapsed = self
+                .history_state
+                .turn_started_at
+                .map(|started| now.saturating_duration_since(started))
+                .unwrap_or_default();
+            let frame =
+                (elapsed.as_millis() / FOOTER_SPINNER_FRAME_MS) as usize % FOOTER_SPINNER.len();
+            widgets.push(format!(
+                "{} {}",
+                FOOTER_SPINNER[frame],
+                format_elapsed(elapsed)
+            ));
+        }
+        let approvals = usize::from(self.overlay_state.pending_approval.is_some())
+            + usize::from(self.overlay_state.pending_patch_approval.is_some());
+        match approvals {
+            0 => {}
+            1 => widgets.push("1 approval".to_string()),
+            count => widgets.push(format!("{count} approvals")),
+        }
+        if self.overlay_state.pending_question.is_some() {
+            widgets.push("1 question".to_string());
+        }
+        if !self.queued_messages.is_empty() {
+            widgets.push(format!("{} queued", self.queued_messages.len()));
+        }
+        widgets.join(" | ")
+    }
+
+    /// True when `text` answers a pending tool approval (`1`-`4`, `y`, `n`,
+    /// `a`, `r`, or `n <reason>`). Anything else typed or pasted while the
+    /// prompt is open belongs to the input draft.
+    pub fn is_tool_approval_answer(text: &str) -> bool {
+        let choice = text.split_whitespace().next().unwrap_or_default();
+        matches!(
+            choice.to_lowercase().as_str(),
+            "1" | "2"
+                | "3"
+                | "4"
+                | "y"
+                | "yes"
+                | "a"
+                | "always"
+                | "n"
+                | "no"
+                | "esc"
+                | "r"
+                | "reason"
+        )
+    }
+
+    pub fn overlay_active(&self) -> bool {
+        self.overlay_state.pending_approval.is_some()
+            || self.overlay_state.pending_patch_approval.is_some()
+            || self.overlay_state.pending_paste.is_some()
+    }
+
+    fn patch_overlay_active(&self) -> bool {
+        self.overlay_state.pending_patch_approval.is_some()
+    }
+
+    pub fn history_lines(&self) -> &[String] {
+        &self.history_state.lines
+    }
+
+    /// Lines the history cap has removed from the front of
+    /// [`Self::history_lines`] since startup.
+    pub fn history_lines_dropped(&self) -> usize {
+        self.history_state.dropped_lines
+    }
+
+    pub fn active_assistant_index(&self) -> Option<usize> {
+        self.history_state.active_assistant_index
+    }
+
+    pub fn history_scroll_offset(&self) -> usize {
+        self.history_state.scroll_offset
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+
+    pub fn pending_patch_overlay(&self) -> Option<(&str, usize)> {
+        self.overlay_state
+            .pending_patch_approval
+            .as_ref()
+            .map(|pending| (pending.patch_preview.as_str(), pending.scroll_offset))
+    }
+
+    pub fn pending_tool_overlay(&self) -> Option<ToolApprovalOverlay<'_>> {
+        self.overlay_state
+            .pending_approval
+            .as_ref()
+            .map(|pending| ToolApprovalOverlay {
+                tool_name: &pending.tool_name,
+                input_preview: &pending.input_preview,
+                class: pending.class,
+                paths: &pending.paths,
+                auto_approve_enabled: self.overlay_state.auto_approve_session,
+                reason_entry: pending.reason_entry,
+                tier: &pending.tier,
+            })
+    }
+
+    /// One-line summaries of tool calls from the current round that are still
+    /// queued behind the call awaiting approval, in model order.
+    pub fn queued_tool_calls(&self) -> Vec<String> {
+        let mut queued: Vec<(&usize, String)> = self
+            .active_stream_blocks
+            .iter()
+            .filter_map(|(index, block)| match block {
+                StreamBlock::ToolCall {
+                    name,
+                    input,
+                    status: ToolStatus::Pending,
+                    ..
+                } => {
+                    let preview = preview_tool_input(name, input, ToolPreviewStyle::Structured, 0);
+                    Some((index, summarize_tool_approval_context(name, &preview)))
+                }
+                _ => None,
+            })
+            .collect();
+        queued.sort_by_key(|(index, _)| **index);
+        queued.into_iter().map(|(_, summary)| summary).collect()
+    }
+
+    /// True while the tool overlay is collecting a free-text denial reason, in
+    /// which case the frontend routes keys to the regular input editor.
+    pub fn approval_reason_entry_active(&self) -> bool {
+        self.overlay_state
+            .pending_approval
+            .as_ref()
+            .is_some_and(|pending| pending.reason_entry)
+    }
+
+    pub fn set_history_content_width(&self, width: usize) {
+        self.history_content_width.set(width.max(1));
+    }
+
+    fn resolve_pending_approval(&mut self, decision: ToolApprovalDecision) {
+        if let Some(pending) = self.overlay_state.pending_approval.take() {
+            let _ = pending.response_tx.send(decision);
+        }
+    }
+
+    fn deny_pending_approval(&mut self, context: &str, reason: &str) {
+        let decision = ToolApprovalDecision::denied_with_reason(reason);
+        match decision.reason() {
+            Some(reason) => self.push_history_line(format!(
+                "[tool approval denied: {context} reason: {reason}]"
+            )),
+            None => self.push_history_line(format!("[tool approval denied: {context}]")),
+        }
+        self.resolve_pending_approval(decision);
+    }
+
+    fn handle_approval_input(&mut self, input: &str, ctx: &mut RuntimeContext) {
+        let context = self
+            .overlay_state
+            .pending_approval
+            .as_ref()
+            .map(|p| summarize_tool_approval_context(&p.tool_name, &p.input_preview))
+            .unwrap_or_else(|| "unknown".to_string());
+
+        if self.approval_reason_entry_active() {
+            let reason = if input.trim().eq_ignore_ascii_case("esc") {
+                ""
+            } else {
+                input
+            };
+            self.deny_pending_approval(&context, reason);
+            return;
+        }
+
+        let trimmed = input.trim();
+        let (choice, rest) = trimmed
+            .split_once(char::is_whitespace)
+            .unwrap_or((trimmed, ""));
+        match choice.to_lowercase().as_str() {
+            "1" | "y" | "yes" if rest.is_empty() => {
+                self.push_history_line(format!("[tool approval accepted once: {context}]"));
+                self.resolve_pending_approval(ToolApprovalDecision::Approved);
+            }
+            "2" | "a" | "always" if rest.is_empty() => {
+                self.overlay_state.auto_approve_session = true;
+                ctx.set_session_auto_approve(true);
+                self.push_history_line(format!("[tool approval enabled for session: {context}]"));
+                self.resolve_pending_approval(ToolApprovalDecision::Approved);
+            }
+            "3" | "n" | "no" | "esc" => self.deny_pending_approval(&context, rest),
+            "4" | "r" | "reason" if rest.is_empty() => {
+                if let Some(pending) = self.overlay_state.pending_approval.as_mut() {
+                    pending.reason_entry = true;
+                }
+                self.push_history_line(
+                    "[type a reason for denying and press Enter; esc denies without one]"
+                        .to_string(),
+                );
+            }
+            _ => {
+                self.push_history_line("[invalid selection, expected 1/2/3/4]".to_string());
+            }
+        }
+    }
+
+    fn resolve_pending_patch_approval(&mut self, approved: bool) {
+        if let Some(mut pending) = self.overlay_state.pending_patch_approval.take() {
+            if let Some(tx) = pending.response_tx.take() {
+                let _ = tx.send(approved);
+            }
+            let decision = if approved { "accepted" } else { "denied" };
+            self.push_history_line(format!("[patch approval {decision}]"));
+        }
+    }
+
+    fn apply_patch_overlay_scroll_action(&mut self, action: ScrollAction) {
+        if let Some(pending) = self.overlay_state.pending_patch_approval.as_mut() {
+            let max = pending.patch_preview.lines().count().saturating_sub(1);
+            match action {
+                ScrollAction::LineUp => {
+                    pending.scroll_offset = pending.scroll_offset.saturating_sub(1);
+                }
+                ScrollAction::LineDown => {
+                    pending.scroll_offset = pending.scroll_offset.saturating_add(1).min(max);
+                }
+                ScrollAction::PageUp(step) => {
+                    pending.scroll_offset = pending.scroll_offset.saturating_sub(step.max(1));
+                }
+                ScrollAction::PageDown(step) => {
+                    pending.scroll_offset =
+                        pending.scroll_offset.saturating_add(step.max(1)).min(max);
+                }
+                ScrollAction::Home => {
+                    pending.scroll_offset = 0;
+                }
+                ScrollAction::End => {
+                    pending.scroll_offset = max;
+                }
+            }
+        }
+    }
+
+    fn handle_patch_overlay_input(&mut self, input: &str) {
+        if self.overlay_state.pending_patch_approval.is_none() {
+            return;
+        }
+
+        let normalized = input.trim().to_lowercase();
+        match normalized.as_str() {
+            "1" | "y" | "yes" => self.resolve_pending_patch_approval(true),
+            "3" | "n" | "no" | "esc" => self.resolve_pending_patch_approval(false),
+            _ => {}
+        }
+    }
+
+    /// A number picks that option; any other text is the answer as typed.
+    fn answer_pending_question(&mut self, input: &str) {
+        let Some(question) = self.overlay_state.pending_question.take() else {
+            return;
+        };
+        let input = input.trim();
+        let answer = if input.is_empty() || input == SKIP_QUESTION_COMMAND {
+            None
+        } else {
+            let picked = input
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| question.options.get(number.checked_sub(1)?));
+            Some(picked.map_or_else(|| input.to_string(), Clone::clone))
+        };
+        match &answer {
+            Some(answer) => self.push_history_line(format!("[answered: {answer}]")),
+            None => self.push_history_line("[question skipped]".to_string()),
+        }
+        let _ = question.response_tx.send(answer);
+    }
+
+    fn handle_paste_overlay_input(&mut self, input: &str) {
+        let Some(paste) = self.overlay_state.pending_paste.take() else {
+            return;
+        };
+
+        let normalized = input.trim().to_lowercase();
+        match normalized.as_str() {
+            "1" | "y" | "yes" | "i" => {
+                *self.paste_insert.borrow_mut() = Some(paste.text);
+            }
+            "2" | "a" | "attach" => {
+                let line = match self
+                    .attachments
+                    .attach_text(&self.workspace_root, &paste.text)
+                {
+                    Ok(attachment) => format!("[attached paste: {}]", attachment.summary()),
+                    Err(error) => format!("[error] {error:#}"),
+                };
+                self.push_history_line(line);
+            }
+            "3" | "n" | "no" | "esc" => {
+                self.push_history_line(format!(
+                    "[paste discarded: {} lines]",
+                    crate::util::group_thousands(paste.lines)
+                ));
+            }
+            _ => self.overlay_state.pending_paste = Some(paste),
+        }
+    }
+
+    /// Shows `image` in the transcript: its placeholder line, plus the rows
+    /// it is drawn over when the frontend can draw images.
+    fn push_inline_image(&mut self, image: InlineImage) {
+        self.push_history_line(image.history_entry(self.inline_image_rows));
+        if self.inline_images.len() == MAX_INLINE_IMAGES {
+            self.inline_images.remove(0);
+        }
+        self.inline_images.push(image);
+    }
+
+    fn push_history_line(&mut self, line: String) {
+        self.history_state.lines.push(line);
+        self.enforce_history_cap();
+        if self.history_state.auto_follow {
+            self.set_scroll_to_bottom();
+        } else {
+            self.clamp_scroll_offset();
+        }
+    }
+
+    fn enforce_history_cap(&mut self) {
+        let cap = self.history_line_cap + self.paged_in_lines;
+        if self.history_state.lines.len() <= cap {
+            return;
+        }
+
+        let excess = self.history_state.lines.len() - cap;
+        let spilled: Vec<String> = self.history_state.lines.drain(..excess).collect();
+        if self.transcript_spill.push(&spilled).is_err() {
+            self.transcript_spill.disable();
+        }
+        self.history_state.dropped_lines += excess;
+        self.history_state.active_assistant_index = self
+            .history_state
+            .active_assistant_index
+            .and_then(|idx| idx.checked_sub(excess));
+        let dropped = self
+            .thinking
+            .blocks
+            .iter()
+            .take_while(|block| block.line < excess)
+            .count();
+        self.thinking.blocks.drain(..dropped);
+        for block in &mut self.thinking.blocks {
+            block.line -= excess;
+        }
+        self.thinking.active = self
+            .thinking
+            .active
+            .and_then(|index| index.checked_sub(dropped));
+        self.history_state.scroll_offset = self.history_state.scroll_offset.saturating_sub(excess);
+        self.clamp_scroll_offset();
+    }
+
+    /// Moves the newest spilled lines back in front of the history, keeping
+    /// the view on the same content. False when nothing was spilled.
+    fn page_in_spilled_lines(&mut self) -> bool {
+        let count = (self.history_line_cap / 2).max(1);
+        let lines = match self.transcript_spill.pop_newest(count) {
+            Ok(lines) if !lines.is_empty() => lines,
+            Ok(_) => return false,
+            Err(_) => {
+                self.transcript_spill.disable();
+                return false;
+            }
+        };
+        let added = lines.len();
+        let added_rows = history_visual_line_count(&lines, self.history_content_width.get());
+        self.history_state.lines.splice(0..0, lines);
+        self.history_state.dropped_lines = self.history_state.dropped_lines.saturating_sub(added);
+        self.history_state.active_assistant_index = self
+            .history_state
+            .active_assistant_index
+            .map(|index| index + added);
+        for block in &mut self.thinking.blocks {
+            block.line += added;
+        }
+        self.paged_in_lines += added;
+        self.history_state.scroll_offset += added_rows;
+        true
+    }
+
+    /// Drops paged-in lines back to disk once the view follows the bottom.
+    fn release_paged_in_lines(&mut self) {
+        if self.paged_in_lines > 0 {
+            self.paged_in_lines = 0;
+            self.enforce_history_cap();
+        }
+    }
+
+    fn max_scroll_offset(&self) -> usize {
+        history_visual_line_count(&self.history_state.lines, self.history_content_width.get())
+            .saturating_sub(1)
+    }
+
+    fn set_scroll_to_bottom(&mut self) {
+        self.history_state.scroll_offset = self.max_scroll_offset();
+    }
+
+    fn clamp_scroll_offset(&mut self) {
+        let max = self.max_scroll_offset();
+        self.history_state.scroll_offset = self.history_state.scroll_offset.min(max);
+    }
+
+    fn apply_page_up(&mut self, page_step: usize) {
+        if page_step.max(1) > self.history_state.scroll_offset {
+            self.page_in_spilled_lines();
+        }
+        self.history_state.scroll_offset = self
+            .history_state
+            .scroll_offset
+            .saturating_sub(page_step.max(1));
+        self.history_state.auto_follow = false;
+    }
+
+    fn apply_page_down(&mut self, page_step: usize) {
+        let max = self.max_scroll_offset();
+        self.history_state.scroll_offset = self
+            .history_state
+            .scroll_offset
+            .saturating_add(page_step.max(1))
+            .min(max);
+        self.history_state.auto_follow = self.history_state.scroll_offset >= max;
+        if self.history_state.auto_follow {
+            self.release_paged_in_lines();
+            self.set_scroll_to_bottom();
+        }
+    }
+
+    /// At the top already, loads the next spilled page first.
+    fn apply_home(&mut self) {
+        if self.history_state.scroll_offset == 0 {
+            self.page_in_spilled_lines();
+        }
+        self.history_state.scroll_offset = 0;
+        self.history_state.auto_follow = false;
+    }
+
+    fn apply_end(&mut self) {
+        self.release_paged_in_lines();
+        self.set_scroll_to_bottom();
+        self.history_state.auto_follow = true;
+    }
+
+    fn apply_history_scroll_action(&mut self, action: ScrollAction) {
+        match action {
+            ScrollAction::LineUp => self.apply_page_up(1),
+            ScrollAction::LineDown => self.apply_page_down(1),
+            ScrollAction::PageUp(step) => self.apply_page_up(step),
+            ScrollAction::PageDown(step) => self.apply_page_down(step),
+            ScrollAction::Home => self.apply_home(),
+            ScrollAction::End => self.apply_end(),
+        }
+    }
+}
+
+fn resolve_history_line_cap() -> usize {
+    std::env::var(MAX_HISTORY_LINES_ENV)
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|cap| *cap > 0)
+        .unwrap_or(DEFAULT_MAX_HISTORY_LINES)
+}
+
+fn resolve_paste_confirm_lines() -> usize {
+    std::env::var(PASTE_CONFIRM_LINES_ENV)
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .unwrap_or(DEFAULT_PASTE_CONFIRM_LINES)
+}
+
+fn resolve_repo_label() -> String {
+    std::env::var("VEX_REPO_LABEL")
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .or_else(|| {
+            std::env::current_dir()
+                .ok()
+                .and_then(|path| {
+                    path.file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                })
+                .filter(|name| !name.trim().is_empty())
+        })
+        .unwrap_or_else(|| "workspace".to_string())
+}
+
+#[cfg(test)]
+fn input_rows_for_buffer(input: &str, width: usize) -> u16 {
+    input_visual_rows(input, width).clamp(1, MAX_INPUT_PANE_ROWS) as u16
+}
+
+#[cfg(test)]
+struct RenderGuard {
+    dirty: bool,
+    cursor_tick: Duration,
+    status_tick: Duration,
+    last_draw_at: Instant,
+    last_render_state_hash: Option<u64>,
+}
+
+#[cfg(test)]
+impl RenderGuard {
+    fn with_intervals(cursor_tick: Duration, status_tick: Duration, now: Instant) -> Self {
+        Self {
+            dirty: true,
+            cursor_tick,
+            status_tick,
+            last_draw_at: now,
+            last_render_state_hash: None,
+        }
+    }
+
+    fn poll_timeout(&self) -> Duration {
+        self.cursor_tick.min(self.status_tick)
+    }
+
+    fn should_draw(&mut self, now: Instant, state_hash: u64) -> bool {
+        if self.last_render_state_hash != Some(state_hash) {
+            self.dirty = true;
+        }
+
+        if self.dirty || now.saturating_duration_since(self.last_draw_at) >= self.poll_timeout() {
+            self.dirty = false;
+            self.last_draw_at = now;
+            self.last_render_state_hash = Some(state_hash);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Default for TuiMode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RuntimeMode for TuiMode {
+    fn on_frontend_event(&mut self, event: UserInputEvent, ctx: &mut RuntimeContext) {
+        match event {
+            UserInputEvent::Text(input) => self.on_user_input(input, ctx),
+            UserInputEvent::Interrupt => self.on_interrupt(ctx),
+            UserInputEvent::LargePaste(text) => {
+                self.overlay_state.pending_paste = Some(PendingPaste {
+                    lines: text.lines().count(),
+                    chars: text.chars().count(),
+                    text,
+                });
+            }
+            UserInputEvent::Scroll { target, action } => {
+                if self.overlay_active() {
+                    if target == ScrollTarget::Overlay {
+                        self.apply_patch_overlay_scroll_action(action);
+                    }
+                } else if self.history_picker_active() {
+                    if target == ScrollTarget::Overlay {
+                        self.apply_history_picker_scroll_action(action);
+                    }
+                } else if self.preset_picker_active() {
+                    if target == ScrollTarget::Overlay {
+                        self.apply_preset_picker_scroll_action(action);
+                    }
+                } else if self.session_picker_active() {
+                    if target == ScrollTarget::Overlay {
+                        self.apply_session_picker_scroll_action(action);
+                    }
+                } else if self.help_active() {
+                    if target == ScrollTarget::Overlay {
+                        self.apply_help_scroll_action(action);
+                    }
+                } else if target == ScrollTarget::History {
+                    self.apply_history_scroll_action(action);
+                }
+            }
+        }
+    }
+
+    fn on_user_input(&mut self, input: String, ctx: &mut RuntimeContext) {
+        if self.overlay_active() {
+            if self.overlay_state.pending_paste.is_some() {
+                self.handle_paste_overlay_input(&input);
+            } else if self.patch_overlay_active() {
+                self.handle_patch_overlay_input(&input);
+            } else {
+                self.handle_approval_input(&input, ctx);
+            }
+            return;
+        }
+
+        if self.history_picker_active() {
+            self.handle_history_picker_input(&input, ctx);
+            return;
+        }
+
+        if self.preset_picker_active() {
+            self.handle_preset_picker_input(&input);
+            return;
+        }
+
+        if self.session_picker_active() {
+            self.handle_session_picker_input(&input, ctx);
+            return;
+        }
+
+        if self.help_active() {
+            self.handle_help_input(&input);
+            return;
+        }
+
+        // `/cancel` and `/help` still work while a question waits; any other
+        // line is the answer.
+        if self.overlay_state.pending_question.is_some()
+            && parse_cancel_command(&input).is_none()
+            && !matches!(input.trim(), "/help" | "/commands")
+        {
+            self.answer_pending_question(&input);
+            return;
+        }
+
+        if self.handle_local_command(&input, ctx) {
+            return;
+        }
+
+        if self.rebase_job.is_some() {
+            self.push_history_line(
+                "[rebase-assist: git is still running; send this again once it finishes]"
+                    .to_string(),
+            );
+            return;
+        }
+
+        if self.history_state.turn_in_progress {
+            if let Some(reason) = parse_cancel_command(&input) {
+                if !self.history_state.cancel_pending {
+                    ctx.cancel_turn_with_reason(reason);
+                    self.begin_turn_cancellation();
+                    if !reason.is_empty() {
+                        self.push_history_line(format!("[cancellation reason: {reason}]"));
+                    }
+                    return;
+                }
+            }
+            if self.history_state.cancel_pending {
+                self.push_history_line(
+                    "[busy - cancelling current turn, input discarded]".to_string(),
+                );
+            } else {
+                self.queued_messages.push_back(input);
+                self.push_history_line(format!(
+                    "[queued - sends when this turn ends ({} waiting)]",
+                    self.queued_messages.len()
+                ));
+            }
+            return;
+        }
+
+        let (overrides, message) = match TurnOverrides::parse_prefix(&input) {
+            Ok(Some((overrides, message))) => (overrides, message),
+            Ok(None) => (TurnOverrides::default(), input.clone()),
+            Err(error) => {
+                self.push_history_line(format!("[error] {error}"));
+                return;
+            }
+        };
+
+        self.pending_quit = false;
+        self.quit_requested = false;
+        self.history_state.cancel_pending = false;
+        self.follow_ups.clear();
+        self.separator_turn += 1;
+        if let Some(separator) = self.turn_separators.line(self.separator_turn, "you") {
+            self.push_history_line(separator);
+            self.response_separator_pending = true;
+        }
+        self.push_history_line(format!("> {input}"));
+        if !overrides.is_empty() {
+            self.push_history_line(format!(
+                "[turn overrides: {}{}]",
+                overrides.describe(),
+                self.dropped_sampling_note(&overrides)
+            ));
+        }
+        self.push_history_line(String::new());
+        self.history_state.active_assistant_index = Some(self.history_state.lines.len() - 1);
+        self.history_state.turn_in_progress = true;
+        self.history_state.turn_started_at = Some(Instant::now());
+        self.prompt_history.push(&input);
+        for (path, content) in self.attachments.pending_uploads() {
+            ctx.queue_document(path.to_string(), content.to_string());
+        }
+        let message = match self.attachments.take_message_prefix() {
+            Some(attachments) => format!("{attachments}\n\n{message}"),
+            None => message,
+        };
+        ctx.start_turn_with_overrides(message, overrides);
+    }
+
+    fn on_model_update(&mut self, update: UiUpdate, ctx: &mut RuntimeContext) {
+        if matches!(
+            update,
+            UiUpdate::StreamDelta(_)
+                | UiUpdate::StreamBlockStart { .. }
+                | UiUpdate::StreamBlockDelta { .. }
+        ) && !self.history_state.cancel_pending
+        {
+            self.push_response_separator();
+        }
+        let smoothed = matches!(update, UiUpdate::StreamDelta(_))
+            && self.thinking.stream_index.is_none()
+            && !self.history_state.cancel_pending;
+        if !smoothed {
+            // Nothing overtakes response text still queued for display.
+            self.flush_typewriter();
+        }
+        match update {
+            UiUpdate::StreamDelta(text) => {
+                if self.history_state.cancel_pending {
+                    return;
+                }
+                if self.thinking.stream_index.is_some() {
+                    self.append_thinking_delta(&text);
+                    if self.history_state.auto_follow {
+                        self.set_scroll_to_bottom();
+                    }
+                    return;
+                }
+                match self.typewriter.as_mut() {
+                    Some(typewriter) => typewriter.push(&text),
+                    None => self.append_response_text(&text),
+                }
+            }
+            UiUpdate::StreamBlockStart { index, block } => {
+                if matches!(block, StreamBlock::Thinking { .. }) {
+                    if self.thinking.stream_index != Some(index) {
+                        self.end_thinking_stream();
+                        self.thinking.stream_index = Some(index);
+                    }
+                } else {
+                    self.end_thinking_stream();
+                }
+                let finished_tool_call = matches!(
+                    &block,
+                    StreamBlock::ToolCall {
+                        status: ToolStatus::Complete | ToolStatus::Error | ToolStatus::Cancelled,
+                        ..
+                    }
+                );
+                if let StreamBlock::ToolCall {
+                    name,
+                    input,
+                    status: ToolStatus::Complete,
+                    ..
+                } = &block
+                {
+                    if name == "edit_file" {
+                        self.push_edit_diff(input);
+                    }
+                }
+                if let StreamBlock::ToolCall {
+                    name,
+                    status: ToolStatus::Pending,
+                    ..
+                } = &block
+                {
+                    // Later Pending starts for the same call re-announce it
+                    // after its input finished streaming.
+                    if matches!(name.as_str(), "edit_file" | "write_file")
+                        && !self.active_stream_blocks.contains_key(&index)
+                    {
+                        self.start_live_tool_preview(index, name);
+                    }
+                }
+                self.record_tool_output(&block);
+                if finished_tool_call {
+                    self.active_stream_blocks.remove(&index);
+                } else {
+                    self.active_stream_blocks.insert(index, block);
+                }
+            }
+            UiUpdate::StreamBlockDelta { index, delta } => {
+                if let Some(block) = self.active_stream_blocks.get_mut(&index) {
+                    match block {
+                        StreamBlock::Thinking { content, .. } => content.push_str(&delta),
+                        StreamBlock::FinalText { content } => content.push_str(&delta),
+                        StreamBlock::ToolCall { .. } => {
+                            self.update_live_tool_preview(index, &delta)
+                        }
+                        StreamBlock::ToolResult { .. } => {}
+                    }
+                }
+            }
+            UiUpdate::StreamBlockComplete { index } => {
+                if self
+                    .live_tool_preview
+                    .as_ref()
+                    .is_some_and(|preview| preview.index == index)
+                {
+                    self.end_live_tool_preview();
+                }
+                if self.thinking.stream_index == Some(index) {
+                    self.end_thinking_stream();
+                }
+                // Tool calls stay listed until they finish so queued calls
+                // remain visible behind a pending approval.
+                if !matches!(
+                    self.active_stream_blocks.get(&index),
+                    Some(StreamBlock::ToolCall { .. })
+                ) {
+                    self.active_stream_blocks.remove(&index);
+                }
+            }
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name,
+                input_preview,
+                class,
+                paths,
+                tier,
+                response_tx,
+                ..
+            }) => {
+                if self.history_state.cancel_pending {
+                    let _ = response_tx.send(false.into());
+                    return;
+                }
+                if self.overlay_state.auto_approve_session && !tier.is_sensitive() {
+                    let _ = response_tx.send(ToolApprovalDecision::Approved);
+                    self.push_history_line(format!("[auto-approved tool: {tool_name} session]"));
+                    return;
+                }
+
+                self.resolve_pending_approval(false.into());
+                self.resolve_pending_patch_approval(false);
+                let summary = summarize_tool_approval_context(&tool_name, &input_preview);
+                match tier.note() {
+                    Some(note) => self
+                        .push_history_line(format!("[tool approval requested: {summary}; {note}]")),
+                    None => self.push_history_line(format!("[tool approval requested: {summary}]")),
+                }
+                self.overlay_state.pending_approval = Some(PendingApproval {
+                    tool_name,
+                    input_preview,
+                    class,
+                    paths,
+                    tier,
+                    response_tx,
+                    reason_entry: false,
+                });
+            }
+            UiUpdate::UserQuestion(UserQuestionRequest {
+                question,
+                options,
+                response_tx,
+                ..
+            }) => {
+                if self.history_state.cancel_pending {
+                    let _ = response_tx.send(None);
+                    return;
+                }
+                self.push_history_line(format!("[question] {question}"));
+                for (number, option) in options.iter().enumerate() {
+                    self.push_history_line(format!("  {}. {option}", number + 1));
+                }
+                self.push_history_line(if options.is_empty() {
+                    format!("[type your answer; {SKIP_QUESTION_COMMAND} skips]")
+                } else {
+                    format!("[type a number or your own answer; {SKIP_QUESTION_COMMAND} skips]")
+                });
+                self.overlay_state.pending_question = Some(PendingQuestion {
+                    options,
+                    response_tx,
+                });
+            }
+            UiUpdate::Status(text) => self.push_history_line(format!("[{text}]")),
+            UiUpdate::TurnChanges(summary) => {
+                for line in summary.render_lines() {
+                    self.push_history_line(line);
+                }
+            }
+            UiUpdate::TurnComplete => {
+                self.prompt_history
+                    .finish_latest(if self.history_state.cancel_pending {
+                        PromptOutcome::Cancelled
+                    } else {
+                        PromptOutcome::Ok
+                    });
+                self.resolve_pending_approval(false.into());
+                self.resolve_pending_patch_approval(false);
+                self.overlay_state.pending_question = None;
+                self.active_stream_blocks.clear();
+                self.end_live_tool_preview();
+                self.response_separator_pending = false;
+                self.end_thinking_stream();
+                if let Some(speech) = self.speech.as_mut() {
+                    if self.history_state.cancel_pending {
+                        speech.cancel();
+                    } else {
+                        speech.finish();
+                    }
+                }
+                if self.claim_check && !self.history_state.cancel_pending {
+                    let (changes, text) = last_turn_changes(&ctx.conversation_reader().messages());
+                    for line in claim_annotations(&changes, &text) {
+                        self.push_history_line(line);
+                    }
+                }
+                if self.follow_ups_enabled && !self.history_state.cancel_pending {
+                    self.follow_ups = ctx
+                        .conversation_reader()
+                        .latest_turn_changes()
+                        .map(|changes| suggest_follow_ups(&changes, &self.workspace_root))
+                        .unwrap_or_default();
+                }
+                if !self.history_state.cancel_pending {
+                    self.review_rebase_resolution();
+                }
+                self.history_state.cancel_pending = false;
+                self.history_state.turn_in_progress = false;
+                self.history_state.turn_started_at = None;
+                self.history_state.active_assistant_index = None;
+                if self.history_state.auto_follow {
+                    self.set_scroll_to_bottom();
+                } else {
+                    self.clamp_scroll_offset();
+                }
+                if let Some(next) = self.queued_messages.pop_front() {
+                    self.on_user_input(next, ctx);
+                }
+            }
+            UiUpdate::Error(msg) => {
+                self.prompt_history.finish_latest(PromptOutcome::Error);
+                self.resolve_pending_approval(false.into());
+                self.resolve_pending_patch_approval(false);
+                self.overlay_state.pending_question = None;
+                self.active_stream_blocks.clear();
+                self.end_live_tool_preview();
+                self.response_separator_pending = false;
+                self.end_thinking_stream();
+                if let Some(speech) = self.speech.as_mut() {
+                    speech.cancel();
+                }
+                self.history_state.cancel_pending = false;
+                self.push_history_line(format!("[error] {msg}"));
+                self.drop_queued_messages();
+                self.history_state.turn_in_progress = false;
+                self.history_state.turn_started_at = None;
+                self.history_state.active_assistant_index = None;
+            }
+        }
+    }
+
+    fn on_interrupt(&mut self, ctx: &mut RuntimeContext) {
+        if self.history_state.turn_in_progress {
+            if self.history_state.cancel_pending {
+                return;
+            }
+            ctx.cancel_turn();
+            self.begin_turn_cancellation();
+            return;
+        }
+
+        if self.pending_quit {
+            self.quit_requested = true;
+        } else {
+            self.pending_quit = true;
+            self.push_history_line("[press Ctrl+C again to exit]".to_string());
+        }
+    }
+
+    fn is_turn_in_progress(&self) -> bool {
+        self.history_state.turn_in_progress
+    }
+
+    fn needs_tick_render(&self) -> bool {
+        // One more tick after the release check ends shows its result.
+        let fetching = self.release_check.is_fetching();
+        let was_fetching = self.release_check_was_fetching.replace(fetching);
+        self.history_state.turn_in_progress || fetching || was_fetching
+    }
+
+    fn poll_background(&mut self, ctx: &mut RuntimeContext) -> bool {
+        self.finish_rebase_job(ctx)
+    }
+
+    fn on_frame(&mut self, now: Instant) -> bool {
+        let Some(text) = self
+            .typewriter
+            .as_mut()
+            .and_then(|typewriter| typewriter.release(now))
+        else {
+            return false;
+        };
+        self.append_response_text(&text);
+        true
+    }
+
+    fn on_shutdown_started(
+        &mut self,
+        in_flight_tools: usize,
+        grace: Duration,
+        _ctx: &mut RuntimeContext,
+    ) {
+        if self.history_state.turn_in_progress && !self.history_state.cancel_pending {
+            self.begin_turn_cancellation();
+        }
+        let waiting = match in_flight_tools {
+            0 => "the turn to stop".to_string(),
+            1 => "1 running tool".to_string(),
+            count => format!("{count} running tools"),
+        };
+        self.push_history_line(format!(
+            "[shutting down: waiting up to {}s for {waiting}; press Ctrl+C to exit now]",
+            grace.as_secs()
+        ));
+    }
+}
+
+impl TuiMode {
+    fn report_speech_failure(&mut self) {
+        let Some(error) = self
+            .speech
+            .as_ref()
+            .and_then(|speech| speech.speaker().take_failure())
+        else {
+            return;
+        };
+        self.speech = None;
+        self.push_history_line(format!("[speech disabled] {error}"));
+    }
+
+    fn begin_turn_cancellation(&mut self) {
+        self.resolve_pending_approval(false.into());
+        self.resolve_pending_patch_approval(false);
+        self.overlay_state.pending_question = None;
+        self.history_state.cancel_pending = true;
+        if let Some(speech) = self.speech.as_mut() {
+            speech.cancel();
+        }
+        self.push_history_line("[turn cancellation requested]".to_string());
+        self.drop_queued_messages();
+        self.pending_quit = false;
+        self.quit_requested = false;
+    }
+
+    /// Messages queued behind a turn that was cancelled or failed are not
+    /// sent; they were written for a turn that did not finish.
+    fn drop_queued_messages(&mut self) {
+        match self.queued_messages.len() {
+            0 => {}
+            1 => self.push_history_line("[dropped 1 queued message]".to_string()),
+            count => self.push_history_line(format!("[dropped {count} queued messages]")),
+        }
+        self.queued_messages.clear();
+    }
+}
+
+/// One saved session as listed by `/resume` and `/sessions`.
+fn saved_session_row(session: &SavedSessionSummary, current: Option<&str>) -> String {
+    let marker = if current == Some(session.id.as_str()) {
+        " (current)"
+    } else {
+        ""
+    };
+    let tags: String = session.tags.iter().map(|tag| format!("  #{tag}")).collect();
+    format!(
+        "{}  {}  {} turns  {}{tags}{marker}",
+        session.id,
+        format_utc(session.saved_at),
+        session.turn_count,
+        session.title
+    )
+}
+
+/// Parses `/cancel [reason]`, returning the (possibly empty) reason.
+fn parse_cancel_command(input: &str) -> Option<&str> {
+    let trimmed = input.trim();
+    let rest = trimmed.strip_prefix("/cancel")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(rest.trim())
+}
+
+/// The path, pattern, or ref a tool call names, for register labels.
+fn tool_input_subject(input: &serde_json::Value) -> Option<String> {
+    [
+        "path",
+        "file_path",
+        "pattern",
+        "query",
+        "from",
+        "ref",
+        "revision",
+    ]
+    .iter()
+    .find_map(|key| input.get(*key).and_then(|value| value.as_str()))
+    .filter(|value| !value.is_empty())
+    .map(str::to_string)
+}
+
+fn summarize_tool_approval_context(tool_name: &str, input_preview: &str) -> String {
+    let mut path: Option<&str> = None;
+    let mut summary_line: Option<&str> = None;
+
+    for line in input_preview.lines().take(8) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if path.is_none() && trimmed.starts_with("path:") {
+            path = Some(trimmed);
+            continue;
+        }
+        if summary_line.is_none()
+            && (trimmed.starts_with("change:") || trimmed.starts_with("content:"))
+        {
+            summary_line = Some(trimmed);
+            continue;
+        }
+        if summary_line.is_none() {
+            summary_line = Some(trimmed);
+        }
+    }
+
+    let summary = match (path, summary_line) {
+        (Some(path), Some(summary)) => format!("{tool_name} {path} {summary}"),
+        (Some(path), None) => format!("{tool_name} {path}"),
+        (None, Some(summary)) => format!("{tool_name} {summary}"),
+        (None, None) => tool_name.to_string(),
+    };
+    truncate_with_ellipsis(&summary, APPROVAL_SUMMARY_MAX_WIDTH, Ellipsis::Unicode)
+}
+
+#[cfg(test)]
+fn overlay_event_to_user_input(event: Event) -> Option<UserInputEvent> {
+    match event {
+        Event::Key(key) => match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(UserInputEvent::Interrupt)
+            }
+            KeyCode::Esc => Some(UserInputEvent::Text("esc".to_string())),
+            KeyCode::Char(ch)
+                if !key.modifiers.contains(KeyModifiers::CONTROL)
+                    && !key.modifiers.contains(KeyModifiers::ALT) =>
+            {
+                Some(UserInputEvent::Text(ch.to_string()))
+            }
+            _ => None,
+        },
+        Event::Paste(text) => {
+            let trimmed = text.trim();
+            if trimmed.is_empty() {
+                None
+            } else {
+                Some(UserInputEvent::Text(trimmed.to_string()))
+            }
+        }
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg(test)]
+enum RenderPass {
+    Header,
+    History,
+    Input,
+    Overlay,
+}
+
+#[cfg(test)]
+fn render_pass_order(mode: &TuiMode) -> Vec<RenderPass> {
+    let mut order = vec![RenderPass::Header, RenderPass::History, RenderPass::Input];
+    if mode.overlay_active()
+        || mode.history_picker_active()
+        || mode.preset_picker_active()
+        || mode.session_picker_active()
+        || mode.help_active()
+    {
+        order.push(RenderPass::Overlay);
+    }
+    order
+}
+
+fn config_highlights(config: &Config) -> Vec<(String, String)> {
+    let on_off = |enabled: bool| if enabled { "on" } else { "off" }.to_string();
+    vec![
+        (
+            "profile".to_string(),
+            active_profile_name().unwrap_or_else(|| "none".to_string()),
+        ),
+        ("model".to_string(), config.model.clone()),
+        ("endpoint".to_string(), config.api_url.clone()),
+        (
+            "sampling".to_string(),
+            if config.sampling.is_empty() {
+                "provider defaults".to_string()
+            } else {
+                config.sampling.describe()
+            },
+        ),
+        (
+            "workspace".to_string(),
+            config.working_dir.display().to_string(),
+        ),
+        (
+            "patch output".to_string(),
+            config
+                .emit_patches_dir
+                .as_ref()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_else(|| "off".to_string()),
+        ),
+        (
+            "audit log".to_string(),
+            on_off(AuditLog::from_env(&config.working_dir).is_some()),
+        ),
+        (
+            "session backups".to_string(),
+            on_off(SessionAutosave::from_env(&config.working_dir).is_some()),
+        ),
+        (
+            "saved sessions".to_string(),
+            on_off(SessionStore::from_env(&config.working_dir).is_some()),
+        ),
+        (
+            "usage log".to_string(),
+            on_off(UsageLog::from_env(&config.working_dir).is_some()),
+        ),
+        (
+            "edit symbol context".to_string(),
+            on_off(symbol_context_enabled_from_env()),
+        ),
+        (
+            "fuzzy edit matching".to_string(),
+            on_off(fuzzy_edit_enabled_from_env()),
+        ),
+        (
+            "follow-up suggestions".to_string(),
+            on_off(follow_ups_enabled_from_env()),
+        ),
+        (
+            "duplicate result references".to_string(),
+            on_off(result_dedup_enabled_from_env()),
+        ),
+        (
+            "version control".to_string(),
+            Vcs::detect(&config.working_dir).label().to_string(),
+        ),
+        (
+            "diff context".to_string(),
+            DiffContext::from_env().describe(),
+        ),
+        (
+            "speech".to_string(),
+            SpeechTarget::from_env()
+                .map(|target| target.describe())
+                .unwrap_or_else(|| "off".to_string()),
+        ),
+    ]
+}
+
+/// The workspace's tool operator, configured from `config` and the
+/// environment the same way for the TUI and unattended runs.
+pub fn build_tool_operator(config: &Config) -> ToolOperator {
+    ToolOperator::new(config.working_dir.clone())
+        .with_patch_output(config.emit_patches_dir.clone())
+        .with_undo_snapshots(Vcs::detect(&config.working_dir) == Vcs::None)
+        .with_file_locks(file_locks_enabled_from_env())
+        .with_symbol_context(symbol_context_enabled_from_env())
+        .with_fuzzy_edit(fuzzy_edit_enabled_from_env())
+        .with_result_dedup(result_dedup_enabled_from_env())
+        .with_diff_context(DiffContext::from_env())
+}
+
+pub fn build_runtime(config: Config) -> Result<(Runtime<TuiMode>, RuntimeContext)> {
+    let client = ApiClient::new(&config)?.with_git_tools(git_repository_found(&config.working_dir));
+    let diff_context = DiffContext::from_env();
+    let operator = build_tool_operator(&config);
+    let custom_tool_errors = operator.custom_tools().errors.clone();
+    let conversation = ConversationManager::new(client, operator)
+        .with_audit_log(AuditLog::from_env(&config.working_dir));
+    let attachment_budget = conversation.tool_result_budget();
+    let file_uploads = conversation.client().files_api_enabled();
+
+    let (update_tx, update_rx) = mpsc::unbounded_channel::<UiUpdate>();
+    let mut ctx = RuntimeContext::new(conversation, update_tx, CancellationToken::new());
+    if let Some(autosave) = SessionAutosave::from_env(&config.working_dir) {
+        ctx = ctx.with_autosave(autosave);
+    }
+    if let Some(store) = SessionStore::from_env(&config.working_dir) {
+        ctx = ctx.with_session_store(store);
+    }
+    if let Some(usage_log) = UsageLog::from_env(&config.working_dir) {
+        ctx = ctx.with_usage_log(usage_log);
+    }
+    if let Some(notifier) = Notifier::from_env(&config.working_dir) {
+        ctx = ctx.with_notifier(notifier);
+    }
+    let hooks = LifecycleHooks::load(&config.working_dir);
+    if let Ok(Some(hooks)) = &hooks {
+        ctx = ctx.with_hooks(hooks.clone());
+    }
+
+    let mut mode = TuiMode::new()
+        .with_workspace_root(config.working_dir.clone())
+        .with_attachment_budget(attachment_budget)
+        .with_file_uploads(file_uploads)
+        .with_config_highlights(config_highlights(&config))
+        .with_diff_context(diff_context)
+        .with_diff_style(DiffStyle::from_env())
+        .with_release_check(ReleaseCheck::spawn_from_env())
+        .with_clipboard(ClipboardTarget::from_env())
+        .with_profile(
+            active_profile_name()
+                .map(|name| Profile::load(&name))
+                .transpose()?,
+        )
+        .with_bundle_metadata(BundleMetadata {
+            model: config.model.clone(),
+            endpoint: config.api_url.clone(),
+            sampling: config.sampling,
+        })
+        .with_api_protocol(config.protocol())
+        .with_startup_presets();
+    if let Some(target) = SpeechTarget::from_env() {
+        mode = mode.with_speaker(Speaker::spawn(target));
+    }
+    if let Some(backend) = active_backend() {
+        // Writes outside the workspace fail in the kernel, so prompts add
+        // nothing; sensitive paths inside it are still confirmed.
+        ctx.set_session_auto_approve(true);
+        mode.push_history_line(format!(
+            "[sandbox: {backend}; writes are confined to {}, tools run without approval]",
+            SandboxPolicy::for_workspace(&config.working_dir)
+                .writable
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    for error in custom_tool_errors {
+        mode.push_history_line(format!("[custom tool skipped] {error}"));
+    }
+    if let Err(error) = hooks {
+        mode.push_history_line(format!("[hooks disabled] {error:#}"));
+    }
+    if let Some(dir) = &config.emit_patches_dir {
+        mode.push_history_line(format!(
+            "[patch output] file changes are written as patches to {} (apply with git apply)",
+            dir.display()
+        ));
+    }
+    let runtime = Runtime::new(mode, update_rx);
+    Ok((runtime, ctx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{mock_client::MockApiClient, ApiClient};
+    use crate::ui::editor::{InputAction, InputEditor};
+    use crossterm::event::KeyEvent;
+    use futures::FutureExt;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn setup_ctx() -> RuntimeContext {
+        let (tx, _rx) = mpsc::unbounded_channel::<UiUpdate>();
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(vec![])));
+        let conversation = ConversationManager::new_mock(client, HashMap::new());
+        RuntimeContext::new(conversation, tx, CancellationToken::new())
+    }
+
+    #[tokio::test]
+    async fn test_ref_03_tui_mode_overlay_blocks_input() {
+        let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+
+        let (response_tx, _rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
+        mode.on_model_update(
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name: "read_file".to_string(),
+                input_preview: "{}".to_string(),
+                tier: ApprovalTier::Standard,
+                response_tx,
+                ..ToolApprovalRequest::test_stub()
+            }),
+            &mut ctx,
+        );
+
+        mode.on_user_input("blocked".to_string(), &mut ctx);
+        assert!(
+            !mode.history_state.turn_in_progress,
+            "overlay must block input dispatch"
+        );
+
+        mode.on_user_input("1".to_string(), &mut ctx);
+        assert!(
+            !mode.overlay_active(),
+            "overlay should clear after decision"
+        );
+
+        mode.on_user_input("resume".to_string(), &mut ctx);
+        assert!(
+            mode.history_state.turn_in_progress,
+            "dispatch should resume after overlay clears"
+        );
+    }
+
+    #[test]
+    fn overlay_blocks_submit() {
+        let overlay_none = overlay_event_to_user_input(Event::Key(KeyEvent::new(
+            KeyCode::Enter,
+            KeyModifiers::NONE,
+        )));
+        assert!(
+            overlay_none.is_none(),
+            "overlay keymap must not route Enter as normal submit"
+        );
+
+        match overlay_event_to_user_input(Event::Key(KeyEvent::new(
+            KeyCode::Char('1'),
+            KeyModifiers::NONE,
+        ))) {
+            Some(UserInputEvent::Text(value)) => assert_eq!(value, "1"),
+            _ => panic!("overlay key '1' must route to modal action"),
+        }
+
+        match overlay_event_to_user_input(Event::Key(KeyEvent::new(
+            KeyCode::Esc,
+            KeyModifiers::NONE,
+        ))) {
+            Some(UserInputEvent::Text(value)) => assert_eq!(value, "esc"),
+            _ => panic!("overlay Esc must route to modal deny action"),
+        }
+    }
+
+    #[test]
+    fn test_ref_08_stream_delta_appends_to_assistant_placeholder_not_user_line() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+        mode.on_user_input("hello".to_string(), &mut ctx);
+        mode.on_model_update(UiUpdate::StreamDelta("assistant".to_string()), &mut ctx);
+
+        assert_eq!(mode.history_state.lines[0], "> hello");
+        assert_eq!(mode.history_state.lines[1], "assistant");
+    }
+
+    #[test]
+    fn test_think_turn_notes_the_pinned_sampling_it_drops() {
+        let mut mode = TuiMode::new().with_bundle_metadata(BundleMetadata {
+            sampling: Sampling {
+                temperature: Some(0.0),
+                top_p: None,
+                seed: Some(7),
+            },
+            ..BundleMetadata::default()
+        });
+        let mut ctx = setup_ctx();
+        mode.on_user_input("/think 2048 plan it".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_state.lines[1],
+            "[turn overrides: thinking budget 2048; pinned temperature 0 dropped: thinking]"
+        );
+
+        let mut mode = mode.with_api_protocol(ApiProtocol::OpenAiChatCompletions);
+        mode.history_state.turn_in_progress = false;
+        mode.on_user_input("/think 2048 again".to_string(), &mut ctx);
+        assert!(mode
+            .history_state
+            .lines
+            .contains(&"[turn overrides: thinking budget 2048]".to_string()));
+    }
+
+    #[test]
+    fn test_stream_delta_strips_tagged_tool_markup_from_history() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+        mode.on_user_input("show diff".to_string(), &mut ctx);
+        mode.on_model_update(
+            UiUpdate::StreamDelta(
+                "I will check.\n<function=git_diff>\n</function>\nDone.".to_string(),
+            ),
+            &mut ctx,
+        );
+
+        assert_eq!(mode.history_state.lines[1], "I will check.\n\nDone.");
+        assert!(!mode.history_state.lines[1].contains("<function="));
+    }
+
+    #[test]
+    fn test_stream_delta_hides_incomplete_tool_tag_suffix() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+        mode.on_user_input("status".to_string(), &mut ctx);
+        mode.on_model_update(
+            UiUpdate::StreamDelta("Checking\n<function=git_status".to_string()),
+            &mut ctx,
+        );
+
+        assert_eq!(mode.history_state.lines[1], "Checking\n");
+    }
+
+    #[test]
+    fn test_transcript_does_not_exceed_cap_after_n_turns() {
+        let _env_lock = crate::test_support::ENV_LOCK.blocking_lock();
+        std::env::set_var(MAX_HISTORY_LINES_ENV, "10");
+
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+        assert_eq!(mode.history_line_cap, 10);
+
+        for i in 0..20 {
+            mode.on_user_input(format!("user-{i}"), &mut ctx);
+            assert!(
+                mode.history_state.lines.len() <= 10,
+                "history must be capped after on_user_input"
+            );
+            if let Some(idx) = mode.history_state.active_assistant_index {
+                assert!(
+                    idx < mode.history_state.lines.len(),
+                    "active assistant index must remain valid after cap enforcement"
+                );
+            }
+
+            mode.on_model_update(UiUpdate::StreamDelta(format!("assistant-{i}")), &mut ctx);
+            assert!(
+                mode.history_state.lines.len() <= 10,
+                "history must be capped after stream update"
+            );
+            if let Some(idx) = mode.history_state.active_assistant_index {
+                assert!(
+                    idx < mode.history_state.lines.len(),
+                    "active assistant index must remain valid during streaming"
+                );
+            }
+
+            mode.on_model_update(UiUpdate::TurnComplete, &mut ctx);
+            assert!(
+                mode.history_state.lines.len() <= 10,
+                "history must stay capped after turn completion"
+            );
+        }
+
+        std::env::remove_var(MAX_HISTORY_LINES_ENV);
+    }
+
+    #[test]
+    fn test_scrolling_past_the_top_pages_spilled_lines_back_in() {
+        let mut mode = TuiMode::new();
+        mode.history_line_cap = 10;
+        mode.history_content_width.set(80);
+        for index in 0..30 {
+            mode.push_history_line(format!("line {index}"));
+        }
+        assert_eq!(mode.history_state.lines[0], "line 20");
+        assert_eq!(mode.transcript_spill.len(), 20);
+
+        mode.apply_home();
+        mode.apply_page_up(3);
+        assert_eq!(mode.history_state.lines.len(), 15);
+        assert_eq!(mode.history_state.lines[0], "line 15");
+        assert_eq!(mode.history_state.scroll_offset, 2);
+        assert_eq!(mode.history_lines_dropped(), 15);
+
+        mode.push_history_line("line 30".to_string());
+        assert_eq!(mode.history_state.lines[0], "line 16");
+
+        mode.apply_end();
+        assert_eq!(mode.history_state.lines.len(), 10);
+        assert_eq!(mode.history_state.lines[0], "line 21");
+        assert_eq!(mode.transcript_spill.len(), 21);
+    }
+
+    #[test]
+    fn test_history_cap_env_invalid_uses_default() {
+        let _env_lock = crate::test_support::ENV_LOCK.blocking_lock();
+        std::env::set_var(MAX_HISTORY_LINES_ENV, "invalid-cap");
+
+        let mode = TuiMode::new();
+        assert_eq!(mode.history_line_cap, DEFAULT_MAX_HISTORY_LINES);
+
+        std::env::remove_var(MAX_HISTORY_LINES_ENV);
+    }
+
+    #[test]
+    fn test_scrollback_retains_position_during_streaming() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+
+        mode.history_state.lines = (0..20).map(|i| format!("line-{i}")).collect();
+        mode.history_state.active_assistant_index = Some(10);
+        mode.history_state.scroll_offset = 5;
+        mode.history_state.auto_follow = false;
+
+        mode.on_model_update(UiUpdate::StreamDelta(" assistant".to_string()), &mut ctx);
+
+        assert_eq!(
+            mode.history_state.scroll_offset, 5,
+            "scrollback position must not be forced while auto-follow is disabled"
+        );
+    }
+
+    #[test]
+    fn test_scrollback_commands_update_scroll_state() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+
+        mode.history_state.lines = (0..100).map(|i| format!("line-{i}")).collect();
+        mode.history_state.scroll_offset = 80;
+        mode.history_state.auto_follow = true;
+
+        mode.on_frontend_event(
+            UserInputEvent::Scroll {
+                target: ScrollTarget::History,
+                action: ScrollAction::PageUp(10),
+            },
+            &mut ctx,
+        );
+        assert_eq!(mode.history_state.scroll_offset, 70);
+        assert!(!mode.history_state.auto_follow);
+
+        mode.on_frontend_event(
+            UserInputEvent::Scroll {
+                target: ScrollTarget::History,
+                action: ScrollAction::PageDown(200),
+            },
+            &mut ctx,
+        );
+        assert_eq!(mode.history_state.scroll_offset, 99);
+        assert!(mode.history_state.auto_follow);
+
+        mode.on_frontend_event(
+            UserInputEvent::Scroll {
+                target: ScrollTarget::History,
+                action: ScrollAction::Home,
+            },
+            &mut ctx,
+        );
+        assert_eq!(mode.history_state.scroll_offset, 0);
+        assert!(!mode.history_state.auto_follow);
+
+        mode.on_frontend_event(
+            UserInputEvent::Scroll {
+                target: ScrollTarget::History,
+                action: ScrollAction::End,
+            },
+            &mut ctx,
+        );
+        assert_eq!(mode.history_state.scroll_offset, 99);
+        assert!(mode.history_state.auto_follow);
+        assert!(
+            !mode.history_state.turn_in_progress,
+            "scroll commands must not dispatch new turns"
+        );
+    }
+
+    #[test]
+    fn test_history_status_and_scroll_use_visual_rows() {
+        let mode = TuiMode {
+            history_state: HistoryState {
+                lines: vec!["a\nb\nc".to_string()],
+                ..HistoryState::default()
+            },
+            ..TuiMode::new()
+        };
+
+        assert_eq!(mode.max_scroll_offset(), 2);
+        assert!(mode.status_line().contains("history:3"));
+    }
+
+    #[tokio::test]
+    async fn test_rebase_assist_runs_git_off_the_ui_thread() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new().with_workspace_root(temp.path().to_path_buf());
+
+        mode.on_user_input("/rebase-assist main".to_string(), &mut ctx);
+        assert!(mode.rebase_job.is_some());
+        mode.on_user_input("hello".to_string(), &mut ctx);
+        assert!(!ctx.test_turn_running());
+        assert!(mode
+            .history_state
+            .lines
+            .last()
+            .is_some_and(|line| line.contains("git is still running")));
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !mode.poll_background(&mut ctx) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(mode.rebase_job.is_none());
+        assert!(mode
+            .history_state
+            .lines
+            .last()
+            .is_some_and(|line| line.starts_with("[rebase-assist] not a git repository")));
+    }
+
+    #[test]
+    fn test_idle_interrupt_shows_feedback() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+
+        assert!(!mode.history_state.turn_in_progress);
+        assert!(!mode.pending_quit);
+        assert!(!mode.quit_requested);
+
+        mode.on_interrupt(&mut ctx);
+        assert!(mode.pending_quit, "first idle interrupt must arm quit");
+        assert!(!mode.quit_requested, "first idle interrupt must not quit");
+        assert!(
+            mode.history_state
+                .lines
+                .iter()
+                .any(|line| line.contains("[press Ctrl+C again to exit]")),
+            "first idle interrupt must show user-visible feedback"
+        );
+
+        mode.on_interrupt(&mut ctx);
+        assert!(
+            mode.quit_requested,
+            "second idle interrupt must request quit"
+        );
+        assert!(
+            mode.quit_requested(),
+            "frontend quit path must observe mode quit request"
+        );
+    }
+
+    #[test]
+    fn test_history_picker_filters_reruns_and_loads_editor() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+        mode.on_user_input("/history".to_string(), &mut ctx);
+        assert!(!mode.history_picker_active());
+        assert!(mode
+            .history_lines()
+            .iter()
+            .any(|line| line == "[no prompts in history yet]"));
+
+        mode.on_user_input("fix the parser".to_string(), &mut ctx);
+        mode.on_model_update(UiUpdate::Error("boom".to_string()), &mut ctx);
+        mode.on_user_input("run tests".to_string(), &mut ctx);
+        mode.on_model_update(UiUpdate::TurnComplete, &mut ctx);
+
+        mode.on_user_input("/history".to_string(), &mut ctx);
+        let (_, _, rows, selected) = mode.history_picker_overlay().expect("picker open");
+        assert_eq!(selected, 0);
+        assert_eq!(rows[0].text, "run tests");
+        assert_eq!(rows[0].outcome, PromptOutcome::Ok);
+        assert_eq!(rows[1].outcome, PromptOutcome::Error);
+
+        for key in ["/", "p", "r", "s", "enter"] {
+            mode.on_user_input(key.to_string(), &mut ctx);
+        }
+        let (query, filtering, rows, _) = mode.history_picker_overlay().expect("still open");
+        assert_eq!((query, filtering, rows.len()), ("prs", false, 1));
+        mode.on_user_input("e".to_string(), &mut ctx);
+        assert!(!mode.history_picker_active());
+        assert_eq!(
+            mode.take_editor_prefill().as_deref(),
+            Some("fix the parser")
+        );
+        assert_eq!(mode.take_editor_prefill(), None);
+
+        mode.on_user_input("/history".to_string(), &mut ctx);
+        mode.on_frontend_event(
+            UserInputEvent::Scroll {
+                target: ScrollTarget::Overlay,
+                action: ScrollAction::LineDown,
+            },
+            &mut ctx,
+        );
+        mode.on_user_input("enter".to_string(), &mut ctx);
+        assert!(!mode.history_picker_active());
+        assert!(mode.history_state.turn_in_progress);
+        assert_eq!(mode.prompt_history.entries().len(), 3);
+        assert_eq!(mode.prompt_history.entries()[2].text, "fix the parser");
+    }
+
+    #[test]
+    fn test_changes_command_lists_turn_changes_from_the_session_index() {
+        let temp = tempfile::TempDir::new().expect("tempdir");
+        std::fs::write(
+            temp.path().join("abc-1.json"),
+            r#"{"version":1,"id":"abc-1","saved_at":10,"turn_count":2,"messages":[{"role":"user","content":"add a parser"}],"turn_changes":[{"turn":2,"files":[{"path":"src/parser.rs","kind":"created","added":3,"removed":0}],"commands":["run_tests"]}]}"#,
+        )
+        .expect("write session");
+        let mut ctx = setup_ctx().with_session_store(SessionStore::new(temp.path().to_path_buf()));
+        let mut mode = TuiMode::new();
+
+        mode.on_user_input("/changes".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_lines().last().map(String::as_str),
+            Some("[no file changes or commands in this session]")
+        );
+        for command in ["/changes 1", "/changes abc"] {
+            mode.on_user_input(command.to_string(), &mut ctx);
+            assert_eq!(
+                mode.history_lines().last().map(String::as_str),
+                Some(
+                    "[changes in session abc-1, oldest first]\n[turn 2: 1 file (+3 -0), 1 command]\n  created  src/parser.rs  +3 -0\n  ran      run_tests"
+                )
+            );
+        }
+        assert!(temp.path().join("index.json").is_file());
+        mode.on_user_input("/changes 5".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_lines().last().map(String::as_str),
+            Some("[error] no saved session 5")
+        );
+    }
+
+    #[test]
+    fn test_sessions_picker_and_resume_filter_by_tag() {
+        let temp = tempfile::TempDir::new().expect("tempdir");
+        for (id, saved_at, prompt, tags) in [
+            (
+                "abc-1",
+                10,
+                "fix the lexer",
+                r#"[{"turn":1,"tag":"bug-hunt","prompt":""}]"#,
+            ),
+            ("abc-2", 20, "design the api", "[]"),
+        ] {
+            std::fs::write(
+                temp.path().join(format!("{id}.json")),
+                format!(
+                    r#"{{"version":1,"id":"{id}","saved_at":{saved_at},"turn_count":1,"messages":[{{"role":"user","content":"{prompt}"}}],"turn_tags":{tags}}}"#
+                ),
+            )
+            .expect("write session");
+        }
+        let mut ctx = setup_ctx().with_session_store(SessionStore::new(temp.path().to_path_buf()));
+        let mut mode = TuiMode::new();
+
+        mode.on_user_input("/resume #bug".to_string(), &mut ctx);
+        let listing = mode.history_lines().last().cloned().unwrap_or_default();
+        assert!(listing.contains("  2. abc-1"), "{listing}");
+        assert!(listing.contains("fix the lexer  #bug-hunt"), "{listing}");
+        assert!(!listing.contains("abc-2"), "{listing}");
+
+        mode.on_user_input("/sessions bug".to_string(), &mut ctx);
+        let (tag, _, rows, _) = mode.session_picker_overlay().expect("picker open");
+        assert_eq!((tag, rows.len()), ("bug", 1));
+        for key in ["backspace", "backspace", "backspace"] {
+            mode.on_user_input(key.to_string(), &mut ctx);
+        }
+        let (_, _, rows, _) = mode.session_picker_overlay().expect("still open");
+        assert_eq!(rows.len(), 2);
+        for key in ["/", "b", "u", "enter"] {
+            mode.on_user_input(key.to_string(), &mut ctx);
+        }
+        let (tag, filtering, rows, _) = mode.session_picker_overlay().expect("still open");
+        assert_eq!((tag, filtering, rows.len()), ("bu", false, 1));
+
+        mode.on_user_input("enter".to_string(), &mut ctx);
+        assert!(!mode.session_picker_active());
+        assert!(mode
+            .history_lines()
+            .iter()
+            .any(|line| line.starts_with("[resumed session abc-1 (1 turns)]")));
+    }
+
+    #[test]
+    fn test_busy_input_is_queued_with_feedback() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+
+        mode.history_state.turn_in_progress = true;
+        mode.on_user_input("hello".to_string(), &mut ctx);
+
+        assert!(
+            mode.history_state.turn_in_progress,
+            "busy input must not start a new turn"
+        );
+        assert!(
+            mode.history_state
+                .lines
+                .iter()
+                .any(|line| line.starts_with("[queued")),
+            "busy input must produce visible queue feedback"
+        );
+        assert_eq!(mode.queued_messages, ["hello"]);
+        assert!(
+            !mode
+                .history_state
+                .lines
+                .iter()
+                .any(|line| line == "> hello"),
+            "queued input must not be appended as user message until sent"
+        );
+    }
+
+    #[test]
+    fn test_pending_quit_resets_on_new_turn_accept() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+
+        mode.on_interrupt(&mut ctx);
+        assert!(mode.pending_quit);
+
+        mode.on_user_input("resume".to_string(), &mut ctx);
+        assert!(
+            !mode.pending_quit,
+            "pending quit must reset when a new turn is accepted"
+        );
+        assert!(!mode.quit_requested);
+        assert!(mode.history_state.turn_in_progress);
+    }
+
+    #[test]
+    fn overlay_renders_after_base_panes() {
+        let mode = TuiMode::new();
+        assert_eq!(
+            render_pass_order(&mode),
+            vec![RenderPass::Header, RenderPass::History, RenderPass::Input]
+        );
+
+        let mut overlay_mode = TuiMode::new();
+        let (response_tx, _response_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
+        overlay_mode.overlay_state.pending_approval = Some(PendingApproval {
+            tool_name: "read_file".to_string(),
+            input_preview: "{\"path\":\"Cargo.toml\"}".to_string(),
+            class: ToolClass::ReadOnly,
+            paths: Vec::new(),
+            tier: ApprovalTier::Standard,
+            response_tx,
+            reason_entry: false,
+        });
+        assert_eq!(
+            render_pass_order(&overlay_mode),
+            vec![
+                RenderPass::Header,
+                RenderPass::History,
+                RenderPass::Input,
+                RenderPass::Overlay,
+            ],
+            "overlay must always render last"
+        );
+    }
+
+    #[test]
+    fn test_render_not_called_when_state_unchanged() {
+        let start = Instant::now();
+        let mut guard = RenderGuard::with_intervals(
+            Duration::from_millis(500),
+            Duration::from_millis(120),
+            start,
+        );
+
+        assert!(
+            guard.should_draw(start, 11),
+            "first render should draw because the guard starts dirty"
+        );
+        assert!(
+            !guard.should_draw(start + Duration::from_millis(20), 11),
+            "unchanged state before tick interval must not draw"
+        );
+        assert!(
+            !guard.should_draw(start + Duration::from_millis(100), 11),
+            "unchanged state still below tick interval must not draw"
+        );
+        assert!(
+            guard.should_draw(start + Duration::from_millis(121), 11),
+            "unchanged state should draw when tick interval elapses"
+        );
+        assert!(
+            guard.should_draw(start + Duration::from_millis(122), 12),
+            "changed state should mark dirty and draw immediately"
+        );
+    }
+
+    #[test]
+    fn test_render_guard_poll_timeout_uses_min_tick_interval() {
+        let guard = RenderGuard::with_intervals(
+            Duration::from_millis(500),
+            Duration::from_millis(120),
+            Instant::now(),
+        );
+        assert_eq!(guard.poll_timeout(), Duration::from_millis(120));
+    }
+
+    #[test]
+    fn header_stable_during_streaming() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+
+        let ready_status = mode.status_line();
+        assert!(
+            ready_status.contains("mode:ready"),
+            "ready state must publish mode token"
+        );
+        assert!(
+            ready_status.contains("approval:none"),
+            "ready state must publish approval token"
+        );
+        assert!(
+            ready_status.contains("history:0"),
+            "ready state must publish history count"
+        );
+        assert!(
+            ready_status.contains("repo:"),
+            "ready state must publish repo token"
+        );
+        assert_eq!(
+            render_pass_order(&mode).first(),
+            Some(&RenderPass::Header),
+            "header row must remain first in render order"
+        );
+
+        mode.on_user_input("hello".to_string(), &mut ctx);
+        mode.on_model_update(UiUpdate::StreamDelta("assistant".to_string()), &mut ctx);
+        let streaming_status = mode.status_line();
+        assert!(
+            streaming_status.contains("mode:streaming"),
+            "streaming state must publish mode token"
+        );
+        assert!(
+            streaming_status.contains("approval:none"),
+            "streaming state must preserve approval token"
+        );
+        assert!(
+            streaming_status.contains("history:2"),
+            "streaming state must keep compact history count"
+        );
+        assert_eq!(
+            render_pass_order(&mode).first(),
+            Some(&RenderPass::Header),
+            "header row must remain first while streaming"
+        );
+
+        let (response_tx, _response_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
+        mode.on_model_update(
             UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
                 tool_name: "read_file".to_string(),
                 input_preview: "{}".to_string(),
+                tier: ApprovalTier::Standard,
                 response_tx,
+                ..ToolApprovalRequest::test_stub()
             }),
             &mut ctx,
         );
+        let overlay_status = mode.status_line();
+        assert!(
+            overlay_status.contains("mode:overlay"),
+            "overlay state must publish overlay mode token"
+        );
+        assert!(
+            overlay_status.contains("approval:pending"),
+            "overlay state must publish pending approval token"
+        );
+        assert_eq!(
+            render_pass_order(&mode).first(),
+            Some(&RenderPass::Header),
+            "header row must remain first under overlay"
+        );
+    }
+
+    #[test]
+    fn multiline_submit_outside_overlay_only() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+        let mut editor = InputEditor::new();
+
+        editor.apply_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+        editor.apply_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::SHIFT));
+        editor.apply_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE));
+        editor.apply_key(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL));
+        editor.apply_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE));
+
+        let submitted = match editor.apply_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)) {
+            InputAction::Submit(value) => value,
+            _ => panic!("enter outside overlay must submit multiline buffer"),
+        };
+        assert_eq!(submitted, "a\nb\nc");
+
+        mode.on_user_input(submitted.clone(), &mut ctx);
+        assert!(
+            mode.history_state.turn_in_progress,
+            "outside overlay, enter must submit and start a turn"
+        );
+        assert!(
+            mode.history_state
+                .lines
+                .iter()
+                .any(|line| line == "> a\nb\nc"),
+            "submitted multiline prompt should be recorded in history"
+        );
+
+        mode.history_state.turn_in_progress = false;
+        mode.history_state.active_assistant_index = None;
+        let (response_tx, _response_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
+        mode.overlay_state.pending_approval = Some(PendingApproval {
+            tool_name: "read_file".to_string(),
+            input_preview: "{}".to_string(),
+            class: ToolClass::ReadOnly,
+            paths: Vec::new(),
+            tier: ApprovalTier::Standard,
+            response_tx,
+            reason_entry: false,
+        });
+
+        let overlay_enter = overlay_event_to_user_input(Event::Key(KeyEvent::new(
+            KeyCode::Enter,
+            KeyModifiers::NONE,
+        )));
+        assert!(
+            overlay_enter.is_none(),
+            "enter in overlay keymap must not route to submit"
+        );
+
+        mode.on_user_input("overlay\nattempt".to_string(), &mut ctx);
+        assert!(
+            mode.overlay_active(),
+            "overlay should remain active after non-decision input"
+        );
+        assert!(
+            !mode
+                .history_state
+                .lines
+                .iter()
+                .any(|line| line == "> overlay\nattempt"),
+            "overlay-focused input must not submit as a user prompt"
+        );
+    }
+
+    #[test]
+    fn history_stable_during_overlay() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+        let mut editor = InputEditor::new();
+
+        editor.input_state.buffer = "first".to_string();
+        let _ = editor.submit();
+        editor.input_state.buffer = "second".to_string();
+        let _ = editor.submit();
+        editor.input_state.buffer = "draft".to_string();
+        editor.input_state.cursor = editor.input_state.buffer.len();
+
+        editor.history_up();
+        let before_overlay_buffer = editor.input_state.buffer.clone();
+        let before_overlay_index = editor.input_state.history_index;
+        let before_overlay_history_len = editor.input_state.history.len();
+
+        let (response_tx, _response_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
+        mode.overlay_state.pending_approval = Some(PendingApproval {
+            tool_name: "read_file".to_string(),
+            input_preview: "{}".to_string(),
+            class: ToolClass::ReadOnly,
+            paths: Vec::new(),
+            tier: ApprovalTier::Standard,
+            response_tx,
+            reason_entry: false,
+        });
+        assert!(mode.overlay_active());
+
+        let up =
+            overlay_event_to_user_input(Event::Key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE)));
+        let down = overlay_event_to_user_input(Event::Key(KeyEvent::new(
+            KeyCode::Down,
+            KeyModifiers::NONE,
+        )));
+        assert!(
+            up.is_none(),
+            "overlay keymap must consume history navigation"
+        );
+        assert!(
+            down.is_none(),
+            "overlay keymap must consume history navigation"
+        );
+
+        assert_eq!(editor.input_state.buffer, before_overlay_buffer);
+        assert_eq!(editor.input_state.history_index, before_overlay_index);
+        assert_eq!(editor.input_state.history.len(), before_overlay_history_len);
+
+        mode.on_user_input("1".to_string(), &mut ctx);
+        assert!(!mode.overlay_active(), "overlay should clear on decision");
+
+        editor.history_down();
+        assert_eq!(editor.input_state.history_index, None);
+        assert_eq!(
+            editor.input_state.buffer, "draft",
+            "prompt draft must restore after overlay transition"
+        );
+    }
+
+    #[tokio::test]
+    async fn diff_overlay_scrolls() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+
+        let patch_preview = [
+            "@@ -1,3 +1,4".to_string(),
+            " context line".to_string(),
+            "-old value".to_string(),
+            "+new value".to_string(),
+            " context tail".to_string(),
+            "-removed again".to_string(),
+            "+added again".to_string(),
+        ]
+        .join("\n");
+
+        let (approve_tx, approve_rx) = tokio::sync::oneshot::channel::<bool>();
+        mode.overlay_state.pending_patch_approval = Some(PendingPatchApproval {
+            patch_preview: patch_preview.clone(),
+            scroll_offset: 0,
+            response_tx: Some(approve_tx),
+        });
+
+        mode.on_frontend_event(
+            UserInputEvent::Scroll {
+                target: ScrollTarget::Overlay,
+                action: ScrollAction::LineDown,
+            },
+            &mut ctx,
+        );
+        assert_eq!(
+            mode.overlay_state
+                .pending_patch_approval
+                .as_ref()
+                .map(|p| p.scroll_offset),
+            Some(1),
+            "down must advance diff overlay scroll"
+        );
 
-        mode.on_user_input("blocked".to_string(), &mut ctx);
-        assert!(
-            !mode.history_state.turn_in_progress,
-            "overlay must block input dispatch"
+        mode.on_frontend_event(
+            UserInputEvent::Scroll {
+                target: ScrollTarget::Overlay,
+                action: ScrollAction::PageDown(3),
+            },
+            &mut ctx,
+        );
+        assert_eq!(
+            mode.overlay_state
+                .pending_patch_approval
+                .as_ref()
+                .map(|p| p.scroll_offset),
+            Some(4),
+            "page down must advance by requested step"
+        );
+
+        mode.on_frontend_event(
+            UserInputEvent::Scroll {
+                target: ScrollTarget::Overlay,
+                action: ScrollAction::End,
+            },
+            &mut ctx,
+        );
+        assert_eq!(
+            mode.overlay_state
+                .pending_patch_approval
+                .as_ref()
+                .map(|p| p.scroll_offset),
+            Some(patch_preview.lines().count().saturating_sub(1)),
+            "end must jump to last diff line"
         );
 
         mode.on_user_input("1".to_string(), &mut ctx);
         assert!(
-            !mode.overlay_active(),
-            "overlay should clear after decision"
+            approve_rx.await.expect("patch approval should resolve"),
+            "approve binding must resolve true"
+        );
+        assert!(
+            !mode.patch_overlay_active(),
+            "overlay must clear after approve decision"
         );
 
-        mode.on_user_input("resume".to_string(), &mut ctx);
+        let (deny_tx, deny_rx) = tokio::sync::oneshot::channel::<bool>();
+        mode.overlay_state.pending_patch_approval = Some(PendingPatchApproval {
+            patch_preview,
+            scroll_offset: 2,
+            response_tx: Some(deny_tx),
+        });
+        mode.on_user_input("n".to_string(), &mut ctx);
         assert!(
-            mode.history_state.turn_in_progress,
-            "dispatch should resume after overlay clears"
+            !deny_rx.await.expect("patch denial should resolve"),
+            "deny binding must resolve false"
+        );
+        assert!(
+            !mode.patch_overlay_active(),
+            "overlay must clear after deny decision"
         );
     }
 
     #[test]
-    fn overlay_blocks_submit() {
-        let overlay_none = overlay_event_to_user_input(Event::Key(KeyEvent::new(
-            KeyCode::Enter,
-            KeyModifiers::NONE,
-        )));
-        assert!(
-            overlay_none.is_none(),
-            "overlay keymap must not route Enter as normal submit"
+    fn input_pane_expands_then_clamps_to_max_rows() {
+        assert_eq!(input_rows_for_buffer("", 80), 1);
+
+        let multiline = (0..12)
+            .map(|idx| format!("line-{idx}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            input_rows_for_buffer(&multiline, 80),
+            MAX_INPUT_PANE_ROWS as u16
         );
+    }
 
-        match overlay_event_to_user_input(Event::Key(KeyEvent::new(
-            KeyCode::Char('1'),
-            KeyModifiers::NONE,
-        ))) {
-            Some(UserInputEvent::Text(value)) => assert_eq!(value, "1"),
-            _ => panic!("overlay key '1' must route to modal action"),
-        }
+    #[test]
+    fn test_editor_cursor_navigation() {
+        let mut editor = InputEditor::new();
+        editor.apply_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+        editor.apply_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE));
+        editor.apply_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE));
+        editor.apply_key(KeyEvent::new(KeyCode::Left, KeyModifiers::NONE));
+        editor.apply_key(KeyEvent::new(KeyCode::Left, KeyModifiers::NONE));
+        editor.apply_key(KeyEvent::new(KeyCode::Char('X'), KeyModifiers::NONE));
+        assert_eq!(editor.input_state.buffer, "aXbc");
+    }
 
-        match overlay_event_to_user_input(Event::Key(KeyEvent::new(
-            KeyCode::Esc,
-            KeyModifiers::NONE,
-        ))) {
-            Some(UserInputEvent::Text(value)) => assert_eq!(value, "esc"),
-            _ => panic!("overlay Esc must route to modal deny action"),
-        }
+    #[test]
+    fn test_editor_history_up_down() {
+        let mut editor = InputEditor::new();
+        editor.input_state.buffer = "first".to_string();
+        let _ = editor.submit();
+        editor.input_state.buffer = "second".to_string();
+        let _ = editor.submit();
+
+        editor.apply_key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
+        assert_eq!(editor.input_state.buffer, "second");
+        editor.apply_key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
+        assert_eq!(editor.input_state.buffer, "first");
+        editor.apply_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        assert_eq!(editor.input_state.buffer, "second");
     }
 
     #[test]
-    fn test_ref_08_stream_delta_appends_to_assistant_placeholder_not_user_line() {
-        let mut mode = TuiMode::new();
-        let mut ctx = setup_ctx();
-        mode.on_user_input("hello".to_string(), &mut ctx);
-        mode.on_model_update(UiUpdate::StreamDelta("assistant".to_string()), &mut ctx);
+    fn test_editor_history_stash_restore() {
+        let mut editor = InputEditor::new();
 
-        assert_eq!(mode.history_state.lines[0], "> hello");
-        assert_eq!(mode.history_state.lines[1], "assistant");
+        editor.input_state.buffer = "first".to_string();
+        let _ = editor.submit();
+        editor.input_state.buffer = "second".to_string();
+        let _ = editor.submit();
+
+        editor.input_state.buffer = "draft".to_string();
+        editor.input_state.cursor = editor.input_state.buffer.len();
+
+        editor.history_up();
+        assert_eq!(editor.input_state.buffer, "second");
+        assert_eq!(editor.input_state.history_index, Some(1));
+
+        editor.history_down();
+        assert_eq!(editor.input_state.history_index, None);
+        assert_eq!(editor.input_state.buffer, "draft");
+        assert_eq!(editor.input_state.cursor, "draft".len());
     }
 
     #[test]
-    fn test_stream_delta_strips_tagged_tool_markup_from_history() {
+    fn test_editor_multiline_shortcuts() {
+        let mut editor = InputEditor::new();
+        editor.apply_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+        editor.apply_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::SHIFT));
+        editor.apply_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE));
+        editor.apply_key(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL));
+        editor.apply_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE));
+        assert_eq!(editor.input_state.buffer, "a\nb\nc");
+    }
+
+    #[test]
+    fn test_editor_undo_redo() {
+        let mut editor = InputEditor::new();
+        editor.apply_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+        editor.apply_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE));
+        editor.apply_key(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
+        assert_eq!(editor.input_state.buffer, "a");
+        editor.apply_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::CONTROL));
+        assert_eq!(editor.input_state.buffer, "ab");
+    }
+
+    #[test]
+    fn test_editor_paste_handling() {
+        let mut editor = InputEditor::new();
+        let _ = editor.apply_event(Event::Paste("hello".to_string()));
+        assert_eq!(editor.input_state.buffer, "hello");
+    }
+
+    #[test]
+    fn test_input_editor_unicode_cursor_backspace_delete_safe() {
+        let mut editor = InputEditor::new();
+        editor.insert_str("a😀b");
+        editor.input_state.cursor = editor.input_state.buffer.len();
+        editor.backspace();
+        assert_eq!(editor.input_state.buffer, "a😀");
+        editor.backspace();
+        assert_eq!(editor.input_state.buffer, "a");
+
+        editor.insert_str("😀b");
+        editor.input_state.cursor = 2; // intentionally non-boundary (inside 😀 codepoint)
+        editor.delete();
+        assert_eq!(editor.input_state.buffer, "ab");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_approval_input_keeps_overlay_active_with_feedback() {
+        let mut ctx = setup_ctx();
         let mut mode = TuiMode::new();
+        let (response_tx, _response_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
+
+        mode.on_model_update(
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name: "read_file".to_string(),
+                input_preview: "{}".to_string(),
+                tier: ApprovalTier::Standard,
+                response_tx,
+                ..ToolApprovalRequest::test_stub()
+            }),
+            &mut ctx,
+        );
+
+        mode.on_user_input("x".to_string(), &mut ctx);
+        assert!(
+            mode.overlay_active(),
+            "overlay should stay active on invalid input"
+        );
+        assert!(
+            mode.history_state
+                .lines
+                .iter()
+                .any(|line| line.contains("[invalid selection, expected 1/2/3/4]")),
+            "expected invalid selection feedback line"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_is_typed_event_not_magic_string_collision() {
         let mut ctx = setup_ctx();
-        mode.on_user_input("show diff".to_string(), &mut ctx);
-        mode.on_model_update(
-            UiUpdate::StreamDelta(
-                "I will check.\n<function=git_diff>\n</function>\nDone.".to_string(),
-            ),
-            &mut ctx,
+        let mut mode = TuiMode::new();
+
+        mode.on_user_input("__VEX_INTERRUPT__".to_string(), &mut ctx);
+        assert!(
+            mode.history_state.turn_in_progress,
+            "plain text matching old sentinel must be treated as normal user input"
         );
 
-        assert_eq!(mode.history_state.lines[1], "I will check.\n\nDone.");
-        assert!(!mode.history_state.lines[1].contains("<function="));
+        mode.on_interrupt(&mut ctx);
+        assert!(
+            mode.history_state.turn_in_progress,
+            "typed interrupt should keep turn active until TurnComplete drains"
+        );
+        assert!(
+            mode.history_state.cancel_pending,
+            "typed interrupt should arm cancel-pending state"
+        );
+        assert!(
+            mode.history_state
+                .lines
+                .iter()
+                .any(|line| line.contains("[turn cancellation requested]")),
+            "cancel path should provide visible feedback"
+        );
+
+        mode.on_model_update(UiUpdate::TurnComplete, &mut ctx);
+        assert!(!mode.history_state.turn_in_progress);
+        assert!(!mode.history_state.cancel_pending);
     }
 
     #[test]
-    fn test_stream_delta_hides_incomplete_tool_tag_suffix() {
+    fn test_stream_delta_ignored_without_active_turn_slot() {
         let mut mode = TuiMode::new();
         let mut ctx = setup_ctx();
-        mode.on_user_input("status".to_string(), &mut ctx);
-        mode.on_model_update(
-            UiUpdate::StreamDelta("Checking\n<function=git_status".to_string()),
-            &mut ctx,
+        mode.on_model_update(UiUpdate::StreamDelta("ghost delta".to_string()), &mut ctx);
+        assert!(
+            mode.history_state.lines.is_empty(),
+            "stale stream deltas must be ignored after turn completion/cancel"
         );
-
-        assert_eq!(mode.history_state.lines[1], "Checking\n");
     }
 
     #[test]
-    fn test_transcript_does_not_exceed_cap_after_n_turns() {
-        let _env_lock = crate::test_support::ENV_LOCK.blocking_lock();
-        std::env::set_var(MAX_HISTORY_LINES_ENV, "10");
-
+    fn test_turn_separators_mark_prompt_and_response() {
         let mut mode = TuiMode::new();
+        mode.set_turn_separators(SeparatorStyle::Ascii);
         let mut ctx = setup_ctx();
-        assert_eq!(mode.history_line_cap, 10);
-
-        for i in 0..20 {
-            mode.on_user_input(format!("user-{i}"), &mut ctx);
-            assert!(
-                mode.history_state.lines.len() <= 10,
-                "history must be capped after on_user_input"
-            );
-            if let Some(idx) = mode.history_state.active_assistant_index {
-                assert!(
-                    idx < mode.history_state.lines.len(),
-                    "active assistant index must remain valid after cap enforcement"
-                );
-            }
-
-            mode.on_model_update(UiUpdate::StreamDelta(format!("assistant-{i}")), &mut ctx);
-            assert!(
-                mode.history_state.lines.len() <= 10,
-                "history must be capped after stream update"
-            );
-            if let Some(idx) = mode.history_state.active_assistant_index {
-                assert!(
-                    idx < mode.history_state.lines.len(),
-                    "active assistant index must remain valid during streaming"
-                );
-            }
-
-            mode.on_model_update(UiUpdate::TurnComplete, &mut ctx);
-            assert!(
-                mode.history_state.lines.len() <= 10,
-                "history must stay capped after turn completion"
-            );
-        }
-
-        std::env::remove_var(MAX_HISTORY_LINES_ENV);
+        mode.on_user_input("hello".to_string(), &mut ctx);
+        mode.on_model_update(UiUpdate::StreamDelta("Hi".to_string()), &mut ctx);
+        mode.on_model_update(UiUpdate::StreamDelta(" there".to_string()), &mut ctx);
+
+        let lines = mode.history_lines();
+        assert_eq!(lines.len(), 4, "{lines:?}");
+        assert!(lines[0].starts_with("---- turn 1 | you | "));
+        assert_eq!(lines[1], "> hello");
+        assert!(lines[2].starts_with("---- turn 1 | vex | "));
+        assert!(lines[2].ends_with(" UTC ----"));
+        assert_eq!(lines[3], "Hi there");
     }
 
     #[test]
-    fn test_history_cap_env_invalid_uses_default() {
-        let _env_lock = crate::test_support::ENV_LOCK.blocking_lock();
-        std::env::set_var(MAX_HISTORY_LINES_ENV, "invalid-cap");
-
-        let mode = TuiMode::new();
-        assert_eq!(mode.history_line_cap, DEFAULT_MAX_HISTORY_LINES);
+    fn test_typewriter_releases_text_per_frame_and_flushes_before_completion() {
+        let mut mode = TuiMode::new().with_typewriter(Some(Typewriter::new(100)));
+        let mut ctx = setup_ctx();
+        mode.on_user_input("hello".to_string(), &mut ctx);
+        mode.on_model_update(UiUpdate::StreamDelta("Hello there".to_string()), &mut ctx);
+        let last = |mode: &TuiMode| mode.history_lines().last().cloned().unwrap_or_default();
+        assert_eq!(last(&mode), "", "queued until the next frame");
+        let start = Instant::now();
+        assert!(mode.on_frame(start));
+        assert_eq!(last(&mode), "H");
+        assert!(!mode.on_frame(start + Duration::from_millis(5)));
+        assert!(mode.on_frame(start + Duration::from_millis(40)));
+        assert_eq!(last(&mode), "Hello");
 
-        std::env::remove_var(MAX_HISTORY_LINES_ENV);
+        mode.on_model_update(UiUpdate::TurnComplete, &mut ctx);
+        assert_eq!(last(&mode), "Hello there");
+        assert!(!mode.on_frame(start + Duration::from_secs(1)));
     }
 
     #[test]
-    fn test_scrollback_retains_position_during_streaming() {
+    fn test_cancel_pending_blocks_stream_delta_appends() {
         let mut mode = TuiMode::new();
         let mut ctx = setup_ctx();
-
-        mode.history_state.lines = (0..20).map(|i| format!("line-{i}")).collect();
-        mode.history_state.active_assistant_index = Some(10);
-        mode.history_state.scroll_offset = 5;
-        mode.history_state.auto_follow = false;
-
-        mode.on_model_update(UiUpdate::StreamDelta(" assistant".to_string()), &mut ctx);
-
-        assert_eq!(
-            mode.history_state.scroll_offset, 5,
-            "scrollback position must not be forced while auto-follow is disabled"
-        );
+        mode.on_user_input("hello".to_string(), &mut ctx);
+        mode.on_interrupt(&mut ctx);
+        mode.on_model_update(UiUpdate::StreamDelta("stale".to_string()), &mut ctx);
+        assert_eq!(mode.history_state.lines[0], "> hello");
+        assert_eq!(mode.history_state.lines[1], "");
     }
 
-    #[test]
-    fn test_scrollback_commands_update_scroll_state() {
-        let mut mode = TuiMode::new();
+    #[tokio::test]
+    async fn test_tool_approval_accept_once() {
         let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
 
-        mode.history_state.lines = (0..100).map(|i| format!("line-{i}")).collect();
-        mode.history_state.scroll_offset = 80;
-        mode.history_state.auto_follow = true;
-
-        mode.on_frontend_event(
-            UserInputEvent::Scroll {
-                target: ScrollTarget::History,
-                action: ScrollAction::PageUp(10),
-            },
+        mode.on_model_update(
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name: "read_file".to_string(),
+                input_preview: "{}".to_string(),
+                tier: ApprovalTier::Standard,
+                response_tx,
+                ..ToolApprovalRequest::test_stub()
+            }),
             &mut ctx,
         );
-        assert_eq!(mode.history_state.scroll_offset, 70);
-        assert!(!mode.history_state.auto_follow);
+        mode.on_user_input("1".to_string(), &mut ctx);
 
-        mode.on_frontend_event(
-            UserInputEvent::Scroll {
-                target: ScrollTarget::History,
-                action: ScrollAction::PageDown(200),
-            },
-            &mut ctx,
-        );
-        assert_eq!(mode.history_state.scroll_offset, 99);
-        assert!(mode.history_state.auto_follow);
+        assert!(response_rx
+            .await
+            .expect("response should resolve")
+            .is_approved());
+    }
 
-        mode.on_frontend_event(
-            UserInputEvent::Scroll {
-                target: ScrollTarget::History,
-                action: ScrollAction::Home,
-            },
-            &mut ctx,
-        );
-        assert_eq!(mode.history_state.scroll_offset, 0);
-        assert!(!mode.history_state.auto_follow);
+    #[tokio::test]
+    async fn test_tool_approval_always_enables_conversation_auto_approve() {
+        let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
 
-        mode.on_frontend_event(
-            UserInputEvent::Scroll {
-                target: ScrollTarget::History,
-                action: ScrollAction::End,
-            },
+        assert!(!ctx.session_auto_approve());
+        mode.on_model_update(
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name: "write_file".to_string(),
+                input_preview: "{}".to_string(),
+                tier: ApprovalTier::Standard,
+                response_tx,
+                ..ToolApprovalRequest::test_stub()
+            }),
             &mut ctx,
         );
-        assert_eq!(mode.history_state.scroll_offset, 99);
-        assert!(mode.history_state.auto_follow);
-        assert!(
-            !mode.history_state.turn_in_progress,
-            "scroll commands must not dispatch new turns"
-        );
-    }
-
-    #[test]
-    fn test_history_status_and_scroll_use_visual_rows() {
-        let mode = TuiMode {
-            history_state: HistoryState {
-                lines: vec!["a\nb\nc".to_string()],
-                ..HistoryState::default()
-            },
-            ..TuiMode::new()
-        };
+        mode.on_user_input("2".to_string(), &mut ctx);
 
-        assert_eq!(mode.max_scroll_offset(), 2);
-        assert!(mode.status_line().contains("history:3"));
+        assert!(response_rx
+            .await
+            .expect("response should resolve")
+            .is_approved());
+        assert!(ctx.session_auto_approve());
     }
 
-    #[test]
-    fn test_idle_interrupt_shows_feedback() {
-        let mut mode = TuiMode::new();
+    #[tokio::test]
+    async fn test_sensitive_tier_bypasses_frontend_auto_approve() {
         let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+        mode.overlay_state.auto_approve_session = true;
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
 
-        assert!(!mode.history_state.turn_in_progress);
-        assert!(!mode.pending_quit);
-        assert!(!mode.quit_requested);
-
-        mode.on_interrupt(&mut ctx);
-        assert!(mode.pending_quit, "first idle interrupt must arm quit");
-        assert!(!mode.quit_requested, "first idle interrupt must not quit");
-        assert!(
-            mode.history_state
-                .lines
-                .iter()
-                .any(|line| line.contains("[press Ctrl+C again to exit]")),
-            "first idle interrupt must show user-visible feedback"
+        mode.on_model_update(
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name: "write_file".to_string(),
+                input_preview: "path: Cargo.lock".to_string(),
+                tier: ApprovalTier::Sensitive {
+                    path: "Cargo.lock".to_string(),
+                    pattern: "*.lock".to_string(),
+                },
+                response_tx,
+                ..ToolApprovalRequest::test_stub()
+            }),
+            &mut ctx,
         );
 
-        mode.on_interrupt(&mut ctx);
-        assert!(
-            mode.quit_requested,
-            "second idle interrupt must request quit"
-        );
-        assert!(
-            mode.quit_requested(),
-            "frontend quit path must observe mode quit request"
-        );
+        let overlay = mode.pending_tool_overlay().expect("sensitive prompt opens");
+        assert!(overlay.tier.is_sensitive());
+        assert!(mode
+            .history_state
+            .lines
+            .iter()
+            .any(|line| line.contains("sensitive file Cargo.lock (matches *.lock)")));
+        mode.on_user_input("1".to_string(), &mut ctx);
+        assert!(response_rx.await.expect("response").is_approved());
     }
 
-    #[test]
-    fn test_input_drop_shows_feedback() {
-        let mut mode = TuiMode::new();
+    #[tokio::test]
+    async fn test_queued_tool_calls_listed_until_they_finish() {
         let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+        let tool_call = |id: &str, path: &str, status| StreamBlock::ToolCall {
+            id: id.to_string(),
+            name: "write_file".to_string(),
+            input: serde_json::json!({"path": path, "content": "x"}),
+            status,
+        };
 
-        mode.history_state.turn_in_progress = true;
-        mode.on_user_input("hello".to_string(), &mut ctx);
+        for (index, block) in [
+            tool_call("a", "a.txt", ToolStatus::WaitingApproval),
+            tool_call("b", "b.txt", ToolStatus::Pending),
+            tool_call("c", "c.txt", ToolStatus::Pending),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            mode.on_model_update(UiUpdate::StreamBlockStart { index, block }, &mut ctx);
+            mode.on_model_update(UiUpdate::StreamBlockComplete { index }, &mut ctx);
+        }
 
-        assert!(
-            mode.history_state.turn_in_progress,
-            "busy input must not start a new turn"
-        );
-        assert!(
-            mode.history_state
-                .lines
-                .iter()
-                .any(|line| line.starts_with("[busy")),
-            "busy input must produce visible rejection feedback"
-        );
-        assert!(
-            !mode
-                .history_state
-                .lines
-                .iter()
-                .any(|line| line == "> hello"),
-            "discarded busy input must not be appended as user message"
+        let queued = mode.queued_tool_calls();
+        assert_eq!(queued.len(), 2);
+        assert!(queued[0].contains("b.txt"));
+        assert!(queued[1].contains("c.txt"));
+
+        mode.on_model_update(
+            UiUpdate::StreamBlockStart {
+                index: 1,
+                block: tool_call("b", "b.txt", ToolStatus::Cancelled),
+            },
+            &mut ctx,
         );
+        assert_eq!(mode.queued_tool_calls().len(), 1);
     }
 
-    #[test]
-    fn test_pending_quit_resets_on_new_turn_accept() {
-        let mut mode = TuiMode::new();
+    #[tokio::test]
+    async fn test_footer_widgets_show_turn_time_approvals_and_queue() {
         let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+        let now = Instant::now();
+        assert_eq!(mode.footer_widgets(now), "");
 
-        mode.on_interrupt(&mut ctx);
-        assert!(mode.pending_quit);
-
-        mode.on_user_input("resume".to_string(), &mut ctx);
-        assert!(
-            !mode.pending_quit,
-            "pending quit must reset when a new turn is accepted"
+        mode.history_state.turn_in_progress = true;
+        mode.history_state.turn_started_at = Some(now - Duration::from_millis(125_250));
+        for (index, path) in ["a.txt", "b.txt"].into_iter().enumerate() {
+            let block = StreamBlock::ToolCall {
+                id: path.to_string(),
+                name: "write_file".to_string(),
+                input: serde_json::json!({"path": path, "content": "x"}),
+                status: ToolStatus::Pending,
+            };
+            mode.on_model_update(UiUpdate::StreamBlockStart { index, block }, &mut ctx);
+        }
+        let (response_tx, _rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
+        mode.on_model_update(
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name: "write_file".to_string(),
+                input_preview: "path: a.txt".to_string(),
+                response_tx,
+                ..ToolApprovalRequest::test_stub()
+            }),
+            &mut ctx,
         );
-        assert!(!mode.quit_requested);
-        assert!(mode.history_state.turn_in_progress);
-    }
 
-    #[test]
-    fn overlay_renders_after_base_panes() {
-        let mode = TuiMode::new();
-        assert_eq!(
-            render_pass_order(&mode),
-            vec![RenderPass::Header, RenderPass::History, RenderPass::Input]
-        );
+        assert_eq!(mode.footer_widgets(now), "⠹ 2m 05s | 1 approval");
 
-        let mut overlay_mode = TuiMode::new();
-        let (response_tx, _response_rx) = tokio::sync::oneshot::channel::<bool>();
-        overlay_mode.overlay_state.pending_approval = Some(PendingApproval {
-            tool_name: "read_file".to_string(),
-            input_preview: "{\"path\":\"Cargo.toml\"}".to_string(),
-            response_tx,
-        });
+        mode.on_user_input("3".to_string(), &mut ctx);
+        mode.on_user_input("then run the tests".to_string(), &mut ctx);
+        mode.on_user_input("and commit".to_string(), &mut ctx);
         assert_eq!(
-            render_pass_order(&overlay_mode),
-            vec![
-                RenderPass::Header,
-                RenderPass::History,
-                RenderPass::Input,
-                RenderPass::Overlay,
-            ],
-            "overlay must always render last"
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[queued - sends when this turn ends (2 waiting)]")
         );
-    }
+        assert_eq!(mode.footer_widgets(now), "⠹ 2m 05s | 2 queued");
 
-    #[test]
-    fn test_render_not_called_when_state_unchanged() {
-        let start = Instant::now();
-        let mut guard = RenderGuard::with_intervals(
-            Duration::from_millis(500),
-            Duration::from_millis(120),
-            start,
+        mode.on_model_update(UiUpdate::TurnComplete, &mut ctx);
+        assert!(mode.history_state.turn_in_progress, "next message sent");
+        assert!(mode
+            .history_state
+            .lines
+            .iter()
+            .any(|line| line == "> then run the tests"));
+        assert_eq!(
+            mode.footer_widgets(now).split(" | ").nth(1),
+            Some("1 queued")
         );
 
-        assert!(
-            guard.should_draw(start, 11),
-            "first render should draw because the guard starts dirty"
-        );
-        assert!(
-            !guard.should_draw(start + Duration::from_millis(20), 11),
-            "unchanged state before tick interval must not draw"
-        );
-        assert!(
-            !guard.should_draw(start + Duration::from_millis(100), 11),
-            "unchanged state still below tick interval must not draw"
-        );
-        assert!(
-            guard.should_draw(start + Duration::from_millis(121), 11),
-            "unchanged state should draw when tick interval elapses"
-        );
-        assert!(
-            guard.should_draw(start + Duration::from_millis(122), 12),
-            "changed state should mark dirty and draw immediately"
-        );
+        mode.on_user_input("/cancel".to_string(), &mut ctx);
+        assert!(mode.queued_messages.is_empty());
+        assert!(mode
+            .history_state
+            .lines
+            .iter()
+            .any(|line| line == "[dropped 1 queued message]"));
     }
 
-    #[test]
-    fn test_render_guard_poll_timeout_uses_min_tick_interval() {
-        let guard = RenderGuard::with_intervals(
-            Duration::from_millis(500),
-            Duration::from_millis(120),
-            Instant::now(),
+    #[tokio::test]
+    async fn test_tree_results_are_drawn_in_the_transcript() {
+        let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+        let output = "./ (2 files, 20 B)\n├── src/ (1 file, 10 B)\n└── a.md (10 B)";
+        let blocks = [
+            StreamBlock::ToolCall {
+                id: "t1".to_string(),
+                name: "tree".to_string(),
+                input: serde_json::json!({}),
+                status: ToolStatus::Complete,
+            },
+            StreamBlock::ToolResult {
+                tool_call_id: "t1".to_string(),
+                output: output.to_string(),
+                is_error: false,
+            },
+        ];
+        for (index, block) in blocks.into_iter().enumerate() {
+            mode.on_model_update(UiUpdate::StreamBlockStart { index, block }, &mut ctx);
+        }
+        let lines = mode.history_lines();
+        assert_eq!(
+            &lines[lines.len() - 3..],
+            [
+                "  ./ (2 files, 20 B)",
+                "  ├── src/ (1 file, 10 B)",
+                "  └── a.md (10 B)",
+            ]
         );
-        assert_eq!(guard.poll_timeout(), Duration::from_millis(120));
     }
 
-    #[test]
-    fn header_stable_during_streaming() {
-        let mut mode = TuiMode::new();
+    #[tokio::test]
+    async fn test_copy_command_copies_tool_results_and_edit_diffs() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        std::fs::create_dir_all(temp.path().join("src")).expect("dirs");
+        std::fs::write(
+            temp.path().join("src/a.rs"),
+            "use std::fs;\n\nfn a() -> u8 { 1 }\n",
+        )
+        .expect("edited file");
         let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new().with_workspace_root(temp.path().to_path_buf());
+        mode.on_user_input("/copy".to_string(), &mut ctx);
+        assert!(mode
+            .history_lines()
+            .last()
+            .is_some_and(|line| line.starts_with("[nothing to copy yet")));
+
+        let blocks = [
+            StreamBlock::ToolCall {
+                id: "t1".to_string(),
+                name: "read_file".to_string(),
+                input: serde_json::json!({"path": "src/a.rs"}),
+                status: ToolStatus::Complete,
+            },
+            StreamBlock::ToolResult {
+                tool_call_id: "t1".to_string(),
+                output: "fn a() {}\n".to_string(),
+                is_error: false,
+            },
+            StreamBlock::ToolCall {
+                id: "t2".to_string(),
+                name: "edit_file".to_string(),
+                input: serde_json::json!({
+                    "path": "src/a.rs",
+                    "old_str": "fn a() {}",
+                    "new_str": "fn a() -> u8 { 1 }"
+                }),
+                status: ToolStatus::Complete,
+            },
+        ];
+        for (index, block) in blocks.into_iter().enumerate() {
+            mode.on_model_update(UiUpdate::StreamBlockStart { index, block }, &mut ctx);
+        }
 
-        let ready_status = mode.status_line();
-        assert!(
-            ready_status.contains("mode:ready"),
-            "ready state must publish mode token"
-        );
-        assert!(
-            ready_status.contains("approval:none"),
-            "ready state must publish approval token"
-        );
-        assert!(
-            ready_status.contains("history:0"),
-            "ready state must publish history count"
-        );
-        assert!(
-            ready_status.contains("repo:"),
-            "ready state must publish repo token"
+        mode.on_user_input("/copy result 1".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_lines().last().map(String::as_str),
+            Some("[copied result 1: read_file src/a.rs (1 lines)]")
         );
         assert_eq!(
-            render_pass_order(&mode).first(),
-            Some(&RenderPass::Header),
-            "header row must remain first in render order"
+            mode.take_clipboard_escape(),
+            Some(osc52_sequence("fn a() {}\n"))
         );
 
-        mode.on_user_input("hello".to_string(), &mut ctx);
-        mode.on_model_update(UiUpdate::StreamDelta("assistant".to_string()), &mut ctx);
-        let streaming_status = mode.status_line();
-        assert!(
-            streaming_status.contains("mode:streaming"),
-            "streaming state must publish mode token"
-        );
-        assert!(
-            streaming_status.contains("approval:none"),
-            "streaming state must preserve approval token"
-        );
-        assert!(
-            streaming_status.contains("history:2"),
-            "streaming state must keep compact history count"
-        );
+        mode.on_user_input("/copy diff last".to_string(), &mut ctx);
+        let escape = mode.take_clipboard_escape().expect("diff copied");
+        assert!(escape.starts_with("\x1b]52;c;"));
+        let diff = mode
+            .registers
+            .get(RegisterKind::Diff, 1)
+            .expect("diff kept");
+        assert!(diff
+            .text
+            .starts_with("diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n"));
+        assert!(diff
+            .text
+            .contains("@@ -1,3 +1,3 @@\n use std::fs;\n \n-fn a() {}\n+fn a() -> u8 { 1 }\n"));
+        let patch = temp.path().join("edit.patch");
+        std::fs::write(&patch, &diff.text).expect("patch");
+        let reverses = std::process::Command::new("git")
+            .current_dir(temp.path())
+            .args(["apply", "--check", "-R", "edit.patch"])
+            .output();
+        if let Ok(output) = reverses {
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        mode.on_user_input("/copy result 5".to_string(), &mut ctx);
         assert_eq!(
-            render_pass_order(&mode).first(),
-            Some(&RenderPass::Header),
-            "header row must remain first while streaming"
+            mode.history_lines().last().map(String::as_str),
+            Some("[error] no result 5 to copy")
         );
+    }
 
-        let (response_tx, _response_rx) = tokio::sync::oneshot::channel::<bool>();
+    #[tokio::test]
+    async fn test_streaming_edit_input_shows_a_live_preview() {
+        let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+        mode.push_history_line("before".to_string());
         mode.on_model_update(
-            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
-                tool_name: "read_file".to_string(),
-                input_preview: "{}".to_string(),
-                response_tx,
-            }),
+            UiUpdate::StreamBlockStart {
+                index: 0,
+                block: StreamBlock::ToolCall {
+                    id: "toolu_1".to_string(),
+                    name: "edit_file".to_string(),
+                    input: serde_json::json!({}),
+                    status: ToolStatus::Pending,
+                },
+            },
             &mut ctx,
         );
-        let overlay_status = mode.status_line();
-        assert!(
-            overlay_status.contains("mode:overlay"),
-            "overlay state must publish overlay mode token"
-        );
-        assert!(
-            overlay_status.contains("approval:pending"),
-            "overlay state must publish pending approval token"
-        );
+        for delta in [
+            r#"{"path":"src/a.rs","#,
+            r#""old_str":"let x = 1;","new_str":"let x"#,
+            r#" = 2;\nlet y"#,
+        ] {
+            mode.on_model_update(
+                UiUpdate::StreamBlockDelta {
+                    index: 0,
+                    delta: delta.to_string(),
+                },
+                &mut ctx,
+            );
+        }
         assert_eq!(
-            render_pass_order(&mode).first(),
-            Some(&RenderPass::Header),
-            "header row must remain first under overlay"
+            mode.history_lines(),
+            [
+                "before",
+                "[streaming edit_file] src/a.rs",
+                "  - let x = 1;",
+                "  + let x = 2;",
+                "  + let y",
+            ]
         );
+
+        mode.on_model_update(UiUpdate::StreamBlockComplete { index: 0 }, &mut ctx);
+        assert_eq!(mode.history_lines(), ["before"]);
     }
 
-    #[test]
-    fn multiline_submit_outside_overlay_only() {
-        let mut mode = TuiMode::new();
+    #[tokio::test]
+    async fn test_tool_approval_deny() {
         let mut ctx = setup_ctx();
-        let mut editor = InputEditor::new();
-
-        editor.apply_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
-        editor.apply_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::SHIFT));
-        editor.apply_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE));
-        editor.apply_key(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL));
-        editor.apply_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE));
-
-        let submitted = match editor.apply_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)) {
-            InputAction::Submit(value) => value,
-            _ => panic!("enter outside overlay must submit multiline buffer"),
-        };
-        assert_eq!(submitted, "a\nb\nc");
-
-        mode.on_user_input(submitted.clone(), &mut ctx);
-        assert!(
-            mode.history_state.turn_in_progress,
-            "outside overlay, enter must submit and start a turn"
-        );
-        assert!(
-            mode.history_state
-                .lines
-                .iter()
-                .any(|line| line == "> a\nb\nc"),
-            "submitted multiline prompt should be recorded in history"
-        );
-
-        mode.history_state.turn_in_progress = false;
-        mode.history_state.active_assistant_index = None;
-        let (response_tx, _response_rx) = tokio::sync::oneshot::channel::<bool>();
-        mode.overlay_state.pending_approval = Some(PendingApproval {
-            tool_name: "read_file".to_string(),
-            input_preview: "{}".to_string(),
-            response_tx,
-        });
+        let mut mode = TuiMode::new();
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
 
-        let overlay_enter = overlay_event_to_user_input(Event::Key(KeyEvent::new(
-            KeyCode::Enter,
-            KeyModifiers::NONE,
-        )));
-        assert!(
-            overlay_enter.is_none(),
-            "enter in overlay keymap must not route to submit"
+        mode.on_model_update(
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name: "read_file".to_string(),
+                input_preview: "{}".to_string(),
+                tier: ApprovalTier::Standard,
+                response_tx,
+                ..ToolApprovalRequest::test_stub()
+            }),
+            &mut ctx,
         );
+        mode.on_user_input("n".to_string(), &mut ctx);
 
-        mode.on_user_input("overlay\nattempt".to_string(), &mut ctx);
-        assert!(
-            mode.overlay_active(),
-            "overlay should remain active after non-decision input"
-        );
-        assert!(
-            !mode
-                .history_state
-                .lines
-                .iter()
-                .any(|line| line == "> overlay\nattempt"),
-            "overlay-focused input must not submit as a user prompt"
-        );
+        assert!(!response_rx
+            .await
+            .expect("response should resolve")
+            .is_approved());
     }
 
-    #[test]
-    fn history_stable_during_overlay() {
-        let mut mode = TuiMode::new();
+    #[tokio::test]
+    async fn approval_sender_resolved_exactly_once() {
         let mut ctx = setup_ctx();
-        let mut editor = InputEditor::new();
+        let mut mode = TuiMode::new();
 
-        editor.input_state.buffer = "first".to_string();
-        let _ = editor.submit();
-        editor.input_state.buffer = "second".to_string();
-        let _ = editor.submit();
-        editor.input_state.buffer = "draft".to_string();
-        editor.input_state.cursor = editor.input_state.buffer.len();
+        let (first_tx, first_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
+        mode.on_model_update(
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name: "read_file".to_string(),
+                input_preview: "first".to_string(),
+                tier: ApprovalTier::Standard,
+                response_tx: first_tx,
+                ..ToolApprovalRequest::test_stub()
+            }),
+            &mut ctx,
+        );
 
-        editor.history_up();
-        let before_overlay_buffer = editor.input_state.buffer.clone();
-        let before_overlay_index = editor.input_state.history_index;
-        let before_overlay_history_len = editor.input_state.history.len();
+        let mut first_rx = Box::pin(first_rx);
+        assert!(
+            first_rx.as_mut().now_or_never().is_none(),
+            "first approval sender must remain unresolved while overlay is active"
+        );
 
-        let (response_tx, _response_rx) = tokio::sync::oneshot::channel::<bool>();
-        mode.overlay_state.pending_approval = Some(PendingApproval {
-            tool_name: "read_file".to_string(),
-            input_preview: "{}".to_string(),
-            response_tx,
-        });
-        assert!(mode.overlay_active());
+        let (second_tx, second_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
+        mode.on_model_update(
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name: "write_file".to_string(),
+                input_preview: "second".to_string(),
+                tier: ApprovalTier::Standard,
+                response_tx: second_tx,
+                ..ToolApprovalRequest::test_stub()
+            }),
+            &mut ctx,
+        );
 
-        let up =
-            overlay_event_to_user_input(Event::Key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE)));
-        let down = overlay_event_to_user_input(Event::Key(KeyEvent::new(
-            KeyCode::Down,
-            KeyModifiers::NONE,
-        )));
         assert!(
-            up.is_none(),
-            "overlay keymap must consume history navigation"
+            !first_rx
+                .await
+                .expect("first sender should resolve when replaced")
+                .is_approved(),
+            "replaced approval sender must resolve false exactly once"
         );
+
+        let mut second_rx = Box::pin(second_rx);
         assert!(
-            down.is_none(),
-            "overlay keymap must consume history navigation"
+            second_rx.as_mut().now_or_never().is_none(),
+            "second approval sender must remain unresolved before decision"
         );
 
-        assert_eq!(editor.input_state.buffer, before_overlay_buffer);
-        assert_eq!(editor.input_state.history_index, before_overlay_index);
-        assert_eq!(editor.input_state.history.len(), before_overlay_history_len);
-
         mode.on_user_input("1".to_string(), &mut ctx);
-        assert!(!mode.overlay_active(), "overlay should clear on decision");
+        assert!(
+            second_rx
+                .await
+                .expect("second sender should resolve on accept")
+                .is_approved(),
+            "approved overlay should resolve true exactly once"
+        );
 
-        editor.history_down();
-        assert_eq!(editor.input_state.history_index, None);
-        assert_eq!(
-            editor.input_state.buffer, "draft",
-            "prompt draft must restore after overlay transition"
+        mode.on_model_update(UiUpdate::TurnComplete, &mut ctx);
+        mode.on_model_update(UiUpdate::Error("post-resolution".to_string()), &mut ctx);
+        assert!(
+            !mode.overlay_active(),
+            "overlay lifecycle should clear cleanly after sender resolution"
         );
     }
 
     #[tokio::test]
-    async fn diff_overlay_scrolls() {
-        let mut mode = TuiMode::new();
+    async fn test_tool_approval_deny_with_inline_reason() {
         let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
 
-        let patch_preview = [
-            "@@ -1,3 +1,4".to_string(),
-            " context line".to_string(),
-            "-old value".to_string(),
-            "+new value".to_string(),
-            " context tail".to_string(),
-            "-removed again".to_string(),
-            "+added again".to_string(),
-        ]
-        .join("\n");
-
-        let (approve_tx, approve_rx) = tokio::sync::oneshot::channel::<bool>();
-        mode.overlay_state.pending_patch_approval = Some(PendingPatchApproval {
-            patch_preview: patch_preview.clone(),
-            scroll_offset: 0,
-            response_tx: Some(approve_tx),
-        });
-
-        mode.on_frontend_event(
-            UserInputEvent::Scroll {
-                target: ScrollTarget::Overlay,
-                action: ScrollAction::LineDown,
-            },
+        mode.on_model_update(
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name: "write_file".to_string(),
+                input_preview: "path: migrations/001.sql".to_string(),
+                tier: ApprovalTier::Standard,
+                response_tx,
+                ..ToolApprovalRequest::test_stub()
+            }),
             &mut ctx,
         );
+        mode.on_user_input("n leave migrations alone".to_string(), &mut ctx);
+
         assert_eq!(
-            mode.overlay_state
-                .pending_patch_approval
-                .as_ref()
-                .map(|p| p.scroll_offset),
-            Some(1),
-            "down must advance diff overlay scroll"
+            response_rx.await.expect("response should resolve"),
+            ToolApprovalDecision::denied_with_reason("leave migrations alone")
         );
+        assert!(!mode.overlay_active());
+    }
 
-        mode.on_frontend_event(
-            UserInputEvent::Scroll {
-                target: ScrollTarget::Overlay,
-                action: ScrollAction::PageDown(3),
-            },
+    #[tokio::test]
+    async fn test_user_question_takes_the_next_input_as_its_answer() {
+        let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel::<Option<String>>();
+
+        mode.history_state.turn_in_progress = true;
+        mode.on_model_update(
+            UiUpdate::UserQuestion(UserQuestionRequest {
+                tool_call_id: "toolu_1".to_string(),
+                question: "Which database?".to_string(),
+                options: vec!["sqlite".to_string(), "postgres".to_string()],
+                response_tx,
+            }),
             &mut ctx,
         );
+        assert!(mode
+            .history_lines()
+            .iter()
+            .any(|line| line == "  2. postgres"));
+        assert!(mode.footer_widgets(Instant::now()).contains("1 question"));
+
+        mode.on_user_input("2".to_string(), &mut ctx);
         assert_eq!(
-            mode.overlay_state
-                .pending_patch_approval
-                .as_ref()
-                .map(|p| p.scroll_offset),
-            Some(4),
-            "page down must advance by requested step"
+            response_rx.await.expect("answer should resolve"),
+            Some("postgres".to_string())
         );
+        assert!(mode.overlay_state.pending_question.is_none());
 
-        mode.on_frontend_event(
-            UserInputEvent::Scroll {
-                target: ScrollTarget::Overlay,
-                action: ScrollAction::End,
-            },
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel::<Option<String>>();
+        mode.on_model_update(
+            UiUpdate::UserQuestion(UserQuestionRequest {
+                tool_call_id: "toolu_2".to_string(),
+                question: "Anything else?".to_string(),
+                options: Vec::new(),
+                response_tx,
+            }),
             &mut ctx,
         );
-        assert_eq!(
-            mode.overlay_state
-                .pending_patch_approval
-                .as_ref()
-                .map(|p| p.scroll_offset),
-            Some(patch_preview.lines().count().saturating_sub(1)),
-            "end must jump to last diff line"
-        );
+        mode.on_user_input(SKIP_QUESTION_COMMAND.to_string(), &mut ctx);
+        assert_eq!(response_rx.await.expect("skip should resolve"), None);
 
-        mode.on_user_input("1".to_string(), &mut ctx);
-        assert!(
-            approve_rx.await.expect("patch approval should resolve"),
-            "approve binding must resolve true"
-        );
-        assert!(
-            !mode.patch_overlay_active(),
-            "overlay must clear after approve decision"
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel::<Option<String>>();
+        mode.on_model_update(
+            UiUpdate::UserQuestion(UserQuestionRequest {
+                tool_call_id: "toolu_3".to_string(),
+                question: "Which branch?".to_string(),
+                options: Vec::new(),
+                response_tx,
+            }),
+            &mut ctx,
         );
+        mode.on_user_input("/help".to_string(), &mut ctx);
+        assert!(mode.help_active());
+        assert!(mode.overlay_state.pending_question.is_some());
+        mode.on_user_input("esc".to_string(), &mut ctx);
+        assert!(!mode.help_active());
+        assert!(mode.overlay_state.pending_question.is_some());
+
+        mode.on_user_input("/cancel".to_string(), &mut ctx);
+        assert!(mode.history_state.cancel_pending);
+        assert!(mode.overlay_state.pending_question.is_none());
+        assert!(response_rx.await.is_err(), "cancel drops the question");
+    }
 
-        let (deny_tx, deny_rx) = tokio::sync::oneshot::channel::<bool>();
-        mode.overlay_state.pending_patch_approval = Some(PendingPatchApproval {
-            patch_preview,
-            scroll_offset: 2,
-            response_tx: Some(deny_tx),
-        });
-        mode.on_user_input("n".to_string(), &mut ctx);
-        assert!(
-            !deny_rx.await.expect("patch denial should resolve"),
-            "deny binding must resolve false"
+    #[tokio::test]
+    async fn test_tool_approval_reason_entry_mode_collects_free_text() {
+        let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel::<ToolApprovalDecision>();
+
+        mode.on_model_update(
+            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
+                tool_name: "write_file".to_string(),
+                input_preview: "{}".to_string(),
+                tier: ApprovalTier::Standard,
+                response_tx,
+                ..ToolApprovalRequest::test_stub()
+            }),
+            &mut ctx,
         );
-        assert!(
-            !mode.patch_overlay_active(),
-            "overlay must clear after deny decision"
+        mode.on_user_input("4".to_string(), &mut ctx);
+        assert!(mode.overlay_active(), "reason entry keeps the overlay open");
+        assert!(mode.approval_reason_entry_active());
+
+        mode.on_user_input("1 is not an approval here".to_string(), &mut ctx);
+        assert_eq!(
+            response_rx.await.expect("response should resolve"),
+            ToolApprovalDecision::denied_with_reason("1 is not an approval here")
         );
+        assert!(!mode.overlay_active());
     }
 
     #[test]
-    fn input_pane_expands_then_clamps_to_max_rows() {
-        assert_eq!(input_rows_for_buffer("", 80), 1);
+    fn test_tool_approval_answer_detection() {
+        for answer in [
+            "1",
+            "2",
+            "3",
+            "4",
+            "y",
+            "N",
+            "a",
+            "r",
+            "esc",
+            "n wrong file",
+        ] {
+            assert!(TuiMode::is_tool_approval_answer(answer), "{answer}");
+        }
+        for draft in ["x", "fix the tests", "", "5", "yolo"] {
+            assert!(!TuiMode::is_tool_approval_answer(draft), "{draft}");
+        }
+    }
 
-        let multiline = (0..12)
-            .map(|idx| format!("line-{idx}"))
-            .collect::<Vec<_>>()
-            .join("\n");
+    #[test]
+    fn test_cancel_command_parses_optional_reason() {
+        assert_eq!(parse_cancel_command("/cancel"), Some(""));
         assert_eq!(
-            input_rows_for_buffer(&multiline, 80),
-            MAX_INPUT_PANE_ROWS as u16
+            parse_cancel_command("  /cancel wrong branch "),
+            Some("wrong branch")
         );
+        assert_eq!(parse_cancel_command("/cancelled"), None);
+        assert_eq!(parse_cancel_command("cancel it"), None);
     }
 
     #[test]
-    fn test_editor_cursor_navigation() {
-        let mut editor = InputEditor::new();
-        editor.apply_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
-        editor.apply_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE));
-        editor.apply_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE));
-        editor.apply_key(KeyEvent::new(KeyCode::Left, KeyModifiers::NONE));
-        editor.apply_key(KeyEvent::new(KeyCode::Left, KeyModifiers::NONE));
-        editor.apply_key(KeyEvent::new(KeyCode::Char('X'), KeyModifiers::NONE));
-        assert_eq!(editor.input_state.buffer, "aXbc");
-    }
+    fn test_cancel_command_during_turn_requests_cancellation() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+        mode.history_state.turn_in_progress = true;
 
-    #[test]
-    fn test_editor_history_up_down() {
-        let mut editor = InputEditor::new();
-        editor.input_state.buffer = "first".to_string();
-        let _ = editor.submit();
-        editor.input_state.buffer = "second".to_string();
-        let _ = editor.submit();
+        mode.on_user_input("/cancel wrong file".to_string(), &mut ctx);
 
-        editor.apply_key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
-        assert_eq!(editor.input_state.buffer, "second");
-        editor.apply_key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
-        assert_eq!(editor.input_state.buffer, "first");
-        editor.apply_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
-        assert_eq!(editor.input_state.buffer, "second");
+        assert!(mode.history_state.cancel_pending);
+        assert!(mode
+            .history_state
+            .lines
+            .iter()
+            .any(|line| line == "[cancellation reason: wrong file]"));
     }
 
     #[test]
-    fn test_editor_history_stash_restore() {
-        let mut editor = InputEditor::new();
-
-        editor.input_state.buffer = "first".to_string();
-        let _ = editor.submit();
-        editor.input_state.buffer = "second".to_string();
-        let _ = editor.submit();
+    fn test_memories_command_lists_store_without_starting_turn() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        MemoryStore::for_workspace(temp.path())
+            .remember("Run cargo fmt before committing")
+            .expect("remember");
+        let mut mode = TuiMode::new().with_workspace_root(temp.path().to_path_buf());
+        let mut ctx = setup_ctx();
 
-        editor.input_state.buffer = "draft".to_string();
-        editor.input_state.cursor = editor.input_state.buffer.len();
+        mode.on_user_input("/memories".to_string(), &mut ctx);
 
-        editor.history_up();
-        assert_eq!(editor.input_state.buffer, "second");
-        assert_eq!(editor.input_state.history_index, Some(1));
+        assert!(!mode.history_state.turn_in_progress);
+        assert!(mode
+            .history_state
+            .lines
+            .iter()
+            .any(|line| line.contains("[#1] Run cargo fmt before committing")));
+
+        mode.on_user_input("/memories forget #1".to_string(), &mut ctx);
+        mode.on_user_input("/memories forget 1".to_string(), &mut ctx);
+        mode.on_user_input("/memories forget".to_string(), &mut ctx);
+        let lines = &mode.history_state.lines;
+        assert_eq!(
+            lines[lines.len() - 3..].to_vec(),
+            vec![
+                "[forgot memory #1: Run cargo fmt before committing]".to_string(),
+                "[no memory #1]".to_string(),
+                "[usage: /memories [forget <id>]]".to_string(),
+            ]
+        );
+        assert!(MemoryStore::for_workspace(temp.path())
+            .load()
+            .expect("load")
+            .is_empty());
+    }
 
-        editor.history_down();
-        assert_eq!(editor.input_state.history_index, None);
-        assert_eq!(editor.input_state.buffer, "draft");
-        assert_eq!(editor.input_state.cursor, "draft".len());
+    #[test]
+    fn test_every_registered_command_is_handled_locally() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        for command in crate::ui::help::SLASH_COMMANDS {
+            if command.name == "/cancel" {
+                // Only meaningful while a turn runs; covered by the /cancel tests.
+                continue;
+            }
+            let mut mode = TuiMode::new().with_workspace_root(temp.path().to_path_buf());
+            assert!(
+                mode.handle_local_command(command.name, &setup_ctx()),
+                "{} is listed in help but not handled",
+                command.name
+            );
+        }
     }
 
     #[test]
-    fn test_editor_multiline_shortcuts() {
-        let mut editor = InputEditor::new();
-        editor.apply_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
-        editor.apply_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::SHIFT));
-        editor.apply_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE));
-        editor.apply_key(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL));
-        editor.apply_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE));
-        assert_eq!(editor.input_state.buffer, "a\nb\nc");
+    fn test_invalid_turn_override_is_reported_without_starting_a_turn() {
+        let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+
+        mode.on_user_input("/temp 5 explain the parser".to_string(), &mut ctx);
+
+        assert!(!mode.history_state.turn_in_progress);
+        assert_eq!(
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[error] /temp must be between 0 and 2")
+        );
     }
 
     #[test]
-    fn test_editor_undo_redo() {
-        let mut editor = InputEditor::new();
-        editor.apply_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
-        editor.apply_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE));
-        editor.apply_key(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
-        assert_eq!(editor.input_state.buffer, "a");
-        editor.apply_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::CONTROL));
-        assert_eq!(editor.input_state.buffer, "ab");
+    fn test_diffstyle_command_switches_palette_and_toggles() {
+        let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+
+        mode.on_user_input("/diffstyle colorblind".to_string(), &mut ctx);
+        mode.on_user_input("/diffstyle glyphs on".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[diff style: colorblind, glyphs on, tint off]")
+        );
+        assert_eq!(mode.diff_style().palette, DiffPalette::Colorblind);
+        mode.on_user_input("/diffstyle sepia".to_string(), &mut ctx);
+        assert!(mode
+            .history_state
+            .lines
+            .last()
+            .is_some_and(|line| line.starts_with("[error] unknown diff palette `sepia`")));
     }
 
     #[test]
-    fn test_editor_paste_handling() {
-        let mut editor = InputEditor::new();
-        let _ = editor.apply_event(Event::Paste("hello".to_string()));
-        assert_eq!(editor.input_state.buffer, "hello");
+    fn test_release_notice_and_release_notes_command() {
+        let mut ctx = setup_ctx();
+        let release = crate::releases::Release {
+            tag_name: "v99.1.0".to_string(),
+            body: Some("- first change\n- second change".to_string()),
+            html_url: String::new(),
+            prerelease: false,
+            assets: Vec::new(),
+        };
+        let mut mode = TuiMode::new().with_release_check(ReleaseCheck::with_release(release));
+        assert!(mode.status_line().ends_with(" update:v99.1.0"));
+
+        mode.on_user_input("/release-notes".to_string(), &mut ctx);
+        let tail: Vec<&str> = mode
+            .history_state
+            .lines
+            .iter()
+            .rev()
+            .take(2)
+            .map(String::as_str)
+            .collect();
+        assert_eq!(tail, vec!["- second change", "- first change"]);
+        assert!(!TuiMode::new().status_line().contains("update:"));
     }
 
     #[test]
-    fn test_input_editor_unicode_cursor_backspace_delete_safe() {
-        let mut editor = InputEditor::new();
-        editor.insert_str("a😀b");
-        editor.input_state.cursor = editor.input_state.buffer.len();
-        editor.backspace();
-        assert_eq!(editor.input_state.buffer, "a😀");
-        editor.backspace();
-        assert_eq!(editor.input_state.buffer, "a");
-
-        editor.insert_str("😀b");
-        editor.input_state.cursor = 2; // intentionally non-boundary (inside 😀 codepoint)
-        editor.delete();
-        assert_eq!(editor.input_state.buffer, "ab");
+    fn test_context_command_prints_a_breakdown_of_the_next_request() {
+        let mut ctx = setup_ctx();
+        let mut mode = TuiMode::new();
+        mode.on_user_input("/context".to_string(), &mut ctx);
+
+        let lines = &mode.history_state.lines;
+        let start = lines
+            .iter()
+            .position(|line| line.starts_with("[context: ~"))
+            .expect("breakdown header");
+        assert!(lines[start + 1].contains("system prompt"));
+        assert!(lines[start + 3].contains("history (0 messages)"));
+        assert!(lines
+            .last()
+            .is_some_and(|line| line.starts_with("  pruning: nothing to prune yet")));
     }
 
     #[tokio::test]
-    async fn test_invalid_approval_input_keeps_overlay_active_with_feedback() {
+    async fn test_diffcontext_override_reaches_transcript_diffs() {
         let mut ctx = setup_ctx();
-        let mut mode = TuiMode::new();
-        let (response_tx, _response_rx) = tokio::sync::oneshot::channel::<bool>();
+        let context = DiffContext::default();
+        let mut mode = TuiMode::new().with_diff_context(context.clone());
+
+        mode.on_user_input("/diffcontext transcript 0".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[diff context: preview 2, history 0, transcript 0]")
+        );
+        mode.on_user_input("/diffcontext review 3".to_string(), &mut ctx);
+        assert!(mode
+            .history_state
+            .lines
+            .last()
+            .unwrap()
+            .starts_with("[error]"));
+        assert_eq!(context.get(DiffSurface::Transcript), 0);
 
         mode.on_model_update(
-            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
-                tool_name: "read_file".to_string(),
-                input_preview: "{}".to_string(),
-                response_tx,
-            }),
+            UiUpdate::StreamBlockStart {
+                index: 0,
+                block: StreamBlock::ToolCall {
+                    id: "toolu_1".to_string(),
+                    name: "edit_file".to_string(),
+                    input: serde_json::json!({
+                        "path": "src/lib.rs",
+                        "old_str": "a\nb\nc",
+                        "new_str": "a\nB\nc",
+                    }),
+                    status: ToolStatus::Complete,
+                },
+            },
             &mut ctx,
         );
+        let diff_start = mode
+            .history_state
+            .lines
+            .iter()
+            .position(|line| line == "[edited src/lib.rs]")
+            .expect("diff header");
+        let diff = &mode.history_state.lines[diff_start + 1..];
+        assert!(diff.iter().any(|line| line.contains("- b")));
+        assert!(diff.iter().any(|line| line.contains("+ B")));
+        assert!(!diff.iter().any(|line| line.contains(" a")));
+
+        mode.on_user_input("/diffcontext 4".to_string(), &mut ctx);
+        assert_eq!(context.get(DiffSurface::Preview), 4);
+        assert_eq!(context.get(DiffSurface::History), 4);
+    }
 
-        mode.on_user_input("x".to_string(), &mut ctx);
-        assert!(
-            mode.overlay_active(),
-            "overlay should stay active on invalid input"
+    #[test]
+    fn test_undo_restores_edits_outside_version_control() -> Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        let operator = ToolOperator::new(temp.path().to_path_buf()).with_undo_snapshots(true);
+        operator.write_file("notes.txt", "first line\n")?;
+        operator.edit_file("notes.txt", "first", "second")?;
+        let mut mode = TuiMode::new().with_workspace_root(temp.path().to_path_buf());
+        mode.vcs = Vcs::None;
+        let ctx = setup_ctx();
+
+        mode.handle_local_command("/undo", &ctx);
+        assert_eq!(
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[undo: restored notes.txt (edit_file)]")
         );
-        assert!(
-            mode.history_state
-                .lines
-                .iter()
-                .any(|line| line.contains("[invalid selection, expected 1/2/3]")),
-            "expected invalid selection feedback line"
+        assert_eq!(
+            std::fs::read_to_string(temp.path().join("notes.txt"))?,
+            "first line\n"
+        );
+        mode.handle_local_command("/undo", &ctx);
+        assert!(!temp.path().join("notes.txt").exists());
+        mode.handle_local_command("/undo", &ctx);
+        assert_eq!(
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[nothing to undo]")
         );
+
+        mode.vcs = Vcs::Git;
+        mode.handle_local_command("/undo", &ctx);
+        assert!(mode.history_state.lines.last().unwrap().contains("use git"));
+        Ok(())
     }
 
-    #[tokio::test]
-    async fn test_interrupt_is_typed_event_not_magic_string_collision() {
-        let mut ctx = setup_ctx();
-        let mut mode = TuiMode::new();
+    #[test]
+    fn test_undo_drops_the_stale_repo_map() -> Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        let operator = ToolOperator::new(temp.path().to_path_buf()).with_undo_snapshots(true);
+        operator.write_file("lib.rs", "fn first() {}\n")?;
+        operator.edit_file("lib.rs", "first", "second")?;
+        assert!(operator.repo_map(None, false)?.contains("fn second()"));
+        let (tx, _rx) = mpsc::unbounded_channel::<UiUpdate>();
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(vec![])));
+        let ctx = RuntimeContext::new(
+            ConversationManager::new(client, operator.clone()),
+            tx,
+            CancellationToken::new(),
+        );
+        let mut mode = TuiMode::new().with_workspace_root(temp.path().to_path_buf());
+        mode.vcs = Vcs::None;
+
+        mode.handle_local_command("/undo", &ctx);
+        let map = operator.repo_map(None, false)?;
+        assert!(map.contains("fn first()"), "{map}");
+        assert!(!map.contains("fn second()"), "{map}");
+        Ok(())
+    }
 
-        mode.on_user_input("__VEX_INTERRUPT__".to_string(), &mut ctx);
-        assert!(
-            mode.history_state.turn_in_progress,
-            "plain text matching old sentinel must be treated as normal user input"
+    #[test]
+    fn test_profile_command_shows_masked_settings() -> Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        std::fs::write(
+            temp.path().join("work.toml"),
+            "ANTHROPIC_API_KEY = \"sk-ant-abcd1234\"\nANTHROPIC_MODEL = \"claude-opus-4-1-20250805\"\n",
+        )?;
+        let profile = Profile::load_from(temp.path(), "work")?;
+        let mut mode = TuiMode::new().with_profile(Some(profile));
+        let ctx = setup_ctx();
+
+        mode.handle_local_command("/profile", &ctx);
+        let lines = &mode.history_state.lines;
+        assert!(lines[lines.len() - 3].starts_with("[profile work from "));
+        assert_eq!(lines[lines.len() - 2], "  ANTHROPIC_API_KEY = ****1234");
+        assert_eq!(
+            lines[lines.len() - 1],
+            "  ANTHROPIC_MODEL = claude-opus-4-1-20250805"
         );
+        Ok(())
+    }
 
-        mode.on_interrupt(&mut ctx);
-        assert!(
-            mode.history_state.turn_in_progress,
-            "typed interrupt should keep turn active until TurnComplete drains"
+    #[test]
+    fn test_followup_inserts_the_numbered_suggestion() {
+        let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+
+        mode.on_user_input("/followup 1".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[no follow-up suggestions for the last turn]")
         );
-        assert!(
-            mode.history_state.cancel_pending,
-            "typed interrupt should arm cancel-pending state"
+
+        mode.follow_ups = vec![
+            "run the tests".to_string(),
+            "update CHANGELOG.md".to_string(),
+        ];
+        mode.on_user_input("/followup 2".to_string(), &mut ctx);
+        assert_eq!(
+            mode.take_paste_insert().as_deref(),
+            Some("update CHANGELOG.md")
         );
-        assert!(
-            mode.history_state
-                .lines
-                .iter()
-                .any(|line| line.contains("[turn cancellation requested]")),
-            "cancel path should provide visible feedback"
+        mode.on_user_input("/followup 3".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[usage: /followup <1-2>]")
         );
-
-        mode.on_model_update(UiUpdate::TurnComplete, &mut ctx);
+        assert!(mode.take_paste_insert().is_none());
         assert!(!mode.history_state.turn_in_progress);
-        assert!(!mode.history_state.cancel_pending);
     }
 
     #[test]
-    fn test_stream_delta_ignored_without_active_turn_slot() {
-        let mut mode = TuiMode::new();
+    fn test_share_without_messages_writes_nothing() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        let mut mode = TuiMode::new().with_workspace_root(temp.path().to_path_buf());
         let mut ctx = setup_ctx();
-        mode.on_model_update(UiUpdate::StreamDelta("ghost delta".to_string()), &mut ctx);
-        assert!(
-            mode.history_state.lines.is_empty(),
-            "stale stream deltas must be ignored after turn completion/cancel"
+
+        mode.on_user_input("/share".to_string(), &mut ctx);
+
+        assert!(!mode.history_state.turn_in_progress);
+        assert_eq!(
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[nothing to share yet]")
         );
+        assert!(!temp.path().join(".aistar").exists());
     }
 
     #[test]
-    fn test_cancel_pending_blocks_stream_delta_appends() {
-        let mut mode = TuiMode::new();
+    fn test_help_screen_filters_and_closes() {
+        let mut mode = TuiMode::new()
+            .with_config_highlights(vec![("model".to_string(), "claude-test".to_string())]);
         let mut ctx = setup_ctx();
-        mode.on_user_input("hello".to_string(), &mut ctx);
-        mode.on_interrupt(&mut ctx);
-        mode.on_model_update(UiUpdate::StreamDelta("stale".to_string()), &mut ctx);
-        assert_eq!(mode.history_state.lines[0], "> hello");
-        assert_eq!(mode.history_state.lines[1], "");
+
+        mode.on_user_input("/help".to_string(), &mut ctx);
+        assert!(mode.help_active());
+        assert_eq!(render_pass_order(&mode).last(), Some(&RenderPass::Overlay));
+        for ch in ["m", "o", "d", "e", "l"] {
+            mode.on_user_input(ch.to_string(), &mut ctx);
+        }
+        let (query, lines, _) = mode.help_overlay().expect("help open");
+        assert_eq!(query, "model");
+        assert_eq!(
+            lines.last(),
+            Some(&HelpLine::Entry {
+                key: "model".to_string(),
+                description: "claude-test".to_string()
+            })
+        );
+
+        mode.on_user_input("attach".to_string(), &mut ctx);
+        let (query, lines, _) = mode.help_overlay().expect("help open");
+        assert_eq!(query, "attach");
+        assert!(lines.contains(&HelpLine::Heading("Commands".to_string())));
+
+        mode.on_user_input("esc".to_string(), &mut ctx);
+        assert!(!mode.help_active());
+        assert!(!mode.history_state.turn_in_progress);
     }
 
-    #[tokio::test]
-    async fn test_tool_approval_accept_once() {
+    #[test]
+    fn test_attach_command_queues_file_for_next_message() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        std::fs::write(temp.path().join("big.rs"), "fn a() {}\n".repeat(50)).expect("seed");
+        let mut mode = TuiMode::new()
+            .with_workspace_root(temp.path().to_path_buf())
+            .with_attachment_budget(200);
         let mut ctx = setup_ctx();
-        let mut mode = TuiMode::new();
-        let (response_tx, response_rx) = tokio::sync::oneshot::channel::<bool>();
 
-        mode.on_model_update(
-            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
-                tool_name: "read_file".to_string(),
-                input_preview: "{}".to_string(),
-                response_tx,
-            }),
-            &mut ctx,
-        );
-        mode.on_user_input("1".to_string(), &mut ctx);
+        mode.on_user_input("/attach big.rs".to_string(), &mut ctx);
+        mode.on_user_input("/attach".to_string(), &mut ctx);
+        mode.on_user_input("/attach missing.rs".to_string(), &mut ctx);
 
-        assert!(response_rx.await.expect("response should resolve"));
+        assert!(!mode.history_state.turn_in_progress);
+        let lines = &mode.history_state.lines;
+        assert!(lines.iter().any(|line| line
+            == "[attached: big.rs (outline: 50 lines, 50 sections; the model reads ranges on demand)]"));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("[queued attachment: big.rs")));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("[error] failed to resolve missing.rs")));
+
+        mode.on_user_input("explain big.rs".to_string(), &mut ctx);
+        assert!(mode.attachments.pending().is_empty());
+        assert_eq!(mode.prompt_history.entries()[0].text, "explain big.rs");
     }
 
-    #[tokio::test]
-    async fn test_tool_approval_deny() {
+    #[test]
+    fn test_add_command_attaches_glob_matches_up_to_the_size_cap() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        let src = temp.path().join("src");
+        std::fs::create_dir_all(src.join("nested")).expect("dirs");
+        std::fs::write(src.join("a.rs"), "fn a() {}\n").expect("seed");
+        std::fs::write(src.join("blob.rs"), [0xff, 0xfe, 0x00]).expect("seed");
+        std::fs::write(src.join("nested/b.rs"), "fn b() {}\n".repeat(50)).expect("seed");
+        std::fs::write(src.join("notes.txt"), "not rust\n").expect("seed");
+        std::fs::write(src.join("z.rs"), "x".repeat(2_000)).expect("seed");
+        let mut mode = TuiMode::new()
+            .with_workspace_root(temp.path().to_path_buf())
+            .with_attachment_budget(200);
         let mut ctx = setup_ctx();
-        let mut mode = TuiMode::new();
-        let (response_tx, response_rx) = tokio::sync::oneshot::channel::<bool>();
 
-        mode.on_model_update(
-            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
-                tool_name: "read_file".to_string(),
-                input_preview: "{}".to_string(),
-                response_tx,
-            }),
-            &mut ctx,
+        mode.on_user_input("/add src/**/*.rs --max-kb 1".to_string(), &mut ctx);
+        let report = mode.history_state.lines.last().cloned().unwrap_or_default();
+        assert!(
+            report.starts_with("[added 2 files: 510 B on disk, about "),
+            "{report}"
         );
-        mode.on_user_input("n".to_string(), &mut ctx);
+        assert!(report.contains("\n  src/a.rs (1 lines)\n"));
+        assert!(report.contains("\n  src/nested/b.rs (outline: 50 lines"));
+        assert!(report.contains("\n  skipped src/blob.rs (failed to read src/blob.rs"));
+        assert!(report.ends_with("\n  skipped src/z.rs (over the 1 KiB total)"));
+        let pending: Vec<&str> = mode
+            .attachments
+            .pending()
+            .iter()
+            .map(|attachment| attachment.path.as_str())
+            .collect();
+        assert_eq!(pending, ["src/a.rs", "src/nested/b.rs"]);
+
+        mode.on_user_input("/add lib/*.py".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[error] no workspace files match lib/*.py")
+        );
+        mode.on_user_input("/add ../*.rs".to_string(), &mut ctx);
+        assert!(mode
+            .history_state
+            .lines
+            .last()
+            .is_some_and(|line| line.contains("must be workspace-relative")));
+        assert!(!mode.history_state.turn_in_progress);
+    }
 
-        assert!(!response_rx.await.expect("response should resolve"));
+    #[test]
+    fn test_attached_image_shows_placeholder_and_reserves_rows_when_drawable() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend([40, 0, 30, 0]);
+        std::fs::write(temp.path().join("shot.gif"), &gif).expect("seed");
+        let mut mode = TuiMode::new().with_workspace_root(temp.path().to_path_buf());
+        let mut ctx = setup_ctx();
+
+        mode.on_user_input("/attach shot.gif".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[image: shot.gif (GIF, 40x30)]")
+        );
+        mode.set_inline_images(true);
+        mode.on_user_input("/attach shot.gif".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_state.lines.last().map(String::as_str),
+            Some("[image: shot.gif (GIF, 40x30)]\n\n")
+        );
+        assert_eq!(mode.inline_images().len(), 2);
+        assert_eq!(mode.inline_images()[0].path, temp.path().join("shot.gif"));
+        assert_eq!(mode.attachments.pending().len(), 1);
     }
 
-    #[tokio::test]
-    async fn approval_sender_resolved_exactly_once() {
+    #[test]
+    fn test_large_paste_asks_then_inserts_attaches_or_discards() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        let mut mode = TuiMode::new()
+            .with_workspace_root(temp.path().to_path_buf())
+            .with_paste_confirm_lines(3);
         let mut ctx = setup_ctx();
+        let blob = "one\ntwo\nthree\nfour\n".to_string();
+        assert!(!mode.paste_needs_confirmation("one\ntwo"));
+        assert!(mode.paste_needs_confirmation(&blob));
+        assert!(mode.paste_needs_confirmation(&"x".repeat(301)));
+
+        mode.on_frontend_event(UserInputEvent::LargePaste(blob.clone()), &mut ctx);
+        assert!(mode.overlay_active());
+        assert_eq!(
+            mode.pending_paste_overlay().map(|(lines, _, _)| lines),
+            Some(4)
+        );
+        mode.on_user_input("x".to_string(), &mut ctx);
+        assert!(mode.overlay_active());
+        mode.on_user_input("1".to_string(), &mut ctx);
+        assert!(!mode.overlay_active());
+        assert_eq!(mode.take_paste_insert().as_deref(), Some(blob.as_str()));
+
+        mode.on_frontend_event(UserInputEvent::LargePaste(blob.clone()), &mut ctx);
+        mode.on_user_input("a".to_string(), &mut ctx);
+        assert!(mode.take_paste_insert().is_none());
+        let attached = &mode.attachments.pending()[0];
+        assert!(attached.path.starts_with(".aistar/pastes/paste-"));
+        assert_eq!(
+            std::fs::read_to_string(temp.path().join(&attached.path)).expect("saved paste"),
+            blob
+        );
+        assert!(mode
+            .history_lines()
+            .iter()
+            .any(|line| line.starts_with("[attached paste: .aistar/pastes/paste-")));
+
+        mode.on_frontend_event(UserInputEvent::LargePaste(blob), &mut ctx);
+        mode.on_user_input("esc".to_string(), &mut ctx);
+        assert!(!mode.overlay_active());
+        assert!(mode.take_paste_insert().is_none());
+        assert_eq!(
+            mode.history_lines().last().map(String::as_str),
+            Some("[paste discarded: 4 lines]")
+        );
+    }
+
+    #[test]
+    fn test_thinking_block_collapses_and_expands_after_the_fact() {
         let mut mode = TuiMode::new();
+        let mut ctx = setup_ctx();
+        mode.history_state.turn_in_progress = true;
+        mode.push_history_line(String::new());
+        mode.history_state.active_assistant_index = Some(mode.history_state.lines.len() - 1);
+        let thinking = (1..=6)
+            .map(|n| format!("step {n}"))
+            .collect::<Vec<_>>()
+            .join("\n");
 
-        let (first_tx, first_rx) = tokio::sync::oneshot::channel::<bool>();
         mode.on_model_update(
-            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
-                tool_name: "read_file".to_string(),
-                input_preview: "first".to_string(),
-                response_tx: first_tx,
-            }),
+            UiUpdate::StreamBlockStart {
+                index: 0,
+                block: StreamBlock::Thinking {
+                    content: String::new(),
+                    collapsed: false,
+                },
+            },
             &mut ctx,
         );
-
-        let mut first_rx = Box::pin(first_rx);
-        assert!(
-            first_rx.as_mut().now_or_never().is_none(),
-            "first approval sender must remain unresolved while overlay is active"
-        );
-
-        let (second_tx, second_rx) = tokio::sync::oneshot::channel::<bool>();
+        mode.on_model_update(UiUpdate::StreamDelta(thinking.clone()), &mut ctx);
         mode.on_model_update(
-            UiUpdate::ToolApprovalRequest(ToolApprovalRequest {
-                tool_name: "write_file".to_string(),
-                input_preview: "second".to_string(),
-                response_tx: second_tx,
-            }),
+            UiUpdate::StreamBlockStart {
+                index: 1,
+                block: StreamBlock::FinalText {
+                    content: String::new(),
+                },
+            },
             &mut ctx,
         );
-
-        assert!(
-            !first_rx
-                .await
-                .expect("first sender should resolve when replaced"),
-            "replaced approval sender must resolve false exactly once"
+        mode.on_model_update(UiUpdate::StreamDelta("All done.".to_string()), &mut ctx);
+
+        let lines = &mode.history_state.lines;
+        let thinking_line = lines.len() - 2;
+        assert!(lines[thinking_line].ends_with("step 4\n  (+2 more lines, Ctrl+T to expand)"));
+        assert_eq!(lines[thinking_line + 1], "All done.");
+
+        mode.on_user_input("/thinking expand last".to_string(), &mut ctx);
+        assert_eq!(mode.history_state.lines[thinking_line], thinking);
+        mode.on_user_input("/thinking toggle".to_string(), &mut ctx);
+        assert!(mode.history_state.lines[thinking_line].contains("(+2 more lines"));
+        mode.on_user_input("/thinking hide all".to_string(), &mut ctx);
+        assert_eq!(
+            mode.history_state.lines[thinking_line],
+            "[thinking hidden: 6 lines, Ctrl+T to show]"
         );
+        assert!(mode.history_state.turn_in_progress);
+    }
 
-        let mut second_rx = Box::pin(second_rx);
-        assert!(
-            second_rx.as_mut().now_or_never().is_none(),
-            "second approval sender must remain unresolved before decision"
-        );
+    #[test]
+    fn test_startup_preset_picker_seeds_first_message() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        let presets_dir = temp.path().join(".aistar/presets");
+        std::fs::create_dir_all(&presets_dir).expect("presets dir");
+        std::fs::write(
+            presets_dir.join("add-feature.md"),
+            "# New feature\nFeature:\n",
+        )
+        .expect("write preset");
+        std::fs::write(presets_dir.join("bugfix.md"), "# Fix a bug\nBug:\nRepro:\n")
+            .expect("write preset");
+        let mut mode = TuiMode::new()
+            .with_workspace_root(temp.path().to_path_buf())
+            .with_startup_presets();
+        let mut ctx = setup_ctx();
+        assert!(mode.preset_picker_active());
 
-        mode.on_user_input("1".to_string(), &mut ctx);
-        assert!(
-            second_rx
-                .await
-                .expect("second sender should resolve on accept"),
-            "approved overlay should resolve true exactly once"
+        mode.on_frontend_event(
+            UserInputEvent::Scroll {
+                target: ScrollTarget::Overlay,
+                action: ScrollAction::LineDown,
+            },
+            &mut ctx,
         );
+        mode.on_user_input("enter".to_string(), &mut ctx);
 
-        mode.on_model_update(UiUpdate::TurnComplete, &mut ctx);
-        mode.on_model_update(UiUpdate::Error("post-resolution".to_string()), &mut ctx);
-        assert!(
-            !mode.overlay_active(),
-            "overlay lifecycle should clear cleanly after sender resolution"
-        );
+        assert!(!mode.preset_picker_active());
+        assert!(!mode.history_state.turn_in_progress);
+        assert_eq!(mode.take_editor_prefill().as_deref(), Some("Bug:\nRepro:"));
+
+        mode.on_user_input("/presets".to_string(), &mut ctx);
+        let (presets, selected) = mode.preset_picker_overlay().expect("picker reopened");
+        assert_eq!((presets.len(), selected), (2, 0));
+        mode.on_user_input("esc".to_string(), &mut ctx);
+        assert!(!mode.preset_picker_active());
+        assert!(mode.take_editor_prefill().is_none());
+    }
+
+    #[test]
+    fn test_audit_command_lists_newest_records() {
+        let _env_lock = crate::test_support::ENV_LOCK.blocking_lock();
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        let log = AuditLog::for_workspace(temp.path());
+        for tool in ["read_file", "edit_file", "run_tests"] {
+            log.append(&crate::audit::AuditRecord::new(
+                tool,
+                Vec::new(),
+                crate::audit::ApprovalSource::Rule,
+                Duration::from_millis(5),
+                &Ok(String::new()),
+            ))
+            .expect("append");
+        }
+        let mut mode = TuiMode::new().with_workspace_root(temp.path().to_path_buf());
+        let mut ctx = setup_ctx();
+
+        std::env::set_var("VEX_AUDIT_LOG", "on");
+        mode.on_user_input("/audit 2".to_string(), &mut ctx);
+        std::env::remove_var("VEX_AUDIT_LOG");
+        mode.on_user_input("/audit".to_string(), &mut ctx);
+
+        assert!(!mode.history_state.turn_in_progress);
+        let listing = mode
+            .history_state
+            .lines
+            .iter()
+            .find(|line| line.starts_with("[audit log: last 2 of 3"))
+            .expect("audit listing");
+        assert!(!listing.contains("read_file"));
+        assert!(listing.contains("edit_file") && listing.contains("run_tests"));
+        assert!(mode
+            .history_state
+            .lines
+            .iter()
+            .any(|line| line.starts_with("[audit log is off")));
     }
 }
@@ -0,0 +1,636 @@
+use crate::state::{count_text_tokens, glob_matches, SensitivePaths};
+use crate::state_dirs::StateDirs;
+use crate::tools::workspace_files;
+use crate::ui::inline_images::{image_info, ImageInfo};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Component, Path};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Matches the remote tool-result history budget, used until the runtime
+/// supplies the endpoint's own budget.
+pub const DEFAULT_ATTACHMENT_BUDGET_CHARS: usize = 6_000;
+/// Total size `/add` attaches unless `--max-kb` says otherwise.
+pub const DEFAULT_ADD_MAX_KB: u64 = 256;
+/// Lines per chunk when a file has no recognizable symbols or headings.
+const OUTLINE_CHUNK_LINES: usize = 200;
+const OUTLINE_LABEL_MAX_CHARS: usize = 80;
+/// Leading keywords that start a symbol line in common languages, checked
+/// after visibility and `async`/`export` prefixes are stripped.
+const SYMBOL_KEYWORDS: &[&str] = &[
+    "fn ",
+    "struct ",
+    "enum ",
+    "trait ",
+    "impl ",
+    "impl<",
+    "mod ",
+    "type ",
+    "const ",
+    "static ",
+    "macro_rules! ",
+    "class ",
+    "def ",
+    "function ",
+    "interface ",
+    "func ",
+];
+const SYMBOL_PREFIXES: &[&str] = &[
+    "pub(crate) ",
+    "pub(super) ",
+    "pub ",
+    "export default ",
+    "export ",
+    "async ",
+    "unsafe ",
+];
+/// Symbols nested deeper than this (in leading spaces) are left out.
+const SYMBOL_MAX_INDENT: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttachmentBody {
+    Full(String),
+    Outline(Vec<OutlineEntry>),
+    /// Sent through the provider's Files API and referenced by id.
+    Upload(String),
+    /// An image file: shown in the transcript, described to the model by
+    /// path and size only.
+    Image(ImageInfo),
+}
+
+/// One outline row: a symbol, heading, or chunk and the lines it spans.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineEntry {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub path: String,
+    pub line_count: usize,
+    pub char_count: usize,
+    pub body: AttachmentBody,
+}
+
+impl Attachment {
+    /// One-line description for the transcript.
+    pub fn summary(&self) -> String {
+        match &self.body {
+            AttachmentBody::Full(_) => format!("{} ({} lines)", self.path, self.line_count),
+            AttachmentBody::Outline(entries) => format!(
+                "{} (outline: {} lines, {} sections; the model reads ranges on demand)",
+                self.path,
+                self.line_count,
+                entries.len()
+            ),
+            AttachmentBody::Upload(_) => format!(
+                "{} ({} lines, uploaded once as a document)",
+                self.path, self.line_count
+            ),
+            AttachmentBody::Image(info) => format!(
+                "{} ({} image, {}x{})",
+                self.path,
+                info.format.name(),
+                info.width,
+                info.height
+            ),
+        }
+    }
+
+    /// Characters this attachment adds to the next request, counting an
+    /// uploaded document's content.
+    pub fn context_chars(&self, budget_chars: usize) -> usize {
+        let rendered = self.render(budget_chars).len();
+        match self.body {
+            AttachmentBody::Upload(_) => rendered + self.char_count,
+            _ => rendered,
+        }
+    }
+
+    /// Tokens this attachment adds to the next request, counted like the
+    /// history is, including an uploaded document's content.
+    pub fn context_tokens(&self, budget_chars: usize) -> usize {
+        let rendered = count_text_tokens(&self.render(budget_chars));
+        match &self.body {
+            AttachmentBody::Upload(content) => rendered + count_text_tokens(content),
+            _ => rendered,
+        }
+    }
+
+    fn render(&self, budget_chars: usize) -> String {
+        match &self.body {
+            AttachmentBody::Full(content) => format!(
+                "[Attached file: {} ({} lines)]\n--- {} ---\n{}\n--- end {} ---",
+                self.path,
+                self.line_count,
+                self.path,
+                content.trim_end(),
+                self.path
+            ),
+            AttachmentBody::Upload(_) => format!(
+                "[Attached file: {} ({} lines), sent as the uploaded document titled {}]",
+                self.path, self.line_count, self.path
+            ),
+            AttachmentBody::Image(info) => format!(
+                "[Attached image: {} ({}, {}x{} pixels); the user sees it, only its path and size are included here]",
+                self.path,
+                info.format.name(),
+                info.width,
+                info.height
+            ),
+            AttachmentBody::Outline(entries) => {
+                let mut out = format!(
+                    "[Attached file outline: {} ({} lines, {} chars; larger than the {budget_chars}-char attachment budget)]\n\
+                     Read the sections you need with read_file using start_line and end_line.\n",
+                    self.path, self.line_count, self.char_count
+                );
+                let mut shown = 0;
+                for entry in entries {
+                    let row = format!(
+                        "L{}-L{}  {}\n",
+                        entry.start_line, entry.end_line, entry.label
+                    );
+                    if out.len() + row.len() > budget_chars {
+                        break;
+                    }
+                    out.push_str(&row);
+                    shown += 1;
+                }
+                if shown < entries.len() {
+                    out.push_str(&format!(
+                        "[... {} more sections; use search_files to locate them]\n",
+                        entries.len() - shown
+                    ));
+                }
+                out.trim_end().to_string()
+            }
+        }
+    }
+}
+
+/// The files one `/add` queued and the matches it left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GlobAttachment {
+    pub attached: Vec<String>,
+    /// Size of the attached files on disk.
+    pub bytes: u64,
+    /// Tokens the attached files add to the next message, outlines counted
+    /// as sent.
+    pub context_tokens: usize,
+    /// Each match left out, as `path (reason)`.
+    pub skipped: Vec<String>,
+}
+
+/// Files attached to the next message. A file that fits the tool-result
+/// budget is attached whole; a larger one is attached as an outline of
+/// symbols, headings, and line ranges so the model can read only what it
+/// needs instead of seeing a blindly truncated file.
+#[derive(Debug)]
+pub struct AttachmentManager {
+    budget_chars: usize,
+    /// Upload files over the budget instead of outlining them.
+    uploads: bool,
+    pending: Vec<Attachment>,
+}
+
+impl AttachmentManager {
+    pub fn new(budget_chars: usize) -> Self {
+        Self {
+            budget_chars,
+            uploads: false,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Files over the budget are uploaded through the provider's Files API
+    /// rather than attached as outlines.
+    pub fn with_uploads(mut self, enabled: bool) -> Self {
+        self.uploads = enabled;
+        self
+    }
+
+    pub fn budget_chars(&self) -> usize {
+        self.budget_chars
+    }
+
+    pub fn pending(&self) -> &[Attachment] {
+        &self.pending
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Reads `path` (relative to `working_dir`) now and queues it for the next
+    /// message, replacing an earlier attachment of the same file.
+    pub fn attach(&mut self, working_dir: &Path, path: &str) -> Result<&Attachment> {
+        let path = path.trim().trim_start_matches("./");
+        if path.is_empty() {
+            bail!("usage: /attach <path>");
+        }
+        let relative = Path::new(path);
+        if relative.is_absolute()
+            || relative
+                .components()
+                .any(|component| matches!(component, Component::ParentDir))
+        {
+            bail!("attachments must be workspace-relative paths: {path}");
+        }
+        if let Some(pattern) = sensitive_pattern(&SensitivePaths::from_env(), working_dir, path) {
+            bail!("{path} matches sensitive path pattern `{pattern}`; ask the model to read it so the access goes through approval");
+        }
+        self.attach_workspace_file(working_dir, path)
+    }
+
+    /// [`Self::attach`] without the sensitive path check, for files vex wrote
+    /// itself under `.aistar/`.
+    fn attach_workspace_file(&mut self, working_dir: &Path, path: &str) -> Result<&Attachment> {
+        let relative = Path::new(path);
+        let root = fs::canonicalize(working_dir)
+            .with_context(|| format!("failed to resolve {}", working_dir.display()))?;
+        let resolved = fs::canonicalize(root.join(relative))
+            .with_context(|| format!("failed to resolve {path}"))?;
+        if !resolved.starts_with(&root) {
+            bail!("{path} resolves outside the workspace");
+        }
+        if resolved.is_dir() {
+            bail!("{path} is a directory");
+        }
+        let bytes = fs::read(&resolved).with_context(|| format!("failed to read {path}"))?;
+        if let Some(info) = image_info(&bytes) {
+            self.pending.retain(|attachment| attachment.path != path);
+            self.pending.push(Attachment {
+                path: path.to_string(),
+                line_count: 0,
+                char_count: 0,
+                body: AttachmentBody::Image(info),
+            });
+            return Ok(self.pending.last().expect("attachment was just pushed"));
+        }
+        let content = String::from_utf8(bytes)
+            .with_context(|| format!("failed to read {path}: not a text file or known image"))?;
+
+        let char_count = content.chars().count();
+        let line_count = content.lines().count();
+        let body = if char_count <= self.budget_chars {
+            AttachmentBody::Full(content)
+        } else if self.uploads {
+            AttachmentBody::Upload(content)
+        } else {
+            AttachmentBody::Outline(outline(path, &content))
+        };
+        self.pending.retain(|attachment| attachment.path != path);
+        self.pending.push(Attachment {
+            path: path.to_string(),
+            line_count,
+            char_count,
+            body,
+        });
+        Ok(self.pending.last().expect("attachment was just pushed"))
+    }
+
+    /// Attaches every workspace file matching one of `patterns`
+    /// (workspace-relative globs where `**` spans directories; a directory
+    /// stands for everything under it), in path order, until their total
+    /// size would pass `max_bytes`. Files `.gitignore` excludes are never
+    /// matched, and sensitive paths are skipped like with [`Self::attach`].
+    pub fn attach_glob(
+        &mut self,
+        working_dir: &Path,
+        patterns: &[&str],
+        max_bytes: u64,
+    ) -> Result<GlobAttachment> {
+        if patterns.is_empty() {
+            bail!("usage: /add <glob>... [--max-kb <n>]");
+        }
+        let mut globs = Vec::new();
+        for pattern in patterns {
+            let pattern = pattern
+                .trim()
+                .trim_start_matches("./")
+                .trim_end_matches('/');
+            if Path::new(pattern).is_absolute() || pattern.split('/').any(|segment| segment == "..")
+            {
+                bail!("/add patterns must be workspace-relative: {pattern}");
+            }
+            if pattern.is_empty() || pattern == "." {
+                globs.push("**".to_string());
+            } else if working_dir.join(pattern).is_dir() {
+                globs.push(format!("{pattern}/**"));
+            } else {
+                globs.push(pattern.to_string());
+            }
+        }
+        let matches: BTreeSet<String> = workspace_files(working_dir)
+            .iter()
+            .filter_map(|file| file.strip_prefix(working_dir).ok())
+            .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+            .filter(|path| globs.iter().any(|glob| glob_matches(glob, path)))
+            .collect();
+        if matches.is_empty() {
+            bail!("no workspace files match {}", patterns.join(" "));
+        }
+
+        let sensitive = SensitivePaths::from_env();
+        let budget_chars = self.budget_chars;
+        let mut added = GlobAttachment::default();
+        for path in matches {
+            if let Some(pattern) = sensitive_pattern(&sensitive, working_dir, &path) {
+                added
+                    .skipped
+                    .push(format!("{path} (sensitive path pattern `{pattern}`)"));
+                continue;
+            }
+            let size = fs::metadata(working_dir.join(&path))
+                .map(|metadata| metadata.len())
+                .unwrap_or_default();
+            if added.bytes + size > max_bytes {
+                added
+                    .skipped
+                    .push(format!("{path} (over the {} KiB total)", max_bytes / 1024));
+                continue;
+            }
+            match self.attach(working_dir, &path) {
+                Ok(attachment) => {
+                    added.context_tokens += attachment.context_tokens(budget_chars);
+                    added.bytes += size;
+                    added.attached.push(path);
+                }
+                Err(error) => added.skipped.push(format!("{path} ({error})")),
+            }
+        }
+        Ok(added)
+    }
+
+    /// Saves pasted `text` under `.aistar/pastes/` and attaches that file,
+    /// so a large paste reaches the model like any other attached file.
+    pub fn attach_text(&mut self, working_dir: &Path, text: &str) -> Result<&Attachment> {
+        let state_dirs = StateDirs::for_workspace(working_dir);
+        let dir = state_dirs.pastes_dir();
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default();
+        let mut name = format!("paste-{stamp}.txt");
+        let mut suffix = 1;
+        while dir.join(&name).exists() {
+            name = format!("paste-{stamp}-{suffix}.txt");
+            suffix += 1;
+        }
+        fs::write(dir.join(&name), text)
+            .with_context(|| format!("failed to write {}", dir.join(&name).display()))?;
+        self.attach_workspace_file(working_dir, &state_dirs.display(&dir.join(&name)))
+    }
+
+    /// Path and content of each queued attachment that goes as an upload.
+    pub fn pending_uploads(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pending
+            .iter()
+            .filter_map(|attachment| match &attachment.body {
+                AttachmentBody::Upload(content) => {
+                    Some((attachment.path.as_str(), content.as_str()))
+                }
+                _ => None,
+            })
+    }
+
+    /// Renders and clears the queued attachments as a prefix for the next
+    /// user message.
+    pub fn take_message_prefix(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let rendered = self
+            .pending
+            .drain(..)
+            .map(|attachment| attachment.render(self.budget_chars))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Some(rendered)
+    }
+}
+
+/// Markdown headings for `.md` files, top-level symbols for code, and fixed
+/// line chunks when neither is found. Each entry runs until the next one.
+pub fn outline(path: &str, content: &str) -> Vec<OutlineEntry> {
+    let markdown = path.ends_with(".md") || path.ends_with(".markdown");
+    let starts: Vec<(usize, String)> = content
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let label = if markdown {
+                line.starts_with('#').then(|| line.trim().to_string())
+            } else {
+                symbol_label(line)
+            }?;
+            Some((index + 1, clip(&label)))
+        })
+        .collect();
+
+    let line_count = content.lines().count();
+    if starts.is_empty() {
+        return (0..line_count.div_ceil(OUTLINE_CHUNK_LINES))
+            .map(|chunk| {
+                let start_line = chunk * OUTLINE_CHUNK_LINES + 1;
+                let end_line = ((chunk + 1) * OUTLINE_CHUNK_LINES).min(line_count);
+                let first = content
+                    .lines()
+                    .skip(start_line - 1)
+                    .map(str::trim)
+                    .find(|line| !line.is_empty())
+                    .unwrap_or_default();
+                OutlineEntry {
+                    start_line,
+                    end_line,
+                    label: clip(first),
+                }
+            })
+            .collect();
+    }
+
+    let mut entries = Vec::with_capacity(starts.len() + 1);
+    if starts[0].0 > 1 {
+        entries.push(OutlineEntry {
+            start_line: 1,
+            end_line: starts[0].0 - 1,
+            label: "(preamble)".to_string(),
+        });
+    }
+    for (position, (start_line, label)) in starts.iter().enumerate() {
+        let end_line = starts
+            .get(position + 1)
+            .map(|(next, _)| next - 1)
+            .unwrap_or(line_count);
+        entries.push(OutlineEntry {
+            start_line: *start_line,
+            end_line,
+            label: label.clone(),
+        });
+    }
+    entries
+}
+
+pub(crate) fn symbol_label(line: &str) -> Option<String> {
+    let indent: usize = line
+        .chars()
+        .take_while(|ch| ch.is_whitespace())
+        .map(|ch| if ch == '\t' { 4 } else { 1 })
+        .sum();
+    if indent > SYMBOL_MAX_INDENT {
+        return None;
+    }
+    let trimmed = line.trim();
+    let rest = strip_symbol_prefixes(trimmed);
+    SYMBOL_KEYWORDS
+        .iter()
+        .any(|keyword| rest.starts_with(keyword))
+        .then(|| {
+            trimmed
+                .trim_end_matches('{')
+                .trim_end_matches(':')
+                .trim_end()
+                .to_string()
+        })
+}
+
+/// Strips visibility, `export`, `async`, and `unsafe` prefixes from the
+/// start of a symbol line.
+pub(crate) fn strip_symbol_prefixes(mut line: &str) -> &str {
+    while let Some(stripped) = SYMBOL_PREFIXES
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))
+    {
+        line = stripped;
+    }
+    line
+}
+
+fn clip(label: &str) -> String {
+    if label.chars().count() > OUTLINE_LABEL_MAX_CHARS {
+        let mut clipped: String = label.chars().take(OUTLINE_LABEL_MAX_CHARS).collect();
+        clipped.push_str("...");
+        clipped
+    } else {
+        label.to_string()
+    }
+}
+
+/// The sensitive pattern `path` matches, as written or once symlinks are
+/// resolved inside `working_dir`, so a link to a sensitive file is refused too.
+fn sensitive_pattern(sensitive: &SensitivePaths, working_dir: &Path, path: &str) -> Option<String> {
+    if let Some(pattern) = sensitive.matching(path) {
+        return Some(pattern.to_string());
+    }
+    let root = fs::canonicalize(working_dir).ok()?;
+    let resolved = fs::canonicalize(root.join(path)).ok()?;
+    let relative = resolved.strip_prefix(&root).ok()?;
+    sensitive
+        .matching(&relative.to_string_lossy().replace('\\', "/"))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_outline_spans_symbols_and_preamble() {
+        let source = "use std::fs;\n\npub struct Store {\n    path: String,\n}\n\nimpl Store {\n    pub fn load(&self) {\n        let inner = 1;\n    }\n}\n";
+        assert_eq!(
+            outline("src/store.rs", source),
+            vec![
+                OutlineEntry {
+                    start_line: 1,
+                    end_line: 2,
+                    label: "(preamble)".to_string()
+                },
+                OutlineEntry {
+                    start_line: 3,
+                    end_line: 6,
+                    label: "pub struct Store".to_string()
+                },
+                OutlineEntry {
+                    start_line: 7,
+                    end_line: 7,
+                    label: "impl Store".to_string()
+                },
+                OutlineEntry {
+                    start_line: 8,
+                    end_line: 11,
+                    label: "pub fn load(&self)".to_string()
+                },
+            ]
+        );
+        let notes = (1..=450).map(|n| format!("note {n}\n")).collect::<String>();
+        let chunks = outline("notes.txt", &notes);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            (
+                chunks[2].start_line,
+                chunks[2].end_line,
+                chunks[2].label.as_str()
+            ),
+            (401, 450, "note 401")
+        );
+    }
+
+    #[test]
+    fn test_large_files_attach_as_outline() -> Result<()> {
+        let temp = TempDir::new()?;
+        fs::write(temp.path().join("small.md"), "# Title\nbody\n")?;
+        let large = (1..=40)
+            .map(|n| format!("## Section {n}\n{}\n", "text ".repeat(20)))
+            .collect::<String>();
+        fs::write(temp.path().join("large.md"), large)?;
+
+        let mut manager = AttachmentManager::new(600);
+        assert_eq!(
+            manager.attach(temp.path(), "small.md")?.summary(),
+            "small.md (2 lines)"
+        );
+        assert_eq!(
+            manager.attach(temp.path(), "./large.md")?.summary(),
+            "large.md (outline: 80 lines, 40 sections; the model reads ranges on demand)"
+        );
+        assert!(manager.attach(temp.path(), "../escape.md").is_err());
+        assert!(manager.attach(temp.path(), "missing.md").is_err());
+
+        let prefix = manager.take_message_prefix().expect("attachments queued");
+        assert!(prefix.starts_with("[Attached file: small.md (2 lines)]\n--- small.md ---\n# Title\nbody\n--- end small.md ---"));
+        assert!(prefix.contains("read_file using start_line and end_line"));
+        assert!(prefix.contains("\nL1-L2  ## Section 1\n"));
+        assert!(prefix.contains("more sections; use search_files to locate them]"));
+        assert!(manager.take_message_prefix().is_none());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_links_to_sensitive_files_are_refused() -> Result<()> {
+        let _env_guard = crate::test_support::ENV_LOCK.blocking_lock();
+        let temp = TempDir::new()?;
+        fs::write(temp.path().join("Cargo.lock"), "version = 3\n")?;
+        fs::write(temp.path().join("notes.md"), "notes\n")?;
+        std::os::unix::fs::symlink("Cargo.lock", temp.path().join("settings.txt"))?;
+
+        let mut manager = AttachmentManager::new(600);
+        let error = manager
+            .attach(temp.path(), "settings.txt")
+            .expect_err("link to a lockfile refused");
+        assert!(error.to_string().contains("sensitive path pattern"));
+        let added = manager.attach_glob(temp.path(), &["*.txt", "*.md"], 1024)?;
+        assert_eq!(added.attached, ["notes.md"]);
+        assert!(added.context_tokens > 0);
+        assert!(added
+            .skipped
+            .iter()
+            .all(|skipped| !skipped.starts_with("settings.txt")
+                || skipped.contains("sensitive path pattern")));
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use crate::state_dirs::StateDirs;
+use crate::util::{parse_bool_str, unix_now};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const AUDIT_ERROR_MAX_CHARS: usize = 200;
+
+/// How an executed tool call was cleared to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalSource {
+    /// Approved at the approval prompt.
+    Manual,
+    /// Would have prompted, but session auto-approve was on.
+    Auto,
+    /// The approval policy does not require a prompt for this tool.
+    Rule,
+    /// Approved by an embedder's `ApprovalHandler` instead of a prompt.
+    Handler,
+}
+
+impl ApprovalSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ApprovalSource::Manual => "manual",
+            ApprovalSource::Auto => "auto",
+            ApprovalSource::Rule => "rule",
+            ApprovalSource::Handler => "handler",
+        }
+    }
+}
+
+/// One executed tool call. Records carry paths and outcomes only, never file
+/// content or tool output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: u64,
+    pub tool: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    pub approval: ApprovalSource,
+    pub duration_ms: u64,
+    pub outcome: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(
+        tool: &str,
+        paths: Vec<String>,
+        approval: ApprovalSource,
+        duration: Duration,
+        result: &Result<String>,
+    ) -> Self {
+        Self {
+            timestamp: unix_now(),
+            tool: tool.to_string(),
+            paths,
+            approval,
+            duration_ms: duration.as_millis() as u64,
+            outcome: if result.is_ok() { "ok" } else { "error" }.to_string(),
+            error: result
+                .as_ref()
+                .err()
+                .map(|error| first_line(&error.to_string())),
+        }
+    }
+}
+
+/// Opt-in, append-only JSONL record of every executed tool call.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn for_workspace(working_dir: &Path) -> Self {
+        Self::new(StateDirs::for_workspace(working_dir).audit_log())
+    }
+
+    /// `VEX_AUDIT_LOG=on` logs to `.aistar/audit.jsonl`; any other non-boolean
+    /// value is used as the log path. Unset or `off` disables logging.
+    pub fn from_env(working_dir: &Path) -> Option<Self> {
+        let value = std::env::var("VEX_AUDIT_LOG").ok()?;
+        let value = value.trim();
+        match parse_bool_str(value) {
+            Some(true) => Some(Self::for_workspace(working_dir)),
+            Some(false) => None,
+            None if value.is_empty() => None,
+            None => Some(Self::new(working_dir.join(value))),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, record: &AuditRecord) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(record)?)
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Reads all records, skipping lines that do not parse.
+    pub fn read(&self) -> Result<Vec<AuditRecord>> {
+        let raw = match fs::read_to_string(&self.path) {
+            Ok(raw) => raw,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed to read {}", self.path.display()))
+            }
+        };
+        Ok(raw
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+/// One aligned line per record, oldest first, with UTC timestamps.
+pub fn render_audit_records(records: &[AuditRecord]) -> String {
+    records
+        .iter()
+        .map(|record| {
+            let line = format!(
+                "{}  {:<14}  {:<6}  {:>6}ms  {:<5}  {}",
+                format_utc(record.timestamp),
+                record.tool,
+                record.approval.label(),
+                record.duration_ms,
+                record.outcome,
+                record.paths.join(", ")
+            );
+            let line = line.trim_end();
+            match &record.error {
+                Some(error) => format!("{line}\n    {error}"),
+                None => line.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn first_line(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default().trim();
+    if line.chars().count() > AUDIT_ERROR_MAX_CHARS {
+        let mut clipped: String = line.chars().take(AUDIT_ERROR_MAX_CHARS).collect();
+        clipped.push_str("...");
+        clipped
+    } else {
+        line.to_string()
+    }
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC.
+pub(crate) fn format_utc(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+    // Civil-from-days, proleptic Gregorian calendar.
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00");
+        assert_eq!(format_utc(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_utc(1_791_982_861), "2026-10-14 13:01:01");
+    }
+
+    #[test]
+    fn test_log_round_trip_and_render() -> Result<()> {
+        let temp = TempDir::new()?;
+        let log = AuditLog::for_workspace(temp.path());
+        assert!(log.read()?.is_empty());
+
+        let mut ok = AuditRecord::new(
+            "edit_file",
+            vec!["/work/src/lib.rs".to_string()],
+            ApprovalSource::Manual,
+            Duration::from_millis(42),
+            &Ok("done".to_string()),
+        );
+        ok.timestamp = 0;
+        let mut failed = AuditRecord::new(
+            "run_tests",
+            Vec::new(),
+            ApprovalSource::Rule,
+            Duration::from_millis(7),
+            &Err(anyhow::anyhow!("exit status 101\nlong output")),
+        );
+        failed.timestamp = 60;
+        log.append(&ok)?;
+        log.append(&failed)?;
+        fs::write(
+            log.path(),
+            format!("{}not json\n", fs::read_to_string(log.path())?),
+        )?;
+
+        let records = log.read()?;
+        assert_eq!(records, vec![ok, failed]);
+        assert_eq!(
+            render_audit_records(&records),
+            "1970-01-01 00:00:00  edit_file       manual      42ms  ok     /work/src/lib.rs\n\
+             1970-01-01 00:01:00  run_tests       rule         7ms  error\n    exit status 101"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,514 @@
+use crate::change_summary::TurnChangeSummary;
+use crate::state::{ApprovalQuery, ConversationManager, ToolApprovalDecision};
+use crate::types::TokenUsage;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub const AUTO_FLAG: &str = "--auto";
+pub const AUTO_USAGE: &str =
+    "usage: vex --auto <goal> [--check <command>] [--max-minutes <n>] [--max-turns <n>] [--report <file>]";
+const DEFAULT_MAX_MINUTES: u64 = 30;
+const DEFAULT_MAX_TURNS: usize = 20;
+/// The line the model replies with when there is no check command and it
+/// considers the goal met.
+const DONE_MARKER: &str = "AUTO-DONE";
+/// The line the model replies with when it cannot make progress alone.
+const BLOCKED_MARKER: &str = "AUTO-BLOCKED";
+const CHECK_OUTPUT_TAIL_CHARS: usize = 2_000;
+const REPORT_REPLY_CHARS: usize = 1_500;
+
+/// Options of an unattended `--auto` run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoOptions {
+    pub goal: String,
+    /// Shell command that exits 0 once the goal is met.
+    pub check: Option<String>,
+    pub max_time: Duration,
+    pub max_turns: usize,
+    pub report: Option<PathBuf>,
+}
+
+impl AutoOptions {
+    /// Removes `--auto <goal>` and its limits from `args`, leaving the rest
+    /// for `Config::apply_cli_args`. `None` when `--auto` is absent.
+    pub fn take(args: &mut Vec<String>) -> Result<Option<Self>> {
+        let mut goal = None;
+        let mut check = None;
+        let mut max_minutes = None;
+        let mut max_turns = None;
+        let mut report = None;
+        let mut rest = Vec::new();
+        let mut iter = std::mem::take(args).into_iter();
+        while let Some(arg) = iter.next() {
+            let slot = match arg.as_str() {
+                AUTO_FLAG => &mut goal,
+                "--check" => &mut check,
+                "--max-minutes" => &mut max_minutes,
+                "--max-turns" => &mut max_turns,
+                "--report" => &mut report,
+                _ => {
+                    rest.push(arg);
+                    continue;
+                }
+            };
+            let Some(value) = iter.next().filter(|value| !value.trim().is_empty()) else {
+                bail!("{arg} requires a value\n{AUTO_USAGE}");
+            };
+            *slot = Some(value);
+        }
+        *args = rest;
+
+        let Some(goal) = goal else {
+            if check.is_some() || max_minutes.is_some() || max_turns.is_some() || report.is_some() {
+                bail!("--check, --max-minutes, --max-turns and --report need --auto\n{AUTO_USAGE}");
+            }
+            return Ok(None);
+        };
+        let max_minutes = match max_minutes {
+            Some(value) => parse_positive(&value, "--max-minutes")?,
+            None => DEFAULT_MAX_MINUTES,
+        };
+        let max_turns = match max_turns {
+            Some(value) => parse_positive(&value, "--max-turns")?,
+            None => DEFAULT_MAX_TURNS as u64,
+        };
+        Ok(Some(Self {
+            goal,
+            check,
+            max_time: Duration::from_secs(max_minutes * 60),
+            max_turns: max_turns as usize,
+            report: report.map(PathBuf::from),
+        }))
+    }
+}
+
+fn parse_positive(value: &str, flag: &str) -> Result<u64> {
+    match value.trim().parse::<u64>() {
+        Ok(parsed) if parsed > 0 => Ok(parsed),
+        _ => bail!("{flag} expects a positive whole number, got '{value}'\n{AUTO_USAGE}"),
+    }
+}
+
+/// Denies every call that would prompt, since nobody is there to answer;
+/// the model is told so and can choose another way.
+pub async fn deny_unattended(query: ApprovalQuery) -> ToolApprovalDecision {
+    ToolApprovalDecision::denied_with_reason(&format!(
+        "unattended --auto run: nobody can approve {}; work around it or reply {BLOCKED_MARKER}",
+        query.tool_name
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AutoOutcome {
+    /// The check passed, or the model replied `AUTO-DONE` without one.
+    Completed,
+    Blocked(String),
+    TurnLimit,
+    TimeLimit,
+    Error(String),
+}
+
+impl AutoOutcome {
+    fn describe(&self) -> String {
+        match self {
+            AutoOutcome::Completed => "completed".to_string(),
+            AutoOutcome::Blocked(reason) => format!("blocked: {reason}"),
+            AutoOutcome::TurnLimit => "stopped at the turn limit".to_string(),
+            AutoOutcome::TimeLimit => "stopped at the time limit".to_string(),
+            AutoOutcome::Error(error) => format!("stopped with an error: {error}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub passed: bool,
+    /// Tail of the combined stdout and stderr.
+    pub output: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AutoReport {
+    pub outcome: AutoOutcome,
+    pub turns: usize,
+    pub elapsed: Duration,
+    pub usage: TokenUsage,
+    pub changes: Vec<TurnChangeSummary>,
+    pub last_check: Option<CheckResult>,
+    pub last_reply: String,
+}
+
+/// Loops on `options.goal` without user input: each turn ends with the check
+/// (or the model's own `AUTO-DONE`) and, when the goal is not met, the next
+/// turn is prompted with what still fails. The conversation's approval
+/// policy, tool budgets and loop guards apply to every turn as usual.
+pub async fn run_auto(
+    conversation: &mut ConversationManager,
+    options: &AutoOptions,
+    working_dir: &Path,
+) -> AutoReport {
+    let started = Instant::now();
+    let deadline = started + options.max_time;
+    let reader = conversation.reader();
+    let mut prompt = initial_prompt(options);
+    let mut turns = 0;
+    let mut changes = Vec::new();
+    let mut last_check = None;
+    let mut last_reply = String::new();
+
+    let outcome = loop {
+        if turns >= options.max_turns {
+            break AutoOutcome::TurnLimit;
+        }
+        turns += 1;
+        eprintln!("auto: turn {turns}/{}", options.max_turns);
+        let sent =
+            tokio::time::timeout_at(deadline, conversation.send_message(prompt.clone(), None))
+                .await;
+        if let Some(summary) = reader.latest_turn_changes() {
+            for line in summary.render_lines() {
+                eprintln!("auto: {line}");
+            }
+            changes.push(summary);
+        }
+        last_reply = match sent {
+            Err(_) => break AutoOutcome::TimeLimit,
+            Ok(Err(error)) => break AutoOutcome::Error(error.to_string()),
+            Ok(Ok(reply)) => reply,
+        };
+        if let Some(reason) = marker_line(&last_reply, BLOCKED_MARKER) {
+            break AutoOutcome::Blocked(if reason.is_empty() {
+                "no reason given".to_string()
+            } else {
+                reason
+            });
+        }
+
+        let Some(command) = &options.check else {
+            if marker_line(&last_reply, DONE_MARKER).is_some() {
+                break AutoOutcome::Completed;
+            }
+            prompt = continue_prompt(turns, options, deadline, None);
+            continue;
+        };
+        let check = match tokio::time::timeout_at(deadline, run_check(command, working_dir)).await {
+            Err(_) => break AutoOutcome::TimeLimit,
+            Ok(Err(error)) => break AutoOutcome::Error(format!("{error:#}")),
+            Ok(Ok(check)) => check,
+        };
+        eprintln!(
+            "auto: check {}",
+            if check.passed { "passed" } else { "failed" }
+        );
+        let passed = check.passed;
+        prompt = continue_prompt(turns, options, deadline, Some(&check));
+        last_check = Some(check);
+        if passed {
+            break AutoOutcome::Completed;
+        }
+    };
+
+    AutoReport {
+        outcome,
+        turns,
+        elapsed: started.elapsed(),
+        usage: conversation.token_usage(),
+        changes,
+        last_check,
+        last_reply,
+    }
+}
+
+fn initial_prompt(options: &AutoOptions) -> String {
+    let finish = match &options.check {
+        Some(command) => format!(
+            "The goal is met when `{command}` exits successfully; it runs after each of your turns."
+        ),
+        None => format!("When the goal is met, end your reply with `{DONE_MARKER}` on its own."),
+    };
+    format!(
+        "You are running unattended: nobody will read your replies until the run ends, and tool \
+         calls that need approval are denied. Work toward the goal below on your own, within {} \
+         turns and {} minutes. {finish} If you cannot make progress without a person, end your \
+         reply with `{BLOCKED_MARKER}: <reason>` instead.\n\nGoal:\n{}",
+        options.max_turns,
+        options.max_time.as_secs() / 60,
+        options.goal.trim()
+    )
+}
+
+fn continue_prompt(
+    turns: usize,
+    options: &AutoOptions,
+    deadline: Instant,
+    check: Option<&CheckResult>,
+) -> String {
+    let left = deadline.saturating_duration_since(Instant::now()).as_secs() / 60;
+    let status = match (check, &options.check) {
+        (Some(check), Some(command)) => format!(
+            "`{command}` still fails:\n```\n{}\n```",
+            check.output.trim_end()
+        ),
+        _ => format!("You have not replied `{DONE_MARKER}` yet."),
+    };
+    format!(
+        "{status}\n\nContinue toward the goal ({} of {} turns left, about {left} minutes).",
+        options.max_turns - turns,
+        options.max_turns
+    )
+}
+
+/// The rest of the first line starting with `marker`, trimmed of `:`.
+fn marker_line(reply: &str, marker: &str) -> Option<String> {
+    reply.lines().find_map(|line| {
+        let rest = line.trim().trim_matches('`').strip_prefix(marker)?;
+        Some(rest.trim_start_matches(':').trim().to_string())
+    })
+}
+
+async fn run_check(command: &str, working_dir: &Path) -> Result<CheckResult> {
+    let output = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(working_dir)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .with_context(|| format!("failed to run check `{command}`"))?;
+    let mut combined = String::from_utf8_lossy(&output.stdout).into_owned();
+    combined.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok(CheckResult {
+        passed: output.status.success(),
+        output: tail_chars(&combined, CHECK_OUTPUT_TAIL_CHARS),
+    })
+}
+
+fn tail_chars(text: &str, max_chars: usize) -> String {
+    let count = text.chars().count();
+    if count <= max_chars {
+        return text.to_string();
+    }
+    let tail: String = text.chars().skip(count - max_chars).collect();
+    format!("...{tail}")
+}
+
+/// The Markdown report printed when the run ends.
+pub fn render_auto_report(options: &AutoOptions, report: &AutoReport) -> String {
+    let mut lines = vec![
+        "# vex auto report".to_string(),
+        String::new(),
+        format!("- Goal: {}", options.goal.trim()),
+        format!("- Outcome: {}", report.outcome.describe()),
+        format!("- Turns: {} of {}", report.turns, options.max_turns),
+        format!(
+            "- Elapsed: {}m {:02}s of {}m",
+            report.elapsed.as_secs() / 60,
+            report.elapsed.as_secs() % 60,
+            options.max_time.as_secs() / 60
+        ),
+        format!(
+            "- Tokens: {} in, {} out",
+            report.usage.input_tokens, report.usage.output_tokens
+        ),
+    ];
+    if let (Some(command), Some(check)) = (&options.check, &report.last_check) {
+        lines.push(format!(
+            "- Check: `{command}` {}",
+            if check.passed { "passed" } else { "failed" }
+        ));
+    }
+
+    lines.extend([String::new(), "## Changes".to_string(), String::new()]);
+    if report.changes.is_empty() {
+        lines.push("No files changed and no commands ran.".to_string());
+    } else {
+        lines.push("```".to_string());
+        for summary in &report.changes {
+            lines.push(format!("turn {}", summary.turn));
+            lines.extend(summary.render_lines());
+        }
+        lines.push("```".to_string());
+    }
+
+    if let Some(check) = report.last_check.as_ref().filter(|check| !check.passed) {
+        lines.extend([
+            String::new(),
+            "## Last check output".to_string(),
+            String::new(),
+            "```".to_string(),
+            check.output.trim_end().to_string(),
+            "```".to_string(),
+        ]);
+    }
+
+    let reply = report.last_reply.trim();
+    if !reply.is_empty() {
+        lines.extend([String::new(), "## Final reply".to_string(), String::new()]);
+        let clipped: String = reply.chars().take(REPORT_REPLY_CHARS).collect();
+        lines.extend(clipped.lines().map(|line| format!("> {line}")));
+    }
+    lines.join("\n")
+}
+
+/// Runs `--auto` to completion, prints the report and writes it to
+/// `--report` when given. Fails when the goal was not met, so scripts can
+/// branch on the exit status.
+pub async fn run_auto_cli(
+    mut conversation: ConversationManager,
+    options: AutoOptions,
+    working_dir: &Path,
+) -> Result<()> {
+    eprintln!(
+        "auto: up to {} turns and {} minutes in {}",
+        options.max_turns,
+        options.max_time.as_secs() / 60,
+        working_dir.display()
+    );
+    let report = run_auto(&mut conversation, &options, working_dir).await;
+    let rendered = render_auto_report(&options, &report);
+    if let Some(path) = &options.report {
+        let path = working_dir.join(path);
+        fs::write(&path, format!("{rendered}\n"))
+            .with_context(|| format!("Failed to write report {}", path.display()))?;
+        eprintln!("auto: report written to {}", path.display());
+    }
+    println!("{rendered}");
+    if report.outcome != AutoOutcome::Completed {
+        bail!("auto run {}", report.outcome.describe());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{mock_client::MockApiClient, ApiClient};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn text_round(text: &str) -> Vec<String> {
+        vec![
+            r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_auto","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string(),
+            r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#.to_string(),
+            format!(
+                "event: content_block_delta\ndata: {}",
+                serde_json::json!({"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":text}})
+            ),
+            r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":4}}"#.to_string(),
+            r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+        ]
+    }
+
+    fn conversation(replies: &[&str]) -> ConversationManager {
+        let rounds = replies.iter().map(|reply| text_round(reply)).collect();
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(rounds)));
+        ConversationManager::new_mock(client, HashMap::new())
+    }
+
+    #[test]
+    fn test_take_splits_auto_flags_from_run_flags() {
+        let mut run = args(&[
+            "--auto",
+            "fix the build",
+            "--emit-patches",
+            "out",
+            "--max-turns",
+            "5",
+            "--check",
+            "cargo check",
+        ]);
+        let options = AutoOptions::take(&mut run).expect("parse").expect("auto");
+        assert_eq!(run, args(&["--emit-patches", "out"]));
+        assert_eq!(options.goal, "fix the build");
+        assert_eq!(options.check.as_deref(), Some("cargo check"));
+        assert_eq!(options.max_turns, 5);
+        assert_eq!(options.max_time, Duration::from_secs(30 * 60));
+
+        assert_eq!(
+            AutoOptions::take(&mut args(&["--check-key"])).expect("parse"),
+            None
+        );
+        assert!(AutoOptions::take(&mut args(&["--max-turns", "3"])).is_err());
+        assert!(AutoOptions::take(&mut args(&["--auto", "x", "--max-minutes", "0"])).is_err());
+        assert!(AutoOptions::take(&mut args(&["--auto"])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_reprompts_until_the_check_passes() {
+        let workspace = TempDir::new().expect("workspace");
+        let mut conversation = conversation(&["working on it", "created the marker"]);
+        let options = AutoOptions {
+            goal: "create ready.txt".to_string(),
+            check: Some("test -f ready.txt || { echo missing ready.txt; exit 1; }".to_string()),
+            max_time: Duration::from_secs(60),
+            max_turns: 3,
+            report: None,
+        };
+
+        // The mock model cannot write files, so the failing check and the
+        // prompt it produces are exercised directly.
+        let first = run_check(options.check.as_deref().unwrap(), workspace.path())
+            .await
+            .expect("check");
+        assert!(!first.passed);
+        assert!(first.output.contains("missing ready.txt"));
+        let prompt = continue_prompt(1, &options, Instant::now() + options.max_time, Some(&first));
+        assert!(prompt.contains("still fails:\n```\nmissing ready.txt\n```"));
+        assert!(prompt.contains("(2 of 3 turns left"));
+
+        fs::write(workspace.path().join("ready.txt"), "").expect("marker");
+        let report = run_auto(&mut conversation, &options, workspace.path()).await;
+        assert_eq!(report.outcome, AutoOutcome::Completed);
+        assert_eq!(report.turns, 1);
+        assert_eq!(report.last_reply, "working on it");
+
+        let rendered = render_auto_report(&options, &report);
+        assert!(rendered.contains("- Outcome: completed"));
+        assert!(rendered.contains("- Turns: 1 of 3"));
+        assert!(rendered.contains("- Check: `test -f ready.txt"));
+        assert!(rendered.contains("No files changed and no commands ran."));
+        assert!(rendered.ends_with("> working on it"));
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_on_markers_and_the_turn_limit() {
+        let workspace = TempDir::new().expect("workspace");
+        let options = AutoOptions {
+            goal: "tidy up".to_string(),
+            check: None,
+            max_time: Duration::from_secs(60),
+            max_turns: 2,
+            report: None,
+        };
+
+        let mut done = conversation(&["still going", "all tidy\nAUTO-DONE"]);
+        let report = run_auto(&mut done, &options, workspace.path()).await;
+        assert_eq!(report.outcome, AutoOutcome::Completed);
+        assert_eq!(report.turns, 2);
+        assert_eq!(report.usage.input_tokens, 20);
+
+        let mut blocked = conversation(&["AUTO-BLOCKED: needs a deploy key"]);
+        let report = run_auto(&mut blocked, &options, workspace.path()).await;
+        assert_eq!(
+            report.outcome,
+            AutoOutcome::Blocked("needs a deploy key".to_string())
+        );
+
+        let mut stuck = conversation(&["one", "two"]);
+        let report = run_auto(&mut stuck, &options, workspace.path()).await;
+        assert_eq!(report.outcome, AutoOutcome::TurnLimit);
+        assert_eq!(report.turns, 2);
+    }
+}
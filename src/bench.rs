@@ -0,0 +1,445 @@
+use crate::api::ApiClient;
+use crate::config::{resolve_model_alias, Config};
+use crate::state::ConversationManager;
+use crate::tools::ToolOperator;
+use crate::types::TokenUsage;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const SKIPPED_WORKSPACE_DIRS: &[&str] = &[".git", "target", "node_modules"];
+const MAX_REPORT_ANSWER_LINES: usize = 20;
+const MAX_REPORT_DIFF_LINES: usize = 200;
+
+pub const BENCH_USAGE: &str =
+    "usage: vex bench --task <task.md> --models <model[@api_url],...> [--out <report.md>]";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchModel {
+    pub label: String,
+    pub model: String,
+    pub api_url: Option<String>,
+}
+
+impl BenchModel {
+    /// Parses `label` or `label@api_url`. Short aliases (`sonnet`, `opus`,
+    /// `haiku`) expand to full Claude model ids.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (label, api_url) = match spec.split_once('@') {
+            Some((label, url)) => (label.trim(), Some(url.trim().to_string())),
+            None => (spec, None),
+        };
+        if label.is_empty() {
+            bail!("Empty model name in --models");
+        }
+        let model = resolve_model_alias(label);
+        Ok(Self {
+            label: label.to_string(),
+            model,
+            api_url,
+        })
+    }
+
+    fn config(&self, base: &Config, workspace: &Path) -> Config {
+        let mut config = base.clone();
+        config.model = self.model.clone();
+        if let Some(api_url) = &self.api_url {
+            config.api_url = api_url.clone();
+        }
+        config.working_dir = workspace.to_path_buf();
+        config
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchOptions {
+    pub task_path: PathBuf,
+    pub models: Vec<BenchModel>,
+    pub output: Option<PathBuf>,
+}
+
+impl BenchOptions {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut task_path = None;
+        let mut models = Vec::new();
+        let mut output = None;
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let mut value = |flag: &str| {
+                iter.next()
+                    .cloned()
+                    .with_context(|| format!("{flag} requires a value\n{BENCH_USAGE}"))
+            };
+            match arg.as_str() {
+                "--task" => task_path = Some(PathBuf::from(value("--task")?)),
+                "--models" => {
+                    for spec in value("--models")?.split(',') {
+                        if !spec.trim().is_empty() {
+                            models.push(BenchModel::parse(spec)?);
+                        }
+                    }
+                }
+                "--out" => output = Some(PathBuf::from(value("--out")?)),
+                other => bail!("Unknown bench argument '{other}'\n{BENCH_USAGE}"),
+            }
+        }
+
+        let task_path = task_path.with_context(|| format!("--task is required\n{BENCH_USAGE}"))?;
+        if models.is_empty() {
+            bail!("--models requires at least one model\n{BENCH_USAGE}");
+        }
+        Ok(Self {
+            task_path,
+            models,
+            output,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffStat {
+    pub files: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub label: String,
+    pub model: String,
+    pub elapsed: Duration,
+    pub usage: TokenUsage,
+    pub diff_stat: DiffStat,
+    pub diff: String,
+    pub outcome: std::result::Result<String, String>,
+}
+
+/// Runs the task once per model, each in a fresh copy of the workspace, and
+/// returns a Markdown comparison report. Tool calls are auto-approved because
+/// every run is confined to its own throwaway workspace.
+pub async fn run_bench(config: &Config, options: &BenchOptions) -> Result<String> {
+    let task = fs::read_to_string(&options.task_path)
+        .with_context(|| format!("Failed to read task {}", options.task_path.display()))?;
+    if task.trim().is_empty() {
+        bail!("Task file {} is empty", options.task_path.display());
+    }
+
+    let mut results = Vec::new();
+    for model in &options.models {
+        eprintln!("bench: running {} ({})", model.label, model.model);
+        let workspace = tempfile::TempDir::new().context("Failed to create bench workspace")?;
+        prepare_workspace(&config.working_dir, workspace.path())?;
+
+        let model_config = model.config(config, workspace.path());
+        let client = model_config
+            .validate()
+            .and_then(|_| ApiClient::new(&model_config));
+        let result = match client {
+            Ok(client) => run_model(model, client, workspace.path(), &task).await,
+            Err(error) => failed_result(model, error.to_string()),
+        };
+        results.push(result);
+    }
+
+    Ok(render_bench_report(&options.task_path, &results))
+}
+
+async fn run_model(
+    model: &BenchModel,
+    client: ApiClient,
+    workspace: &Path,
+    task: &str,
+) -> BenchResult {
+    let mut conversation =
+        ConversationManager::new(client, ToolOperator::new(workspace.to_path_buf()));
+    let started = Instant::now();
+    let outcome = conversation
+        .send_message(task.to_string(), None)
+        .await
+        .map_err(|error| error.to_string());
+    let elapsed = started.elapsed();
+
+    let (diff_stat, diff) = match workspace_diff(workspace) {
+        Ok(diff) => diff,
+        Err(error) => (DiffStat::default(), format!("[diff unavailable: {error}]")),
+    };
+    BenchResult {
+        label: model.label.clone(),
+        model: model.model.clone(),
+        elapsed,
+        usage: conversation.token_usage(),
+        diff_stat,
+        diff,
+        outcome,
+    }
+}
+
+fn failed_result(model: &BenchModel, error: String) -> BenchResult {
+    BenchResult {
+        label: model.label.clone(),
+        model: model.model.clone(),
+        elapsed: Duration::ZERO,
+        usage: TokenUsage::default(),
+        diff_stat: DiffStat::default(),
+        diff: String::new(),
+        outcome: Err(error),
+    }
+}
+
+/// Copies `source` into `dest` (skipping VCS and build output) and commits it
+/// as a baseline so the diff afterwards only reflects the model's changes.
+pub fn prepare_workspace(source: &Path, dest: &Path) -> Result<()> {
+    copy_tree(source, dest)?;
+    run_git(dest, &["init", "-q"])?;
+    run_git(dest, &["add", "-A"])?;
+    run_git(
+        dest,
+        &[
+            "-c",
+            "user.name=vex-bench",
+            "-c",
+            "user.email=vex-bench@localhost",
+            "commit",
+            "-q",
+            "--allow-empty",
+            "--no-gpg-sign",
+            "-m",
+            "bench baseline",
+        ],
+    )?;
+    Ok(())
+}
+
+fn copy_tree(source: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+    for entry in
+        fs::read_dir(source).with_context(|| format!("Failed to read {}", source.display()))?
+    {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let name = entry.file_name();
+        let target = dest.join(&name);
+        if file_type.is_dir() {
+            if SKIPPED_WORKSPACE_DIRS
+                .iter()
+                .any(|skipped| name.to_str() == Some(*skipped))
+            {
+                continue;
+            }
+            copy_tree(&entry.path(), &target)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &target)
+                .with_context(|| format!("Failed to copy {}", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn workspace_diff(workspace: &Path) -> Result<(DiffStat, String)> {
+    run_git(workspace, &["add", "-A"])?;
+    let numstat = run_git(workspace, &["diff", "--cached", "--numstat"])?;
+    let mut stat = DiffStat::default();
+    for line in numstat.lines() {
+        let mut columns = line.split('\t');
+        let insertions = columns.next().and_then(|value| value.parse::<usize>().ok());
+        let deletions = columns.next().and_then(|value| value.parse::<usize>().ok());
+        stat.files += 1;
+        stat.insertions += insertions.unwrap_or(0);
+        stat.deletions += deletions.unwrap_or(0);
+    }
+    let diff = run_git(workspace, &["diff", "--cached"])?;
+    Ok((stat, diff))
+}
+
+fn run_git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .context("Failed to execute git command")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub fn render_bench_report(task_path: &Path, results: &[BenchResult]) -> String {
+    let mut report = format!("# Bench: {}\n\n", task_path.display());
+    report.push_str(
+        "| Model | Status | Time (s) | Input tokens | Output tokens | Files | +/- |\n\
+         |---|---|---:|---:|---:|---:|---|\n",
+    );
+    for result in results {
+        let status = if result.outcome.is_ok() {
+            "ok"
+        } else {
+            "error"
+        };
+        report.push_str(&format!(
+            "| {} | {status} | {:.1} | {} | {} | {} | +{}/-{} |\n",
+            result.label,
+            result.elapsed.as_secs_f64(),
+            result.usage.input_tokens,
+            result.usage.output_tokens,
+            result.diff_stat.files,
+            result.diff_stat.insertions,
+            result.diff_stat.deletions,
+        ));
+    }
+
+    for result in results {
+        report.push_str(&format!("\n## {} (`{}`)\n\n", result.label, result.model));
+        match &result.outcome {
+            Ok(answer) => {
+                report.push_str("Final answer:\n\n");
+                report.push_str(&quote_lines(answer, MAX_REPORT_ANSWER_LINES));
+            }
+            Err(error) => report.push_str(&format!("Error: {error}\n")),
+        }
+        if !result.diff.trim().is_empty() {
+            report.push_str("\n```diff\n");
+            report.push_str(&head_lines(&result.diff, MAX_REPORT_DIFF_LINES));
+            report.push_str("\n```\n");
+        }
+    }
+    report
+}
+
+fn quote_lines(text: &str, max_lines: usize) -> String {
+    let body = head_lines(text.trim(), max_lines);
+    body.lines()
+        .map(|line| format!("> {line}\n"))
+        .collect::<String>()
+}
+
+fn head_lines(text: &str, max_lines: usize) -> String {
+    let lines: Vec<&str> = text.trim_end().lines().collect();
+    if lines.len() <= max_lines {
+        return lines.join("\n");
+    }
+    format!(
+        "{}\n[... {} more lines]",
+        lines[..max_lines].join("\n"),
+        lines.len() - max_lines
+    )
+}
+
+/// Entry point for `vex bench`: prints the report and optionally writes it.
+pub async fn run_bench_cli(config: Config, args: &[String]) -> Result<()> {
+    let options = BenchOptions::parse(args)?;
+    let report = run_bench(&config, &options).await?;
+    if let Some(output) = &options.output {
+        fs::write(output, &report)
+            .with_context(|| format!("Failed to write report {}", output.display()))?;
+        eprintln!("bench: report written to {}", output.display());
+    }
+    println!("{report}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::mock_client::MockApiClient;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_bench_options_expands_aliases_and_endpoints() {
+        let options = BenchOptions::parse(&args(&[
+            "--task",
+            "task.md",
+            "--models",
+            "sonnet, local/qwen@http://localhost:8000/v1/messages",
+        ]))
+        .expect("parse");
+
+        assert_eq!(options.task_path, PathBuf::from("task.md"));
+        assert_eq!(options.models[0].model, "claude-sonnet-4-5-20250929");
+        assert_eq!(options.models[1].label, "local/qwen");
+        assert_eq!(
+            options.models[1].api_url.as_deref(),
+            Some("http://localhost:8000/v1/messages")
+        );
+        assert!(BenchOptions::parse(&args(&["--models", "opus"])).is_err());
+        assert!(BenchOptions::parse(&args(&["--task", "t.md", "--bogus"])).is_err());
+    }
+
+    #[test]
+    fn test_prepared_workspace_diff_reports_only_new_changes() {
+        let source = TempDir::new().expect("source");
+        fs::write(source.path().join("a.txt"), "one\n").expect("seed");
+        fs::create_dir(source.path().join("target")).expect("target dir");
+        fs::write(source.path().join("target/big.bin"), "skip").expect("seed target");
+
+        let workspace = TempDir::new().expect("workspace");
+        prepare_workspace(source.path(), workspace.path()).expect("prepare");
+        assert!(!workspace.path().join("target").exists());
+        assert_eq!(
+            workspace_diff(workspace.path()).expect("diff").0,
+            DiffStat::default()
+        );
+
+        fs::write(workspace.path().join("a.txt"), "one\ntwo\n").expect("edit");
+        let (stat, diff) = workspace_diff(workspace.path()).expect("diff");
+        assert_eq!(
+            stat,
+            DiffStat {
+                files: 1,
+                insertions: 1,
+                deletions: 0
+            }
+        );
+        assert!(diff.contains("+two"));
+    }
+
+    #[tokio::test]
+    async fn test_run_model_records_usage_and_report_row() {
+        let source = TempDir::new().expect("source");
+        let workspace = TempDir::new().expect("workspace");
+        prepare_workspace(source.path(), workspace.path()).expect("prepare");
+        let round = vec![
+            r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_bench","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":12,"output_tokens":1}}}"#,
+            r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"done"}}"#,
+            r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":5}}"#,
+            r#"event: message_stop
+data: {"type":"message_stop"}"#,
+        ]
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(vec![round])));
+        let model = BenchModel::parse("mock").expect("model");
+
+        let result = run_model(&model, client, workspace.path(), "say done").await;
+        assert_eq!(result.outcome.as_deref(), Ok("done"));
+        assert_eq!(
+            result.usage,
+            TokenUsage {
+                input_tokens: 12,
+                output_tokens: 5
+            }
+        );
+
+        let report = render_bench_report(Path::new("task.md"), &[result]);
+        assert!(report.contains("| mock | ok |"));
+        assert!(report.contains("| 12 | 5 | 0 | +0/-0 |"));
+        assert!(report.contains("> done"));
+    }
+}
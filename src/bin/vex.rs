@@ -1,17 +1,56 @@
 use anyhow::Result;
+use crossterm::cursor::{RestorePosition, SavePosition};
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::queue;
+use ratatui::backend::Backend;
 use ratatui::widgets::Clear;
+use std::collections::VecDeque;
+use std::io::{IsTerminal, Write};
 use std::time::{Duration, Instant};
-use vexcoder::app::{build_runtime, TuiMode};
+use vexcoder::api::ApiClient;
+use vexcoder::app::{build_runtime, build_tool_operator, TuiMode};
+use vexcoder::audit::AuditLog;
+use vexcoder::autonomous::{deny_unattended, run_auto_cli, AutoOptions};
+use vexcoder::bench::run_bench_cli;
 use vexcoder::config::Config;
+use vexcoder::debug_parse::run_debug_parse_cli;
+use vexcoder::first_run::{first_run_pending, run_first_run};
+use vexcoder::hooks::run_trust_hooks_cli;
+use vexcoder::mirror::{run_mirror_cli, MirrorServer};
+use vexcoder::print_mode::{deny_headless, run_print_cli, PrintOptions};
+use vexcoder::profiles::{active_profile_name, default_profile_name, take_profile_arg, Profile};
+use vexcoder::releases::run_self_update_cli;
 use vexcoder::runtime::frontend::{FrontendAdapter, ScrollAction, ScrollTarget, UserInputEvent};
-use vexcoder::terminal;
+use vexcoder::runtime::mode::RuntimeMode;
+use vexcoder::runtime::r#loop::ShutdownOutcome;
+use vexcoder::sandbox;
+use vexcoder::share::run_open_bundle_cli;
+use vexcoder::state::{ApprovalHandler, ConversationManager};
+use vexcoder::state_dirs::StateDirs;
+use vexcoder::status_server::StatusServer;
+use vexcoder::supervisor;
+use vexcoder::terminal::graphics::{detect_graphics, ImageFrame, InlineImageRenderer};
+use vexcoder::terminal::input::{InputQueue, TerminalInput};
+use vexcoder::terminal::{self, TerminalSupport};
+use vexcoder::tools::git_repository_found;
 use vexcoder::ui::editor::{InputAction, InputEditor};
-use vexcoder::ui::layout::split_three_pane_layout;
+use vexcoder::ui::help::render_help_text;
+use vexcoder::ui::hyperlinks::{hyperlinks_enabled, osc8_open, DrawnLink, LinkFinder, OSC8_CLOSE};
+use vexcoder::ui::inline_images::find_placements;
+use vexcoder::ui::layout::{
+    footer_widget_columns, split_follow_ups, split_input_footer, split_three_pane_layout,
+};
+use vexcoder::ui::plain::{
+    render_patch_approval_prompt, render_picker_prompt, render_tool_approval_prompt,
+    PlainTranscript, SeparatorStyle,
+};
+use vexcoder::ui::prompt_history::render_prompt_history_rows;
 use vexcoder::ui::render::{
-    history_content_width_for_area, input_visual_rows, render_input, render_messages,
-    render_overlay_modal, render_status_line, OverlayModal,
+    history_content_width_for_area, input_pane_rows, render_follow_ups, render_footer_widgets,
+    render_help_overlay, render_input, render_messages_cached, render_overlay_modal,
+    render_status_line, HistoryRenderCache, InputViewport, OverlayModal,
 };
+use vexcoder::usage::run_usage_cli;
 
 const STARTUP_NOISE_GUARD: Duration = Duration::from_secs(15);
 
@@ -64,25 +103,117 @@ fn looks_like_terminal_transcript(text: &str) -> bool {
     signature_hits >= 2 || (signature_hits >= 1 && numbered_lines >= 2)
 }
 
+/// Keys that edit the input draft while a tool approval is open and the
+/// prompt does not have focus. Approval hotkeys are not among them: typed
+/// text such as "make a change" must never answer the prompt.
+fn is_draft_key(key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Char(_) => {
+            !key.modifiers.contains(KeyModifiers::CONTROL)
+                && !key.modifiers.contains(KeyModifiers::ALT)
+        }
+        KeyCode::Backspace | KeyCode::Delete | KeyCode::Left | KeyCode::Right => true,
+        _ => false,
+    }
+}
+
+/// Alt plus an approval hotkey answers the prompt even while typing goes to
+/// the draft.
+fn approval_hotkey(key: KeyEvent) -> Option<char> {
+    match key.code {
+        KeyCode::Char(ch)
+            if key.modifiers.contains(KeyModifiers::ALT)
+                && !key.modifiers.contains(KeyModifiers::CONTROL)
+                && TuiMode::is_tool_approval_answer(&ch.to_string()) =>
+        {
+            Some(ch)
+        }
+        _ => None,
+    }
+}
+
 struct ManagedTuiFrontend {
     terminal: terminal::TerminalType,
     quit: bool,
     editor: InputEditor,
+    input_viewport: InputViewport,
     started_at: Instant,
+    mirror: Option<MirrorServer>,
+    links: Option<LinkFinder>,
+    images: Option<InlineImageRenderer>,
+    input: InputQueue,
+    history_cache: HistoryRenderCache,
+    /// Set when a terminal event changed only frontend state (the draft, a
+    /// resize), which the runtime loop cannot see.
+    redraw_requested: bool,
+    /// Tab moves focus from the draft to an open tool approval prompt; only
+    /// then do unmodified keys answer it.
+    approval_focus: bool,
 }
 
 impl ManagedTuiFrontend {
-    fn new() -> Result<Self> {
+    fn new(
+        mirror: Option<MirrorServer>,
+        links: Option<LinkFinder>,
+        images: Option<InlineImageRenderer>,
+    ) -> Result<Self> {
         let terminal = terminal::setup()?;
+        // Replies to setup escapes are already buffered; drop them before the
+        // input reader starts.
         Self::drain_startup_events();
         Ok(Self {
             terminal,
+            input: InputQueue::events(),
             quit: false,
             editor: InputEditor::new(),
+            input_viewport: InputViewport::default(),
             started_at: Instant::now(),
+            mirror,
+            links,
+            images,
+            history_cache: HistoryRenderCache::default(),
+            redraw_requested: false,
+            approval_focus: false,
         })
     }
 
+    /// Draws each link's cells again inside OSC 8 escapes. The buffer itself
+    /// cannot hold them: ratatui would count the escape as visible width.
+    fn draw_hyperlinks(
+        terminal: &mut terminal::TerminalType,
+        links: &[DrawnLink],
+    ) -> std::io::Result<()> {
+        let backend = terminal.backend_mut();
+        queue!(backend, SavePosition)?;
+        for link in links {
+            write!(backend, "{}", osc8_open(&link.url))?;
+            backend.draw(link.cells.iter().map(|(x, y, cell)| (*x, *y, cell)))?;
+            write!(backend, "{OSC8_CLOSE}")?;
+        }
+        queue!(backend, RestorePosition)?;
+        Backend::flush(backend)
+    }
+
+    /// Brings the images on screen in line with the placeholders drawn this
+    /// frame. Terminals that draw images into cells need a full repaint to
+    /// move or remove one.
+    fn draw_images(&mut self, frame: ImageFrame) {
+        match frame {
+            ImageFrame::Unchanged => {}
+            ImageFrame::Draw(escapes) => {
+                let backend = self.terminal.backend_mut();
+                let _ = queue!(backend, SavePosition)
+                    .and_then(|()| write!(backend, "{escapes}"))
+                    .and_then(|()| queue!(backend, RestorePosition))
+                    .and_then(|()| Backend::flush(backend));
+            }
+            ImageFrame::Repaint => {
+                let _ = self.terminal.clear();
+                self.redraw_requested = true;
+            }
+        }
+    }
+
     fn drain_startup_events() {
         for _ in 0..1024 {
             match event::poll(Duration::from_millis(0)) {
@@ -170,6 +301,14 @@ impl ManagedTuiFrontend {
         }
     }
 
+    fn map_history_picker_key(&mut self, key: KeyEvent) -> Option<UserInputEvent> {
+        match key.code {
+            KeyCode::Enter => Some(UserInputEvent::Text("enter".to_string())),
+            KeyCode::Backspace => Some(UserInputEvent::Text("backspace".to_string())),
+            _ => self.map_overlay_key(key),
+        }
+    }
+
     fn map_regular_key(&mut self, key: KeyEvent) -> Option<UserInputEvent> {
         match key.code {
             KeyCode::PageUp => Some(UserInputEvent::Scroll {
@@ -180,6 +319,14 @@ impl ManagedTuiFrontend {
                 target: ScrollTarget::History,
                 action: ScrollAction::PageDown(10),
             }),
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.input_viewport.scroll_up();
+                None
+            }
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.input_viewport.scroll_down();
+                None
+            }
             KeyCode::Up if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 Some(UserInputEvent::Scroll {
                     target: ScrollTarget::History,
@@ -204,7 +351,18 @@ impl ManagedTuiFrontend {
                     action: ScrollAction::End,
                 })
             }
+            KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(UserInputEvent::Text("/thinking toggle".to_string()))
+            }
+            KeyCode::Char(digit @ '1'..='3') if key.modifiers.contains(KeyModifiers::ALT) => {
+                Some(UserInputEvent::Text(format!("/followup {digit}")))
+            }
+            KeyCode::F(1) => Some(UserInputEvent::Text("/help".to_string())),
+            KeyCode::Char('?') if self.editor.buffer().is_empty() => {
+                Some(UserInputEvent::Text("/help".to_string()))
+            }
             _ => {
+                self.input_viewport.follow_cursor();
                 let action = self.editor.apply_key(key);
                 self.map_editor_action(action)
             }
@@ -225,32 +383,60 @@ impl FrontendAdapter<TuiMode> for ManagedTuiFrontend {
             return None;
         }
 
-        let Ok(has_event) = event::poll(Duration::from_millis(16)) else {
-            self.quit = true;
-            return None;
+        let ev = match self.input.try_next()? {
+            TerminalInput::Event(ev) => ev,
+            TerminalInput::Closed => {
+                self.quit = true;
+                return None;
+            }
+            TerminalInput::Line(_) | TerminalInput::Interrupt => return None,
         };
-        if !has_event {
-            return None;
+        self.redraw_requested = true;
+        let tool_prompt_open = mode.pending_tool_overlay().is_some()
+            && mode.pending_patch_overlay().is_none()
+            && !mode.approval_reason_entry_active();
+        if !tool_prompt_open {
+            self.approval_focus = false;
         }
 
-        let Ok(ev) = event::read() else {
-            self.quit = true;
-            return None;
-        };
-
         match ev {
             Event::Key(key) => {
                 if key.kind == KeyEventKind::Release {
                     return None;
                 }
-                if mode.overlay_active() {
+                if mode.overlay_active() && !mode.approval_reason_entry_active() {
+                    if tool_prompt_open {
+                        if key.code == KeyCode::Tab {
+                            self.approval_focus = !self.approval_focus;
+                            return None;
+                        }
+                        if let Some(ch) = approval_hotkey(key) {
+                            return Some(UserInputEvent::Text(ch.to_string()));
+                        }
+                        if !self.approval_focus && is_draft_key(key) {
+                            // Typed-ahead text stays in the input draft while
+                            // the approval prompt is open.
+                            self.input_viewport.follow_cursor();
+                            self.editor.apply_key(key);
+                            return None;
+                        }
+                    }
                     self.map_overlay_key(key)
+                } else if mode.history_picker_active()
+                    || mode.preset_picker_active()
+                    || mode.session_picker_active()
+                    || mode.help_active()
+                {
+                    self.map_history_picker_key(key)
                 } else {
                     self.map_regular_key(key)
                 }
             }
             Event::Paste(text) => {
-                if mode.overlay_active() {
+                let approval_answer = mode.pending_patch_overlay().is_some()
+                    || (self.approval_focus && TuiMode::is_tool_approval_answer(&text));
+                if mode.overlay_active() && !mode.approval_reason_entry_active() && approval_answer
+                {
                     let trimmed = text.trim();
                     if trimmed.is_empty() {
                         None
@@ -261,6 +447,10 @@ impl FrontendAdapter<TuiMode> for ManagedTuiFrontend {
                     if self.should_ignore_startup_paste(&text) {
                         return None;
                     }
+                    if !mode.overlay_active() && mode.paste_needs_confirmation(&text) {
+                        return Some(UserInputEvent::LargePaste(text));
+                    }
+                    self.input_viewport.follow_cursor();
                     self.editor.insert_str(&text);
                     None
                 }
@@ -270,15 +460,45 @@ impl FrontendAdapter<TuiMode> for ManagedTuiFrontend {
     }
 
     fn render(&mut self, mode: &TuiMode) {
+        if let Some(prefill) = mode.take_editor_prefill() {
+            self.editor.replace_buffer(&prefill);
+        }
+        if let Some(text) = mode.take_paste_insert() {
+            self.input_viewport.follow_cursor();
+            self.editor.insert_str(&text);
+        }
+        if let Some(escape) = mode.take_clipboard_escape() {
+            // The terminal sets its clipboard from the escape; nothing is drawn.
+            let mut stdout = std::io::stdout().lock();
+            let _ = stdout.write_all(escape.as_bytes());
+            let _ = stdout.flush();
+        }
         let input = self.editor.buffer().to_string();
         let cursor = self.editor.cursor();
+        if let Some(mirror) = &self.mirror {
+            mirror.publish(mode.history_lines(), &mode.status_line());
+        }
+
+        let widgets = mode.footer_widgets(Instant::now());
+        let follow_ups = mode.follow_ups();
 
-        let _ = self.terminal.draw(|frame| {
+        let mut history_area = ratatui::layout::Rect::default();
+        let drawn = self.terminal.draw(|frame| {
             let area = frame.area();
             frame.render_widget(Clear, area);
-            let input_width = area.width.saturating_sub(2).max(1) as usize;
-            let input_rows = input_visual_rows(&input, input_width).max(1) as u16;
+            let widget_columns = footer_widget_columns(area.width);
+            let input_width = area
+                .width
+                .saturating_sub(widget_columns)
+                .saturating_sub(2)
+                .max(1) as usize;
+            let input_rows = input_pane_rows(&input, input_width, area.height)
+                + u16::from(!follow_ups.is_empty());
             let panes = split_three_pane_layout(area, input_rows);
+            history_area = panes.history;
+            let (follow_up_area, input_pane) =
+                split_follow_ups(panes.input, !follow_ups.is_empty());
+            let (input_area, widget_area) = split_input_footer(input_pane, widget_columns);
             let history_width = history_content_width_for_area(mode.history_lines(), panes.history);
             mode.set_history_content_width(history_width);
 
@@ -286,52 +506,533 @@ impl FrontendAdapter<TuiMode> for ManagedTuiFrontend {
             let history_scroll = mode.history_scroll_offset();
 
             render_status_line(frame, panes.header, &status);
-            render_messages(frame, panes.history, mode.history_lines(), history_scroll);
-            render_input(frame, panes.input, &input, cursor);
+            let diff_style = mode.diff_style();
+            render_messages_cached(
+                frame,
+                panes.history,
+                mode.history_lines(),
+                history_scroll,
+                &diff_style,
+                &mut self.history_cache,
+            );
+            if let Some(follow_up_area) = follow_up_area {
+                render_follow_ups(frame, follow_up_area, follow_ups);
+            }
+            render_input(frame, input_area, &input, cursor, &mut self.input_viewport);
+            if let Some(widget_area) = widget_area {
+                render_footer_widgets(frame, widget_area, &widgets);
+            }
 
-            if let Some((patch_preview, scroll_offset)) = mode.pending_patch_overlay() {
+            if let Some((lines, chars, text)) = mode.pending_paste_overlay() {
+                render_overlay_modal(frame, OverlayModal::PasteConfirm { lines, chars, text });
+            } else if let Some((patch_preview, scroll_offset)) = mode.pending_patch_overlay() {
                 render_overlay_modal(
                     frame,
                     OverlayModal::PatchApprove {
                         patch_preview,
                         scroll_offset,
                         viewport_rows: panes.history.height.max(1) as usize,
+                        diff_style,
                     },
                 );
-            } else if let Some((tool_name, input_preview, auto_approve_enabled)) =
-                mode.pending_tool_overlay()
-            {
+            } else if let Some(overlay) = mode.pending_tool_overlay() {
+                let queued = mode.queued_tool_calls();
                 render_overlay_modal(
                     frame,
                     OverlayModal::ToolPermission {
-                        tool_name,
-                        input_preview,
-                        auto_approve_enabled,
+                        tool_name: overlay.tool_name,
+                        input_preview: overlay.input_preview,
+                        class: overlay.class,
+                        paths: overlay.paths,
+                        auto_approve_enabled: overlay.auto_approve_enabled,
+                        reason_entry: overlay.reason_entry,
+                        prompt_focused: self.approval_focus,
+                        tier: overlay.tier,
+                        queued: &queued,
+                        diff_style,
+                    },
+                );
+            } else if let Some((query, filtering, rows, selected)) = mode.history_picker_overlay() {
+                render_overlay_modal(
+                    frame,
+                    OverlayModal::HistoryPicker {
+                        query,
+                        filtering,
+                        rows: &rows,
+                        selected,
+                    },
+                );
+            } else if let Some((presets, selected)) = mode.preset_picker_overlay() {
+                render_overlay_modal(frame, OverlayModal::PresetPicker { presets, selected });
+            } else if let Some((tag, filtering, rows, selected)) = mode.session_picker_overlay() {
+                render_overlay_modal(
+                    frame,
+                    OverlayModal::SessionPicker {
+                        tag,
+                        filtering,
+                        rows: &rows,
+                        selected,
                     },
                 );
+            } else if let Some((query, lines, scroll)) = mode.help_overlay() {
+                render_help_overlay(frame, query, &lines, scroll);
             }
         });
+        let Ok(frame) = drawn else {
+            return;
+        };
+        let placements = match &self.images {
+            Some(_) if mode.overlay_active() || mode.help_active() => Some(Vec::new()),
+            Some(_) => Some(find_placements(
+                frame.buffer,
+                history_area,
+                mode.inline_images(),
+            )),
+            None => None,
+        };
+        let found = self
+            .links
+            .as_mut()
+            .map(|links| links.buffer_links(frame.buffer))
+            .unwrap_or_default();
+        if !found.is_empty() {
+            let _ = Self::draw_hyperlinks(&mut self.terminal, &found);
+        }
+        if let (Some(placements), Some(images)) = (placements, self.images.as_mut()) {
+            let image_frame = images.frame(placements);
+            self.draw_images(image_frame);
+        }
+    }
+
+    fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    fn take_redraw_request(&mut self) -> bool {
+        std::mem::take(&mut self.redraw_requested)
+    }
+
+    fn poll_force_quit(&mut self) -> bool {
+        // Raw mode delivers Ctrl+C as a key, not a signal.
+        self.input.take_first(TerminalInput::is_interrupt).is_some()
+    }
+}
+
+/// Line-oriented frontend for terminals that cannot host the TUI: history is
+/// written sequentially with no cursor movement, colors, or footer, and input
+/// is read a line at a time from stdin.
+struct PlainFrontend {
+    transcript: PlainTranscript,
+    input: InputQueue,
+    queued: VecDeque<UserInputEvent>,
+    prompt_shown: Option<String>,
+    stdin_closed: bool,
+    quit: bool,
+    mirror: Option<MirrorServer>,
+    links: Option<LinkFinder>,
+}
+
+impl PlainFrontend {
+    fn new(reason: &str, mirror: Option<MirrorServer>, links: Option<LinkFinder>) -> Self {
+        println!("[plain output: {reason}; set VEX_TERMINAL=full to force the TUI]");
+        Self {
+            transcript: PlainTranscript::default(),
+            input: InputQueue::lines(),
+            queued: VecDeque::new(),
+            prompt_shown: None,
+            stdin_closed: false,
+            quit: false,
+            mirror,
+            links,
+        }
+    }
+
+    /// A bare number picks that picker row and Enter alone closes the
+    /// picker; anything else closes it and is submitted as typed. A number
+    /// past the last row is refused and the picker asked again.
+    fn queue_picker_line(&mut self, line: String, mode: &TuiMode) {
+        match picker_line_events(line, picker_row_count(mode)) {
+            Ok(events) => self.queued.extend(events),
+            Err(error) => {
+                let mut out = format!("[error] {error}\n");
+                if let Some((_, prompt)) = Self::pending_prompt(mode) {
+                    out.push_str(&prompt);
+                }
+                print!("{out}");
+                let _ = std::io::stdout().flush();
+            }
+        }
+    }
+
+    /// The prompt for whatever overlay or picker is waiting on input, keyed so
+    /// each one is written once.
+    fn pending_prompt(mode: &TuiMode) -> Option<(String, String)> {
+        if let Some((patch_preview, _)) = mode.pending_patch_overlay() {
+            return Some((
+                format!("patch:{patch_preview}"),
+                render_patch_approval_prompt(patch_preview),
+            ));
+        }
+        if let Some(overlay) = mode.pending_tool_overlay() {
+            return Some((
+                format!("tool:{}:{}", overlay.tool_name, overlay.input_preview),
+                render_tool_approval_prompt(overlay.tool_name, overlay.input_preview),
+            ));
+        }
+        if let Some((query, _, rows, _)) = mode.history_picker_overlay() {
+            let rows = render_prompt_history_rows(&rows)
+                .lines()
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            return Some((
+                format!("history:{query}"),
+                render_picker_prompt("prompt history", &rows),
+            ));
+        }
+        if let Some((presets, _)) = mode.preset_picker_overlay() {
+            let rows = presets
+                .iter()
+                .map(|preset| format!("{:<16}  {}", preset.name, preset.title))
+                .collect::<Vec<_>>();
+            return Some((
+                "presets".to_string(),
+                render_picker_prompt("presets", &rows),
+            ));
+        }
+        if let Some((tag, _, rows, _)) = mode.session_picker_overlay() {
+            return Some((
+                format!("sessions:{tag}"),
+                render_picker_prompt("saved sessions", &rows),
+            ));
+        }
+        if let Some((query, lines, _)) = mode.help_overlay() {
+            return Some((
+                format!("help:{query}"),
+                format!(
+                    "{}\n  type text to filter, or press Enter to close\n> ",
+                    render_help_text(&lines)
+                ),
+            ));
+        }
+        None
+    }
+}
+
+/// Rows in the open picker, for checking a typed row number.
+fn picker_row_count(mode: &TuiMode) -> usize {
+    if let Some((_, _, rows, _)) = mode.history_picker_overlay() {
+        rows.len()
+    } else if let Some((presets, _)) = mode.preset_picker_overlay() {
+        presets.len()
+    } else if let Some((_, _, rows, _)) = mode.session_picker_overlay() {
+        rows.len()
+    } else {
+        0
+    }
+}
+
+/// The picker events for a line typed at a picker with `rows` rows.
+fn picker_line_events(line: String, rows: usize) -> Result<Vec<UserInputEvent>, String> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        return Ok(vec![UserInputEvent::Text("esc".to_string())]);
+    }
+    if let Ok(number) = trimmed.parse::<usize>() {
+        if !(1..=rows).contains(&number) {
+            return Err(match rows {
+                0 => "there are no rows to choose".to_string(),
+                1 => format!("no row {number}; type 1 or press Enter to close"),
+                _ => format!("no row {number}; type 1 to {rows} or press Enter to close"),
+            });
+        }
+        let mut events = vec![overlay_scroll(ScrollAction::Home)];
+        if number > 1 {
+            events.push(overlay_scroll(ScrollAction::PageDown(number - 1)));
+        }
+        events.push(UserInputEvent::Text("enter".to_string()));
+        return Ok(events);
+    }
+    Ok(vec![
+        UserInputEvent::Text("esc".to_string()),
+        UserInputEvent::Text(line),
+    ])
+}
+
+fn overlay_scroll(action: ScrollAction) -> UserInputEvent {
+    UserInputEvent::Scroll {
+        target: ScrollTarget::Overlay,
+        action,
+    }
+}
+
+impl FrontendAdapter<TuiMode> for PlainFrontend {
+    fn poll_user_input(&mut self, mode: &TuiMode) -> Option<UserInputEvent> {
+        if mode.quit_requested() {
+            self.quit = true;
+            return None;
+        }
+        if let Some(event) = self.queued.pop_front() {
+            return Some(event);
+        }
+        let picker_active = mode.history_picker_active()
+            || mode.preset_picker_active()
+            || mode.session_picker_active();
+        if self.stdin_closed {
+            // With no more input, deny or close anything still waiting and
+            // exit once the current turn finishes.
+            if mode.overlay_active() || picker_active || mode.help_active() {
+                return Some(UserInputEvent::Text("esc".to_string()));
+            }
+            if !mode.is_turn_in_progress() {
+                self.quit = true;
+            }
+            return None;
+        }
+        match self.input.try_next()? {
+            TerminalInput::Line(line) => {
+                if picker_active && !mode.overlay_active() {
+                    self.queue_picker_line(line, mode);
+                    self.queued.pop_front()
+                } else if mode.help_active() && line.trim().is_empty() {
+                    Some(UserInputEvent::Text("esc".to_string()))
+                } else {
+                    Some(UserInputEvent::Text(line))
+                }
+            }
+            TerminalInput::Interrupt => Some(UserInputEvent::Interrupt),
+            TerminalInput::Closed => {
+                self.stdin_closed = true;
+                None
+            }
+            TerminalInput::Event(_) => None,
+        }
+    }
+
+    fn render(&mut self, mode: &TuiMode) {
+        if let Some(mirror) = &self.mirror {
+            mirror.publish(mode.history_lines(), &mode.status_line());
+        }
+        let mut out = self
+            .transcript
+            .update(mode.history_lines_dropped(), mode.history_lines());
+        if let Some(prefill) = mode.take_editor_prefill() {
+            out.push_str(self.transcript.line_break());
+            out.push_str(&format!(
+                "[prefilled input; edit and send it as your next message]\n{prefill}\n"
+            ));
+        }
+        if let Some(escape) = mode.take_clipboard_escape() {
+            // Only a terminal understands the escape; a pipe would record it.
+            if std::io::stdout().is_terminal() {
+                out.push_str(&escape);
+            }
+        }
+        match Self::pending_prompt(mode) {
+            Some((key, prompt)) if self.prompt_shown.as_ref() != Some(&key) => {
+                out.push_str(self.transcript.line_break());
+                out.push_str(&prompt);
+                self.prompt_shown = Some(key);
+            }
+            Some(_) => {}
+            None => self.prompt_shown = None,
+        }
+        if let Some(links) = self.links.as_mut() {
+            out = links.linkify(&out);
+        }
+        if !out.is_empty() {
+            let mut stdout = std::io::stdout().lock();
+            let _ = stdout.write_all(out.as_bytes());
+            let _ = stdout.flush();
+        }
     }
 
     fn should_quit(&self) -> bool {
         self.quit
     }
+
+    fn poll_force_quit(&mut self) -> bool {
+        self.input.take_first(TerminalInput::is_interrupt).is_some()
+    }
+}
+
+impl Drop for PlainFrontend {
+    fn drop(&mut self) {
+        print!("{}", self.transcript.line_break());
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// A conversation for runs with nobody at the terminal: calls that would
+/// prompt go to `deny`, and a sandbox lets tools run without approval.
+fn build_headless_conversation(
+    config: &Config,
+    deny: impl ApprovalHandler + 'static,
+) -> Result<ConversationManager> {
+    let client = ApiClient::new(config)?.with_git_tools(git_repository_found(&config.working_dir));
+    let conversation = ConversationManager::new(client, build_tool_operator(config))
+        .with_audit_log(AuditLog::from_env(&config.working_dir))
+        .with_approval_handler(deny);
+    if sandbox::active_backend().is_some() {
+        conversation
+            .approval_policy()
+            .set_session_auto_approve(true);
+    }
+    Ok(conversation)
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let config = Config::load()?;
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if supervisor::take_supervised_flag(&mut args) {
+        let code = supervisor::run_supervised(&args).await?;
+        std::process::exit(code);
+    }
+    if sandbox::take_sandbox_flag(&mut args) {
+        let code = sandbox::run_sandboxed(&args).await?;
+        std::process::exit(code);
+    }
+    if let Some(name) = take_profile_arg(&mut args)?.or_else(active_profile_name) {
+        Profile::load(&name)?.apply();
+    } else if let Some(name) = default_profile_name() {
+        Profile::load(&name)?.apply_defaults();
+    }
+    let mut config = Config::load()?;
+    if args.first().map(String::as_str) == Some("bench") {
+        return run_bench_cli(config, &args[1..]).await;
+    }
+    if args.first().map(String::as_str) == Some("mirror") {
+        return run_mirror_cli(&args[1..]).await;
+    }
+    if args.first().map(String::as_str) == Some("debug-parse") {
+        return run_debug_parse_cli(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("open-bundle") {
+        return run_open_bundle_cli(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("self-update") {
+        return run_self_update_cli(&args[1..]).await;
+    }
+    if args.first().map(String::as_str) == Some("trust-hooks") {
+        return run_trust_hooks_cli(&config.working_dir, &args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("usage") {
+        return run_usage_cli(&config, &args[1..]);
+    }
+    let auto = AutoOptions::take(&mut args)?;
+    let print = PrintOptions::take(&mut args)?;
+    if auto.is_some() && print.is_some() {
+        anyhow::bail!("--auto and -p cannot be combined");
+    }
+    if auto.is_none()
+        && print.is_none()
+        && first_run_pending()
+        && std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal()
+    {
+        let mut stdin = std::io::stdin().lock();
+        if let Some(choice) = run_first_run(&mut stdin, &mut std::io::stdout()).await? {
+            config.api_url = choice.api_url;
+            config.model = choice.model;
+        }
+    }
+    config.apply_cli_args(&args)?;
     config.validate()?;
+    StateDirs::for_workspace(&config.working_dir).prepare()?;
+    if config.check_api_key {
+        ApiClient::new(&config)?.validate_api_key().await?;
+    }
+    if let Some(options) = auto {
+        let conversation = build_headless_conversation(&config, deny_unattended)?;
+        let _status =
+            StatusServer::from_env(&conversation.event_bus(), conversation.reader()).transpose()?;
+        return run_auto_cli(conversation, options, &config.working_dir).await;
+    }
+    if let Some(options) = print {
+        let conversation = build_headless_conversation(&config, deny_headless)?;
+        return run_print_cli(conversation, options, !std::io::stdin().is_terminal()).await;
+    }
 
     let (mut runtime, mut ctx) = build_runtime(config)?;
-    let mut frontend = ManagedTuiFrontend::new()?;
-    runtime.run(&mut frontend, &mut ctx).await;
-    Ok(())
+    let mirror = MirrorServer::from_env().transpose()?;
+    let _status = StatusServer::from_env(&ctx.events(), ctx.conversation_reader()).transpose()?;
+    let links = (std::io::stdout().is_terminal() && hyperlinks_enabled())
+        .then(|| LinkFinder::new(runtime.mode.workspace_root().to_path_buf()));
+    // Each frontend is dropped at the end of its arm, restoring the terminal
+    // before anything below is printed.
+    let report = match terminal::detect_support() {
+        TerminalSupport::Full => {
+            let images = std::io::stdout()
+                .is_terminal()
+                .then(detect_graphics)
+                .flatten()
+                .map(InlineImageRenderer::new);
+            runtime.mode.set_inline_images(images.is_some());
+            let mut frontend = ManagedTuiFrontend::new(mirror, links, images)?;
+            runtime.run(&mut frontend, &mut ctx).await
+        }
+        TerminalSupport::Plain(reason) => {
+            runtime.mode.set_turn_separators(SeparatorStyle::from_env());
+            let mut frontend = PlainFrontend::new(&reason, mirror, links);
+            runtime.run(&mut frontend, &mut ctx).await
+        }
+    };
+    if let Some(error) = &report.session_error {
+        eprintln!("[{error}]");
+    }
+    if report.undeleted_uploads > 0 {
+        eprintln!(
+            "[{} uploaded file(s) could not be deleted from the provider's Files API]",
+            report.undeleted_uploads
+        );
+    }
+    if report.abandoned_tools > 0 {
+        eprintln!(
+            "[exited with {} tool call(s) still running; their changes may be incomplete]",
+            report.abandoned_tools
+        );
+    }
+    match report.outcome {
+        ShutdownOutcome::Clean => Ok(()),
+        // Returning would drop the Tokio runtime, which waits for the
+        // abandoned blocking tool calls.
+        ShutdownOutcome::TimedOut => std::process::exit(0),
+        ShutdownOutcome::Forced => std::process::exit(130),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::looks_like_terminal_transcript;
+    use super::{
+        approval_hotkey, is_draft_key, looks_like_terminal_transcript, picker_line_events,
+        ScrollAction, ScrollTarget, UserInputEvent,
+    };
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    #[test]
+    fn typed_text_goes_to_the_draft_and_only_alt_answers_the_prompt() {
+        let key = |ch| KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE);
+        let alt = |ch| KeyEvent::new(KeyCode::Char(ch), KeyModifiers::ALT);
+        for ch in "make a change 1234yn".chars() {
+            assert!(is_draft_key(key(ch)), "{ch}");
+            assert_eq!(approval_hotkey(key(ch)), None, "{ch}");
+        }
+        assert!(is_draft_key(KeyEvent::new(
+            KeyCode::Backspace,
+            KeyModifiers::NONE
+        )));
+        assert_eq!(approval_hotkey(alt('1')), Some('1'));
+        assert_eq!(approval_hotkey(alt('a')), Some('a'));
+        assert_eq!(approval_hotkey(alt('x')), None);
+        assert!(!is_draft_key(alt('a')));
+        assert!(!is_draft_key(KeyEvent::new(
+            KeyCode::Char('c'),
+            KeyModifiers::CONTROL
+        )));
+        assert!(!is_draft_key(KeyEvent::new(
+            KeyCode::Enter,
+            KeyModifiers::NONE
+        )));
+    }
 
     #[test]
     fn transcript_detection_matches_following_view_dump() {
@@ -353,4 +1054,33 @@ mod tests {
         let input = "list files in this directory and summarize in one sentence";
         assert!(!looks_like_terminal_transcript(input));
     }
+
+    #[test]
+    fn picker_numbers_past_the_last_row_are_refused() {
+        let events = picker_line_events(" 3 ".to_string(), 3).expect("in range");
+        assert!(matches!(
+            events.as_slice(),
+            [
+                UserInputEvent::Scroll {
+                    target: ScrollTarget::Overlay,
+                    action: ScrollAction::Home,
+                },
+                UserInputEvent::Scroll {
+                    action: ScrollAction::PageDown(2),
+                    ..
+                },
+                UserInputEvent::Text(enter),
+            ] if enter == "enter"
+        ));
+        assert_eq!(
+            picker_line_events("4".to_string(), 3).err().as_deref(),
+            Some("no row 4; type 1 to 3 or press Enter to close")
+        );
+        assert!(picker_line_events("0".to_string(), 3).is_err());
+        assert!(matches!(
+            picker_line_events("fix it".to_string(), 3).expect("text").as_slice(),
+            [UserInputEvent::Text(esc), UserInputEvent::Text(text)]
+                if esc == "esc" && text == "fix it"
+        ));
+    }
 }
@@ -0,0 +1,230 @@
+use crate::edit_diff::diff_line_counts;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    Created,
+    Modified,
+    Deleted,
+    Renamed { from: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    /// Workspace-relative, with `/` separators.
+    pub path: String,
+    pub kind: FileChangeKind,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// What the tools of one turn changed, kept with the conversation and
+/// listed in the saved-session index.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TurnChangeSummary {
+    /// 1-based turn number within the session.
+    pub turn: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileChange>,
+    /// Commands the tools ran, e.g. `run_tests` or `git commit`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<String>,
+    /// Content of each changed path before the turn touched it.
+    #[serde(skip)]
+    originals: HashMap<String, Option<String>>,
+}
+
+/// Summaries are equal when they record the same changes; the originals
+/// kept while the turn runs are not part of the record.
+impl PartialEq for TurnChangeSummary {
+    fn eq(&self, other: &Self) -> bool {
+        self.turn == other.turn && self.files == other.files && self.commands == other.commands
+    }
+}
+
+impl Eq for TurnChangeSummary {}
+
+impl TurnChangeSummary {
+    pub fn new(turn: usize) -> Self {
+        Self {
+            turn,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.commands.is_empty()
+    }
+
+    /// Records `path` going from `before` to `after`, where `None` is a
+    /// missing file. Counts are taken against the path's content before its
+    /// first change in the turn, so repeated edits fold into one entry.
+    pub fn record_file(&mut self, path: &str, before: Option<&str>, after: Option<&str>) {
+        let original = self
+            .originals
+            .entry(path.to_string())
+            .or_insert_with(|| before.map(str::to_string))
+            .clone();
+        let index = self.files.iter().position(|file| file.path == path);
+        let renamed_from = index.and_then(|index| match &self.files[index].kind {
+            FileChangeKind::Renamed { from } => Some(from.clone()),
+            _ => None,
+        });
+        let kind = match (original.as_deref(), after) {
+            (None, None) => None,
+            (None, Some(_)) => Some(FileChangeKind::Created),
+            (Some(_), None) => Some(FileChangeKind::Deleted),
+            (Some(old), Some(new)) if old == new && renamed_from.is_none() => None,
+            (Some(_), Some(_)) => Some(match renamed_from {
+                Some(from) => FileChangeKind::Renamed { from },
+                None => FileChangeKind::Modified,
+            }),
+        };
+        let (removed, added) = diff_line_counts(
+            original.as_deref().unwrap_or_default(),
+            after.unwrap_or_default(),
+        );
+        match (index, kind) {
+            // Reverted, or created and deleted again: nothing to report.
+            (Some(index), None) => {
+                self.files.remove(index);
+            }
+            (Some(index), Some(kind)) => {
+                let entry = &mut self.files[index];
+                entry.kind = kind;
+                entry.added = added;
+                entry.removed = removed;
+            }
+            (None, Some(kind)) => self.files.push(FileChange {
+                path: path.to_string(),
+                kind,
+                added,
+                removed,
+            }),
+            (None, None) => {}
+        }
+    }
+
+    /// A file created earlier in the turn stays created under its new name.
+    pub fn record_rename(&mut self, from: &str, to: &str) {
+        if let Some(original) = self.originals.remove(from) {
+            self.originals.insert(to.to_string(), original);
+        }
+        match self.files.iter_mut().find(|file| file.path == from) {
+            Some(entry) => {
+                entry.path = to.to_string();
+                if entry.kind == FileChangeKind::Modified {
+                    entry.kind = FileChangeKind::Renamed {
+                        from: from.to_string(),
+                    };
+                }
+            }
+            None => self.files.push(FileChange {
+                path: to.to_string(),
+                kind: FileChangeKind::Renamed {
+                    from: from.to_string(),
+                },
+                added: 0,
+                removed: 0,
+            }),
+        }
+    }
+
+    pub fn record_command(&mut self, command: String) {
+        self.commands.push(command);
+    }
+
+    /// The block shown at the end of the turn.
+    pub fn render_lines(&self) -> Vec<String> {
+        self.lines_under("changes this turn")
+    }
+
+    /// The block for `/changes`, headed by the turn number.
+    pub fn history_lines(&self) -> Vec<String> {
+        self.lines_under(&format!("turn {}", self.turn))
+    }
+
+    fn lines_under(&self, heading: &str) -> Vec<String> {
+        let added: usize = self.files.iter().map(|file| file.added).sum();
+        let removed: usize = self.files.iter().map(|file| file.removed).sum();
+        let mut parts = Vec::new();
+        if !self.files.is_empty() {
+            let noun = if self.files.len() == 1 {
+                "file"
+            } else {
+                "files"
+            };
+            parts.push(format!("{} {noun} (+{added} -{removed})", self.files.len()));
+        }
+        if !self.commands.is_empty() {
+            let noun = if self.commands.len() == 1 {
+                "command"
+            } else {
+                "commands"
+            };
+            parts.push(format!("{} {noun}", self.commands.len()));
+        }
+        let mut lines = vec![format!("[{heading}: {}]", parts.join(", "))];
+        for file in &self.files {
+            let (verb, note) = match &file.kind {
+                FileChangeKind::Created => ("created", String::new()),
+                FileChangeKind::Modified => ("modified", String::new()),
+                FileChangeKind::Deleted => ("deleted", String::new()),
+                FileChangeKind::Renamed { from } => ("renamed", format!(" (from {from})")),
+            };
+            lines.push(format!(
+                "  {verb:<8} {}{note}  +{} -{}",
+                file.path, file.added, file.removed
+            ));
+        }
+        for command in &self.commands {
+            lines.push(format!("  {:<8} {command}", "ran"));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_fold_per_path_and_render_a_block() {
+        let mut summary = TurnChangeSummary::new(3);
+        summary.record_file("src/new.rs", None, Some("a\nb\n"));
+        summary.record_file("src/new.rs", Some("a\nb\n"), Some("a\nc\n"));
+        summary.record_file("src/lib.rs", Some("x\ny\n"), Some("x\nz\nw\n"));
+        summary.record_rename("src/lib.rs", "src/core.rs");
+        summary.record_file("tmp.txt", None, Some("t\n"));
+        summary.record_file("tmp.txt", Some("t\n"), None);
+        summary.record_command("git commit".to_string());
+
+        assert_eq!(
+            summary.files[0],
+            FileChange {
+                path: "src/new.rs".to_string(),
+                kind: FileChangeKind::Created,
+                added: 2,
+                removed: 0,
+            }
+        );
+        assert_eq!(summary.files.len(), 2);
+        assert_eq!(
+            summary.render_lines(),
+            [
+                "[changes this turn: 2 files (+4 -1), 1 command]",
+                "  created  src/new.rs  +2 -0",
+                "  renamed  src/core.rs (from src/lib.rs)  +2 -1",
+                "  ran      git commit",
+            ]
+        );
+
+        let restored: TurnChangeSummary =
+            serde_json::from_str(&serde_json::to_string(&summary).expect("serialize"))
+                .expect("deserialize");
+        assert_eq!(restored.files, summary.files);
+        assert_eq!(restored.commands, summary.commands);
+    }
+}
@@ -0,0 +1,628 @@
+use crate::tool_keys::{
+    first_str, CONTENT_KEYS, NEW_TEXT_KEYS, OLD_TEXT_KEYS, PATH_KEYS, SOURCE_PATH_KEYS,
+    TARGET_PATH_KEYS,
+};
+use crate::types::{ApiMessage, Content, ContentBlock};
+use crate::util::parse_bool_str;
+use std::collections::{HashMap, HashSet};
+
+pub const CLAIM_CHECK_ENV: &str = "VEX_CLAIM_CHECK";
+
+const ADD_VERBS: [&str; 6] = [
+    "added",
+    "created",
+    "introduced",
+    "implemented",
+    "wrote",
+    "defined",
+];
+const REMOVE_VERBS: [&str; 3] = ["removed", "deleted", "dropped"];
+const UPDATE_VERBS: [&str; 6] = [
+    "updated",
+    "changed",
+    "modified",
+    "edited",
+    "fixed",
+    "refactored",
+];
+/// Sentences with these describe intentions or suggestions, not work done.
+const HEDGES: [&str; 10] = [
+    "could ",
+    "should ",
+    "would ",
+    "might ",
+    "consider",
+    "not ",
+    "n't ",
+    "todo",
+    "next step",
+    "if you",
+];
+
+/// Claim checking runs after edit turns unless `VEX_CLAIM_CHECK` is off.
+pub fn claim_check_enabled_from_env() -> bool {
+    std::env::var(CLAIM_CHECK_ENV)
+        .ok()
+        .and_then(|value| parse_bool_str(&value))
+        .unwrap_or(true)
+}
+
+/// What the successful file tools of one turn changed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TurnChanges {
+    pub paths: Vec<String>,
+    pub renames: Vec<(String, String)>,
+    /// `old_str` of every edit.
+    pub removed_text: String,
+    /// `new_str` of every edit and the content of every write.
+    pub added_text: String,
+    /// Files replaced wholesale by write_file.
+    pub rewritten: Vec<Rewrite>,
+}
+
+/// One write_file: the file's content as last read or written earlier in the
+/// conversation, when known, and what replaced it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Rewrite {
+    pub before: Option<String>,
+    pub after: String,
+}
+
+impl TurnChanges {
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.renames.is_empty()
+    }
+
+    fn touches_path(&self, subject: &str) -> bool {
+        self.paths
+            .iter()
+            .chain(self.renames.iter().map(|(_, to)| to))
+            .any(|path| path_matches(path, subject))
+    }
+
+    fn adds(&self, subject: &str) -> bool {
+        self.added_text.contains(subject) || self.touches_path(subject)
+    }
+
+    fn removes(&self, subject: &str) -> bool {
+        self.removed_text.contains(subject)
+            || self
+                .renames
+                .iter()
+                .any(|(from, _)| path_matches(from, subject))
+            || self.rewritten.iter().any(|rewrite| {
+                rewrite
+                    .before
+                    .as_deref()
+                    .is_some_and(|before| before.contains(subject))
+                    && !rewrite.after.contains(subject)
+            })
+    }
+
+    fn renames_to(&self, from: &str, to: &str) -> bool {
+        self.renames
+            .iter()
+            .any(|(old, new)| path_matches(old, from) && path_matches(new, to))
+            || (self.removed_text.contains(from) && self.added_text.contains(to))
+    }
+}
+
+fn path_matches(path: &str, subject: &str) -> bool {
+    path == subject || path.ends_with(&format!("/{subject}"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Claim {
+    Added(String),
+    Removed(String),
+    Updated(String),
+    Renamed { from: String, to: String },
+}
+
+impl Claim {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Added(subject) => format!("added `{subject}`"),
+            Self::Removed(subject) => format!("removed `{subject}`"),
+            Self::Updated(subject) => format!("updated `{subject}`"),
+            Self::Renamed { from, to } => format!("renamed `{from}` to `{to}`"),
+        }
+    }
+
+    fn verified_by(&self, changes: &TurnChanges) -> bool {
+        match self {
+            Self::Added(subject) => changes.adds(subject),
+            Self::Removed(subject) => changes.removes(subject),
+            Self::Updated(subject) => {
+                changes.touches_path(subject)
+                    || changes.added_text.contains(subject.as_str())
+                    || changes.removed_text.contains(subject.as_str())
+            }
+            Self::Renamed { from, to } => changes.renames_to(from, to),
+        }
+    }
+
+    fn missing(&self) -> &'static str {
+        match self {
+            Self::Added(_) => "no change adds it",
+            Self::Removed(_) => "no change removes it",
+            Self::Updated(_) => "no change touches it",
+            Self::Renamed { .. } => "no rename or edit matches",
+        }
+    }
+}
+
+/// The successful write_file, edit_file, and rename_file calls in the
+/// newest turn of `messages`, and that turn's final assistant text.
+pub fn last_turn_changes(messages: &[ApiMessage]) -> (TurnChanges, String) {
+    let turn_start = messages
+        .iter()
+        .rposition(|message| message.role == "user" && !is_tool_result_message(message))
+        .map_or(0, |index| index + 1);
+    let turn = &messages[turn_start..];
+
+    let failed: HashSet<&str> = messages
+        .iter()
+        .flat_map(blocks)
+        .filter_map(|block| match block {
+            ContentBlock::ToolResult {
+                tool_use_id,
+                is_error: true,
+                ..
+            } => Some(tool_use_id.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    // What each file held at each point, from reads and earlier writes and
+    // edits, so a rewrite only verifies a removal of text it really dropped.
+    let mut known: HashMap<&str, String> = HashMap::new();
+    let mut reads: HashMap<&str, &str> = HashMap::new();
+    let mut changes = TurnChanges::default();
+    for (index, message) in messages.iter().enumerate() {
+        let in_turn = index >= turn_start;
+        for block in blocks(message) {
+            let (id, name, input) = match block {
+                ContentBlock::ToolUse { id, name, input } => (id, name, input),
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    is_error: false,
+                } => {
+                    if let Some(path) = reads.remove(tool_use_id.as_str()) {
+                        known.insert(path, content.clone());
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            if failed.contains(id.as_str()) {
+                continue;
+            }
+            match name.as_str() {
+                "read_file" => {
+                    if let Some(path) = first_string(input, &PATH_KEYS) {
+                        reads.insert(id, path);
+                    }
+                }
+                "write_file" => {
+                    let Some(path) = first_string(input, &PATH_KEYS) else {
+                        continue;
+                    };
+                    let content = first_string(input, &CONTENT_KEYS).unwrap_or_default();
+                    let before = known.insert(path, content.to_string());
+                    if in_turn {
+                        changes.paths.push(path.to_string());
+                        changes.added_text.push_str(content);
+                        changes.added_text.push('\n');
+                        changes.rewritten.push(Rewrite {
+                            before,
+                            after: content.to_string(),
+                        });
+                    }
+                }
+                "edit_file" => {
+                    let Some(path) = first_string(input, &PATH_KEYS) else {
+                        continue;
+                    };
+                    let old_text = first_string(input, &OLD_TEXT_KEYS).unwrap_or_default();
+                    let new_text = first_string(input, &NEW_TEXT_KEYS).unwrap_or_default();
+                    if let Some(content) = known.get_mut(path) {
+                        *content = content.replacen(old_text, new_text, 1);
+                    }
+                    if in_turn {
+                        changes.paths.push(path.to_string());
+                        changes.removed_text.push_str(old_text);
+                        changes.removed_text.push('\n');
+                        changes.added_text.push_str(new_text);
+                        changes.added_text.push('\n');
+                    }
+                }
+                "rename_file" if in_turn => {
+                    let from = first_string(input, &SOURCE_PATH_KEYS);
+                    let to = first_string(input, &TARGET_PATH_KEYS);
+                    if let (Some(from), Some(to)) = (from, to) {
+                        changes.renames.push((from.to_string(), to.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let final_text = turn
+        .iter()
+        .rev()
+        .find(|message| message.role == "assistant")
+        .map(|message| match &message.content {
+            Content::Text(text) => text.clone(),
+            Content::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        })
+        .unwrap_or_default();
+    (changes, final_text)
+}
+
+fn is_tool_result_message(message: &ApiMessage) -> bool {
+    blocks(message)
+        .iter()
+        .any(|block| matches!(block, ContentBlock::ToolResult { .. }))
+}
+
+fn blocks(message: &ApiMessage) -> &[ContentBlock] {
+    match &message.content {
+        Content::Blocks(blocks) => blocks,
+        Content::Text(_) => &[],
+    }
+}
+
+fn first_string<'a>(input: &'a serde_json::Value, keys: &[&str]) -> Option<&'a str> {
+    first_str(input, keys)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// Claims of finished work in `text`: a verb such as "added" or "renamed"
+/// followed by backticked names. Code blocks, questions, and hedged
+/// sentences ("you could add ...") are skipped.
+pub fn extract_claims(text: &str) -> Vec<Claim> {
+    let mut claims = Vec::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        for sentence in split_sentences(line) {
+            for claim in sentence_claims(sentence) {
+                if !claims.contains(&claim) {
+                    claims.push(claim);
+                }
+            }
+        }
+    }
+    claims
+}
+
+fn split_sentences(line: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut in_code = false;
+    for (index, ch) in line.char_indices() {
+        match ch {
+            '`' => in_code = !in_code,
+            ';' | '!' | '?' if !in_code => {
+                sentences.push(&line[start..=index]);
+                start = index + 1;
+            }
+            '.' if !in_code && line[index + 1..].starts_with(' ') => {
+                sentences.push(&line[start..=index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    sentences.push(&line[start..]);
+    sentences
+}
+
+fn sentence_claims(sentence: &str) -> Vec<Claim> {
+    let lower = sentence.to_lowercase();
+    if lower.trim_end().ends_with('?') || HEDGES.iter().any(|hedge| lower.contains(hedge)) {
+        return Vec::new();
+    }
+    let spans = code_spans(sentence);
+    let mut claims = Vec::new();
+    let mut index = 0;
+    while index < spans.len() {
+        let (start, subject) = spans[index];
+        index += 1;
+        // Lowercasing can change byte lengths ('İ', the Kelvin sign), so
+        // offsets into `sentence` are only used on `sentence` itself.
+        let Some(verb) = nearest_verb(&sentence[..start].to_lowercase()) else {
+            continue;
+        };
+        let claim = match verb {
+            Verb::Rename => {
+                let Some(&(next_start, to)) = spans.get(index) else {
+                    continue;
+                };
+                if !sentence[start..next_start].to_lowercase().contains(" to ") {
+                    continue;
+                }
+                index += 1;
+                Claim::Renamed {
+                    from: subject.to_string(),
+                    to: to.to_string(),
+                }
+            }
+            Verb::Add => Claim::Added(subject.to_string()),
+            Verb::Remove => Claim::Removed(subject.to_string()),
+            Verb::Update => Claim::Updated(subject.to_string()),
+        };
+        claims.push(claim);
+    }
+    claims
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verb {
+    Add,
+    Remove,
+    Update,
+    Rename,
+}
+
+/// The verb closest before the end of `prefix`.
+fn nearest_verb(prefix: &str) -> Option<Verb> {
+    let groups: [(&[&str], Verb); 4] = [
+        (&ADD_VERBS, Verb::Add),
+        (&REMOVE_VERBS, Verb::Remove),
+        (&UPDATE_VERBS, Verb::Update),
+        (&["renamed"], Verb::Rename),
+    ];
+    groups
+        .iter()
+        .flat_map(|(words, verb)| {
+            words
+                .iter()
+                .filter_map(move |word| find_word(prefix, word).map(|at| (at, *verb)))
+        })
+        .max_by_key(|(at, _)| *at)
+        .map(|(_, verb)| verb)
+}
+
+/// Byte offset of the last whole-word occurrence of `word`.
+fn find_word(text: &str, word: &str) -> Option<usize> {
+    text.rmatch_indices(word).map(|(at, _)| at).find(|&at| {
+        let before = text[..at].chars().next_back();
+        let after = text[at + word.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Backticked names and where they start. Call parentheses and a leading
+/// `fn ` are dropped so `parse()` matches `fn parse(`.
+fn code_spans(sentence: &str) -> Vec<(usize, &str)> {
+    let mut spans = Vec::new();
+    let mut rest = sentence;
+    let mut offset = 0;
+    while let Some(open) = rest.find('`') {
+        let Some(len) = rest[open + 1..].find('`') else {
+            break;
+        };
+        let inner = rest[open + 1..open + 1 + len].trim();
+        let inner = inner.strip_prefix("fn ").unwrap_or(inner);
+        let inner = inner.strip_suffix("()").unwrap_or(inner).trim();
+        if !inner.is_empty() {
+            spans.push((offset + open, inner));
+        }
+        let consumed = open + len + 2;
+        offset += consumed;
+        rest = &rest[consumed..];
+    }
+    spans
+}
+
+/// A verified/unverified line per claim in `text`, headed by a summary;
+/// empty when the turn changed no files or made no checkable claims.
+pub fn claim_annotations(changes: &TurnChanges, text: &str) -> Vec<String> {
+    if changes.is_empty() {
+        return Vec::new();
+    }
+    let claims = extract_claims(text);
+    if claims.is_empty() {
+        return Vec::new();
+    }
+    let checks: Vec<(bool, &Claim)> = claims
+        .iter()
+        .map(|claim| (claim.verified_by(changes), claim))
+        .collect();
+    let verified = checks.iter().filter(|(ok, _)| *ok).count();
+    let mut lines = vec![format!(
+        "[claim check] {verified} of {} claims match this turn's file changes",
+        checks.len()
+    )];
+    lines.extend(checks.into_iter().map(|(ok, claim)| {
+        if ok {
+            format!("  verified    {}", claim.describe())
+        } else {
+            format!("  unverified  {}: {}", claim.describe(), claim.missing())
+        }
+    }));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(role: &str, blocks: Vec<ContentBlock>) -> ApiMessage {
+        ApiMessage {
+            role: role.to_string(),
+            content: Content::Blocks(blocks),
+        }
+    }
+
+    fn tool_use(id: &str, name: &str, input: serde_json::Value) -> ContentBlock {
+        ContentBlock::ToolUse {
+            id: id.to_string(),
+            name: name.to_string(),
+            input,
+        }
+    }
+
+    fn tool_result(id: &str, is_error: bool) -> ContentBlock {
+        ContentBlock::ToolResult {
+            tool_use_id: id.to_string(),
+            content: "ok".to_string(),
+            is_error,
+        }
+    }
+
+    #[test]
+    fn test_claims_are_checked_against_the_last_turn() {
+        let messages = vec![
+            ApiMessage {
+                role: "user".to_string(),
+                content: Content::Text("rename parse and add a test".to_string()),
+            },
+            message(
+                "assistant",
+                vec![
+                    tool_use(
+                        "t1",
+                        "edit_file",
+                        json!({
+                            "path": "src/lib.rs",
+                            "old_str": "fn parse(input: &str)",
+                            "new_str": "fn parse_line(input: &str)",
+                        }),
+                    ),
+                    tool_use(
+                        "t2",
+                        "edit_file",
+                        json!({
+                            "path": "src/lib.rs",
+                            "old_str": "mod tests {",
+                            "new_str": "mod tests {\n    fn test_empty() {}",
+                        }),
+                    ),
+                ],
+            ),
+            message("user", vec![tool_result("t1", false), tool_result("t2", true)]),
+            message(
+                "assistant",
+                vec![ContentBlock::Text {
+                    text: "Renamed `parse` to `parse_line` and added `test_empty()`. I also updated `README.md`.\n```rust\nadded `ignored`\n```\nYou could add `bench_parse` next.".to_string(),
+                }],
+            ),
+        ];
+
+        let (changes, text) = last_turn_changes(&messages);
+        assert_eq!(changes.paths, vec!["src/lib.rs"]);
+        assert_eq!(
+            claim_annotations(&changes, &text),
+            vec![
+                "[claim check] 1 of 3 claims match this turn's file changes",
+                "  verified    renamed `parse` to `parse_line`",
+                "  unverified  added `test_empty`: no change adds it",
+                "  unverified  updated `README.md`: no change touches it",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_renames_and_removals_match_tool_calls() {
+        let changes = TurnChanges {
+            paths: vec!["src/util.rs".to_string()],
+            renames: vec![("src/old.rs".to_string(), "src/new.rs".to_string())],
+            removed_text: "fn legacy() {}\n".to_string(),
+            added_text: String::new(),
+            rewritten: Vec::new(),
+        };
+        let lines = claim_annotations(
+            &changes,
+            "Moved things: renamed `old.rs` to `new.rs`, deleted `legacy`, and removed `helper`.",
+        );
+        assert_eq!(
+            lines[0],
+            "[claim check] 2 of 3 claims match this turn's file changes"
+        );
+        assert_eq!(
+            lines[3],
+            "  unverified  removed `helper`: no change removes it"
+        );
+        assert!(claim_annotations(&TurnChanges::default(), "added `x`").is_empty());
+    }
+
+    #[test]
+    fn test_rewrites_verify_only_removals_of_text_they_dropped() {
+        let messages = vec![
+            ApiMessage {
+                role: "user".to_string(),
+                content: Content::Text("clean up İstanbul.rs, KELVİN K".to_string()),
+            },
+            message(
+                "assistant",
+                vec![tool_use("r1", "read_file", json!({ "path": "src/a.rs" }))],
+            ),
+            message(
+                "user",
+                vec![ContentBlock::ToolResult {
+                    tool_use_id: "r1".to_string(),
+                    content: "fn kept() {}\nfn legacy() {}".to_string(),
+                    is_error: false,
+                }],
+            ),
+            message(
+                "assistant",
+                vec![
+                    tool_use(
+                        "w1",
+                        "write_file",
+                        json!({ "path": "src/a.rs", "content": "fn kept() {}" }),
+                    ),
+                    tool_use(
+                        "w2",
+                        "write_file",
+                        json!({ "path": "src/b.rs", "content": "fn b() {}" }),
+                    ),
+                ],
+            ),
+            message(
+                "user",
+                vec![tool_result("w1", false), tool_result("w2", false)],
+            ),
+            message(
+                "assistant",
+                vec![ContentBlock::Text {
+                    text: "İİİ \u{212A}\u{212A} removed `legacy` and deleted `ghost`. Renamed `İ` to `K`."
+                        .to_string(),
+                }],
+            ),
+        ];
+
+        let (changes, text) = last_turn_changes(&messages);
+        let lines = claim_annotations(&changes, &text);
+        assert_eq!(
+            lines[0],
+            "[claim check] 1 of 3 claims match this turn's file changes"
+        );
+        assert_eq!(lines[1], "  verified    removed `legacy`");
+        assert_eq!(
+            lines[2],
+            "  unverified  removed `ghost`: no change removes it"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{bail, Context, Result};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+pub const CLIPBOARD_COMMAND_ENV: &str = "VEX_CLIPBOARD_COMMAND";
+/// Tool results and diffs kept for `/copy`, per kind.
+const REGISTER_LIMIT: usize = 20;
+
+/// A copyable tool output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Register {
+    /// `read_file src/main.rs`, `edit_file src/lib.rs`, ...
+    pub label: String,
+    pub text: String,
+}
+
+impl Register {
+    pub fn summary(&self) -> String {
+        format!("{} ({} lines)", self.label, self.text.lines().count())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterKind {
+    Result,
+    Diff,
+}
+
+impl RegisterKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Result => "result",
+            Self::Diff => "diff",
+        }
+    }
+}
+
+/// The newest tool results and diffs of the session, numbered from the
+/// newest: `result 1` is the latest result, `result 2` the one before it.
+#[derive(Debug, Default)]
+pub struct Registers {
+    results: VecDeque<Register>,
+    diffs: VecDeque<Register>,
+}
+
+impl Registers {
+    pub fn push(&mut self, kind: RegisterKind, label: String, text: String) {
+        let registers = self.registers_mut(kind);
+        registers.push_front(Register { label, text });
+        registers.truncate(REGISTER_LIMIT);
+    }
+
+    /// `number` counts from 1 at the newest.
+    pub fn get(&self, kind: RegisterKind, number: usize) -> Option<&Register> {
+        self.registers(kind).get(number.checked_sub(1)?)
+    }
+
+    /// Newest first.
+    pub fn iter(&self, kind: RegisterKind) -> impl Iterator<Item = &Register> {
+        self.registers(kind).iter()
+    }
+
+    fn registers(&self, kind: RegisterKind) -> &VecDeque<Register> {
+        match kind {
+            RegisterKind::Result => &self.results,
+            RegisterKind::Diff => &self.diffs,
+        }
+    }
+
+    fn registers_mut(&mut self, kind: RegisterKind) -> &mut VecDeque<Register> {
+        match kind {
+            RegisterKind::Result => &mut self.results,
+            RegisterKind::Diff => &mut self.diffs,
+        }
+    }
+}
+
+/// `result 2`, `diff last`, or `result` (the newest).
+pub fn parse_register(args: &str) -> Option<(RegisterKind, usize)> {
+    let mut words = args.split_whitespace();
+    let kind = match words.next()? {
+        "result" | "results" | "r" => RegisterKind::Result,
+        "diff" | "diffs" | "d" => RegisterKind::Diff,
+        _ => return None,
+    };
+    let number = match words.next() {
+        None | Some("last") => 1,
+        Some(number) => number.parse().ok().filter(|number| *number > 0)?,
+    };
+    words.next().is_none().then_some((kind, number))
+}
+
+/// Where `/copy` puts text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardTarget {
+    /// Shell command that takes the text on stdin, e.g. `pbcopy`,
+    /// `wl-copy`, or `xclip -selection clipboard`.
+    Command(String),
+    /// An OSC 52 escape written to the terminal, which works over SSH in
+    /// terminals that allow it.
+    Terminal,
+}
+
+impl ClipboardTarget {
+    /// `VEX_CLIPBOARD_COMMAND` when set, otherwise the terminal.
+    pub fn from_env() -> Self {
+        std::env::var(CLIPBOARD_COMMAND_ENV)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .map_or(Self::Terminal, Self::Command)
+    }
+}
+
+/// Runs `command` with `text` on stdin.
+pub fn copy_with_command(command: &str, text: &str) -> Result<()> {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("failed to run {CLIPBOARD_COMMAND_ENV} `{command}`"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .with_context(|| format!("failed to write to `{command}`"))?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{CLIPBOARD_COMMAND_ENV} `{command}` exited with {status}");
+    }
+    Ok(())
+}
+
+/// The OSC 52 sequence that sets the system clipboard to `text`.
+pub fn osc52_sequence(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64_encode(text.as_bytes()))
+}
+
+pub(crate) fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = (u32::from(chunk[0]) << 16)
+            | (u32::from(chunk.get(1).copied().unwrap_or(0)) << 8)
+            | u32::from(chunk.get(2).copied().unwrap_or(0));
+        for position in 0..4 {
+            if position <= chunk.len() {
+                let index = (triple >> (18 - position * 6)) & 0x3f;
+                out.push(char::from(ALPHABET[index as usize]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registers_number_from_newest_and_parse_selectors() {
+        let mut registers = Registers::default();
+        for index in 0..25 {
+            registers.push(
+                RegisterKind::Result,
+                format!("read_file {index}.rs"),
+                format!("body {index}"),
+            );
+        }
+        assert_eq!(
+            registers
+                .get(RegisterKind::Result, 1)
+                .map(|register| register.text.as_str()),
+            Some("body 24")
+        );
+        assert_eq!(
+            registers
+                .get(RegisterKind::Result, 2)
+                .map(|register| register.label.as_str()),
+            Some("read_file 23.rs")
+        );
+        assert!(registers.get(RegisterKind::Result, 21).is_none());
+        assert!(registers.get(RegisterKind::Diff, 1).is_none());
+
+        assert_eq!(parse_register("result 2"), Some((RegisterKind::Result, 2)));
+        assert_eq!(parse_register("diff last"), Some((RegisterKind::Diff, 1)));
+        assert_eq!(parse_register("diff"), Some((RegisterKind::Diff, 1)));
+        assert_eq!(parse_register("result 0"), None);
+        assert_eq!(parse_register("output 1"), None);
+    }
+
+    #[test]
+    fn test_osc52_sequence_base64_encodes_the_text() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(osc52_sequence("hi"), "\x1b]52;c;aGk=\x07");
+    }
+}
@@ -1,8 +1,184 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
-use crate::util::is_local_endpoint_url;
+use crate::util::{is_anthropic_host, is_local_endpoint_url, parse_bool_str};
+
+/// Short names accepted wherever a model is chosen (`vex bench --models`,
+/// `@opus:` turn overrides).
+pub const MODEL_ALIASES: &[(&str, &str)] = &[
+    ("sonnet", "claude-sonnet-4-5-20250929"),
+    ("opus", "claude-opus-4-1-20250805"),
+    ("haiku", "claude-haiku-4-5-20251001"),
+];
+
+/// Expands a [`MODEL_ALIASES`] short name; other names pass through.
+pub fn resolve_model_alias(name: &str) -> String {
+    MODEL_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map(|(_, model)| model.to_string())
+        .unwrap_or_else(|| name.to_string())
+}
+
+pub const DEFAULT_API_URL: &str = "https://api.anthropic.com/v1/messages";
+pub const DEFAULT_MODEL: &str = "claude-sonnet-4-5-20250929";
+
+/// The highest temperature any supported protocol accepts; see
+/// [`ApiProtocol::max_temperature`] for each one.
+pub const MAX_TEMPERATURE: f64 = 2.0;
+
+pub const RUN_USAGE: &str =
+    "usage: vex [--supervised] [--profile <name>] [--emit-patches <dir>] [--check-key] [--temperature <t>] [--top-p <p>] [--seed <n>] | vex --auto <goal> ... | vex -p <prompt> | vex bench ... | vex mirror ... | vex usage ...";
+
+/// Wire protocol of the model endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiProtocol {
+    AnthropicMessages,
+    /// OpenAI-compatible `/v1/chat/completions`, as served by OpenAI, vLLM,
+    /// Ollama, and LM Studio.
+    OpenAiChatCompletions,
+    /// Ollama's native `/api/chat`, streamed as NDJSON.
+    OllamaChat,
+}
+
+impl ApiProtocol {
+    /// A `VEX_API_PROTOCOL` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "anthropic" | "anthropic_messages" | "messages" | "v1/messages" => {
+                Some(Self::AnthropicMessages)
+            }
+            "openai" | "chat" | "chat_completions" | "openai_chat_completions" => {
+                Some(Self::OpenAiChatCompletions)
+            }
+            "ollama" | "ollama_chat" | "api/chat" => Some(Self::OllamaChat),
+            _ => None,
+        }
+    }
+
+    /// The highest temperature the protocol accepts.
+    pub fn max_temperature(self) -> f64 {
+        match self {
+            Self::AnthropicMessages => 1.0,
+            Self::OpenAiChatCompletions | Self::OllamaChat => MAX_TEMPERATURE,
+        }
+    }
+
+    /// Rejects a temperature the protocol's endpoints would refuse.
+    pub fn check_temperature(self, temperature: f64) -> Result<()> {
+        let max = self.max_temperature();
+        if temperature > max {
+            let api = match self {
+                Self::AnthropicMessages => "the Messages API",
+                Self::OpenAiChatCompletions => "the chat completions API",
+                Self::OllamaChat => "Ollama",
+            };
+            bail!("temperature {temperature} is above {max}, the highest {api} accepts");
+        }
+        Ok(())
+    }
+
+    /// The protocol an endpoint URL implies: Ollama's native API for
+    /// `.../api/chat`, chat completions for
+    /// `.../chat/completions` and `.../v1` base URLs, Messages otherwise.
+    pub fn infer(api_url: &str) -> Self {
+        let normalized = api_url.trim().trim_end_matches('/').to_ascii_lowercase();
+        if crate::api::ollama::is_native_url(&normalized) {
+            Self::OllamaChat
+        } else if normalized.contains("/chat/completions") || normalized.ends_with("/v1") {
+            Self::OpenAiChatCompletions
+        } else {
+            Self::AnthropicMessages
+        }
+    }
+}
+
+/// Sampling parameters pinned for a whole session, so benchmark and
+/// debugging runs repeat as closely as the provider allows. Unset values
+/// keep the provider's defaults, and a `/temp` turn override still wins for
+/// its turn. The seed is sent only where the protocol has one (chat
+/// completions and Ollama).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sampling {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+impl Sampling {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `temperature 0, top_p 0.9, seed 7`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(temperature) = self.temperature {
+            parts.push(format!("temperature {temperature}"));
+        }
+        if let Some(top_p) = self.top_p {
+            parts.push(format!("top_p {top_p}"));
+        }
+        if let Some(seed) = self.seed {
+            parts.push(format!("seed {seed}"));
+        }
+        parts.join(", ")
+    }
+
+    /// `VEX_TEMPERATURE`, `VEX_TOP_P`, and `VEX_SEED`.
+    fn from_env() -> Result<Self> {
+        let mut sampling = Self::default();
+        for (name, parameter) in [
+            ("VEX_TEMPERATURE", "temperature"),
+            ("VEX_TOP_P", "top_p"),
+            ("VEX_SEED", "seed"),
+        ] {
+            if let Some(value) = std::env::var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+            {
+                sampling
+                    .set(parameter, value.trim())
+                    .with_context(|| format!("invalid {name}"))?;
+            }
+        }
+        Ok(sampling)
+    }
+
+    fn set(&mut self, parameter: &str, value: &str) -> Result<()> {
+        match parameter {
+            "temperature" => {
+                let temperature: f64 = value
+                    .parse()
+                    .with_context(|| format!("temperature expects a number, got `{value}`"))?;
+                if !(0.0..=MAX_TEMPERATURE).contains(&temperature) {
+                    bail!("temperature must be between 0 and {MAX_TEMPERATURE}");
+                }
+                self.temperature = Some(temperature);
+            }
+            "top_p" => {
+                let top_p: f64 = value
+                    .parse()
+                    .with_context(|| format!("top_p expects a number, got `{value}`"))?;
+                if !(0.0..=1.0).contains(&top_p) {
+                    bail!("top_p must be between 0 and 1");
+                }
+                self.top_p = Some(top_p);
+            }
+            "seed" => {
+                self.seed = Some(value.parse().with_context(|| {
+                    format!("seed expects a non-negative integer, got `{value}`")
+                })?);
+            }
+            _ => bail!("unknown sampling parameter {parameter}"),
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -10,31 +186,83 @@ pub struct Config {
     pub model: String,
     pub api_url: String,
     pub anthropic_version: String,
+    /// Protocol chosen with `VEX_API_PROTOCOL`, or by setting only
+    /// `OPENAI_BASE_URL`; otherwise inferred from `api_url`.
+    #[serde(default)]
+    pub api_protocol: Option<ApiProtocol>,
     pub working_dir: PathBuf,
+    /// When set, write/edit/rename tools emit `.patch` files here instead of
+    /// modifying the working tree (`--emit-patches <dir>` / `VEX_EMIT_PATCHES`).
+    #[serde(default)]
+    pub emit_patches_dir: Option<PathBuf>,
+    /// Check the API key with a cheap request before the first turn
+    /// (`--check-key` / `VEX_CHECK_API_KEY`).
+    #[serde(default)]
+    pub check_api_key: bool,
+    #[serde(default)]
+    pub sampling: Sampling,
 }
 
 impl Config {
     pub fn load() -> Result<Self> {
-        let api_url = std::env::var("ANTHROPIC_API_URL")
-            .unwrap_or_else(|_| "https://api.anthropic.com/v1/messages".to_string());
-        let api_key = std::env::var("ANTHROPIC_API_KEY").ok().and_then(|v| {
-            if v.trim().is_empty() {
-                None
-            } else {
-                Some(v)
+        let non_empty = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+        };
+        let anthropic_url = non_empty("ANTHROPIC_API_URL");
+        let openai_url = non_empty("OPENAI_BASE_URL");
+        let api_protocol = non_empty("VEX_API_PROTOCOL")
+            .and_then(|value| ApiProtocol::parse(&value))
+            .or_else(|| {
+                (anthropic_url.is_none() && openai_url.is_some())
+                    .then_some(ApiProtocol::OpenAiChatCompletions)
+            });
+        let api_url = anthropic_url
+            .or(openai_url)
+            .unwrap_or_else(|| DEFAULT_API_URL.to_string());
+        // The OpenAI protocol prefers its own variables, and the Anthropic key
+        // only ever goes to an Anthropic host. Ollama never gets it: a
+        // server behind an authenticating proxy takes `OLLAMA_API_KEY`.
+        let (api_key, model) = match api_protocol.unwrap_or_else(|| ApiProtocol::infer(&api_url)) {
+            ApiProtocol::OpenAiChatCompletions => (
+                non_empty("OPENAI_API_KEY").or_else(|| {
+                    is_anthropic_host(&api_url)
+                        .then(|| non_empty("ANTHROPIC_API_KEY"))
+                        .flatten()
+                }),
+                non_empty("OPENAI_MODEL").or_else(|| non_empty("ANTHROPIC_MODEL")),
+            ),
+            ApiProtocol::OllamaChat => (non_empty("OLLAMA_API_KEY"), non_empty("ANTHROPIC_MODEL")),
+            ApiProtocol::AnthropicMessages => {
+                (non_empty("ANTHROPIC_API_KEY"), non_empty("ANTHROPIC_MODEL"))
             }
-        });
-        let model = std::env::var("ANTHROPIC_MODEL")
-            .unwrap_or_else(|_| "claude-sonnet-4-5-20250929".to_string());
+        };
+        let model = model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
         let anthropic_version =
             std::env::var("ANTHROPIC_VERSION").unwrap_or_else(|_| "2023-06-01".to_string());
 
+        let working_dir = std::env::current_dir()?;
+        let emit_patches_dir = std::env::var("VEX_EMIT_PATCHES")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| working_dir.join(value.trim()));
+        let check_api_key = std::env::var("VEX_CHECK_API_KEY")
+            .ok()
+            .and_then(|value| parse_bool_str(&value))
+            .unwrap_or(false);
+        let sampling = Sampling::from_env()?;
+
         Ok(Self {
             api_key,
             model,
             api_url,
             anthropic_version,
-            working_dir: std::env::current_dir()?,
+            api_protocol,
+            working_dir,
+            emit_patches_dir,
+            check_api_key,
+            sampling,
         })
     }
 
@@ -47,20 +275,33 @@ impl Config {
         }
 
         let local_endpoint = self.is_local_endpoint();
-        if !local_endpoint && self.api_key.is_none() {
+        // Ollama servers take no key; one behind an authenticating proxy
+        // can still be given one.
+        if !local_endpoint && self.api_key.is_none() && self.protocol() != ApiProtocol::OllamaChat {
+            let variable = match self.protocol() {
+                ApiProtocol::OpenAiChatCompletions => "OPENAI_API_KEY",
+                _ => "ANTHROPIC_API_KEY",
+            };
             bail!(
-                "ANTHROPIC_API_KEY must be set for non-local endpoints (url: '{}')",
+                "{variable} must be set for non-local endpoints (url: '{}')",
                 self.api_url
             );
         }
 
+        if let Some(temperature) = self.sampling.temperature {
+            self.protocol().check_temperature(temperature)?;
+        }
+
         if !local_endpoint && self.model.starts_with("local/") {
             bail!("Local models are only allowed for localhost endpoints");
         }
 
-        if !local_endpoint && !self.model.starts_with("claude-") {
+        if !local_endpoint
+            && self.protocol() == ApiProtocol::AnthropicMessages
+            && !self.model.starts_with("claude-")
+        {
             bail!(
-                "Invalid model name: '{}'. Expected a model starting with 'claude-'",
+                "Invalid model name: '{}'. Expected a model starting with 'claude-', or set VEX_API_PROTOCOL=openai for an OpenAI-compatible endpoint",
                 self.model
             );
         }
@@ -68,7 +309,222 @@ impl Config {
         Ok(())
     }
 
+    /// Applies interactive-mode flags (`--emit-patches <dir>`,
+    /// `--temperature <t>`, `--top-p <p>`, `--seed <n>`) on top of the
+    /// environment configuration.
+    pub fn apply_cli_args(&mut self, args: &[String]) -> Result<()> {
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(dir) = arg.strip_prefix("--emit-patches=") {
+                self.emit_patches_dir = Some(self.working_dir.join(dir));
+                continue;
+            }
+            match arg.as_str() {
+                "--check-key" => self.check_api_key = true,
+                "--emit-patches" => {
+                    let Some(dir) = iter.next() else {
+                        bail!("--emit-patches requires a directory\n{RUN_USAGE}");
+                    };
+                    self.emit_patches_dir = Some(self.working_dir.join(dir));
+                }
+                "--temperature" | "--top-p" | "--seed" => {
+                    let Some(value) = iter.next() else {
+                        bail!("{arg} requires a value\n{RUN_USAGE}");
+                    };
+                    let parameter = arg.trim_start_matches("--").replace('-', "_");
+                    self.sampling.set(&parameter, value)?;
+                }
+                other => bail!("Unknown argument '{other}'\n{RUN_USAGE}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// The chosen protocol, or the one `api_url` implies.
+    pub fn protocol(&self) -> ApiProtocol {
+        self.api_protocol
+            .unwrap_or_else(|| ApiProtocol::infer(&self.api_url))
+    }
+
     fn is_local_endpoint(&self) -> bool {
         is_local_endpoint_url(&self.api_url)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            api_key: None,
+            model: "local/test".to_string(),
+            api_url: "http://localhost:8000/v1/messages".to_string(),
+            anthropic_version: "2023-06-01".to_string(),
+            api_protocol: None,
+            working_dir: PathBuf::from("/work"),
+            emit_patches_dir: None,
+            check_api_key: false,
+            sampling: Sampling::default(),
+        }
+    }
+
+    #[test]
+    fn test_emit_patches_flag_resolves_against_working_dir() {
+        let mut config = config();
+        config
+            .apply_cli_args(&["--emit-patches".to_string(), "out".to_string()])
+            .unwrap();
+        assert_eq!(config.emit_patches_dir, Some(PathBuf::from("/work/out")));
+
+        config
+            .apply_cli_args(&["--emit-patches=/tmp/patches".to_string()])
+            .unwrap();
+        assert_eq!(config.emit_patches_dir, Some(PathBuf::from("/tmp/patches")));
+
+        assert!(config
+            .apply_cli_args(&["--emit-patches".to_string()])
+            .is_err());
+        assert!(config.apply_cli_args(&["--bogus".to_string()]).is_err());
+
+        assert!(!config.check_api_key);
+        config.apply_cli_args(&["--check-key".to_string()]).unwrap();
+        assert!(config.check_api_key);
+    }
+
+    #[test]
+    fn test_sampling_flags_pin_the_session() {
+        let mut config = config();
+        let args: Vec<String> = ["--temperature", "0", "--top-p", "0.9", "--seed", "42"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        config.apply_cli_args(&args).unwrap();
+        assert_eq!(
+            config.sampling,
+            Sampling {
+                temperature: Some(0.0),
+                top_p: Some(0.9),
+                seed: Some(42),
+            }
+        );
+        assert_eq!(
+            config.sampling.describe(),
+            "temperature 0, top_p 0.9, seed 42"
+        );
+
+        for bad in [
+            ["--temperature", "2.5"],
+            ["--top-p", "1.2"],
+            ["--seed", "-1"],
+            ["--seed", "lucky"],
+        ] {
+            let args: Vec<String> = bad.iter().map(|arg| arg.to_string()).collect();
+            assert!(config.apply_cli_args(&args).is_err(), "{bad:?}");
+        }
+        assert!(config.apply_cli_args(&["--seed".to_string()]).is_err());
+
+        // 1.5 is a valid chat completions temperature but too high for the
+        // Messages API.
+        config.sampling.temperature = Some(1.5);
+        let error = config
+            .validate()
+            .expect_err("messages protocol")
+            .to_string();
+        assert!(
+            error.contains("above 1, the highest the Messages API accepts"),
+            "{error}"
+        );
+        config.api_protocol = Some(ApiProtocol::OpenAiChatCompletions);
+        config.validate().expect("chat completions");
+    }
+
+    #[test]
+    fn test_openai_base_url_selects_chat_completions_and_any_model() {
+        let _env_lock = crate::test_support::ENV_LOCK.blocking_lock();
+        for name in [
+            "ANTHROPIC_API_URL",
+            "ANTHROPIC_API_KEY",
+            "ANTHROPIC_MODEL",
+            "VEX_API_PROTOCOL",
+        ] {
+            std::env::remove_var(name);
+        }
+        std::env::set_var("OPENAI_BASE_URL", "https://llm.example.com/v1");
+        std::env::set_var("ANTHROPIC_API_KEY", "sk-ant-secret");
+        std::env::set_var("ANTHROPIC_MODEL", "claude-sonnet-4-5");
+        let without_openai_key = Config::load();
+        std::env::set_var("OPENAI_API_KEY", "sk-test");
+        std::env::set_var("OPENAI_MODEL", "gpt-4o");
+        let loaded = Config::load();
+        for name in [
+            "OPENAI_BASE_URL",
+            "OPENAI_API_KEY",
+            "OPENAI_MODEL",
+            "ANTHROPIC_API_KEY",
+            "ANTHROPIC_MODEL",
+        ] {
+            std::env::remove_var(name);
+        }
+        let without_openai_key = without_openai_key.expect("config");
+        assert_eq!(without_openai_key.api_key, None);
+        assert_eq!(without_openai_key.model, "claude-sonnet-4-5");
+        assert!(without_openai_key
+            .validate()
+            .expect_err("no key")
+            .to_string()
+            .contains("OPENAI_API_KEY must be set"));
+        let loaded = loaded.expect("config");
+        assert_eq!(loaded.protocol(), ApiProtocol::OpenAiChatCompletions);
+        assert_eq!(loaded.api_key.as_deref(), Some("sk-test"));
+        assert_eq!(loaded.model, "gpt-4o");
+        loaded
+            .validate()
+            .expect("any model name on chat completions");
+
+        let mut anthropic = loaded.clone();
+        anthropic.api_url = "https://llm.example.com/v1/messages".to_string();
+        anthropic.api_protocol = None;
+        assert_eq!(anthropic.protocol(), ApiProtocol::AnthropicMessages);
+        assert!(anthropic.validate().is_err());
+        assert_eq!(
+            ApiProtocol::parse(" OpenAI "),
+            Some(ApiProtocol::OpenAiChatCompletions)
+        );
+
+        let mut ollama = loaded.clone();
+        ollama.api_url = "https://ollama.internal/api/chat".to_string();
+        ollama.api_protocol = None;
+        ollama.api_key = None;
+        ollama.model = "qwen3:8b".to_string();
+        assert_eq!(ollama.protocol(), ApiProtocol::OllamaChat);
+        ollama.validate().expect("remote Ollama needs no key");
+    }
+
+    #[test]
+    fn test_ollama_never_gets_the_anthropic_key() {
+        let _env_lock = crate::test_support::ENV_LOCK.blocking_lock();
+        for name in ["OPENAI_BASE_URL", "VEX_API_PROTOCOL", "OLLAMA_API_KEY"] {
+            std::env::remove_var(name);
+        }
+        std::env::set_var("ANTHROPIC_API_URL", "https://ollama.internal/api/chat");
+        std::env::set_var("ANTHROPIC_API_KEY", "sk-ant-secret");
+        let without_ollama_key = Config::load();
+        std::env::set_var("OLLAMA_API_KEY", "proxy-token");
+        let loaded = Config::load();
+        for name in ["ANTHROPIC_API_URL", "ANTHROPIC_API_KEY", "OLLAMA_API_KEY"] {
+            std::env::remove_var(name);
+        }
+        assert_eq!(without_ollama_key.expect("config").api_key, None);
+        assert_eq!(
+            loaded.expect("config").api_key.as_deref(),
+            Some("proxy-token")
+        );
+        // Ollama's port also serves the OpenAI-compatible API, so a bare
+        // address does not pick the native one.
+        assert_eq!(
+            ApiProtocol::infer("http://localhost:11434"),
+            ApiProtocol::AnthropicMessages
+        );
+    }
+}
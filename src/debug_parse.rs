@@ -0,0 +1,345 @@
+use crate::api::raw_capture::{read_capture, RAW_STREAM_ENV};
+use crate::api::stream::StreamParser;
+use crate::config::ApiProtocol;
+use crate::state::{append_incremental_suffix, RoundAssembly, ToolInputDelta};
+use crate::types::{ContentBlock, StreamEvent};
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const DEBUG_PARSE_USAGE: &str = "usage: vex debug-parse <capture.sse>";
+/// Longest text shown for one delta before it is elided.
+const PREVIEW_CHARS: usize = 120;
+
+/// Replays events into the [`RoundAssembly`] the conversation loop uses,
+/// reporting each event and anything the loop would drop or repair.
+struct Replay {
+    round: RoundAssembly,
+    /// Kind and text of each started block; text deltas are deduplicated
+    /// against it as the loop does.
+    blocks: BTreeMap<usize, (&'static str, String)>,
+    stopped: Vec<usize>,
+    saw_message_stop: bool,
+    warnings: usize,
+}
+
+impl Replay {
+    fn warn(&mut self, report: &mut Vec<String>, message: String) {
+        self.warnings += 1;
+        report.push(format!("    ! {message}"));
+    }
+
+    fn apply(&mut self, event: StreamEvent, report: &mut Vec<String>) {
+        match event {
+            StreamEvent::MessageStart { message } => {
+                report.push(format!(
+                    "  message_start id={} model={} usage={:?}",
+                    message.id, message.model, message.usage
+                ));
+                self.round.message_start(message.usage);
+            }
+            StreamEvent::ContentBlockStart {
+                index,
+                content_block,
+            } => {
+                let kind = block_kind(&content_block);
+                report.push(format!("  block_start #{index} {kind}"));
+                let text = match &content_block {
+                    ContentBlock::Text { text } => text.clone(),
+                    _ => String::new(),
+                };
+                if self.blocks.insert(index, (kind, text)).is_some() {
+                    self.warn(
+                        report,
+                        format!("block #{index} started twice; the earlier content is replaced"),
+                    );
+                }
+                self.round.start_block(index, &content_block);
+            }
+            StreamEvent::ContentBlockDelta { index, delta } => {
+                self.round.thinking_delta(index, &delta);
+                if let Some(text) = &delta.text {
+                    let appended = match self.blocks.get_mut(&index) {
+                        Some(("text", content)) => append_incremental_suffix(content, text),
+                        Some((kind, _)) => {
+                            let kind = *kind;
+                            self.warn(
+                                report,
+                                format!("text delta for {kind} block #{index} is dropped"),
+                            );
+                            return;
+                        }
+                        None => {
+                            self.warn(
+                                report,
+                                format!("text delta before block #{index} started; starting a text block"),
+                            );
+                            self.blocks.insert(index, ("text", text.clone()));
+                            text.clone()
+                        }
+                    };
+                    report.push(format!("  text_delta #{index} {}", preview(text)));
+                    if appended != *text {
+                        self.warn(
+                            report,
+                            format!(
+                                "delta repeats earlier text; only {} appended",
+                                preview(&appended)
+                            ),
+                        );
+                    }
+                    self.round.text.push_str(&appended);
+                }
+                if let Some(partial) = &delta.partial_json {
+                    report.push(format!("  input_json_delta #{index} {}", preview(partial)));
+                    match self.round.tool_input_delta(index, partial) {
+                        ToolInputDelta::Appended(_) => {}
+                        ToolInputDelta::Oversized => self.warn(
+                            report,
+                            format!("tool input for block #{index} is over the size limit; the call is refused"),
+                        ),
+                        ToolInputDelta::Dropped => self.warn(
+                            report,
+                            format!("input_json delta for block #{index}, which is not a tool_use; dropped"),
+                        ),
+                    }
+                }
+            }
+            StreamEvent::ContentBlockStop { index } => {
+                report.push(format!("  block_stop #{index}"));
+                if self.stopped.contains(&index) {
+                    self.warn(report, format!("block #{index} stopped twice"));
+                }
+                self.stopped.push(index);
+                if !self.blocks.contains_key(&index) {
+                    self.warn(
+                        report,
+                        format!("stop for block #{index}, which never started"),
+                    );
+                }
+                if let Some(Err(error)) = self.round.stop_block(index) {
+                    let message = format!(
+                        "tool input for block #{index} is not valid JSON ({error}); the start input is kept"
+                    );
+                    self.warn(report, message);
+                }
+            }
+            StreamEvent::MessageDelta { delta, usage } => {
+                report.push(format!(
+                    "  message_delta stop_reason={:?} usage={usage:?}",
+                    delta.stop_reason
+                ));
+                self.round.message_delta(&delta, usage.as_ref());
+            }
+            StreamEvent::MessageStop => {
+                report.push("  message_stop".to_string());
+                self.saw_message_stop = true;
+            }
+            StreamEvent::Ping => report.push("  ping".to_string()),
+            StreamEvent::Error { error } => {
+                let message = format!("provider error {}: {}", error.error_type, error.message);
+                self.warn(report, message);
+            }
+            StreamEvent::Unknown => {
+                self.warn(report, "event of an unknown type; ignored".to_string())
+            }
+        }
+    }
+}
+
+fn block_kind(block: &ContentBlock) -> &'static str {
+    match block {
+        ContentBlock::Text { .. } => "text",
+        ContentBlock::ToolUse { .. } => "tool_use",
+        ContentBlock::ToolResult { .. } => "tool_result",
+        ContentBlock::Document { .. } => "document",
+        ContentBlock::Thinking { .. } => "thinking",
+        ContentBlock::RedactedThinking { .. } => "redacted_thinking",
+    }
+}
+
+fn preview(text: &str) -> String {
+    let mut shown: String = text.chars().take(PREVIEW_CHARS).collect();
+    if shown.len() < text.len() {
+        shown.push('…');
+    }
+    format!("{shown:?}")
+}
+
+/// The stream format of a capture: Ollama's native API sends NDJSON, so a
+/// capture starting with a JSON object is read as that; anything else is
+/// SSE.
+fn capture_protocol(chunks: &[Vec<u8>]) -> ApiProtocol {
+    let first = chunks
+        .iter()
+        .flatten()
+        .find(|byte| !byte.is_ascii_whitespace());
+    if first == Some(&b'{') {
+        ApiProtocol::OllamaChat
+    } else {
+        ApiProtocol::AnthropicMessages
+    }
+}
+
+/// Feeds `chunks` through the [`StreamParser`] a turn would use, one at a
+/// time, and assembles the round as the turn would, returning a line per
+/// chunk, event, and anomaly followed by the assembled result.
+pub fn replay_chunks(chunks: &[Vec<u8>]) -> Vec<String> {
+    let mut parser = StreamParser::for_protocol(capture_protocol(chunks)).with_diagnostics();
+    let mut replay = Replay {
+        round: RoundAssembly::from_env(),
+        blocks: BTreeMap::new(),
+        stopped: Vec::new(),
+        saw_message_stop: false,
+        warnings: 0,
+    };
+    let mut report = Vec::new();
+    let mut failure = None;
+
+    for (number, chunk) in chunks.iter().enumerate() {
+        report.push(format!("chunk {} ({} bytes)", number + 1, chunk.len()));
+        let result = parser.process(chunk);
+        for note in parser.take_diagnostics() {
+            replay.warn(&mut report, note);
+        }
+        match result {
+            Ok(events) => {
+                for event in events {
+                    replay.apply(event, &mut report);
+                }
+            }
+            Err(error) => {
+                report.push(format!("  parser error: {error}"));
+                failure = Some(error.to_string());
+                break;
+            }
+        }
+    }
+
+    if failure.is_none() && parser.buffered_bytes() > 0 {
+        let message = format!(
+            "stream ended with {} bytes after the last complete frame",
+            parser.buffered_bytes()
+        );
+        replay.warn(&mut report, message);
+    }
+    if failure.is_none() && !replay.saw_message_stop {
+        replay.warn(&mut report, "stream ended without message_stop".to_string());
+    }
+    let unstopped: Vec<String> = replay
+        .blocks
+        .keys()
+        .filter(|index| !replay.stopped.contains(index))
+        .map(|index| format!("#{index}"))
+        .collect();
+    if !unstopped.is_empty() {
+        let message = format!("blocks never stopped: {}", unstopped.join(", "));
+        replay.warn(&mut report, message);
+    }
+
+    let Replay {
+        round, warnings, ..
+    } = replay;
+    let stop_reason = round.stop_reason.clone();
+    let text = round.text.clone();
+    let (tool_uses, thinking) = round.into_blocks();
+    report.push(String::new());
+    report.push("assembled round:".to_string());
+    for block in &thinking {
+        report.push(format!("  {}", block_kind(block)));
+    }
+    if !text.is_empty() {
+        report.push(format!("  text {}", preview(&text)));
+    }
+    for block in &tool_uses {
+        if let ContentBlock::ToolUse { name, input, .. } = block {
+            report.push(format!("  tool_use {name} {input}"));
+        }
+    }
+    report.push(format!(
+        "stop_reason: {}",
+        stop_reason.as_deref().unwrap_or("(none)")
+    ));
+    report.push(match failure {
+        Some(error) => format!("result: the turn would fail: {error}"),
+        None => format!("result: parsed with {warnings} warning(s)"),
+    });
+    report
+}
+
+/// `vex debug-parse <file>` replays a capture recorded with
+/// `VEX_RAW_STREAM=1`, or any file of raw SSE text.
+pub fn run_debug_parse_cli(args: &[String]) -> Result<()> {
+    let [path] = args else {
+        bail!("{DEBUG_PARSE_USAGE}\nrecord captures with {RAW_STREAM_ENV}=1");
+    };
+    let chunks = read_capture(Path::new(path))?;
+    for line in replay_chunks(&chunks) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(frames: &[&str]) -> Vec<u8> {
+        frames
+            .iter()
+            .map(|frame| format!("data: {frame}\n\n"))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn test_replay_assembles_blocks_across_split_chunks() {
+        let stream = frames(&[
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"t1","name":"read_file","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"path\":"}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"\"a.rs\"}"}}"#,
+            r#"{"type":"content_block_stop","index":1}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"tool_use"}}"#,
+            r#"{"type":"message_stop"}"#,
+        ]);
+        let (head, tail) = stream.split_at(37);
+        let report = replay_chunks(&[head.to_vec(), tail.to_vec()]);
+
+        assert_eq!(report[0], format!("chunk 1 ({} bytes)", 37));
+        assert!(report.contains(&"  text \"Hello\"".to_string()));
+        assert!(report.contains(&"  tool_use read_file {\"path\":\"a.rs\"}".to_string()));
+        assert!(report.contains(&"stop_reason: tool_use".to_string()));
+        assert_eq!(
+            report.last().map(String::as_str),
+            Some("result: parsed with 0 warning(s)")
+        );
+    }
+
+    #[test]
+    fn test_replay_flags_malformed_frames_and_truncation() {
+        let mut stream = frames(&[
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"t1","name":"write_file","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":"{\"path\""}}"#,
+            r#"{not json"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+        ]);
+        stream.extend_from_slice(b"data: {\"type\":\"message_st");
+        let report = replay_chunks(&[stream]);
+
+        let warnings: Vec<&String> = report
+            .iter()
+            .filter(|line| line.starts_with("    ! "))
+            .collect();
+        assert!(warnings[0].contains("skipped frame (event <none>)"));
+        assert!(warnings[1].contains("tool input for block #0 is not valid JSON"));
+        assert!(warnings[2].contains("bytes after the last complete frame"));
+        assert!(warnings[3].contains("without message_stop"));
+        assert_eq!(
+            report.last().map(String::as_str),
+            Some("result: parsed with 4 warning(s)")
+        );
+    }
+}
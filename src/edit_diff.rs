@@ -1,3 +1,5 @@
+use std::sync::{Arc, Mutex};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum DiffKind {
     Equal,
@@ -14,6 +16,107 @@ struct DiffLine {
 }
 
 pub const DEFAULT_EDIT_DIFF_CONTEXT_LINES: usize = 2;
+const MAX_EDIT_DIFF_CONTEXT_LINES: usize = 50;
+
+/// A place where edit diffs are shown, each with its own context lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffSurface {
+    /// Approval previews, where a reviewer wants surrounding code.
+    Preview,
+    /// The edit_file result kept in model history, where tokens are scarce.
+    History,
+    /// The diff echoed to the transcript after an edit completes.
+    Transcript,
+}
+
+impl DiffSurface {
+    pub const ALL: [DiffSurface; 3] = [Self::Preview, Self::History, Self::Transcript];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Preview => "preview",
+            Self::History => "history",
+            Self::Transcript => "transcript",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|surface| surface.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    fn env_var(self) -> &'static str {
+        match self {
+            Self::Preview => "VEX_DIFF_CONTEXT_PREVIEW",
+            Self::History => "VEX_DIFF_CONTEXT_HISTORY",
+            Self::Transcript => "VEX_DIFF_CONTEXT_TRANSCRIPT",
+        }
+    }
+}
+
+/// Context lines per [`DiffSurface`]. Clones share one setting so a runtime
+/// override (`/diffcontext`) reaches every holder.
+#[derive(Debug, Clone)]
+pub struct DiffContext {
+    lines: Arc<Mutex<[usize; 3]>>,
+}
+
+impl Default for DiffContext {
+    fn default() -> Self {
+        Self::new([
+            DEFAULT_EDIT_DIFF_CONTEXT_LINES,
+            0,
+            DEFAULT_EDIT_DIFF_CONTEXT_LINES,
+        ])
+    }
+}
+
+impl DiffContext {
+    fn new(lines: [usize; 3]) -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(lines)),
+        }
+    }
+
+    /// Defaults (preview 2, history 0, transcript 2) overridden by
+    /// `VEX_DIFF_CONTEXT_PREVIEW`, `VEX_DIFF_CONTEXT_HISTORY`, and
+    /// `VEX_DIFF_CONTEXT_TRANSCRIPT`.
+    pub fn from_env() -> Self {
+        let context = Self::default();
+        for surface in DiffSurface::ALL {
+            if let Some(lines) = std::env::var(surface.env_var())
+                .ok()
+                .and_then(|value| value.trim().parse::<usize>().ok())
+            {
+                context.set(surface, lines);
+            }
+        }
+        context
+    }
+
+    pub fn get(&self, surface: DiffSurface) -> usize {
+        self.lines
+            .lock()
+            .map(|lines| lines[surface as usize])
+            .unwrap_or(DEFAULT_EDIT_DIFF_CONTEXT_LINES)
+    }
+
+    pub fn set(&self, surface: DiffSurface, lines: usize) {
+        if let Ok(mut current) = self.lines.lock() {
+            current[surface as usize] = lines.min(MAX_EDIT_DIFF_CONTEXT_LINES);
+        }
+    }
+
+    /// `preview 2, history 0, transcript 2`.
+    pub fn describe(&self) -> String {
+        DiffSurface::ALL
+            .into_iter()
+            .map(|surface| format!("{} {}", surface.name(), self.get(surface)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
 
 pub fn format_edit_hunks(
     old_str: &str,
@@ -81,6 +184,172 @@ pub fn format_edit_hunks(
     out
 }
 
+/// Largest LCS table (old lines x new lines, after trimming the common
+/// prefix and suffix) computed for a whole-file diff; larger changes are
+/// rendered as a full replacement of the differing region.
+const MAX_FILE_DIFF_CELLS: usize = 4_000_000;
+
+/// Renders a `git apply`-compatible unified diff between two versions of a
+/// file. `None` marks a missing side (file creation or deletion).
+pub fn format_unified_file_diff(
+    path: &str,
+    old: Option<&str>,
+    new: Option<&str>,
+    context_lines: usize,
+) -> String {
+    let old_lines: Vec<&str> = old.unwrap_or_default().split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.unwrap_or_default().split_inclusive('\n').collect();
+    let diff_lines = build_file_diff_lines(&old_lines, &new_lines);
+    let hunks = build_hunk_ranges(&diff_lines, context_lines);
+
+    let mut out = format!("diff --git a/{path} b/{path}\n");
+    match (old.is_some(), new.is_some()) {
+        (false, _) => out.push_str(&format!(
+            "new file mode 100644\n--- /dev/null\n+++ b/{path}\n"
+        )),
+        (true, false) => out.push_str(&format!(
+            "deleted file mode 100644\n--- a/{path}\n+++ /dev/null\n"
+        )),
+        (true, true) => out.push_str(&format!("--- a/{path}\n+++ b/{path}\n")),
+    }
+
+    for (start, end) in hunks {
+        let before = &diff_lines[..start];
+        let hunk = &diff_lines[start..end];
+        let old_before = before.iter().filter(|line| line.old_line.is_some()).count();
+        let new_before = before.iter().filter(|line| line.new_line.is_some()).count();
+        let old_count = hunk.iter().filter(|line| line.old_line.is_some()).count();
+        let new_count = hunk.iter().filter(|line| line.new_line.is_some()).count();
+        let old_start = old_before + usize::from(old_count > 0);
+        let new_start = new_before + usize::from(new_count > 0);
+        out.push_str(&format!(
+            "@@ -{old_start},{old_count} +{new_start},{new_count} @@\n"
+        ));
+        for line in hunk {
+            let marker = match line.kind {
+                DiffKind::Equal => ' ',
+                DiffKind::Delete => '-',
+                DiffKind::Insert => '+',
+            };
+            out.push(marker);
+            out.push_str(&line.text);
+            if !line.text.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// Where two versions of a file differ: `(changed, first, last)`, where
+/// `changed` is the larger of the removed and added line counts and
+/// `first..=last` are the affected lines in `new`. `None` when the contents
+/// match.
+pub fn changed_line_span(old: &str, new: &str) -> Option<(usize, usize, usize)> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let (mut deleted, mut inserted) = (0, 0);
+    let mut span: Option<(usize, usize)> = None;
+    let mut new_seen = 0;
+    for line in build_file_diff_lines(&old_lines, &new_lines) {
+        let position = match line.kind {
+            DiffKind::Equal => {
+                new_seen += 1;
+                continue;
+            }
+            DiffKind::Insert => {
+                inserted += 1;
+                new_seen += 1;
+                new_seen
+            }
+            // A removed line sits where the next new line starts.
+            DiffKind::Delete => {
+                deleted += 1;
+                (new_seen + 1).min(new_lines.len()).max(1)
+            }
+        };
+        span = Some(match span {
+            Some((first, last)) => (first.min(position), last.max(position)),
+            None => (position, position),
+        });
+    }
+    span.map(|(first, last)| (deleted.max(inserted), first, last))
+}
+
+/// Lines removed from `old` and added in `new`, as `git diff --stat`
+/// counts them.
+pub fn diff_line_counts(old: &str, new: &str) -> (usize, usize) {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    build_file_diff_lines(&old_lines, &new_lines)
+        .iter()
+        .fold((0, 0), |(removed, added), line| match line.kind {
+            DiffKind::Equal => (removed, added),
+            DiffKind::Delete => (removed + 1, added),
+            DiffKind::Insert => (removed, added + 1),
+        })
+}
+
+fn build_file_diff_lines(old_lines: &[&str], new_lines: &[&str]) -> Vec<DiffLine> {
+    let prefix = old_lines
+        .iter()
+        .zip(new_lines)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_middle = &old_lines[prefix..old_lines.len() - suffix];
+    let new_middle = &new_lines[prefix..new_lines.len() - suffix];
+
+    let mut middle = if old_middle.len().saturating_mul(new_middle.len()) <= MAX_FILE_DIFF_CELLS {
+        build_diff_lines(old_middle, new_middle)
+    } else {
+        let deletes = old_middle.iter().enumerate().map(|(index, text)| DiffLine {
+            kind: DiffKind::Delete,
+            text: text.to_string(),
+            old_line: Some(index + 1),
+            new_line: None,
+        });
+        let inserts = new_middle.iter().enumerate().map(|(index, text)| DiffLine {
+            kind: DiffKind::Insert,
+            text: text.to_string(),
+            old_line: None,
+            new_line: Some(index + 1),
+        });
+        deletes.chain(inserts).collect()
+    };
+    for line in &mut middle {
+        line.old_line = line.old_line.map(|number| number + prefix);
+        line.new_line = line.new_line.map(|number| number + prefix);
+    }
+
+    let equal = |index: usize, text: &str| DiffLine {
+        kind: DiffKind::Equal,
+        text: text.to_string(),
+        old_line: Some(index + 1),
+        new_line: Some(index + 1),
+    };
+    let mut out: Vec<DiffLine> = old_lines[..prefix]
+        .iter()
+        .enumerate()
+        .map(|(index, text)| equal(index, text))
+        .collect();
+    out.extend(middle);
+    let old_suffix_start = old_lines.len() - suffix;
+    let new_suffix_start = new_lines.len() - suffix;
+    out.extend((0..suffix).map(|offset| DiffLine {
+        kind: DiffKind::Equal,
+        text: old_lines[old_suffix_start + offset].to_string(),
+        old_line: Some(old_suffix_start + offset + 1),
+        new_line: Some(new_suffix_start + offset + 1),
+    }));
+    out
+}
+
 fn collect_lines(text: &str) -> Vec<&str> {
     if text.is_empty() {
         Vec::new()
@@ -198,6 +467,22 @@ fn build_hunk_ranges(diff_lines: &[DiffLine], context_lines: usize) -> Vec<(usiz
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_diff_context_clones_share_overrides() {
+        let context = DiffContext::default();
+        let shared = context.clone();
+        assert_eq!(context.describe(), "preview 2, history 0, transcript 2");
+        shared.set(DiffSurface::Preview, 6);
+        shared.set(DiffSurface::History, 500);
+        assert_eq!(context.get(DiffSurface::Preview), 6);
+        assert_eq!(context.get(DiffSurface::History), 50);
+        assert_eq!(
+            DiffSurface::parse(" Transcript"),
+            Some(DiffSurface::Transcript)
+        );
+        assert_eq!(DiffSurface::parse("review"), None);
+    }
+
     #[test]
     fn test_format_edit_hunks_uses_compact_context() {
         let old_str = "a\nb\nc\nd\ne\nf";
@@ -223,10 +508,50 @@ mod tests {
         assert!(rendered.contains("  ..."));
     }
 
+    #[test]
+    fn test_unified_file_diff_modification_has_git_headers_and_context() {
+        let old = "a\nb\nc\nd\ne\nf\ng\n";
+        let new = "a\nb\nc\nD\ne\nf\ng\n";
+        let patch = format_unified_file_diff("src/lib.rs", Some(old), Some(new), 3);
+        assert_eq!(
+            patch,
+            "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n\
+             @@ -1,7 +1,7 @@\n a\n b\n c\n-d\n+D\n e\n f\n g\n"
+        );
+    }
+
+    #[test]
+    fn test_unified_file_diff_new_file_and_missing_newline() {
+        let created = format_unified_file_diff("notes.txt", None, Some("one\ntwo"), 3);
+        assert!(created.contains("new file mode 100644\n--- /dev/null\n+++ b/notes.txt\n"));
+        assert!(created.contains("@@ -0,0 +1,2 @@\n+one\n+two\n\\ No newline at end of file\n"));
+
+        let newline_only = format_unified_file_diff("x", Some("x"), Some("x\n"), 3);
+        assert!(newline_only.contains("@@ -1,1 +1,1 @@\n-x\n\\ No newline at end of file\n+x\n"));
+    }
+
+    #[test]
+    fn test_unified_file_diff_pure_insertion_header() {
+        let patch = format_unified_file_diff("f", Some("a\nb\n"), Some("a\nnew\nb\n"), 0);
+        assert!(patch.ends_with("@@ -1,0 +2,1 @@\n+new\n"));
+    }
+
     #[test]
     fn test_format_edit_hunks_handles_empty_insert() {
         let rendered = format_edit_hunks("", "new line", "  ", 2);
         assert!(rendered.contains("@@ -1,0 +1,1 @@"));
         assert!(rendered.contains("  1 + new line"));
     }
+
+    #[test]
+    fn test_changed_line_span_reports_count_and_new_lines() {
+        let old = "a\nb\nc\nd\ne\n";
+        assert_eq!(changed_line_span(old, old), None);
+        assert_eq!(changed_line_span(old, "a\nB\nC\nd\ne\n"), Some((2, 2, 3)));
+        assert_eq!(changed_line_span(old, "a\nb\nc\nd\n"), Some((1, 4, 4)));
+        assert_eq!(
+            changed_line_span(old, "a\nb\nx\ny\nz\nc\nd\ne\n"),
+            Some((3, 3, 5))
+        );
+    }
 }
@@ -0,0 +1,311 @@
+use crate::api::watchdog::StallAction;
+use crate::state::{StreamBlock, ToolClass};
+use crate::types::TokenUsage;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// What happened in the runtime, published on the [`EventBus`].
+#[derive(Debug, Clone)]
+pub enum RuntimeEvent {
+    TurnStarted {
+        input: String,
+    },
+    /// A turn that ended without an error, including a cancelled one.
+    TurnCompleted {
+        elapsed: Duration,
+        cancelled: bool,
+        /// `[changes this turn: ...]` lines, when tools changed anything.
+        changes: Vec<String>,
+    },
+    TurnFailed {
+        elapsed: Duration,
+        error: String,
+        changes: Vec<String>,
+    },
+    /// A model request within a turn; tool rounds make more than one.
+    RoundStarted {
+        turn: usize,
+        round: usize,
+    },
+    BlockStarted {
+        index: usize,
+        block: StreamBlock,
+    },
+    BlockDelta {
+        index: usize,
+        delta: String,
+    },
+    BlockCompleted {
+        index: usize,
+    },
+    ToolStarted {
+        id: String,
+        name: String,
+    },
+    ToolFinished {
+        id: String,
+        name: String,
+        duration: Duration,
+        /// First line of the error when the call failed.
+        error: Option<String>,
+    },
+    ApprovalRequested {
+        tool_name: String,
+        /// First line of the approval preview.
+        detail: String,
+        class: ToolClass,
+    },
+    /// The model's response stream sent nothing for `silent`.
+    StreamStalled {
+        round: usize,
+        silent: Duration,
+        action: StallAction,
+    },
+    /// Tokens a finished turn used against `model`.
+    Usage {
+        model: String,
+        usage: TokenUsage,
+        elapsed: Duration,
+        succeeded: bool,
+    },
+}
+
+/// Groups of events a subscriber can ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Turn,
+    Block,
+    Tool,
+    Approval,
+    Error,
+    Metrics,
+}
+
+impl EventKind {
+    pub const ALL: [Self; 6] = [
+        Self::Turn,
+        Self::Block,
+        Self::Tool,
+        Self::Approval,
+        Self::Error,
+        Self::Metrics,
+    ];
+}
+
+impl RuntimeEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::TurnStarted { .. } | Self::TurnCompleted { .. } | Self::RoundStarted { .. } => {
+                EventKind::Turn
+            }
+            Self::TurnFailed { .. } | Self::StreamStalled { .. } => EventKind::Error,
+            Self::BlockStarted { .. } | Self::BlockDelta { .. } | Self::BlockCompleted { .. } => {
+                EventKind::Block
+            }
+            Self::ToolStarted { .. } | Self::ToolFinished { .. } => EventKind::Tool,
+            Self::ApprovalRequested { .. } => EventKind::Approval,
+            Self::Usage { .. } => EventKind::Metrics,
+        }
+    }
+}
+
+type EventHandler = Arc<dyn Fn(&RuntimeEvent) + Send + Sync>;
+
+#[derive(Clone)]
+enum Sink {
+    Channel(mpsc::UnboundedSender<Arc<RuntimeEvent>>),
+    Handler(EventHandler),
+}
+
+struct Subscriber {
+    id: u64,
+    kinds: Vec<EventKind>,
+    sink: Sink,
+}
+
+/// Names a handler added with [`EventBus::subscribe_fn`], for
+/// [`EventBus::unsubscribe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandlerId(u64);
+
+/// Fan-out of [`RuntimeEvent`]s to any number of subscribers, each getting
+/// only the kinds it asked for. Clones publish to the same subscribers.
+/// Events are observations: anything that must answer the runtime, like an
+/// approval decision, still goes through its own channel.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+/// Events received by a channel subscriber; dropping it unsubscribes.
+pub struct EventSubscription {
+    rx: mpsc::UnboundedReceiver<Arc<RuntimeEvent>>,
+}
+
+impl EventSubscription {
+    pub async fn recv(&mut self) -> Option<Arc<RuntimeEvent>> {
+        self.rx.recv().await
+    }
+
+    pub fn try_recv(&mut self) -> Option<Arc<RuntimeEvent>> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl EventBus {
+    /// Queues events of `kinds` for the returned subscription.
+    pub fn subscribe(&self, kinds: &[EventKind]) -> EventSubscription {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.add(kinds, Sink::Channel(tx));
+        EventSubscription { rx }
+    }
+
+    /// Calls `handler` with each event of `kinds` on the publishing thread,
+    /// so it must not block; slow work belongs in a spawned task. The bus is
+    /// not locked during the call, so a handler may publish or subscribe.
+    /// The handler stays until [`EventBus::unsubscribe`] removes it.
+    pub fn subscribe_fn(
+        &self,
+        kinds: &[EventKind],
+        handler: impl Fn(&RuntimeEvent) + Send + Sync + 'static,
+    ) -> HandlerId {
+        self.add(kinds, Sink::Handler(Arc::new(handler)))
+    }
+
+    /// Removes a handler; an event already being delivered may still reach it.
+    pub fn unsubscribe(&self, id: HandlerId) {
+        self.lock().retain(|subscriber| subscriber.id != id.0);
+    }
+
+    fn add(&self, kinds: &[EventKind], sink: Sink) -> HandlerId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock().push(Subscriber {
+            id,
+            kinds: kinds.to_vec(),
+            sink,
+        });
+        HandlerId(id)
+    }
+
+    pub fn publish(&self, event: RuntimeEvent) {
+        let kind = event.kind();
+        let sinks: Vec<Sink> = {
+            let mut subscribers = self.lock();
+            subscribers.retain(
+                |subscriber| !matches!(&subscriber.sink, Sink::Channel(tx) if tx.is_closed()),
+            );
+            subscribers
+                .iter()
+                .filter(|subscriber| subscriber.kinds.contains(&kind))
+                .map(|subscriber| subscriber.sink.clone())
+                .collect()
+        };
+        let event = Arc::new(event);
+        for sink in sinks {
+            match sink {
+                Sink::Channel(tx) => {
+                    let _ = tx.send(Arc::clone(&event));
+                }
+                Sink::Handler(handler) => handler(&event),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn subscriber_count(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Subscriber>> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_subscribers_get_only_their_kinds() {
+        let bus = EventBus::default();
+        let mut turns = bus.subscribe(&[EventKind::Turn, EventKind::Error]);
+        let tools = bus.subscribe(&[EventKind::Tool]);
+        let handled = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&handled);
+        bus.clone().subscribe_fn(&EventKind::ALL, move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        bus.publish(RuntimeEvent::TurnStarted {
+            input: "hello".to_string(),
+        });
+        bus.publish(RuntimeEvent::BlockCompleted { index: 0 });
+        drop(tools);
+        bus.publish(RuntimeEvent::ToolStarted {
+            id: "t1".to_string(),
+            name: "read_file".to_string(),
+        });
+        bus.publish(RuntimeEvent::TurnFailed {
+            elapsed: Duration::from_secs(1),
+            error: "boom".to_string(),
+            changes: Vec::new(),
+        });
+
+        assert!(matches!(
+            turns.try_recv().as_deref(),
+            Some(RuntimeEvent::TurnStarted { input }) if input == "hello"
+        ));
+        assert!(matches!(
+            turns.try_recv().as_deref(),
+            Some(RuntimeEvent::TurnFailed { error, .. }) if error == "boom"
+        ));
+        assert!(turns.try_recv().is_none());
+        assert_eq!(handled.load(Ordering::SeqCst), 4);
+        // The dropped tool subscription was pruned on the next event.
+        assert_eq!(bus.lock().len(), 2);
+
+        let counter = Arc::clone(&handled);
+        let handler = bus.subscribe_fn(&[EventKind::Turn], move |_| {
+            counter.fetch_add(10, Ordering::SeqCst);
+        });
+        bus.unsubscribe(handler);
+        bus.publish(RuntimeEvent::TurnStarted {
+            input: "again".to_string(),
+        });
+        assert_eq!(handled.load(Ordering::SeqCst), 5);
+        assert_eq!(bus.subscriber_count(), 2);
+    }
+
+    #[test]
+    fn test_handlers_may_publish_without_deadlocking() {
+        let bus = EventBus::default();
+        let mut errors = bus.subscribe(&[EventKind::Error]);
+        let inner = bus.clone();
+        bus.subscribe_fn(&[EventKind::Turn], move |event| {
+            if let RuntimeEvent::TurnStarted { input } = event {
+                inner.publish(RuntimeEvent::StreamStalled {
+                    round: 1,
+                    silent: Duration::from_secs(30),
+                    action: StallAction::Waiting,
+                });
+                inner.subscribe_fn(&[EventKind::Tool], |_| {});
+                assert_eq!(input, "hello");
+            }
+        });
+
+        bus.publish(RuntimeEvent::TurnStarted {
+            input: "hello".to_string(),
+        });
+        assert!(matches!(
+            errors.try_recv().as_deref(),
+            Some(RuntimeEvent::StreamStalled { .. })
+        ));
+        assert_eq!(bus.lock().len(), 3);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::api::ollama::{OllamaProvider, OLLAMA_PORT};
+use crate::api::openai::OpenAiProvider;
+use crate::api::provider::Endpoint;
+use crate::api::ModelProvider;
+use crate::config::{DEFAULT_API_URL, MODEL_ALIASES};
+use crate::profiles::{active_profile_name, profiles_dir, DEFAULT_PROFILE};
+use crate::util::parse_bool_str;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const FIRST_RUN_ENV: &str = "VEX_FIRST_RUN";
+const ANTHROPIC_MODELS_URL: &str = "https://api.anthropic.com/v1/models";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const KEY_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const LOCAL_PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+/// OpenAI-compatible servers commonly left running on these ports.
+const LOCAL_SERVERS: [(u16, &str); 3] = [(1234, "LM Studio"), (8080, "llama.cpp"), (8000, "vLLM")];
+/// Models offered per local server, in the order it lists them.
+const MAX_LOCAL_MODELS: usize = 3;
+/// Anthropic model families offered, preferred first.
+const ANTHROPIC_FAMILIES: [&str; 3] = ["sonnet", "opus", "haiku"];
+
+/// A model vex can use with the credentials or servers found on this machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelCandidate {
+    pub provider: String,
+    pub api_url: String,
+    pub model: String,
+    /// False when the provider could not be asked which models it serves.
+    pub verified: bool,
+}
+
+/// The first-run prompt is shown unless `VEX_FIRST_RUN` is off, and only
+/// when nothing picks a model yet: no `ANTHROPIC_MODEL` or
+/// `ANTHROPIC_API_URL` and no profile, including the saved default.
+pub fn first_run_pending() -> bool {
+    let enabled = std::env::var(FIRST_RUN_ENV)
+        .ok()
+        .and_then(|value| parse_bool_str(&value))
+        .unwrap_or(true);
+    let set = |key: &str| std::env::var(key).is_ok_and(|value| !value.trim().is_empty());
+    enabled
+        && !set("ANTHROPIC_MODEL")
+        && !set("ANTHROPIC_API_URL")
+        && active_profile_name().is_none()
+}
+
+/// Probes for credentials and local servers, asks which model to use, and
+/// saves the answer as the default profile so later runs start with it.
+/// Returns `None` when nothing was found or the user skipped the choice.
+pub async fn run_first_run(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<Option<ModelCandidate>> {
+    writeln!(output, "No model is configured; looking for credentials...")?;
+    let candidates = probe_candidates(output).await?;
+    let Some(choice) = choose_candidate(&candidates, input, output)? else {
+        return Ok(None);
+    };
+    if let Some(dir) = profiles_dir() {
+        let path = save_default_profile(&dir, &choice)?;
+        writeln!(
+            output,
+            "Saved to {}; edit or delete it to choose again.",
+            path.display()
+        )?;
+    }
+    Ok(Some(choice))
+}
+
+async fn probe_candidates(output: &mut impl Write) -> Result<Vec<ModelCandidate>> {
+    let http = reqwest::Client::new();
+    let mut candidates = Vec::new();
+    if let Some(key) = std::env::var("ANTHROPIC_API_KEY")
+        .ok()
+        .filter(|key| !key.trim().is_empty())
+    {
+        match list_anthropic_models(&http, &key).await {
+            Ok(ids) => candidates.extend(anthropic_candidates(&ids)),
+            Err(KeyProbeError::Rejected(status)) => writeln!(
+                output,
+                "ANTHROPIC_API_KEY was rejected (HTTP {status}); skipping Anthropic models."
+            )?,
+            Err(KeyProbeError::Unreachable) => candidates.extend(anthropic_candidates(&[])),
+        }
+    }
+    let providers = local_providers(&http);
+    let probes = providers
+        .iter()
+        .map(|(provider, label)| list_local_models(provider.as_ref(), label));
+    for found in futures::future::join_all(probes).await {
+        candidates.extend(found);
+    }
+    Ok(candidates)
+}
+
+enum KeyProbeError {
+    Rejected(u16),
+    Unreachable,
+}
+
+async fn list_anthropic_models(
+    http: &reqwest::Client,
+    key: &str,
+) -> std::result::Result<Vec<String>, KeyProbeError> {
+    let response = http
+        .get(ANTHROPIC_MODELS_URL)
+        .header("x-api-key", key)
+        .header("anthropic-version", ANTHROPIC_VERSION)
+        .timeout(KEY_PROBE_TIMEOUT)
+        .send()
+        .await
+        .map_err(|_| KeyProbeError::Unreachable)?;
+    let status = response.status();
+    if matches!(status.as_u16(), 401 | 403) {
+        return Err(KeyProbeError::Rejected(status.as_u16()));
+    }
+    if !status.is_success() {
+        return Err(KeyProbeError::Unreachable);
+    }
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|_| KeyProbeError::Unreachable)?;
+    Ok(model_ids(&body))
+}
+
+/// Up to [`MAX_LOCAL_MODELS`] models `provider` lists, when its server
+/// answers within [`LOCAL_PROBE_TIMEOUT`].
+async fn list_local_models(provider: &dyn ModelProvider, label: &str) -> Vec<ModelCandidate> {
+    let Ok(Ok(models)) = tokio::time::timeout(LOCAL_PROBE_TIMEOUT, provider.list_models()).await
+    else {
+        return Vec::new();
+    };
+    models
+        .into_iter()
+        .take(MAX_LOCAL_MODELS)
+        .map(|model| ModelCandidate {
+            provider: label.to_string(),
+            api_url: provider.endpoint().api_url.clone(),
+            model,
+            verified: true,
+        })
+        .collect()
+}
+
+/// The local servers to probe: Ollama through its native API, so its
+/// models are used through `/api/chat`, and the rest through their
+/// OpenAI-compatible endpoints.
+fn local_providers(http: &reqwest::Client) -> Vec<(Box<dyn ModelProvider>, String)> {
+    let endpoint = |api_url: String| Endpoint {
+        http: http.clone(),
+        api_url,
+        api_key: None,
+    };
+    let mut providers: Vec<(Box<dyn ModelProvider>, String)> = vec![(
+        Box::new(OllamaProvider::new(endpoint(format!(
+            "http://localhost:{OLLAMA_PORT}/api/chat"
+        )))),
+        format!("Ollama on port {OLLAMA_PORT}"),
+    )];
+    for (port, name) in LOCAL_SERVERS {
+        providers.push((
+            Box::new(OpenAiProvider::new(endpoint(format!(
+                "http://localhost:{port}/v1/chat/completions"
+            )))),
+            format!("{name} on port {port}"),
+        ));
+    }
+    providers
+}
+
+/// `data[].id` from an Anthropic or OpenAI-style models list.
+fn model_ids(body: &Value) -> Vec<String> {
+    body.get("data")
+        .and_then(Value::as_array)
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|model| model.get("id")?.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The newest model of each family the key can use. The models endpoint
+/// lists newest first. With no list, the built-in aliases are offered
+/// unverified.
+fn anthropic_candidates(ids: &[String]) -> Vec<ModelCandidate> {
+    let candidate = |model: String, verified| ModelCandidate {
+        provider: "Anthropic".to_string(),
+        api_url: DEFAULT_API_URL.to_string(),
+        model,
+        verified,
+    };
+    if ids.is_empty() {
+        return MODEL_ALIASES
+            .iter()
+            .map(|(_, model)| candidate(model.to_string(), false))
+            .collect();
+    }
+    ANTHROPIC_FAMILIES
+        .iter()
+        .filter_map(|family| ids.iter().find(|id| id.contains(family)))
+        .map(|model| candidate(model.clone(), true))
+        .collect()
+}
+
+/// Lists `candidates` and reads a choice; Enter takes the first one and `s`
+/// skips. Asks again after an answer it does not understand.
+fn choose_candidate(
+    candidates: &[ModelCandidate],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<Option<ModelCandidate>> {
+    if candidates.is_empty() {
+        writeln!(
+            output,
+            "No credentials found. Set ANTHROPIC_API_KEY, or start a local model server \
+             (Ollama, LM Studio, llama.cpp, vLLM) and run vex again."
+        )?;
+        return Ok(None);
+    }
+    writeln!(output, "Available models:")?;
+    for (index, candidate) in candidates.iter().enumerate() {
+        let note = if candidate.verified {
+            ""
+        } else {
+            " (could not confirm access)"
+        };
+        writeln!(
+            output,
+            "  {}. {} ({}){note}",
+            index + 1,
+            candidate.model,
+            candidate.provider
+        )?;
+    }
+    loop {
+        write!(
+            output,
+            "Use which model? [1-{}, Enter for 1, s to skip] ",
+            candidates.len()
+        )?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+        match answer.trim() {
+            "" => return Ok(candidates.first().cloned()),
+            "s" | "S" => return Ok(None),
+            answer => {
+                if let Some(candidate) = answer
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| candidates.get(number.checked_sub(1)?))
+                {
+                    return Ok(Some(candidate.clone()));
+                }
+                writeln!(output, "'{answer}' is not one of the choices.")?;
+            }
+        }
+    }
+}
+
+/// Writes `choice` as the default profile in `dir`. The API key stays in
+/// the environment and is not copied into the file.
+fn save_default_profile(dir: &Path, choice: &ModelCandidate) -> Result<PathBuf> {
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{DEFAULT_PROFILE}.toml"));
+    let source = format!(
+        "# Chosen on first run; edit or delete this file to choose again.\n\
+         ANTHROPIC_API_URL = \"{}\"\nANTHROPIC_MODEL = \"{}\"\n",
+        choice.api_url, choice.model
+    );
+    fs::write(&path, source).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::Profile;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_choice_prefers_models_the_key_can_use_and_is_saved() -> Result<()> {
+        let body = json!({"data": [
+            {"id": "claude-opus-4-1-20250805"},
+            {"id": "claude-sonnet-4-20250514"},
+            {"id": "claude-3-5-haiku-20241022"},
+            {"id": "claude-3-7-sonnet-20250219"},
+        ]});
+        let mut candidates = anthropic_candidates(&model_ids(&body));
+        let models: Vec<&str> = candidates.iter().map(|c| c.model.as_str()).collect();
+        assert_eq!(
+            models,
+            vec![
+                "claude-sonnet-4-20250514",
+                "claude-opus-4-1-20250805",
+                "claude-3-5-haiku-20241022"
+            ]
+        );
+        assert!(anthropic_candidates(&[]).iter().all(|c| !c.verified));
+
+        candidates.push(ModelCandidate {
+            provider: "Ollama on port 11434".to_string(),
+            api_url: "http://localhost:11434/api/chat".to_string(),
+            model: "qwen2.5-coder:7b".to_string(),
+            verified: true,
+        });
+        let mut output = Vec::new();
+        let choice = choose_candidate(&candidates, &mut "9\n4\n".as_bytes(), &mut output)?;
+        let choice = choice.expect("choice");
+        assert_eq!(choice.model, "qwen2.5-coder:7b");
+        let output = String::from_utf8(output)?;
+        assert!(
+            output.contains("'9' is not one of the choices."),
+            "{output}"
+        );
+        let first = choose_candidate(&candidates, &mut "\n".as_bytes(), &mut Vec::new())?;
+        assert_eq!(first.as_ref(), candidates.first());
+        assert!(choose_candidate(&candidates, &mut "s\n".as_bytes(), &mut Vec::new())?.is_none());
+
+        let temp = TempDir::new()?;
+        save_default_profile(temp.path(), &choice)?;
+        let profile = Profile::load_from(temp.path(), DEFAULT_PROFILE)?;
+        assert_eq!(
+            profile.describe_settings(),
+            vec![
+                "ANTHROPIC_API_URL = http://localhost:11434/api/chat",
+                "ANTHROPIC_MODEL = qwen2.5-coder:7b",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::change_summary::{FileChangeKind, TurnChangeSummary};
+use crate::util::parse_bool_str;
+use std::path::Path;
+
+pub const FOLLOW_UPS_ENV: &str = "VEX_FOLLOW_UPS";
+/// Suggestions shown after a turn; each gets an Alt+digit shortcut.
+pub const MAX_FOLLOW_UPS: usize = 3;
+const CHANGELOG_NAMES: [&str; 3] = ["CHANGELOG.md", "CHANGELOG", "CHANGES.md"];
+const DOC_EXTENSIONS: [&str; 4] = ["md", "txt", "rst", "adoc"];
+
+/// Follow-up suggestions are shown only when `VEX_FOLLOW_UPS` is on.
+pub fn follow_ups_enabled_from_env() -> bool {
+    std::env::var(FOLLOW_UPS_ENV)
+        .ok()
+        .and_then(|value| parse_bool_str(&value))
+        .unwrap_or(false)
+}
+
+/// Up to [`MAX_FOLLOW_UPS`] one-line prompts worth sending after a turn,
+/// picked by local heuristics over what the turn's tools changed and ran;
+/// no model call is made. Empty when the turn changed nothing.
+pub fn suggest_follow_ups(changes: &TurnChangeSummary, workspace: &Path) -> Vec<String> {
+    let ran = |prefix: &str| {
+        changes
+            .commands
+            .iter()
+            .any(|command| command.starts_with(prefix))
+    };
+    let code: Vec<&str> = changes
+        .files
+        .iter()
+        .filter(|file| file.kind != FileChangeKind::Deleted && !is_doc(&file.path))
+        .map(|file| file.path.as_str())
+        .collect();
+    let touched_tests = code.iter().any(|path| is_test(path));
+
+    let mut suggestions = Vec::new();
+    if !code.is_empty() && !ran("run_tests") {
+        suggestions.push("run the tests".to_string());
+    }
+    if let Some(path) = code.iter().find(|path| !is_test(path)) {
+        if !touched_tests {
+            suggestions.push(format!("add tests for the change to {path}"));
+        }
+    }
+    if !changes.files.is_empty() {
+        let changelog = CHANGELOG_NAMES
+            .iter()
+            .find(|name| workspace.join(name).is_file());
+        if let Some(name) = changelog {
+            if !changes.files.iter().any(|file| file.path == *name) {
+                suggestions.push(format!("update {name}"));
+            }
+        }
+        if !ran("git commit") {
+            suggestions.push("review the diff and commit the changes".to_string());
+        }
+    }
+    suggestions.truncate(MAX_FOLLOW_UPS);
+    suggestions
+}
+
+fn is_doc(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| DOC_EXTENSIONS.contains(&extension))
+}
+
+fn is_test(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    path.split('/')
+        .any(|part| matches!(part, "tests" | "test" | "__tests__" | "spec"))
+        || name.starts_with("test_")
+        || name.contains("_test.")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+        || name == "tests.rs"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_suggestions_follow_what_the_turn_changed() {
+        let temp = TempDir::new().expect("temp");
+        let mut changes = TurnChangeSummary::new(1);
+        assert!(suggest_follow_ups(&changes, temp.path()).is_empty());
+
+        changes.record_file("src/parser.rs", Some("a\n"), Some("b\n"));
+        assert_eq!(
+            suggest_follow_ups(&changes, temp.path()),
+            [
+                "run the tests",
+                "add tests for the change to src/parser.rs",
+                "review the diff and commit the changes"
+            ]
+        );
+
+        std::fs::write(temp.path().join("CHANGELOG.md"), "").expect("changelog");
+        changes.record_file("tests/parser.rs", None, Some("#[test]\n"));
+        changes.record_command("run_tests parser".to_string());
+        changes.record_command("git commit".to_string());
+        assert_eq!(
+            suggest_follow_ups(&changes, temp.path()),
+            ["update CHANGELOG.md"]
+        );
+    }
+}
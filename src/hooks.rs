@@ -0,0 +1,366 @@
+use crate::profiles::config_dir;
+use crate::state_dirs::StateDirs;
+use crate::tools::{own_process_group, parse_flat_toml, ProcessGroupGuard, TomlScalar};
+use crate::util::{sha256_hex, unix_now};
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// A hook still running after this long is killed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+/// Under the user config dir: one `<sha256> <workspace>` line per
+/// repository hooks file the user reviewed with `vex trust-hooks`.
+const TRUSTED_HOOKS_FILE: &str = "trusted_hooks";
+pub const TRUST_HOOKS_USAGE: &str = "usage: vex trust-hooks";
+
+/// A point in a session that can run a hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    TurnStart,
+    TurnComplete,
+    Error,
+    SessionEnd,
+}
+
+impl HookEvent {
+    pub const ALL: [Self; 4] = [
+        Self::TurnStart,
+        Self::TurnComplete,
+        Self::Error,
+        Self::SessionEnd,
+    ];
+
+    /// The key in `hooks.toml`, e.g. `on_turn_start`.
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::TurnStart => "on_turn_start",
+            Self::TurnComplete => "on_turn_complete",
+            Self::Error => "on_error",
+            Self::SessionEnd => "on_session_end",
+        }
+    }
+
+    /// The `event` field of the payload, e.g. `turn_start`.
+    pub fn name(self) -> &'static str {
+        &self.key()["on_".len()..]
+    }
+}
+
+/// Commands run on conversation lifecycle events, loaded from
+/// `.aistar/hooks.toml`:
+///
+/// ```toml
+/// on_turn_start = "notify-send 'vex is working'"
+/// on_turn_complete = "./scripts/log-turn.sh"
+/// on_error = "curl -s -d @- https://ci.example/vex-error"
+/// on_session_end = "notify-send 'vex session ended'"
+/// ```
+///
+/// Each runs under `sh -c` in the workspace root with the event as a JSON
+/// object on stdin and its name in `VEX_HOOK_EVENT`. Hooks are best-effort:
+/// a failing or slow hook never fails or delays a turn. The file comes with
+/// the repository, so it only runs once the user trusted its exact contents.
+#[derive(Debug, Clone, Default)]
+pub struct LifecycleHooks {
+    working_dir: PathBuf,
+    commands: Vec<(HookEvent, String)>,
+}
+
+impl LifecycleHooks {
+    pub fn parse(working_dir: PathBuf, source: &str) -> Result<Self> {
+        let mut commands = Vec::new();
+        for (key, value) in parse_flat_toml(source)? {
+            let Some(event) = HookEvent::ALL.into_iter().find(|event| event.key() == key) else {
+                bail!(
+                    "unknown hook '{key}'; expected one of {}",
+                    HookEvent::ALL.map(HookEvent::key).join(", ")
+                );
+            };
+            match value {
+                TomlScalar::String(command) if !command.trim().is_empty() => {
+                    commands.push((event, command));
+                }
+                TomlScalar::String(_) => {}
+                TomlScalar::Bool(_) => bail!("'{key}' must be a command string"),
+            }
+        }
+        Ok(Self {
+            working_dir,
+            commands,
+        })
+    }
+
+    /// Reads `.aistar/hooks.toml`; `None` when it does not exist. A file
+    /// whose contents the user has not trusted is an error.
+    pub fn load(working_dir: &Path) -> Result<Option<Self>> {
+        Self::load_with_trust(working_dir, trust_file().as_deref())
+    }
+
+    fn load_with_trust(working_dir: &Path, trust_file: Option<&Path>) -> Result<Option<Self>> {
+        let state_dirs = StateDirs::for_workspace(working_dir);
+        let path = state_dirs.hooks_file();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let source = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let hooks = Self::parse(working_dir.to_path_buf(), &source)
+            .with_context(|| format!("invalid {}", state_dirs.display(&path)))?;
+        if !trust_file.is_some_and(|file| is_trusted(file, working_dir, &source)) {
+            bail!(
+                "{} is not trusted; review it and run `vex trust-hooks` to enable it",
+                state_dirs.display(&path)
+            );
+        }
+        Ok(Some(hooks))
+    }
+
+    pub fn command(&self, event: HookEvent) -> Option<&str> {
+        self.commands
+            .iter()
+            .find(|(configured, _)| *configured == event)
+            .map(|(_, command)| command.as_str())
+    }
+
+    /// `details` with the event name, workspace, and a Unix timestamp.
+    pub fn payload(&self, event: HookEvent, details: Value) -> Value {
+        let mut payload = json!({
+            "event": event.name(),
+            "workspace": self.working_dir.display().to_string(),
+            "timestamp": unix_now(),
+        });
+        if let (Some(payload), Value::Object(details)) = (payload.as_object_mut(), details) {
+            payload.extend(details);
+        }
+        payload
+    }
+
+    /// Runs the hook for `event` in the background, if one is configured.
+    pub fn fire(&self, event: HookEvent, details: Value) {
+        self.fire_then(event, details, || {});
+    }
+
+    /// Like [`LifecycleHooks::fire`], calling `after` once the hook has
+    /// finished (or failed), e.g. to forget state the hook may have changed.
+    pub fn fire_then(
+        &self,
+        event: HookEvent,
+        details: Value,
+        after: impl FnOnce() + Send + 'static,
+    ) {
+        if self.command(event).is_none() {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let hooks = self.clone();
+        handle.spawn(async move {
+            let _ = hooks.run(event, details, HOOK_TIMEOUT).await;
+            after();
+        });
+    }
+
+    /// Runs the hook for `event` and waits for it, at most `timeout`.
+    pub async fn run(&self, event: HookEvent, details: Value, timeout: Duration) -> Result<()> {
+        let Some(command) = self.command(event) else {
+            return Ok(());
+        };
+        let payload = self.payload(event, details).to_string();
+        let mut process = Command::new("sh");
+        process
+            .arg("-c")
+            .arg(command)
+            .current_dir(&self.working_dir)
+            .env("VEX_HOOK_EVENT", event.name())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        let mut child = own_process_group(&mut process)
+            .spawn()
+            .with_context(|| format!("failed to start {} hook", event.key()))?;
+        // Timing out, or the runtime dropping this task, kills anything the
+        // hook started as well.
+        let mut group = ProcessGroupGuard::new(child.id());
+        let finished = tokio::time::timeout(timeout, async {
+            if let Some(mut stdin) = child.stdin.take() {
+                // A hook that ignores its stdin closes the pipe early.
+                let _ = stdin.write_all(payload.as_bytes()).await;
+            }
+            child.wait().await
+        })
+        .await;
+        match finished {
+            Ok(status) => {
+                group.disarm();
+                let status = status?;
+                if !status.success() {
+                    bail!("{} hook exited with {status}", event.key());
+                }
+                Ok(())
+            }
+            Err(_) => bail!(
+                "{} hook timed out after {}s",
+                event.key(),
+                timeout.as_secs()
+            ),
+        }
+    }
+}
+
+fn trust_file() -> Option<PathBuf> {
+    Some(config_dir()?.join(TRUSTED_HOOKS_FILE))
+}
+
+fn trust_line(working_dir: &Path, source: &str) -> String {
+    format!(
+        "{} {}",
+        sha256_hex(source.as_bytes()),
+        working_dir.display()
+    )
+}
+
+fn is_trusted(trust_file: &Path, working_dir: &Path, source: &str) -> bool {
+    let line = trust_line(working_dir, source);
+    std::fs::read_to_string(trust_file)
+        .is_ok_and(|trusted| trusted.lines().any(|trusted| trusted == line))
+}
+
+/// Records the current contents of the workspace's hooks file as trusted.
+/// Editing the file later needs another trust.
+fn trust(trust_file: &Path, working_dir: &Path, source: &str) -> Result<()> {
+    if is_trusted(trust_file, working_dir, source) {
+        return Ok(());
+    }
+    if let Some(parent) = trust_file.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let mut trusted = std::fs::read_to_string(trust_file).unwrap_or_default();
+    if !trusted.is_empty() && !trusted.ends_with('\n') {
+        trusted.push('\n');
+    }
+    trusted.push_str(&trust_line(working_dir, source));
+    trusted.push('\n');
+    std::fs::write(trust_file, trusted)
+        .with_context(|| format!("failed to write {}", trust_file.display()))
+}
+
+/// `vex trust-hooks`: shows the workspace's hook commands and trusts them.
+pub fn run_trust_hooks_cli(working_dir: &Path, args: &[String]) -> Result<()> {
+    if !args.is_empty() {
+        bail!("{TRUST_HOOKS_USAGE}");
+    }
+    let state_dirs = StateDirs::for_workspace(working_dir);
+    let path = state_dirs.hooks_file();
+    let source = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let hooks = LifecycleHooks::parse(working_dir.to_path_buf(), &source)
+        .with_context(|| format!("invalid {}", state_dirs.display(&path)))?;
+    let trust_file =
+        trust_file().context("cannot locate the config dir: set VEX_CONFIG_DIR or HOME")?;
+    trust(&trust_file, working_dir, &source)?;
+    println!("trusted {}:", state_dirs.display(&path));
+    for (event, command) in &hooks.commands {
+        println!("  {} = {command}", event.key());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_rejects_unknown_hooks() {
+        let hooks = LifecycleHooks::parse(
+            PathBuf::from("/work"),
+            "on_turn_start = \"echo start\"\non_error = \"\"\n",
+        )
+        .expect("parse");
+        assert_eq!(hooks.command(HookEvent::TurnStart), Some("echo start"));
+        assert_eq!(hooks.command(HookEvent::Error), None);
+        assert!(LifecycleHooks::parse(PathBuf::from("/work"), "on_exit = \"true\"\n").is_err());
+        assert!(LifecycleHooks::parse(PathBuf::from("/work"), "on_error = true\n").is_err());
+    }
+
+    #[test]
+    fn test_repository_hooks_load_only_once_trusted() {
+        let temp = TempDir::new().expect("temp");
+        let trust_file = temp.path().join("config").join(TRUSTED_HOOKS_FILE);
+        let hooks_file = StateDirs::for_workspace(temp.path()).hooks_file();
+        std::fs::create_dir_all(hooks_file.parent().expect("parent")).expect("dir");
+        std::fs::write(&hooks_file, "on_turn_start = \"echo start\"\n").expect("write");
+
+        let error =
+            LifecycleHooks::load_with_trust(temp.path(), Some(&trust_file)).expect_err("untrusted");
+        assert!(error.to_string().contains("vex trust-hooks"), "{error}");
+        assert!(LifecycleHooks::load_with_trust(temp.path(), None).is_err());
+
+        let source = std::fs::read_to_string(&hooks_file).expect("read");
+        trust(&trust_file, temp.path(), &source).expect("trust");
+        let hooks = LifecycleHooks::load_with_trust(temp.path(), Some(&trust_file))
+            .expect("trusted")
+            .expect("hooks");
+        assert_eq!(hooks.command(HookEvent::TurnStart), Some("echo start"));
+
+        std::fs::write(&hooks_file, "on_turn_start = \"curl evil\"\n").expect("write");
+        assert!(LifecycleHooks::load_with_trust(temp.path(), Some(&trust_file)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hook_gets_the_event_payload_on_stdin() {
+        let temp = TempDir::new().expect("temp");
+        let hooks = LifecycleHooks::parse(
+            temp.path().to_path_buf(),
+            "on_turn_complete = \"cat > event.json; echo $VEX_HOOK_EVENT > name.txt\"\non_error = \"exit 3\"\n",
+        )
+        .expect("parse");
+
+        hooks
+            .run(
+                HookEvent::TurnComplete,
+                json!({ "elapsed_ms": 1200, "changes": ["[changes this turn: 1 file (+1 -0)]"] }),
+                HOOK_TIMEOUT,
+            )
+            .await
+            .expect("hook runs");
+        let payload: Value = serde_json::from_str(
+            &std::fs::read_to_string(temp.path().join("event.json")).expect("payload"),
+        )
+        .expect("json");
+        assert_eq!(payload["event"], "turn_complete");
+        assert_eq!(payload["elapsed_ms"], 1200);
+        assert_eq!(payload["workspace"], temp.path().display().to_string());
+        assert_eq!(
+            std::fs::read_to_string(temp.path().join("name.txt")).expect("name"),
+            "turn_complete\n"
+        );
+
+        let error = hooks
+            .run(HookEvent::Error, json!({}), HOOK_TIMEOUT)
+            .await
+            .expect_err("failing hook");
+        assert!(error.to_string().contains("on_error hook exited"));
+        assert!(hooks
+            .run(HookEvent::SessionEnd, json!({}), HOOK_TIMEOUT)
+            .await
+            .is_ok());
+
+        // `after` runs once the hook is done, even when it failed.
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+        hooks.fire_then(HookEvent::Error, json!({}), move || {
+            let _ = done_tx.send(());
+        });
+        tokio::time::timeout(HOOK_TIMEOUT, done_rx)
+            .await
+            .expect("hook finished")
+            .expect("after ran");
+    }
+}
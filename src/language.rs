@@ -0,0 +1,377 @@
+use crate::util::parse_bool_str;
+
+pub const RESPONSE_LANGUAGE_ENV: &str = "VEX_RESPONSE_LANGUAGE";
+pub const LANGUAGE_RETRY_ENV: &str = "VEX_RESPONSE_LANGUAGE_RETRY";
+/// Replies with fewer letters of prose than this are too short to judge.
+const MIN_PROSE_LETTERS: usize = 40;
+/// A Latin-script language must match at least this many stopwords.
+const MIN_STOPWORD_HITS: usize = 3;
+/// Below this share of letters in the expected script, a reply for a
+/// non-Latin language counts as written in something else.
+const MIN_TARGET_SCRIPT_SHARE: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+    Hangul,
+    Kana,
+    Han,
+}
+
+/// A language drift can be detected for: its names, its script, and for
+/// Latin-script languages, common short words that rarely appear in the
+/// others.
+struct KnownLanguage {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    script: Script,
+    stopwords: &'static [&'static str],
+}
+
+const KNOWN_LANGUAGES: &[KnownLanguage] = &[
+    KnownLanguage {
+        name: "English",
+        aliases: &["en", "english"],
+        script: Script::Latin,
+        stopwords: &[
+            "the", "and", "is", "are", "of", "to", "that", "this", "with", "for", "it", "you",
+            "not", "be", "was",
+        ],
+    },
+    KnownLanguage {
+        name: "German",
+        aliases: &["de", "german", "deutsch"],
+        script: Script::Latin,
+        stopwords: &[
+            "der", "die", "das", "und", "ist", "nicht", "ein", "eine", "mit", "für", "auf", "ich",
+            "sie", "den", "wird",
+        ],
+    },
+    KnownLanguage {
+        name: "French",
+        aliases: &["fr", "french", "français", "francais"],
+        script: Script::Latin,
+        stopwords: &[
+            "le", "les", "et", "est", "des", "une", "pour", "dans", "pas", "qui", "avec", "sur",
+            "ce", "vous", "sont",
+        ],
+    },
+    KnownLanguage {
+        name: "Spanish",
+        aliases: &["es", "spanish", "español", "espanol"],
+        script: Script::Latin,
+        stopwords: &[
+            "el", "los", "las", "y", "es", "que", "para", "con", "una", "por", "del", "está",
+            "como", "pero", "son",
+        ],
+    },
+    KnownLanguage {
+        name: "Portuguese",
+        aliases: &["pt", "portuguese", "português", "portugues"],
+        script: Script::Latin,
+        stopwords: &[
+            "o", "os", "e", "é", "que", "para", "com", "uma", "não", "do", "da", "em", "você",
+            "isso", "são",
+        ],
+    },
+    KnownLanguage {
+        name: "Italian",
+        aliases: &["it", "italian", "italiano"],
+        script: Script::Latin,
+        stopwords: &[
+            "il", "gli", "e", "è", "che", "di", "per", "con", "non", "della", "sono", "questo",
+            "anche", "nel", "come",
+        ],
+    },
+    KnownLanguage {
+        name: "Dutch",
+        aliases: &["nl", "dutch", "nederlands"],
+        script: Script::Latin,
+        stopwords: &[
+            "de", "het", "een", "en", "niet", "van", "dat", "met", "voor", "op", "zijn", "je",
+            "wordt", "ook", "deze",
+        ],
+    },
+    KnownLanguage {
+        name: "Russian",
+        aliases: &["ru", "russian", "русский"],
+        script: Script::Cyrillic,
+        stopwords: &[],
+    },
+    KnownLanguage {
+        name: "Greek",
+        aliases: &["el", "greek", "ελληνικά"],
+        script: Script::Greek,
+        stopwords: &[],
+    },
+    KnownLanguage {
+        name: "Arabic",
+        aliases: &["ar", "arabic", "العربية"],
+        script: Script::Arabic,
+        stopwords: &[],
+    },
+    KnownLanguage {
+        name: "Hebrew",
+        aliases: &["he", "hebrew", "עברית"],
+        script: Script::Hebrew,
+        stopwords: &[],
+    },
+    KnownLanguage {
+        name: "Hindi",
+        aliases: &["hi", "hindi", "हिन्दी", "हिंदी"],
+        script: Script::Devanagari,
+        stopwords: &[],
+    },
+    KnownLanguage {
+        name: "Thai",
+        aliases: &["th", "thai", "ไทย"],
+        script: Script::Thai,
+        stopwords: &[],
+    },
+    KnownLanguage {
+        name: "Korean",
+        aliases: &["ko", "korean", "한국어"],
+        script: Script::Hangul,
+        stopwords: &[],
+    },
+    KnownLanguage {
+        name: "Japanese",
+        aliases: &["ja", "japanese", "日本語"],
+        script: Script::Kana,
+        stopwords: &[],
+    },
+    KnownLanguage {
+        name: "Chinese",
+        aliases: &["zh", "chinese", "中文"],
+        script: Script::Han,
+        stopwords: &[],
+    },
+];
+
+/// The language every reply should be written in, from
+/// `VEX_RESPONSE_LANGUAGE`. Any language can be named; drift is detected
+/// for the ones vex knows (English, German, French, Spanish, Portuguese,
+/// Italian, Dutch, Russian, Greek, Arabic, Hebrew, Hindi, Thai, Korean,
+/// Japanese, Chinese).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseLanguage {
+    pub name: String,
+    known: Option<usize>,
+}
+
+impl ResponseLanguage {
+    /// A name (`German`, `deutsch`) or code (`de`, `pt-BR`).
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        let lower = value.to_lowercase();
+        let primary = lower.split(['-', '_']).next().unwrap_or_default();
+        let known = KNOWN_LANGUAGES.iter().position(|language| {
+            language
+                .aliases
+                .iter()
+                .any(|alias| *alias == lower || *alias == primary)
+        });
+        Some(Self {
+            name: known.map_or_else(
+                || value.to_string(),
+                |index| KNOWN_LANGUAGES[index].name.to_string(),
+            ),
+            known,
+        })
+    }
+
+    pub fn from_env() -> Option<Self> {
+        Self::parse(&std::env::var(RESPONSE_LANGUAGE_ENV).ok()?)
+    }
+
+    /// System prompt line asking for replies in this language.
+    pub fn directive(&self) -> String {
+        format!(
+            "Write every reply in {}, even when the user, files, or tool output use another language. Keep code, identifiers, paths, and quoted output unchanged.",
+            self.name
+        )
+    }
+
+    /// The known language `reply` appears to be written in, when that is
+    /// not this one. `None` when the reply matches, is too short to judge,
+    /// or this language cannot be detected.
+    pub fn detect_drift(&self, reply: &str) -> Option<&'static str> {
+        let target = &KNOWN_LANGUAGES[self.known?];
+        let prose = strip_code(reply);
+        let counts = script_counts(&prose);
+        let letters: usize = counts.iter().map(|(_, count)| count).sum();
+        if letters < MIN_PROSE_LETTERS {
+            return None;
+        }
+        if target.script != Script::Latin {
+            let share = |script| {
+                counts
+                    .iter()
+                    .filter(|(counted, _)| *counted == script)
+                    .map(|(_, count)| *count)
+                    .sum::<usize>() as f32
+                    / letters as f32
+            };
+            // Japanese prose mixes kana with kanji.
+            let target_share = match target.script {
+                Script::Kana => share(Script::Kana) + share(Script::Han),
+                script => share(script),
+            };
+            if target_share >= MIN_TARGET_SCRIPT_SHARE {
+                return None;
+            }
+        }
+        let detected = detect_language(&prose, &counts)?;
+        (detected.name != target.name).then_some(detected.name)
+    }
+
+    /// Follow-up message asking the model to rewrite its last reply.
+    pub fn correction_prompt(&self, detected: &str) -> String {
+        format!(
+            "Your last reply was written in {detected}. Rewrite it in {}, keeping its content, code, and identifiers unchanged.",
+            self.name
+        )
+    }
+}
+
+/// Replies that drift into another language are rewritten with one
+/// follow-up request when `VEX_RESPONSE_LANGUAGE_RETRY` is on.
+pub fn language_retry_enabled_from_env() -> bool {
+    std::env::var(LANGUAGE_RETRY_ENV)
+        .ok()
+        .and_then(|value| parse_bool_str(&value))
+        .unwrap_or(false)
+}
+
+/// `text` without fenced code blocks and inline code spans.
+fn strip_code(text: &str) -> String {
+    let mut prose = String::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        for (index, part) in line.split('`').enumerate() {
+            if index % 2 == 0 {
+                prose.push_str(part);
+                prose.push(' ');
+            }
+        }
+        prose.push('\n');
+    }
+    prose
+}
+
+fn script_of(ch: char) -> Option<Script> {
+    let script = match ch as u32 {
+        0x3040..=0x30FF | 0x31F0..=0x31FF => Script::Kana,
+        0x4E00..=0x9FFF | 0x3400..=0x4DBF => Script::Han,
+        0xAC00..=0xD7AF | 0x1100..=0x11FF | 0x3130..=0x318F => Script::Hangul,
+        0x0400..=0x04FF => Script::Cyrillic,
+        0x0370..=0x03FF => Script::Greek,
+        0x0600..=0x06FF => Script::Arabic,
+        0x0590..=0x05FF => Script::Hebrew,
+        0x0900..=0x097F => Script::Devanagari,
+        0x0E00..=0x0E7F => Script::Thai,
+        _ if ch.is_alphabetic() && (ch.is_ascii() || ('\u{00C0}'..='\u{024F}').contains(&ch)) => {
+            Script::Latin
+        }
+        _ => return None,
+    };
+    Some(script)
+}
+
+fn script_counts(prose: &str) -> Vec<(Script, usize)> {
+    let mut counts: Vec<(Script, usize)> = Vec::new();
+    for script in prose.chars().filter_map(script_of) {
+        match counts.iter_mut().find(|(counted, _)| *counted == script) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((script, 1)),
+        }
+    }
+    counts
+}
+
+/// The known language of `prose`: by dominant script, and for Latin script
+/// by the language whose stopwords clearly match most often.
+fn detect_language(prose: &str, counts: &[(Script, usize)]) -> Option<&'static KnownLanguage> {
+    let count = |script| {
+        counts
+            .iter()
+            .find(|(counted, _)| *counted == script)
+            .map_or(0, |(_, count)| *count)
+    };
+    let (dominant, _) = counts.iter().max_by_key(|(_, count)| *count)?;
+    let script = match dominant {
+        Script::Han if count(Script::Kana) > 0 => Script::Kana,
+        script => *script,
+    };
+    if script != Script::Latin {
+        return KNOWN_LANGUAGES
+            .iter()
+            .find(|language| language.script == script);
+    }
+    let words: Vec<String> = prose
+        .split(|ch: char| !ch.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut scores: Vec<(usize, &KnownLanguage)> = KNOWN_LANGUAGES
+        .iter()
+        .filter(|language| language.script == Script::Latin)
+        .map(|language| {
+            let hits = words
+                .iter()
+                .filter(|word| language.stopwords.contains(&word.as_str()))
+                .count();
+            (hits, language)
+        })
+        .collect();
+    scores.sort_by_key(|(hits, _)| std::cmp::Reverse(*hits));
+    let (best, language) = scores[0];
+    let runner_up = scores.get(1).map_or(0, |(hits, _)| *hits);
+    // Require a clear lead so mixed or technical text is not flagged.
+    (best >= MIN_STOPWORD_HITS && best * 2 > runner_up * 3).then_some(language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drift_is_detected_outside_code() {
+        let german = ResponseLanguage::parse("de-DE").expect("language");
+        assert_eq!(german.name, "German");
+        assert!(german.directive().contains("in German"));
+
+        let english_reply = "The function is not called anywhere, so the branch with the early return is dead code and can be removed.";
+        assert_eq!(german.detect_drift(english_reply), Some("English"));
+        let german_reply = "Die Funktion wird nicht aufgerufen, und der Zweig mit der frühen Rückkehr ist toter Code, den man entfernen kann.";
+        assert_eq!(german.detect_drift(german_reply), None);
+        let code_only = "Die Änderung:\n```rust\n// the value is not used and the function is dead\nfn main() {}\n```";
+        assert_eq!(german.detect_drift(code_only), None);
+
+        let japanese = ResponseLanguage::parse("Japanese").expect("language");
+        let mixed = "この関数は `parse_config` から呼ばれていないため、削除しても問題ありません。テストも通ります。";
+        assert_eq!(japanese.detect_drift(mixed), None);
+        assert_eq!(japanese.detect_drift(english_reply), Some("English"));
+
+        let unknown = ResponseLanguage::parse("Klingon").expect("language");
+        assert_eq!(unknown.name, "Klingon");
+        assert_eq!(unknown.detect_drift(english_reply), None);
+        assert!(ResponseLanguage::parse("  ").is_none());
+    }
+}
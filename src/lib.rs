@@ -1,14 +1,44 @@
 pub mod api;
 pub mod app;
+pub mod attachments;
+pub mod audit;
+pub mod autonomous;
+pub mod bench;
+pub mod change_summary;
+pub mod claims;
+pub mod clipboard;
 pub mod config;
+pub mod debug_parse;
 pub mod edit_diff;
+pub mod events;
+pub mod first_run;
+pub mod follow_ups;
+pub mod hooks;
+pub mod language;
+pub mod mirror;
+pub mod notifier;
+pub mod presets;
+pub mod print_mode;
+pub mod profiles;
+pub mod rebase_assist;
+pub mod releases;
 pub mod runtime;
+pub mod sandbox;
+pub mod share;
+pub mod speech;
 pub mod state;
+pub mod state_dirs;
+pub mod status_server;
+pub mod supervisor;
+pub mod tags;
 pub mod terminal;
+pub mod tool_keys;
 pub mod tool_preview;
 pub mod tools;
+pub mod turn_overrides;
 pub mod types;
 pub mod ui;
+pub mod usage;
 pub mod util;
 
 #[cfg(test)]
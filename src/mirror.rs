@@ -0,0 +1,467 @@
+use crate::terminal;
+use crate::ui::diff_style::DiffStyle;
+use crate::ui::render::{
+    history_content_width_for_area, history_visual_line_count, render_messages, render_status_line,
+};
+use anyhow::{bail, Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::widgets::Clear;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(unix)]
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+pub const MIRROR_USAGE: &str = "usage: vex mirror --attach <socket>";
+const MIRROR_EVENT_BUFFER: usize = 1024;
+
+/// Transcript changes sent from a running session to attached mirrors, one
+/// JSON object per line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MirrorEvent {
+    Snapshot { lines: Vec<String>, status: String },
+    Line { index: usize, text: String },
+    Truncate { len: usize },
+    Status { status: String },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MirrorState {
+    pub lines: Vec<String>,
+    pub status: String,
+}
+
+impl MirrorState {
+    pub fn apply(&mut self, event: MirrorEvent) {
+        match event {
+            MirrorEvent::Snapshot { lines, status } => {
+                self.lines = lines;
+                self.status = status;
+            }
+            MirrorEvent::Line { index, text } => {
+                if index >= self.lines.len() {
+                    self.lines.resize(index + 1, String::new());
+                }
+                self.lines[index] = text;
+            }
+            MirrorEvent::Truncate { len } => self.lines.truncate(len),
+            MirrorEvent::Status { status } => self.status = status,
+        }
+    }
+
+    fn snapshot(&self) -> MirrorEvent {
+        MirrorEvent::Snapshot {
+            lines: self.lines.clone(),
+            status: self.status.clone(),
+        }
+    }
+}
+
+/// Events that turn `previous` into `current`. When most rows moved (for
+/// example after the history cap drops old lines) a full snapshot is cheaper.
+pub fn diff_transcript(previous: &MirrorState, current: &MirrorState) -> Vec<MirrorEvent> {
+    let changed: Vec<usize> = (0..current.lines.len())
+        .filter(|&index| previous.lines.get(index) != Some(&current.lines[index]))
+        .collect();
+    if changed.len() > 8 && changed.len() * 2 > current.lines.len() {
+        return vec![current.snapshot()];
+    }
+
+    let mut events = Vec::new();
+    if current.lines.len() < previous.lines.len() {
+        events.push(MirrorEvent::Truncate {
+            len: current.lines.len(),
+        });
+    }
+    events.extend(changed.into_iter().map(|index| MirrorEvent::Line {
+        index,
+        text: current.lines[index].clone(),
+    }));
+    if current.status != previous.status {
+        events.push(MirrorEvent::Status {
+            status: current.status.clone(),
+        });
+    }
+    events
+}
+
+/// Publishes the transcript of a running session on a Unix socket. Mirrors
+/// only ever receive events; nothing read from a mirror connection is used.
+pub struct MirrorServer {
+    path: PathBuf,
+    state: Arc<Mutex<MirrorState>>,
+    events: broadcast::Sender<MirrorEvent>,
+    accept_task: JoinHandle<()>,
+}
+
+impl MirrorServer {
+    /// Binds `path`, replacing a stale socket file left by a crashed session
+    /// but never any other kind of file. The socket is readable by its owner
+    /// only. Must be called inside a Tokio runtime.
+    #[cfg(unix)]
+    pub fn bind(path: &Path) -> Result<Self> {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                bail!(
+                    "refusing to replace {}: it exists and is not a socket",
+                    path.display()
+                );
+            }
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                bail!("mirror socket {} is already in use", path.display());
+            }
+            std::fs::remove_file(path)
+                .with_context(|| format!("failed to remove stale socket {}", path.display()))?;
+        }
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("failed to bind mirror socket {}", path.display()))?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("failed to restrict mirror socket {}", path.display()))?;
+        let state = Arc::new(Mutex::new(MirrorState::default()));
+        let (events, _) = broadcast::channel(MIRROR_EVENT_BUFFER);
+        let accept_task =
+            tokio::spawn(accept_mirrors(listener, Arc::clone(&state), events.clone()));
+        Ok(Self {
+            path: path.to_path_buf(),
+            state,
+            events,
+            accept_task,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn bind(path: &Path) -> Result<Self> {
+        bail!(
+            "mirror socket {} needs Unix domain sockets, which this platform lacks",
+            path.display()
+        )
+    }
+
+    /// Binds the socket named by `VEX_MIRROR_SOCKET`, if set.
+    pub fn from_env() -> Option<Result<Self>> {
+        let path = std::env::var("VEX_MIRROR_SOCKET").ok()?;
+        let path = path.trim();
+        (!path.is_empty()).then(|| Self::bind(Path::new(path)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn publish(&self, lines: &[String], status: &str) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if state.lines == lines && state.status == status {
+            return;
+        }
+        let current = MirrorState {
+            lines: lines.to_vec(),
+            status: status.to_string(),
+        };
+        for event in diff_transcript(&state, &current) {
+            let _ = self.events.send(event);
+        }
+        *state = current;
+    }
+}
+
+impl Drop for MirrorServer {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+async fn accept_mirrors(
+    listener: UnixListener,
+    state: Arc<Mutex<MirrorState>>,
+    events: broadcast::Sender<MirrorEvent>,
+) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let receiver = events.subscribe();
+        let snapshot = match state.lock() {
+            Ok(state) => state.snapshot(),
+            Err(_) => continue,
+        };
+        tokio::spawn(serve_mirror(stream, snapshot, receiver, Arc::clone(&state)));
+    }
+}
+
+#[cfg(unix)]
+async fn serve_mirror(
+    mut stream: UnixStream,
+    snapshot: MirrorEvent,
+    mut receiver: broadcast::Receiver<MirrorEvent>,
+    state: Arc<Mutex<MirrorState>>,
+) {
+    let mut next = Some(snapshot);
+    loop {
+        let event = match next.take() {
+            Some(event) => event,
+            None => match receiver.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(_)) => match state.lock() {
+                    Ok(state) => state.snapshot(),
+                    Err(_) => return,
+                },
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        };
+        let Ok(mut line) = serde_json::to_string(&event) else {
+            continue;
+        };
+        line.push('\n');
+        if stream.write_all(line.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// `vex mirror --attach <socket>`: renders a live, read-only copy of another
+/// session's transcript. Only `q`, `Esc`, `Ctrl+C` and scroll keys are handled.
+pub async fn run_mirror_cli(args: &[String]) -> Result<()> {
+    let socket = match args {
+        [flag, socket] if flag == "--attach" => PathBuf::from(socket),
+        _ => bail!("{MIRROR_USAGE}"),
+    };
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<Option<MirrorEvent>>();
+    read_mirror_events(&socket, event_tx).await?;
+
+    let mut terminal = terminal::setup()?;
+    let result = mirror_loop(&mut terminal, &socket, &mut event_rx);
+    terminal::restore()?;
+    result
+}
+
+/// Connects to `socket` and forwards its events, then `None` once the
+/// session goes away.
+#[cfg(unix)]
+async fn read_mirror_events(
+    socket: &Path,
+    event_tx: mpsc::UnboundedSender<Option<MirrorEvent>>,
+) -> Result<()> {
+    let stream = UnixStream::connect(socket)
+        .await
+        .with_context(|| format!("failed to attach to {}", socket.display()))?;
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stream).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Ok(event) = serde_json::from_str(&line) {
+                if event_tx.send(Some(event)).is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = event_tx.send(None);
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn read_mirror_events(
+    socket: &Path,
+    _event_tx: mpsc::UnboundedSender<Option<MirrorEvent>>,
+) -> Result<()> {
+    bail!(
+        "cannot attach to {}: this platform lacks Unix domain sockets",
+        socket.display()
+    )
+}
+
+fn mirror_loop(
+    terminal: &mut terminal::TerminalType,
+    socket: &Path,
+    event_rx: &mut mpsc::UnboundedReceiver<Option<MirrorEvent>>,
+) -> Result<()> {
+    let mut state = MirrorState::default();
+    let mut connected = true;
+    let mut lines_from_bottom = 0usize;
+    let diff_style = DiffStyle::from_env();
+    loop {
+        while let Ok(event) = event_rx.try_recv() {
+            match event {
+                Some(event) => state.apply(event),
+                None => connected = false,
+            }
+        }
+
+        let connection = if connected { "live" } else { "disconnected" };
+        let header = format!(
+            "mirror (read-only, {connection}) {} | {}",
+            socket.display(),
+            state.status
+        );
+        terminal.draw(|frame| {
+            let area = frame.area();
+            frame.render_widget(Clear, area);
+            let panes = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(1),
+                    Constraint::Min(1),
+                    Constraint::Length(1),
+                ])
+                .split(area);
+            let width = history_content_width_for_area(&state.lines, panes[1]);
+            let total_rows = history_visual_line_count(&state.lines, width);
+            let max_scroll = total_rows.saturating_sub(panes[1].height as usize);
+            lines_from_bottom = lines_from_bottom.min(max_scroll);
+            render_status_line(frame, panes[0], &header);
+            render_messages(
+                frame,
+                panes[1],
+                &state.lines,
+                max_scroll - lines_from_bottom,
+                &diff_style,
+            );
+            render_status_line(
+                frame,
+                panes[2],
+                "q quit   up/down/pgup/pgdn scroll   end follow",
+            );
+        })?;
+
+        if !event::poll(Duration::from_millis(50))? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                KeyCode::Up => lines_from_bottom += 1,
+                KeyCode::Down => lines_from_bottom = lines_from_bottom.saturating_sub(1),
+                KeyCode::PageUp => lines_from_bottom += 10,
+                KeyCode::PageDown => lines_from_bottom = lines_from_bottom.saturating_sub(10),
+                KeyCode::End => lines_from_bottom = 0,
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn state(lines: &[&str], status: &str) -> MirrorState {
+        MirrorState {
+            lines: lines.iter().map(|line| line.to_string()).collect(),
+            status: status.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_diff_transcript_round_trips_through_apply() {
+        let cases = [
+            (state(&[], ""), state(&["> hi", "hello"], "mode:busy")),
+            (
+                state(&["> hi", "hel"], "a"),
+                state(&["> hi", "hello", ""], "a"),
+            ),
+            (state(&["a", "b", "c"], "x"), state(&["a"], "y")),
+        ];
+        for (previous, current) in cases {
+            let mut mirrored = previous.clone();
+            for event in diff_transcript(&previous, &current) {
+                mirrored.apply(event);
+            }
+            assert_eq!(mirrored, current);
+        }
+    }
+
+    #[test]
+    fn test_diff_transcript_sends_snapshot_when_history_shifts() {
+        let previous = MirrorState {
+            lines: (0..20).map(|n| n.to_string()).collect(),
+            status: String::new(),
+        };
+        let current = MirrorState {
+            lines: (5..25).map(|n| n.to_string()).collect(),
+            status: String::new(),
+        };
+        let events = diff_transcript(&previous, &current);
+        assert!(matches!(events.as_slice(), [MirrorEvent::Snapshot { .. }]));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_attached_mirror_receives_snapshot_then_updates() -> Result<()> {
+        let temp = TempDir::new()?;
+        let socket = temp.path().join("vex.sock");
+        let server = MirrorServer::bind(&socket)?;
+        server.publish(&["> hello".to_string()], "mode:busy");
+
+        let stream = UnixStream::connect(&socket).await?;
+        let mut lines = BufReader::new(stream).lines();
+        let read_event = |line: Option<String>| -> MirrorEvent {
+            serde_json::from_str(&line.expect("event line")).expect("valid event")
+        };
+        assert_eq!(
+            read_event(lines.next_line().await?),
+            MirrorEvent::Snapshot {
+                lines: vec!["> hello".to_string()],
+                status: "mode:busy".to_string(),
+            }
+        );
+
+        server.publish(
+            &["> hello".to_string(), "hi there".to_string()],
+            "mode:busy",
+        );
+        assert_eq!(
+            read_event(lines.next_line().await?),
+            MirrorEvent::Line {
+                index: 1,
+                text: "hi there".to_string(),
+            }
+        );
+
+        drop(server);
+        assert!(!socket.exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bind_refuses_live_socket_and_replaces_stale_one() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new()?;
+        let socket = temp.path().join("vex.sock");
+        let server = MirrorServer::bind(&socket)?;
+        assert_eq!(
+            std::fs::metadata(&socket)?.permissions().mode() & 0o777,
+            0o600
+        );
+        assert!(MirrorServer::bind(&socket).is_err());
+        drop(server);
+
+        drop(std::os::unix::net::UnixListener::bind(&socket)?);
+        let _server = MirrorServer::bind(&socket)?;
+
+        let notes = temp.path().join("notes.txt");
+        std::fs::write(&notes, b"keep me")?;
+        let error = MirrorServer::bind(&notes)
+            .err()
+            .expect("a regular file is not replaced");
+        assert!(error.to_string().contains("not a socket"));
+        assert_eq!(std::fs::read(&notes)?, b"keep me");
+        Ok(())
+    }
+}
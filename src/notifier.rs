@@ -0,0 +1,256 @@
+use crate::util::format_elapsed;
+use serde_json::json;
+use std::path::Path;
+use std::time::Duration;
+
+pub const WEBHOOK_URL_ENV: &str = "VEX_WEBHOOK_URL";
+/// Overrides the link back to the session, e.g. a URL of a remote desktop.
+pub const WEBHOOK_LINK_ENV: &str = "VEX_WEBHOOK_LINK";
+pub const WEBHOOK_MIN_TURN_SECS_ENV: &str = "VEX_WEBHOOK_MIN_TURN_SECS";
+const DEFAULT_MIN_TURN: Duration = Duration::from_secs(30);
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_DETAIL_CHARS: usize = 300;
+
+/// Something worth telling a person who is not watching the terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotifyEvent {
+    ApprovalPending {
+        tool_name: String,
+        /// First line of the approval preview.
+        detail: String,
+    },
+    TurnComplete {
+        elapsed: Duration,
+        /// `None` when the turn succeeded.
+        error: Option<String>,
+        /// `[changes this turn: ...]` lines, when tools changed anything.
+        changes: Vec<String>,
+    },
+}
+
+/// Where the session runs, for the message and its link back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionLink {
+    pub host: String,
+    pub workspace: String,
+    /// A URL or an attach command; `None` when there is nothing to open.
+    pub link: Option<String>,
+}
+
+impl SessionLink {
+    /// The host name, the workspace, and `VEX_WEBHOOK_LINK`, falling back to
+    /// a `vex mirror --attach` command when `VEX_MIRROR_SOCKET` is set.
+    pub fn detect(working_dir: &Path) -> Self {
+        let host = host_name();
+        let link = non_empty_env(WEBHOOK_LINK_ENV).or_else(|| {
+            non_empty_env("VEX_MIRROR_SOCKET")
+                .map(|socket| format!("ssh -t {host} vex mirror --attach {socket}"))
+        });
+        Self {
+            host,
+            workspace: working_dir.display().to_string(),
+            link,
+        }
+    }
+}
+
+fn non_empty_env(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn host_name() -> String {
+    non_empty_env("HOSTNAME")
+        .or_else(|| {
+            std::fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|name| name.trim().to_string())
+        })
+        .or_else(|| {
+            std::process::Command::new("hostname")
+                .output()
+                .ok()
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown host".to_string())
+}
+
+/// Posts approval requests and finished long turns to a webhook, as the
+/// `{"text": ...}` payload Slack incoming webhooks accept. Delivery is
+/// best-effort and never delays or fails a turn.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    url: String,
+    session: SessionLink,
+    min_turn: Duration,
+    http: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(url: String, session: SessionLink) -> Self {
+        Self {
+            url,
+            session,
+            min_turn: DEFAULT_MIN_TURN,
+            http: reqwest::Client::builder()
+                .timeout(POST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Only turns that ran at least `min_turn` are reported.
+    pub fn with_min_turn(mut self, min_turn: Duration) -> Self {
+        self.min_turn = min_turn;
+        self
+    }
+
+    /// Reads `VEX_WEBHOOK_URL` and `VEX_WEBHOOK_MIN_TURN_SECS` (default 30).
+    pub fn from_env(working_dir: &Path) -> Option<Self> {
+        let url = non_empty_env(WEBHOOK_URL_ENV)?;
+        let mut notifier = Self::new(url, SessionLink::detect(working_dir));
+        if let Some(secs) =
+            non_empty_env(WEBHOOK_MIN_TURN_SECS_ENV).and_then(|value| value.parse().ok())
+        {
+            notifier = notifier.with_min_turn(Duration::from_secs(secs));
+        }
+        Some(notifier)
+    }
+
+    /// Whether `event` is reported at all.
+    pub fn wants(&self, event: &NotifyEvent) -> bool {
+        match event {
+            NotifyEvent::ApprovalPending { .. } => true,
+            NotifyEvent::TurnComplete { elapsed, .. } => *elapsed >= self.min_turn,
+        }
+    }
+
+    pub fn payload(&self, event: &NotifyEvent) -> serde_json::Value {
+        let session = &self.session;
+        let mut text = match event {
+            NotifyEvent::ApprovalPending { tool_name, detail } => {
+                let detail = clip(detail);
+                let detail = if detail.is_empty() {
+                    String::new()
+                } else {
+                    format!("\n> {detail}")
+                };
+                format!(
+                    "vex on {} is waiting for approval to run `{tool_name}` in `{}`{detail}",
+                    session.host, session.workspace
+                )
+            }
+            NotifyEvent::TurnComplete {
+                elapsed,
+                error,
+                changes,
+            } => {
+                let outcome = match error {
+                    None => "finished a turn".to_string(),
+                    Some(error) => format!("stopped with an error: {}", clip(error)),
+                };
+                let mut text = format!(
+                    "vex on {} {outcome} after {} in `{}`",
+                    session.host,
+                    format_elapsed(*elapsed),
+                    session.workspace
+                );
+                if !changes.is_empty() {
+                    text.push_str(&format!("\n```\n{}\n```", changes.join("\n")));
+                }
+                text
+            }
+        };
+        match session.link.as_deref() {
+            Some(link) if link.starts_with("http://") || link.starts_with("https://") => {
+                text.push_str(&format!("\n<{link}|Open the session>"));
+            }
+            Some(link) => text.push_str(&format!("\nAttach: `{link}`")),
+            None => {}
+        }
+        json!({ "text": text })
+    }
+
+    /// Posts `event` in the background when it is wanted.
+    pub fn notify(&self, event: NotifyEvent) {
+        if !self.wants(&event) {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let request = self.http.post(&self.url).json(&self.payload(&event));
+        handle.spawn(async move {
+            let _ = request.send().await;
+        });
+    }
+}
+
+fn clip(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default().trim();
+    let mut clipped: String = line.chars().take(MAX_DETAIL_CHARS).collect();
+    if clipped.len() < line.len() {
+        clipped.push_str("...");
+    }
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notifier(link: Option<&str>) -> Notifier {
+        Notifier::new(
+            "http://127.0.0.1:9/hook".to_string(),
+            SessionLink {
+                host: "build-01".to_string(),
+                workspace: "/work/app".to_string(),
+                link: link.map(str::to_string),
+            },
+        )
+    }
+
+    #[test]
+    fn test_payloads_are_slack_text_with_a_link_back() {
+        let approval = NotifyEvent::ApprovalPending {
+            tool_name: "write_file".to_string(),
+            detail: "path: src/lib.rs\ncontent: ...".to_string(),
+        };
+        assert_eq!(
+            notifier(Some("https://desk.example/s/1")).payload(&approval),
+            json!({
+                "text": "vex on build-01 is waiting for approval to run `write_file` in `/work/app`\n> path: src/lib.rs\n<https://desk.example/s/1|Open the session>"
+            })
+        );
+
+        let turn = NotifyEvent::TurnComplete {
+            elapsed: Duration::from_secs(125),
+            error: None,
+            changes: vec!["[changes this turn: 1 file (+3 -1)]".to_string()],
+        };
+        assert_eq!(
+            notifier(Some("ssh -t build-01 vex mirror --attach /tmp/vex.sock")).payload(&turn)
+                ["text"],
+            "vex on build-01 finished a turn after 2m 05s in `/work/app`\n```\n[changes this turn: 1 file (+3 -1)]\n```\nAttach: `ssh -t build-01 vex mirror --attach /tmp/vex.sock`"
+        );
+    }
+
+    #[test]
+    fn test_short_turns_are_not_reported() {
+        let notifier = notifier(None).with_min_turn(Duration::from_secs(60));
+        let turn = |secs| NotifyEvent::TurnComplete {
+            elapsed: Duration::from_secs(secs),
+            error: None,
+            changes: Vec::new(),
+        };
+        assert!(!notifier.wants(&turn(59)));
+        assert!(notifier.wants(&turn(60)));
+        assert!(notifier.wants(&NotifyEvent::ApprovalPending {
+            tool_name: "git_commit".to_string(),
+            detail: String::new(),
+        }));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::state_dirs::StateDirs;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CONVENTIONS_PLACEHOLDER: &str = "{{conventions}}";
+const TITLE_MAX_CHARS: usize = 80;
+
+/// A first-message template from `.aistar/presets/<name>.md`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preset {
+    pub name: String,
+    pub title: String,
+    pub template: String,
+}
+
+/// Project prompt presets. A leading `# Heading` becomes the picker title and
+/// is left out of the template; `{{conventions}}` in a template expands to
+/// `.aistar/conventions.md` so presets can share one conventions section.
+#[derive(Debug, Clone)]
+pub struct PresetStore {
+    dir: PathBuf,
+    conventions_path: PathBuf,
+}
+
+impl PresetStore {
+    pub fn for_workspace(working_dir: &Path) -> Self {
+        let state_dirs = StateDirs::for_workspace(working_dir);
+        Self {
+            dir: state_dirs.presets_dir(),
+            conventions_path: state_dirs.conventions_file(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Presets sorted by name; an absent directory means no presets.
+    pub fn load(&self) -> Result<Vec<Preset>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to read {}", self.dir.display()))
+            }
+        };
+        let conventions = fs::read_to_string(&self.conventions_path).unwrap_or_default();
+        let mut presets = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let raw = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            presets.push(parse_preset(name, &raw, conventions.trim()));
+        }
+        presets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(presets)
+    }
+}
+
+fn parse_preset(name: &str, raw: &str, conventions: &str) -> Preset {
+    let raw = raw.trim_start();
+    let (title, body) = match raw.strip_prefix("# ") {
+        Some(rest) => {
+            let (heading, body) = rest.split_once('\n').unwrap_or((rest, ""));
+            (heading.trim().to_string(), body)
+        }
+        None => {
+            let first_line = raw.lines().next().unwrap_or_default().trim();
+            (first_line.chars().take(TITLE_MAX_CHARS).collect(), raw)
+        }
+    };
+    Preset {
+        name: name.to_string(),
+        title,
+        template: body
+            .replace(CONVENTIONS_PLACEHOLDER, conventions)
+            .trim()
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_sorts_presets_and_expands_conventions() -> Result<()> {
+        let temp = TempDir::new()?;
+        let store = PresetStore::for_workspace(temp.path());
+        assert!(store.load()?.is_empty());
+
+        fs::create_dir_all(store.dir())?;
+        fs::write(
+            store.dir().join("bugfix.md"),
+            "# Fix a bug\n\nBug:\nRepro:\n\n{{conventions}}\n",
+        )?;
+        fs::write(
+            store.dir().join("refactor.md"),
+            "Refactor without behavior change.\n",
+        )?;
+        fs::write(store.dir().join("notes.txt"), "ignored")?;
+        fs::write(
+            StateDirs::for_workspace(temp.path()).conventions_file(),
+            "Conventions: anyhow errors, VEX_* config.\n",
+        )?;
+
+        let presets = store.load()?;
+        assert_eq!(
+            presets,
+            vec![
+                Preset {
+                    name: "bugfix".to_string(),
+                    title: "Fix a bug".to_string(),
+                    template: "Bug:\nRepro:\n\nConventions: anyhow errors, VEX_* config."
+                        .to_string(),
+                },
+                Preset {
+                    name: "refactor".to_string(),
+                    title: "Refactor without behavior change.".to_string(),
+                    template: "Refactor without behavior change.".to_string(),
+                },
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use crate::state::{ApprovalQuery, ConversationManager, ToolApprovalDecision};
+use anyhow::{bail, Result};
+use std::io::Read;
+
+pub const PRINT_FLAGS: [&str; 2] = ["-p", "--print"];
+pub const PRINT_USAGE: &str = "usage: vex -p <prompt>, or <input> | vex -p [prompt]";
+
+/// Options of a one-turn `-p` run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrintOptions {
+    /// `None` when the prompt comes only from stdin.
+    pub prompt: Option<String>,
+}
+
+impl PrintOptions {
+    /// Removes `-p [prompt]` from `args`, leaving the rest for
+    /// `Config::apply_cli_args`. An argument after `-p` that starts with `-`
+    /// is left as a flag. `None` when `-p` is absent.
+    pub fn take(args: &mut Vec<String>) -> Result<Option<Self>> {
+        let Some(at) = args
+            .iter()
+            .position(|arg| PRINT_FLAGS.contains(&arg.as_str()))
+        else {
+            return Ok(None);
+        };
+        args.remove(at);
+        let prompt = match args.get(at) {
+            Some(value) if !value.starts_with('-') => Some(args.remove(at)),
+            _ => None,
+        };
+        if args.iter().any(|arg| PRINT_FLAGS.contains(&arg.as_str())) {
+            bail!("-p given twice\n{PRINT_USAGE}");
+        }
+        Ok(Some(Self {
+            prompt: prompt.filter(|prompt| !prompt.trim().is_empty()),
+        }))
+    }
+
+    /// The turn's message: the prompt followed by piped input, or either
+    /// one alone.
+    pub fn message(&self, piped: Option<&str>) -> Result<String> {
+        let piped = piped
+            .map(str::trim_end)
+            .filter(|text| !text.trim().is_empty());
+        match (self.prompt.as_deref(), piped) {
+            (Some(prompt), Some(input)) => Ok(format!("{prompt}\n\n{input}")),
+            (Some(prompt), None) => Ok(prompt.to_string()),
+            (None, Some(input)) => Ok(input.to_string()),
+            (None, None) => bail!("no prompt given and nothing piped on stdin\n{PRINT_USAGE}"),
+        }
+    }
+}
+
+/// Denies every call that would prompt; the approval policy is applied as
+/// configured, but nobody is there to answer.
+pub async fn deny_headless(query: ApprovalQuery) -> ToolApprovalDecision {
+    ToolApprovalDecision::denied_with_reason(&format!(
+        "non-interactive -p run: nobody can approve {}; answer without it",
+        query.tool_name
+    ))
+}
+
+/// Runs one turn and returns the final response.
+pub async fn run_print(conversation: &mut ConversationManager, message: String) -> Result<String> {
+    conversation.send_message(message, None).await
+}
+
+/// Runs `-p` to completion and prints only the final response to stdout.
+/// Piped stdin is read when stdin is not a terminal. Fails, and so exits
+/// non-zero, when the turn fails.
+pub async fn run_print_cli(
+    mut conversation: ConversationManager,
+    options: PrintOptions,
+    stdin_piped: bool,
+) -> Result<()> {
+    let piped = if stdin_piped {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        Some(input)
+    } else {
+        None
+    };
+    let message = options.message(piped.as_deref())?;
+    let reply = run_print(&mut conversation, message).await?;
+    println!("{}", reply.trim_end());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{mock_client::MockApiClient, ApiClient};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_takes_the_prompt_and_prints_the_final_reply() {
+        let mut rest = args(&["-p", "summarize", "--check-key"]);
+        let options = PrintOptions::take(&mut rest).expect("take").expect("print");
+        assert_eq!(options.prompt.as_deref(), Some("summarize"));
+        assert_eq!(rest, args(&["--check-key"]));
+        assert_eq!(
+            options.message(Some("log line\n")).expect("message"),
+            "summarize\n\nlog line"
+        );
+
+        let mut rest = args(&["--print", "--check-key"]);
+        let stdin_only = PrintOptions::take(&mut rest).expect("take").expect("print");
+        assert_eq!(stdin_only.prompt, None);
+        assert_eq!(
+            stdin_only.message(Some("explain this")).expect("message"),
+            "explain this"
+        );
+        assert!(stdin_only.message(Some("  \n")).is_err());
+        assert_eq!(
+            PrintOptions::take(&mut args(&["--check-key"])).expect("take"),
+            None
+        );
+        assert!(PrintOptions::take(&mut args(&["-p", "a", "-p", "b"])).is_err());
+
+        let round = vec![
+            r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_print","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string(),
+            r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#.to_string(),
+            r#"event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Three files changed."}}"#.to_string(),
+            r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":4}}"#.to_string(),
+            r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+        ];
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(vec![round])));
+        let mut conversation = ConversationManager::new_mock(client, HashMap::new());
+        let reply = run_print(&mut conversation, "summarize".to_string())
+            .await
+            .expect("reply");
+        assert_eq!(reply, "Three files changed.");
+    }
+}
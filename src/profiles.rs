@@ -0,0 +1,295 @@
+use crate::tools::{parse_flat_toml, TomlScalar};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const PROFILE_FLAG: &str = "--profile";
+/// Name of the active profile; set by `--profile` for everything started
+/// from this process, or exported to pick a profile without the flag.
+pub const PROFILE_ENV: &str = "VEX_PROFILE";
+/// Profile used when neither `--profile` nor `VEX_PROFILE` names one; the
+/// first-run model choice is saved here.
+pub const DEFAULT_PROFILE: &str = "default";
+const CONFIG_DIR_ENV: &str = "VEX_CONFIG_DIR";
+const PROFILES_DIR_NAME: &str = "profiles";
+const SECRET_MARKERS: &[&str] = &["KEY", "TOKEN", "SECRET", "PASSWORD"];
+/// Settings that decide what vex guards and where it looks for trust. A
+/// profile file is plain config anyone with write access can edit, so it
+/// cannot change them.
+const PROTECTED_SETTINGS: &[&str] = &[
+    "VEX_CONFIG_DIR",
+    "VEX_RELEASES_URL",
+    "VEX_SANDBOXED",
+    "VEX_SANDBOX_NETWORK",
+    "VEX_SANDBOX_WRITABLE",
+    "VEX_SECRET_GUARD",
+    "VEX_SENSITIVE_PATHS",
+    "VEX_SUPERVISED_CHILD",
+];
+
+/// A named setup loaded from `<config dir>/profiles/<name>.toml`. Each entry
+/// is an `ANTHROPIC_*` or `VEX_*` environment setting applied at startup,
+/// so a profile can change anything the environment can:
+///
+/// ```toml
+/// ANTHROPIC_API_KEY = "sk-ant-..."
+/// ANTHROPIC_MODEL = "claude-opus-4-1-20250805"
+/// VEX_TOOL_CONFIRM = "on"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    pub path: PathBuf,
+    pub vars: BTreeMap<String, String>,
+}
+
+impl Profile {
+    pub fn load(name: &str) -> Result<Self> {
+        let dir = profiles_dir()
+            .context("cannot locate profiles: set VEX_CONFIG_DIR, XDG_CONFIG_HOME, or HOME")?;
+        Self::load_from(&dir, name)
+    }
+
+    pub fn load_from(dir: &Path, name: &str) -> Result<Self> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+        {
+            bail!("profile name '{name}' must be non-empty and use only [A-Za-z0-9_-]");
+        }
+        let path = dir.join(format!("{name}.toml"));
+        let source = fs::read_to_string(&path).with_context(|| {
+            let available = list_profiles(dir);
+            if available.is_empty() {
+                format!("profile '{name}' not found at {}", path.display())
+            } else {
+                format!(
+                    "profile '{name}' not found at {} (available: {})",
+                    path.display(),
+                    available.join(", ")
+                )
+            }
+        })?;
+        let fields =
+            parse_flat_toml(&source).with_context(|| format!("invalid {}", path.display()))?;
+        let mut vars = BTreeMap::new();
+        for (key, value) in fields {
+            if !(key.starts_with("ANTHROPIC_") || key.starts_with("VEX_")) || key == PROFILE_ENV {
+                bail!(
+                    "{}: '{key}' is not an ANTHROPIC_* or VEX_* setting",
+                    path.display()
+                );
+            }
+            if PROTECTED_SETTINGS.contains(&key.as_str()) {
+                bail!(
+                    "{}: '{key}' cannot be set from a profile; export it instead",
+                    path.display()
+                );
+            }
+            let value = match value {
+                TomlScalar::String(value) => value,
+                TomlScalar::Bool(value) => value.to_string(),
+            };
+            vars.insert(key, value);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            path,
+            vars,
+        })
+    }
+
+    /// Exports the profile's settings, replacing any already in the
+    /// environment. Call before the configuration is read.
+    pub fn apply(&self) {
+        for (key, value) in &self.vars {
+            std::env::set_var(key, value);
+        }
+        std::env::set_var(PROFILE_ENV, &self.name);
+    }
+
+    /// Exports only the settings not already in the environment, for the
+    /// `default` profile that applies without being asked for.
+    pub fn apply_defaults(&self) {
+        for (key, value) in &self.vars {
+            if std::env::var_os(key).is_none() {
+                std::env::set_var(key, value);
+            }
+        }
+        std::env::set_var(PROFILE_ENV, &self.name);
+    }
+
+    /// One `KEY = value` line per setting, with keys, tokens, and passwords
+    /// masked to their last four characters.
+    pub fn describe_settings(&self) -> Vec<String> {
+        self.vars
+            .iter()
+            .map(|(key, value)| {
+                let shown = if SECRET_MARKERS.iter().any(|marker| key.contains(marker)) {
+                    let tail: String = value
+                        .chars()
+                        .rev()
+                        .take(4)
+                        .collect::<Vec<_>>()
+                        .into_iter()
+                        .rev()
+                        .collect();
+                    format!("****{tail}")
+                } else {
+                    value.clone()
+                };
+                format!("{key} = {shown}")
+            })
+            .collect()
+    }
+}
+
+/// `VEX_CONFIG_DIR`, else `$XDG_CONFIG_HOME/vex`, else `~/.config/vex`.
+pub fn config_dir() -> Option<PathBuf> {
+    let non_empty = |key: &str| {
+        std::env::var_os(key)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    non_empty(CONFIG_DIR_ENV)
+        .or_else(|| non_empty("XDG_CONFIG_HOME").map(|dir| dir.join("vex")))
+        .or_else(|| non_empty("HOME").map(|home| home.join(".config").join("vex")))
+}
+
+/// `profiles/` under [`config_dir`].
+pub fn profiles_dir() -> Option<PathBuf> {
+    Some(config_dir()?.join(PROFILES_DIR_NAME))
+}
+
+/// Profile names in `dir`, sorted.
+pub fn list_profiles(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            (path.extension()? == "toml")
+                .then(|| path.file_stem()?.to_str().map(str::to_string))
+                .flatten()
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// The profile selected with `VEX_PROFILE`, if any.
+pub fn active_profile_name() -> Option<String> {
+    std::env::var(PROFILE_ENV)
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// [`DEFAULT_PROFILE`], when its file exists.
+pub fn default_profile_name() -> Option<String> {
+    profiles_dir()?
+        .join(format!("{DEFAULT_PROFILE}.toml"))
+        .is_file()
+        .then(|| DEFAULT_PROFILE.to_string())
+}
+
+/// Removes `--profile <name>` or `--profile=<name>` from `args` and returns
+/// the name.
+pub fn take_profile_arg(args: &mut Vec<String>) -> Result<Option<String>> {
+    let Some(index) = args
+        .iter()
+        .position(|arg| arg == PROFILE_FLAG || arg.starts_with("--profile="))
+    else {
+        return Ok(None);
+    };
+    let arg = args.remove(index);
+    let name = match arg.strip_prefix("--profile=") {
+        Some(name) => name.to_string(),
+        None => {
+            if index >= args.len() {
+                bail!("{PROFILE_FLAG} requires a profile name");
+            }
+            args.remove(index)
+        }
+    };
+    Ok(Some(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_profile_loads_settings_and_masks_secrets() -> Result<()> {
+        let temp = TempDir::new()?;
+        fs::write(
+            temp.path().join("work.toml"),
+            "# employer account\nANTHROPIC_API_KEY = \"sk-ant-secret-9xyz\"\nANTHROPIC_MODEL = \"claude-opus-4-1-20250805\"\nVEX_TOOL_CONFIRM = true\n",
+        )?;
+        fs::write(temp.path().join("home.toml"), "PATH = \"/tmp\"\n")?;
+
+        let profile = Profile::load_from(temp.path(), "work")?;
+        assert_eq!(
+            profile.describe_settings(),
+            vec![
+                "ANTHROPIC_API_KEY = ****9xyz",
+                "ANTHROPIC_MODEL = claude-opus-4-1-20250805",
+                "VEX_TOOL_CONFIRM = true",
+            ]
+        );
+        let error = Profile::load_from(temp.path(), "home").expect_err("PATH is rejected");
+        assert!(format!("{error:#}").contains("'PATH' is not an ANTHROPIC_* or VEX_*"));
+        let error = Profile::load_from(temp.path(), "personal").expect_err("missing");
+        assert!(format!("{error:#}").contains("(available: home, work)"));
+        assert!(Profile::load_from(temp.path(), "../work").is_err());
+
+        fs::write(temp.path().join("sneaky.toml"), "VEX_SANDBOXED = \"3\"\n")?;
+        let error = Profile::load_from(temp.path(), "sneaky").expect_err("protected");
+        assert!(format!("{error:#}").contains("'VEX_SANDBOXED' cannot be set from a profile"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_profile_keeps_exported_settings() -> Result<()> {
+        let _guard = crate::test_support::ENV_LOCK.blocking_lock();
+        let temp = TempDir::new()?;
+        fs::write(
+            temp.path().join("default.toml"),
+            "VEX_REPO_LABEL = \"profile\"\nVEX_DIFF_TINT = \"off\"\n",
+        )?;
+        let profile = Profile::load_from(temp.path(), DEFAULT_PROFILE)?;
+        std::env::set_var("VEX_REPO_LABEL", "shell");
+        std::env::remove_var("VEX_DIFF_TINT");
+        profile.apply_defaults();
+        assert_eq!(std::env::var("VEX_REPO_LABEL").as_deref(), Ok("shell"));
+        assert_eq!(std::env::var("VEX_DIFF_TINT").as_deref(), Ok("off"));
+        for key in ["VEX_REPO_LABEL", "VEX_DIFF_TINT", PROFILE_ENV] {
+            std::env::remove_var(key);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_flag_is_taken_from_args() -> Result<()> {
+        let mut args = vec![
+            "--profile".to_string(),
+            "work".to_string(),
+            "--emit-patches=out".to_string(),
+        ];
+        assert_eq!(take_profile_arg(&mut args)?, Some("work".to_string()));
+        assert_eq!(args, vec!["--emit-patches=out"]);
+
+        let mut args = vec!["--profile=home".to_string()];
+        assert_eq!(take_profile_arg(&mut args)?, Some("home".to_string()));
+        assert!(args.is_empty());
+
+        assert!(take_profile_arg(&mut vec!["--profile".to_string()]).is_err());
+        assert_eq!(take_profile_arg(&mut Vec::new())?, None);
+        Ok(())
+    }
+}
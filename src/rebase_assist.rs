@@ -0,0 +1,459 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::mpsc;
+use std::thread;
+
+/// Unchanged lines shown above and below each conflict.
+const CONTEXT_LINES: usize = 5;
+/// Conflicts longer than this are cut short in the prompt; the model reads
+/// the rest from the file.
+const MAX_SHOWN_CONFLICT_LINES: usize = 120;
+
+/// Where a rebase that is in progress has stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebaseProgress {
+    /// 1-based number of the commit being replayed.
+    pub step: usize,
+    pub total: usize,
+    /// First line of the commit being replayed, when git recorded it.
+    pub subject: Option<String>,
+}
+
+impl RebaseProgress {
+    pub fn describe(&self) -> String {
+        match &self.subject {
+            Some(subject) => format!("step {}/{} ({subject})", self.step, self.total),
+            None => format!("step {}/{}", self.step, self.total),
+        }
+    }
+}
+
+/// What the rebase is waiting for after a step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebaseStep {
+    /// A commit stopped with conflicts; the prompt asks the model to resolve
+    /// them.
+    Conflicts {
+        progress: RebaseProgress,
+        files: Vec<String>,
+        prompt: String,
+    },
+    /// Stopped without conflicts, such as on an `edit` line of an
+    /// interactive todo list.
+    Stopped(RebaseProgress),
+    Finished,
+    Aborted,
+}
+
+/// A `/rebase-assist` request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebaseAction {
+    /// Start a rebase onto the upstream, or pick up the one in progress
+    /// when it is empty.
+    Start(String),
+    Continue,
+    Abort,
+}
+
+/// What a [`RebaseAssist::spawn`] run hands back: the assist itself, what
+/// it was asked to do, and how that went.
+#[derive(Debug)]
+pub struct RebaseOutcome {
+    pub assist: RebaseAssist,
+    pub action: RebaseAction,
+    pub step: Result<RebaseStep>,
+}
+
+/// Drives a rebase one stop at a time: the model resolves the conflicts of
+/// each stop, and the rebase continues once the user approves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebaseAssist {
+    root: PathBuf,
+    files: Vec<String>,
+}
+
+impl RebaseAssist {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn run(&mut self, action: &RebaseAction) -> Result<RebaseStep> {
+        match action {
+            RebaseAction::Start(upstream) => self.start(upstream),
+            RebaseAction::Continue => self.continue_rebase(),
+            RebaseAction::Abort => self.abort().map(|()| RebaseStep::Aborted),
+        }
+    }
+
+    /// Runs `action` on its own thread, since git can take a while; the
+    /// outcome arrives on the returned channel.
+    pub fn spawn(mut self, action: RebaseAction) -> mpsc::Receiver<RebaseOutcome> {
+        let (outcome_tx, outcome_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let step = self.run(&action);
+            let _ = outcome_tx.send(RebaseOutcome {
+                assist: self,
+                action,
+                step,
+            });
+        });
+        outcome_rx
+    }
+
+    /// Starts `git rebase <upstream>`, or picks up the rebase already in
+    /// progress when `upstream` is empty.
+    pub fn start(&mut self, upstream: &str) -> Result<RebaseStep> {
+        let in_progress = rebase_progress(&self.root)?.is_some();
+        match (upstream.is_empty(), in_progress) {
+            (true, false) => bail!("no rebase in progress; /rebase-assist <upstream> starts one"),
+            (false, true) => {
+                bail!("a rebase is already in progress; /rebase-assist picks it up")
+            }
+            (true, true) => {}
+            (false, false) => {
+                let output = git(&self.root, &["rebase", upstream])?;
+                if !output.status.success() && rebase_progress(&self.root)?.is_none() {
+                    bail!("git rebase {upstream} failed: {}", stderr(&output));
+                }
+            }
+        }
+        self.next_step()
+    }
+
+    /// Files the model was asked to resolve at the current stop.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// Files of the current stop that still contain conflict markers. A
+    /// deleted file is resolved: deleting it is how a delete/modify
+    /// conflict is settled.
+    pub fn unresolved(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|file| match fs::read_to_string(self.root.join(file)) {
+                Ok(text) => has_conflict_markers(&text),
+                Err(error) => error.kind() != std::io::ErrorKind::NotFound,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Stages the resolved files, deletions included, and runs
+    /// `git rebase --continue`. Refuses while any file still has conflict
+    /// markers.
+    pub fn continue_rebase(&mut self) -> Result<RebaseStep> {
+        if rebase_progress(&self.root)?.is_none() {
+            self.files.clear();
+            return Ok(RebaseStep::Finished);
+        }
+        let unresolved = self.unresolved();
+        if !unresolved.is_empty() {
+            bail!("still has conflict markers: {}", unresolved.join(", "));
+        }
+        if !self.files.is_empty() {
+            let mut args = vec!["add", "-A", "--"];
+            args.extend(self.files.iter().map(String::as_str));
+            let output = git(&self.root, &args)?;
+            if !output.status.success() {
+                bail!("git add failed: {}", stderr(&output));
+            }
+        }
+        let output = git(&self.root, &["rebase", "--continue"])?;
+        if !output.status.success() && conflicted_files(&self.root)?.is_empty() {
+            bail!("git rebase --continue failed: {}", stderr(&output));
+        }
+        self.next_step()
+    }
+
+    pub fn abort(&mut self) -> Result<()> {
+        self.files.clear();
+        let output = git(&self.root, &["rebase", "--abort"])?;
+        if !output.status.success() {
+            bail!("git rebase --abort failed: {}", stderr(&output));
+        }
+        Ok(())
+    }
+
+    fn next_step(&mut self) -> Result<RebaseStep> {
+        self.files.clear();
+        let Some(progress) = rebase_progress(&self.root)? else {
+            return Ok(RebaseStep::Finished);
+        };
+        let files = conflicted_files(&self.root)?;
+        if files.is_empty() {
+            return Ok(RebaseStep::Stopped(progress));
+        }
+        let prompt = conflict_prompt(&self.root, &progress, &files);
+        self.files = files.clone();
+        Ok(RebaseStep::Conflicts {
+            progress,
+            files,
+            prompt,
+        })
+    }
+}
+
+/// Progress of the rebase in progress in `root`, or `None` when there is
+/// none.
+pub fn rebase_progress(root: &Path) -> Result<Option<RebaseProgress>> {
+    let output = git(root, &["rev-parse", "--absolute-git-dir"])?;
+    if !output.status.success() {
+        bail!("not a git repository: {}", root.display());
+    }
+    let git_dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    let read_number = |path: PathBuf| {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| text.trim().parse::<usize>().ok())
+    };
+    let merge = git_dir.join("rebase-merge");
+    let apply = git_dir.join("rebase-apply");
+    let (step, total, message) = if merge.is_dir() {
+        (
+            read_number(merge.join("msgnum")),
+            read_number(merge.join("end")),
+            merge.join("message"),
+        )
+    } else if apply.is_dir() {
+        (
+            read_number(apply.join("next")),
+            read_number(apply.join("last")),
+            apply.join("final-commit"),
+        )
+    } else {
+        return Ok(None);
+    };
+    let subject = fs::read_to_string(message)
+        .ok()
+        .and_then(|text| text.lines().next().map(str::trim).map(str::to_string))
+        .filter(|subject| !subject.is_empty());
+    Ok(Some(RebaseProgress {
+        step: step.unwrap_or(0),
+        total: total.unwrap_or(0),
+        subject,
+    }))
+}
+
+/// Workspace-relative paths with unmerged changes.
+pub fn conflicted_files(root: &Path) -> Result<Vec<String>> {
+    let output = git(root, &["diff", "--name-only", "--diff-filter=U"])?;
+    if !output.status.success() {
+        bail!("git diff failed: {}", stderr(&output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Whether `text` still has a start or end conflict marker. A lone
+/// `=======` is not counted; it is also a Markdown heading underline.
+pub fn has_conflict_markers(text: &str) -> bool {
+    text.lines()
+        .any(|line| line.starts_with("<<<<<<<") || line.starts_with(">>>>>>>"))
+}
+
+/// Line ranges (0-based, end exclusive) of each complete conflict in
+/// `text`, from `<<<<<<<` through `>>>>>>>`.
+pub fn conflict_regions(text: &str) -> Vec<Range<usize>> {
+    let mut regions = Vec::new();
+    let mut start = None;
+    for (index, line) in text.lines().enumerate() {
+        if line.starts_with("<<<<<<<") {
+            start = Some(index);
+        } else if line.starts_with(">>>>>>>") {
+            if let Some(start) = start.take() {
+                regions.push(start..index + 1);
+            }
+        }
+    }
+    regions
+}
+
+/// Asks the model to resolve every conflict of one rebase stop, showing each
+/// with a few lines of context.
+pub fn conflict_prompt(root: &Path, progress: &RebaseProgress, files: &[String]) -> String {
+    let mut prompt = format!(
+        "Rebase {} stopped with conflicts in {} file(s). During a rebase, \
+         \"ours\" (HEAD) is the branch being rebased onto and \"theirs\" is the \
+         commit being replayed. Resolve every conflict below by editing the files \
+         so that no conflict markers remain, keeping the intent of both sides. \
+         Do not run git commands; I will review your edits and continue the rebase.\n",
+        progress.describe(),
+        files.len()
+    );
+    for file in files {
+        prompt.push_str(&format!("\n## {file}\n"));
+        let text = match fs::read_to_string(root.join(file)) {
+            Ok(text) => text,
+            Err(error) => {
+                prompt.push_str(&format!("(could not read: {error}; resolve it from git)\n"));
+                continue;
+            }
+        };
+        let lines: Vec<&str> = text.lines().collect();
+        let regions = conflict_regions(&text);
+        if regions.is_empty() {
+            prompt.push_str(
+                "(no conflict markers; the conflict is about the file itself, \
+                 such as a deletion or rename)\n",
+            );
+            continue;
+        }
+        for region in regions {
+            let shown_start = region.start.saturating_sub(CONTEXT_LINES);
+            let shown_end = (region.end + CONTEXT_LINES).min(lines.len());
+            prompt.push_str(&format!(
+                "\nConflict at lines {}-{}:\n```\n",
+                region.start + 1,
+                region.end
+            ));
+            let shown = &lines[shown_start..shown_end];
+            if shown.len() > MAX_SHOWN_CONFLICT_LINES {
+                for line in &shown[..MAX_SHOWN_CONFLICT_LINES] {
+                    prompt.push_str(line);
+                    prompt.push('\n');
+                }
+                prompt.push_str(&format!(
+                    "... ({} more lines; read the file for the rest)\n",
+                    shown.len() - MAX_SHOWN_CONFLICT_LINES
+                ));
+            } else {
+                for line in shown {
+                    prompt.push_str(line);
+                    prompt.push('\n');
+                }
+            }
+            prompt.push_str("```\n");
+        }
+    }
+    prompt
+}
+
+/// Runs git in `root`. `GIT_EDITOR=true` keeps `rebase --continue` from
+/// opening an editor for the commit message.
+fn git(root: &Path, args: &[&str]) -> Result<Output> {
+    Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .env("GIT_EDITOR", "true")
+        .output()
+        .with_context(|| format!("failed to run git {}", args.join(" ")))
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn run(root: &Path, args: &[&str]) {
+        let output = git(root, args).expect("git");
+        assert!(output.status.success(), "git {args:?}: {}", stderr(&output));
+    }
+
+    #[test]
+    fn test_walks_a_conflicted_rebase_to_completion() {
+        let temp = TempDir::new().expect("temp dir");
+        let root = temp.path();
+        run(root, &["init", "-q", "-b", "main"]);
+        run(root, &["config", "user.email", "dev@example.com"]);
+        run(root, &["config", "user.name", "Dev"]);
+        let base: String = (1..=10).map(|n| format!("line {n}\n")).collect();
+        fs::write(root.join("notes.txt"), &base).expect("write");
+        run(root, &["add", "."]);
+        run(root, &["commit", "-q", "-m", "base"]);
+        run(root, &["checkout", "-q", "-b", "feature"]);
+        fs::write(
+            root.join("notes.txt"),
+            base.replace("line 5", "feature five"),
+        )
+        .expect("write");
+        run(root, &["commit", "-q", "-am", "feature edit"]);
+        run(root, &["checkout", "-q", "main"]);
+        fs::write(root.join("notes.txt"), base.replace("line 5", "main five")).expect("write");
+        run(root, &["commit", "-q", "-am", "main edit"]);
+        run(root, &["checkout", "-q", "feature"]);
+
+        let mut assist = RebaseAssist::new(root.to_path_buf());
+        assert!(assist.start("").is_err(), "nothing to pick up yet");
+        let RebaseStep::Conflicts {
+            progress,
+            files,
+            prompt,
+        } = assist.start("main").expect("start")
+        else {
+            panic!("expected conflicts");
+        };
+        assert_eq!((progress.step, progress.total), (1, 1));
+        assert_eq!(progress.subject.as_deref(), Some("feature edit"));
+        assert_eq!(files, vec!["notes.txt".to_string()]);
+        assert!(prompt.contains("## notes.txt"));
+        assert!(prompt.contains("Conflict at lines 5-9:"));
+        assert!(prompt.contains("line 4\n<<<<<<<"));
+        assert!(prompt.contains("main five") && prompt.contains("feature five"));
+
+        let error = assist.continue_rebase().expect_err("markers remain");
+        assert!(error.to_string().contains("notes.txt"));
+
+        fs::write(root.join("notes.txt"), base.replace("line 5", "both fives")).expect("write");
+        assert!(assist.unresolved().is_empty());
+        assert_eq!(
+            assist.continue_rebase().expect("continue"),
+            RebaseStep::Finished
+        );
+        assert!(rebase_progress(root).expect("progress").is_none());
+        let log = git(root, &["log", "--format=%s"]).expect("log");
+        assert_eq!(
+            String::from_utf8_lossy(&log.stdout),
+            "feature edit\nmain edit\nbase\n"
+        );
+    }
+
+    #[test]
+    fn test_deleting_a_conflicted_file_resolves_and_stages_it() {
+        let temp = TempDir::new().expect("temp dir");
+        let root = temp.path();
+        run(root, &["init", "-q", "-b", "main"]);
+        run(root, &["config", "user.email", "dev@example.com"]);
+        run(root, &["config", "user.name", "Dev"]);
+        fs::write(root.join("keep.txt"), "keep\n").expect("write");
+        fs::write(root.join("old.txt"), "old\n").expect("write");
+        run(root, &["add", "."]);
+        run(root, &["commit", "-q", "-m", "base"]);
+        run(root, &["checkout", "-q", "-b", "feature"]);
+        fs::write(root.join("old.txt"), "changed\n").expect("write");
+        run(root, &["commit", "-q", "-am", "feature edit"]);
+        run(root, &["checkout", "-q", "main"]);
+        run(root, &["rm", "-q", "old.txt"]);
+        run(root, &["commit", "-q", "-m", "drop old"]);
+        run(root, &["checkout", "-q", "feature"]);
+
+        let mut assist = RebaseAssist::new(root.to_path_buf());
+        let step = assist
+            .run(&RebaseAction::Start("main".to_string()))
+            .expect("start");
+        assert!(matches!(step, RebaseStep::Conflicts { .. }), "{step:?}");
+        assert_eq!(assist.files(), ["old.txt".to_string()]);
+
+        fs::remove_file(root.join("old.txt")).expect("delete");
+        assert!(assist.unresolved().is_empty());
+        assert!(matches!(
+            assist.run(&RebaseAction::Continue).expect("continue"),
+            RebaseStep::Finished
+        ));
+        assert!(!root.join("old.txt").exists());
+    }
+}
@@ -0,0 +1,476 @@
+use crate::util::{parse_bool_str, sha256_hex};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const UPDATE_CHECK_ENV: &str = "VEX_UPDATE_CHECK";
+pub const RELEASES_URL_ENV: &str = "VEX_RELEASES_URL";
+pub const DEFAULT_RELEASES_URL: &str =
+    "https://api.github.com/repos/aistar-au/vexcoder/releases/latest";
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const SELF_UPDATE_USAGE: &str = "usage: vex self-update [--check]";
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+/// Checksum file listing `<sha256>  <asset name>` rows for every asset.
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+/// Hex Ed25519 public key release binaries are signed with, pinned by
+/// release builds. A checksum from the release itself only catches a
+/// corrupted download; the signature also catches a tampered release.
+const RELEASE_SIGNING_KEY: Option<&str> = option_env!("VEX_RELEASE_SIGNING_KEY");
+
+/// The startup release check runs only when `VEX_UPDATE_CHECK` is on.
+pub fn update_check_enabled_from_env() -> bool {
+    std::env::var(UPDATE_CHECK_ENV)
+        .ok()
+        .and_then(|value| parse_bool_str(&value))
+        .unwrap_or(false)
+}
+
+/// `VEX_RELEASES_URL`, or the GitHub latest-release endpoint.
+pub fn releases_url() -> String {
+    std::env::var(RELEASES_URL_ENV)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| DEFAULT_RELEASES_URL.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+/// One entry of the GitHub releases feed.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub html_url: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+impl Release {
+    /// The tag without a leading `v`.
+    pub fn version(&self) -> &str {
+        self.tag_name.trim().trim_start_matches('v')
+    }
+
+    /// True for a full release with a higher version than `current`.
+    pub fn is_newer_than(&self, current: &str) -> bool {
+        if self.prerelease {
+            return false;
+        }
+        match (parse_version(self.version()), parse_version(current)) {
+            (Some(latest), Some(current)) => latest > current,
+            _ => false,
+        }
+    }
+
+    /// The binary built for this platform, named `vex-<arch>-<os>`.
+    pub fn binary_asset(&self) -> Option<&ReleaseAsset> {
+        let name = platform_asset_name();
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+
+    /// `<asset>.sig`, a hex Ed25519 signature of the binary.
+    fn signature_asset(&self, asset: &ReleaseAsset) -> Option<&ReleaseAsset> {
+        let name = format!("{}.sig", asset.name);
+        self.assets.iter().find(|candidate| candidate.name == name)
+    }
+
+    /// `<asset>.sha256`, or the release-wide `SHA256SUMS`.
+    fn checksum_asset(&self, asset: &ReleaseAsset) -> Option<&ReleaseAsset> {
+        let sidecar = format!("{}.sha256", asset.name);
+        self.assets
+            .iter()
+            .find(|candidate| candidate.name == sidecar)
+            .or_else(|| {
+                self.assets
+                    .iter()
+                    .find(|candidate| candidate.name == CHECKSUMS_ASSET)
+            })
+    }
+
+    /// Transcript lines for `/release-notes`.
+    pub fn notes_lines(&self) -> Vec<String> {
+        let status = if self.is_newer_than(CURRENT_VERSION) {
+            "run `vex self-update` to install it"
+        } else {
+            "this is the running version or older"
+        };
+        let mut lines = vec![format!(
+            "[release notes: v{} (running v{CURRENT_VERSION}; {status})]",
+            self.version()
+        )];
+        let body = self.body.as_deref().unwrap_or_default().trim();
+        if body.is_empty() {
+            lines.push("(this release has no notes)".to_string());
+        } else {
+            lines.extend(body.lines().map(|line| line.trim_end().to_string()));
+        }
+        if !self.html_url.is_empty() {
+            lines.push(self.html_url.clone());
+        }
+        lines
+    }
+}
+
+/// `1.2.3` as a comparable triple; a pre-release suffix is ignored.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.trim().trim_start_matches('v');
+    let core = core.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+fn platform_asset_name() -> String {
+    format!(
+        "vex-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+fn http_client(timeout: Duration) -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .user_agent(format!("vex/{CURRENT_VERSION}"))
+        .timeout(timeout)
+        .build()
+        .context("failed to build the HTTP client")
+}
+
+pub async fn fetch_latest_release(url: &str) -> Result<Release> {
+    http_client(CHECK_TIMEOUT)?
+        .get(url)
+        .header("accept", "application/vnd.github+json")
+        .send()
+        .await
+        .with_context(|| format!("failed to reach {url}"))?
+        .error_for_status()
+        .with_context(|| format!("release check against {url} failed"))?
+        .json::<Release>()
+        .await
+        .with_context(|| format!("{url} did not return a release"))
+}
+
+/// The newest release seen by a background check, shared with the status
+/// bar and `/release-notes`. Clones observe the same result.
+#[derive(Debug, Clone, Default)]
+pub struct ReleaseCheck {
+    latest: Arc<Mutex<Option<Release>>>,
+    fetching: Arc<AtomicBool>,
+}
+
+impl ReleaseCheck {
+    /// Starts a background check when `VEX_UPDATE_CHECK` is on.
+    pub fn spawn_from_env() -> Self {
+        let check = Self::default();
+        if update_check_enabled_from_env() {
+            check.fetch_in_background();
+        }
+        check
+    }
+
+    #[cfg(test)]
+    pub fn with_release(release: Release) -> Self {
+        let check = Self::default();
+        check.store(release);
+        check
+    }
+
+    /// Fetches the latest release unless a fetch is already running. Does
+    /// nothing outside a Tokio runtime.
+    pub fn fetch_in_background(&self) {
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        if self.fetching.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let check = self.clone();
+        handle.spawn(async move {
+            // A failed check stays silent; `vex self-update --check` reports
+            // the error.
+            if let Ok(release) = fetch_latest_release(&releases_url()).await {
+                check.store(release);
+            }
+            check.fetching.store(false, Ordering::SeqCst);
+        });
+    }
+
+    pub fn is_fetching(&self) -> bool {
+        self.fetching.load(Ordering::SeqCst)
+    }
+
+    pub fn latest(&self) -> Option<Release> {
+        self.latest.lock().ok().and_then(|latest| latest.clone())
+    }
+
+    /// The newer version to advertise in the status bar, if any.
+    pub fn available_update(&self) -> Option<String> {
+        self.latest()
+            .filter(|release| release.is_newer_than(CURRENT_VERSION))
+            .map(|release| release.version().to_string())
+    }
+
+    fn store(&self, release: Release) {
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = Some(release);
+        }
+    }
+}
+
+/// `vex self-update` downloads this platform's binary from the latest
+/// release, verifies its signature against the pinned key (or, in builds
+/// without one, its checksum), and replaces the running executable.
+/// `--check` only reports whether one is available.
+pub async fn run_self_update_cli(args: &[String]) -> Result<()> {
+    let check_only = match args {
+        [] => false,
+        [flag] if flag == "--check" => true,
+        _ => bail!("{SELF_UPDATE_USAGE}"),
+    };
+    let url = releases_url();
+    let release = fetch_latest_release(&url).await?;
+    if !release.is_newer_than(CURRENT_VERSION) {
+        println!(
+            "vex v{CURRENT_VERSION} is up to date (latest release: v{})",
+            release.version()
+        );
+        return Ok(());
+    }
+    if check_only {
+        println!(
+            "vex v{} is available (running v{CURRENT_VERSION}); run `vex self-update` to install it",
+            release.version()
+        );
+        return Ok(());
+    }
+
+    let asset = release.binary_asset().with_context(|| {
+        format!(
+            "release v{} has no `{}` binary for this platform",
+            release.version(),
+            platform_asset_name()
+        )
+    })?;
+    let client = http_client(DOWNLOAD_TIMEOUT)?;
+    let binary = download(&client, &asset.browser_download_url).await?;
+    let verified = match RELEASE_SIGNING_KEY {
+        Some(key) => {
+            let signature_asset = release.signature_asset(asset).with_context(|| {
+                format!(
+                    "release v{} publishes no signature for {}; refusing to install an unsigned binary",
+                    release.version(),
+                    asset.name
+                )
+            })?;
+            let signature = download(&client, &signature_asset.browser_download_url).await?;
+            verify_signature(key, &binary, &String::from_utf8_lossy(&signature)).with_context(
+                || format!("{} failed verification; nothing was replaced", asset.name),
+            )?;
+            "signature verified"
+        }
+        None => {
+            verify_checksum(&release, &client, asset, &binary).await?;
+            eprintln!(
+                "note: this build pins no release signing key, so only the checksum published \
+                 with the release was checked. It catches a corrupted download, not a tampered release."
+            );
+            "checksum matched"
+        }
+    };
+
+    let executable = std::env::current_exe().context("failed to locate the running binary")?;
+    replace_executable(&executable, &binary)?;
+    println!(
+        "updated {} from v{CURRENT_VERSION} to v{} ({verified}; sha256 {})",
+        executable.display(),
+        release.version(),
+        sha256_hex(&binary)
+    );
+    Ok(())
+}
+
+/// Checks `binary` against the release's `<asset>.sha256` or `SHA256SUMS`.
+async fn verify_checksum(
+    release: &Release,
+    client: &reqwest::Client,
+    asset: &ReleaseAsset,
+    binary: &[u8],
+) -> Result<()> {
+    let checksum_asset = release.checksum_asset(asset).with_context(|| {
+        format!(
+            "release v{} publishes no checksum for {}; refusing to install an unchecked binary",
+            release.version(),
+            asset.name
+        )
+    })?;
+    let checksums =
+        String::from_utf8_lossy(&download(client, &checksum_asset.browser_download_url).await?)
+            .into_owned();
+    let expected = expected_checksum(&checksums, &asset.name).with_context(|| {
+        format!(
+            "{} lists no checksum for {}",
+            checksum_asset.name, asset.name
+        )
+    })?;
+    let actual = sha256_hex(binary);
+    if actual != expected {
+        bail!(
+            "checksum mismatch for {}: expected {expected}, downloaded {actual}; nothing was replaced",
+            asset.name
+        );
+    }
+    Ok(())
+}
+
+/// Verifies a hex Ed25519 `signature` of `message` against the hex
+/// `public_key`.
+fn verify_signature(public_key: &str, message: &[u8], signature: &str) -> Result<()> {
+    let public_key = decode_hex(public_key.trim()).context("the signing key is not hex")?;
+    let signature = decode_hex(signature.trim()).context("the signature is not hex")?;
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, public_key)
+        .verify(message, &signature)
+        .map_err(|_| anyhow::anyhow!("the signature does not match the pinned release key"))
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    let bytes = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("failed to download {url}"))?
+        .error_for_status()
+        .with_context(|| format!("failed to download {url}"))?
+        .bytes()
+        .await
+        .with_context(|| format!("failed to download {url}"))?;
+    Ok(bytes.to_vec())
+}
+
+/// The checksum for `asset_name` from a `<hex>  <name>` listing, or the
+/// first word of a single-checksum sidecar file.
+fn expected_checksum(listing: &str, asset_name: &str) -> Option<String> {
+    let rows: Vec<(&str, Option<&str>)> = listing
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let hex = words.next()?;
+            Some((hex, words.next().map(|name| name.trim_start_matches('*'))))
+        })
+        .collect();
+    let hex = match rows.as_slice() {
+        [(hex, None)] => *hex,
+        _ => rows
+            .iter()
+            .find(|(_, name)| *name == Some(asset_name))
+            .map(|(hex, _)| *hex)?,
+    };
+    let hex = hex.to_ascii_lowercase();
+    (hex.len() == 64 && hex.chars().all(|ch| ch.is_ascii_hexdigit())).then_some(hex)
+}
+
+/// Writes `binary` beside `executable` and renames it into place, so an
+/// interrupted update leaves the old binary intact.
+fn replace_executable(executable: &Path, binary: &[u8]) -> Result<()> {
+    let dir = executable
+        .parent()
+        .context("the running binary has no parent directory")?;
+    let staged = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("failed to stage the update in {}", dir.display()))?;
+    std::fs::write(staged.path(), binary)
+        .with_context(|| format!("failed to write {}", staged.path().display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(staged.path(), std::fs::Permissions::from_mode(0o755))
+            .context("failed to mark the update executable")?;
+    }
+    staged
+        .persist(executable)
+        .map_err(|error| error.error)
+        .with_context(|| format!("failed to replace {}", executable.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(tag: &str) -> Release {
+        Release {
+            tag_name: tag.to_string(),
+            body: Some("- faster diffs\n- new /release-notes".to_string()),
+            html_url: "https://example.com/r".to_string(),
+            prerelease: false,
+            assets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_versions_compare_numerically_and_skip_prereleases() {
+        assert!(release("v0.10.0").is_newer_than("0.9.3"));
+        assert!(!release("v0.1.0").is_newer_than("0.1.0"));
+        assert!(!release("nightly").is_newer_than("0.1.0"));
+        let mut candidate = release("v9.0.0-rc1");
+        candidate.prerelease = true;
+        assert!(!candidate.is_newer_than("0.1.0"));
+
+        let check = ReleaseCheck::with_release(release("v99.0.0"));
+        assert_eq!(check.available_update().as_deref(), Some("99.0.0"));
+        let lines = check.latest().expect("stored").notes_lines();
+        assert!(lines[0].starts_with("[release notes: v99.0.0 (running v"));
+        assert_eq!(lines[1], "- faster diffs");
+    }
+
+    #[test]
+    fn test_checksum_listings_and_signatures() {
+        let digest = sha256_hex(b"abc");
+        let listing = format!(
+            "{}  vex-other\n{digest} *vex-x86_64-linux\n",
+            "0".repeat(64)
+        );
+        assert_eq!(
+            expected_checksum(&listing, "vex-x86_64-linux").as_deref(),
+            Some(digest.as_str())
+        );
+        assert_eq!(expected_checksum(&digest, "anything"), Some(digest.clone()));
+        assert_eq!(expected_checksum("nothex  vex", "vex"), None);
+
+        use ring::signature::KeyPair;
+        let pkcs8 =
+            ring::signature::Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new())
+                .expect("key");
+        let key_pair = ring::signature::Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).expect("pair");
+        let hex = |bytes: &[u8]| -> String { bytes.iter().map(|b| format!("{b:02x}")).collect() };
+        let public_key = hex(key_pair.public_key().as_ref());
+        let signature = hex(key_pair.sign(b"binary").as_ref());
+        assert!(verify_signature(&public_key, b"binary", &format!("{signature}\n")).is_ok());
+        assert!(verify_signature(&public_key, b"tampered", &signature).is_err());
+        assert!(verify_signature(&public_key, b"binary", "zz").is_err());
+    }
+}
@@ -1,3 +1,4 @@
+pub mod autosave;
 pub mod context;
 pub mod frontend;
 pub mod r#loop;
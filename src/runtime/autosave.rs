@@ -0,0 +1,286 @@
+use crate::profiles::active_profile_name;
+use crate::state::ConversationReader;
+use crate::state_dirs::StateDirs;
+use crate::tags::TurnTag;
+use crate::tools::{FileSnapshots, Snapshot};
+use crate::turn_overrides::TurnOverrideRecord;
+use crate::types::{ApiMessage, TokenUsage};
+use crate::util::sha256_hex;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const BACKUP_FILE_PREFIX: &str = "session-";
+const DEFAULT_AUTOSAVE_KEEP: usize = 5;
+const DEFAULT_AUTOSAVE_INTERVAL_SECS: u64 = 300;
+
+/// Serialized conversation state written by [`SessionAutosave`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionBackup {
+    pub saved_at: u64,
+    pub messages: Vec<ApiMessage>,
+    #[serde(default)]
+    pub token_usage: TokenUsage,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turn_overrides: Vec<TurnOverrideRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turn_tags: Vec<TurnTag>,
+    /// The `/undo` snapshots at the time of the backup, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub undo_journal: Vec<Snapshot>,
+}
+
+/// Rotating session backups under `.aistar/backups/` (`.aistar/backups/<profile>/`
+/// under a named profile), holding the conversation and the undo journal. A
+/// backup is written after every completed turn and, while idle, every
+/// `interval`; only the newest `keep` files are retained.
+#[derive(Debug)]
+pub struct SessionAutosave {
+    dir: PathBuf,
+    keep: usize,
+    interval: Option<Duration>,
+    snapshots: FileSnapshots,
+    /// SHA-256 of the last backup written, less its timestamp.
+    saved_digest: Mutex<Option<String>>,
+    sequence: AtomicU64,
+}
+
+impl SessionAutosave {
+    pub fn new(working_dir: &Path, keep: usize, interval: Option<Duration>) -> Self {
+        Self {
+            dir: StateDirs::for_workspace(working_dir).backups_dir(),
+            keep: keep.max(1),
+            interval,
+            snapshots: FileSnapshots::for_workspace(working_dir),
+            saved_digest: Mutex::new(None),
+            sequence: AtomicU64::new(0),
+        }
+    }
+
+    /// Reads `VEX_AUTOSAVE_KEEP` (default 5, `0` disables backups),
+    /// `VEX_AUTOSAVE_INTERVAL_SECS` (default 300, `0` saves on turn
+    /// completion only), and the active profile.
+    pub fn from_env(working_dir: &Path) -> Option<Self> {
+        let keep = env_u64("VEX_AUTOSAVE_KEEP")
+            .map(|value| value.min(100) as usize)
+            .unwrap_or(DEFAULT_AUTOSAVE_KEEP);
+        if keep == 0 {
+            return None;
+        }
+        let interval_secs =
+            env_u64("VEX_AUTOSAVE_INTERVAL_SECS").unwrap_or(DEFAULT_AUTOSAVE_INTERVAL_SECS);
+        let interval = (interval_secs > 0).then(|| Duration::from_secs(interval_secs.max(10)));
+        Some(Self::new(working_dir, keep, interval).for_profile(active_profile_name().as_deref()))
+    }
+
+    /// Keeps backups for `profile` apart from other profiles' sessions.
+    pub fn for_profile(mut self, profile: Option<&str>) -> Self {
+        if let Some(profile) = profile {
+            self.dir = self.dir.join(profile);
+        }
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Writes a backup if the conversation or the undo journal changed
+    /// since the last save, edits included. The conversation is taken as of
+    /// its last round boundary, so a backup made while a tool call waits
+    /// never holds a tool_use without its result. Returns the new file path,
+    /// or `None` when there was nothing to save.
+    pub fn save(&self, conversation: &ConversationReader) -> Result<Option<PathBuf>> {
+        let Some(session) = conversation
+            .checkpoint()
+            .filter(|session| !session.messages.is_empty())
+        else {
+            return Ok(None);
+        };
+        let mut backup = SessionBackup {
+            saved_at: 0,
+            messages: session.messages,
+            token_usage: session.token_usage,
+            turn_overrides: session.turn_overrides,
+            turn_tags: session.turn_tags,
+            undo_journal: self.snapshots.journal()?,
+        };
+        let digest = sha256_hex(&serde_json::to_vec(&backup)?);
+        let mut saved_digest = self
+            .saved_digest
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if saved_digest.as_deref() == Some(digest.as_str()) {
+            return Ok(None);
+        }
+
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        let saved_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        backup.saved_at = saved_at_ms / 1000;
+        let sequence = self.sequence.fetch_add(1, Ordering::SeqCst);
+        let path = self.dir.join(format!(
+            "{BACKUP_FILE_PREFIX}{saved_at_ms:013}-{sequence:04}.json"
+        ));
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(&backup)?)
+            .with_context(|| format!("failed to write {}", temp_path.display()))?;
+        fs::rename(&temp_path, &path)
+            .with_context(|| format!("failed to finalize {}", path.display()))?;
+        *saved_digest = Some(digest);
+
+        self.prune()?;
+        Ok(Some(path))
+    }
+
+    /// Backup files, oldest first.
+    pub fn backups(&self) -> Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to read {}", self.dir.display()))
+            }
+        };
+        let mut backups: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension().is_some_and(|ext| ext == "json")
+                    && path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with(BACKUP_FILE_PREFIX))
+            })
+            .collect();
+        backups.sort();
+        Ok(backups)
+    }
+
+    pub fn load_latest(&self) -> Result<Option<SessionBackup>> {
+        let Some(path) = self.backups()?.pop() else {
+            return Ok(None);
+        };
+        let raw = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        let backup = serde_json::from_slice(&raw)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        Ok(Some(backup))
+    }
+
+    fn prune(&self) -> Result<()> {
+        let backups = self.backups()?;
+        let excess = backups.len().saturating_sub(self.keep);
+        for path in &backups[..excess] {
+            fs::remove_file(path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn env_u64(key: &str) -> Option<u64> {
+    std::env::var(key)
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{mock_client::MockApiClient, ApiClient};
+    use crate::state::ConversationManager;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn plain_round(text: &str) -> Vec<String> {
+        vec![
+            r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_autosave","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":3,"output_tokens":1}}}"#.to_string(),
+            r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#.to_string(),
+            format!(
+                "event: content_block_delta\ndata: {{\"type\":\"content_block_delta\",\"index\":0,\"delta\":{{\"type\":\"text_delta\",\"text\":\"{text}\"}}}}"
+            ),
+            r#"event: content_block_stop
+data: {"type":"content_block_stop","index":0}"#.to_string(),
+            r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_save_rotates_and_skips_unchanged_sessions() -> Result<()> {
+        let temp = TempDir::new()?;
+        let autosave = SessionAutosave::new(temp.path(), 2, None);
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(vec![
+            plain_round("one"),
+            plain_round("two"),
+            plain_round("three"),
+        ])));
+        let mut conversation = ConversationManager::new_mock(client, HashMap::new());
+
+        assert!(autosave.save(&conversation.reader())?.is_none());
+        for prompt in ["first", "second", "third"] {
+            conversation.send_message(prompt.to_string(), None).await?;
+            assert!(autosave.save(&conversation.reader())?.is_some());
+            assert!(autosave.save(&conversation.reader())?.is_none());
+        }
+
+        assert_eq!(autosave.backups()?.len(), 2);
+        let latest = autosave.load_latest()?.expect("latest backup");
+        assert_eq!(latest.messages.len(), 6);
+        assert!(!autosave.dir().join("session-").exists());
+
+        // Same message count, different content.
+        let mut edited = conversation.saved_session();
+        edited.messages[0].content = crate::types::Content::Text("first, edited".to_string());
+        conversation.restore_session(edited);
+        assert!(autosave.save(&conversation.reader())?.is_some());
+
+        // A new undo snapshot is a change too, and goes into the backup.
+        fs::write(temp.path().join("a.txt"), "before")?;
+        FileSnapshots::for_workspace(temp.path()).record_write("write_file", "a.txt")?;
+        assert!(autosave.save(&conversation.reader())?.is_some());
+        let latest = autosave.load_latest()?.expect("latest backup");
+        assert_eq!(latest.undo_journal.len(), 1);
+        assert!(autosave.save(&conversation.reader())?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_env_respects_retention_and_interval() {
+        let _env_lock = crate::test_support::ENV_LOCK.blocking_lock();
+        let temp = TempDir::new().expect("tempdir");
+
+        std::env::set_var("VEX_AUTOSAVE_KEEP", "0");
+        assert!(SessionAutosave::from_env(temp.path()).is_none());
+
+        std::env::set_var("VEX_AUTOSAVE_KEEP", "3");
+        std::env::set_var("VEX_AUTOSAVE_INTERVAL_SECS", "0");
+        let autosave = SessionAutosave::from_env(temp.path()).expect("autosave enabled");
+        assert_eq!(autosave.keep, 3);
+        assert_eq!(autosave.interval(), None);
+
+        std::env::remove_var("VEX_AUTOSAVE_KEEP");
+        std::env::remove_var("VEX_AUTOSAVE_INTERVAL_SECS");
+        let autosave = SessionAutosave::from_env(temp.path()).expect("default autosave");
+        assert_eq!(autosave.keep, DEFAULT_AUTOSAVE_KEEP);
+        assert_eq!(autosave.interval(), Some(Duration::from_secs(300)));
+
+        std::env::set_var(crate::profiles::PROFILE_ENV, "work");
+        let autosave = SessionAutosave::from_env(temp.path()).expect("profile autosave");
+        std::env::remove_var(crate::profiles::PROFILE_ENV);
+        assert!(autosave.dir().ends_with(".aistar/backups/work"));
+    }
+}
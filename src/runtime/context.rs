@@ -1,13 +1,38 @@
+use crate::api::files::PendingDocument;
+use crate::events::{EventBus, EventKind, RuntimeEvent};
+use crate::hooks::{HookEvent, LifecycleHooks};
+use crate::notifier::{Notifier, NotifyEvent};
+use crate::runtime::autosave::SessionAutosave;
 use crate::runtime::UiUpdate;
-use crate::state::{ConversationManager, ConversationStreamUpdate, StreamBlock};
-use std::sync::Arc;
+use crate::state::{
+    render_turn_cancelled_note, ConversationManager, ConversationReader, ConversationStreamUpdate,
+    SavedSession, SavedSessionSummary, SessionStore, StreamBlock, ToolApprovalPolicy,
+};
+use crate::tools::{ToolOperator, ToolPoolStats};
+use crate::turn_overrides::TurnOverrides;
+use crate::usage::{UsageLog, UsageRecord};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
 use tokio::sync::{mpsc, Mutex};
 use tokio_util::sync::CancellationToken;
 
 pub struct RuntimeContext {
+    /// Held for a whole turn only to serialize turns; state reads go through
+    /// `reader`, which never waits on a running turn.
     conversation: Arc<Mutex<ConversationManager>>,
+    reader: ConversationReader,
     update_tx: mpsc::UnboundedSender<UiUpdate>,
     cancel: CancellationToken,
+    pending_cancel_reason: Option<String>,
+    pending_documents: Vec<PendingDocument>,
+    approval_policy: ToolApprovalPolicy,
+    autosave: Option<Arc<SessionAutosave>>,
+    session_store: Option<Arc<SessionStore>>,
+    events: EventBus,
+    hooks: Option<LifecycleHooks>,
+    tool_operator: ToolOperator,
 }
 
 impl RuntimeContext {
@@ -16,14 +41,192 @@ impl RuntimeContext {
         update_tx: mpsc::UnboundedSender<UiUpdate>,
         cancel: CancellationToken,
     ) -> Self {
+        let approval_policy = conversation.approval_policy();
+        let reader = conversation.reader();
+        let events = conversation.event_bus();
+        let tool_operator = conversation.tool_operator();
+        // The frontend follows stream blocks as a bus subscriber.
+        let block_tx = update_tx.clone();
+        let textual_block_by_index = std::sync::Mutex::new(HashMap::new());
+        events.subscribe_fn(&[EventKind::Block], move |event| {
+            let mut textual = textual_block_by_index
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            forward_block_event(event, &mut textual, &block_tx);
+        });
         Self {
             conversation: Arc::new(Mutex::new(conversation)),
+            reader,
             update_tx,
             cancel,
+            pending_cancel_reason: None,
+            pending_documents: Vec::new(),
+            approval_policy,
+            autosave: None,
+            session_store: None,
+            events,
+            hooks: None,
+            tool_operator,
+        }
+    }
+
+    /// Enables session backups after each completed turn and, when the
+    /// autosave has an interval, on a background timer that also runs while
+    /// a turn is waiting on tool approval.
+    pub fn with_autosave(mut self, autosave: SessionAutosave) -> Self {
+        let autosave = Arc::new(autosave);
+        if let (Some(interval), Ok(handle)) =
+            (autosave.interval(), tokio::runtime::Handle::try_current())
+        {
+            handle.spawn(run_periodic_autosave(
+                Arc::downgrade(&self.conversation),
+                self.reader.clone(),
+                Arc::clone(&autosave),
+                interval,
+                self.update_tx.clone(),
+            ));
+        }
+        self.autosave = Some(autosave);
+        self
+    }
+
+    /// Appends a content-free usage record for every finished turn. The
+    /// file is written on a blocking thread, not by the publisher.
+    pub fn with_usage_log(self, usage_log: UsageLog) -> Self {
+        self.events
+            .subscribe_fn(&[EventKind::Metrics], move |event| {
+                if let RuntimeEvent::Usage {
+                    model,
+                    usage,
+                    elapsed,
+                    succeeded,
+                } = event
+                {
+                    let record = UsageRecord::new(model, *usage, *elapsed, *succeeded);
+                    let usage_log = usage_log.clone();
+                    // Usage logging is best-effort and must never fail a turn.
+                    let append = move || {
+                        let _ = usage_log.append(&record);
+                    };
+                    match tokio::runtime::Handle::try_current() {
+                        Ok(handle) => drop(handle.spawn_blocking(append)),
+                        Err(_) => append(),
+                    }
+                }
+            });
+        self
+    }
+
+    /// Posts pending approvals and finished long turns to a webhook.
+    pub fn with_notifier(self, notifier: Notifier) -> Self {
+        let kinds = [EventKind::Approval, EventKind::Turn, EventKind::Error];
+        self.events.subscribe_fn(&kinds, move |event| {
+            let event = match event {
+                RuntimeEvent::ApprovalRequested {
+                    tool_name, detail, ..
+                } => NotifyEvent::ApprovalPending {
+                    tool_name: tool_name.clone(),
+                    detail: detail.clone(),
+                },
+                // Someone who cancelled a turn is already watching it.
+                RuntimeEvent::TurnCompleted {
+                    elapsed,
+                    cancelled: false,
+                    changes,
+                } => NotifyEvent::TurnComplete {
+                    elapsed: *elapsed,
+                    error: None,
+                    changes: changes.clone(),
+                },
+                RuntimeEvent::TurnFailed {
+                    elapsed,
+                    error,
+                    changes,
+                } => NotifyEvent::TurnComplete {
+                    elapsed: *elapsed,
+                    error: Some(error.clone()),
+                    changes: changes.clone(),
+                },
+                _ => return,
+            };
+            notifier.notify(event);
+        });
+        self
+    }
+
+    /// Runs the project's lifecycle hooks on turn start, completion, errors,
+    /// and session end. A hook may edit files, so each one that finishes
+    /// drops the repo map and workspace stats.
+    pub fn with_hooks(mut self, hooks: LifecycleHooks) -> Self {
+        let handler = hooks.clone();
+        let operator = self.tool_operator.clone();
+        self.events
+            .subscribe_fn(&[EventKind::Turn, EventKind::Error], move |event| {
+                let (hook, details) = match event {
+                    RuntimeEvent::TurnStarted { input } => {
+                        (HookEvent::TurnStart, json!({ "input": input }))
+                    }
+                    RuntimeEvent::TurnCompleted {
+                        elapsed,
+                        cancelled,
+                        changes,
+                    } => (
+                        HookEvent::TurnComplete,
+                        turn_hook_details(*elapsed, changes, *cancelled),
+                    ),
+                    RuntimeEvent::TurnFailed { error, .. } => {
+                        (HookEvent::Error, json!({ "error": error }))
+                    }
+                    _ => return,
+                };
+                let operator = operator.clone();
+                handler.fire_then(hook, details, move || {
+                    operator.invalidate_workspace_caches();
+                });
+            });
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// The bus turn, stream, tool, approval, and usage events are published
+    /// on. Frontends and integrations subscribe here instead of being wired
+    /// into the turn loop.
+    pub fn events(&self) -> EventBus {
+        self.events.clone()
+    }
+
+    /// Runs the `on_session_end` hook and waits for it, at most `timeout`.
+    pub async fn run_session_end_hook(&self, outcome: &str, timeout: std::time::Duration) {
+        if let Some(hooks) = &self.hooks {
+            let details = json!({
+                "outcome": outcome,
+                "turns": self.reader.turn_count(),
+            });
+            let _ = hooks.run(HookEvent::SessionEnd, details, timeout).await;
         }
     }
 
+    /// Saves the conversation to `store` after each turn and on exit so
+    /// `/resume` can reload it later.
+    pub fn with_session_store(mut self, store: SessionStore) -> Self {
+        self.session_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Uploads `content` through the provider's Files API and references it
+    /// from the next turn's message, titled `title`.
+    pub fn queue_document(&mut self, title: String, content: String) {
+        self.pending_documents
+            .push(PendingDocument { title, content });
+    }
+
     pub fn start_turn(&mut self, input: String) {
+        self.start_turn_with_overrides(input, TurnOverrides::default());
+    }
+
+    /// Starts a turn whose requests use `overrides`; the next turn is back
+    /// on the configured defaults.
+    pub fn start_turn_with_overrides(&mut self, input: String, overrides: TurnOverrides) {
         if tokio::runtime::Handle::try_current().is_err() {
             let _ = self.update_tx.send(UiUpdate::Error(
                 "runtime error: start_turn requires active Tokio runtime".to_string(),
@@ -31,37 +234,114 @@ impl RuntimeContext {
             return;
         }
 
+        let input = match self.pending_cancel_reason.take() {
+            Some(reason) => format!("{}\n\n{input}", render_turn_cancelled_note(&reason)),
+            None => input,
+        };
+        let documents = std::mem::take(&mut self.pending_documents);
         let turn_cancel = self.cancel.child_token();
         let tx = self.update_tx.clone();
         let conversation = Arc::clone(&self.conversation);
+        let reader = self.reader.clone();
+        let changes_reader = self.reader.clone();
+        let autosave = self.autosave.clone();
+        let session_store = self.session_store.clone();
+        let events = self.events.clone();
+        let usage_events = self.events.clone();
+        events.publish(RuntimeEvent::TurnStarted {
+            input: input.clone(),
+        });
 
+        let save_tx = self.update_tx.clone();
         tokio::spawn(async move {
+            let turn_started = std::time::Instant::now();
             let (delta_tx, mut delta_rx) = mpsc::unbounded_channel::<ConversationStreamUpdate>();
 
             let send_handle = tokio::spawn(async move {
                 let mut mgr = conversation.lock().await;
-                mgr.send_message(input, Some(&delta_tx)).await
+                mgr.queue_documents(documents);
+                let started = std::time::Instant::now();
+                let usage_before = mgr.token_usage();
+                let model = overrides
+                    .model
+                    .clone()
+                    .unwrap_or_else(|| mgr.client().model().to_string());
+                let result = mgr
+                    .send_message_with_overrides(input, overrides, Some(&delta_tx))
+                    .await;
+                let mut usage = mgr.token_usage();
+                usage.input_tokens = usage.input_tokens.saturating_sub(usage_before.input_tokens);
+                usage.output_tokens = usage
+                    .output_tokens
+                    .saturating_sub(usage_before.output_tokens);
+                usage_events.publish(RuntimeEvent::Usage {
+                    model,
+                    usage,
+                    elapsed: started.elapsed(),
+                    succeeded: result.is_ok(),
+                });
+                // A failed save must not fail the turn that just finished.
+                if let Some(store) = session_store {
+                    if let Err(error) = store.save(&mgr.saved_session()) {
+                        let _ = save_tx.send(UiUpdate::Status(format!(
+                            "saving the session failed: {error:#}"
+                        )));
+                    }
+                }
+                if let Some(autosave) = autosave {
+                    if let Err(error) = autosave.save(&reader) {
+                        let _ = save_tx.send(UiUpdate::Status(format!(
+                            "session autosave failed: {error:#}"
+                        )));
+                    }
+                }
+                result
             });
 
-            let mut textual_block_by_index = std::collections::HashMap::<usize, bool>::new();
-
             loop {
                 tokio::select! {
                     _ = turn_cancel.cancelled() => {
                         send_handle.abort();
+                        let changes = send_turn_changes(&changes_reader, &tx);
+                        events.publish(RuntimeEvent::TurnCompleted {
+                            elapsed: turn_started.elapsed(),
+                            cancelled: true,
+                            changes,
+                        });
                         let _ = tx.send(UiUpdate::TurnComplete);
                         return;
                     }
                     update = delta_rx.recv() => {
                         match update {
-                            Some(update) => forward_conversation_update(update, &mut textual_block_by_index, &tx),
+                            Some(update) => {
+                                publish_stream_event(&events, &update);
+                                forward_conversation_update(update, &tx)
+                            }
                             None => break,
                         }
                     }
                 }
             }
 
-            match send_handle.await {
+            let outcome = send_handle.await;
+            let changes = send_turn_changes(&changes_reader, &tx);
+            let error = match &outcome {
+                Ok(result) => result.as_ref().err().map(ToString::to_string),
+                Err(error) => (!error.is_cancelled()).then(|| error.to_string()),
+            };
+            events.publish(match error {
+                Some(error) => RuntimeEvent::TurnFailed {
+                    elapsed: turn_started.elapsed(),
+                    error,
+                    changes,
+                },
+                None => RuntimeEvent::TurnCompleted {
+                    elapsed: turn_started.elapsed(),
+                    cancelled: matches!(&outcome, Err(error) if error.is_cancelled()),
+                    changes,
+                },
+            });
+            match outcome {
                 Ok(Ok(_)) => {
                     let _ = tx.send(UiUpdate::TurnComplete);
                 }
@@ -79,17 +359,14 @@ impl RuntimeContext {
         });
     }
 
-    #[cfg(test)]
-    pub fn test_message_count_try_lock(&self) -> Option<usize> {
-        self.conversation
-            .try_lock()
-            .ok()
-            .map(|mgr| mgr.messages_for_api().len())
+    /// Read access to the conversation that does not wait for a running turn.
+    pub fn conversation_reader(&self) -> ConversationReader {
+        self.reader.clone()
     }
 
     #[cfg(test)]
-    pub async fn test_message_count(&self) -> usize {
-        self.conversation.lock().await.messages_for_api().len()
+    pub fn test_turn_running(&self) -> bool {
+        self.conversation.try_lock().is_err()
     }
 
     #[cfg(test)]
@@ -97,59 +374,277 @@ impl RuntimeContext {
         self.cancel.is_cancelled()
     }
 
+    /// Turns session-wide tool auto-approval on or off. The conversation reads
+    /// this directly, so approved tools skip the approval prompt entirely.
+    pub fn set_session_auto_approve(&self, enabled: bool) {
+        self.approval_policy.set_session_auto_approve(enabled);
+    }
+
+    pub fn session_auto_approve(&self) -> bool {
+        self.approval_policy.session_auto_approve()
+    }
+
     pub fn cancel_turn(&mut self) {
         self.cancel.cancel();
         self.cancel = CancellationToken::new();
     }
+
+    /// Tool calls still running, including any left behind by a cancelled
+    /// turn.
+    pub fn in_flight_tools(&self) -> usize {
+        self.reader.in_flight_tools()
+    }
+
+    pub fn tool_pool_stats(&self) -> ToolPoolStats {
+        self.reader.tool_pool_stats()
+    }
+
+    /// True when no turn holds the conversation and no tool call is running,
+    /// so the process can exit without abandoning work.
+    pub fn shutdown_settled(&self) -> bool {
+        self.conversation.try_lock().is_ok() && self.reader.in_flight_tools() == 0
+    }
+
+    /// Writes a last session backup before exit. `None` when backups are off
+    /// or nothing changed since the last one. A turn still running is saved
+    /// as of its last round boundary.
+    pub fn flush_session(&self) -> anyhow::Result<Option<PathBuf>> {
+        if let Some(store) = &self.session_store {
+            let session = match self.conversation.try_lock() {
+                Ok(conversation) => Some(conversation.saved_session()),
+                Err(_) => self.reader.checkpoint(),
+            };
+            if let Some(session) = session {
+                store.save(&session)?;
+            }
+        }
+        match &self.autosave {
+            Some(autosave) => autosave.save(&self.reader),
+            None => Ok(None),
+        }
+    }
+
+    /// Saved sessions for `/resume`, newest first; empty when saving is off.
+    pub fn saved_sessions(&self) -> anyhow::Result<Vec<SavedSessionSummary>> {
+        match &self.session_store {
+            Some(store) => store.list(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The saved-session index entry for `id` or a unique prefix of one,
+    /// with each turn's file changes.
+    pub fn saved_session_summary(&self, id: &str) -> anyhow::Result<SavedSessionSummary> {
+        match &self.session_store {
+            Some(store) => store.find_summary(id),
+            None => anyhow::bail!(
+                "saved sessions are off ({} is false)",
+                crate::state::SESSIONS_ENV
+            ),
+        }
+    }
+
+    /// Drops the repo map and workspace stats after the workspace changed
+    /// outside the tools, e.g. through `/undo`.
+    pub fn invalidate_workspace_caches(&self) {
+        self.tool_operator.invalidate_workspace_caches();
+    }
+
+    /// Id the current conversation is saved under. While a turn runs it
+    /// comes from the last round checkpoint.
+    pub fn current_session_id(&self) -> Option<String> {
+        match self.conversation.try_lock() {
+            Ok(conversation) => Some(conversation.session_id().to_string()),
+            Err(_) => self.reader.checkpoint().map(|session| session.id),
+        }
+    }
+
+    /// Saves the current conversation, then replaces it with the saved
+    /// session `id` (or a unique prefix of one).
+    pub fn resume_session(&self, id: &str) -> anyhow::Result<SavedSession> {
+        let Some(store) = &self.session_store else {
+            anyhow::bail!(
+                "saved sessions are off ({} is false)",
+                crate::state::SESSIONS_ENV
+            );
+        };
+        let Ok(mut conversation) = self.conversation.try_lock() else {
+            anyhow::bail!("a turn is running; wait for it to finish or cancel it first");
+        };
+        let saved = store.load(id)?;
+        store.save(&conversation.saved_session())?;
+        conversation.restore_session(saved.clone());
+        Ok(saved)
+    }
+
+    /// Deletes the files this session uploaded, waiting at most `timeout`.
+    /// Skipped while a turn still holds the conversation. Returns how many
+    /// uploads could not be deleted.
+    pub async fn release_uploaded_files(&self, timeout: std::time::Duration) -> usize {
+        let Ok(mut conversation) = self.conversation.try_lock() else {
+            return 0;
+        };
+        let uploaded = conversation.uploaded_file_count();
+        tokio::time::timeout(timeout, conversation.release_uploaded_files())
+            .await
+            .unwrap_or(uploaded)
+    }
+
+    /// Cancels the active turn and carries `reason` into the next user message
+    /// so the model can adjust instead of retrying the cancelled work.
+    pub fn cancel_turn_with_reason(&mut self, reason: &str) {
+        self.cancel_turn();
+        let reason = reason.trim();
+        if !reason.is_empty() {
+            self.pending_cancel_reason = Some(reason.to_string());
+        }
+    }
+}
+
+async fn run_periodic_autosave(
+    conversation: Weak<Mutex<ConversationManager>>,
+    reader: ConversationReader,
+    autosave: Arc<SessionAutosave>,
+    interval: std::time::Duration,
+    update_tx: mpsc::UnboundedSender<UiUpdate>,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+        if conversation.strong_count() == 0 {
+            return;
+        }
+        if let Err(error) = autosave.save(&reader) {
+            let _ = update_tx.send(UiUpdate::Status(format!(
+                "session autosave failed: {error:#}"
+            )));
+        }
+    }
+}
+
+fn turn_hook_details(elapsed: std::time::Duration, changes: &[String], cancelled: bool) -> Value {
+    json!({
+        "elapsed_ms": elapsed.as_millis() as u64,
+        "cancelled": cancelled,
+        "changes": changes,
+    })
+}
+
+/// Sends the turn's change summary, returning its rendered lines.
+fn send_turn_changes(
+    reader: &ConversationReader,
+    tx: &mpsc::UnboundedSender<UiUpdate>,
+) -> Vec<String> {
+    let Some(summary) = reader.latest_turn_changes() else {
+        return Vec::new();
+    };
+    let lines = summary.render_lines();
+    let _ = tx.send(UiUpdate::TurnChanges(summary));
+    lines
 }
 
+/// Publishes the bus counterpart of a conversation update. Plain text deltas
+/// are not published; subscribers follow the blocks instead.
+fn publish_stream_event(events: &EventBus, update: &ConversationStreamUpdate) {
+    let event = match update {
+        // Status notices are published by the conversation as their own events.
+        ConversationStreamUpdate::Delta(_)
+        | ConversationStreamUpdate::Status(_)
+        | ConversationStreamUpdate::UserQuestion(_) => return,
+        ConversationStreamUpdate::BlockStart { index, block } => RuntimeEvent::BlockStarted {
+            index: *index,
+            block: block.clone(),
+        },
+        ConversationStreamUpdate::BlockDelta { index, delta } => RuntimeEvent::BlockDelta {
+            index: *index,
+            delta: delta.clone(),
+        },
+        ConversationStreamUpdate::BlockComplete { index } => {
+            RuntimeEvent::BlockCompleted { index: *index }
+        }
+        ConversationStreamUpdate::ToolApprovalRequest(request) => RuntimeEvent::ApprovalRequested {
+            tool_name: request.tool_name.clone(),
+            detail: request.input_preview.clone(),
+            class: request.class,
+        },
+    };
+    events.publish(event);
+}
+
+/// Sends the updates that only the frontend receives; stream blocks reach
+/// it through the bus (see [`forward_block_event`]).
 fn forward_conversation_update(
     update: ConversationStreamUpdate,
-    textual_block_by_index: &mut std::collections::HashMap<usize, bool>,
     tx: &mpsc::UnboundedSender<UiUpdate>,
 ) {
     match update {
         ConversationStreamUpdate::Delta(text) => {
             let _ = tx.send(UiUpdate::StreamDelta(text));
         }
-        ConversationStreamUpdate::BlockStart { index, block } => {
+        ConversationStreamUpdate::BlockStart { .. }
+        | ConversationStreamUpdate::BlockDelta { .. }
+        | ConversationStreamUpdate::BlockComplete { .. } => {}
+        ConversationStreamUpdate::ToolApprovalRequest(request) => {
+            let _ = tx.send(UiUpdate::ToolApprovalRequest(request));
+        }
+        ConversationStreamUpdate::UserQuestion(request) => {
+            let _ = tx.send(UiUpdate::UserQuestion(request));
+        }
+        ConversationStreamUpdate::Status(text) => {
+            let _ = tx.send(UiUpdate::Status(text));
+        }
+    }
+}
+
+/// Turns a bus block event into frontend updates, mirroring the text of
+/// thinking and final-text blocks into the plain text stream.
+fn forward_block_event(
+    event: &RuntimeEvent,
+    textual_block_by_index: &mut HashMap<usize, bool>,
+    tx: &mpsc::UnboundedSender<UiUpdate>,
+) {
+    match event {
+        RuntimeEvent::BlockStarted { index, block } => {
             let is_textual = matches!(
                 block,
                 StreamBlock::Thinking { .. } | StreamBlock::FinalText { .. }
             );
-            textual_block_by_index.insert(index, is_textual);
-            if let StreamBlock::FinalText { content } = &block {
+            textual_block_by_index.insert(*index, is_textual);
+            if let StreamBlock::FinalText { content } = block {
                 if !content.is_empty() {
                     let _ = tx.send(UiUpdate::StreamDelta(content.clone()));
                 }
             }
-            let _ = tx.send(UiUpdate::StreamBlockStart { index, block });
+            let _ = tx.send(UiUpdate::StreamBlockStart {
+                index: *index,
+                block: block.clone(),
+            });
         }
-        ConversationStreamUpdate::BlockDelta { index, delta } => {
+        RuntimeEvent::BlockDelta { index, delta } => {
             let _ = tx.send(UiUpdate::StreamBlockDelta {
-                index,
+                index: *index,
                 delta: delta.clone(),
             });
-            if textual_block_by_index.get(&index).copied().unwrap_or(false) {
-                let _ = tx.send(UiUpdate::StreamDelta(delta));
+            if textual_block_by_index.get(index).copied().unwrap_or(false) {
+                let _ = tx.send(UiUpdate::StreamDelta(delta.clone()));
             }
         }
-        ConversationStreamUpdate::BlockComplete { index } => {
-            textual_block_by_index.remove(&index);
-            let _ = tx.send(UiUpdate::StreamBlockComplete { index });
-        }
-        ConversationStreamUpdate::ToolApprovalRequest(request) => {
-            let _ = tx.send(UiUpdate::ToolApprovalRequest(request));
+        RuntimeEvent::BlockCompleted { index } => {
+            textual_block_by_index.remove(index);
+            let _ = tx.send(UiUpdate::StreamBlockComplete { index: *index });
         }
+        _ => {}
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{forward_conversation_update, RuntimeContext};
+    use super::{forward_block_event, RuntimeContext};
     use crate::api::{mock_client::MockApiClient, ApiClient};
+    use crate::events::{EventKind, RuntimeEvent};
+    use crate::runtime::autosave::SessionAutosave;
     use crate::runtime::UiUpdate;
-    use crate::state::{ConversationManager, ConversationStreamUpdate, StreamBlock};
+    use crate::state::{ConversationManager, StreamBlock};
+    use crate::usage::UsageLog;
     use std::collections::HashMap;
     use std::sync::Arc;
     use std::time::Duration;
@@ -189,6 +684,141 @@ mod tests {
         assert!(saw_complete, "expected TurnComplete");
     }
 
+    #[tokio::test]
+    async fn test_turn_events_reach_bus_subscribers() {
+        let (tx, mut rx) = mpsc::unbounded_channel::<UiUpdate>();
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(vec![vec![
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hello\"},\"finish_reason\":\"stop\"}]}\n\n".to_string(),
+        ]])));
+        let conversation = ConversationManager::new_mock(client, HashMap::new());
+        let mut ctx = RuntimeContext::new(conversation, tx, CancellationToken::new());
+        let mut turns = ctx
+            .events()
+            .subscribe(&[EventKind::Turn, EventKind::Metrics]);
+
+        ctx.start_turn("say hello".to_string());
+        loop {
+            match tokio::time::timeout(Duration::from_millis(500), rx.recv()).await {
+                Ok(Some(UiUpdate::TurnComplete)) => break,
+                Ok(Some(UiUpdate::Error(e))) => panic!("unexpected error: {e}"),
+                Ok(None) | Err(_) => panic!("turn did not complete"),
+                _ => {}
+            }
+        }
+
+        let mut seen = Vec::new();
+        while let Some(event) = turns.try_recv() {
+            seen.push(match event.as_ref() {
+                RuntimeEvent::TurnStarted { input } => format!("started {input}"),
+                RuntimeEvent::RoundStarted { round, .. } => format!("round {round}"),
+                RuntimeEvent::Usage { succeeded, .. } => format!("usage {succeeded}"),
+                RuntimeEvent::TurnCompleted { cancelled, .. } => format!("completed {cancelled}"),
+                other => panic!("unsubscribed event {other:?}"),
+            });
+        }
+        assert_eq!(
+            seen,
+            [
+                "started say hello",
+                "round 1",
+                "usage true",
+                "completed false"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_completed_turn_writes_session_backup() {
+        let temp = tempfile::TempDir::new().expect("tempdir");
+        let (tx, mut rx) = mpsc::unbounded_channel::<UiUpdate>();
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(vec![vec![
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Saved\"},\"finish_reason\":\"stop\"}]}\n\n".to_string(),
+        ]])));
+        let conversation = ConversationManager::new_mock(client, HashMap::new());
+        let mut ctx = RuntimeContext::new(conversation, tx, CancellationToken::new())
+            .with_autosave(SessionAutosave::new(temp.path(), 3, None));
+
+        ctx.start_turn("remember this".to_string());
+        loop {
+            match tokio::time::timeout(Duration::from_millis(500), rx.recv()).await {
+                Ok(Some(UiUpdate::TurnComplete)) => break,
+                Ok(Some(UiUpdate::Error(e))) => panic!("unexpected error: {e}"),
+                Ok(None) | Err(_) => panic!("turn did not complete"),
+                _ => {}
+            }
+        }
+
+        let backup = SessionAutosave::new(temp.path(), 3, None)
+            .load_latest()
+            .expect("read backups")
+            .expect("backup written on turn completion");
+        assert_eq!(backup.messages.len(), 2);
+        assert!(backup.turn_tags.is_empty());
+
+        // A tag added after the turn still gets its own backup.
+        let reader = ctx.conversation_reader();
+        assert_eq!(reader.tag_turn(&["bug-hunt".to_string()]), Some(1));
+        assert_eq!(reader.tag_turn(&["bug-hunt".to_string()]), Some(1));
+        assert!(ctx.flush_session().expect("flush").is_some());
+        let backup = SessionAutosave::new(temp.path(), 3, None)
+            .load_latest()
+            .expect("read backups")
+            .expect("backup");
+        assert_eq!(backup.turn_tags.len(), 1);
+        assert_eq!(
+            (
+                backup.turn_tags[0].turn,
+                backup.turn_tags[0].prompt.as_str()
+            ),
+            (1, "remember this")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_completed_turn_appends_usage_record_without_content() {
+        let temp = tempfile::TempDir::new().expect("tempdir");
+        let (tx, mut rx) = mpsc::unbounded_channel::<UiUpdate>();
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(vec![vec![
+            "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_usage\",\"type\":\"message\",\"role\":\"assistant\",\"model\":\"mock-model\",\"content\":[],\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n".to_string(),
+            "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n".to_string(),
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"secret answer\"}}\n\n".to_string(),
+            "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n".to_string(),
+            "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\",\"stop_sequence\":null},\"usage\":{\"output_tokens\":4}}\n\n".to_string(),
+            "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n".to_string(),
+        ]])));
+        let conversation = ConversationManager::new_mock(client, HashMap::new());
+        let usage_log = UsageLog::for_workspace(temp.path());
+        let mut ctx = RuntimeContext::new(conversation, tx, CancellationToken::new())
+            .with_usage_log(usage_log.clone());
+
+        ctx.start_turn("secret prompt".to_string());
+        loop {
+            match tokio::time::timeout(Duration::from_millis(500), rx.recv()).await {
+                Ok(Some(UiUpdate::TurnComplete)) => break,
+                Ok(Some(UiUpdate::Error(e))) => panic!("unexpected error: {e}"),
+                Ok(None) | Err(_) => panic!("turn did not complete"),
+                _ => {}
+            }
+        }
+
+        // The record is written off the turn task.
+        let mut records = usage_log.read().expect("read usage log");
+        for _ in 0..50 {
+            if !records.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            records = usage_log.read().expect("read usage log");
+        }
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].model, "mock-model");
+        assert_eq!(records[0].input_tokens, 12);
+        assert_eq!(records[0].output_tokens, 4);
+        assert_eq!(records[0].outcome, "complete");
+        let raw = std::fs::read_to_string(usage_log.path()).expect("raw log");
+        assert!(!raw.contains("secret"));
+    }
+
     #[test]
     fn test_ref_07_no_runtime_guard() {
         let (tx, mut rx) = mpsc::unbounded_channel::<UiUpdate>();
@@ -210,8 +840,8 @@ mod tests {
         }
 
         assert_eq!(
-            ctx.test_message_count_try_lock(),
-            Some(0),
+            ctx.conversation_reader().message_count(),
+            0,
             "history must stay clean when guard fires"
         );
     }
@@ -260,10 +890,7 @@ mod tests {
             "exactly one TurnComplete"
         );
     }
-    #[tokio::test(flavor = "current_thread")]
-    async fn test_ref_08_tool_approval_forwarding_no_hang() {
-        let _env_lock = crate::test_support::ENV_LOCK.lock().await;
-        std::env::set_var("VEX_TOOL_CONFIRM", "true");
+    fn tool_then_final_rounds() -> Vec<Vec<String>> {
         let first_response_sse = vec![
             r#"event: message_start
 data: {"type":"message_start","message":{"id":"msg_tool_then_final_1","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string(),
@@ -294,11 +921,15 @@ data: {"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":
 data: {"type":"message_stop"}"#.to_string(),
         ];
 
+        vec![first_response_sse, second_response_sse]
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_ref_08_tool_approval_forwarding_no_hang() {
+        let _env_lock = crate::test_support::ENV_LOCK.lock().await;
+        std::env::set_var("VEX_TOOL_CONFIRM", "true");
         let (tx, mut rx) = mpsc::unbounded_channel::<UiUpdate>();
-        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(vec![
-            first_response_sse,
-            second_response_sse,
-        ])));
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(tool_then_final_rounds())));
         let conversation = ConversationManager::new_mock(client, HashMap::new());
         let mut ctx = RuntimeContext::new(conversation, tx, CancellationToken::new());
 
@@ -310,7 +941,7 @@ data: {"type":"message_stop"}"#.to_string(),
             match tokio::time::timeout(Duration::from_millis(800), rx.recv()).await {
                 Ok(Some(UiUpdate::ToolApprovalRequest(request))) => {
                     saw_request = true;
-                    let _ = request.response_tx.send(false);
+                    let _ = request.response_tx.send(false.into());
                 }
                 Ok(Some(UiUpdate::TurnComplete)) => {
                     saw_complete = true;
@@ -327,13 +958,60 @@ data: {"type":"message_stop"}"#.to_string(),
         std::env::remove_var("VEX_TOOL_CONFIRM");
     }
 
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_state_is_readable_while_tool_waits_for_approval() {
+        let _env_lock = crate::test_support::ENV_LOCK.lock().await;
+        std::env::set_var("VEX_TOOL_CONFIRM", "true");
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        let autosave = SessionAutosave::new(temp.path(), 3, None);
+        let (tx, mut rx) = mpsc::unbounded_channel::<UiUpdate>();
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(tool_then_final_rounds())));
+        let conversation = ConversationManager::new_mock(client, HashMap::new());
+        let mut ctx = RuntimeContext::new(conversation, tx, CancellationToken::new());
+
+        ctx.start_turn("read file".to_string());
+
+        let request = loop {
+            match tokio::time::timeout(Duration::from_millis(800), rx.recv()).await {
+                Ok(Some(UiUpdate::ToolApprovalRequest(request))) => break request,
+                Ok(Some(UiUpdate::Error(e))) => panic!("unexpected error: {e}"),
+                Ok(Some(_)) => {}
+                _ => panic!("expected a tool approval request"),
+            }
+        };
+
+        assert!(ctx.test_turn_running(), "turn must still be in flight");
+        let reader = ctx.conversation_reader();
+        assert_eq!(reader.message_count(), 2, "user prompt and tool_use");
+        assert_eq!(reader.token_usage().input_tokens, 10);
+        assert!(autosave.save(&reader).expect("save").is_some());
+        // The backup stops at the round boundary, before the tool_use that
+        // has no result yet.
+        let backup = autosave.load_latest().expect("load").expect("backup");
+        assert_eq!(backup.messages.len(), 1);
+        assert_eq!(ctx.current_session_id(), reader.checkpoint().map(|s| s.id));
+        assert!(ctx.current_session_id().is_some());
+
+        let _ = request.response_tx.send(false.into());
+        loop {
+            match tokio::time::timeout(Duration::from_millis(800), rx.recv()).await {
+                Ok(Some(UiUpdate::TurnComplete)) => break,
+                Ok(Some(UiUpdate::Error(e))) => panic!("unexpected error: {e}"),
+                Ok(Some(_)) => {}
+                _ => panic!("turn did not complete"),
+            }
+        }
+        assert_eq!(reader.message_count(), 4);
+        std::env::remove_var("VEX_TOOL_CONFIRM");
+    }
+
     #[tokio::test]
     async fn test_ref_08_block_delta_partial_json_not_mirrored_to_stream_delta() {
         let (tx, mut rx) = mpsc::unbounded_channel::<UiUpdate>();
         let mut textual_block_by_index = std::collections::HashMap::new();
 
-        forward_conversation_update(
-            ConversationStreamUpdate::BlockStart {
+        forward_block_event(
+            &RuntimeEvent::BlockStarted {
                 index: 1,
                 block: StreamBlock::ToolCall {
                     id: "toolu_1".to_string(),
@@ -346,8 +1024,8 @@ data: {"type":"message_stop"}"#.to_string(),
             &tx,
         );
 
-        forward_conversation_update(
-            ConversationStreamUpdate::BlockDelta {
+        forward_block_event(
+            &RuntimeEvent::BlockDelta {
                 index: 1,
                 delta: "{\"path\":\"file.txt\"}".to_string(),
             },
@@ -385,8 +1063,8 @@ data: {"type":"message_stop"}"#.to_string(),
         let (tx, mut rx) = mpsc::unbounded_channel::<UiUpdate>();
         let mut textual_block_by_index = std::collections::HashMap::new();
 
-        forward_conversation_update(
-            ConversationStreamUpdate::BlockDelta {
+        forward_block_event(
+            &RuntimeEvent::BlockDelta {
                 index: 99,
                 delta: "mystery".to_string(),
             },
@@ -476,4 +1154,35 @@ data: {"type":"message_stop"}"#.to_string(),
             "cancel path must emit exactly one terminal event"
         );
     }
+
+    #[tokio::test]
+    async fn test_cancel_reason_is_prepended_to_next_user_message() {
+        let (tx, mut rx) = mpsc::unbounded_channel::<UiUpdate>();
+        let client = ApiClient::new_mock(Arc::new(MockApiClient::new(vec![vec![
+            "data: {\"choices\":[{\"delta\":{\"content\":\"ok\"},\"finish_reason\":\"stop\"}]}\n\n"
+                .to_string(),
+        ]])));
+        let conversation = ConversationManager::new_mock(client, HashMap::new());
+        let mut ctx = RuntimeContext::new(conversation, tx, CancellationToken::new());
+
+        ctx.cancel_turn_with_reason("don't touch the migrations folder");
+        ctx.start_turn("try again".to_string());
+
+        loop {
+            match tokio::time::timeout(Duration::from_millis(800), rx.recv()).await {
+                Ok(Some(UiUpdate::TurnComplete)) => break,
+                Ok(Some(UiUpdate::Error(e))) => panic!("unexpected error: {e}"),
+                Ok(Some(_)) => {}
+                _ => panic!("turn did not complete"),
+            }
+        }
+
+        let messages = ctx.conversation_reader().messages();
+        let crate::types::Content::Text(first) = &messages[0].content else {
+            panic!("expected text user message");
+        };
+        assert!(first.contains("cancelled by the user"));
+        assert!(first.contains("don't touch the migrations folder"));
+        assert!(first.ends_with("try again"));
+    }
 }
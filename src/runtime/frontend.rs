@@ -18,6 +18,8 @@ pub enum ScrollAction {
 
 pub enum UserInputEvent {
     Text(String),
+    /// A paste big enough that the mode should confirm where it goes.
+    LargePaste(String),
     Interrupt,
     Scroll {
         target: ScrollTarget,
@@ -29,4 +31,14 @@ pub trait FrontendAdapter<M: RuntimeMode> {
     fn poll_user_input(&mut self, mode: &M) -> Option<UserInputEvent>;
     fn render(&mut self, mode: &M);
     fn should_quit(&self) -> bool;
+    /// True once after input the frontend handled itself (editing the
+    /// draft, a resize) changed what it shows, so the loop draws again.
+    fn take_redraw_request(&mut self) -> bool {
+        false
+    }
+    /// Checked while shutdown waits on running tools; true when the user
+    /// asked to exit immediately (a second Ctrl+C).
+    fn poll_force_quit(&mut self) -> bool {
+        false
+    }
 }
@@ -11,6 +11,41 @@ pub struct Runtime<M: RuntimeMode> {
 
 const IDLE_RENDER_TICK: Duration = Duration::from_millis(120);
 const IDLE_LOOP_BACKOFF: Duration = Duration::from_millis(4);
+/// How long quitting waits for a cancelled turn and its tools to finish.
+pub const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+const SHUTDOWN_POLL: Duration = Duration::from_millis(25);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownOutcome {
+    /// Nothing was left running.
+    Clean,
+    /// The grace period ran out with tools still running.
+    TimedOut,
+    /// A second Ctrl+C skipped the wait.
+    Forced,
+}
+
+impl ShutdownOutcome {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Clean => "clean",
+            Self::TimedOut => "timed_out",
+            Self::Forced => "forced",
+        }
+    }
+}
+
+/// What `Runtime::run` left behind. When tools were abandoned the caller
+/// should exit the process directly: dropping the Tokio runtime would wait
+/// for their blocking tasks.
+#[derive(Debug)]
+pub struct ShutdownReport {
+    pub outcome: ShutdownOutcome,
+    pub abandoned_tools: usize,
+    /// Files API uploads that could not be deleted at exit.
+    pub undeleted_uploads: usize,
+    pub session_error: Option<String>,
+}
 
 impl<M: RuntimeMode> Runtime<M> {
     pub fn new(mode: M, update_rx: mpsc::UnboundedReceiver<UiUpdate>) -> Self {
@@ -21,7 +56,7 @@ impl<M: RuntimeMode> Runtime<M> {
     /// Must be called within a Tokio runtime context (e.g., `#[tokio::main]`
     /// or `block_on`). The async signature enforces `.await` at compile time
     /// for the loop path.
-    pub async fn run<F>(&mut self, frontend: &mut F, ctx: &mut RuntimeContext)
+    pub async fn run<F>(&mut self, frontend: &mut F, ctx: &mut RuntimeContext) -> ShutdownReport
     where
         F: FrontendAdapter<M>,
     {
@@ -43,8 +78,19 @@ impl<M: RuntimeMode> Runtime<M> {
                 self.mode.on_model_update(update, ctx);
             }
 
+            if frontend.take_redraw_request() {
+                state_changed = true;
+            }
+
             let now = Instant::now();
-            let tick_due = now.saturating_duration_since(last_render_at) >= IDLE_RENDER_TICK;
+            if self.mode.on_frame(now.into_std()) {
+                state_changed = true;
+            }
+            if self.mode.poll_background(ctx) {
+                state_changed = true;
+            }
+            let tick_due = now.saturating_duration_since(last_render_at) >= IDLE_RENDER_TICK
+                && self.mode.needs_tick_render();
             if first_render_pending || state_changed || tick_due {
                 frontend.render(&self.mode);
                 last_render_at = now;
@@ -53,6 +99,59 @@ impl<M: RuntimeMode> Runtime<M> {
                 sleep(IDLE_LOOP_BACKOFF).await;
             }
         }
+        self.shutdown(frontend, ctx).await
+    }
+
+    /// Cancels any running turn, waits up to `SHUTDOWN_GRACE` for it and
+    /// its tools to finish while still rendering updates, deletes the
+    /// session's uploaded files, then saves the session.
+    async fn shutdown<F>(&mut self, frontend: &mut F, ctx: &mut RuntimeContext) -> ShutdownReport
+    where
+        F: FrontendAdapter<M>,
+    {
+        let mut outcome = ShutdownOutcome::Clean;
+        if !ctx.shutdown_settled() {
+            ctx.cancel_turn();
+            self.mode
+                .on_shutdown_started(ctx.in_flight_tools(), SHUTDOWN_GRACE, ctx);
+            let deadline = Instant::now() + SHUTDOWN_GRACE;
+            loop {
+                while let Ok(update) = self.update_rx.try_recv() {
+                    self.mode.on_model_update(update, ctx);
+                }
+                frontend.render(&self.mode);
+                if ctx.shutdown_settled() {
+                    break;
+                }
+                if frontend.poll_force_quit() {
+                    outcome = ShutdownOutcome::Forced;
+                    break;
+                }
+                if Instant::now() >= deadline {
+                    outcome = ShutdownOutcome::TimedOut;
+                    break;
+                }
+                sleep(SHUTDOWN_POLL).await;
+            }
+        }
+        let undeleted_uploads = if outcome == ShutdownOutcome::Forced {
+            0
+        } else {
+            ctx.release_uploaded_files(SHUTDOWN_GRACE).await
+        };
+        if outcome != ShutdownOutcome::Forced {
+            ctx.run_session_end_hook(outcome.label(), SHUTDOWN_GRACE)
+                .await;
+        }
+        ShutdownReport {
+            outcome,
+            abandoned_tools: ctx.in_flight_tools(),
+            undeleted_uploads,
+            session_error: ctx
+                .flush_session()
+                .err()
+                .map(|error| format!("session autosave failed: {error:#}")),
+        }
     }
 }
 
@@ -190,12 +289,17 @@ mod tests {
         let mut runtime = Runtime::new(mode, update_rx);
 
         let mut frontend = HeadlessFrontend::new(vec!["hello", "world"], 3);
-        runtime.run(&mut frontend, &mut ctx).await;
+        let report = runtime.run(&mut frontend, &mut ctx).await;
 
-        assert_eq!(
-            frontend.render_count, 3,
-            "loop must render exactly quit_after times before exiting"
+        // Shutdown keeps rendering while it waits for the turn started by
+        // "hello", which fails against the empty mock.
+        assert!(
+            frontend.render_count >= 3,
+            "loop must render at least quit_after times before exiting"
         );
+        assert_eq!(report.outcome, ShutdownOutcome::Clean);
+        assert_eq!(report.abandoned_tools, 0);
+        assert!(ctx.shutdown_settled());
     }
 
     #[tokio::test]
@@ -261,4 +365,66 @@ mod tests {
             "idle render count should remain bounded by tick-driven scheduling"
         );
     }
+
+    struct StillMode;
+
+    impl RuntimeMode for StillMode {
+        fn on_user_input(&mut self, _input: String, _ctx: &mut RuntimeContext) {}
+
+        fn on_model_update(&mut self, _update: UiUpdate, _ctx: &mut RuntimeContext) {}
+
+        fn is_turn_in_progress(&self) -> bool {
+            false
+        }
+
+        fn needs_tick_render(&self) -> bool {
+            false
+        }
+    }
+
+    struct DraftFrontend {
+        render_count: usize,
+        polls: usize,
+    }
+
+    impl FrontendAdapter<StillMode> for DraftFrontend {
+        fn poll_user_input(&mut self, _mode: &StillMode) -> Option<UserInputEvent> {
+            self.polls += 1;
+            None
+        }
+
+        fn render(&mut self, _mode: &StillMode) {
+            self.render_count += 1;
+        }
+
+        fn should_quit(&self) -> bool {
+            self.polls > 80
+        }
+
+        fn take_redraw_request(&mut self) -> bool {
+            // A keystroke edits the draft on the 40th poll.
+            self.polls == 40
+        }
+    }
+
+    #[tokio::test]
+    async fn test_still_mode_draws_only_when_something_changed() {
+        let mock = Arc::new(MockApiClient::new(vec![]));
+        let client = ApiClient::new_mock(mock);
+        let conversation = ConversationManager::new_mock(client, HashMap::new());
+
+        let (tx, update_rx) = mpsc::unbounded_channel::<UiUpdate>();
+        let mut ctx =
+            RuntimeContext::new(conversation, tx, tokio_util::sync::CancellationToken::new());
+        let mut runtime = Runtime::new(StillMode, update_rx);
+
+        // 80 polls with the idle backoff span several render ticks.
+        let mut frontend = DraftFrontend {
+            render_count: 0,
+            polls: 0,
+        };
+        runtime.run(&mut frontend, &mut ctx).await;
+
+        assert_eq!(frontend.render_count, 2);
+    }
 }
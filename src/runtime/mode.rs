@@ -1,4 +1,5 @@
 use crate::runtime::UiUpdate;
+use std::time::{Duration, Instant};
 
 use super::context::RuntimeContext;
 use super::frontend::UserInputEvent;
@@ -9,10 +10,38 @@ pub trait RuntimeMode {
     fn on_interrupt(&mut self, _ctx: &mut RuntimeContext) {}
     fn on_frontend_event(&mut self, event: UserInputEvent, ctx: &mut RuntimeContext) {
         match event {
-            UserInputEvent::Text(input) => self.on_user_input(input, ctx),
+            UserInputEvent::Text(input) | UserInputEvent::LargePaste(input) => {
+                self.on_user_input(input, ctx)
+            }
             UserInputEvent::Interrupt => self.on_interrupt(ctx),
             UserInputEvent::Scroll { .. } => {}
         }
     }
     fn is_turn_in_progress(&self) -> bool;
+    /// Asked when an idle tick is due: whether the screen changes with time
+    /// alone (a spinner, an elapsed clock), so the tick should still draw.
+    /// Modes that report false are drawn only after input or a model update.
+    fn needs_tick_render(&self) -> bool {
+        true
+    }
+    /// Called every loop pass after model updates are drained: whether the
+    /// mode changed with time alone, such as releasing smoothed text, and
+    /// needs a render this frame.
+    fn on_frame(&mut self, _now: Instant) -> bool {
+        false
+    }
+    /// Called every loop pass: picks up work the mode started off the UI
+    /// thread, and whether that changed anything to render.
+    fn poll_background(&mut self, _ctx: &mut RuntimeContext) -> bool {
+        false
+    }
+    /// Called once when quitting has to wait for a cancelled turn or
+    /// running tools, for up to `grace`.
+    fn on_shutdown_started(
+        &mut self,
+        _in_flight_tools: usize,
+        _grace: Duration,
+        _ctx: &mut RuntimeContext,
+    ) {
+    }
 }
@@ -3,6 +3,7 @@ pub trait RuntimeCorePolicy {
     fn request_requires_tool_evidence(&self, input: &str) -> bool;
     fn tool_retry_instruction(&self) -> &'static str;
     fn repeated_tool_round_instruction(&self) -> &'static str;
+    fn continuation_instruction(&self) -> &'static str;
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -21,6 +22,10 @@ const REPEATED_TOOL_ROUND_INSTRUCTION: &str =
 Do not repeat identical tool calls. Use existing tool results to answer now. \
 Only call a different tool if new evidence is required.";
 
+const CONTINUATION_INSTRUCTION: &str =
+    "Your previous answer was cut off at the output token limit. Continue exactly \
+where it stopped, mid-sentence if needed. Do not repeat or summarize earlier text.";
+
 const TOOL_REQUIRED_HINTS: [&str; 29] = [
     "file",
     "files",
@@ -80,6 +85,10 @@ impl RuntimeCorePolicy for DefaultRuntimeCorePolicy {
     fn repeated_tool_round_instruction(&self) -> &'static str {
         REPEATED_TOOL_ROUND_INSTRUCTION
     }
+
+    fn continuation_instruction(&self) -> &'static str {
+        CONTINUATION_INSTRUCTION
+    }
 }
 
 fn strip_tagged_tool_markup(text: &str) -> String {
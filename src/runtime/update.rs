@@ -1,4 +1,5 @@
-use crate::state::{StreamBlock, ToolApprovalRequest};
+use crate::change_summary::TurnChangeSummary;
+use crate::state::{StreamBlock, ToolApprovalRequest, UserQuestionRequest};
 
 pub enum UiUpdate {
     StreamDelta(String),
@@ -6,6 +7,12 @@ pub enum UiUpdate {
     StreamBlockDelta { index: usize, delta: String },
     StreamBlockComplete { index: usize },
     ToolApprovalRequest(ToolApprovalRequest),
+    // A question from the model's `ask_user` tool.
+    UserQuestion(UserQuestionRequest),
+    // What the turn's tools changed; sent just before the turn ends.
+    TurnChanges(TurnChangeSummary),
     TurnComplete,
+    // A one-line notice about the running turn.
+    Status(String),
     Error(String),
 }
@@ -0,0 +1,409 @@
+use crate::state_dirs::StateDirs;
+use crate::util::parse_bool_str;
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::OnceLock;
+
+pub const SANDBOX_FLAG: &str = "--sandbox";
+/// Set on the sandboxed child to the number of an inherited pipe that holds
+/// the backend's name, so it skips tool approval instead of sandboxing again.
+/// The variable alone proves nothing, since a profile can set it; the pipe
+/// can only come from the process that started the sandbox.
+pub const SANDBOXED_ENV: &str = "VEX_SANDBOXED";
+/// Extra writable paths, separated like `PATH`.
+pub const SANDBOX_WRITABLE_ENV: &str = "VEX_SANDBOX_WRITABLE";
+/// `off` cuts the sandbox off from the network. vex itself then reaches only
+/// a model served inside the sandbox, so the default keeps it.
+pub const SANDBOX_NETWORK_ENV: &str = "VEX_SANDBOX_NETWORK";
+const SANDBOX_TOKEN_PREFIX: &str = "vex-sandbox:";
+
+/// Removes `--sandbox` from `args`. True when this process should start a
+/// sandboxed child, which a sandboxed child never does.
+pub fn take_sandbox_flag(args: &mut Vec<String>) -> bool {
+    let before = args.len();
+    args.retain(|arg| arg != SANDBOX_FLAG);
+    args.len() != before && active_backend().is_none()
+}
+
+/// The backend this process runs under, when it was started by
+/// [`run_sandboxed`].
+pub fn active_backend() -> Option<String> {
+    static BACKEND: OnceLock<Option<String>> = OnceLock::new();
+    BACKEND
+        .get_or_init(|| {
+            let fd = std::env::var(SANDBOXED_ENV).ok()?.trim().parse().ok()?;
+            inherited_backend(fd)
+        })
+        .clone()
+}
+
+/// Reads the backend name [`run_sandboxed`] left in the pipe at `fd`.
+/// Anything but a pipe holding the token, including the standard streams,
+/// means this process was not started sandboxed.
+#[cfg(unix)]
+fn inherited_backend(fd: u32) -> Option<String> {
+    use std::io::Read;
+    use std::os::unix::fs::FileTypeExt;
+
+    if fd <= 2 {
+        return None;
+    }
+    let path = PathBuf::from(format!("/dev/fd/{fd}"));
+    if !std::fs::metadata(&path).ok()?.file_type().is_fifo() {
+        return None;
+    }
+    let mut token = String::new();
+    std::fs::File::open(&path)
+        .ok()?
+        .take(256)
+        .read_to_string(&mut token)
+        .ok()?;
+    token
+        .strip_prefix(SANDBOX_TOKEN_PREFIX)
+        .filter(|backend| !backend.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(not(unix))]
+fn inherited_backend(_fd: u32) -> Option<String> {
+    None
+}
+
+/// OS facility that confines writes to the allowed paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SandboxBackend {
+    /// Linux mount namespaces through `bwrap`: the filesystem is bound
+    /// read-only except for the writable paths.
+    Bubblewrap,
+    /// macOS Seatbelt through `sandbox-exec` with a generated profile.
+    Seatbelt,
+}
+
+impl SandboxBackend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bubblewrap => "bubblewrap",
+            Self::Seatbelt => "seatbelt",
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Self::Bubblewrap => "bwrap",
+            Self::Seatbelt => "sandbox-exec",
+        }
+    }
+
+    /// The backend for this platform when its wrapper is installed.
+    pub fn detect() -> Result<Self> {
+        let backend = if cfg!(target_os = "linux") {
+            Self::Bubblewrap
+        } else if cfg!(target_os = "macos") {
+            Self::Seatbelt
+        } else {
+            bail!(
+                "{SANDBOX_FLAG} is supported on Linux (bubblewrap) and macOS (sandbox-exec) only"
+            );
+        };
+        if find_program(backend.program()).is_none() {
+            bail!(
+                "{SANDBOX_FLAG} needs `{}` on PATH for the {} sandbox; vex will not run unsandboxed",
+                backend.program(),
+                backend.name()
+            );
+        }
+        Ok(backend)
+    }
+
+    /// Wrapper arguments that run `exe args` with writes confined to the
+    /// policy's writable paths, minus its read-only ones. Reads and process
+    /// execution stay open; the network unless the policy cuts it.
+    pub fn wrapper_args(self, policy: &SandboxPolicy, exe: &Path, args: &[String]) -> Vec<String> {
+        let mut out = Vec::new();
+        match self {
+            Self::Bubblewrap => {
+                out.extend(["--ro-bind", "/", "/", "--dev-bind", "/dev", "/dev"].map(String::from));
+                for path in &policy.writable {
+                    let path = path.to_string_lossy().into_owned();
+                    out.extend(["--bind".to_string(), path.clone(), path]);
+                }
+                for path in &policy.read_only {
+                    let path = path.to_string_lossy().into_owned();
+                    out.extend(["--ro-bind-try".to_string(), path.clone(), path]);
+                }
+                out.extend(["--unshare-ipc", "--unshare-pid", "--proc", "/proc"].map(String::from));
+                if !policy.network {
+                    out.push("--unshare-net".to_string());
+                }
+                out.extend(["--new-session", "--die-with-parent", "--"].map(String::from));
+            }
+            Self::Seatbelt => {
+                out.push("-p".to_string());
+                out.push(seatbelt_profile(policy));
+            }
+        }
+        out.push(exe.to_string_lossy().into_owned());
+        out.extend(args.iter().cloned());
+        out
+    }
+}
+
+/// What the sandboxed process may write and reach.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxPolicy {
+    pub writable: Vec<PathBuf>,
+    /// Inside a writable path but still read-only: files vex later runs or
+    /// trusts, which the sandboxed model must not plant.
+    pub read_only: Vec<PathBuf>,
+    pub network: bool,
+}
+
+impl SandboxPolicy {
+    /// The workspace, the temp dir, and `VEX_SANDBOX_WRITABLE`, canonicalized
+    /// because Seatbelt matches resolved paths; paths that do not exist are
+    /// left out. See [`protected_paths`] for what stays read-only inside the
+    /// workspace. The vex config dir (profiles, hook trust) is not writable
+    /// at all.
+    pub fn for_workspace(workspace: &Path) -> Self {
+        let mut candidates = vec![workspace.to_path_buf(), std::env::temp_dir()];
+        if let Some(extra) = std::env::var_os(SANDBOX_WRITABLE_ENV) {
+            candidates.extend(std::env::split_paths(&extra).filter(|path| path.is_absolute()));
+        }
+        let mut writable: Vec<PathBuf> = Vec::new();
+        for candidate in candidates {
+            if let Ok(path) = candidate.canonicalize() {
+                if !writable.contains(&path) {
+                    writable.push(path);
+                }
+            }
+        }
+        let root = workspace
+            .canonicalize()
+            .unwrap_or_else(|_| workspace.to_path_buf());
+        Self {
+            writable,
+            read_only: protected_paths(&root),
+            network: std::env::var(SANDBOX_NETWORK_ENV)
+                .ok()
+                .and_then(|value| parse_bool_str(&value))
+                .unwrap_or(true),
+        }
+    }
+}
+
+/// Workspace paths whose contents run outside the sandbox later: vex's
+/// custom tools and hooks, and git's hooks and config (which can also name a
+/// hooks path, pager, or fsmonitor command). A `.git` file (a worktree
+/// pointer), a missing `.git`, or one without `hooks/` is protected whole,
+/// since otherwise a repository or hooks directory could be created.
+fn protected_paths(root: &Path) -> Vec<PathBuf> {
+    let state_dirs = StateDirs::for_workspace(root);
+    let mut paths = vec![state_dirs.tools_dir(), state_dirs.hooks_file()];
+    let git = root.join(".git");
+    if git.join("hooks").is_dir() {
+        paths.extend([git.join("hooks"), git.join("config")]);
+    } else {
+        paths.push(git);
+    }
+    paths
+}
+
+fn seatbelt_profile(policy: &SandboxPolicy) -> String {
+    let subpaths: String = policy
+        .writable
+        .iter()
+        .map(|path| format!(" (subpath {:?})", path.to_string_lossy()))
+        .collect();
+    let mut profile = format!(
+        "(version 1)\n(allow default)\n(deny file-write*)\n(allow file-write*{subpaths} (literal \"/dev/null\") (regex #\"^/dev/(tty|fd/)\"))\n"
+    );
+    // Later rules win, so these carve the read-only paths back out.
+    if !policy.read_only.is_empty() {
+        let read_only: String = policy
+            .read_only
+            .iter()
+            .map(|path| format!(" (subpath {:?})", path.to_string_lossy()))
+            .collect();
+        profile.push_str(&format!("(deny file-write*{read_only})\n"));
+    }
+    if !policy.network {
+        profile.push_str("(deny network*)\n");
+    }
+    profile
+}
+
+fn find_program(name: &str) -> Option<PathBuf> {
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Runs vex with `args` under the platform sandbox described by
+/// [`SandboxPolicy::for_workspace`]. Returns the exit code.
+pub async fn run_sandboxed(args: &[String]) -> Result<i32> {
+    let backend = SandboxBackend::detect()?;
+    let exe = std::env::current_exe().context("failed to locate the vex executable")?;
+    let workspace = std::env::current_dir()?;
+    let policy = SandboxPolicy::for_workspace(&workspace);
+    let wrapper_args = backend.wrapper_args(&policy, &exe, args);
+    let mut command = tokio::process::Command::new(backend.program());
+    command
+        .args(&wrapper_args)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    // Held until the child exits so its descriptor stays valid.
+    let _token = pass_sandbox_token(&mut command, backend)?;
+    let status = command
+        .status()
+        .await
+        .with_context(|| format!("failed to start `{}`", backend.program()))?;
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Leaves the backend's name in a pipe the child inherits and points
+/// `VEX_SANDBOXED` at it.
+#[cfg(unix)]
+fn pass_sandbox_token(
+    command: &mut tokio::process::Command,
+    backend: SandboxBackend,
+) -> Result<std::io::PipeReader> {
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+
+    let (reader, mut writer) = std::io::pipe().context("failed to create the sandbox pipe")?;
+    writer.write_all(format!("{SANDBOX_TOKEN_PREFIX}{}", backend.name()).as_bytes())?;
+    drop(writer);
+    rustix::io::fcntl_setfd(&reader, rustix::io::FdFlags::empty())
+        .context("failed to pass the sandbox pipe")?;
+    command.env(SANDBOXED_ENV, reader.as_raw_fd().to_string());
+    Ok(reader)
+}
+
+#[cfg(not(unix))]
+fn pass_sandbox_token(
+    _command: &mut tokio::process::Command,
+    _backend: SandboxBackend,
+) -> Result<()> {
+    bail!("{SANDBOX_FLAG} is supported on Linux and macOS only")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrapper_args_bind_only_the_writable_paths() {
+        let policy = SandboxPolicy {
+            writable: vec![PathBuf::from("/work"), PathBuf::from("/tmp")],
+            read_only: vec![PathBuf::from("/work/.aistar/tools")],
+            network: false,
+        };
+        let args = ["--emit-patches=out".to_string()];
+        assert_eq!(
+            SandboxBackend::Bubblewrap.wrapper_args(&policy, Path::new("/bin/vex"), &args),
+            [
+                "--ro-bind",
+                "/",
+                "/",
+                "--dev-bind",
+                "/dev",
+                "/dev",
+                "--bind",
+                "/work",
+                "/work",
+                "--bind",
+                "/tmp",
+                "/tmp",
+                "--ro-bind-try",
+                "/work/.aistar/tools",
+                "/work/.aistar/tools",
+                "--unshare-ipc",
+                "--unshare-pid",
+                "--proc",
+                "/proc",
+                "--unshare-net",
+                "--new-session",
+                "--die-with-parent",
+                "--",
+                "/bin/vex",
+                "--emit-patches=out",
+            ]
+        );
+
+        let seatbelt = SandboxBackend::Seatbelt.wrapper_args(&policy, Path::new("/bin/vex"), &args);
+        assert_eq!(seatbelt[0], "-p");
+        assert!(seatbelt[1].contains("(deny file-write*)"));
+        assert!(seatbelt[1].contains("(subpath \"/work\") (subpath \"/tmp\")"));
+        assert!(seatbelt[1].contains("(deny file-write* (subpath \"/work/.aistar/tools\"))"));
+        assert!(seatbelt[1].contains("(deny network*)"));
+        assert_eq!(seatbelt[2..], ["/bin/vex", "--emit-patches=out"]);
+    }
+
+    #[test]
+    fn test_git_hooks_and_config_are_bound_read_only() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        let root = temp.path().canonicalize().expect("canonical root");
+        std::fs::create_dir_all(root.join(".git/hooks")).expect("git dir");
+        std::fs::write(root.join(".git/config"), "[core]\n").expect("git config");
+        let hooks = root.join(".git/hooks").to_string_lossy().into_owned();
+        let config = root.join(".git/config").to_string_lossy().into_owned();
+
+        let policy = SandboxPolicy::for_workspace(&root);
+        assert!(policy.writable.contains(&root));
+        let bwrap = SandboxBackend::Bubblewrap.wrapper_args(&policy, Path::new("/bin/vex"), &[]);
+        for path in [&hooks, &config] {
+            assert!(
+                bwrap
+                    .windows(3)
+                    .any(|args| args == ["--ro-bind-try", path.as_str(), path.as_str()]),
+                "{bwrap:?}"
+            );
+        }
+        let seatbelt = seatbelt_profile(&policy);
+        let denied = seatbelt
+            .lines()
+            .rfind(|line| line.starts_with("(deny file-write* (subpath"))
+            .expect("read-only rule");
+        assert!(
+            denied.contains(&format!("(subpath {hooks:?})")),
+            "{seatbelt}"
+        );
+        assert!(
+            denied.contains(&format!("(subpath {config:?})")),
+            "{seatbelt}"
+        );
+
+        // A worktree's `.git` file could be repointed, so all of it is kept.
+        std::fs::remove_dir_all(root.join(".git")).expect("remove git dir");
+        std::fs::write(root.join(".git"), "gitdir: /elsewhere\n").expect("git file");
+        assert!(SandboxPolicy::for_workspace(&root)
+            .read_only
+            .contains(&root.join(".git")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_only_an_inherited_pipe_marks_the_process_sandboxed() {
+        use std::io::Write;
+        use std::os::fd::AsRawFd;
+
+        let (reader, mut writer) = std::io::pipe().expect("pipe");
+        writer
+            .write_all(b"vex-sandbox:bubblewrap")
+            .expect("write token");
+        drop(writer);
+        let fd = reader.as_raw_fd() as u32;
+        assert_eq!(inherited_backend(fd).as_deref(), Some("bubblewrap"));
+
+        let file = tempfile::tempfile().expect("file");
+        assert_eq!(inherited_backend(file.as_raw_fd() as u32), None);
+        assert_eq!(inherited_backend(0), None);
+        assert_eq!(inherited_backend(u32::MAX), None);
+
+        let mut args = vec!["--sandbox".to_string(), "--supervised".to_string()];
+        assert!(take_sandbox_flag(&mut args));
+        assert_eq!(args, vec!["--supervised"]);
+    }
+}
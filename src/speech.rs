@@ -0,0 +1,374 @@
+use anyhow::{bail, Context, Result};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::{mpsc, Notify};
+
+const CODE_FENCE: &str = "```";
+
+/// Where finished sentences are sent for speaking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpeechTarget {
+    /// Shell command run once per sentence with the sentence on stdin, e.g.
+    /// `say` or `espeak --stdin`.
+    Command(String),
+    /// URL that receives each sentence as a `text/plain` POST body.
+    Http(String),
+}
+
+impl SpeechTarget {
+    /// `VEX_TTS_COMMAND` takes precedence over `VEX_TTS_URL`; neither set
+    /// leaves speech off.
+    pub fn from_env() -> Option<Self> {
+        let non_empty = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        non_empty("VEX_TTS_COMMAND")
+            .map(Self::Command)
+            .or_else(|| non_empty("VEX_TTS_URL").map(Self::Http))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Command(command) => format!("command `{command}`"),
+            Self::Http(url) => format!("POST {url}"),
+        }
+    }
+}
+
+/// Splits streamed response text into speakable sentences. Markdown markers
+/// are stripped and fenced code blocks are skipped entirely.
+#[derive(Debug)]
+pub struct SentenceBuffer {
+    pending: String,
+    at_line_start: bool,
+    in_code_fence: bool,
+}
+
+impl Default for SentenceBuffer {
+    fn default() -> Self {
+        Self {
+            pending: String::new(),
+            at_line_start: true,
+            in_code_fence: false,
+        }
+    }
+}
+
+impl SentenceBuffer {
+    /// Appends `text` and returns the sentences it completed.
+    pub fn push(&mut self, text: &str) -> Vec<String> {
+        self.pending.push_str(text);
+        let mut sentences = Vec::new();
+        loop {
+            if self.at_line_start {
+                let head = self.pending.trim_start_matches([' ', '\t']);
+                let fence_line = head.starts_with(CODE_FENCE);
+                if self.in_code_fence || fence_line {
+                    let Some(end) = self.pending.find('\n') else {
+                        break;
+                    };
+                    if fence_line {
+                        self.in_code_fence = !self.in_code_fence;
+                    }
+                    self.pending.drain(..=end);
+                    continue;
+                }
+                if CODE_FENCE.starts_with(head) && !self.pending.contains('\n') {
+                    // Could still become a fence line.
+                    break;
+                }
+            }
+            let Some(end) = sentence_end(&self.pending) else {
+                break;
+            };
+            let sentence: String = self.pending.drain(..end).collect();
+            self.at_line_start = sentence.ends_with('\n');
+            sentences.extend(speakable(&sentence));
+        }
+        sentences
+    }
+
+    /// Returns the unterminated tail at the end of a response.
+    pub fn flush(&mut self) -> Option<String> {
+        let tail = std::mem::take(&mut self.pending);
+        let in_code_fence = self.in_code_fence;
+        self.clear();
+        if in_code_fence {
+            None
+        } else {
+            speakable(&tail)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Byte offset just past the first sentence terminator (`.`, `!`, or `?`
+/// followed by whitespace) or newline.
+fn sentence_end(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, ch)) = chars.next() {
+        if ch == '\n' {
+            return Some(index + 1);
+        }
+        if matches!(ch, '.' | '!' | '?') {
+            if let Some(&(next_index, next)) = chars.peek() {
+                if next.is_whitespace() {
+                    return Some(next_index + next.len_utf8());
+                }
+            }
+        }
+    }
+    None
+}
+
+fn speakable(text: &str) -> Option<String> {
+    let line = text.trim().trim_start_matches('#').trim_start();
+    let line = ["- ", "* ", "+ ", "> "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))
+        .unwrap_or(line);
+    let cleaned: String = line.chars().filter(|ch| !matches!(ch, '*' | '`')).collect();
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    cleaned
+        .chars()
+        .any(char::is_alphanumeric)
+        .then_some(cleaned)
+}
+
+/// Speaks sentences one at a time on a background task so the TUI never
+/// waits on the TTS engine. The first failure disables speech and is kept
+/// for the frontend to report.
+#[derive(Debug)]
+pub struct Speaker {
+    target: SpeechTarget,
+    tx: mpsc::UnboundedSender<String>,
+    interrupt: Arc<Notify>,
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+impl Speaker {
+    /// Must be called from within a Tokio runtime.
+    pub fn spawn(target: SpeechTarget) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        let interrupt = Arc::new(Notify::new());
+        let failure = Arc::new(Mutex::new(None));
+        let worker_target = target.clone();
+        let worker_interrupt = Arc::clone(&interrupt);
+        let worker_failure = Arc::clone(&failure);
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            while let Some(sentence) = rx.recv().await {
+                let spoken = tokio::select! {
+                    result = speak(&client, &worker_target, &sentence) => result,
+                    _ = worker_interrupt.notified() => {
+                        // Drop whatever was queued behind the interrupted sentence.
+                        while rx.try_recv().is_ok() {}
+                        Ok(())
+                    }
+                };
+                if let Err(error) = spoken {
+                    if let Ok(mut failure) = worker_failure.lock() {
+                        *failure = Some(format!("{error:#}"));
+                    }
+                    break;
+                }
+            }
+        });
+        Self {
+            target,
+            tx,
+            interrupt,
+            failure,
+        }
+    }
+
+    pub fn target(&self) -> &SpeechTarget {
+        &self.target
+    }
+
+    pub fn say(&self, sentence: String) {
+        let _ = self.tx.send(sentence);
+    }
+
+    /// Stops the sentence being spoken and drops queued ones.
+    pub fn interrupt(&self) {
+        self.interrupt.notify_waiters();
+    }
+
+    /// The error that stopped the speaker, reported once.
+    pub fn take_failure(&self) -> Option<String> {
+        self.failure.lock().ok()?.take()
+    }
+}
+
+async fn speak(client: &reqwest::Client, target: &SpeechTarget, sentence: &str) -> Result<()> {
+    match target {
+        SpeechTarget::Command(command) => {
+            let mut child = shell_command(command)
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .kill_on_drop(true)
+                .spawn()
+                .with_context(|| format!("failed to run VEX_TTS_COMMAND `{command}`"))?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(sentence.as_bytes()).await?;
+            }
+            let status = child.wait().await?;
+            if !status.success() {
+                bail!("VEX_TTS_COMMAND `{command}` exited with {status}");
+            }
+        }
+        SpeechTarget::Http(url) => {
+            client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+                .body(sentence.to_string())
+                .send()
+                .await
+                .with_context(|| format!("failed to reach VEX_TTS_URL {url}"))?
+                .error_for_status()?;
+        }
+    }
+    Ok(())
+}
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+/// Feeds one response stream to a [`Speaker`] sentence by sentence.
+#[derive(Debug)]
+pub struct SpeechStream {
+    speaker: Speaker,
+    buffer: SentenceBuffer,
+}
+
+impl SpeechStream {
+    pub fn new(speaker: Speaker) -> Self {
+        Self {
+            speaker,
+            buffer: SentenceBuffer::default(),
+        }
+    }
+
+    pub fn speaker(&self) -> &Speaker {
+        &self.speaker
+    }
+
+    pub fn push(&mut self, text: &str) {
+        for sentence in self.buffer.push(text) {
+            self.speaker.say(sentence);
+        }
+    }
+
+    /// Speaks the unterminated tail once the response is complete.
+    pub fn finish(&mut self) {
+        if let Some(tail) = self.buffer.flush() {
+            self.speaker.say(tail);
+        }
+    }
+
+    /// Drops buffered text and silences the speaker.
+    pub fn cancel(&mut self) {
+        self.buffer.clear();
+        self.speaker.interrupt();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sentences_split_as_they_stream() {
+        let mut buffer = SentenceBuffer::default();
+        assert!(buffer.push("I updated **the").is_empty());
+        assert_eq!(
+            buffer.push(" parser**. Tests pass! Version 3.14 is"),
+            vec!["I updated the parser.", "Tests pass!"]
+        );
+        assert_eq!(buffer.push(" out.\n"), vec!["Version 3.14 is out."]);
+        assert_eq!(
+            buffer.push("## Next steps\n- Run `cargo test`\n"),
+            vec!["Next steps", "Run cargo test"]
+        );
+        assert_eq!(buffer.flush(), None);
+        assert_eq!(buffer.push("Done"), Vec::<String>::new());
+        assert_eq!(buffer.flush(), Some("Done".to_string()));
+    }
+
+    #[test]
+    fn test_code_fences_are_not_spoken() {
+        let mut buffer = SentenceBuffer::default();
+        let mut spoken = buffer.push("Here is the fix.\n``");
+        spoken.extend(buffer.push("`rust\nlet x = a.b(); // note. more\n"));
+        spoken.extend(buffer.push("```\nThat should do it."));
+        assert_eq!(spoken, vec!["Here is the fix."]);
+        assert_eq!(buffer.flush(), Some("That should do it.".to_string()));
+
+        buffer.push("```sh\necho unfinished");
+        assert_eq!(buffer.flush(), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_speaker_receives_sentences_in_order() -> Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        let out = temp.path().join("spoken.txt");
+        let command = format!("cat >> '{}'; echo >> '{}'", out.display(), out.display());
+        let mut stream = SpeechStream::new(Speaker::spawn(SpeechTarget::Command(command)));
+        stream.push("First sentence. Second");
+        stream.push(" sentence");
+        stream.finish();
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        loop {
+            let spoken = std::fs::read_to_string(&out).unwrap_or_default();
+            if spoken.lines().count() == 2 {
+                assert_eq!(spoken, "First sentence.\nSecond sentence\n");
+                break;
+            }
+            assert!(std::time::Instant::now() < deadline, "spoken: {spoken:?}");
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(stream.speaker().take_failure(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_target_prefers_command() {
+        let _guard = crate::test_support::ENV_LOCK.blocking_lock();
+        std::env::set_var("VEX_TTS_URL", "http://localhost:5002/speak");
+        std::env::set_var("VEX_TTS_COMMAND", " ");
+        assert_eq!(
+            SpeechTarget::from_env(),
+            Some(SpeechTarget::Http(
+                "http://localhost:5002/speak".to_string()
+            ))
+        );
+        std::env::set_var("VEX_TTS_COMMAND", "espeak --stdin");
+        assert_eq!(
+            SpeechTarget::from_env(),
+            Some(SpeechTarget::Command("espeak --stdin".to_string()))
+        );
+        std::env::remove_var("VEX_TTS_COMMAND");
+        std::env::remove_var("VEX_TTS_URL");
+        assert_eq!(SpeechTarget::from_env(), None);
+    }
+}
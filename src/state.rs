@@ -1,5 +1,21 @@
 mod conversation;
+mod secret_guard;
+mod sensitive_paths;
+mod session_store;
 mod stream_block;
 
-pub use conversation::{ConversationManager, ConversationStreamUpdate, ToolApprovalRequest};
-pub use stream_block::{StreamBlock, ToolStatus};
+pub(crate) use conversation::append_incremental_suffix;
+pub use conversation::{
+    count_text_tokens, estimate_tokens, render_turn_cancelled_note, ApprovalFuture,
+    ApprovalHandler, ApprovalQuery, ApprovalTier, ContextBreakdown, ConversationManager,
+    ConversationReader, ConversationStreamUpdate, MessageSize, RequestOverhead, RoundAssembly,
+    ToolApprovalDecision, ToolApprovalPolicy, ToolApprovalRequest, ToolClass, ToolInputDelta,
+    UserQuestionRequest, CHARS_PER_TOKEN,
+};
+pub use secret_guard::{send_secrets_input, KnownSecret, SecretGuard, SEND_SECRETS_APPROVAL};
+pub use sensitive_paths::{glob_matches, SensitivePaths, DEFAULT_SENSITIVE_PATTERNS};
+pub use session_store::{
+    sessions_enabled_from_env, SavedSession, SavedSessionSummary, SessionStore,
+    SAVED_SESSION_VERSION, SESSIONS_ENV,
+};
+pub use stream_block::{StreamBlock, ToolStatus, ToolStatusRecord};
@@ -1,17 +1,34 @@
+mod approval_handler;
+mod context_budget;
 mod core;
 mod history;
+mod round_assembly;
 mod state;
 mod streaming;
+mod tool_inputs;
 mod tools;
 
 #[cfg(test)]
 mod tests;
 
-pub use state::{ConversationManager, ConversationStreamUpdate, ToolApprovalRequest};
+pub use approval_handler::{ApprovalFuture, ApprovalHandler, ApprovalQuery};
+pub use context_budget::{
+    count_text_tokens, estimate_tokens, ContextBreakdown, MessageSize, RequestOverhead,
+    CHARS_PER_TOKEN,
+};
+pub use round_assembly::{RoundAssembly, ToolInputDelta};
+pub use state::{
+    ApprovalTier, ConversationManager, ConversationReader, ConversationStreamUpdate,
+    ToolApprovalDecision, ToolApprovalPolicy, ToolApprovalRequest, ToolClass, UserQuestionRequest,
+};
+pub(crate) use streaming::append_incremental_suffix;
+pub use tools::render_turn_cancelled_note;
 
 #[cfg(test)]
 use history::*;
 #[cfg(test)]
 use streaming::*;
 #[cfg(test)]
+use tool_inputs::*;
+#[cfg(test)]
 use tools::*;
@@ -0,0 +1,38 @@
+use super::{ApprovalTier, ToolApprovalDecision, ToolClass};
+use std::future::Future;
+use std::pin::Pin;
+
+/// A tool call waiting for an [`ApprovalHandler`]'s decision.
+#[derive(Debug, Clone)]
+pub struct ApprovalQuery {
+    pub tool_call_id: Option<String>,
+    pub tool_name: String,
+    pub input: serde_json::Value,
+    /// The same preview the approval prompt shows, diff included.
+    pub input_preview: String,
+    pub class: ToolClass,
+    pub paths: Vec<String>,
+    pub tier: ApprovalTier,
+}
+
+pub type ApprovalFuture = Pin<Box<dyn Future<Output = ToolApprovalDecision> + Send>>;
+
+/// Decides the tool calls that need approval, in place of the prompt sent to
+/// the frontend over the turn's update channel. Embedders install one with
+/// [`super::ConversationManager::with_approval_handler`] to approve from a
+/// policy engine or forward the question elsewhere. It is consulted only for
+/// calls that would otherwise prompt, sensitive paths included, and also for
+/// turns sent without an update channel.
+pub trait ApprovalHandler: Send + Sync {
+    fn decide(&self, query: ApprovalQuery) -> ApprovalFuture;
+}
+
+impl<F, Fut> ApprovalHandler for F
+where
+    F: Fn(ApprovalQuery) -> Fut + Send + Sync,
+    Fut: Future<Output = ToolApprovalDecision> + Send + 'static,
+{
+    fn decide(&self, query: ApprovalQuery) -> ApprovalFuture {
+        Box::pin(self(query))
+    }
+}
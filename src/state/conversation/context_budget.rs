@@ -0,0 +1,280 @@
+use super::history::message_contains_tool_result;
+use crate::types::{ApiMessage, Content, ContentBlock};
+use std::ops::Range;
+
+/// Rough characters per token, used where no tokenizer is available.
+pub const CHARS_PER_TOKEN: usize = 4;
+
+pub fn estimate_tokens(chars: usize) -> usize {
+    chars.div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Letters a common English or identifier word piece covers in one token.
+const LETTERS_PER_TOKEN: usize = 6;
+/// Digits BPE vocabularies group into one token.
+const DIGITS_PER_TOKEN: usize = 3;
+/// Indentation spaces that share one token.
+const SPACES_PER_TOKEN: usize = 4;
+/// Role and framing tokens every message carries.
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// Token count of `text` under a BPE-like split, close enough to budget a
+/// context window without shipping a model's vocabulary. A word piece of up
+/// to six letters is one token, digits go three to a token, a single space
+/// rides with the word after it, runs of spaces go four to a token, and
+/// every other symbol or non-ASCII character is its own token. Unlike a
+/// flat characters-per-token ratio, this does not undercount code, which
+/// is dense in punctuation.
+pub fn count_text_tokens(text: &str) -> usize {
+    let mut tokens = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_alphabetic() {
+            let mut letters: usize = 1;
+            while chars.next_if(char::is_ascii_alphabetic).is_some() {
+                letters += 1;
+            }
+            tokens += letters.div_ceil(LETTERS_PER_TOKEN);
+        } else if c.is_ascii_digit() {
+            let mut digits: usize = 1;
+            while chars.next_if(char::is_ascii_digit).is_some() {
+                digits += 1;
+            }
+            tokens += digits.div_ceil(DIGITS_PER_TOKEN);
+        } else if c == ' ' {
+            let mut spaces: usize = 1;
+            while chars.next_if_eq(&' ').is_some() {
+                spaces += 1;
+            }
+            let joins_word = chars.peek().is_some_and(char::is_ascii_alphanumeric);
+            let loose = if joins_word { spaces - 1 } else { spaces };
+            tokens += loose.div_ceil(SPACES_PER_TOKEN);
+        } else {
+            tokens += 1;
+        }
+    }
+    tokens
+}
+
+/// Tokens `message` adds to a request. Uploaded documents are counted by
+/// their reference only, since their size is not known here.
+pub fn message_tokens(message: &ApiMessage) -> usize {
+    let content = match &message.content {
+        Content::Text(text) => count_text_tokens(text),
+        Content::Blocks(blocks) => blocks.iter().map(block_tokens).sum(),
+    };
+    MESSAGE_OVERHEAD_TOKENS + content
+}
+
+fn block_tokens(block: &ContentBlock) -> usize {
+    match block {
+        ContentBlock::Text { text } => count_text_tokens(text),
+        ContentBlock::ToolUse { name, input, .. } => {
+            MESSAGE_OVERHEAD_TOKENS
+                + count_text_tokens(name)
+                + count_text_tokens(&input.to_string())
+        }
+        ContentBlock::ToolResult { content, .. } => {
+            MESSAGE_OVERHEAD_TOKENS + count_text_tokens(content)
+        }
+        ContentBlock::Document { title, .. } => {
+            MESSAGE_OVERHEAD_TOKENS + title.as_deref().map_or(0, count_text_tokens)
+        }
+        ContentBlock::Thinking { thinking, .. } => {
+            MESSAGE_OVERHEAD_TOKENS + count_text_tokens(thinking)
+        }
+        ContentBlock::RedactedThinking { data } => MESSAGE_OVERHEAD_TOKENS + data.len() / 4,
+    }
+}
+
+/// What every request carries besides the message history, in tokens as
+/// [`count_text_tokens`] counts them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestOverhead {
+    pub system_tokens: usize,
+    pub tool_schema_tokens: usize,
+    /// History length above which the oldest messages are pruned.
+    pub max_api_messages: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageSize {
+    pub role: String,
+    /// `text`, `tool_use read_file`, `tool_result x2`, and so on.
+    pub label: String,
+    /// As [`message_tokens`] counts them, the same count history budgeting
+    /// uses.
+    pub tokens: usize,
+}
+
+/// Size of each part of the next request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContextBreakdown {
+    pub overhead: RequestOverhead,
+    pub messages: Vec<MessageSize>,
+    /// History messages pruning removes next: at the next request when the
+    /// history is already at the limit, otherwise once it reaches it.
+    pub next_pruned: Range<usize>,
+    /// Whether `next_pruned` goes with the next request.
+    pub prunes_next_request: bool,
+}
+
+impl ContextBreakdown {
+    pub fn new(overhead: RequestOverhead, messages: &[ApiMessage]) -> Self {
+        let sizes = messages.iter().map(message_size).collect();
+        // The next request adds one user message before pruning runs.
+        let upcoming = messages.len() + 1;
+        let prunes_next_request = overhead.max_api_messages > 0
+            && upcoming > overhead.max_api_messages
+            && !messages.is_empty();
+        let target = if prunes_next_request {
+            upcoming - overhead.max_api_messages
+        } else {
+            1
+        };
+        // Pruning keeps history starting at a user prompt, so the replies
+        // after the first dropped message go with it.
+        let mut keep_start = target.min(messages.len());
+        while keep_start < messages.len() {
+            let message = &messages[keep_start];
+            if message.role == "user" && !message_contains_tool_result(message) {
+                break;
+            }
+            keep_start += 1;
+        }
+        Self {
+            overhead,
+            messages: sizes,
+            next_pruned: 0..keep_start,
+            prunes_next_request,
+        }
+    }
+
+    pub fn history_tokens(&self) -> usize {
+        self.messages.iter().map(|message| message.tokens).sum()
+    }
+
+    pub fn pruned_tokens(&self) -> usize {
+        self.messages[self.next_pruned.clone()]
+            .iter()
+            .map(|message| message.tokens)
+            .sum()
+    }
+}
+
+fn message_size(message: &ApiMessage) -> MessageSize {
+    let label = match &message.content {
+        Content::Text(_) => "text".to_string(),
+        Content::Blocks(blocks) => {
+            let mut parts: Vec<String> = Vec::new();
+            let mut results = 0;
+            for block in blocks {
+                match block {
+                    ContentBlock::Text { .. } => parts.push("text".to_string()),
+                    ContentBlock::ToolUse { name, .. } => parts.push(format!("tool_use {name}")),
+                    ContentBlock::ToolResult { .. } => results += 1,
+                    ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => {
+                        parts.push("thinking".to_string())
+                    }
+                    ContentBlock::Document { title, .. } => parts.push(format!(
+                        "document {}",
+                        title.as_deref().unwrap_or("(untitled)")
+                    )),
+                }
+            }
+            match results {
+                0 => {}
+                1 => parts.push("tool_result".to_string()),
+                count => parts.push(format!("tool_result x{count}")),
+            }
+            parts.dedup();
+            parts.join(", ")
+        }
+    };
+    MessageSize {
+        role: message.role.clone(),
+        label,
+        tokens: message_tokens(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(role: &str, body: &str) -> ApiMessage {
+        ApiMessage {
+            role: role.to_string(),
+            content: Content::Text(body.to_string()),
+        }
+    }
+
+    fn tool_result(id: &str) -> ApiMessage {
+        ApiMessage {
+            role: "user".to_string(),
+            content: Content::Blocks(vec![ContentBlock::ToolResult {
+                tool_use_id: id.to_string(),
+                content: "ok".to_string(),
+                is_error: false,
+            }]),
+        }
+    }
+
+    #[test]
+    fn test_breakdown_names_the_messages_pruning_removes_next() {
+        let messages = vec![
+            text("user", "first"),
+            ApiMessage {
+                role: "assistant".to_string(),
+                content: Content::Blocks(vec![ContentBlock::ToolUse {
+                    id: "t1".to_string(),
+                    name: "read_file".to_string(),
+                    input: serde_json::json!({"path": "a.rs"}),
+                }]),
+            },
+            tool_result("t1"),
+            text("assistant", "done"),
+            text("user", "second"),
+            text("assistant", "ok"),
+        ];
+        let overhead = RequestOverhead {
+            system_tokens: 100,
+            tool_schema_tokens: 1_000,
+            max_api_messages: 5,
+        };
+        let breakdown = ContextBreakdown::new(overhead, &messages);
+        assert_eq!(breakdown.messages[1].label, "tool_use read_file");
+        assert_eq!(breakdown.messages[2].label, "tool_result");
+        assert_eq!(breakdown.messages[0].tokens, message_tokens(&messages[0]));
+        // Seven messages against a limit of five: the first exchange goes.
+        assert!(breakdown.prunes_next_request);
+        assert_eq!(breakdown.next_pruned, 0..4);
+
+        let roomy = ContextBreakdown::new(
+            RequestOverhead {
+                max_api_messages: 32,
+                ..overhead
+            },
+            &messages,
+        );
+        assert!(!roomy.prunes_next_request);
+        assert_eq!(roomy.next_pruned, 0..4);
+        assert_eq!(estimate_tokens(4_001), 1_001);
+    }
+
+    #[test]
+    fn test_token_count_follows_word_and_symbol_boundaries() {
+        assert_eq!(count_text_tokens(""), 0);
+        assert_eq!(count_text_tokens("hello world"), 2);
+        assert_eq!(count_text_tokens("internationalization"), 4);
+        assert_eq!(count_text_tokens("1234567"), 3);
+        // `fn`, ` main`, `(`, `)`, ` `, `{`, `\n`, four spaces, `x`, `;`,
+        // `\n`, `}`.
+        assert_eq!(count_text_tokens("fn main() {\n    x;\n}"), 12);
+        assert_eq!(count_text_tokens("日本"), 2);
+        // Code costs more per character than the flat estimate assumes.
+        let code = "let v = map.get(&k)?.clone();";
+        assert!(count_text_tokens(code) > estimate_tokens(code.len()));
+        assert_eq!(message_tokens(&text("user", "hello world")), 6);
+    }
+}
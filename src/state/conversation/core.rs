@@ -1,31 +1,90 @@
 use super::super::stream_block::{StreamBlock, ToolStatus};
-use super::{history::*, streaming::*, tools::*, ConversationManager, ConversationStreamUpdate};
-use crate::api::stream::StreamParser;
+use super::round_assembly::{RoundAssembly, ToolInputDelta};
+use super::{
+    history::*, streaming::*, tools::*, ConversationManager, ConversationStreamUpdate,
+    ToolApprovalDecision,
+};
+use crate::api::runaway::{RunawayCut, RunawayDetector};
+use crate::api::stream::StreamError;
+use crate::api::watchdog::{StallAction, StallPolicy, StreamPoll, MAX_STALL_RETRIES};
+use crate::audit::ApprovalSource;
+use crate::events::RuntimeEvent;
+use crate::language::language_retry_enabled_from_env;
 use crate::runtime::policy::{default_runtime_policy, RuntimeCorePolicy};
+use crate::turn_overrides::{TurnOverrideRecord, TurnOverrides};
 use crate::types::{ApiMessage, Content, ContentBlock, StreamEvent};
 use anyhow::Result;
-use futures::StreamExt;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
+use std::time::Instant;
 use tokio::sync::mpsc;
 
+/// Marks where a response cut off at `max_tokens` was continued.
+const CONTINUATION_SEAM: &str = " [continued] ";
+
 impl ConversationManager {
     pub async fn send_message(
         &mut self,
         content: String,
         stream_delta_tx: Option<&mpsc::UnboundedSender<ConversationStreamUpdate>>,
+    ) -> Result<String> {
+        self.send_message_with_overrides(content, TurnOverrides::default(), stream_delta_tx)
+            .await
+    }
+
+    /// Sends one user turn with `overrides` applied to every request in it.
+    /// Non-empty overrides are recorded with the turn number.
+    pub async fn send_message_with_overrides(
+        &mut self,
+        content: String,
+        overrides: TurnOverrides,
+        stream_delta_tx: Option<&mpsc::UnboundedSender<ConversationStreamUpdate>>,
+    ) -> Result<String> {
+        let result = self.run_turn(content, overrides, stream_delta_tx).await;
+        self.checkpoint();
+        result
+    }
+
+    async fn run_turn(
+        &mut self,
+        content: String,
+        overrides: TurnOverrides,
+        stream_delta_tx: Option<&mpsc::UnboundedSender<ConversationStreamUpdate>>,
     ) -> Result<String> {
         self.current_turn_blocks.clear();
+        // File locks taken by this turn's edits are released when it ends.
+        let _file_lock_turn = self.tool_operator.file_lock_turn();
+        self.tool_operator.begin_scratch_turn();
+        {
+            let mut transcript = self.transcript();
+            transcript.turn_count += 1;
+            if !overrides.is_empty() {
+                let record =
+                    TurnOverrideRecord::new(transcript.turn_count, &content, overrides.clone());
+                transcript.turn_overrides.push(record);
+            }
+        }
         let original_user_input = content.clone();
-        self.push_user_message(content);
+        let content = self.with_recalled_memories(content);
+        let content = match self.pending_turn_note.take() {
+            Some(note) => format!("{note}\n\n{content}"),
+            None => content,
+        };
+        let (documents, upload_notes) = self.upload_pending_documents(stream_delta_tx).await;
+        let content = if upload_notes.is_empty() {
+            content
+        } else {
+            format!("{}\n\n{content}", upload_notes.join("\n"))
+        };
+        self.push_user_message_with_documents(content, documents);
         if let Some(response) = builtin_supported_git_tools_response(&original_user_input) {
-            self.api_messages.push(ApiMessage {
+            self.transcript().api_messages.push(ApiMessage {
                 role: "assistant".to_string(),
                 content: Content::Text(response.clone()),
             });
             emit_text_update(stream_delta_tx, response.clone());
             return Ok(response);
         }
-        let mut turn_user_anchor_index = self.api_messages.len().saturating_sub(1);
+        let mut turn_user_anchor_index = self.transcript().api_messages.len().saturating_sub(1);
 
         let core_policy = default_runtime_policy();
         let use_structured_tool_protocol = self.client.supports_structured_tool_protocol();
@@ -33,11 +92,25 @@ impl ConversationManager {
         let requires_tool_evidence =
             core_policy.request_requires_tool_evidence(&original_user_input);
         let limits = resolve_history_limits(self.client.is_local_endpoint());
+        let history_token_budget = self.history_token_budget(&overrides);
         let tool_timeout = resolve_tool_timeout(self.client.is_local_endpoint());
         let max_tool_rounds = resolve_max_tool_rounds(self.client.is_local_endpoint());
         let stream_server_events = stream_server_events_enabled();
         let stream_local_tool_events = stream_local_tool_events_enabled();
         let require_tool_approval = tool_approval_enabled(self.client.is_local_endpoint());
+        let tool_input_limits = resolve_tool_input_limits();
+        let retry_transient_tool_errors = tool_retry_enabled();
+        let max_continuations = resolve_max_continuations();
+        let runaway_block_bytes = resolve_runaway_block_bytes();
+        let stream_watchdog = self.stream_watchdog;
+        let mut stall_retries = 0usize;
+        let language_retry = language_retry_enabled_from_env();
+        let mut language_retry_used = false;
+        let mut continuations = 0usize;
+        // Text of earlier rounds that stopped at max_tokens, stitched onto
+        // the final answer.
+        let mut continued_text = String::new();
+        let mut tool_failure_streaks = HashMap::new();
         let mut rounds = 0usize;
         let mut forced_tool_retry_count = 0usize;
         let mut saw_any_tool_round = false;
@@ -50,6 +123,14 @@ impl ConversationManager {
             self.current_turn_blocks.clear();
             turn_user_anchor_index = self
                 .prune_message_history_preserving(limits.max_api_messages, turn_user_anchor_index);
+            if let Some(budget) = history_token_budget {
+                turn_user_anchor_index =
+                    self.fit_history_to_token_budget(budget, turn_user_anchor_index);
+                turn_user_anchor_index = self
+                    .refit_history_to_counted_tokens(budget, turn_user_anchor_index)
+                    .await;
+            }
+            self.checkpoint();
             rounds += 1;
             if rounds > max_tool_rounds {
                 return Ok(render_loop_limit_guard_message(
@@ -57,22 +138,71 @@ impl ConversationManager {
                     max_tool_rounds,
                 ));
             }
+            self.events.publish(RuntimeEvent::RoundStarted {
+                turn: self.transcript().turn_count,
+                round: rounds,
+            });
 
-            let mut stream = self.client.create_stream(&self.api_messages).await?;
-            let mut parser = StreamParser::new();
-            let mut assistant_text = String::new();
-            let mut tool_use_blocks = Vec::new();
-            let mut tool_input_buffers: Vec<Option<String>> = Vec::new();
+            self.guard_outgoing_secrets(stream_delta_tx).await?;
+            let request_messages = self.messages_for_api();
+            let mut stream = self
+                .client
+                .create_stream_with_overrides(&request_messages, &overrides)
+                .await?;
+            let mut parser = self.client.stream_parser();
+            let mut round = RoundAssembly::new(tool_input_limits.max_input_bytes);
             let mut tool_input_event_emitted: Vec<bool> = Vec::new();
             let mut deferred_text_block_indices = BTreeSet::new();
+            let mut runaway_detector =
+                (runaway_block_bytes > 0).then(|| RunawayDetector::new(runaway_block_bytes));
+            let mut runaway_cut: Option<RunawayCut> = None;
+            let mut stalled_before_output = false;
 
-            while let Some(chunk_result) = stream.next().await {
+            'stream: loop {
+                let report_round = rounds;
+                let chunk_result = match stream_watchdog
+                    .next(&mut stream, |silent| {
+                        self.report_stream_stall(
+                            report_round,
+                            silent,
+                            StallAction::Waiting,
+                            stream_delta_tx,
+                        )
+                    })
+                    .await
+                {
+                    StreamPoll::Item(chunk_result) => chunk_result,
+                    StreamPoll::Ended => break,
+                    StreamPoll::Stalled(silent) => {
+                        // A retry after partial output would show it twice.
+                        let retry = stream_watchdog.policy() == StallPolicy::Retry
+                            && stall_retries < MAX_STALL_RETRIES
+                            && round.text.is_empty()
+                            && !round.has_tool_uses();
+                        let action = if retry {
+                            StallAction::Retrying
+                        } else {
+                            StallAction::Aborting
+                        };
+                        self.report_stream_stall(rounds, silent, action, stream_delta_tx);
+                        if !retry {
+                            return Err(StreamError::Stalled {
+                                silent_secs: silent.as_secs(),
+                            }
+                            .into());
+                        }
+                        stall_retries += 1;
+                        stalled_before_output = true;
+                        break;
+                    }
+                };
                 let chunk = chunk_result?;
                 let events = parser.process(&chunk)?;
 
                 for event in events {
                     match event {
-                        StreamEvent::MessageStart { .. } => {
+                        StreamEvent::MessageStart { message } => {
+                            round.message_start(message.usage);
                             if !use_structured_blocks && stream_server_events {
                                 emit_text_update(
                                     stream_delta_tx,
@@ -113,7 +243,10 @@ impl ConversationManager {
                                             stream_delta_tx,
                                         );
                                     }
-                                    ContentBlock::ToolResult { .. } => {}
+                                    ContentBlock::ToolResult { .. }
+                                    | ContentBlock::Document { .. }
+                                    | ContentBlock::Thinking { .. }
+                                    | ContentBlock::RedactedThinking { .. } => {}
                                 }
                             } else if stream_server_events {
                                 let event_label = match &content_block {
@@ -124,48 +257,56 @@ impl ConversationManager {
                                     ContentBlock::ToolResult { .. } => {
                                         format!("\n* Event: tool_result_block#{index}\n")
                                     }
+                                    ContentBlock::Document { .. } => {
+                                        format!("\n* Event: document_block#{index}\n")
+                                    }
+                                    ContentBlock::Thinking { .. }
+                                    | ContentBlock::RedactedThinking { .. } => {
+                                        format!("\n* Event: thinking_block#{index}\n")
+                                    }
                                 };
                                 emit_text_update(stream_delta_tx, event_label);
                             }
 
-                            let tool_name =
-                                if let ContentBlock::ToolUse { name, .. } = &content_block {
-                                    Some(name.clone())
-                                } else {
-                                    None
-                                };
-                            if tool_name.is_some() {
-                                while tool_use_blocks.len() <= index {
-                                    tool_use_blocks.push(None);
-                                    tool_input_buffers.push(None);
-                                    tool_input_event_emitted.push(false);
-                                }
-                                tool_use_blocks[index] = Some(content_block);
-                                tool_input_buffers[index] = Some(String::new());
+                            round.start_block(index, &content_block);
+                            if matches!(content_block, ContentBlock::ToolUse { .. })
+                                && tool_input_event_emitted.len() <= index
+                            {
+                                tool_input_event_emitted.resize(index + 1, false);
                             }
                         }
                         StreamEvent::ContentBlockDelta { index, delta } => {
+                            round.thinking_delta(index, &delta);
                             if let Some(text) = delta.text {
-                                if use_structured_blocks {
+                                let appended = if use_structured_blocks {
                                     let delta_tx = if deferred_text_block_indices.contains(&index) {
                                         None
                                     } else {
                                         stream_delta_tx
                                     };
                                     let appended = self.append_text_delta(index, &text, delta_tx);
-                                    assistant_text.push_str(&appended);
+                                    round.text.push_str(&appended);
+                                    appended
                                 } else {
-                                    assistant_text.push_str(&text);
-                                    emit_text_update(stream_delta_tx, text);
+                                    round.text.push_str(&text);
+                                    emit_text_update(stream_delta_tx, text.clone());
+                                    text
+                                };
+                                if let Some(cut) = runaway_detector
+                                    .as_mut()
+                                    .and_then(|detector| detector.observe(index, &appended))
+                                {
+                                    self.clamp_runaway_block(index, &cut, &mut round.text);
+                                    runaway_cut = Some(cut);
+                                    break 'stream;
                                 }
                             }
 
                             if let Some(partial_json) = delta.partial_json {
-                                let maybe_buffer = tool_input_buffers.get_mut(index);
-                                if let Some(Some(buffer)) = maybe_buffer {
-                                    buffer.push_str(&partial_json);
-
-                                    if use_structured_blocks {
+                                match round.tool_input_delta(index, &partial_json) {
+                                    ToolInputDelta::Oversized => continue,
+                                    ToolInputDelta::Dropped => {}
+                                    ToolInputDelta::Appended(buffer) if use_structured_blocks => {
                                         if let Ok(parsed_input) =
                                             serde_json::from_str::<serde_json::Value>(buffer)
                                         {
@@ -183,6 +324,7 @@ impl ConversationManager {
                                             },
                                         );
                                     }
+                                    ToolInputDelta::Appended(_) => {}
                                 }
                                 if !use_structured_blocks && stream_server_events {
                                     let should_emit = tool_input_event_emitted
@@ -203,26 +345,12 @@ impl ConversationManager {
                             }
                         }
                         StreamEvent::ContentBlockStop { index } => {
-                            let maybe_json = tool_input_buffers.get_mut(index);
-                            let maybe_tool = tool_use_blocks.get_mut(index);
-
-                            if let (
-                                Some(Some(json_str)),
-                                Some(Some(ContentBlock::ToolUse { input, .. })),
-                            ) = (maybe_json, maybe_tool)
-                            {
-                                if !json_str.is_empty() {
-                                    if let Ok(parsed_input) = serde_json::from_str(json_str) {
-                                        *input = parsed_input;
-
-                                        if let Some(StreamBlock::ToolCall {
-                                            input: block_input,
-                                            ..
-                                        }) = self.current_turn_blocks.get_mut(index)
-                                        {
-                                            *block_input = input.clone();
-                                        }
-                                    }
+                            if let Some(Ok(input)) = round.stop_block(index) {
+                                if let Some(StreamBlock::ToolCall {
+                                    input: block_input, ..
+                                }) = self.current_turn_blocks.get_mut(index)
+                                {
+                                    *block_input = input.clone();
                                 }
                             }
                             if use_structured_blocks {
@@ -232,7 +360,8 @@ impl ConversationManager {
                                 );
                             }
                         }
-                        StreamEvent::MessageDelta { delta } => {
+                        StreamEvent::MessageDelta { delta, usage } => {
+                            round.message_delta(&delta, usage.as_ref());
                             if !use_structured_blocks && stream_server_events {
                                 let stop_reason =
                                     delta.stop_reason.unwrap_or_else(|| "none".to_string());
@@ -250,6 +379,10 @@ impl ConversationManager {
                                 );
                             }
                         }
+                        StreamEvent::Ping => {}
+                        StreamEvent::Error { error } => {
+                            return Err(StreamError::from(error).into());
+                        }
                         StreamEvent::Unknown => {
                             if !use_structured_blocks && stream_server_events {
                                 emit_text_update(
@@ -262,10 +395,47 @@ impl ConversationManager {
                 }
             }
 
+            if stalled_before_output {
+                rounds -= 1;
+                continue;
+            }
+            self.transcript().token_usage += round.usage;
+            let assistant_text = std::mem::take(&mut round.text);
+            if let Some(cut) = runaway_cut {
+                // The rest of the stream is dropped, so calls it announced
+                // may be incomplete and are not run.
+                if use_structured_blocks {
+                    self.promote_thinking_blocks_to_final_text(
+                        &deferred_text_block_indices,
+                        stream_delta_tx,
+                    );
+                }
+                let notice = format!("[output truncated: the response {}]", cut.reason);
+                emit_text_update(
+                    stream_delta_tx,
+                    format!("\n[runaway output stopped: the response {}]", cut.reason),
+                );
+                self.transcript().api_messages.push(ApiMessage {
+                    role: "assistant".to_string(),
+                    content: Content::Text(truncate_for_history(
+                        &format!("{assistant_text}\n{notice}"),
+                        limits.max_assistant_history_chars,
+                    )),
+                });
+                self.pending_turn_note = Some(render_runaway_note(&cut.reason));
+                continued_text.push_str(&assistant_text);
+                continued_text.push('\n');
+                continued_text.push_str(&notice);
+                return Ok(continued_text);
+            }
+            let oversized_tool_inputs = round.oversized_tool_inputs();
+            let round_stop_reason = round.stop_reason.take();
+
             let mut assistant_text_for_history = assistant_text.clone();
             let mut used_tagged_fallback = false;
-            let mut tool_use_blocks: Vec<ContentBlock> =
-                tool_use_blocks.into_iter().flatten().collect();
+            // Thinking blocks are sent back ahead of the round's text and
+            // tool calls.
+            let (mut tool_use_blocks, thinking_blocks) = round.into_blocks();
             if tool_use_blocks.is_empty() && self.client.is_local_endpoint() {
                 let tagged_calls = parse_tagged_tool_calls(&assistant_text);
                 if !tagged_calls.is_empty() {
@@ -363,7 +533,7 @@ impl ConversationManager {
                 truncate_for_history(&assistant_history_text, limits.max_assistant_history_chars);
 
             if use_structured_round {
-                let mut assistant_content_blocks = Vec::new();
+                let mut assistant_content_blocks = thinking_blocks;
                 if !assistant_text_for_history.is_empty() {
                     assistant_content_blocks.push(ContentBlock::Text {
                         text: truncate_for_history(
@@ -374,12 +544,12 @@ impl ConversationManager {
                 }
                 assistant_content_blocks.extend(tool_use_blocks.clone());
 
-                self.api_messages.push(ApiMessage {
+                self.transcript().api_messages.push(ApiMessage {
                     role: "assistant".to_string(),
                     content: Content::Blocks(assistant_content_blocks),
                 });
             } else {
-                self.api_messages.push(ApiMessage {
+                self.transcript().api_messages.push(ApiMessage {
                     role: "assistant".to_string(),
                     content: Content::Text(assistant_history_text),
                 });
@@ -387,7 +557,7 @@ impl ConversationManager {
             last_assistant_text_for_history = assistant_text_for_history.clone();
 
             if inject_repeated_round_nudge {
-                self.api_messages.push(ApiMessage {
+                self.transcript().api_messages.push(ApiMessage {
                     role: "user".to_string(),
                     content: Content::Text(
                         core_policy.repeated_tool_round_instruction().to_string(),
@@ -396,6 +566,31 @@ impl ConversationManager {
                 continue;
             }
 
+            if tool_use_blocks.is_empty() && round_stop_reason.as_deref() == Some("max_tokens") {
+                if continuations < max_continuations {
+                    continuations += 1;
+                    if use_structured_blocks {
+                        self.promote_thinking_blocks_to_final_text(
+                            &deferred_text_block_indices,
+                            stream_delta_tx,
+                        );
+                    }
+                    continued_text.push_str(&assistant_text_for_history);
+                    emit_text_update(stream_delta_tx, CONTINUATION_SEAM.to_string());
+                    self.transcript().api_messages.push(ApiMessage {
+                        role: "user".to_string(),
+                        content: Content::Text(core_policy.continuation_instruction().to_string()),
+                    });
+                    continue;
+                }
+                if max_continuations > 0 {
+                    emit_text_update(
+                        stream_delta_tx,
+                        format!("\n[response stopped at the output token limit after {continuations} continuations]"),
+                    );
+                }
+            }
+
             if tool_use_blocks.is_empty() {
                 if self.client.is_local_endpoint()
                     && requires_tool_evidence
@@ -404,7 +599,7 @@ impl ConversationManager {
                     && rounds < max_tool_rounds
                 {
                     forced_tool_retry_count += 1;
-                    self.api_messages.push(ApiMessage {
+                    self.transcript().api_messages.push(ApiMessage {
                         role: "user".to_string(),
                         content: Content::Text(core_policy.tool_retry_instruction().to_string()),
                     });
@@ -422,14 +617,106 @@ impl ConversationManager {
                         stream_delta_tx,
                     );
                 }
-                return Ok(assistant_text_for_history);
+                let drift = self.client.response_language().and_then(|language| {
+                    let detected = language.detect_drift(&assistant_text_for_history)?;
+                    Some((language.clone(), detected))
+                });
+                if let Some((language, detected)) = drift {
+                    if language_retry && !language_retry_used && rounds < max_tool_rounds {
+                        language_retry_used = true;
+                        emit_text_update(
+                            stream_delta_tx,
+                            format!(
+                                "\n[reply looks like {detected}; asking for it in {}]\n",
+                                language.name
+                            ),
+                        );
+                        continued_text.clear();
+                        self.transcript().api_messages.push(ApiMessage {
+                            role: "user".to_string(),
+                            content: Content::Text(language.correction_prompt(detected)),
+                        });
+                        continue;
+                    }
+                    emit_text_update(
+                        stream_delta_tx,
+                        format!("\n[reply looks like {detected}, not {}]\n", language.name),
+                    );
+                }
+                continued_text.push_str(&assistant_text_for_history);
+                return Ok(continued_text);
+            }
+
+            if use_structured_blocks {
+                // Re-announce every queued call with its final input so the
+                // frontend can list what runs after the first approval.
+                for block in &tool_use_blocks {
+                    if let ContentBlock::ToolUse { id, .. } = block {
+                        self.set_tool_call_status(id, ToolStatus::Pending, stream_delta_tx);
+                    }
+                }
             }
 
             let mut tool_result_blocks = Vec::new();
             let mut text_protocol_tool_results = Vec::new();
+            let mut tool_failure_warnings = Vec::new();
+            let mut round_results: Vec<RoundToolResult> = Vec::new();
             for block in tool_use_blocks {
                 if let ContentBlock::ToolUse { id, name, input } = block {
-                    if let Some(clarification) = missing_mutating_location_prompt(&name, &input) {
+                    let input = with_commit_defaults(&name, input, &original_user_input, || {
+                        self.tool_operator.suggested_commit_message()
+                    });
+                    // An identical call already answered in this round is not
+                    // run again, so a repeated edit cannot apply twice.
+                    if let Some(earlier) = find_duplicate_tool_call(&round_results, &name, &input) {
+                        let note = render_duplicate_tool_call_note(&earlier.id);
+                        let is_error = earlier.is_error;
+                        let content = format!("{}\n{note}", earlier.content);
+                        if use_structured_blocks {
+                            let status = if is_error {
+                                ToolStatus::Error
+                            } else {
+                                ToolStatus::Complete
+                            };
+                            self.set_tool_call_status(&id, status, stream_delta_tx);
+                            self.push_tool_result_block(
+                                StreamBlock::ToolResult {
+                                    tool_call_id: id.clone(),
+                                    output: note,
+                                    is_error,
+                                },
+                                stream_delta_tx,
+                            );
+                        } else if stream_local_tool_events {
+                            emit_text_update(
+                                stream_delta_tx,
+                                format!("\n+ [tool_result] {name} {note}\n"),
+                            );
+                        }
+                        if use_structured_round {
+                            tool_result_blocks.push(ContentBlock::ToolResult {
+                                tool_use_id: id,
+                                content,
+                                is_error,
+                            });
+                        } else {
+                            let kind = if is_error {
+                                "tool_error"
+                            } else {
+                                "tool_result"
+                            };
+                            text_protocol_tool_results.push(format!("{kind} {name}:\n{content}"));
+                        }
+                        continue;
+                    }
+                    let rejection = oversized_tool_input_error(
+                        &name,
+                        &input,
+                        oversized_tool_inputs.get(&id).copied(),
+                        tool_input_limits,
+                    )
+                    .or_else(|| missing_mutating_location_prompt(&name, &input));
+                    if let Some(clarification) = rejection {
                         if use_structured_blocks {
                             self.set_tool_call_status(&id, ToolStatus::Cancelled, stream_delta_tx);
                             self.push_tool_result_block(
@@ -507,8 +794,25 @@ impl ConversationManager {
                         continue;
                     }
 
-                    let tool_requires_approval =
-                        require_tool_approval || tool_requires_confirmation(&name);
+                    let approval_tier = self.tool_approval_tier(&name, &input);
+                    let policy_requires_approval = require_tool_approval
+                        || tool_requires_confirmation(&name)
+                        || self.custom_tool_requires_approval(&name);
+                    // Asking the user is its own prompt.
+                    let tool_requires_approval = name != ASK_USER_TOOL
+                        && (approval_tier.is_sensitive()
+                            || (policy_requires_approval
+                                && !self.approval_policy.session_auto_approve()));
+                    let approval_source =
+                        if tool_requires_approval && self.approval_handler.is_some() {
+                            ApprovalSource::Handler
+                        } else if tool_requires_approval && stream_delta_tx.is_some() {
+                            ApprovalSource::Manual
+                        } else if policy_requires_approval || tool_requires_approval {
+                            ApprovalSource::Auto
+                        } else {
+                            ApprovalSource::Rule
+                        };
 
                     if use_structured_blocks && tool_requires_approval {
                         self.set_tool_call_status(
@@ -517,12 +821,19 @@ impl ConversationManager {
                             stream_delta_tx,
                         );
                     }
-                    let approved = if tool_requires_approval {
-                        self.request_tool_approval(&name, &input, stream_delta_tx)
-                            .await
+                    let decision = if tool_requires_approval {
+                        self.request_tool_approval(
+                            Some(&id),
+                            &name,
+                            &input,
+                            approval_tier,
+                            stream_delta_tx,
+                        )
+                        .await
                     } else {
-                        true
+                        ToolApprovalDecision::Approved
                     };
+                    let approved = decision.is_approved();
 
                     if use_structured_blocks {
                         if approved {
@@ -533,7 +844,7 @@ impl ConversationManager {
                     }
 
                     if !approved {
-                        let denial = render_tool_denied_message(&name);
+                        let denial = render_tool_denied_message(&name, decision.reason());
                         if use_structured_blocks {
                             self.push_tool_result_block(
                                 StreamBlock::ToolResult {
@@ -552,6 +863,14 @@ impl ConversationManager {
                         emit_text_update(stream_delta_tx, denial.clone());
                         let history_content =
                             truncate_for_history(&denial, limits.max_tool_result_history_chars);
+                        round_results.push(RoundToolResult {
+                            id: id.clone(),
+                            name: name.clone(),
+                            input: input.clone(),
+                            content: history_content.clone(),
+                            is_error: true,
+                            mutated: false,
+                        });
                         if use_structured_round {
                             tool_result_blocks.push(ContentBlock::ToolResult {
                                 tool_use_id: id,
@@ -568,9 +887,68 @@ impl ConversationManager {
                         continue;
                     }
 
-                    let result = self
-                        .execute_tool_with_timeout(&name, &input, tool_timeout)
-                        .await;
+                    let change_before = self.change_snapshot(&name, &input);
+                    let started = Instant::now();
+                    self.events.publish(RuntimeEvent::ToolStarted {
+                        id: id.clone(),
+                        name: name.clone(),
+                    });
+                    let mut result = if name == ASK_USER_TOOL {
+                        self.ask_user(&id, &input, stream_delta_tx).await
+                    } else {
+                        self.execute_tool_with_timeout(&name, &input, tool_timeout)
+                            .await
+                    };
+                    if retry_transient_tool_errors {
+                        if let Err(error) = &result {
+                            if is_transient_tool_error(&name, error) {
+                                tokio::time::sleep(TOOL_RETRY_DELAY).await;
+                                result = self
+                                    .execute_tool_with_timeout(&name, &input, tool_timeout)
+                                    .await
+                                    .map_err(|error| {
+                                        anyhow::anyhow!(
+                                            "{error:#} (retried once after a transient failure)"
+                                        )
+                                    });
+                            }
+                        }
+                    }
+                    if result.is_ok() {
+                        self.record_turn_change(&name, &input, change_before);
+                    }
+                    self.events.publish(RuntimeEvent::ToolFinished {
+                        id: id.clone(),
+                        name: name.clone(),
+                        duration: started.elapsed(),
+                        error: result.as_ref().err().map(|error| {
+                            error
+                                .to_string()
+                                .lines()
+                                .next()
+                                .unwrap_or_default()
+                                .to_string()
+                        }),
+                    });
+                    if let Some(warning) = self.record_tool_audit(
+                        &name,
+                        &input,
+                        approval_source,
+                        started.elapsed(),
+                        &result,
+                    ) {
+                        emit_text_update(stream_delta_tx, format!("\n{warning}\n"));
+                    }
+                    if let Some(warning) =
+                        record_tool_failure_streak(&mut tool_failure_streaks, &name, result.is_ok())
+                    {
+                        emit_text_update(stream_delta_tx, format!("\n{warning}\n"));
+                        tool_failure_warnings.push(warning);
+                    }
+                    for warning in self.tool_operator.take_lock_contentions() {
+                        emit_text_update(stream_delta_tx, format!("\n{warning}\n"));
+                        tool_failure_warnings.push(warning);
+                    }
                     if use_structured_blocks {
                         let final_status = if result.is_err() {
                             ToolStatus::Error
@@ -607,10 +985,20 @@ impl ConversationManager {
                         }
                     }
 
-                    let history_content = truncate_for_history(
-                        &self.format_tool_result_for_history(&name, &input, &result),
-                        limits.max_tool_result_history_chars,
+                    let budget =
+                        self.tool_output_budget(&name, limits.max_tool_result_history_chars);
+                    let history_content = sample_head_tail(
+                        &self.format_tool_result_for_history(&name, &id, &input, &result),
+                        budget,
                     );
+                    round_results.push(RoundToolResult {
+                        id: id.clone(),
+                        name: name.clone(),
+                        input: input.clone(),
+                        content: history_content.clone(),
+                        is_error: result.is_err(),
+                        mutated: !is_read_only_tool_name(&name),
+                    });
                     if use_structured_round {
                         tool_result_blocks.push(ContentBlock::ToolResult {
                             tool_use_id: id,
@@ -622,21 +1010,24 @@ impl ConversationManager {
                             |_| format!("tool_error {name}:\n{history_content}"),
                             |_| format!("tool_result {name}:\n{history_content}"),
                         );
-                        text_protocol_tool_results.push(truncate_for_history(
-                            &rendered,
-                            limits.max_tool_result_history_chars,
-                        ));
+                        text_protocol_tool_results.push(sample_head_tail(&rendered, budget));
                     }
                 }
             }
 
             if use_structured_round {
-                self.api_messages.push(ApiMessage {
+                tool_result_blocks.extend(
+                    tool_failure_warnings
+                        .into_iter()
+                        .map(|text| ContentBlock::Text { text }),
+                );
+                self.transcript().api_messages.push(ApiMessage {
                     role: "user".to_string(),
                     content: Content::Blocks(tool_result_blocks),
                 });
             } else {
-                self.api_messages.push(ApiMessage {
+                text_protocol_tool_results.extend(tool_failure_warnings);
+                self.transcript().api_messages.push(ApiMessage {
                     role: "user".to_string(),
                     content: Content::Text(text_protocol_tool_results.join("\n\n")),
                 });
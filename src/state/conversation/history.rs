@@ -1,8 +1,11 @@
+use super::context_budget::{count_text_tokens, message_tokens};
 use super::ConversationManager;
 use crate::tool_preview::{
-    format_read_file_snapshot_message, read_file_path, ReadFileSnapshotSummary,
+    format_read_file_snapshot_message, read_file_snapshot_key, ReadFileSnapshotSummary,
     ReadFileSummaryMessageStyle,
 };
+use crate::tools::render_memory_entries;
+use crate::turn_overrides::TurnOverrides;
 use crate::types::{ApiMessage, Content, ContentBlock};
 use anyhow::Result;
 use std::time::Duration;
@@ -15,6 +18,46 @@ const REMOTE_DEFAULT_MAX_ASSISTANT_HISTORY_CHARS: usize = 3_000;
 const REMOTE_DEFAULT_MAX_TOOL_RESULT_HISTORY_CHARS: usize = 6_000;
 const REMOTE_DEFAULT_MAX_API_MESSAGES: usize = 32;
 const REMOTE_DEFAULT_TOOL_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_MEMORY_RECALL_LIMIT: usize = 3;
+const DEFAULT_MAX_TOOL_INPUT_BYTES: usize = 8 * 1024 * 1024;
+const DEFAULT_MAX_TOOL_ARG_BYTES: usize = 2 * 1024 * 1024;
+const DEFAULT_RUNAWAY_BLOCK_BYTES: usize = 256 * 1024;
+const DEFAULT_MAX_CONTINUATIONS: usize = 2;
+/// `name=chars[:tail%]` entries, comma separated, e.g. `run_tests=12000:80`.
+const TOOL_BUDGETS_ENV: &str = "VEX_TOOL_BUDGETS";
+/// Share of a cut tool result kept from the end. Build and test logs end
+/// with the failures and the summary, so they keep more of their tail.
+const DEFAULT_TAIL_PERCENT: usize = 50;
+const LOG_TAIL_PERCENT: usize = 75;
+/// A token count for every model, or `prefix=tokens` entries for models
+/// whose names start with `prefix`, comma separated; `0` turns token
+/// budgeting off.
+const CONTEXT_TOKENS_ENV: &str = "VEX_CONTEXT_TOKENS";
+/// Context windows by model name prefix; the longest match wins.
+const MODEL_CONTEXT_TOKENS: &[(&str, usize)] = &[
+    ("claude-", 200_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-5", 400_000),
+];
+const LOCAL_DEFAULT_CONTEXT_TOKENS: usize = 32_768;
+const REMOTE_DEFAULT_CONTEXT_TOKENS: usize = 128_000;
+const MIN_CONTEXT_TOKENS: usize = 1_024;
+/// Share of the window a request may fill, leaving room for the error of
+/// the token estimate.
+const CONTEXT_FILL_PERCENT: usize = 90;
+/// Tool results at most this long are left whole when history is shrunk.
+const MIN_ELIDED_RESULT_TOKENS: usize = 64;
+const MAX_ELIDED_RESULT_HEAD_CHARS: usize = 200;
+const ELIDED_RESULT_NOTE: &str = "[result elided to fit the context window";
+
+/// History budget for one tool's results: the character cap and the share
+/// of it kept from the end when the output is cut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ToolOutputBudget {
+    pub(super) max_chars: usize,
+    pub(super) tail_percent: usize,
+}
 
 #[derive(Clone, Copy)]
 pub(super) struct HistoryLimits {
@@ -23,20 +66,195 @@ pub(super) struct HistoryLimits {
     pub(super) max_api_messages: usize,
 }
 
+/// Size caps applied to model-supplied tool input before it is buffered or
+/// executed.
+#[derive(Clone, Copy)]
+pub(super) struct ToolInputLimits {
+    /// Maximum accumulated `partial_json` bytes for one tool_use block.
+    pub(super) max_input_bytes: usize,
+    /// Maximum size of any single top-level tool argument.
+    pub(super) max_arg_bytes: usize,
+}
+
 impl ConversationManager {
+    /// Largest tool result kept in history for this endpoint; attachments
+    /// above it are sent as outlines.
+    pub fn tool_result_budget(&self) -> usize {
+        resolve_history_limits(self.client.is_local_endpoint()).max_tool_result_history_chars
+    }
+
+    /// The history budget for `name`'s results, starting from the endpoint's
+    /// `default_chars` and applying any `VEX_TOOL_BUDGETS` entry for it.
+    /// `run_tests` and custom tools, which mostly print logs, keep more of
+    /// their tail.
+    pub(super) fn tool_output_budget(&self, name: &str, default_chars: usize) -> ToolOutputBudget {
+        let log_like = name == "run_tests" || self.tool_operator.custom_tools().get(name).is_some();
+        let budget = ToolOutputBudget {
+            max_chars: default_chars,
+            tail_percent: if log_like {
+                LOG_TAIL_PERCENT
+            } else {
+                DEFAULT_TAIL_PERCENT
+            },
+        };
+        match std::env::var(TOOL_BUDGETS_ENV) {
+            Ok(spec) => apply_tool_budget_spec(budget, name, &spec),
+            Err(_) => budget,
+        }
+    }
+
+    /// Appends the top-k remembered project facts matching `content` so the
+    /// model sees them without an explicit `recall` round.
+    pub(super) fn with_recalled_memories(&self, content: String) -> String {
+        let limit = resolve_memory_recall_limit();
+        if limit == 0 {
+            return content;
+        }
+        let entries = match self.tool_operator.memory_store().recall(&content, limit) {
+            Ok(entries) if !entries.is_empty() => entries,
+            _ => return content,
+        };
+        format!(
+            "{content}\n\n[Project memories that may be relevant]\n{}",
+            render_memory_entries(&entries)
+        )
+    }
+
+    /// Tokens the history may take in the next request: the model's context
+    /// window less a safety margin, the reply's max_tokens, and the system
+    /// prompt and tool schemas. `overrides` can change the model and
+    /// max_tokens for the turn. `None` when token budgeting is off.
+    pub(super) fn history_token_budget(&self, overrides: &TurnOverrides) -> Option<usize> {
+        let model = overrides.model.as_deref().unwrap_or(self.client.model());
+        let window = resolve_context_tokens(model, self.client.is_local_endpoint());
+        if window == 0 {
+            return None;
+        }
+        let max_tokens = overrides.max_tokens.unwrap_or(self.client.max_tokens()) as usize;
+        let (system_prompt, tool_schemas) = self.client.request_overhead_text();
+        let reserved =
+            max_tokens + count_text_tokens(&system_prompt) + count_text_tokens(&tool_schemas);
+        Some((window * CONTEXT_FILL_PERCENT / 100).saturating_sub(reserved))
+    }
+
+    /// Shrinks the history to `budget_tokens`, oldest content first: tool
+    /// results of earlier turns are cut down to their first line, then whole
+    /// earlier exchanges are dropped. Messages from `preserve_index` on, the
+    /// current turn, are never touched. Returns the new `preserve_index`.
+    pub(super) fn fit_history_to_token_budget(
+        &mut self,
+        budget_tokens: usize,
+        preserve_index: usize,
+    ) -> usize {
+        let mut transcript = self.transcript();
+        let messages = &mut transcript.api_messages;
+        let preserve_index = preserve_index.min(messages.len());
+        let mut sizes: Vec<usize> = messages.iter().map(message_tokens).collect();
+        let mut total: usize = sizes.iter().sum();
+        if total <= budget_tokens {
+            return preserve_index;
+        }
+
+        let mut removed_ids = Vec::new();
+        for index in 0..preserve_index {
+            if total <= budget_tokens {
+                break;
+            }
+            if elide_tool_results(&mut messages[index], &mut removed_ids) {
+                let size = message_tokens(&messages[index]);
+                total = total - sizes[index] + size;
+                sizes[index] = size;
+            }
+        }
+
+        // History must still begin with a user prompt, so each drop takes
+        // an exchange through to the next prompt.
+        let mut keep_start = 0;
+        while total > budget_tokens && keep_start < preserve_index {
+            total -= sizes[keep_start];
+            keep_start += 1;
+            while keep_start < preserve_index {
+                let message = &messages[keep_start];
+                if message.role == "user" && !message_contains_tool_result(message) {
+                    break;
+                }
+                total -= sizes[keep_start];
+                keep_start += 1;
+            }
+        }
+        removed_ids.extend(tool_result_ids(&messages[..keep_start]));
+        let read_keys = read_file_keys(messages, &removed_ids);
+        if keep_start > 0 {
+            messages.drain(0..keep_start);
+        }
+        drop(transcript);
+        self.forget_removed_results(&removed_ids, &read_keys);
+        preserve_index - keep_start
+    }
+
+    /// The local estimate runs low on code and non-Latin text. Once the
+    /// history nears `budget_tokens`, where that matters, asks a backend that
+    /// counts exactly for the real size of the request and, when it is
+    /// larger than estimated, refits to the budget scaled down by the same
+    /// ratio. Keeps the estimate when counting fails.
+    pub(super) async fn refit_history_to_counted_tokens(
+        &mut self,
+        budget_tokens: usize,
+        preserve_index: usize,
+    ) -> usize {
+        if !self.client.counts_tokens_exactly() {
+            return preserve_index;
+        }
+        let history: usize = self
+            .transcript()
+            .api_messages
+            .iter()
+            .map(message_tokens)
+            .sum();
+        if history < budget_tokens * 3 / 4 {
+            return preserve_index;
+        }
+        let (system_prompt, tool_schemas) = self.client.request_overhead_text();
+        let estimated =
+            history + count_text_tokens(&system_prompt) + count_text_tokens(&tool_schemas);
+        let messages = self.messages_for_api();
+        let Ok(counted) = self.client.count_tokens(&messages).await else {
+            return preserve_index;
+        };
+        let counted = usize::try_from(counted).unwrap_or(usize::MAX);
+        if counted <= estimated {
+            return preserve_index;
+        }
+        let scaled = budget_tokens.saturating_mul(estimated) / counted;
+        self.fit_history_to_token_budget(scaled, preserve_index)
+    }
+
+    /// Forgets what left the history: read_file snapshots whose content the
+    /// model no longer sees, so a re-read sends the file again instead of
+    /// "unchanged" or a diff, and stored results a later duplicate would
+    /// otherwise be referenced against.
+    fn forget_removed_results(&mut self, tool_use_ids: &[String], read_keys: &[String]) {
+        for key in read_keys {
+            self.read_file_history_cache.forget(key);
+        }
+        self.tool_operator.forget_results(tool_use_ids);
+    }
+
     #[cfg(test)]
     pub(super) fn prune_message_history(&mut self, max_api_messages: usize) {
-        if self.api_messages.len() <= max_api_messages {
+        let mut transcript = self.transcript();
+        let messages = &mut transcript.api_messages;
+        if messages.len() <= max_api_messages {
             return;
         }
 
-        let len = self.api_messages.len();
+        let len = messages.len();
         let mut keep_start = len.saturating_sub(max_api_messages);
 
         // Anthropic requires history to begin with a user message.
         // Additionally, a leading user tool_result is invalid without its preceding assistant tool_use.
         while keep_start < len {
-            let message = &self.api_messages[keep_start];
+            let message = &messages[keep_start];
             if message.role == "user" && !message_contains_tool_result(message) {
                 break;
             }
@@ -44,12 +262,12 @@ impl ConversationManager {
         }
 
         if keep_start >= len {
-            self.api_messages.clear();
+            messages.clear();
             return;
         }
 
         if keep_start > 0 {
-            self.api_messages.drain(0..keep_start);
+            messages.drain(0..keep_start);
         }
     }
 
@@ -58,14 +276,16 @@ impl ConversationManager {
         max_api_messages: usize,
         preserve_index: usize,
     ) -> usize {
-        if self.api_messages.is_empty() {
+        let mut transcript = self.transcript();
+        let messages = &mut transcript.api_messages;
+        if messages.is_empty() {
             return 0;
         }
-        if self.api_messages.len() <= max_api_messages {
-            return preserve_index.min(self.api_messages.len().saturating_sub(1));
+        if messages.len() <= max_api_messages {
+            return preserve_index.min(messages.len().saturating_sub(1));
         }
 
-        let len = self.api_messages.len();
+        let len = messages.len();
         let target_keep_start = len.saturating_sub(max_api_messages);
         let preserve_distance = target_keep_start.saturating_sub(preserve_index);
         let keep_preserve_anchor = preserve_index < target_keep_start && preserve_distance <= 2;
@@ -79,29 +299,34 @@ impl ConversationManager {
             if keep_preserve_anchor && keep_start == preserve_index {
                 break;
             }
-            let message = &self.api_messages[keep_start];
+            let message = &messages[keep_start];
             if message.role == "user" && !message_contains_tool_result(message) {
                 break;
             }
             keep_start += 1;
         }
 
-        if keep_start >= len {
-            self.api_messages.clear();
-            return 0;
-        }
-
-        if keep_start > 0 {
-            self.api_messages.drain(0..keep_start);
+        let keep_start = keep_start.min(len);
+        let removed_ids: Vec<String> = tool_result_ids(&messages[..keep_start]).collect();
+        let read_keys = read_file_keys(messages, &removed_ids);
+        let preserve_index = if keep_start >= len {
+            messages.clear();
+            0
+        } else if keep_start > 0 {
+            messages.drain(0..keep_start);
             preserve_index.saturating_sub(keep_start)
         } else {
             preserve_index
-        }
+        };
+        drop(transcript);
+        self.forget_removed_results(&removed_ids, &read_keys);
+        preserve_index
     }
 
     pub(super) fn format_tool_result_for_history(
         &mut self,
         name: &str,
+        tool_use_id: &str,
         input: &serde_json::Value,
         result: &Result<String>,
     ) -> String {
@@ -113,14 +338,33 @@ impl ConversationManager {
         };
 
         if name == "read_file" {
-            // read_file_path returns None if the "path" key is absent or non-string.
+            // read_file_snapshot_key returns None if the "path" key is absent or non-string.
             // The fallback "<missing>" is a display-layer decision kept here, not baked into the helper.
-            let path = read_file_path(input).unwrap_or_else(|| "<missing>".to_string());
-            let summary = self.read_file_history_cache.summarize(&path, output);
+            let path = read_file_snapshot_key(input).unwrap_or_else(|| "<missing>".to_string());
+            let (summary, mini_diff) = self
+                .read_file_history_cache
+                .summarize_with_diff(&path, output);
+            if let (
+                ReadFileSnapshotSummary::Changed {
+                    delta: Some(delta), ..
+                },
+                Some(diff),
+            ) = (summary, mini_diff)
+            {
+                return format!(
+                    "Read {path}: {} since the last read. Full content omitted; diff against the previous read:\n{diff}",
+                    delta.describe()
+                );
+            }
             return self.format_read_file_result_for_model_context(&path, output, summary);
         }
+        if name == "expand_tool_result" {
+            return output.clone();
+        }
 
-        output.clone()
+        self.tool_operator
+            .reference_duplicate_result(name, tool_use_id, output)
+            .unwrap_or_else(|| output.clone())
     }
 
     pub(super) fn format_read_file_result_for_model_context(
@@ -145,8 +389,10 @@ impl ConversationManager {
                         before_lines,
                         after_chars,
                         after_lines,
+                        delta,
                     } => format!(
-                        "Read {path}: content changed ({before_chars} chars/{before_lines} lines -> {after_chars} chars/{after_lines} lines). Snapshot included below for model context."
+                        "Read {path}: content changed ({before_chars} chars/{before_lines} lines -> {after_chars} chars/{after_lines} lines{}). Snapshot included below for model context.",
+                        delta.map(|delta| format!(", {}", delta.describe())).unwrap_or_default()
                     ),
                     ReadFileSnapshotSummary::Unchanged { .. } => unreachable!(),
                 };
@@ -167,6 +413,136 @@ pub(super) fn message_contains_tool_result(message: &ApiMessage) -> bool {
     }
 }
 
+/// Cuts each long tool result in `message` to its first line and a note of
+/// its size. Returns whether anything changed.
+fn elide_tool_results(message: &mut ApiMessage, elided_ids: &mut Vec<String>) -> bool {
+    let Content::Blocks(blocks) = &mut message.content else {
+        return false;
+    };
+    let mut changed = false;
+    for block in blocks {
+        let ContentBlock::ToolResult {
+            tool_use_id,
+            content,
+            ..
+        } = block
+        else {
+            continue;
+        };
+        if content.contains(ELIDED_RESULT_NOTE) {
+            continue;
+        }
+        let tokens = count_text_tokens(content);
+        if tokens <= MIN_ELIDED_RESULT_TOKENS {
+            continue;
+        }
+        let head: String = content
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .take(MAX_ELIDED_RESULT_HEAD_CHARS)
+            .collect();
+        *content = format!("{head}\n{ELIDED_RESULT_NOTE}; it was about {tokens} tokens]");
+        elided_ids.push(tool_use_id.clone());
+        changed = true;
+    }
+    changed
+}
+
+fn tool_result_ids(messages: &[ApiMessage]) -> impl Iterator<Item = String> + '_ {
+    messages.iter().flat_map(|message| match &message.content {
+        Content::Blocks(blocks) => blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolResult { tool_use_id, .. } => Some(tool_use_id.clone()),
+                _ => None,
+            })
+            .collect(),
+        Content::Text(_) => Vec::new(),
+    })
+}
+
+/// Snapshot keys of the read_file calls in `messages` whose results are
+/// among `tool_use_ids`.
+fn read_file_keys(messages: &[ApiMessage], tool_use_ids: &[String]) -> Vec<String> {
+    if tool_use_ids.is_empty() {
+        return Vec::new();
+    }
+    messages
+        .iter()
+        .filter_map(|message| match &message.content {
+            Content::Blocks(blocks) => Some(blocks),
+            Content::Text(_) => None,
+        })
+        .flatten()
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, name, input }
+                if name == "read_file" && tool_use_ids.contains(id) =>
+            {
+                read_file_snapshot_key(input)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The context window of `model` in tokens, from [`MODEL_CONTEXT_TOKENS`]
+/// or the endpoint's default, with any `VEX_CONTEXT_TOKENS` setting
+/// applied. `0` means token budgeting is off.
+pub(super) fn resolve_context_tokens(model: &str, is_local_endpoint: bool) -> usize {
+    let default = MODEL_CONTEXT_TOKENS
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(
+            if is_local_endpoint {
+                LOCAL_DEFAULT_CONTEXT_TOKENS
+            } else {
+                REMOTE_DEFAULT_CONTEXT_TOKENS
+            },
+            |(_, tokens)| *tokens,
+        );
+    match std::env::var(CONTEXT_TOKENS_ENV) {
+        Ok(spec) => apply_context_tokens_spec(default, model, &spec),
+        Err(_) => default,
+    }
+}
+
+/// `default` with the `spec` entry for `model` applied: the longest
+/// matching `prefix=tokens` entry, else a bare token count. Malformed
+/// entries are ignored.
+pub(super) fn apply_context_tokens_spec(default: usize, model: &str, spec: &str) -> usize {
+    let mut window = None;
+    let mut matched_prefix: Option<usize> = None;
+    for entry in spec.split(',') {
+        let (prefix, tokens) = match entry.split_once('=') {
+            Some((prefix, tokens)) => (Some(prefix.trim()), tokens),
+            None => (None, entry),
+        };
+        let Ok(tokens) = tokens.trim().parse::<usize>() else {
+            continue;
+        };
+        match prefix {
+            Some(prefix)
+                if model.starts_with(prefix)
+                    && matched_prefix.is_none_or(|len| prefix.len() > len) =>
+            {
+                matched_prefix = Some(prefix.len());
+                window = Some(tokens);
+            }
+            Some(_) => {}
+            None if matched_prefix.is_none() => window = Some(tokens),
+            None => {}
+        }
+    }
+    match window {
+        Some(0) => 0,
+        Some(tokens) => tokens.max(MIN_CONTEXT_TOKENS),
+        None => default,
+    }
+}
+
 pub(super) fn resolve_history_limits(is_local_endpoint: bool) -> HistoryLimits {
     let defaults = if is_local_endpoint {
         HistoryLimits {
@@ -228,6 +604,43 @@ pub(super) fn resolve_max_tool_rounds(is_local_endpoint: bool) -> usize {
         .clamp(2, 64)
 }
 
+/// Follow-up requests allowed when a response stops at `max_tokens`
+/// (`VEX_MAX_CONTINUATIONS`, default 2, `0` disables).
+pub(super) fn resolve_max_continuations() -> usize {
+    env_override_usize("VEX_MAX_CONTINUATIONS", DEFAULT_MAX_CONTINUATIONS, 0, 16)
+}
+
+pub(super) fn resolve_tool_input_limits() -> ToolInputLimits {
+    ToolInputLimits {
+        max_input_bytes: env_override_usize(
+            "VEX_MAX_TOOL_INPUT_BYTES",
+            DEFAULT_MAX_TOOL_INPUT_BYTES,
+            1_024,
+            256 * 1024 * 1024,
+        ),
+        max_arg_bytes: env_override_usize(
+            "VEX_MAX_TOOL_ARG_BYTES",
+            DEFAULT_MAX_TOOL_ARG_BYTES,
+            1_024,
+            256 * 1024 * 1024,
+        ),
+    }
+}
+
+/// Per-block byte budget for streamed text; 0 turns runaway detection off.
+pub(super) fn resolve_runaway_block_bytes() -> usize {
+    env_override_usize(
+        "VEX_RUNAWAY_BLOCK_BYTES",
+        DEFAULT_RUNAWAY_BLOCK_BYTES,
+        0,
+        64 * 1024 * 1024,
+    )
+}
+
+pub(super) fn resolve_memory_recall_limit() -> usize {
+    env_override_usize("VEX_MEMORY_RECALL_K", DEFAULT_MEMORY_RECALL_LIMIT, 0, 20)
+}
+
 pub(super) fn env_override_usize(key: &str, default: usize, min: usize, max: usize) -> usize {
     std::env::var(key)
         .ok()
@@ -264,3 +677,92 @@ pub(super) fn truncate_for_history(text: &str, max_chars: usize) -> String {
     let tail: String = chars.iter().skip(total.saturating_sub(keep_tail)).collect();
     format!("{head}{indicator}{tail}")
 }
+
+/// `budget` with the `spec` entry for `name` applied; malformed values are
+/// ignored.
+pub(super) fn apply_tool_budget_spec(
+    mut budget: ToolOutputBudget,
+    name: &str,
+    spec: &str,
+) -> ToolOutputBudget {
+    for entry in spec.split(',') {
+        let Some((tool, value)) = entry.split_once('=') else {
+            continue;
+        };
+        if tool.trim() != name {
+            continue;
+        }
+        let (chars, tail) = match value.split_once(':') {
+            Some((chars, tail)) => (chars, Some(tail)),
+            None => (value, None),
+        };
+        if let Ok(chars) = chars.trim().parse::<usize>() {
+            budget.max_chars = chars.clamp(200, 40_000);
+        }
+        if let Some(Ok(tail)) = tail.map(|tail| tail.trim().trim_end_matches('%').parse::<usize>())
+        {
+            budget.tail_percent = tail.min(100);
+        }
+    }
+    budget
+}
+
+/// Cuts `text` to the budget by whole lines: the first lines up to the head
+/// share, the last lines up to the tail share (plus whatever the head left
+/// unused), and a marker counting what was elided between them. Text whose
+/// lines are too long to sample falls back to [`truncate_for_history`].
+pub(super) fn sample_head_tail(text: &str, budget: ToolOutputBudget) -> String {
+    let total = text.chars().count();
+    if total <= budget.max_chars {
+        return text.to_string();
+    }
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    // Room for the marker at its widest.
+    let marker_chars = format!("...[{} lines, {total} chars elided]...\n", lines.len())
+        .chars()
+        .count()
+        + 1;
+    if marker_chars * 2 >= budget.max_chars {
+        return truncate_for_history(text, budget.max_chars);
+    }
+    let available = budget.max_chars - marker_chars;
+    let tail_budget = available * budget.tail_percent.min(100) / 100;
+    let head_budget = available - tail_budget;
+
+    let mut head_end = 0;
+    let mut head_chars = 0;
+    for line in &lines {
+        let chars = line.chars().count();
+        if head_chars + chars > head_budget {
+            break;
+        }
+        head_chars += chars;
+        head_end += 1;
+    }
+    let tail_budget = available - head_chars;
+    let mut tail_start = lines.len();
+    let mut tail_chars = 0;
+    while tail_start > head_end {
+        let chars = lines[tail_start - 1].chars().count();
+        if tail_chars + chars > tail_budget {
+            break;
+        }
+        tail_chars += chars;
+        tail_start -= 1;
+    }
+    if head_end == 0 && tail_start == lines.len() {
+        return truncate_for_history(text, budget.max_chars);
+    }
+
+    let mut sampled = lines[..head_end].concat();
+    if !sampled.is_empty() && !sampled.ends_with('\n') {
+        sampled.push('\n');
+    }
+    sampled.push_str(&format!(
+        "...[{} lines, {} chars elided]...\n",
+        tail_start - head_end,
+        total - head_chars - tail_chars
+    ));
+    sampled.push_str(&lines[tail_start..].concat());
+    sampled
+}
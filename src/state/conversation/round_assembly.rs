@@ -0,0 +1,211 @@
+use super::history::resolve_tool_input_limits;
+use crate::types::{ContentBlock, Delta, MessageDelta, TokenUsage};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+/// What [`RoundAssembly::tool_input_delta`] did with a piece of tool input.
+#[derive(Debug)]
+pub enum ToolInputDelta<'a> {
+    /// Appended; the input received so far.
+    Appended(&'a str),
+    /// The input is over the size limit and is no longer kept.
+    Oversized,
+    /// The block is not a tool_use.
+    Dropped,
+}
+
+/// One streamed response put together from its events: the text, thinking
+/// blocks, tool calls with their streamed input, usage, and stop reason.
+/// The conversation loop and `vex debug-parse` both assemble rounds with it.
+#[derive(Debug)]
+pub struct RoundAssembly {
+    /// Text appended so far, after any deduplication by the caller.
+    pub text: String,
+    tool_uses: Vec<Option<ContentBlock>>,
+    tool_inputs: Vec<Option<String>>,
+    thinking: BTreeMap<usize, ContentBlock>,
+    /// Tool blocks whose input overflowed the limit, with the bytes received.
+    oversized_inputs: BTreeMap<usize, usize>,
+    max_input_bytes: usize,
+    pub usage: TokenUsage,
+    pub stop_reason: Option<String>,
+}
+
+impl RoundAssembly {
+    pub fn new(max_input_bytes: usize) -> Self {
+        Self {
+            text: String::new(),
+            tool_uses: Vec::new(),
+            tool_inputs: Vec::new(),
+            thinking: BTreeMap::new(),
+            oversized_inputs: BTreeMap::new(),
+            max_input_bytes,
+            usage: TokenUsage::default(),
+            stop_reason: None,
+        }
+    }
+
+    /// With the tool input limit the conversation loop uses
+    /// (`VEX_MAX_TOOL_INPUT_BYTES`).
+    pub fn from_env() -> Self {
+        Self::new(resolve_tool_input_limits().max_input_bytes)
+    }
+
+    pub fn message_start(&mut self, usage: Option<TokenUsage>) {
+        if let Some(usage) = usage {
+            self.usage = usage;
+        }
+    }
+
+    pub fn start_block(&mut self, index: usize, block: &ContentBlock) {
+        match block {
+            ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => {
+                self.thinking.insert(index, block.clone());
+            }
+            ContentBlock::ToolUse { .. } => {
+                if self.tool_uses.len() <= index {
+                    self.tool_uses.resize(index + 1, None);
+                    self.tool_inputs.resize(index + 1, None);
+                }
+                self.tool_uses[index] = Some(block.clone());
+                self.tool_inputs[index] = Some(String::new());
+            }
+            _ => {}
+        }
+    }
+
+    /// Extends a thinking block with the delta's thinking text and signature.
+    pub fn thinking_delta(&mut self, index: usize, delta: &Delta) {
+        if let Some(ContentBlock::Thinking {
+            thinking,
+            signature,
+        }) = self.thinking.get_mut(&index)
+        {
+            if let Some(text) = &delta.thinking {
+                thinking.push_str(text);
+            }
+            if let Some(text) = &delta.signature {
+                signature.push_str(text);
+            }
+        }
+    }
+
+    /// Adds streamed input to tool block `index`. Input past the size limit
+    /// is counted but not kept.
+    pub fn tool_input_delta(&mut self, index: usize, partial_json: &str) -> ToolInputDelta<'_> {
+        if let Some(received) = self.oversized_inputs.get_mut(&index) {
+            *received += partial_json.len();
+            return ToolInputDelta::Oversized;
+        }
+        let Some(Some(buffer)) = self.tool_inputs.get_mut(index) else {
+            return ToolInputDelta::Dropped;
+        };
+        if buffer.len() + partial_json.len() > self.max_input_bytes {
+            self.oversized_inputs
+                .insert(index, buffer.len() + partial_json.len());
+            *buffer = String::new();
+            return ToolInputDelta::Oversized;
+        }
+        buffer.push_str(partial_json);
+        ToolInputDelta::Appended(buffer)
+    }
+
+    /// Parses tool block `index`'s streamed input into its call. `None`
+    /// when the block is not a tool_use or streamed no input; on an error
+    /// the input from the block's start is kept.
+    pub fn stop_block(&mut self, index: usize) -> Option<Result<&Value, serde_json::Error>> {
+        let json = self.tool_inputs.get(index)?.as_deref()?;
+        let Some(Some(ContentBlock::ToolUse { input, .. })) = self.tool_uses.get_mut(index) else {
+            return None;
+        };
+        if json.is_empty() {
+            return None;
+        }
+        match serde_json::from_str(json) {
+            Ok(parsed) => {
+                *input = parsed;
+                Some(Ok(input))
+            }
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    /// message_delta carries the cumulative output count.
+    pub fn message_delta(&mut self, delta: &MessageDelta, usage: Option<&TokenUsage>) {
+        if delta.stop_reason.is_some() {
+            self.stop_reason = delta.stop_reason.clone();
+        }
+        if let Some(usage) = usage {
+            self.usage.output_tokens = usage.output_tokens;
+            if usage.input_tokens > 0 {
+                self.usage.input_tokens = usage.input_tokens;
+            }
+        }
+    }
+
+    pub fn has_tool_uses(&self) -> bool {
+        self.tool_uses.iter().any(Option::is_some)
+    }
+
+    /// Ids of calls whose input overflowed the limit, with the bytes sent.
+    pub fn oversized_tool_inputs(&self) -> HashMap<String, usize> {
+        self.oversized_inputs
+            .iter()
+            .filter_map(|(index, bytes)| match self.tool_uses.get(*index) {
+                Some(Some(ContentBlock::ToolUse { id, .. })) => Some((id.clone(), *bytes)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The tool calls and the thinking blocks, each in stream order.
+    pub fn into_blocks(self) -> (Vec<ContentBlock>, Vec<ContentBlock>) {
+        (
+            self.tool_uses.into_iter().flatten().collect(),
+            self.thinking.into_values().collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_input_is_parsed_at_stop_and_capped() {
+        let mut round = RoundAssembly::new(16);
+        let call = |id: &str| ContentBlock::ToolUse {
+            id: id.to_string(),
+            name: "read_file".to_string(),
+            input: serde_json::json!({}),
+        };
+        round.start_block(1, &call("t1"));
+        round.start_block(2, &call("t2"));
+        assert!(matches!(
+            round.tool_input_delta(1, r#"{"path":"#),
+            ToolInputDelta::Appended(r#"{"path":"#)
+        ));
+        round.tool_input_delta(1, r#""a.rs"}"#);
+        assert!(matches!(
+            round.tool_input_delta(2, r#"{"path":"a-very-long-name.rs"}"#),
+            ToolInputDelta::Oversized
+        ));
+        assert!(matches!(
+            round.tool_input_delta(0, "{}"),
+            ToolInputDelta::Dropped
+        ));
+
+        assert_eq!(
+            round.stop_block(1).map(|input| input.ok().cloned()),
+            Some(Some(serde_json::json!({"path": "a.rs"})))
+        );
+        assert!(round.stop_block(2).is_none());
+        assert_eq!(
+            round.oversized_tool_inputs(),
+            HashMap::from([("t2".to_string(), 30)])
+        );
+        let (tool_uses, thinking) = round.into_blocks();
+        assert_eq!(tool_uses.len(), 2);
+        assert!(thinking.is_empty());
+    }
+}
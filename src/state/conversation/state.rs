@@ -1,57 +1,425 @@
-use super::super::stream_block::StreamBlock;
+use super::super::secret_guard::{redact_text, SecretGuard};
+use super::super::sensitive_paths::SensitivePaths;
+use super::super::session_store::{SavedSession, SAVED_SESSION_VERSION};
+use super::super::stream_block::{StreamBlock, ToolStatusRecord};
+use super::approval_handler::ApprovalHandler;
+use super::context_budget::{count_text_tokens, ContextBreakdown, RequestOverhead};
+use super::history::resolve_history_limits;
+use super::tools::{emit_secret_redaction_note, migrate_text_protocol_history};
+use crate::api::files::{document_note, FileUploads, PendingDocument, UploadedFile};
+use crate::api::watchdog::StreamWatchdog;
 use crate::api::ApiClient;
+use crate::audit::AuditLog;
+use crate::change_summary::TurnChangeSummary;
+use crate::events::EventBus;
+use crate::tags::{starts_turn, TurnTag};
 use crate::tool_preview::ReadFileSnapshotCache;
-use crate::tools::ToolOperator;
-use crate::types::{ApiMessage, Content};
-use std::sync::Arc;
+use crate::tools::{ToolOperator, ToolPool, ToolPoolStats};
+use crate::turn_overrides::{clip_recorded_prompt, TurnOverrideRecord};
+use crate::types::{ApiMessage, Content, ContentBlock, TokenUsage};
+use crate::util::unix_now;
 #[cfg(test)]
-use std::{collections::HashMap, sync::Mutex};
-use tokio::sync::oneshot;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::{mpsc, oneshot};
 
 pub enum ConversationStreamUpdate {
     Delta(String),
-    BlockStart { index: usize, block: StreamBlock },
-    BlockDelta { index: usize, delta: String },
-    BlockComplete { index: usize },
+    BlockStart {
+        index: usize,
+        block: StreamBlock,
+    },
+    BlockDelta {
+        index: usize,
+        delta: String,
+    },
+    BlockComplete {
+        index: usize,
+    },
     ToolApprovalRequest(ToolApprovalRequest),
+    UserQuestion(UserQuestionRequest),
+    /// A one-line notice about the turn, such as a stalled stream.
+    Status(String),
 }
 
+/// A call waiting on the user. `input_preview` is the rendered summary the
+/// built-in prompts show; frontends may build their own from the rest.
 pub struct ToolApprovalRequest {
+    /// Id of the model's tool call; `None` for prompts not tied to one.
+    pub tool_call_id: Option<String>,
     pub tool_name: String,
+    pub input: serde_json::Value,
     pub input_preview: String,
-    pub response_tx: oneshot::Sender<bool>,
+    pub class: ToolClass,
+    /// Workspace paths named by the input, resolved where possible.
+    pub paths: Vec<String>,
+    pub tier: ApprovalTier,
+    pub response_tx: oneshot::Sender<ToolApprovalDecision>,
+}
+
+/// A clarifying question the model asked with `ask_user`. The answer, or
+/// `None` when the user skips it, goes back to the model as the tool result.
+pub struct UserQuestionRequest {
+    pub tool_call_id: String,
+    pub question: String,
+    /// Suggested answers; the user may still type their own.
+    pub options: Vec<String>,
+    pub response_tx: oneshot::Sender<Option<String>>,
+}
+
+/// What a tool call can do to the workspace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolClass {
+    ReadOnly,
+    #[default]
+    Mutating,
+    /// Replaces or moves existing content, e.g. overwriting or renaming a file.
+    Destructive,
+}
+
+impl ToolClass {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::ReadOnly => "read-only",
+            Self::Mutating => "mutating",
+            Self::Destructive => "destructive",
+        }
+    }
+}
+
+/// Why a tool call needs approval. Sensitive-tier calls prompt even while
+/// session auto-approve is on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ApprovalTier {
+    #[default]
+    Standard,
+    Sensitive {
+        path: String,
+        pattern: String,
+    },
+    /// The next request carries values of local secrets; denying redacts
+    /// them before it is sent.
+    Secrets {
+        sources: Vec<String>,
+    },
+}
+
+impl ApprovalTier {
+    pub fn is_sensitive(&self) -> bool {
+        matches!(self, Self::Sensitive { .. } | Self::Secrets { .. })
+    }
+
+    /// Modal/history note explaining a sensitive-tier prompt.
+    pub fn note(&self) -> Option<String> {
+        match self {
+            Self::Standard => None,
+            Self::Sensitive { path, pattern } => Some(format!(
+                "sensitive file {path} (matches {pattern}) - always confirmed"
+            )),
+            Self::Secrets { sources } => Some(format!(
+                "request contains the value of {} - always confirmed, deny to redact",
+                sources.join(", ")
+            )),
+        }
+    }
+}
+
+/// Outcome of a tool approval prompt. A denial may carry a short user-supplied
+/// reason that is forwarded to the model alongside the denied tool result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolApprovalDecision {
+    Approved,
+    Denied { reason: Option<String> },
+}
+
+impl ToolApprovalDecision {
+    pub fn denied_with_reason(reason: &str) -> Self {
+        let reason = reason.trim();
+        Self::Denied {
+            reason: (!reason.is_empty()).then(|| reason.to_string()),
+        }
+    }
+
+    pub fn is_approved(&self) -> bool {
+        matches!(self, Self::Approved)
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Self::Approved => None,
+            Self::Denied { reason } => reason.as_deref(),
+        }
+    }
+}
+
+impl From<bool> for ToolApprovalDecision {
+    fn from(approved: bool) -> Self {
+        if approved {
+            Self::Approved
+        } else {
+            Self::Denied { reason: None }
+        }
+    }
+}
+
+/// Approval policy shared between the conversation and the frontend. When
+/// session auto-approve is on, tools run without an approval round-trip and
+/// never enter `ToolStatus::WaitingApproval`.
+#[derive(Debug, Clone, Default)]
+pub struct ToolApprovalPolicy {
+    session_auto_approve: Arc<AtomicBool>,
+    sensitive_paths: Arc<SensitivePaths>,
+}
+
+impl ToolApprovalPolicy {
+    pub fn with_sensitive_paths(mut self, sensitive_paths: SensitivePaths) -> Self {
+        self.sensitive_paths = Arc::new(sensitive_paths);
+        self
+    }
+
+    pub fn sensitive_paths(&self) -> &SensitivePaths {
+        &self.sensitive_paths
+    }
+
+    pub fn set_session_auto_approve(&self, enabled: bool) {
+        self.session_auto_approve.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn session_auto_approve(&self) -> bool {
+        self.session_auto_approve.load(Ordering::SeqCst)
+    }
 }
 
 #[cfg(test)]
 impl ToolApprovalRequest {
     pub fn test_stub() -> Self {
-        let (response_tx, _response_rx) = oneshot::channel::<bool>();
+        let (response_tx, _response_rx) = oneshot::channel::<ToolApprovalDecision>();
         Self {
+            tool_call_id: None,
             tool_name: "read_file".to_string(),
+            input: serde_json::json!({}),
             input_preview: "{}".to_string(),
+            class: ToolClass::ReadOnly,
+            paths: Vec::new(),
+            tier: ApprovalTier::Standard,
             response_tx,
         }
     }
 }
 
+/// Message history and usage totals. Shared with [`ConversationReader`]s and
+/// locked only for short mutations, never across an await.
+#[derive(Default)]
+pub(super) struct Transcript {
+    pub(super) api_messages: Vec<ApiMessage>,
+    pub(super) token_usage: TokenUsage,
+    /// User turns sent so far, counting from 1.
+    pub(super) turn_count: usize,
+    pub(super) turn_overrides: Vec<TurnOverrideRecord>,
+    /// One entry per turn whose tools changed files or ran commands.
+    pub(super) turn_changes: Vec<TurnChangeSummary>,
+    pub(super) turn_tags: Vec<TurnTag>,
+    /// How each tool call shown as a block ended, oldest first.
+    pub(super) tool_statuses: Vec<ToolStatusRecord>,
+    /// The conversation as of the last round boundary, where every tool_use
+    /// has its result. Saves taken while a turn runs use this.
+    pub(super) checkpoint: Option<SavedSession>,
+}
+
+fn lock_transcript(transcript: &Mutex<Transcript>) -> MutexGuard<'_, Transcript> {
+    transcript
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Read-only handle on a manager's transcript. Stays usable while a turn is
+/// in flight, e.g. when a tool call is waiting minutes for approval.
+#[derive(Clone, Default)]
+pub struct ConversationReader {
+    transcript: Arc<Mutex<Transcript>>,
+    tool_pool: Arc<ToolPool>,
+    overhead: RequestOverhead,
+}
+
+impl ConversationReader {
+    /// Tool calls still executing, including ones whose turn was cancelled;
+    /// a blocking tool cannot be stopped mid-call.
+    pub fn in_flight_tools(&self) -> usize {
+        self.tool_pool.stats().running
+    }
+
+    /// Concurrency, queue, and queue-wait counters of the tool pool.
+    pub fn tool_pool_stats(&self) -> ToolPoolStats {
+        self.tool_pool.stats()
+    }
+
+    pub fn messages(&self) -> Vec<ApiMessage> {
+        lock_transcript(&self.transcript).api_messages.clone()
+    }
+
+    pub fn message_count(&self) -> usize {
+        lock_transcript(&self.transcript).api_messages.len()
+    }
+
+    pub fn token_usage(&self) -> TokenUsage {
+        lock_transcript(&self.transcript).token_usage
+    }
+
+    /// User turns sent so far.
+    pub fn turn_count(&self) -> usize {
+        lock_transcript(&self.transcript).turn_count
+    }
+
+    /// Per-turn model parameter overrides, oldest first.
+    pub fn turn_overrides(&self) -> Vec<TurnOverrideRecord> {
+        lock_transcript(&self.transcript).turn_overrides.clone()
+    }
+
+    /// What each turn's tools changed, oldest first.
+    pub fn turn_changes(&self) -> Vec<TurnChangeSummary> {
+        lock_transcript(&self.transcript).turn_changes.clone()
+    }
+
+    /// How each tool call ended, oldest first.
+    pub fn tool_statuses(&self) -> Vec<ToolStatusRecord> {
+        lock_transcript(&self.transcript).tool_statuses.clone()
+    }
+
+    /// Tags put on turns with `/tag`, oldest first.
+    pub fn turn_tags(&self) -> Vec<TurnTag> {
+        lock_transcript(&self.transcript).turn_tags.clone()
+    }
+
+    /// The conversation as of the last round boundary or finished turn, with
+    /// no tool_use left waiting for its result. Reading it never waits on a
+    /// running turn. `None` before the first round.
+    pub fn checkpoint(&self) -> Option<SavedSession> {
+        lock_transcript(&self.transcript).checkpoint.clone()
+    }
+
+    /// Tags the running turn, or the last one when idle, with each of
+    /// `tags` it does not already carry. Returns the turn tagged; `None`
+    /// before the first turn. Annotations are the one change a reader makes,
+    /// so a turn can be tagged while it runs.
+    pub fn tag_turn(&self, tags: &[String]) -> Option<usize> {
+        let mut transcript = lock_transcript(&self.transcript);
+        let turn = transcript.turn_count;
+        if turn == 0 {
+            return None;
+        }
+        let prompt = transcript
+            .api_messages
+            .iter()
+            .rev()
+            .find(|message| starts_turn(message))
+            .map(|message| match &message.content {
+                Content::Text(text) => text.clone(),
+                Content::Blocks(blocks) => blocks
+                    .iter()
+                    .find_map(|block| match block {
+                        ContentBlock::Text { text } => Some(text.clone()),
+                        _ => None,
+                    })
+                    .unwrap_or_default(),
+            })
+            .unwrap_or_default();
+        let prompt = clip_recorded_prompt(prompt.trim());
+        for tag in tags {
+            if !transcript
+                .turn_tags
+                .iter()
+                .any(|tagged| tagged.turn == turn && tagged.tag == *tag)
+            {
+                transcript.turn_tags.push(TurnTag {
+                    turn,
+                    tag: tag.clone(),
+                    prompt: prompt.clone(),
+                });
+            }
+        }
+        let turn_tags = transcript.turn_tags.clone();
+        if let Some(checkpoint) = transcript.checkpoint.as_mut() {
+            checkpoint.turn_tags = turn_tags;
+        }
+        Some(turn)
+    }
+
+    /// What the latest turn's tools changed, if anything.
+    pub fn latest_turn_changes(&self) -> Option<TurnChangeSummary> {
+        let transcript = lock_transcript(&self.transcript);
+        transcript
+            .turn_changes
+            .last()
+            .filter(|summary| summary.turn == transcript.turn_count)
+            .cloned()
+    }
+
+    /// Size of each part of the next request, for `/context`.
+    pub fn context_breakdown(&self) -> ContextBreakdown {
+        ContextBreakdown::new(
+            self.overhead,
+            &lock_transcript(&self.transcript).api_messages,
+        )
+    }
+}
+
 pub struct ConversationManager {
     pub(super) client: Arc<ApiClient>,
     pub(super) tool_operator: ToolOperator,
-    pub(super) api_messages: Vec<ApiMessage>,
+    pub(super) transcript: Arc<Mutex<Transcript>>,
     pub(super) current_turn_blocks: Vec<StreamBlock>,
     pub(super) read_file_history_cache: ReadFileSnapshotCache,
+    pub(super) approval_policy: ToolApprovalPolicy,
+    /// Replaces the approval prompt sent to the frontend when set.
+    pub(super) approval_handler: Option<Arc<dyn ApprovalHandler>>,
+    pub(super) audit_log: Option<AuditLog>,
+    pub(super) secret_guard: Arc<SecretGuard>,
+    /// Secrets the user agreed to send this session; not asked about again.
+    pub(super) allowed_secrets: HashSet<String>,
+    pub(super) tool_pool: Arc<ToolPool>,
+    /// Prepended to the next user message, e.g. after runaway output.
+    pub(super) pending_turn_note: Option<String>,
+    /// Attachments to upload and reference from the next user message.
+    pub(super) pending_documents: Vec<PendingDocument>,
+    pub(super) file_uploads: FileUploads,
+    pub(super) events: EventBus,
+    pub(super) stream_watchdog: StreamWatchdog,
+    /// Name the conversation is saved under; taken over from a resumed one.
+    pub(super) session_id: String,
     #[cfg(test)]
     pub(super) mock_tool_operator_responses: Option<Arc<Mutex<HashMap<String, String>>>>,
 }
 
 impl ConversationManager {
     pub fn new(client: ApiClient, operator: ToolOperator) -> Self {
+        let custom_tool_definitions = operator
+            .custom_tools()
+            .tools
+            .iter()
+            .map(|tool| tool.definition())
+            .collect();
+        let session_id = operator.session_id().to_string();
         Self {
-            client: Arc::new(client),
+            client: Arc::new(client.with_custom_tool_definitions(custom_tool_definitions)),
             tool_operator: operator,
-            api_messages: Vec::new(),
+            transcript: Arc::default(),
             current_turn_blocks: Vec::new(),
             read_file_history_cache: ReadFileSnapshotCache::default(),
+            approval_policy: ToolApprovalPolicy::default()
+                .with_sensitive_paths(SensitivePaths::from_env()),
+            approval_handler: None,
+            audit_log: None,
+            secret_guard: Arc::new(SecretGuard::from_env()),
+            allowed_secrets: HashSet::new(),
+            tool_pool: Arc::new(ToolPool::from_env()),
+            pending_turn_note: None,
+            pending_documents: Vec::new(),
+            file_uploads: FileUploads::default(),
+            events: EventBus::default(),
+            stream_watchdog: StreamWatchdog::from_env(),
+            session_id,
             #[cfg(test)]
             mock_tool_operator_responses: None,
         }
@@ -62,25 +430,280 @@ impl ConversationManager {
         Self {
             client: Arc::new(client),
             tool_operator: ToolOperator::new(std::path::PathBuf::from("/tmp")), // Dummy operator
-            api_messages: Vec::new(),
+            transcript: Arc::default(),
             current_turn_blocks: Vec::new(),
             read_file_history_cache: ReadFileSnapshotCache::default(),
+            approval_policy: ToolApprovalPolicy::default(),
+            approval_handler: None,
+            audit_log: None,
+            secret_guard: Arc::default(),
+            allowed_secrets: HashSet::new(),
+            tool_pool: Arc::default(),
+            pending_turn_note: None,
+            pending_documents: Vec::new(),
+            file_uploads: FileUploads::default(),
+            events: EventBus::default(),
+            stream_watchdog: StreamWatchdog::from_env(),
+            session_id: crate::state_dirs::new_session_id(),
             mock_tool_operator_responses: Some(Arc::new(Mutex::new(tool_operator_responses))),
         }
     }
 
+    /// Records every executed tool call to `audit_log`.
+    pub fn with_audit_log(mut self, audit_log: Option<AuditLog>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Replaces the stall watchdog built from the environment.
+    pub fn with_stream_watchdog(mut self, stream_watchdog: StreamWatchdog) -> Self {
+        self.stream_watchdog = stream_watchdog;
+        self
+    }
+
+    /// Replaces the guard built from the environment.
+    pub fn with_secret_guard(mut self, secret_guard: SecretGuard) -> Self {
+        self.secret_guard = Arc::new(secret_guard);
+        self
+    }
+
+    /// Sends tool calls that need approval to `handler` instead of the
+    /// frontend.
+    pub fn with_approval_handler(mut self, handler: impl ApprovalHandler + 'static) -> Self {
+        self.approval_handler = Some(Arc::new(handler));
+        self
+    }
+
+    pub(super) fn transcript(&self) -> MutexGuard<'_, Transcript> {
+        lock_transcript(&self.transcript)
+    }
+
+    /// A handle that reads the transcript without the caller holding the
+    /// manager, so the UI can query state while a turn is running.
+    pub fn reader(&self) -> ConversationReader {
+        let (system_prompt, tool_schemas) = self.client.request_overhead_text();
+        ConversationReader {
+            transcript: Arc::clone(&self.transcript),
+            tool_pool: Arc::clone(&self.tool_pool),
+            overhead: RequestOverhead {
+                system_tokens: count_text_tokens(&system_prompt),
+                tool_schema_tokens: count_text_tokens(&tool_schemas),
+                max_api_messages: resolve_history_limits(self.client.is_local_endpoint())
+                    .max_api_messages,
+            },
+        }
+    }
+
     pub fn push_user_message(&mut self, input: String) {
-        self.api_messages.push(ApiMessage {
+        self.transcript().api_messages.push(ApiMessage {
             role: "user".to_string(),
             content: Content::Text(input),
         });
     }
 
+    /// Pushes `input` after `documents`, or as plain text when there are none.
+    pub fn push_user_message_with_documents(
+        &mut self,
+        input: String,
+        documents: Vec<ContentBlock>,
+    ) {
+        if documents.is_empty() {
+            self.push_user_message(input);
+            return;
+        }
+        let mut blocks = documents;
+        blocks.push(ContentBlock::Text { text: input });
+        self.transcript().api_messages.push(ApiMessage {
+            role: "user".to_string(),
+            content: Content::Blocks(blocks),
+        });
+    }
+
+    /// Files to upload and reference from the next user message.
+    pub fn queue_documents(&mut self, documents: Vec<PendingDocument>) {
+        self.pending_documents.extend(documents);
+    }
+
+    /// Uploads the queued documents, reusing this session's upload of the
+    /// same content. Secrets in a document are confirmed like those in a
+    /// message and redacted from the upload when denied. Returns the
+    /// document blocks and a note for each upload that failed.
+    pub(super) async fn upload_pending_documents(
+        &mut self,
+        stream_delta_tx: Option<&mpsc::UnboundedSender<ConversationStreamUpdate>>,
+    ) -> (Vec<ContentBlock>, Vec<String>) {
+        let mut blocks = Vec::new();
+        let mut notes = Vec::new();
+        for mut document in std::mem::take(&mut self.pending_documents) {
+            let found = self.secret_guard.scan_text(&document.content);
+            let denied = self.confirm_secrets(found, stream_delta_tx).await;
+            if !denied.is_empty() {
+                redact_text(&mut document.content, &denied);
+                emit_secret_redaction_note(
+                    stream_delta_tx,
+                    &denied,
+                    &format!("from {} before uploading", document.title),
+                );
+            }
+            if let Some(uploaded) = self.file_uploads.get(&document.content) {
+                blocks.push(uploaded.document_block());
+                continue;
+            }
+            let file_name = std::path::Path::new(&document.title)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(&document.title);
+            match self.client.upload_file(file_name, &document.content).await {
+                Ok(id) => {
+                    let uploaded = UploadedFile {
+                        id,
+                        title: document.title.clone(),
+                        bytes: document.content.len(),
+                    };
+                    blocks.push(uploaded.document_block());
+                    self.file_uploads.insert(&document.content, uploaded);
+                }
+                Err(error) => notes.push(format!(
+                    "[upload of {} failed: {error}; read it with read_file instead]",
+                    document.title
+                )),
+            }
+        }
+        (blocks, notes)
+    }
+
+    /// Files this session has uploaded and not yet deleted.
+    pub fn uploaded_file_count(&self) -> usize {
+        self.file_uploads.len()
+    }
+
+    /// Deletes this session's uploads and turns the transcript's references
+    /// to them into notes, so a restored session never points at a deleted
+    /// file. Returns how many deletions failed.
+    pub async fn release_uploaded_files(&mut self) -> usize {
+        let uploaded = self.file_uploads.drain();
+        if uploaded.is_empty() {
+            return 0;
+        }
+        let mut failed = 0;
+        for file in &uploaded {
+            if self.client.delete_file(&file.id).await.is_err() {
+                failed += 1;
+            }
+        }
+        for message in &mut self.transcript().api_messages {
+            let Content::Blocks(blocks) = &mut message.content else {
+                continue;
+            };
+            for block in blocks.iter_mut() {
+                if let ContentBlock::Document { title, .. } = block {
+                    *block = ContentBlock::Text {
+                        text: document_note(title.as_deref()),
+                    };
+                }
+            }
+        }
+        failed
+    }
+
     pub fn messages_for_api(&self) -> Vec<ApiMessage> {
-        self.api_messages.clone()
+        self.transcript().api_messages.clone()
     }
 
     pub fn client(&self) -> Arc<ApiClient> {
         Arc::clone(&self.client)
     }
+
+    /// Handle to the shared approval policy; clones observe the same state.
+    /// Shares the operator's caches with work done outside a turn.
+    pub fn tool_operator(&self) -> ToolOperator {
+        self.tool_operator.clone()
+    }
+
+    pub fn approval_policy(&self) -> ToolApprovalPolicy {
+        self.approval_policy.clone()
+    }
+
+    /// The bus tool calls are published on; the runtime publishes turn and
+    /// stream events on the same one.
+    pub fn event_bus(&self) -> EventBus {
+        self.events.clone()
+    }
+
+    /// Token usage reported by the provider, summed over every round so far.
+    pub fn token_usage(&self) -> TokenUsage {
+        self.transcript().token_usage
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// The conversation as it would be saved now, with local secrets
+    /// redacted.
+    pub fn saved_session(&self) -> SavedSession {
+        let transcript = self.transcript();
+        let mut session = SavedSession {
+            version: SAVED_SESSION_VERSION,
+            id: self.session_id.clone(),
+            saved_at: unix_now(),
+            messages: transcript.api_messages.clone(),
+            token_usage: transcript.token_usage,
+            turn_count: transcript.turn_count,
+            tool_statuses: transcript.tool_statuses.clone(),
+            read_file_snapshots: self.read_file_history_cache.clone(),
+            turn_overrides: transcript.turn_overrides.clone(),
+            turn_changes: transcript.turn_changes.clone(),
+            turn_tags: transcript.turn_tags.clone(),
+            sampling: self.client.sampling(),
+        };
+        self.secret_guard.redact_session(&mut session);
+        session
+    }
+
+    /// Records the conversation as it stands for saves taken while a turn
+    /// runs. Called where no tool_use is waiting for its result.
+    pub(super) fn checkpoint(&self) {
+        let session = self.saved_session();
+        self.transcript().checkpoint = Some(session);
+    }
+
+    /// Replaces this conversation with `saved` and continues saving under
+    /// its id. Documents uploaded by the earlier process may be gone, so
+    /// they become notes asking the model to read the file instead.
+    pub fn restore_session(&mut self, saved: SavedSession) {
+        let mut messages = saved.messages;
+        // A session saved against a text-protocol endpoint has its tagged
+        // tool rounds rewritten as the blocks a structured endpoint expects.
+        if self.client.supports_structured_tool_protocol() {
+            migrate_text_protocol_history(&mut messages);
+        }
+        for message in &mut messages {
+            let Content::Blocks(blocks) = &mut message.content else {
+                continue;
+            };
+            for block in blocks.iter_mut() {
+                if let ContentBlock::Document { title, .. } = block {
+                    *block = ContentBlock::Text {
+                        text: document_note(title.as_deref()),
+                    };
+                }
+            }
+        }
+        *self.transcript() = Transcript {
+            api_messages: messages,
+            token_usage: saved.token_usage,
+            turn_count: saved.turn_count,
+            turn_overrides: saved.turn_overrides,
+            turn_changes: saved.turn_changes,
+            turn_tags: saved.turn_tags,
+            tool_statuses: saved.tool_statuses,
+            checkpoint: None,
+        };
+        self.read_file_history_cache = saved.read_file_snapshots;
+        self.current_turn_blocks.clear();
+        self.pending_turn_note = None;
+        self.session_id = saved.id;
+        self.checkpoint();
+    }
 }
@@ -1,7 +1,11 @@
-use super::super::stream_block::{StreamBlock, ToolStatus};
+use super::super::stream_block::{StreamBlock, ToolStatus, ToolStatusRecord};
 use super::{ConversationManager, ConversationStreamUpdate};
+use crate::api::runaway::RunawayCut;
+use crate::api::watchdog::StallAction;
+use crate::events::RuntimeEvent;
 use crate::util::parse_bool_flag;
 use std::collections::BTreeSet;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
 impl ConversationManager {
@@ -75,6 +79,22 @@ impl ConversationManager {
         appended
     }
 
+    /// Cuts the runaway text block `index` back to `cut.keep_bytes`, along
+    /// with the same tail of `assistant_text`.
+    pub(super) fn clamp_runaway_block(
+        &mut self,
+        index: usize,
+        cut: &RunawayCut,
+        assistant_text: &mut String,
+    ) {
+        let dropped = cut.block_bytes.saturating_sub(cut.keep_bytes);
+        if let Some(StreamBlock::Thinking { content, .. }) = self.current_turn_blocks.get_mut(index)
+        {
+            truncate_at_char_boundary(content, content.len().saturating_sub(dropped));
+        }
+        truncate_at_char_boundary(assistant_text, assistant_text.len().saturating_sub(dropped));
+    }
+
     pub(super) fn set_tool_call_status(
         &mut self,
         tool_call_id: &str,
@@ -92,11 +112,17 @@ impl ConversationManager {
                     )
                 })
         {
+            let mut finished = None;
             if let StreamBlock::ToolCall {
-                status: current, ..
+                status: current,
+                name,
+                ..
             } = block
             {
-                *current = status;
+                if status.is_final() {
+                    finished = Some(name.clone());
+                }
+                *current = status.clone();
             }
 
             emit_stream_update(
@@ -106,6 +132,16 @@ impl ConversationManager {
                     block: block.clone(),
                 },
             );
+            if let Some(name) = finished {
+                let mut transcript = self.transcript();
+                let turn = transcript.turn_count;
+                transcript.tool_statuses.push(ToolStatusRecord {
+                    turn,
+                    id: tool_call_id.to_string(),
+                    name,
+                    status,
+                });
+            }
         }
     }
 
@@ -193,6 +229,37 @@ impl ConversationManager {
             }
         }
     }
+
+    /// Tells the user and event subscribers that the response stream has
+    /// been silent for `silent` and what happens next.
+    pub(super) fn report_stream_stall(
+        &self,
+        round: usize,
+        silent: Duration,
+        action: StallAction,
+        stream_delta_tx: Option<&mpsc::UnboundedSender<ConversationStreamUpdate>>,
+    ) {
+        self.events.publish(RuntimeEvent::StreamStalled {
+            round,
+            silent,
+            action,
+        });
+        emit_stream_update(
+            stream_delta_tx,
+            ConversationStreamUpdate::Status(format!(
+                "stream stalled for {}s; {}",
+                silent.as_secs(),
+                action.describe()
+            )),
+        );
+    }
+}
+
+fn truncate_at_char_boundary(text: &mut String, mut len: usize) {
+    while len > 0 && !text.is_char_boundary(len) {
+        len -= 1;
+    }
+    text.truncate(len);
 }
 
 pub(super) fn emit_stream_update(
@@ -218,7 +285,7 @@ pub(super) fn structured_blocks_enabled() -> bool {
         .unwrap_or(true)
 }
 
-pub(super) fn append_incremental_suffix(existing: &mut String, incoming: &str) -> String {
+pub(crate) fn append_incremental_suffix(existing: &mut String, incoming: &str) -> String {
     if incoming.is_empty() {
         return String::new();
     }
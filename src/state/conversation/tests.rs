@@ -1,6 +1,6 @@
 use super::*;
 use crate::api::ApiClient;
-use crate::state::{StreamBlock, ToolStatus};
+use crate::state::{SecretGuard, StreamBlock, ToolStatus, SEND_SECRETS_APPROVAL};
 use crate::tools::ToolOperator;
 use crate::types::{ApiMessage, Content, ContentBlock};
 use anyhow::Result;
@@ -97,6 +97,64 @@ fn test_read_only_tool_round_helpers() {
     assert_ne!(sig_a, sig_c);
 }
 
+#[test]
+fn test_fuzzy_edit_result_shows_the_edit_as_applied() -> Result<()> {
+    let temp = TempDir::new()?;
+    std::fs::write(
+        temp.path().join("lib.py"),
+        "def total(items):\n    return sum(items)\n",
+    )?;
+    let operator = ToolOperator::new(temp.path().to_path_buf()).with_fuzzy_edit(true);
+    let result = execute_tool_dispatch(
+        &operator,
+        "edit_file",
+        &json!({
+            "path": "lib.py",
+            "old_str": "\treturn sum(items)",
+            "new_str": "\treturn sum(items) + 1"
+        }),
+    )?;
+    assert!(
+        result.contains("new_str re-indented to match the file]"),
+        "{result}"
+    );
+    assert!(result.contains("1 -     return sum(items)\n"), "{result}");
+    assert!(
+        result.ends_with("1 +     return sum(items) + 1"),
+        "{result}"
+    );
+    assert!(!result.contains("\treturn"), "{result}");
+    Ok(())
+}
+
+#[test]
+fn test_commit_tools_default_to_the_request_and_the_suggested_draft() {
+    let draft = || Some("fix: crash on empty input".to_string());
+    assert_eq!(
+        with_commit_defaults("suggest_commit_message", json!({}), "fix the crash", draft),
+        json!({"intent": "fix the crash"})
+    );
+    assert_eq!(
+        with_commit_defaults(
+            "git_commit",
+            json!({"message": " "}),
+            "fix the crash",
+            draft
+        ),
+        json!({"message": "fix: crash on empty input"})
+    );
+    assert_eq!(
+        with_commit_defaults("git_commit", json!({"msg": "mine"}), "", draft),
+        json!({"msg": "mine"})
+    );
+    assert_eq!(
+        with_commit_defaults("git_commit", json!({}), "", || None),
+        json!({})
+    );
+    assert!(is_read_only_tool_name("suggest_commit_message"));
+    assert!(!is_read_only_tool_name("git_commit"));
+}
+
 #[test]
 fn test_tool_requires_confirmation_for_mutating_tools() {
     assert!(tool_requires_confirmation("write_file"));
@@ -176,7 +234,7 @@ data: {"type": "message_stop"}"#.to_string(),
     assert!(final_text.contains("The content of file.txt is 'Hello from file.txt'"));
 
     // Verify the message history order
-    let messages = &manager.api_messages;
+    let messages = manager.messages_for_api();
     assert_eq!(messages.len(), 4);
 
     // Initial user message
@@ -227,6 +285,76 @@ data: {"type": "message_stop"}"#.to_string(),
     Ok(())
 }
 
+#[tokio::test]
+async fn test_thinking_blocks_are_kept_ahead_of_tool_calls() -> Result<()> {
+    let tool_round = vec![
+        r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_think_1","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string(),
+        r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"thinking","thinking":"","signature":""}}"#.to_string(),
+        r#"event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"Need the file "}}"#.to_string(),
+        r#"event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"first."}}"#.to_string(),
+        r#"event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"signature_delta","signature":"sig-abc"}}"#.to_string(),
+        r#"event: content_block_stop
+data: {"type":"content_block_stop","index":0}"#.to_string(),
+        r#"event: content_block_start
+data: {"type":"content_block_start","index":1,"content_block":{"type":"redacted_thinking","data":"opaque"}}"#.to_string(),
+        r#"event: content_block_stop
+data: {"type":"content_block_stop","index":1}"#.to_string(),
+        r#"event: content_block_start
+data: {"type":"content_block_start","index":2,"content_block":{"type":"tool_use","id":"toolu_think","name":"read_file","input":{}}}"#.to_string(),
+        r#"event: content_block_delta
+data: {"type":"content_block_delta","index":2,"delta":{"type":"input_json_delta","partial_json":"{\"path\": \"file.txt\"}"}}"#.to_string(),
+        r#"event: content_block_stop
+data: {"type":"content_block_stop","index":2}"#.to_string(),
+        r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":6}}"#.to_string(),
+        r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+    ];
+    let final_round = vec![
+        r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_think_2","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string(),
+        r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#.to_string(),
+        r#"event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"It says hello."}}"#.to_string(),
+        r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":3}}"#.to_string(),
+        r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+    ];
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            tool_round,
+            final_round,
+        ])));
+    let mut responses = HashMap::new();
+    responses.insert("file.txt".to_string(), "hello".to_string());
+    let mut manager = ConversationManager::new_mock(mock_api_client, responses);
+
+    manager
+        .send_message("what is in file.txt?".into(), None)
+        .await?;
+
+    let messages = manager.messages_for_api();
+    let Content::Blocks(blocks) = &messages[1].content else {
+        panic!("assistant blocks");
+    };
+    assert_eq!(
+        serde_json::to_value(&blocks[..2])?,
+        json!([
+            { "type": "thinking", "thinking": "Need the file first.", "signature": "sig-abc" },
+            { "type": "redacted_thinking", "data": "opaque" },
+        ])
+    );
+    assert!(matches!(&blocks[2], ContentBlock::ToolUse { name, .. } if name == "read_file"));
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_structured_text_only_round_streams_final_text_block() -> Result<()> {
     let response_sse = vec![
@@ -339,11 +467,13 @@ data: {"type":"message_stop"}"#.to_string(),
                         }
                     }
                     ConversationStreamUpdate::ToolApprovalRequest(request) => {
-                        let _ = request.response_tx.send(true);
+                        let _ = request.response_tx.send(true.into());
                     }
                     ConversationStreamUpdate::Delta(_)
                     | ConversationStreamUpdate::BlockDelta { .. }
-                    | ConversationStreamUpdate::BlockComplete { .. } => {}
+                    | ConversationStreamUpdate::BlockComplete { .. }
+                    | ConversationStreamUpdate::UserQuestion(_)
+                    | ConversationStreamUpdate::Status(_) => {}
                 }
             }
         }
@@ -394,6 +524,98 @@ fn test_parse_tagged_tool_calls_without_parameters() {
     assert_eq!(calls[0].input, json!({}));
 }
 
+#[test]
+fn test_resuming_on_structured_protocol_migrates_tagged_rounds() {
+    let mock = || {
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(
+            vec![],
+        )))
+    };
+    let text_manager =
+        ConversationManager::new_mock(mock().with_structured_tool_protocol(false), HashMap::new());
+    let text_history = vec![
+        ApiMessage {
+            role: "user".to_string(),
+            content: Content::Text("check the build".to_string()),
+        },
+        ApiMessage {
+            role: "assistant".to_string(),
+            content: Content::Text(
+                "Checking.\n<function=git_status>\n</function>\n<function=read_file>\n<parameter=path>\nCargo.toml\n</parameter>\n</function>".to_string(),
+            ),
+        },
+        ApiMessage {
+            role: "user".to_string(),
+            content: Content::Text(
+                "tool_result git_status:\nclean\n\ntool_error read_file:\nnot found".to_string(),
+            ),
+        },
+        ApiMessage {
+            role: "assistant".to_string(),
+            content: Content::Text("The tree is clean.".to_string()),
+        },
+    ];
+    text_manager.transcript().api_messages = text_history.clone();
+    let saved = text_manager.saved_session();
+
+    let mut still_text =
+        ConversationManager::new_mock(mock().with_structured_tool_protocol(false), HashMap::new());
+    still_text.restore_session(saved.clone());
+    assert_eq!(
+        serde_json::to_value(still_text.messages_for_api()).unwrap(),
+        serde_json::to_value(&text_history).unwrap()
+    );
+
+    let mut manager = ConversationManager::new_mock(mock(), HashMap::new());
+    manager.restore_session(saved);
+    let messages = manager.messages_for_api();
+    assert_eq!(messages.len(), 4);
+    assert!(matches!(&messages[0].content, Content::Text(text) if text == "check the build"));
+    let Content::Blocks(calls) = &messages[1].content else {
+        panic!("assistant blocks");
+    };
+    assert!(matches!(&calls[0], ContentBlock::Text { text } if text == "Checking."));
+    let ContentBlock::ToolUse { id, name, input } = &calls[2] else {
+        panic!("tool use");
+    };
+    assert_eq!(
+        (id.as_str(), name.as_str()),
+        ("toolu_migrated_2_1", "read_file")
+    );
+    assert_eq!(input, &json!({ "path": "Cargo.toml" }));
+    let Content::Blocks(results) = &messages[2].content else {
+        panic!("result blocks");
+    };
+    let results: Vec<_> = results
+        .iter()
+        .map(|block| match block {
+            ContentBlock::ToolResult {
+                tool_use_id,
+                content,
+                is_error,
+            } => (tool_use_id.as_str(), content.as_str(), *is_error),
+            _ => panic!("tool result"),
+        })
+        .collect();
+    assert_eq!(
+        results,
+        [
+            ("toolu_migrated_2_0", "clean", false),
+            ("toolu_migrated_2_1", "not found", true)
+        ]
+    );
+    assert!(matches!(&messages[3].content, Content::Text(text) if text == "The tree is clean."));
+
+    // Already structured: resuming again leaves history alone.
+    let migrated = serde_json::to_value(&messages).unwrap();
+    let mut again = ConversationManager::new_mock(mock(), HashMap::new());
+    again.restore_session(manager.saved_session());
+    assert_eq!(
+        serde_json::to_value(again.messages_for_api()).unwrap(),
+        migrated
+    );
+}
+
 #[test]
 fn test_parse_tagged_tool_calls_with_missing_closing_tags() {
     let text = r#"I'll check it.
@@ -430,16 +652,138 @@ fn test_truncate_for_history_preserves_tail_context() {
     assert!(truncated.contains("tail"));
 }
 
+#[test]
+fn test_sample_head_tail_keeps_whole_lines_weighted_to_the_tail() {
+    let log: String = (1..=100).map(|n| format!("line {n:03}\n")).collect();
+    let budget = ToolOutputBudget {
+        max_chars: 400,
+        tail_percent: 75,
+    };
+    let sampled = sample_head_tail(&log, budget);
+    assert!(sampled.chars().count() <= 400);
+    assert!(sampled.starts_with("line 001\nline 002\n"));
+    assert!(sampled.ends_with("line 099\nline 100\n"));
+    let head = sampled
+        .lines()
+        .take_while(|line| line.starts_with("line"))
+        .count();
+    let tail = sampled
+        .lines()
+        .rev()
+        .take_while(|line| line.starts_with("line"))
+        .count();
+    assert!(tail > head * 2, "head {head}, tail {tail}");
+    let elided = 100 - head - tail;
+    assert!(sampled.contains(&format!(
+        "...[{elided} lines, {} chars elided]...\n",
+        elided * 9
+    )));
+    assert_eq!(sample_head_tail("short\n", budget), "short\n");
+
+    // One long line cannot be sampled by lines.
+    let blob = "x".repeat(1_000);
+    assert!(sample_head_tail(&blob, budget).contains("[truncated"));
+}
+
+#[test]
+fn test_tool_budget_spec_overrides_one_tool() {
+    let budget = ToolOutputBudget {
+        max_chars: 6_000,
+        tail_percent: 50,
+    };
+    let spec = "git_diff=9000, run_tests = 12000:80%,bogus";
+    assert_eq!(
+        apply_tool_budget_spec(budget, "run_tests", spec),
+        ToolOutputBudget {
+            max_chars: 12_000,
+            tail_percent: 80
+        }
+    );
+    assert_eq!(
+        apply_tool_budget_spec(budget, "git_diff", spec).max_chars,
+        9_000
+    );
+    assert_eq!(apply_tool_budget_spec(budget, "read_file", spec), budget);
+    assert_eq!(
+        apply_tool_budget_spec(budget, "tree", "tree=10").max_chars,
+        200
+    );
+}
+
 #[test]
 fn test_required_tool_string_validation() {
-    let input = json!({ "path": " cal.rs " });
+    let input: ReadFileInput =
+        parse_tool_input("read_file", &json!({ "file": " cal.rs " })).unwrap();
     assert_eq!(
-        required_tool_string(&input, "read_file", "path").unwrap(),
+        non_empty("read_file", "path", &input.path).unwrap(),
         "cal.rs"
     );
+    assert!(non_empty("read_file", "path", "  ").is_err());
 
-    let missing = json!({});
-    assert!(required_tool_string(&missing, "read_file", "path").is_err());
+    let missing = parse_tool_input::<ReadFileInput>("read_file", &json!({})).unwrap_err();
+    assert!(
+        missing.to_string().contains("missing field `path`"),
+        "{missing}"
+    );
+    let unknown =
+        parse_tool_input::<EditFileInput>("edit_file", &json!({ "path": "a.rs", "old_strr": "x" }))
+            .unwrap_err();
+    assert!(
+        unknown
+            .to_string()
+            .starts_with("Invalid edit_file input: unknown field `old_strr`"),
+        "{unknown}"
+    );
+    let mistyped = parse_tool_input::<ReadFileInput>(
+        "read_file",
+        &json!({ "path": "a.rs", "start_line": "3" }),
+    )
+    .unwrap_err();
+    assert!(
+        mistyped.to_string().contains("invalid type: string \"3\""),
+        "{mistyped}"
+    );
+}
+
+#[test]
+fn test_write_file_requires_content() {
+    let missing = parse_tool_input::<WriteFileInput>("write_file", &json!({ "path": "a.rs" }))
+        .unwrap_err()
+        .to_string();
+    assert!(
+        missing.starts_with("Invalid write_file input: missing field `content`"),
+        "{missing}"
+    );
+    assert!(missing.contains("No action was taken"), "{missing}");
+
+    let empty =
+        parse_tool_input::<WriteFileInput>("write_file", &json!({ "path": "a.rs", "text": "" }))
+            .unwrap();
+    assert_eq!(empty.content, "");
+}
+
+#[test]
+fn test_repeated_path_aliases_are_accepted_when_they_agree() {
+    let input: WriteFileInput = parse_tool_input(
+        "write_file",
+        &json!({ "path": "a.rs", "file_path": "a.rs", "content": "x" }),
+    )
+    .unwrap();
+    assert_eq!(input.path, "a.rs");
+
+    let conflict = parse_tool_input::<WriteFileInput>(
+        "write_file",
+        &json!({ "path": "a.rs", "file_path": "b.rs", "content": "x" }),
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(
+        conflict.starts_with(
+            "Invalid write_file input: `path` and `file_path` name the same argument with different values"
+        ),
+        "{conflict}"
+    );
+    assert!(conflict.contains("No action was taken"), "{conflict}");
 }
 
 #[test]
@@ -529,6 +873,24 @@ fn test_env_bool_off_is_false_across_state_paths() {
     std::env::remove_var("VEX_TOOL_CONFIRM");
 }
 
+#[test]
+fn test_saved_session_records_pinned_sampling() {
+    let sampling = crate::config::Sampling {
+        temperature: Some(0.0),
+        seed: Some(42),
+        ..crate::config::Sampling::default()
+    };
+    let mock_api_client = ApiClient::new_mock(Arc::new(
+        crate::api::mock_client::MockApiClient::new(vec![]),
+    ))
+    .with_sampling(sampling);
+    let manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+    let saved = manager.saved_session();
+    assert_eq!(saved.sampling, sampling);
+    let json = serde_json::to_value(&saved).expect("serialize");
+    assert_eq!(json["sampling"], json!({ "temperature": 0.0, "seed": 42 }));
+}
+
 #[test]
 fn test_format_tool_result_for_history_read_file_diff_and_repeat() {
     let mock_api_client = ApiClient::new_mock(Arc::new(
@@ -539,6 +901,7 @@ fn test_format_tool_result_for_history_read_file_diff_and_repeat() {
 
     let first = manager.format_tool_result_for_history(
         "read_file",
+        "toolu_read",
         &input,
         &Ok("line1\nline2".to_string()),
     );
@@ -548,6 +911,7 @@ fn test_format_tool_result_for_history_read_file_diff_and_repeat() {
 
     let second = manager.format_tool_result_for_history(
         "read_file",
+        "toolu_read",
         &input,
         &Ok("line1\nline2".to_string()),
     );
@@ -555,17 +919,23 @@ fn test_format_tool_result_for_history_read_file_diff_and_repeat() {
 
     let third = manager.format_tool_result_for_history(
         "read_file",
+        "toolu_read",
         &input,
         &Ok("line1\nline2 changed".to_string()),
     );
-    assert!(third.contains("content changed"));
-    assert!(third.contains("Content for model context:"));
-    assert!(third.contains("line1\nline2 changed"));
+    assert!(
+        third.starts_with("Read cal.rs: 1 line changed near line 2 since the last read."),
+        "{third}"
+    );
+    assert!(third.contains("-line2\n"));
+    assert!(third.contains("+line2 changed"));
+    assert!(!third.contains("Content for model context:"));
 
     // After a change the cache must update, so the same content read again
     // must be classified as Unchanged — not another Changed.
     let fourth = manager.format_tool_result_for_history(
         "read_file",
+        "toolu_read",
         &input,
         &Ok("line1\nline2 changed".to_string()),
     );
@@ -575,6 +945,60 @@ fn test_format_tool_result_for_history_read_file_diff_and_repeat() {
     );
 }
 
+#[test]
+fn test_repeated_tool_results_are_referenced_and_expandable() -> Result<()> {
+    let mock_api_client = ApiClient::new_mock(Arc::new(
+        crate::api::mock_client::MockApiClient::new(vec![]),
+    ));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+    manager.tool_operator =
+        ToolOperator::new(std::path::PathBuf::from("/tmp")).with_result_dedup(true);
+    let input = serde_json::json!({ "query": "parse" });
+    let output = "src/parser.rs:10: fn parse(input: &str)\n".repeat(20);
+
+    let first = manager.format_tool_result_for_history(
+        "search_files",
+        "toolu_1",
+        &input,
+        &Ok(output.clone()),
+    );
+    assert_eq!(first, output);
+    let second = manager.format_tool_result_for_history(
+        "search_files",
+        "toolu_2",
+        &input,
+        &Ok(output.clone()),
+    );
+    assert!(
+        second.starts_with("[identical to the earlier search_files result toolu_1 (hash "),
+        "{second}"
+    );
+    let hash = second
+        .split("(hash ")
+        .nth(1)
+        .and_then(|rest| rest.split(',').next())
+        .expect("hash in reference");
+
+    let expanded = execute_tool_dispatch(
+        &manager.tool_operator,
+        "expand_tool_result",
+        &serde_json::json!({ "hash": hash }),
+    )?;
+    assert_eq!(expanded, output);
+    // Expansions go into history in full, even when repeated.
+    let input = serde_json::json!({ "hash": hash });
+    for id in ["toolu_3", "toolu_4"] {
+        let restored = manager.format_tool_result_for_history(
+            "expand_tool_result",
+            id,
+            &input,
+            &Ok(expanded.clone()),
+        );
+        assert_eq!(restored, output);
+    }
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_text_tagged_tool_call_executes_as_fallback_for_local_endpoint() -> Result<()> {
     let first_response_sse = vec![
@@ -615,7 +1039,7 @@ data: {"type":"message_stop"}"#.to_string(),
     let final_text = manager.send_message("Read file".into(), None).await?;
     assert!(final_text.contains("Read complete: Hello from fallback."));
 
-    let messages = &manager.api_messages;
+    let messages = manager.messages_for_api();
     assert!(
         messages.iter().any(|message| {
             if message.role != "assistant" {
@@ -697,11 +1121,13 @@ data: {"type":"message_stop"}"#.to_string(),
                         }
                     }
                     ConversationStreamUpdate::ToolApprovalRequest(request) => {
-                        let _ = request.response_tx.send(true);
+                        let _ = request.response_tx.send(true.into());
                     }
                     ConversationStreamUpdate::Delta(_)
                     | ConversationStreamUpdate::BlockDelta { .. }
-                    | ConversationStreamUpdate::BlockComplete { .. } => {}
+                    | ConversationStreamUpdate::BlockComplete { .. }
+                    | ConversationStreamUpdate::UserQuestion(_)
+                    | ConversationStreamUpdate::Status(_) => {}
                 }
             }
         }
@@ -749,7 +1175,7 @@ async fn test_openai_stream_tool_call_round_trip() -> Result<()> {
     let final_text = manager.send_message("Read file".into(), None).await?;
     assert!(final_text.contains("Hello from OpenAI stream."));
 
-    let messages = &manager.api_messages;
+    let messages = manager.messages_for_api();
     assert_eq!(messages.len(), 4);
     assert_eq!(messages[1].role, "assistant");
     if let Content::Blocks(blocks) = &messages[1].content {
@@ -795,7 +1221,7 @@ data: {"type":"message_stop"}"#.to_string(),
         while let Some(update) = rx.recv().await {
             if let ConversationStreamUpdate::ToolApprovalRequest(request) = update {
                 saw_approval_request = true;
-                let _ = request.response_tx.send(false);
+                let _ = request.response_tx.send(false.into());
             }
         }
         saw_approval_request
@@ -809,7 +1235,8 @@ data: {"type":"message_stop"}"#.to_string(),
     std::env::remove_var("VEX_TOOL_CONFIRM");
     assert!(saw_approval_request);
     assert!(final_text.contains("No changes were applied."));
-    let tool_result_message = manager
+    let transcript = manager.transcript();
+    let tool_result_message = transcript
         .api_messages
         .iter()
         .find(|message| {
@@ -833,6 +1260,446 @@ data: {"type":"message_stop"}"#.to_string(),
     Ok(())
 }
 
+#[tokio::test]
+async fn test_secret_guard_confirms_or_redacts_secrets_before_sending() -> Result<()> {
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            plain_text_round("msg_secret_01", "Noted."),
+            plain_text_round("msg_secret_02", "Noted again."),
+            plain_text_round("msg_secret_03", "Done."),
+        ])));
+    let temp = TempDir::new()?;
+    let decisions = Arc::new(std::sync::Mutex::new(vec![
+        ToolApprovalDecision::Denied { reason: None },
+        ToolApprovalDecision::Approved,
+    ]));
+    let queries = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let (next, seen) = (Arc::clone(&decisions), Arc::clone(&queries));
+    let mut manager = ConversationManager::new(
+        mock_api_client,
+        ToolOperator::new(temp.path().to_path_buf()),
+    )
+    .with_secret_guard(SecretGuard::new([(
+        "DEPLOY_TOKEN".to_string(),
+        "tok-0123456789".to_string(),
+    )]))
+    .with_approval_handler(move |query: ApprovalQuery| {
+        seen.lock()
+            .expect("queries")
+            .push((query.tool_name, query.tier));
+        let decision = next.lock().expect("decisions").remove(0);
+        async move { decision }
+    });
+    let user_texts = |manager: &ConversationManager| -> Vec<String> {
+        manager
+            .reader()
+            .messages()
+            .iter()
+            .filter(|message| message.role == "user")
+            .filter_map(|message| match &message.content {
+                Content::Text(text) => Some(text.clone()),
+                Content::Blocks(_) => None,
+            })
+            .collect()
+    };
+
+    manager
+        .send_message("deploy with tok-0123456789".to_string(), None)
+        .await?;
+    assert_eq!(
+        user_texts(&manager),
+        ["deploy with [redacted DEPLOY_TOKEN]"]
+    );
+
+    manager
+        .send_message("really, tok-0123456789".to_string(), None)
+        .await?;
+    manager
+        .send_message("and tok-0123456789 once more".to_string(), None)
+        .await?;
+    assert!(user_texts(&manager)[1..]
+        .iter()
+        .all(|text| text.contains("tok-0123456789")));
+    // Approved once, the secret is not asked about again this session.
+    assert_eq!(
+        *queries.lock().expect("queries"),
+        vec![
+            (
+                SEND_SECRETS_APPROVAL.to_string(),
+                ApprovalTier::Secrets {
+                    sources: vec!["DEPLOY_TOKEN".to_string()]
+                }
+            );
+            2
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_secret_guard_covers_memories_uploads_and_the_system_prompt() -> Result<()> {
+    let _env_lock = crate::test_support::ENV_LOCK.lock().await;
+    std::env::remove_var("VEX_MEMORY_RECALL_K");
+    let guard = || SecretGuard::new([("DEPLOY_TOKEN".to_string(), "tok-0123456789".to_string())]);
+    let temp = TempDir::new()?;
+    let executor = ToolOperator::new(temp.path().to_path_buf());
+    executor.remember("deploys authenticate with tok-0123456789")?;
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            plain_text_round("msg_secret_upload_01", "Noted."),
+        ])));
+    let mut manager =
+        ConversationManager::new(mock_api_client, executor).with_secret_guard(guard());
+    let redacted_log = "deploy [redacted DEPLOY_TOKEN]\n";
+    manager.queue_documents(vec![crate::api::files::PendingDocument {
+        title: "deploy.log".to_string(),
+        content: "deploy tok-0123456789\n".to_string(),
+    }]);
+    manager
+        .send_message("how do deploys work".to_string(), None)
+        .await?;
+
+    let Content::Blocks(blocks) = manager.reader().messages()[0].content.clone() else {
+        panic!("expected document and text blocks");
+    };
+    // The mock names an upload after its length, so this is the redacted one.
+    assert!(matches!(
+        &blocks[0],
+        ContentBlock::Document {
+            source: crate::types::DocumentSource::File { file_id },
+            ..
+        } if *file_id == format!("file_mock_{}", redacted_log.len())
+    ));
+    assert!(matches!(
+        &blocks[1],
+        ContentBlock::Text { text }
+            if text.contains("deploys authenticate with [redacted DEPLOY_TOKEN]")
+    ));
+
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            plain_text_round("msg_secret_prompt_01", "Noted."),
+        ])))
+        .with_response_language(crate::language::ResponseLanguage::parse("tok-0123456789"));
+    let mut manager = ConversationManager::new(
+        mock_api_client,
+        ToolOperator::new(temp.path().to_path_buf()),
+    )
+    .with_secret_guard(guard());
+    let error = manager
+        .send_message("hello".to_string(), None)
+        .await
+        .expect_err("a secret in the system prompt cannot be redacted");
+    assert!(error
+        .to_string()
+        .contains("system prompt contains the value of DEPLOY_TOKEN"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_approval_handler_decides_instead_of_the_frontend() -> Result<()> {
+    let first_response_sse = vec![
+        r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_handler_01","name":"write_file","input":{"path":"notes.txt","content":"hello\n"}}}"#.to_string(),
+        r#"event: content_block_stop
+data: {"type":"content_block_stop","index":0}"#.to_string(),
+        r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":4}}"#.to_string(),
+        r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+    ];
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            first_response_sse,
+            plain_text_round("msg_handler_02", "Understood."),
+        ])));
+    let temp = TempDir::new()?;
+    std::fs::write(temp.path().join("notes.txt"), "kept\n")?;
+    let queries = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let seen = Arc::clone(&queries);
+    let mut manager = ConversationManager::new(
+        mock_api_client,
+        ToolOperator::new(temp.path().to_path_buf()),
+    )
+    .with_approval_handler(move |query: ApprovalQuery| {
+        seen.lock().expect("queries").push((
+            query.tool_call_id,
+            query.tool_name,
+            query.input["path"].clone(),
+            query.class,
+            query.paths.len(),
+        ));
+        async {
+            ToolApprovalDecision::Denied {
+                reason: Some("policy: no writes".to_string()),
+            }
+        }
+    });
+
+    // No update channel: the handler still decides.
+    manager
+        .send_message("write notes.txt".to_string(), None)
+        .await?;
+
+    // Overwriting an existing file is classed as destructive.
+    assert_eq!(
+        *queries.lock().expect("queries"),
+        vec![(
+            Some("toolu_handler_01".to_string()),
+            "write_file".to_string(),
+            json!("notes.txt"),
+            ToolClass::Destructive,
+            1
+        )]
+    );
+    assert_eq!(
+        std::fs::read_to_string(temp.path().join("notes.txt"))?,
+        "kept\n"
+    );
+    let denial = manager
+        .reader()
+        .messages()
+        .iter()
+        .find_map(|message| match &message.content {
+            Content::Blocks(blocks) => blocks.iter().find_map(|block| match block {
+                ContentBlock::ToolResult { content, .. } => Some(content.clone()),
+                _ => None,
+            }),
+            Content::Text(_) => None,
+        })
+        .expect("denial recorded");
+    assert!(denial.contains("User reason: policy: no writes"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_session_auto_approve_skips_waiting_approval_state() -> Result<()> {
+    let _env_lock = crate::test_support::ENV_LOCK.lock().await;
+    std::env::set_var("VEX_TOOL_CONFIRM", "on");
+
+    let first_response_sse = vec![
+        r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_auto_01","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string(),
+        r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_auto_01","name":"write_file","input":{"path":"notes.txt","content":"hello\n"}}}"#.to_string(),
+        r#"event: content_block_stop
+data: {"type":"content_block_stop","index":0}"#.to_string(),
+        r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":4}}"#.to_string(),
+        r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+    ];
+    let second_response_sse = plain_text_round("msg_auto_02", "Wrote notes.txt.");
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            first_response_sse,
+            second_response_sse,
+        ])));
+    let temp = TempDir::new()?;
+    let audit_log = crate::audit::AuditLog::new(temp.path().join("audit.jsonl"));
+    let mut manager = ConversationManager::new(
+        mock_api_client,
+        ToolOperator::new(temp.path().to_path_buf()),
+    )
+    .with_audit_log(Some(audit_log.clone()));
+    manager.approval_policy().set_session_auto_approve(true);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    manager
+        .send_message("write notes.txt".to_string(), Some(&tx))
+        .await?;
+    drop(tx);
+    std::env::remove_var("VEX_TOOL_CONFIRM");
+
+    let mut statuses = Vec::new();
+    while let Ok(update) = rx.try_recv() {
+        match update {
+            ConversationStreamUpdate::ToolApprovalRequest(_) => {
+                panic!("auto-approved tools must not request approval")
+            }
+            ConversationStreamUpdate::BlockStart {
+                block: StreamBlock::ToolCall { id, status, .. },
+                ..
+            } if id == "toolu_auto_01" => statuses.push(status.label()),
+            _ => {}
+        }
+    }
+    statuses.dedup();
+    assert_eq!(statuses, vec!["preparing", "running", "done"]);
+    assert_eq!(
+        std::fs::read_to_string(temp.path().join("notes.txt"))?,
+        "hello\n"
+    );
+    let records = audit_log.read()?;
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].tool, "write_file");
+    assert_eq!(records[0].approval, crate::audit::ApprovalSource::Auto);
+    assert_eq!(records[0].outcome, "ok");
+    let changes = manager
+        .reader()
+        .latest_turn_changes()
+        .expect("write recorded in the turn summary");
+    assert_eq!(changes.turn, 1);
+    assert_eq!(
+        changes.render_lines(),
+        [
+            "[changes this turn: 1 file (+1 -0)]",
+            "  created  notes.txt  +1 -0"
+        ]
+    );
+    assert_eq!(
+        records[0].paths,
+        vec![std::fs::canonicalize(temp.path())?
+            .join("notes.txt")
+            .display()
+            .to_string()]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sensitive_path_prompts_despite_session_auto_approve() -> Result<()> {
+    let first_response_sse = vec![
+        r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_sensitive_01","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string(),
+        r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_sensitive_01","name":"edit_file","input":{"path":"crates/core/Cargo.toml","old_str":"a","new_str":"b"}}}"#.to_string(),
+        r#"event: content_block_stop
+data: {"type":"content_block_stop","index":0}"#.to_string(),
+        r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+    ];
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            first_response_sse,
+            plain_text_round("msg_sensitive_02", "Left Cargo.toml alone."),
+        ])));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+    manager.approval_policy().set_session_auto_approve(true);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let approval_task = tokio::spawn(async move {
+        let mut tiers = Vec::new();
+        while let Some(update) = rx.recv().await {
+            if let ConversationStreamUpdate::ToolApprovalRequest(request) = update {
+                tiers.push(request.tier.clone());
+                let _ = request.response_tx.send(false.into());
+            }
+        }
+        tiers
+    });
+    manager
+        .send_message("bump the version".to_string(), Some(&tx))
+        .await?;
+    drop(tx);
+
+    assert_eq!(
+        approval_task.await?,
+        vec![ApprovalTier::Sensitive {
+            path: "crates/core/Cargo.toml".to_string(),
+            pattern: "Cargo.toml".to_string(),
+        }]
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sensitive_tier_follows_symlinks_in_the_workspace() -> Result<()> {
+    let temp = TempDir::new()?;
+    std::fs::create_dir_all(temp.path().join(".github/workflows"))?;
+    std::fs::write(temp.path().join(".github/workflows/ci.yml"), "on: push\n")?;
+    std::os::unix::fs::symlink(".github/workflows/ci.yml", temp.path().join("ci.yml"))?;
+    std::os::unix::fs::symlink(".github/workflows", temp.path().join("flows"))?;
+    let manager = ConversationManager::new(
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(
+            vec![],
+        ))),
+        ToolOperator::new(temp.path().to_path_buf()),
+    );
+
+    for path in ["ci.yml", "./flows/new.yml"] {
+        assert_eq!(
+            manager.tool_approval_tier("write_file", &json!({"path": path, "content": ""})),
+            ApprovalTier::Sensitive {
+                path: path.to_string(),
+                pattern: ".github/workflows/**".to_string(),
+            }
+        );
+    }
+    assert_eq!(
+        manager.tool_approval_tier("write_file", &json!({"path": "src/ci.yml", "content": ""})),
+        ApprovalTier::Standard
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_denial_reason_is_forwarded_in_tool_result() -> Result<()> {
+    let _env_lock = crate::test_support::ENV_LOCK.lock().await;
+    std::env::set_var("VEX_TOOL_CONFIRM", "off");
+
+    let first_response_sse = vec![
+        r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_reason_01","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string(),
+        r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_reason_01","name":"write_file","input":{"path":"migrations/001.sql","content":"drop table users;\n"}}}"#.to_string(),
+        r#"event: content_block_stop
+data: {"type":"content_block_stop","index":0}"#.to_string(),
+        r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":4}}"#.to_string(),
+        r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+    ];
+    let second_response_sse = plain_text_round("msg_reason_02", "Understood.");
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            first_response_sse,
+            second_response_sse,
+        ])));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let approval_task = tokio::spawn(async move {
+        while let Some(update) = rx.recv().await {
+            if let ConversationStreamUpdate::ToolApprovalRequest(request) = update {
+                let _ = request
+                    .response_tx
+                    .send(ToolApprovalDecision::denied_with_reason(
+                        "don't touch the migrations folder",
+                    ));
+            }
+        }
+    });
+    manager
+        .send_message("reset the schema".to_string(), Some(&tx))
+        .await?;
+    drop(tx);
+    approval_task.await?;
+    std::env::remove_var("VEX_TOOL_CONFIRM");
+
+    let transcript = manager.transcript();
+
+    let denied_content = transcript
+        .api_messages
+        .iter()
+        .find_map(|message| match &message.content {
+            Content::Blocks(blocks) => blocks.iter().find_map(|block| match block {
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    is_error: true,
+                } if tool_use_id == "toolu_reason_01" => Some(content.clone()),
+                _ => None,
+            }),
+            Content::Text(_) => None,
+        })
+        .expect("expected denied tool_result in history");
+    assert!(denied_content.contains("approval denied for write_file"));
+    assert!(denied_content.contains("User reason: don't touch the migrations folder"));
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_edit_file_missing_path_returns_clarification_instead_of_looping() -> Result<()> {
     let first_response_sse = vec![
@@ -861,7 +1728,8 @@ data: {"type":"message_stop"}"#.to_string(),
         .send_message("please edit".to_string(), None)
         .await?;
     assert!(final_text.contains("target file path"));
-    let tool_result_message = manager
+    let transcript = manager.transcript();
+    let tool_result_message = transcript
         .api_messages
         .iter()
         .find(|message| {
@@ -928,6 +1796,158 @@ data: {"type":"message_stop"}"#.to_string(),
     Ok(())
 }
 
+#[test]
+fn test_transient_tool_errors_and_failure_streaks() {
+    let busy = anyhow::anyhow!(
+        "git add failed: fatal: Unable to create '/w/.git/index.lock': File exists."
+    );
+    assert!(is_transient_tool_error("git_add", &busy));
+    let timeout = anyhow::anyhow!("Tool execution timed out after 30s for read_file");
+    assert!(is_transient_tool_error("read_file", &timeout));
+    let edit_timeout = anyhow::anyhow!("Tool execution timed out after 30s for edit_file");
+    assert!(!is_transient_tool_error("edit_file", &edit_timeout));
+    let custom_timeout = anyhow::anyhow!("Tool execution timed out after 30s for deploy");
+    assert!(!is_transient_tool_error("deploy", &custom_timeout));
+    assert!(!is_transient_tool_error(
+        "read_file",
+        &anyhow::anyhow!("Failed to read file")
+    ));
+
+    let mut streaks = HashMap::new();
+    assert!(record_tool_failure_streak(&mut streaks, "read_file", false).is_none());
+    assert!(record_tool_failure_streak(&mut streaks, "read_file", true).is_none());
+    assert!(record_tool_failure_streak(&mut streaks, "read_file", false).is_none());
+    assert!(record_tool_failure_streak(&mut streaks, "git_diff", false).is_none());
+    assert!(record_tool_failure_streak(&mut streaks, "read_file", false).is_none());
+    let warning = record_tool_failure_streak(&mut streaks, "read_file", false)
+        .expect("third consecutive failure warns");
+    assert!(warning.contains("read_file has failed 3 times in a row"));
+    assert!(record_tool_failure_streak(&mut streaks, "read_file", false).is_none());
+}
+
+fn failing_read_round(message_id: &str, path: &str) -> Vec<String> {
+    vec![
+        format!(
+            "event: message_start\ndata: {{\"type\":\"message_start\",\"message\":{{\"id\":\"{message_id}\",\"type\":\"message\",\"role\":\"assistant\",\"model\":\"mock-model\",\"content\":[],\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{{\"input_tokens\":10,\"output_tokens\":1}}}}}}"
+        ),
+        format!(
+            "event: content_block_start\ndata: {{\"type\":\"content_block_start\",\"index\":0,\"content_block\":{{\"type\":\"tool_use\",\"id\":\"toolu_{message_id}\",\"name\":\"read_file\",\"input\":{{\"path\":\"{path}\"}}}}}}"
+        ),
+        r#"event: content_block_stop
+data: {"type":"content_block_stop","index":0}"#.to_string(),
+        r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+    ]
+}
+
+#[tokio::test]
+async fn test_repeated_tool_failures_add_warning_for_model() -> Result<()> {
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            failing_read_round("msg_streak_01", "a.rs"),
+            failing_read_round("msg_streak_02", "b.rs"),
+            failing_read_round("msg_streak_03", "c.rs"),
+            plain_text_round("msg_streak_04", "Giving up on reads."),
+        ])));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+
+    let final_text = manager
+        .send_message("read the files".to_string(), None)
+        .await?;
+    assert!(final_text.contains("Giving up on reads."));
+
+    let warnings: Vec<String> = manager
+        .messages_for_api()
+        .into_iter()
+        .filter_map(|message| match message.content {
+            Content::Blocks(blocks) => Some(blocks),
+            Content::Text(_) => None,
+        })
+        .flatten()
+        .filter_map(|block| match block {
+            ContentBlock::Text { text } if text.starts_with("[tool warning]") => Some(text),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("read_file has failed 3 times"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_queued_tool_calls_are_announced_before_first_approval() -> Result<()> {
+    let _env_lock = crate::test_support::ENV_LOCK.lock().await;
+    std::env::set_var("VEX_TOOL_CONFIRM", "on");
+
+    let first_response_sse = vec![
+        r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_queue_01","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string(),
+        r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_queue_a","name":"write_file","input":{"path":"a.txt","content":"a\n"}}}"#.to_string(),
+        r#"event: content_block_stop
+data: {"type":"content_block_stop","index":0}"#.to_string(),
+        r#"event: content_block_start
+data: {"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_queue_b","name":"write_file"}}"#.to_string(),
+        r#"event: content_block_delta
+data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"path\":\"b.txt\",\"content\":\"b\\n\"}"}}"#.to_string(),
+        r#"event: content_block_stop
+data: {"type":"content_block_stop","index":1}"#.to_string(),
+        r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":6}}"#.to_string(),
+        r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+    ];
+    let second_response_sse = plain_text_round("msg_queue_02", "Stopped.");
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            first_response_sse,
+            second_response_sse,
+        ])));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let approval_task = tokio::spawn(async move {
+        let mut queued_path_at_first_approval = None;
+        let mut latest_b_input = None;
+        while let Some(update) = rx.recv().await {
+            match update {
+                ConversationStreamUpdate::BlockStart {
+                    block:
+                        StreamBlock::ToolCall {
+                            id, input, status, ..
+                        },
+                    ..
+                } if id == "toolu_queue_b" && status == ToolStatus::Pending => {
+                    latest_b_input = Some(input);
+                }
+                ConversationStreamUpdate::ToolApprovalRequest(request) => {
+                    if queued_path_at_first_approval.is_none() {
+                        queued_path_at_first_approval = Some(
+                            latest_b_input
+                                .as_ref()
+                                .and_then(|input| input.get("path"))
+                                .and_then(|path| path.as_str())
+                                .map(str::to_string),
+                        );
+                    }
+                    let _ = request.response_tx.send(false.into());
+                }
+                _ => {}
+            }
+        }
+        queued_path_at_first_approval
+    });
+    manager
+        .send_message("write both files".to_string(), Some(&tx))
+        .await?;
+    drop(tx);
+    let queued_path = approval_task.await?;
+    std::env::remove_var("VEX_TOOL_CONFIRM");
+
+    assert_eq!(queued_path, Some(Some("b.txt".to_string())));
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_multi_tool_round_collects_results_after_approval_denial() -> Result<()> {
     let _env_lock = crate::test_support::ENV_LOCK.lock().await;
@@ -965,7 +1985,7 @@ data: {"type":"message_stop"}"#.to_string(),
         while let Some(update) = rx.recv().await {
             if let ConversationStreamUpdate::ToolApprovalRequest(request) = update {
                 saw_approval_request = true;
-                let _ = request.response_tx.send(false);
+                let _ = request.response_tx.send(false.into());
             }
         }
         saw_approval_request
@@ -983,7 +2003,9 @@ data: {"type":"message_stop"}"#.to_string(),
     );
     assert!(final_text.contains("Handled both tool outcomes."));
 
-    let tool_result_message = manager
+    let transcript = manager.transcript();
+
+    let tool_result_message = transcript
         .api_messages
         .iter()
         .find(|message| {
@@ -1015,6 +2037,155 @@ data: {"type":"message_stop"}"#.to_string(),
     Ok(())
 }
 
+#[tokio::test]
+async fn test_identical_tool_calls_in_one_round_run_once() -> Result<()> {
+    let tool_use = |index: usize, id: &str| {
+        vec![
+            format!(
+                r#"event: content_block_start
+data: {{"type":"content_block_start","index":{index},"content_block":{{"type":"tool_use","id":"{id}","name":"read_file","input":{{"path":"file.txt"}}}}}}"#
+            ),
+            format!(
+                r#"event: content_block_stop
+data: {{"type":"content_block_stop","index":{index}}}"#
+            ),
+        ]
+    };
+    let mut first_response_sse = vec![r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_dup_01","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string()];
+    first_response_sse.extend(tool_use(0, "toolu_dup_a"));
+    first_response_sse.extend(tool_use(1, "toolu_dup_b"));
+    first_response_sse.push(
+        r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":6}}"#
+            .to_string(),
+    );
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            first_response_sse,
+            plain_text_round("msg_dup_02", "Done."),
+        ])));
+    let mut mock_tool_responses = HashMap::new();
+    mock_tool_responses.insert("file.txt".to_string(), "hello".to_string());
+    let mut manager = ConversationManager::new_mock(mock_api_client, mock_tool_responses);
+    let mut tool_events = manager
+        .event_bus()
+        .subscribe(&[crate::events::EventKind::Tool]);
+
+    manager.send_message("read it".to_string(), None).await?;
+
+    let mut started = 0;
+    while let Some(event) = tool_events.try_recv() {
+        if matches!(*event, crate::events::RuntimeEvent::ToolStarted { .. }) {
+            started += 1;
+        }
+    }
+    assert_eq!(started, 1);
+    let transcript = manager.transcript();
+    let Some(Content::Blocks(blocks)) = transcript
+        .api_messages
+        .iter()
+        .find(|message| message_contains_tool_result(message))
+        .map(|message| &message.content)
+    else {
+        panic!("expected tool_result blocks");
+    };
+    let results: Vec<(&str, &str)> = blocks
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::ToolResult {
+                tool_use_id,
+                content,
+                is_error: false,
+            } => Some((tool_use_id.as_str(), content.as_str())),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].0, "toolu_dup_a");
+    assert_eq!(results[1].0, "toolu_dup_b");
+    assert!(results[1].1.starts_with(results[0].1));
+    assert!(results[1].1.ends_with(
+        "[duplicate of tool call toolu_dup_a in this round; it ran once and this is its result]"
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_repeated_call_after_a_mutation_is_not_a_duplicate() {
+    use super::tools::{find_duplicate_tool_call, RoundToolResult};
+    let result = |id: &str, name: &str, input: serde_json::Value, mutated: bool| RoundToolResult {
+        id: id.to_string(),
+        name: name.to_string(),
+        input,
+        content: String::new(),
+        is_error: false,
+        mutated,
+    };
+    let read = json!({"path": "file.txt"});
+    let mut results = vec![result("toolu_read", "read_file", read.clone(), false)];
+    assert_eq!(
+        find_duplicate_tool_call(&results, "read_file", &read).map(|r| r.id.as_str()),
+        Some("toolu_read")
+    );
+
+    results.push(result(
+        "toolu_denied",
+        "write_file",
+        json!({"path": "file.txt", "content": "x"}),
+        false,
+    ));
+    assert!(find_duplicate_tool_call(&results, "read_file", &read).is_some());
+
+    results.push(result(
+        "toolu_write",
+        "write_file",
+        json!({"path": "file.txt", "content": "y"}),
+        true,
+    ));
+    assert!(find_duplicate_tool_call(&results, "read_file", &read).is_none());
+
+    results.push(result("toolu_reread", "read_file", read.clone(), false));
+    assert_eq!(
+        find_duplicate_tool_call(&results, "read_file", &read).map(|r| r.id.as_str()),
+        Some("toolu_reread")
+    );
+}
+
+#[tokio::test]
+async fn test_reply_in_the_wrong_language_is_rewritten_once() -> Result<()> {
+    let _env_lock = crate::test_support::ENV_LOCK.lock().await;
+    std::env::set_var(crate::language::LANGUAGE_RETRY_ENV, "on");
+    let english = "The branch is not reachable, so the early return is dead and can be removed.";
+    let german = "Der Zweig ist nicht erreichbar, und die frühe Rückkehr ist toter Code, den man entfernen kann.";
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            plain_text_round("msg_lang_01", english),
+            plain_text_round("msg_lang_02", german),
+        ])))
+        .with_response_language(crate::language::ResponseLanguage::parse("de"));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+
+    let reply = manager
+        .send_message("why is the early return dead?".to_string(), None)
+        .await;
+    std::env::remove_var(crate::language::LANGUAGE_RETRY_ENV);
+
+    assert_eq!(reply?, german);
+    let transcript = manager.transcript();
+    let correction = transcript
+        .api_messages
+        .iter()
+        .filter(|message| message.role == "user")
+        .find_map(|message| match &message.content {
+            Content::Text(text) if text.starts_with("Your last reply") => Some(text.clone()),
+            _ => None,
+        })
+        .expect("correction request");
+    assert!(correction.contains("written in English. Rewrite it in German"));
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_read_only_request_blocks_mutating_tool_without_approval_prompt() -> Result<()> {
     let _env_lock = crate::test_support::ENV_LOCK.lock().await;
@@ -1066,7 +2237,9 @@ data: {"type":"message_stop"}"#.to_string(),
     );
     assert!(final_text.contains("Read-only request handled"));
 
-    let tool_result_message = manager
+    let transcript = manager.transcript();
+
+    let tool_result_message = transcript
         .api_messages
         .iter()
         .find(|message| {
@@ -1109,7 +2282,7 @@ async fn test_git_tool_capability_query_short_circuits_without_api_round() -> Re
     assert!(response.contains("git_add"));
     assert!(response.contains("git_commit"));
     assert_eq!(
-        manager.api_messages.len(),
+        manager.transcript().api_messages.len(),
         2,
         "capability response should not call API or create extra rounds"
     );
@@ -1161,7 +2334,7 @@ data: {"type":"message_stop"}"#.to_string(),
     let final_text = manager.send_message("Read file".into(), None).await?;
     assert!(final_text.contains("Tool result consumed."));
 
-    let messages = &manager.api_messages;
+    let messages = manager.messages_for_api();
     assert!(
         messages.iter().any(|message| {
             if message.role != "assistant" {
@@ -1247,7 +2420,9 @@ data: {"type":"message_stop"}"#.to_string(),
         .await?;
     assert!(final_text.contains("Tool-backed summary complete."));
 
-    let correction_count = manager
+    let transcript = manager.transcript();
+
+    let correction_count = transcript
         .api_messages
         .iter()
         .filter(|message| {
@@ -1322,7 +2497,9 @@ data: {"type":"message_stop"}"#.to_string(),
         "tool-evidence-required prompts must return guard text when model stays toolless"
     );
 
-    let correction_count = manager
+    let transcript = manager.transcript();
+
+    let correction_count = transcript
         .api_messages
         .iter()
         .filter(|message| {
@@ -1357,7 +2534,9 @@ async fn test_repeated_read_only_round_injects_nudge_then_recovers() -> Result<(
     let final_text = manager.send_message("read file".to_string(), None).await?;
     assert!(final_text.contains("Done after loop correction."));
 
-    let nudge_count = manager
+    let transcript = manager.transcript();
+
+    let nudge_count = transcript
         .api_messages
         .iter()
         .filter(|message| {
@@ -1529,6 +2708,7 @@ async fn test_execute_tool_edit_file_accepts_alias_argument_names() -> Result<()
         )
         .await?;
     assert!(result.contains("Updated snippet in src/calculator.rs"));
+    assert!(result.ends_with("  1 - 1\n  1 + 2"), "{result}");
 
     let updated = std::fs::read_to_string(&target)?;
     assert!(updated.contains("2"));
@@ -1607,7 +2787,7 @@ fn test_prune_message_history_reanchors_to_user() {
     let executor = ToolOperator::new(std::path::PathBuf::from("."));
     let mut manager = ConversationManager::new(mock_api_client, executor);
 
-    manager.api_messages = vec![
+    manager.transcript().api_messages = vec![
         ApiMessage {
             role: "user".to_string(),
             content: Content::Text("u0".to_string()),
@@ -1632,9 +2812,132 @@ fn test_prune_message_history_reanchors_to_user() {
 
     manager.prune_message_history(3);
 
-    assert_eq!(manager.api_messages.len(), 2);
-    assert_eq!(manager.api_messages[0].role, "user");
-    assert_eq!(manager.api_messages[1].role, "assistant");
+    assert_eq!(manager.transcript().api_messages.len(), 2);
+    assert_eq!(manager.transcript().api_messages[0].role, "user");
+    assert_eq!(manager.transcript().api_messages[1].role, "assistant");
+}
+
+#[test]
+fn test_history_fits_token_budget_by_eliding_then_dropping_old_turns() {
+    let mock_api_client = ApiClient::new_mock(Arc::new(
+        crate::api::mock_client::MockApiClient::new(vec![]),
+    ));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+    let text = |role: &str, body: &str| ApiMessage {
+        role: role.to_string(),
+        content: Content::Text(body.to_string()),
+    };
+    let log = format!(
+        "Ran cargo test: 3 failed\n{}",
+        "error at line 12\n".repeat(200)
+    );
+    manager.transcript().api_messages = vec![
+        text("user", "run the tests"),
+        ApiMessage {
+            role: "assistant".to_string(),
+            content: Content::Blocks(vec![ContentBlock::ToolUse {
+                id: "t1".to_string(),
+                name: "run_tests".to_string(),
+                input: json!({}),
+            }]),
+        },
+        ApiMessage {
+            role: "user".to_string(),
+            content: Content::Blocks(vec![ContentBlock::ToolResult {
+                tool_use_id: "t1".to_string(),
+                content: log,
+                is_error: false,
+            }]),
+        },
+        text("assistant", "Three tests fail."),
+        text("user", "fix the first one"),
+        text("assistant", "Fixed."),
+        text("user", "now the second"),
+    ];
+
+    // Eliding the old log is enough; every message stays.
+    let anchor = manager.fit_history_to_token_budget(200, 6);
+    assert_eq!(anchor, 6);
+    let messages = manager.transcript().api_messages.clone();
+    assert_eq!(messages.len(), 7);
+    let Content::Blocks(blocks) = &messages[2].content else {
+        panic!("tool result blocks");
+    };
+    let ContentBlock::ToolResult { content, .. } = &blocks[0] else {
+        panic!("tool result");
+    };
+    assert!(content.starts_with("Ran cargo test: 3 failed\n[result elided to fit"));
+
+    // A tighter budget drops whole exchanges, keeping a prompt first and
+    // never touching the current turn.
+    let anchor = manager.fit_history_to_token_budget(40, 6);
+    assert_eq!(anchor, 2);
+    let messages = manager.transcript().api_messages.clone();
+    assert_eq!(messages.len(), 3);
+    assert!(matches!(&messages[0].content, Content::Text(text) if text == "fix the first one"));
+    assert_eq!(manager.fit_history_to_token_budget(0, 2), 0);
+    assert_eq!(manager.transcript().api_messages.len(), 1);
+
+    assert_eq!(
+        apply_context_tokens_spec(200_000, "local/qwen", "local/=32768,local/qwen3=40960"),
+        32_768
+    );
+    assert_eq!(
+        apply_context_tokens_spec(200_000, "local/qwen3-coder", "16000,local/qwen3=40960"),
+        40_960
+    );
+    assert_eq!(
+        apply_context_tokens_spec(200_000, "claude-opus", "16000,local/=8192"),
+        16_000
+    );
+    assert_eq!(apply_context_tokens_spec(200_000, "claude-opus", "0"), 0);
+    assert_eq!(
+        apply_context_tokens_spec(200_000, "claude-opus", "lots"),
+        200_000
+    );
+}
+
+#[test]
+fn test_elided_read_is_sent_in_full_when_read_again() {
+    let mock_api_client = ApiClient::new_mock(Arc::new(
+        crate::api::mock_client::MockApiClient::new(vec![]),
+    ));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+    let input = json!({ "path": "big.rs" });
+    let body = "fn line() {}\n".repeat(200);
+    let first =
+        manager.format_tool_result_for_history("read_file", "r1", &input, &Ok(body.clone()));
+    manager.transcript().api_messages = vec![
+        ApiMessage {
+            role: "user".to_string(),
+            content: Content::Text("read big.rs".to_string()),
+        },
+        ApiMessage {
+            role: "assistant".to_string(),
+            content: Content::Blocks(vec![ContentBlock::ToolUse {
+                id: "r1".to_string(),
+                name: "read_file".to_string(),
+                input: input.clone(),
+            }]),
+        },
+        ApiMessage {
+            role: "user".to_string(),
+            content: Content::Blocks(vec![ContentBlock::ToolResult {
+                tool_use_id: "r1".to_string(),
+                content: first,
+                is_error: false,
+            }]),
+        },
+        ApiMessage {
+            role: "user".to_string(),
+            content: Content::Text("read it again".to_string()),
+        },
+    ];
+
+    manager.fit_history_to_token_budget(100, 3);
+    let again = manager.format_tool_result_for_history("read_file", "r2", &input, &Ok(body));
+    assert!(again.contains("Content for model context:"), "{again}");
+    assert!(!again.contains("No changes since last read"));
 }
 
 #[test]
@@ -1644,7 +2947,7 @@ fn test_prune_message_history_preserving_keeps_turn_user_anchor() {
     ));
     let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
 
-    manager.api_messages = vec![
+    manager.transcript().api_messages = vec![
         ApiMessage {
             role: "user".to_string(),
             content: Content::Text("anchor user prompt".to_string()),
@@ -1679,7 +2982,7 @@ fn test_prune_message_history_preserving_keeps_turn_user_anchor() {
     );
     assert!(
         matches!(
-            &manager.api_messages.first().map(|m| (&m.role, &m.content)),
+            &manager.transcript().api_messages.first().map(|m| (&m.role, &m.content)),
             Some((role, Content::Text(text))) if role.as_str() == "user" && text == "anchor user prompt"
         ),
         "turn anchor user prompt must be preserved during loop pruning"
@@ -1693,7 +2996,7 @@ fn test_prune_message_history_preserving_allows_prune_when_anchor_is_far_behind(
     ));
     let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
 
-    manager.api_messages = vec![
+    manager.transcript().api_messages = vec![
         ApiMessage {
             role: "user".to_string(),
             content: Content::Text("anchor user prompt".to_string()),
@@ -1730,16 +3033,16 @@ fn test_prune_message_history_preserving_allows_prune_when_anchor_is_far_behind(
 
     let new_anchor = manager.prune_message_history_preserving(4, 0);
     assert_eq!(
-        manager.api_messages.len(),
+        manager.transcript().api_messages.len(),
         4,
         "pruning should proceed when anchor is far behind target window"
     );
     assert_eq!(new_anchor, 0);
-    assert_eq!(manager.api_messages[0].role, "user");
-    match &manager.api_messages[0].content {
+    assert_eq!(manager.transcript().api_messages[0].role, "user");
+    match &manager.transcript().api_messages[0].content {
         Content::Text(text) => assert_eq!(text, "u2"),
         _ => panic!("expected user text content"),
-    }
+    };
 }
 
 #[test]
@@ -1793,7 +3096,7 @@ fn test_prune_message_history_clears_if_no_user_remains() {
     let executor = ToolOperator::new(std::path::PathBuf::from("."));
     let mut manager = ConversationManager::new(mock_api_client, executor);
 
-    manager.api_messages = vec![
+    manager.transcript().api_messages = vec![
         ApiMessage {
             role: "user".to_string(),
             content: Content::Text("u0".to_string()),
@@ -1813,7 +3116,7 @@ fn test_prune_message_history_clears_if_no_user_remains() {
     ];
 
     manager.prune_message_history(2);
-    assert!(manager.api_messages.is_empty());
+    assert!(manager.transcript().api_messages.is_empty());
 }
 
 #[test]
@@ -1824,7 +3127,7 @@ fn test_prune_message_history_reanchors_even_if_it_reduces_below_limit() {
     let executor = ToolOperator::new(std::path::PathBuf::from("."));
     let mut manager = ConversationManager::new(mock_api_client, executor);
 
-    manager.api_messages = vec![
+    manager.transcript().api_messages = vec![
         ApiMessage {
             role: "user".to_string(),
             content: Content::Text("u0".to_string()),
@@ -1845,13 +3148,13 @@ fn test_prune_message_history_reanchors_even_if_it_reduces_below_limit() {
 
     manager.prune_message_history(3);
 
-    assert_eq!(manager.api_messages.len(), 2);
-    assert_eq!(manager.api_messages[0].role, "user");
-    if let Content::Text(text) = &manager.api_messages[0].content {
+    assert_eq!(manager.transcript().api_messages.len(), 2);
+    assert_eq!(manager.transcript().api_messages[0].role, "user");
+    if let Content::Text(text) = &manager.transcript().api_messages[0].content {
         assert_eq!(text, "u1");
     } else {
         panic!("expected user text content");
-    }
+    };
 }
 
 #[test]
@@ -1862,7 +3165,7 @@ fn test_prune_message_history_skips_leading_tool_result_user_message() {
     let executor = ToolOperator::new(std::path::PathBuf::from("."));
     let mut manager = ConversationManager::new(mock_api_client, executor);
 
-    manager.api_messages = vec![
+    manager.transcript().api_messages = vec![
         ApiMessage {
             role: "user".to_string(),
             content: Content::Text("u0".to_string()),
@@ -1899,12 +3202,12 @@ fn test_prune_message_history_skips_leading_tool_result_user_message() {
 
     manager.prune_message_history(4);
 
-    assert_eq!(manager.api_messages.len(), 2);
-    assert_eq!(manager.api_messages[0].role, "user");
-    match &manager.api_messages[0].content {
+    assert_eq!(manager.transcript().api_messages.len(), 2);
+    assert_eq!(manager.transcript().api_messages[0].role, "user");
+    match &manager.transcript().api_messages[0].content {
         Content::Text(text) => assert_eq!(text, "u1"),
         _ => panic!("expected first retained message to be user text, not tool_result"),
-    }
+    };
 }
 
 #[test]
@@ -1915,7 +3218,7 @@ fn test_prune_message_history_clears_if_only_tool_result_user_messages_remain()
     let executor = ToolOperator::new(std::path::PathBuf::from("."));
     let mut manager = ConversationManager::new(mock_api_client, executor);
 
-    manager.api_messages = vec![
+    manager.transcript().api_messages = vec![
         ApiMessage {
             role: "assistant".to_string(),
             content: Content::Text("a0".to_string()),
@@ -1936,5 +3239,512 @@ fn test_prune_message_history_clears_if_only_tool_result_user_messages_remain()
 
     manager.prune_message_history(2);
 
-    assert!(manager.api_messages.is_empty());
+    assert!(manager.transcript().api_messages.is_empty());
+}
+
+#[tokio::test]
+async fn test_recalled_memories_are_attached_to_user_message() -> Result<()> {
+    let _env_lock = crate::test_support::ENV_LOCK.lock().await;
+    std::env::remove_var("VEX_MEMORY_RECALL_K");
+    let temp = TempDir::new()?;
+    let executor = ToolOperator::new(temp.path().to_path_buf());
+    executor.remember("Database migrations must stay append-only")?;
+    executor.remember("Use anyhow for error handling")?;
+
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            plain_text_round("msg_memory_01", "Noted."),
+        ])));
+    let mut manager = ConversationManager::new(mock_api_client, executor);
+    manager
+        .send_message("add a column via migrations".to_string(), None)
+        .await?;
+
+    let Content::Text(first) = &manager.transcript().api_messages[0].content else {
+        panic!("expected text user message");
+    };
+    assert!(first.starts_with("add a column via migrations"));
+    assert!(first.contains("[Project memories that may be relevant]"));
+    assert!(first.contains("Database migrations must stay append-only"));
+    assert!(!first.contains("anyhow"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_oversized_streamed_tool_input_is_rejected_before_execution() -> Result<()> {
+    let _env_lock = crate::test_support::ENV_LOCK.lock().await;
+    std::env::set_var("VEX_MAX_TOOL_INPUT_BYTES", "1024");
+    let temp = TempDir::new()?;
+    let chunk = "x".repeat(800);
+
+    let first_response_sse = vec![
+        r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_mock_big","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string(),
+        r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_big_write","name":"write_file"}}"#.to_string(),
+        format!(
+            r#"event: content_block_delta
+data: {{"type":"content_block_delta","index":0,"delta":{{"type":"input_json_delta","partial_json":"{{\"path\":\"big.txt\",\"content\":\"{chunk}"}}}}"#
+        ),
+        format!(
+            r#"event: content_block_delta
+data: {{"type":"content_block_delta","index":0,"delta":{{"type":"input_json_delta","partial_json":"{chunk}\"}}"}}}}"#
+        ),
+        r#"event: content_block_stop
+data: {"type":"content_block_stop","index":0}"#.to_string(),
+        r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":12}}"#.to_string(),
+        r#"event: message_stop
+data: {"type":"message_stop"}"#.to_string(),
+    ];
+
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            first_response_sse,
+            plain_text_round("msg_mock_big_2", "Too large."),
+        ])));
+    let mut manager = ConversationManager::new(
+        mock_api_client,
+        ToolOperator::new(temp.path().to_path_buf()),
+    );
+    let sent = manager
+        .send_message("write a big file".to_string(), None)
+        .await;
+    // Cleared before `?` so a failed send cannot leak the limit.
+    std::env::remove_var("VEX_MAX_TOOL_INPUT_BYTES");
+    sent?;
+
+    assert!(!temp.path().join("big.txt").exists());
+    let transcript = manager.transcript();
+    let rejected = transcript
+        .api_messages
+        .iter()
+        .any(|message| match &message.content {
+            Content::Blocks(blocks) => blocks.iter().any(|block| {
+                matches!(
+                    block,
+                    ContentBlock::ToolResult { content, is_error: true, .. }
+                        if content.contains("\"error\":\"tool_input_too_large\"")
+                            && content.contains("VEX_MAX_TOOL_INPUT_BYTES")
+                )
+            }),
+            Content::Text(_) => false,
+        });
+    assert!(rejected, "expected a tool_input_too_large tool result");
+    Ok(())
+}
+
+#[test]
+fn test_oversized_tool_argument_is_reported_by_name() {
+    let limits = ToolInputLimits {
+        max_input_bytes: 4096,
+        max_arg_bytes: 16,
+    };
+    let input = serde_json::json!({ "path": "a.txt", "content": "y".repeat(32) });
+
+    let error = oversized_tool_input_error("write_file", &input, None, limits)
+        .expect("oversized argument should be rejected");
+    let parsed: serde_json::Value = serde_json::from_str(&error).expect("structured error");
+    assert_eq!(parsed["argument"], "content");
+    assert_eq!(parsed["bytes"], 32);
+    assert_eq!(parsed["limit"], 16);
+    assert!(oversized_tool_input_error(
+        "write_file",
+        &serde_json::json!({ "path": "a.txt" }),
+        None,
+        limits
+    )
+    .is_none());
+}
+
+#[tokio::test]
+async fn test_provider_error_event_fails_turn_with_typed_error() {
+    let error_round = vec![
+        r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_overloaded","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":1}}}"#.to_string(),
+        r#"event: error
+data: {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#.to_string(),
+    ];
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            error_round,
+        ])));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+
+    let error = manager
+        .send_message("hello".to_string(), None)
+        .await
+        .expect_err("provider error should end the turn");
+    let typed = error
+        .downcast_ref::<crate::api::stream::StreamError>()
+        .expect("typed stream error");
+    assert!(typed.is_retryable());
+    assert!(error.to_string().contains("overloaded_error"));
+}
+
+#[tokio::test]
+async fn test_runaway_text_is_truncated_and_the_model_is_told() -> Result<()> {
+    let mut looping = plain_text_round("msg_runaway_01", "Let me look.");
+    let repeat = (0..600).map(|_| {
+        r#"event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"I will now check the file. "}}"#
+            .to_string()
+    });
+    looping.splice(3..3, repeat);
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            looping,
+            plain_text_round("msg_runaway_02", "Done."),
+        ])));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+
+    let first = manager.send_message("check it".to_string(), None).await?;
+    assert!(first.starts_with("Let me look.I will now check the file."));
+    assert!(
+        first.ends_with("[output truncated: the response repeated the same 27-byte sequence]"),
+        "{first}"
+    );
+    assert!(first.len() < 600 * 27 / 2);
+
+    manager.send_message("try again".to_string(), None).await?;
+    let messages = manager.reader().messages();
+    let Content::Text(retry) = &messages[messages.len() - 2].content else {
+        panic!("user message is text");
+    };
+    assert!(retry.starts_with("[Your previous response was cut off because it repeated"));
+    assert!(retry.ends_with("try again"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_custom_project_tool_is_dispatched_and_maps_output() -> Result<()> {
+    let temp = TempDir::new()?;
+    let tools_dir = temp.path().join(".aistar/tools");
+    std::fs::create_dir_all(&tools_dir)?;
+    std::fs::write(
+        tools_dir.join("greet.toml"),
+        "name = \"greet\"\ncommand = \"echo hello {{who}}\"\nrequires_approval = false\n",
+    )?;
+
+    let manager = ConversationManager::new(
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(
+            vec![],
+        ))),
+        ToolOperator::new(temp.path().to_path_buf()),
+    );
+    assert!(manager.custom_tool_requires_approval("greet"));
+
+    let output = manager
+        .execute_tool_with_timeout(
+            "greet",
+            &serde_json::json!({ "who": "team" }),
+            Duration::from_secs(10),
+        )
+        .await?;
+    assert_eq!(output, "hello team");
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_timed_out_tool_is_killed_and_frees_its_slot() -> Result<()> {
+    let temp = TempDir::new()?;
+    let tools_dir = temp.path().join(".aistar/tools");
+    std::fs::create_dir_all(&tools_dir)?;
+    std::fs::write(
+        tools_dir.join("nap.toml"),
+        "name = \"nap\"\ncommand = \"sleep 30 & echo $! > nap.pid; wait\"\nrequires_approval = false\n",
+    )?;
+    let manager = ConversationManager::new(
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(
+            vec![],
+        ))),
+        ToolOperator::new(temp.path().to_path_buf()),
+    );
+    let reader = manager.reader();
+
+    let error = manager
+        .execute_tool_with_timeout("nap", &json!({}), Duration::from_millis(300))
+        .await
+        .expect_err("tool outlives its timeout");
+    assert!(error.to_string().contains("timed out"));
+    assert_eq!(reader.in_flight_tools(), 0);
+
+    let sleeper: u32 = std::fs::read_to_string(temp.path().join("nap.pid"))?
+        .trim()
+        .parse()?;
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while crate::state_dirs::process_alive(sleeper) && std::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(!crate::state_dirs::process_alive(sleeper));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_run_tests_outlasts_the_tool_timeout_up_to_its_own() -> Result<()> {
+    let _guard = crate::test_support::ENV_LOCK.lock().await;
+    let temp = TempDir::new()?;
+    std::fs::write(temp.path().join("slow.sh"), "sleep 30\n")?;
+    std::env::set_var("VEX_TEST_COMMAND", "sh slow.sh");
+    std::env::set_var("VEX_TEST_TIMEOUT_SECS", "1");
+    let manager = ConversationManager::new(
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(
+            vec![],
+        ))),
+        ToolOperator::new(temp.path().to_path_buf()),
+    );
+
+    let error = manager
+        .execute_tool_with_timeout("run_tests", &json!({}), Duration::from_millis(200))
+        .await
+        .expect_err("test command outlives its timeout");
+    std::env::remove_var("VEX_TEST_COMMAND");
+    std::env::remove_var("VEX_TEST_TIMEOUT_SECS");
+    assert!(
+        error
+            .to_string()
+            .contains("timed out after 1s (VEX_TEST_TIMEOUT_SECS"),
+        "{error}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_max_tokens_stop_is_continued_and_stitched() -> Result<()> {
+    let _guard = crate::test_support::ENV_LOCK.lock().await;
+    let truncated = |message_id: &str, text: &str| {
+        plain_text_round(message_id, text)
+            .into_iter()
+            .map(|frame| frame.replace("end_turn", "max_tokens"))
+            .collect::<Vec<_>>()
+    };
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            truncated("msg_cont_1", "The quick brown"),
+            plain_text_round("msg_cont_2", " fox jumps."),
+        ])));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let final_text = manager
+        .send_message("Say the pangram".into(), Some(&tx))
+        .await?;
+    assert_eq!(final_text, "The quick brown fox jumps.");
+
+    let messages = manager.messages_for_api();
+    let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+    assert_eq!(roles, vec!["user", "assistant", "user", "assistant"]);
+    assert!(matches!(
+        &messages[2].content,
+        Content::Text(text) if text.contains("cut off at the output token limit")
+    ));
+
+    drop(tx);
+    let mut streamed = String::new();
+    while let Some(update) = rx.recv().await {
+        match update {
+            ConversationStreamUpdate::Delta(text) => streamed.push_str(&text),
+            ConversationStreamUpdate::BlockStart {
+                block: StreamBlock::FinalText { content },
+                ..
+            } => streamed.push_str(&content),
+            _ => {}
+        }
+    }
+    assert_eq!(streamed, "The quick brown [continued]  fox jumps.");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_max_tokens_continuations_are_bounded() -> Result<()> {
+    let _guard = crate::test_support::ENV_LOCK.lock().await;
+    std::env::set_var("VEX_MAX_CONTINUATIONS", "0");
+    let mock_api_client =
+        ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+            plain_text_round("msg_cont_only", "Cut off mid")
+                .into_iter()
+                .map(|frame| frame.replace("end_turn", "max_tokens"))
+                .collect(),
+        ])));
+    let mut manager = ConversationManager::new_mock(mock_api_client, HashMap::new());
+    let final_text = manager.send_message("Say something".into(), None).await;
+    std::env::remove_var("VEX_MAX_CONTINUATIONS");
+    assert_eq!(final_text?, "Cut off mid");
+    assert_eq!(manager.messages_for_api().len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_turn_overrides_are_recorded_for_their_turn_only() -> Result<()> {
+    let client = ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+        plain_text_round("msg_1", "first"),
+        plain_text_round("msg_2", "second"),
+    ])));
+    let mut manager = ConversationManager::new_mock(client, HashMap::new());
+    let overrides = crate::turn_overrides::TurnOverrides {
+        temperature: Some(0.2),
+        ..Default::default()
+    };
+
+    manager
+        .send_message_with_overrides("careful answer".to_string(), overrides.clone(), None)
+        .await?;
+    manager
+        .send_message("normal answer".to_string(), None)
+        .await?;
+
+    let records = manager.reader().turn_overrides();
+    assert_eq!(
+        records,
+        vec![crate::turn_overrides::TurnOverrideRecord::new(
+            1,
+            "careful answer",
+            overrides
+        )]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_queued_documents_are_uploaded_once_and_released_at_close() -> Result<()> {
+    let client = ApiClient::new_mock(Arc::new(crate::api::mock_client::MockApiClient::new(vec![
+        plain_text_round("msg_1", "first"),
+        plain_text_round("msg_2", "second"),
+    ])));
+    let mut manager = ConversationManager::new_mock(client, HashMap::new());
+    let document = crate::api::files::PendingDocument {
+        title: "logs/build.log".to_string(),
+        content: "line\n".repeat(10),
+    };
+
+    manager.queue_documents(vec![document.clone()]);
+    manager.send_message("summarize".to_string(), None).await?;
+    manager.queue_documents(vec![document]);
+    manager.send_message("again".to_string(), None).await?;
+    assert_eq!(manager.uploaded_file_count(), 1);
+
+    let expected = ContentBlock::Document {
+        source: crate::types::DocumentSource::File {
+            file_id: "file_mock_50".to_string(),
+        },
+        title: Some("logs/build.log".to_string()),
+    };
+    let user_blocks = |messages: &[ApiMessage]| -> Vec<Vec<ContentBlock>> {
+        messages
+            .iter()
+            .filter_map(|message| match &message.content {
+                Content::Blocks(blocks) if message.role == "user" => Some(blocks.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+    for blocks in user_blocks(&manager.messages_for_api()) {
+        assert_eq!(
+            serde_json::to_value(&blocks[0])?,
+            serde_json::to_value(&expected)?
+        );
+    }
+
+    assert_eq!(manager.release_uploaded_files().await, 0);
+    assert_eq!(manager.uploaded_file_count(), 0);
+    for blocks in user_blocks(&manager.messages_for_api()) {
+        assert!(
+            matches!(&blocks[0], ContentBlock::Text { text } if text.contains("logs/build.log is not available")),
+            "{blocks:?}"
+        );
+    }
+    Ok(())
+}
+
+/// Serves a silent stream for each of the first `stalls` requests, then a
+/// short text answer.
+struct StallingStream {
+    stalls: std::sync::Mutex<usize>,
+    partial: bool,
+}
+
+impl crate::api::client::MockStreamProducer for StallingStream {
+    fn create_mock_stream(
+        &self,
+        _messages: &[ApiMessage],
+    ) -> Result<crate::api::client::ByteStream> {
+        let frames = [
+            r#"data: {"type":"message_start","message":{"id":"msg_stall","type":"message","role":"assistant","model":"mock-model","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":4,"output_tokens":1}}}"#,
+            r#"data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Recovered."}}"#,
+            r#"data: {"type":"message_stop"}"#,
+        ];
+        let frames: Vec<Result<bytes::Bytes>> = frames
+            .iter()
+            .map(|frame| Ok(bytes::Bytes::from(format!("{frame}\n\n"))))
+            .collect();
+        let mut stalls = self.stalls.lock().unwrap();
+        if *stalls == 0 {
+            return Ok(Box::pin(futures::stream::iter(frames)));
+        }
+        *stalls -= 1;
+        let shown = if self.partial { 3 } else { 0 };
+        let head = futures::stream::iter(frames.into_iter().take(shown));
+        Ok(Box::pin(futures::StreamExt::chain(
+            head,
+            futures::stream::pending(),
+        )))
+    }
+}
+
+#[tokio::test]
+async fn test_stalled_stream_is_retried_before_output_and_aborted_after() -> Result<()> {
+    use crate::api::watchdog::{StallAction, StallPolicy, StreamWatchdog};
+    use crate::events::{EventKind, RuntimeEvent};
+
+    let watchdog = StreamWatchdog::new(Some(Duration::from_millis(20)), StallPolicy::Retry);
+    let client = ApiClient::new_mock(Arc::new(StallingStream {
+        stalls: std::sync::Mutex::new(1),
+        partial: false,
+    }));
+    let mut manager =
+        ConversationManager::new_mock(client, HashMap::new()).with_stream_watchdog(watchdog);
+    let mut events = manager.event_bus().subscribe(&[EventKind::Error]);
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let text = manager.send_message("hello".to_string(), Some(&tx)).await?;
+    assert!(text.contains("Recovered."));
+    let mut notices = Vec::new();
+    while let Ok(update) = rx.try_recv() {
+        if let ConversationStreamUpdate::Status(notice) = update {
+            notices.push(notice);
+        }
+    }
+    assert_eq!(
+        notices,
+        [
+            "stream stalled for 0s; still waiting",
+            "stream stalled for 0s; sending the request again"
+        ]
+    );
+    let mut actions = Vec::new();
+    while let Some(event) = events.try_recv() {
+        if let RuntimeEvent::StreamStalled { action, round, .. } = event.as_ref() {
+            assert_eq!(*round, 1);
+            actions.push(*action);
+        }
+    }
+    assert_eq!(actions, [StallAction::Waiting, StallAction::Retrying]);
+
+    let client = ApiClient::new_mock(Arc::new(StallingStream {
+        stalls: std::sync::Mutex::new(1),
+        partial: true,
+    }));
+    let mut manager =
+        ConversationManager::new_mock(client, HashMap::new()).with_stream_watchdog(watchdog);
+    let error = manager
+        .send_message("hello".to_string(), None)
+        .await
+        .expect_err("a stall after output aborts the turn");
+    assert!(matches!(
+        error.downcast_ref::<crate::api::stream::StreamError>(),
+        Some(crate::api::stream::StreamError::Stalled { .. })
+    ));
+    Ok(())
 }
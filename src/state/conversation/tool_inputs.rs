@@ -0,0 +1,320 @@
+use crate::tool_keys::{
+    CONTENT_KEYS, NEW_TEXT_KEYS, OLD_TEXT_KEYS, PATH_KEYS, SOURCE_PATH_KEYS, TARGET_PATH_KEYS,
+};
+use anyhow::{bail, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ReadFileInput {
+    #[serde(alias = "file_path", alias = "file")]
+    pub path: String,
+    pub start_line: Option<usize>,
+    pub end_line: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct WriteFileInput {
+    #[serde(alias = "file_path", alias = "file", alias = "filename")]
+    pub path: String,
+    #[serde(alias = "text")]
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct EditFileInput {
+    #[serde(alias = "file_path", alias = "file", alias = "filename")]
+    pub path: String,
+    #[serde(
+        alias = "old_text",
+        alias = "old_string",
+        alias = "find",
+        alias = "search"
+    )]
+    pub old_str: String,
+    #[serde(
+        default,
+        alias = "new_text",
+        alias = "new_string",
+        alias = "replace",
+        alias = "replace_with",
+        alias = "replacement"
+    )]
+    pub new_str: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RenameFileInput {
+    #[serde(alias = "from", alias = "source_path")]
+    pub old_path: String,
+    #[serde(alias = "to", alias = "target_path")]
+    pub new_path: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct TailFileInput {
+    #[serde(alias = "file_path", alias = "file")]
+    pub path: String,
+    #[serde(default = "default_tail_lines")]
+    pub max_lines: usize,
+    #[serde(default = "default_tail_bytes")]
+    pub max_bytes: usize,
+    #[serde(default)]
+    pub reset: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ListFilesInput {
+    pub path: Option<String>,
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct TreeInput {
+    pub path: Option<String>,
+    #[serde(default = "default_tree_depth")]
+    pub max_depth: usize,
+    #[serde(default = "default_tree_entries")]
+    pub max_entries: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RepoMapInput {
+    pub path: Option<String>,
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct SearchFilesInput {
+    pub query: String,
+    pub path: Option<String>,
+    #[serde(default = "default_max_results")]
+    pub max_results: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct NoInput {}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct GitStatusInput {
+    #[serde(default = "default_true")]
+    pub short: bool,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct GitDiffInput {
+    #[serde(default)]
+    pub cached: bool,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct GitLogInput {
+    #[serde(default = "default_max_count")]
+    pub max_count: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct GitShowInput {
+    pub revision: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct GitAddInput {
+    #[serde(alias = "file_path", alias = "file")]
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct GitCommitInput {
+    #[serde(default, alias = "msg", alias = "commit_message")]
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct AskUserInput {
+    #[serde(default)]
+    pub question: String,
+    #[serde(default, alias = "choices")]
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct SuggestCommitMessageInput {
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default, alias = "request", alias = "summary")]
+    pub intent: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RememberInput {
+    #[serde(alias = "memory", alias = "fact", alias = "content")]
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RecallInput {
+    #[serde(alias = "topic", alias = "text")]
+    pub query: String,
+    #[serde(default = "default_recall_limit")]
+    pub limit: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ExpandToolResultInput {
+    #[serde(alias = "id", alias = "tool_use_id")]
+    pub hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ScratchWriteInput {
+    #[serde(alias = "path", alias = "file")]
+    pub name: String,
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ScratchReadInput {
+    #[serde(alias = "path", alias = "file")]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RunTestsInput {
+    #[serde(alias = "test", alias = "test_name", alias = "name")]
+    pub filter: Option<String>,
+}
+
+fn default_max_entries() -> usize {
+    100
+}
+
+fn default_tail_lines() -> usize {
+    200
+}
+
+fn default_tail_bytes() -> usize {
+    32 * 1024
+}
+
+fn default_tree_depth() -> usize {
+    3
+}
+
+fn default_tree_entries() -> usize {
+    200
+}
+
+fn default_max_results() -> usize {
+    30
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_count() -> usize {
+    10
+}
+
+fn default_recall_limit() -> usize {
+    5
+}
+
+/// Argument names a model may send together for one field; repeats with the
+/// same value are dropped before decoding.
+const ALIAS_GROUPS: [&[&str]; 6] = [
+    &PATH_KEYS,
+    &SOURCE_PATH_KEYS,
+    &TARGET_PATH_KEYS,
+    &OLD_TEXT_KEYS,
+    &NEW_TEXT_KEYS,
+    &CONTENT_KEYS,
+];
+
+/// Decodes `input` for `tool`. Unknown fields are rejected so a misspelled
+/// argument fails instead of taking a default; errors name the missing,
+/// unknown, mistyped, or conflicting field.
+pub(super) fn parse_tool_input<T: DeserializeOwned>(
+    tool: &str,
+    input: &serde_json::Value,
+) -> Result<T> {
+    let invalid = |error: &dyn std::fmt::Display| {
+        anyhow::anyhow!(
+            "Invalid {tool} input: {error}. No action was taken; fix the arguments to match the {tool} schema and call it again."
+        )
+    };
+    let input = match input {
+        serde_json::Value::Null => serde_json::Value::Object(serde_json::Map::new()),
+        serde_json::Value::Object(object) => {
+            serde_json::Value::Object(collapse_repeated_aliases(object).map_err(|e| invalid(&e))?)
+        }
+        other => other.clone(),
+    };
+    T::deserialize(&input).map_err(|error| invalid(&error))
+}
+
+/// `object` with each alias group down to its first key present, so
+/// `{"path": "a", "file_path": "a"}` decodes like `{"path": "a"}`. Aliases
+/// with different values are an error rather than a guess.
+fn collapse_repeated_aliases(
+    object: &serde_json::Map<String, serde_json::Value>,
+) -> std::result::Result<serde_json::Map<String, serde_json::Value>, String> {
+    let mut object = object.clone();
+    for group in ALIAS_GROUPS {
+        let present: Vec<&str> = group
+            .iter()
+            .copied()
+            .filter(|key| object.contains_key(*key))
+            .collect();
+        let Some((first, repeats)) = present.split_first() else {
+            continue;
+        };
+        for key in repeats {
+            if object[*key] != object[*first] {
+                return Err(format!(
+                    "`{first}` and `{key}` name the same argument with different values"
+                ));
+            }
+            object.remove(*key);
+        }
+    }
+    Ok(object)
+}
+
+/// `value` trimmed, or an error naming `key` when that leaves nothing.
+pub(super) fn non_empty<'a>(tool: &str, key: &str, value: &'a str) -> Result<&'a str> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        bail!("{tool} requires a non-empty '{key}' string argument");
+    }
+    Ok(trimmed)
+}
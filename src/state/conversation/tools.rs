@@ -1,32 +1,75 @@
-use super::{ConversationManager, ConversationStreamUpdate, ToolApprovalRequest};
-use crate::edit_diff::DEFAULT_EDIT_DIFF_CONTEXT_LINES;
+use super::super::secret_guard::{send_secrets_input, KnownSecret, SEND_SECRETS_APPROVAL};
+use super::history::ToolInputLimits;
+use super::streaming::emit_text_update;
+use super::tool_inputs::*;
+use super::{
+    ApprovalQuery, ApprovalTier, ConversationManager, ConversationStreamUpdate,
+    ToolApprovalDecision, ToolApprovalRequest, ToolClass, UserQuestionRequest,
+};
+use crate::audit::{ApprovalSource, AuditRecord};
+use crate::change_summary::TurnChangeSummary;
+use crate::edit_diff::{format_edit_hunks, DiffSurface};
+use crate::tool_keys::{all_path_keys, first_str, PATH_KEYS, SOURCE_PATH_KEYS, TARGET_PATH_KEYS};
 use crate::tool_preview::{preview_tool_input, ToolPreviewStyle};
-use crate::tools::ToolOperator;
-use crate::types::ContentBlock;
+use crate::tools::{resolve_test_timeout, CallProcesses, ToolOperator, ToolPriority, ToolSlot};
+use crate::types::{ApiMessage, Content, ContentBlock};
 use crate::util::parse_bool_flag;
 use anyhow::{bail, Result};
-#[cfg(test)]
 use std::collections::HashMap;
 #[cfg(test)]
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::sync::{mpsc, oneshot};
 
+pub(super) const ASK_USER_TOOL: &str = "ask_user";
+/// Suggested answers beyond this many are dropped.
+const MAX_QUESTION_OPTIONS: usize = 9;
+const RUN_TESTS_TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+
 impl ConversationManager {
+    /// `tool_call_id` is `None` for prompts not tied to a model tool call.
     pub(super) async fn request_tool_approval(
         &self,
+        tool_call_id: Option<&str>,
         name: &str,
         input: &serde_json::Value,
+        tier: ApprovalTier,
         stream_delta_tx: Option<&mpsc::UnboundedSender<ConversationStreamUpdate>>,
-    ) -> bool {
+    ) -> ToolApprovalDecision {
+        let input_preview = || {
+            tool_input_preview(
+                name,
+                input,
+                self.tool_operator.diff_context().get(DiffSurface::Preview),
+            )
+        };
+        let class = self.tool_class(name, input);
+        if let Some(handler) = &self.approval_handler {
+            return handler
+                .decide(ApprovalQuery {
+                    tool_call_id: tool_call_id.map(str::to_string),
+                    tool_name: name.to_string(),
+                    input: input.clone(),
+                    input_preview: input_preview(),
+                    class,
+                    paths: self.input_paths(input),
+                    tier,
+                })
+                .await;
+        }
         let Some(tx) = stream_delta_tx else {
-            return true;
+            return ToolApprovalDecision::Approved;
         };
 
         let (response_tx, response_rx) = oneshot::channel();
         let request = ToolApprovalRequest {
+            tool_call_id: tool_call_id.map(str::to_string),
             tool_name: name.to_string(),
-            input_preview: tool_input_preview(name, input),
+            input: input.clone(),
+            input_preview: input_preview(),
+            class,
+            paths: self.input_paths(input),
+            tier,
             response_tx,
         };
 
@@ -34,10 +77,309 @@ impl ConversationManager {
             .send(ConversationStreamUpdate::ToolApprovalRequest(request))
             .is_err()
         {
-            return false;
+            return ToolApprovalDecision::Denied { reason: None };
+        }
+
+        response_rx
+            .await
+            .unwrap_or(ToolApprovalDecision::Denied { reason: None })
+    }
+
+    /// Puts an `ask_user` question to the user and returns their answer as
+    /// the tool result. Fails when the turn has no frontend to ask, so the
+    /// model goes on with a stated assumption instead.
+    pub(super) async fn ask_user(
+        &self,
+        tool_call_id: &str,
+        input: &serde_json::Value,
+        stream_delta_tx: Option<&mpsc::UnboundedSender<ConversationStreamUpdate>>,
+    ) -> Result<String> {
+        let args: AskUserInput = parse_tool_input(ASK_USER_TOOL, input)?;
+        let question = non_empty(ASK_USER_TOOL, "question", &args.question)?.to_string();
+        let options: Vec<String> = args
+            .options
+            .iter()
+            .map(|option| option.trim().to_string())
+            .filter(|option| !option.is_empty())
+            .take(MAX_QUESTION_OPTIONS)
+            .collect();
+        let Some(tx) = stream_delta_tx else {
+            bail!("nobody is available to answer in this session; proceed with your best assumption and state it");
+        };
+        let (response_tx, response_rx) = oneshot::channel();
+        let request = UserQuestionRequest {
+            tool_call_id: tool_call_id.to_string(),
+            question,
+            options,
+            response_tx,
+        };
+        if tx
+            .send(ConversationStreamUpdate::UserQuestion(request))
+            .is_err()
+        {
+            bail!(
+                "the question could not be shown; proceed with your best assumption and state it"
+            );
+        }
+        Ok(match response_rx.await.ok().flatten() {
+            Some(answer) => format!("The user answered: {answer}"),
+            None => "The user skipped the question; proceed with your best judgment and state the assumption you made.".to_string(),
+        })
+    }
+
+    /// Asks before a request carries values of local secrets not already
+    /// allowed this session. A denial, or a turn with nobody to ask,
+    /// redacts them from the history before it is sent. The system prompt
+    /// is checked too; it cannot be redacted, so a denied secret there ends
+    /// the turn instead.
+    pub(super) async fn guard_outgoing_secrets(
+        &mut self,
+        stream_delta_tx: Option<&mpsc::UnboundedSender<ConversationStreamUpdate>>,
+    ) -> Result<()> {
+        if self.secret_guard.is_empty() {
+            return Ok(());
+        }
+        let mut found = self.secret_guard.scan(&self.transcript().api_messages);
+        let (system_prompt, _) = self.client.request_overhead_text();
+        let in_system_prompt = self.secret_guard.scan_text(&system_prompt);
+        for secret in &in_system_prompt {
+            if !found.contains(secret) {
+                found.push(secret.clone());
+            }
+        }
+        let denied = self.confirm_secrets(found, stream_delta_tx).await;
+        if denied.is_empty() {
+            return Ok(());
+        }
+        if let Some(secret) = denied
+            .iter()
+            .find(|secret| in_system_prompt.contains(secret))
+        {
+            bail!(
+                "the system prompt contains the value of {}; remove it from the configuration (e.g. VEX_RESPONSE_LANGUAGE) before sending",
+                secret.source
+            );
+        }
+        self.secret_guard
+            .redact(&mut self.transcript().api_messages, &denied);
+        emit_secret_redaction_note(stream_delta_tx, &denied, "before sending");
+        Ok(())
+    }
+
+    /// Asks about the `found` secrets not already allowed this session and
+    /// returns the ones that must not be sent: all of them on a denial or
+    /// when nobody can answer, none once approved.
+    pub(super) async fn confirm_secrets(
+        &mut self,
+        found: Vec<KnownSecret>,
+        stream_delta_tx: Option<&mpsc::UnboundedSender<ConversationStreamUpdate>>,
+    ) -> Vec<KnownSecret> {
+        let found: Vec<KnownSecret> = found
+            .into_iter()
+            .filter(|secret| !self.allowed_secrets.contains(&secret.source))
+            .collect();
+        if found.is_empty() {
+            return found;
+        }
+        let decision = if self.approval_handler.is_none() && stream_delta_tx.is_none() {
+            ToolApprovalDecision::Denied { reason: None }
+        } else {
+            let tier = ApprovalTier::Secrets {
+                sources: found.iter().map(|secret| secret.source.clone()).collect(),
+            };
+            self.request_tool_approval(
+                None,
+                SEND_SECRETS_APPROVAL,
+                &send_secrets_input(&found),
+                tier,
+                stream_delta_tx,
+            )
+            .await
+        };
+        if decision.is_approved() {
+            self.allowed_secrets
+                .extend(found.into_iter().map(|secret| secret.source));
+            return Vec::new();
+        }
+        found
+    }
+
+    /// Read-only tools, calls that replace or move existing files, and
+    /// everything else.
+    pub(super) fn tool_class(&self, name: &str, input: &serde_json::Value) -> ToolClass {
+        if is_read_only_tool_name(name) {
+            return ToolClass::ReadOnly;
+        }
+        let overwrites = || {
+            PATH_KEYS
+                .iter()
+                .filter_map(|key| input.get(*key).and_then(|value| value.as_str()))
+                .filter_map(|path| self.tool_operator.canonical_path(path))
+                .any(|path| path.is_file())
+        };
+        match name {
+            "rename_file" => ToolClass::Destructive,
+            "write_file" if overwrites() => ToolClass::Destructive,
+            _ => ToolClass::Mutating,
         }
+    }
+
+    /// Paths named by `input`, canonical where they resolve in the workspace.
+    fn input_paths(&self, input: &serde_json::Value) -> Vec<String> {
+        all_path_keys()
+            .filter_map(|key| input.get(key).and_then(|value| value.as_str()))
+            .map(|path| {
+                self.tool_operator
+                    .canonical_path(path)
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| path.trim().to_string())
+            })
+            .collect()
+    }
+
+    /// The sensitive tier when a write/edit/rename touches a path on the
+    /// policy's sensitive list, as written or once symlinks are resolved.
+    pub(super) fn tool_approval_tier(&self, name: &str, input: &serde_json::Value) -> ApprovalTier {
+        let keys: Vec<&str> = match name {
+            "write_file" | "edit_file" => PATH_KEYS.to_vec(),
+            "rename_file" => SOURCE_PATH_KEYS
+                .into_iter()
+                .chain(TARGET_PATH_KEYS)
+                .collect(),
+            _ => return ApprovalTier::Standard,
+        };
+        let sensitive_paths = self.approval_policy.sensitive_paths();
+        keys.into_iter()
+            .filter_map(|key| input.get(key).and_then(|value| value.as_str()))
+            .find_map(|path| {
+                let canonical = self.tool_operator.canonical_relative_path(path);
+                sensitive_paths
+                    .matching(path)
+                    .or_else(|| {
+                        canonical
+                            .as_deref()
+                            .and_then(|c| sensitive_paths.matching(c))
+                    })
+                    .map(|pattern| ApprovalTier::Sensitive {
+                        path: path.trim().to_string(),
+                        pattern: pattern.to_string(),
+                    })
+            })
+            .unwrap_or_default()
+    }
 
-        response_rx.await.unwrap_or(false)
+    /// Appends an audit record when the audit log is on. Returns a warning
+    /// for the transcript when the record could not be written.
+    pub(super) fn record_tool_audit(
+        &self,
+        name: &str,
+        input: &serde_json::Value,
+        approval: ApprovalSource,
+        duration: Duration,
+        result: &Result<String>,
+    ) -> Option<String> {
+        let audit_log = self.audit_log.as_ref()?;
+        let record = AuditRecord::new(name, self.input_paths(input), approval, duration, result);
+        audit_log
+            .append(&record)
+            .err()
+            .map(|error| format!("[audit] failed to record {name}: {error:#}"))
+    }
+
+    /// The file a write or edit is about to change, with its content, so
+    /// the turn's change summary can count lines once the tool has run.
+    pub(super) fn change_snapshot(
+        &self,
+        name: &str,
+        input: &serde_json::Value,
+    ) -> Option<(String, Option<String>)> {
+        if !matches!(name, "write_file" | "edit_file") {
+            return None;
+        }
+        let path = first_str(input, &PATH_KEYS)?;
+        self.tool_operator.file_state(path)
+    }
+
+    /// Folds a successful tool call into the current turn's change summary.
+    /// `before` comes from [`Self::change_snapshot`].
+    pub(super) fn record_turn_change(
+        &self,
+        name: &str,
+        input: &serde_json::Value,
+        before: Option<(String, Option<String>)>,
+    ) {
+        let workspace_key = |path: &str| {
+            self.tool_operator
+                .file_state(path)
+                .map_or_else(|| path.trim().to_string(), |(key, _)| key)
+        };
+        let rename = (name == "rename_file")
+            .then(|| {
+                let from = first_str(input, &SOURCE_PATH_KEYS)?;
+                let to = first_str(input, &TARGET_PATH_KEYS)?;
+                Some((workspace_key(from), workspace_key(to)))
+            })
+            .flatten();
+        let after = before
+            .as_ref()
+            .and_then(|(key, _)| self.tool_operator.file_state(key));
+        let command = self.tool_command(name, input);
+        if before.is_none() && rename.is_none() && command.is_none() {
+            return;
+        }
+
+        let mut transcript = self.transcript();
+        let turn = transcript.turn_count;
+        if transcript
+            .turn_changes
+            .last()
+            .is_none_or(|summary| summary.turn != turn)
+        {
+            transcript.turn_changes.push(TurnChangeSummary::new(turn));
+        }
+        let Some(summary) = transcript.turn_changes.last_mut() else {
+            return;
+        };
+        if let Some((key, before)) = before {
+            let after = after.and_then(|(_, content)| content);
+            summary.record_file(&key, before.as_deref(), after.as_deref());
+        }
+        if let Some((from, to)) = rename {
+            summary.record_rename(&from, &to);
+        }
+        if let Some(command) = command {
+            summary.record_command(command);
+        }
+        if summary.is_empty() {
+            transcript.turn_changes.pop();
+        }
+    }
+
+    /// The command a tool ran, for the change summary.
+    fn tool_command(&self, name: &str, input: &serde_json::Value) -> Option<String> {
+        match name {
+            "run_tests" => Some(
+                match first_str(input, &["filter", "test", "test_name", "name"]) {
+                    Some(filter) => format!("run_tests {}", filter.trim()),
+                    None => "run_tests".to_string(),
+                },
+            ),
+            "git_add" => {
+                first_str(input, &PATH_KEYS).map(|path| format!("git add {}", path.trim()))
+            }
+            "git_commit" => Some("git commit".to_string()),
+            _ => self
+                .tool_operator
+                .custom_tools()
+                .get(name)
+                .and_then(|tool| tool.render_command(input).ok()),
+        }
+    }
+
+    /// Every project tool is confirmed, whatever its `requires_approval`
+    /// says, since a cloned repository chooses both the command and the flag.
+    pub(super) fn custom_tool_requires_approval(&self, name: &str) -> bool {
+        self.tool_operator.custom_tools().get(name).is_some()
     }
 
     pub(super) async fn execute_tool_with_timeout(
@@ -46,30 +388,62 @@ impl ConversationManager {
         input: &serde_json::Value,
         tool_timeout: Duration,
     ) -> Result<String> {
+        // `run_tests` has its own, longer timeout and reports the output
+        // it got when that runs out, so the tool timeout leaves it room.
+        let tool_timeout = if name == "run_tests" {
+            tool_timeout.max(resolve_test_timeout() + RUN_TESTS_TIMEOUT_GRACE)
+        } else {
+            tool_timeout
+        };
         let tool_name = name.to_string();
         let task_name = tool_name.clone();
         let task_input = input.clone();
         let task_executor = self.tool_operator.clone();
         #[cfg(test)]
         let task_mock_responses = self.mock_tool_operator_responses.clone();
+        let slot =
+            match tokio::time::timeout(tool_timeout, self.tool_pool.acquire(tool_priority(name)))
+                .await
+            {
+                Ok(slot) => slot,
+                Err(_) => bail!(
+                    "Tool {tool_name} waited {}s for a free tool slot ({} already running)",
+                    tool_timeout.as_secs(),
+                    self.tool_pool.stats().running
+                ),
+            };
 
+        // Killed on timeout, or when the turn is cancelled and drops this
+        // future, so the slot is not handed on while the work still runs.
+        let processes = CallProcesses::default();
+        let mut call = CallGuard {
+            processes: Some(processes.clone()),
+            _slot: slot,
+        };
         let mut task = tokio::task::spawn_blocking(move || {
-            #[cfg(test)]
-            {
-                execute_tool_blocking_with_operator(
-                    &task_executor,
-                    &task_name,
-                    &task_input,
-                    task_mock_responses,
-                )
-            }
-            #[cfg(not(test))]
-            {
-                execute_tool_blocking_with_operator(&task_executor, &task_name, &task_input)
-            }
+            processes.scope(|| {
+                #[cfg(test)]
+                {
+                    execute_tool_blocking_with_operator(
+                        &task_executor,
+                        &task_name,
+                        &task_input,
+                        task_mock_responses,
+                    )
+                }
+                #[cfg(not(test))]
+                {
+                    execute_tool_blocking_with_operator(&task_executor, &task_name, &task_input)
+                }
+            })
         });
 
-        match tokio::time::timeout(tool_timeout, &mut task).await {
+        let finished = tokio::time::timeout(tool_timeout, &mut task).await;
+        if finished.is_ok() {
+            call.processes = None;
+        }
+        drop(call);
+        match finished {
             Ok(join_result) => match join_result {
                 Ok(result) => result,
                 Err(join_error) => Err(anyhow::anyhow!(
@@ -87,6 +461,21 @@ impl ConversationManager {
     }
 }
 
+/// A running tool call's pool slot, and the processes to kill before the
+/// slot is released unless the call finished.
+struct CallGuard {
+    processes: Option<CallProcesses>,
+    _slot: ToolSlot,
+}
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        if let Some(processes) = self.processes.take() {
+            processes.kill_all();
+        }
+    }
+}
+
 #[cfg(test)]
 pub(super) fn execute_tool_blocking_with_operator(
     tool_operator: &ToolOperator,
@@ -97,7 +486,8 @@ pub(super) fn execute_tool_blocking_with_operator(
     if let Some(responses_arc) = mock_tool_operator_responses {
         let responses = responses_arc.lock().unwrap();
         if name == "read_file" {
-            let path = required_tool_string(input, name, "path")?;
+            let args: ReadFileInput = parse_tool_input(name, input)?;
+            let path = non_empty(name, "path", &args.path)?;
             if let Some(content) = responses.get(path) {
                 return Ok(content.clone());
             }
@@ -125,57 +515,37 @@ pub(super) fn execute_tool_dispatch(
     name: &str,
     input: &serde_json::Value,
 ) -> Result<String> {
-    let get_str = |key: &str| input.get(key).and_then(|v| v.as_str()).unwrap_or("");
-    let get_bool =
-        |key: &str, default: bool| input.get(key).and_then(|v| v.as_bool()).unwrap_or(default);
-    let get_usize = |key: &str, default: usize| {
-        input
-            .get(key)
-            .and_then(|v| v.as_u64())
-            .map(|v| v as usize)
-            .unwrap_or(default)
-    };
-
     match name {
         "read_file" => {
-            let path =
-                required_tool_string_any(input, name, "path", &["path", "file_path", "file"])?;
-            tool_operator.read_file(path)
+            let args: ReadFileInput = parse_tool_input(name, input)?;
+            let path = non_empty(name, "path", &args.path)?;
+            match (args.start_line, args.end_line) {
+                (None, None) => tool_operator.read_file(path),
+                (start, end) => tool_operator.read_file_lines(path, start.unwrap_or(1), end),
+            }
         }
         "write_file" => {
-            let path =
-                required_tool_string_any(input, name, "path", &["path", "file_path", "file"])?;
-            let content = first_tool_string(input, &["content", "text"]).unwrap_or("");
+            let args: WriteFileInput = parse_tool_input(name, input)?;
+            let path = non_empty(name, "path", &args.path)?;
+            let content = args.content.as_str();
             let (chars, lines) = text_stats(content);
-            tool_operator
-                .write_file(path, content)
-                .map(|_| format!("Wrote {path} ({chars} chars, {lines} lines)."))
+            let lock_warning = tool_operator.claim_file_lock(path);
+            tool_operator.write_file(path, content).map(|_| {
+                let result = format!(
+                    "Wrote {path} ({chars} chars, {lines} lines).{}",
+                    emitted_patch_note(tool_operator)
+                );
+                with_lock_warnings(result, lock_warning)
+            })
         }
         "edit_file" => {
-            let path = required_tool_string_any(
-                input,
-                name,
-                "path",
-                &["path", "file_path", "file", "filename"],
-            )?;
-            let old_str = required_tool_string_any_preserve(
-                input,
-                name,
-                "old_str",
-                &["old_str", "old_text", "old_string", "find", "search"],
-            )?;
-            let new_str = first_tool_string(
-                input,
-                &[
-                    "new_str",
-                    "new_text",
-                    "new_string",
-                    "replace",
-                    "replace_with",
-                    "replacement",
-                ],
-            )
-            .unwrap_or("");
+            let args: EditFileInput = parse_tool_input(name, input)?;
+            let path = non_empty(name, "path", &args.path)?;
+            let old_str = args.old_str.as_str();
+            if old_str.is_empty() {
+                bail!("{name} requires a non-empty 'old_str' string argument");
+            }
+            let new_str = args.new_str.as_str();
             let (old_chars, old_lines) = text_stats(old_str);
             let (new_chars, new_lines) = text_stats(new_str);
             let summary = if old_lines > 0 && new_lines == 0 {
@@ -191,108 +561,178 @@ pub(super) fn execute_tool_dispatch(
                     "Updated snippet in {path} ({old_chars} chars/{old_lines} lines -> {new_chars} chars/{new_lines} lines)."
                 )
             };
+            let lock_warning = tool_operator.claim_file_lock(path);
             tool_operator
                 .edit_file(path, old_str, new_str)
-                .map(|_| summary)
+                .map(|fuzzy| {
+                    let mut result = format!("{summary}{}", emitted_patch_note(tool_operator));
+                    let (old_str, new_str) = match &fuzzy {
+                        Some(fuzzy) => {
+                            result.push('\n');
+                            result.push_str(&fuzzy.report);
+                            (fuzzy.matched.as_str(), fuzzy.replacement.as_str())
+                        }
+                        None => (old_str, new_str),
+                    };
+                    let context_lines = tool_operator.diff_context().get(DiffSurface::History);
+                    result.push('\n');
+                    result.push_str(
+                        format_edit_hunks(old_str, new_str, "  ", context_lines).trim_end(),
+                    );
+                    if let Some(context) = tool_operator.edit_symbol_context(path, new_str) {
+                        result.push_str("\n\n");
+                        result.push_str(&context);
+                    }
+                    with_lock_warnings(result, lock_warning)
+                })
         }
         "rename_file" => {
-            let old_path = required_tool_string_any(
-                input,
-                name,
-                "old_path",
-                &["old_path", "from", "source_path"],
-            )?;
-            let new_path = required_tool_string_any(
-                input,
-                name,
-                "new_path",
-                &["new_path", "to", "target_path"],
-            )?;
-            tool_operator.rename_file(old_path, new_path)
-        }
-        "list_files" | "list_directory" => tool_operator.list_files(
-            input.get("path").and_then(|v| v.as_str()),
-            get_usize("max_entries", 100),
-        ),
-        "search_files" | "search" => tool_operator.search_files(
-            get_str("query"),
-            input.get("path").and_then(|v| v.as_str()),
-            get_usize("max_results", 30),
-        ),
-        "git_status" => tool_operator.git_status(
-            get_bool("short", true),
-            input.get("path").and_then(|v| v.as_str()),
-        ),
-        "git_diff" => tool_operator.git_diff(
-            get_bool("cached", false),
-            input.get("path").and_then(|v| v.as_str()),
-        ),
-        "git_log" => tool_operator.git_log(get_usize("max_count", 10)),
-        "git_show" => tool_operator.git_show(required_tool_string(input, name, "revision")?),
-        "git_add" => tool_operator.git_add(required_tool_string_any(
-            input,
-            name,
-            "path",
-            &["path", "file_path", "file"],
-        )?),
-        "git_commit" => tool_operator.git_commit(required_tool_string_any(
-            input,
-            name,
-            "message",
-            &["message", "msg", "commit_message"],
-        )?),
-        _ => bail!("Unknown tool: {name}"),
-    }
-}
-
-pub(super) fn required_tool_string<'a>(
-    input: &'a serde_json::Value,
-    tool: &str,
-    key: &str,
-) -> Result<&'a str> {
-    let value = input
-        .get(key)
-        .and_then(|v| v.as_str())
-        .map(str::trim)
-        .unwrap_or("");
-    if value.is_empty() {
-        bail!("{tool} requires a non-empty '{key}' string argument");
-    }
-    Ok(value)
-}
-
-pub(super) fn first_tool_string<'a>(
-    input: &'a serde_json::Value,
-    keys: &[&str],
-) -> Option<&'a str> {
-    keys.iter()
-        .find_map(|key| input.get(*key).and_then(|v| v.as_str()))
-}
-
-pub(super) fn required_tool_string_any<'a>(
-    input: &'a serde_json::Value,
-    tool: &str,
-    canonical_key: &str,
-    keys: &[&str],
-) -> Result<&'a str> {
-    let value = first_tool_string(input, keys).map(str::trim).unwrap_or("");
-    if value.is_empty() {
-        bail!("{tool} requires a non-empty '{canonical_key}' string argument");
-    }
-    Ok(value)
-}
-
-pub(super) fn required_tool_string_any_preserve<'a>(
-    input: &'a serde_json::Value,
-    tool: &str,
-    canonical_key: &str,
-    keys: &[&str],
-) -> Result<&'a str> {
-    let value = first_tool_string(input, keys).unwrap_or("");
-    if value.is_empty() {
-        bail!("{tool} requires a non-empty '{canonical_key}' string argument");
-    }
-    Ok(value)
+            let args: RenameFileInput = parse_tool_input(name, input)?;
+            let old_path = non_empty(name, "old_path", &args.old_path)?;
+            let new_path = non_empty(name, "new_path", &args.new_path)?;
+            let lock_warnings = [
+                tool_operator.claim_file_lock(old_path),
+                tool_operator.claim_file_lock(new_path),
+            ];
+            tool_operator
+                .rename_file(old_path, new_path)
+                .map(|result| with_lock_warnings(result, lock_warnings.into_iter().flatten()))
+        }
+        "list_files" | "list_directory" => {
+            let args: ListFilesInput = parse_tool_input(name, input)?;
+            tool_operator.list_files(args.path.as_deref(), args.max_entries)
+        }
+        "tail_file" => {
+            let args: TailFileInput = parse_tool_input(name, input)?;
+            let path = non_empty(name, "path", &args.path)?;
+            tool_operator.tail_file(path, args.max_lines, args.max_bytes, args.reset)
+        }
+        "tree" => {
+            let args: TreeInput = parse_tool_input(name, input)?;
+            tool_operator.tree(args.path.as_deref(), args.max_depth, args.max_entries)
+        }
+        "search_files" | "search" => {
+            let args: SearchFilesInput = parse_tool_input(name, input)?;
+            tool_operator.search_files(&args.query, args.path.as_deref(), args.max_results)
+        }
+        "workspace_stats" => {
+            let NoInput {} = parse_tool_input(name, input)?;
+            tool_operator.workspace_stats()
+        }
+        "repo_map" => {
+            let args: RepoMapInput = parse_tool_input(name, input)?;
+            tool_operator.repo_map(args.path.as_deref(), args.refresh)
+        }
+        "git_status" => {
+            let args: GitStatusInput = parse_tool_input(name, input)?;
+            tool_operator.git_status(args.short, args.path.as_deref())
+        }
+        "git_diff" => {
+            let args: GitDiffInput = parse_tool_input(name, input)?;
+            tool_operator.git_diff(args.cached, args.path.as_deref())
+        }
+        "git_log" => {
+            let args: GitLogInput = parse_tool_input(name, input)?;
+            tool_operator.git_log(args.max_count)
+        }
+        "git_show" => {
+            let args: GitShowInput = parse_tool_input(name, input)?;
+            tool_operator.git_show(non_empty(name, "revision", &args.revision)?)
+        }
+        "git_add" => {
+            let args: GitAddInput = parse_tool_input(name, input)?;
+            tool_operator.git_add(non_empty(name, "path", &args.path)?)
+        }
+        "git_commit" => {
+            let args: GitCommitInput = parse_tool_input(name, input)?;
+            tool_operator.git_commit(non_empty(name, "message", &args.message)?)
+        }
+        "suggest_commit_message" => {
+            let args: SuggestCommitMessageInput = parse_tool_input(name, input)?;
+            tool_operator.suggest_commit_message(args.source.as_deref(), args.intent.as_deref())
+        }
+        "remember" => {
+            let args: RememberInput = parse_tool_input(name, input)?;
+            tool_operator.remember(non_empty(name, "text", &args.text)?)
+        }
+        "recall" => {
+            let args: RecallInput = parse_tool_input(name, input)?;
+            tool_operator.recall(non_empty(name, "query", &args.query)?, args.limit)
+        }
+        "scratch_write" => {
+            let args: ScratchWriteInput = parse_tool_input(name, input)?;
+            tool_operator.scratch_write(non_empty(name, "name", &args.name)?, &args.content)
+        }
+        "scratch_read" => {
+            let args: ScratchReadInput = parse_tool_input(name, input)?;
+            tool_operator.scratch_read(non_empty(name, "name", &args.name)?)
+        }
+        "expand_tool_result" => {
+            let args: ExpandToolResultInput = parse_tool_input(name, input)?;
+            tool_operator.expand_tool_result(non_empty(name, "hash", &args.hash)?)
+        }
+        "run_tests" => {
+            let args: RunTestsInput = parse_tool_input(name, input)?;
+            tool_operator.run_tests(args.filter.as_deref())
+        }
+        ASK_USER_TOOL => bail!("ask_user is answered by the user, not run as a tool"),
+        _ => match tool_operator.run_custom_tool(name, input) {
+            Some(result) => result,
+            None => bail!("Unknown tool: {name}"),
+        },
+    }
+}
+
+/// Rejects tool calls whose streamed input or individual arguments exceed
+/// `limits`. `streamed_bytes` is set when the input was dropped mid-stream
+/// after overflowing `max_input_bytes`.
+pub(super) fn oversized_tool_input_error(
+    name: &str,
+    input: &serde_json::Value,
+    streamed_bytes: Option<usize>,
+    limits: ToolInputLimits,
+) -> Option<String> {
+    let render = |argument: Option<&str>, bytes: usize, limit: usize, env_key: &str| {
+        serde_json::json!({
+            "error": "tool_input_too_large",
+            "tool": name,
+            "argument": argument,
+            "bytes": bytes,
+            "limit": limit,
+            "limit_env": env_key,
+            "hint": "No changes were made. Split the work into smaller calls (e.g. several edit_file calls) instead of sending one oversized input.",
+        })
+        .to_string()
+    };
+
+    if let Some(bytes) = streamed_bytes {
+        return Some(render(
+            None,
+            bytes,
+            limits.max_input_bytes,
+            "VEX_MAX_TOOL_INPUT_BYTES",
+        ));
+    }
+
+    let (argument, bytes) = input
+        .as_object()?
+        .iter()
+        .map(|(key, value)| {
+            let bytes = match value {
+                serde_json::Value::String(text) => text.len(),
+                other => other.to_string().len(),
+            };
+            (key, bytes)
+        })
+        .max_by_key(|(_, bytes)| *bytes)?;
+    (bytes > limits.max_arg_bytes).then(|| {
+        render(
+            Some(argument),
+            bytes,
+            limits.max_arg_bytes,
+            "VEX_MAX_TOOL_ARG_BYTES",
+        )
+    })
 }
 
 pub(super) fn missing_mutating_location_prompt(
@@ -300,27 +740,25 @@ pub(super) fn missing_mutating_location_prompt(
     input: &serde_json::Value,
 ) -> Option<String> {
     let missing =
-        |keys: &[&str]| first_tool_string(input, keys).is_none_or(|value| value.trim().is_empty());
+        |keys: &[&str]| first_str(input, keys).is_none_or(|value| value.trim().is_empty());
 
     match name {
         "write_file" => {
-            if missing(&["path", "file_path", "file", "filename"]) {
+            if missing(&PATH_KEYS) {
                 Some("I need the target file path before creating a file. Please provide an explicit path like `src/calculator.rs`. No file changes were made.".to_string())
             } else {
                 None
             }
         }
         "edit_file" => {
-            if missing(&["path", "file_path", "file", "filename"]) {
+            if missing(&PATH_KEYS) {
                 Some("I need the target file path before editing a file. Please provide an explicit path like `src/calculator.rs`. No file changes were made.".to_string())
             } else {
                 None
             }
         }
         "rename_file" => {
-            if missing(&["old_path", "from", "source_path"])
-                || missing(&["new_path", "to", "target_path"])
-            {
+            if missing(&SOURCE_PATH_KEYS) || missing(&TARGET_PATH_KEYS) {
                 Some("I need both source and destination file paths before renaming. Please provide `old_path` and `new_path`. No file changes were made.".to_string())
             } else {
                 None
@@ -396,6 +834,48 @@ pub(super) fn text_stats(text: &str) -> (usize, usize) {
     )
 }
 
+pub(super) fn emit_secret_redaction_note(
+    stream_delta_tx: Option<&mpsc::UnboundedSender<ConversationStreamUpdate>>,
+    secrets: &[KnownSecret],
+    when: &str,
+) {
+    emit_text_update(
+        stream_delta_tx,
+        format!(
+            "\n[secret guard] redacted the value of {} {when}\n",
+            secrets
+                .iter()
+                .map(|secret| secret.source.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    );
+}
+
+fn with_lock_warnings(mut result: String, warnings: impl IntoIterator<Item = String>) -> String {
+    for warning in warnings {
+        result.push_str("\n\n");
+        result.push_str(&warning);
+    }
+    result
+}
+
+/// Tells the model where a write went when the operator emits patches
+/// instead of touching the working tree.
+fn emitted_patch_note(tool_operator: &ToolOperator) -> String {
+    tool_operator
+        .patch_output()
+        .and_then(|output| {
+            let entry = output.entries().pop()?;
+            Some(format!(
+                " Emitted as patch {} in {}; the working tree is unchanged.",
+                entry.file,
+                output.dir().display()
+            ))
+        })
+        .unwrap_or_default()
+}
+
 pub(super) fn default_tool_approval_enabled(is_local_endpoint: bool) -> bool {
     !is_local_endpoint
 }
@@ -407,12 +887,67 @@ pub(super) fn tool_approval_enabled(is_local_endpoint: bool) -> bool {
         .unwrap_or(default_tool_approval_enabled(is_local_endpoint))
 }
 
-pub(super) fn tool_input_preview(tool_name: &str, input: &serde_json::Value) -> String {
+/// Consecutive failures of one tool within a turn that add a warning for the
+/// model instead of letting it retry silently.
+pub(super) const TOOL_FAILURE_STREAK_LIMIT: usize = 3;
+pub(super) const TOOL_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+pub(super) fn tool_retry_enabled() -> bool {
+    std::env::var("VEX_TOOL_RETRY")
+        .ok()
+        .and_then(parse_bool_flag)
+        .unwrap_or(true)
+}
+
+/// Failures worth one automatic retry: contention (file busy, git lock) for
+/// any tool, and timeouts for tools that do not modify the workspace.
+pub(super) fn is_transient_tool_error(name: &str, error: &anyhow::Error) -> bool {
+    const CONTENTION_HINTS: [&str; 6] = [
+        "resource busy",
+        "text file busy",
+        "temporarily unavailable",
+        "index.lock",
+        "another git process",
+        "unable to create '",
+    ];
+    let message = format!("{error:#}").to_ascii_lowercase();
+    if CONTENTION_HINTS.iter().any(|hint| message.contains(hint)) {
+        return true;
+    }
+    // A timed-out call that writes may have done part of its work.
+    message.contains("timed out") && is_read_only_tool_name(name)
+}
+
+/// Updates the failure streak for `name` and returns a warning when it
+/// reaches [`TOOL_FAILURE_STREAK_LIMIT`].
+pub(super) fn record_tool_failure_streak(
+    streaks: &mut HashMap<String, usize>,
+    name: &str,
+    succeeded: bool,
+) -> Option<String> {
+    if succeeded {
+        streaks.remove(name);
+        return None;
+    }
+    let streak = streaks.entry(name.to_string()).or_default();
+    *streak += 1;
+    (*streak == TOOL_FAILURE_STREAK_LIMIT).then(|| {
+        format!(
+            "[tool warning] {name} has failed {TOOL_FAILURE_STREAK_LIMIT} times in a row. Do not call it again with similar input; read the errors above, change approach, or explain the blocker to the user."
+        )
+    })
+}
+
+pub(super) fn tool_input_preview(
+    tool_name: &str,
+    input: &serde_json::Value,
+    diff_context_lines: usize,
+) -> String {
     preview_tool_input(
         tool_name,
         input,
         ToolPreviewStyle::Compact,
-        DEFAULT_EDIT_DIFF_CONTEXT_LINES,
+        diff_context_lines,
     )
 }
 
@@ -562,6 +1097,112 @@ pub(super) fn normalize_tagged_parameter_value(raw: &str) -> String {
     value
 }
 
+/// Result of a tool call that has no `tool_result`/`tool_error` section in
+/// the text-protocol reply, e.g. one cut off by history truncation.
+const MISSING_MIGRATED_RESULT: &str = "[result not kept in the text-protocol history]";
+
+/// Rewrites text-protocol tool rounds in `messages` as structured blocks: an
+/// assistant message with tagged `<function=...>` calls becomes text plus
+/// `ToolUse` blocks, and the user message of `tool_result`/`tool_error`
+/// sections after it becomes `ToolResult` blocks. Returns the number of
+/// rounds rewritten; other messages are left as they are.
+pub(super) fn migrate_text_protocol_history(messages: &mut [ApiMessage]) -> usize {
+    let mut migrated = 0;
+    for index in 1..messages.len() {
+        let (earlier, later) = messages.split_at_mut(index);
+        let (assistant, reply) = (&mut earlier[index - 1], &mut later[0]);
+        if assistant.role != "assistant" || reply.role != "user" {
+            continue;
+        }
+        let (Content::Text(text), Content::Text(results)) = (&assistant.content, &reply.content)
+        else {
+            continue;
+        };
+        let calls = parse_tagged_tool_calls(text);
+        if calls.is_empty()
+            || !(results.starts_with("tool_result ") || results.starts_with("tool_error "))
+        {
+            continue;
+        }
+
+        let mut assistant_blocks = Vec::new();
+        let lead = text[..text.find("<function=").unwrap_or(text.len())].trim_end();
+        if !lead.is_empty() {
+            assistant_blocks.push(ContentBlock::Text {
+                text: lead.to_string(),
+            });
+        }
+        let mut result_blocks = Vec::new();
+        for (position, (call, (content, is_error))) in calls
+            .iter()
+            .zip(split_text_protocol_results(results, &calls))
+            .enumerate()
+        {
+            let id = format!("toolu_migrated_{index}_{position}");
+            assistant_blocks.push(ContentBlock::ToolUse {
+                id: id.clone(),
+                name: call.name.clone(),
+                input: call.input.clone(),
+            });
+            result_blocks.push(ContentBlock::ToolResult {
+                tool_use_id: id,
+                content,
+                is_error,
+            });
+        }
+        assistant.content = Content::Blocks(assistant_blocks);
+        reply.content = Content::Blocks(result_blocks);
+        migrated += 1;
+    }
+    migrated
+}
+
+/// The output and error flag of each of `calls`, in order, cut from a
+/// text-protocol reply. Sections are matched by tool name in call order;
+/// warnings appended after the last section stay with it.
+fn split_text_protocol_results(results: &str, calls: &[TaggedToolCall]) -> Vec<(String, bool)> {
+    let mut sections = Vec::new();
+    let mut cursor = 0;
+    for call in calls {
+        let found = [("tool_result", false), ("tool_error", true)]
+            .into_iter()
+            .filter_map(|(kind, is_error)| {
+                let header = format!("{kind} {}:\n", call.name);
+                results[cursor..]
+                    .find(&header)
+                    .map(|rel| (cursor + rel, cursor + rel + header.len(), is_error))
+            })
+            .min_by_key(|(start, _, _)| *start);
+        match found {
+            Some((start, body_start, is_error)) => {
+                cursor = body_start;
+                sections.push(Some((start, body_start, is_error)));
+            }
+            None => sections.push(None),
+        }
+    }
+
+    let starts: Vec<usize> = sections
+        .iter()
+        .flatten()
+        .map(|(start, ..)| *start)
+        .collect();
+    sections
+        .into_iter()
+        .map(|section| match section {
+            Some((_, body_start, is_error)) => {
+                let end = starts
+                    .iter()
+                    .copied()
+                    .find(|start| *start > body_start)
+                    .unwrap_or(results.len());
+                (results[body_start..end].trim_end().to_string(), is_error)
+            }
+            None => (MISSING_MIGRATED_RESULT.to_string(), true),
+        })
+        .collect()
+}
+
 pub(super) fn render_loop_limit_guard_message(
     last_assistant_text: &str,
     max_rounds: usize,
@@ -587,14 +1228,70 @@ pub(super) fn render_repeated_mutating_tool_guard_message(last_assistant_text: &
     )
 }
 
-pub(super) fn render_tool_denied_message(tool_name: &str) -> String {
-    if tool_requires_confirmation(tool_name) {
+/// A call that already got a result earlier in the same round.
+pub(super) struct RoundToolResult {
+    pub(super) id: String,
+    pub(super) name: String,
+    pub(super) input: serde_json::Value,
+    pub(super) content: String,
+    pub(super) is_error: bool,
+    /// The call ran and may have changed the workspace.
+    pub(super) mutated: bool,
+}
+
+/// The latest earlier call in this round with the same name and input, if
+/// nothing that may have changed the workspace ran after it; after a
+/// mutation the same call can return something else, so it runs again.
+pub(super) fn find_duplicate_tool_call<'a>(
+    results: &'a [RoundToolResult],
+    name: &str,
+    input: &serde_json::Value,
+) -> Option<&'a RoundToolResult> {
+    let position = results
+        .iter()
+        .rposition(|earlier| earlier.name == name && earlier.input == *input)?;
+    results[position + 1..]
+        .iter()
+        .all(|later| !later.mutated)
+        .then(|| &results[position])
+}
+
+pub(super) fn render_duplicate_tool_call_note(original_id: &str) -> String {
+    format!(
+        "[duplicate of tool call {original_id} in this round; it ran once and this is its result]"
+    )
+}
+
+pub(super) fn render_tool_denied_message(tool_name: &str, reason: Option<&str>) -> String {
+    let base = if tool_requires_confirmation(tool_name) {
         format!("Stopped: approval denied for {tool_name}. No file changes were made.")
     } else {
         format!("Stopped: approval denied for {tool_name}. No tool actions were performed.")
+    };
+    match reason.map(str::trim).filter(|reason| !reason.is_empty()) {
+        Some(reason) => format!(
+            "{base}\nUser reason: {reason}\nAdjust your approach to respect this reason instead of retrying the same call."
+        ),
+        None => base,
     }
 }
 
+/// Note prepended to the next user message after a turn was cancelled with a reason.
+pub fn render_turn_cancelled_note(reason: &str) -> String {
+    format!(
+        "[The previous turn was cancelled by the user. Reason: {}]",
+        reason.trim()
+    )
+}
+
+/// Prepended to the next user message after a response was cut off as
+/// runaway output.
+pub(super) fn render_runaway_note(reason: &str) -> String {
+    format!(
+        "[Your previous response was cut off because it {reason}. Do not repeat earlier text; continue from where the useful output ended, or say briefly that you are stuck.]"
+    )
+}
+
 pub(super) fn render_missing_tool_evidence_guard_message(last_assistant_text: &str) -> String {
     render_loop_guard_message(
         last_assistant_text,
@@ -612,13 +1309,78 @@ pub(super) fn render_loop_guard_message(last_assistant_text: &str, reason: Strin
     format!("{summary}\n\n[loop guard] {reason}")
 }
 
+/// A `suggest_commit_message` call without an `intent` describes the request
+/// that started the turn, and a `git_commit` call without a message commits
+/// the last suggested draft, so the approval prompt shows that draft.
+pub(super) fn with_commit_defaults(
+    name: &str,
+    mut input: serde_json::Value,
+    request: &str,
+    suggestion: impl FnOnce() -> Option<String>,
+) -> serde_json::Value {
+    let (key, default) = match name {
+        "suggest_commit_message" if !request.trim().is_empty() => ("intent", request.to_string()),
+        "git_commit" => {
+            let has_message = ["message", "msg", "commit_message"].iter().any(|key| {
+                input
+                    .get(key)
+                    .and_then(|value| value.as_str())
+                    .is_some_and(|message| !message.trim().is_empty())
+            });
+            match suggestion() {
+                Some(draft) if !has_message => ("message", draft),
+                _ => return input,
+            }
+        }
+        _ => return input,
+    };
+    if input.is_null() {
+        input = serde_json::Value::Object(Default::default());
+    }
+    if let Some(fields) = input.as_object_mut() {
+        let value = fields.entry(key).or_insert(serde_json::Value::Null);
+        if value.as_str().is_none_or(|text| text.trim().is_empty()) {
+            *value = serde_json::Value::String(default);
+        }
+    }
+    input
+}
+
 pub(super) fn is_read_only_tool_name(name: &str) -> bool {
     matches!(
         name,
-        "read_file" | "search" | "search_files" | "list_files" | "list_directory"
+        "read_file"
+            | "search"
+            | "search_files"
+            | "list_files"
+            | "list_directory"
+            | "tree"
+            | "tail_file"
+            | "workspace_stats"
+            | "repo_map"
+            | "recall"
+            | "expand_tool_result"
+            | "scratch_read"
+            | "suggest_commit_message"
+            | "ask_user"
     )
 }
 
+/// Read-only lookups queue first and workspace-wide scans and test runs
+/// last; everything else, edits included, in between.
+pub(super) fn tool_priority(name: &str) -> ToolPriority {
+    if matches!(
+        name,
+        "search" | "search_files" | "workspace_stats" | "repo_map" | "run_tests"
+    ) {
+        ToolPriority::Bulk
+    } else if is_read_only_tool_name(name) {
+        ToolPriority::Interactive
+    } else {
+        ToolPriority::Normal
+    }
+}
+
 pub(super) fn is_read_only_tool_round(blocks: &[ContentBlock]) -> bool {
     blocks.iter().all(|block| {
         matches!(
@@ -640,7 +1402,7 @@ pub(super) fn is_mutating_tool_round(blocks: &[ContentBlock]) -> bool {
 pub(super) fn tool_requires_confirmation(name: &str) -> bool {
     matches!(
         name,
-        "write_file" | "edit_file" | "rename_file" | "git_add" | "git_commit"
+        "write_file" | "edit_file" | "rename_file" | "git_add" | "git_commit" | "run_tests"
     )
 }
 
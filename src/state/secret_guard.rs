@@ -0,0 +1,343 @@
+use super::session_store::SavedSession;
+use crate::types::{ApiMessage, Content, ContentBlock};
+use crate::util::parse_bool_str;
+use aho_corasick::AhoCorasick;
+use serde_json::{json, Value};
+
+const SECRET_GUARD_ENV: &str = "VEX_SECRET_GUARD";
+/// Name of the approval prompt raised before a request carrying secrets.
+pub const SEND_SECRETS_APPROVAL: &str = "send_secrets";
+/// Shorter values match too much ordinary text to be worth guarding.
+const MIN_SECRET_CHARS: usize = 8;
+const SECRET_NAME_HINTS: [&str; 8] = [
+    "KEY",
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "PASSWD",
+    "CREDENTIAL",
+    "PRIVATE",
+    "AUTH",
+];
+/// Variables whose names match a hint but hold locations, not credentials.
+const NON_SECRET_SUFFIXES: [&str; 5] = ["_SOCK", "_PATH", "_FILE", "_DIR", "_URL"];
+
+/// A local secret value and where it came from, e.g. an environment
+/// variable name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownSecret {
+    pub source: String,
+    value: String,
+}
+
+impl KnownSecret {
+    /// The first four characters and the length, for prompts and notes.
+    pub fn masked(&self) -> String {
+        let prefix: String = self.value.chars().take(4).collect();
+        format!("{prefix}... ({} chars)", self.value.chars().count())
+    }
+}
+
+/// Finds values of local secrets in outgoing messages, so they are confirmed
+/// or redacted before a request leaves for the provider.
+#[derive(Debug, Clone, Default)]
+pub struct SecretGuard {
+    secrets: Vec<KnownSecret>,
+    matcher: Option<AhoCorasick>,
+}
+
+impl SecretGuard {
+    /// `(source, value)` pairs; short and duplicate values are dropped.
+    pub fn new(secrets: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut known: Vec<KnownSecret> = Vec::new();
+        for (source, value) in secrets {
+            let value = value.trim().to_string();
+            if value.chars().count() < MIN_SECRET_CHARS
+                || known.iter().any(|secret| secret.value == value)
+            {
+                continue;
+            }
+            known.push(KnownSecret { source, value });
+        }
+        let matcher = (!known.is_empty())
+            .then(|| AhoCorasick::new(known.iter().map(|secret| &secret.value)).ok())
+            .flatten();
+        Self {
+            secrets: known,
+            matcher,
+        }
+    }
+
+    /// Values of environment variables whose names suggest credentials
+    /// (`*_API_KEY`, `*_TOKEN`, `*_SECRET`, ...), which include the
+    /// provider key vex loaded. `VEX_SECRET_GUARD=off` disables the guard.
+    pub fn from_env() -> Self {
+        if std::env::var(SECRET_GUARD_ENV)
+            .ok()
+            .and_then(|value| parse_bool_str(&value))
+            == Some(false)
+        {
+            return Self::default();
+        }
+        Self::new(std::env::vars().filter(|(name, value)| {
+            let value = value.trim();
+            looks_like_secret_name(name)
+                && !value.starts_with('/')
+                && !value.contains(char::is_whitespace)
+        }))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+
+    /// Secrets whose values appear anywhere in `messages`, in guard order.
+    pub fn scan(&self, messages: &[ApiMessage]) -> Vec<KnownSecret> {
+        self.scan_with(|visit| {
+            for message in messages {
+                for_each_text(message, &mut |text| visit(text));
+            }
+        })
+    }
+
+    /// Secrets whose values appear in `text`, such as a document about to be
+    /// uploaded or the system prompt.
+    pub fn scan_text(&self, text: &str) -> Vec<KnownSecret> {
+        self.scan_with(|visit| visit(text))
+    }
+
+    fn scan_with(&self, texts: impl FnOnce(&mut dyn FnMut(&str))) -> Vec<KnownSecret> {
+        let Some(matcher) = &self.matcher else {
+            return Vec::new();
+        };
+        let mut found = vec![false; self.secrets.len()];
+        texts(&mut |text| {
+            for hit in matcher.find_iter(text) {
+                found[hit.pattern().as_usize()] = true;
+            }
+        });
+        self.secrets
+            .iter()
+            .zip(found)
+            .filter(|(_, found)| *found)
+            .map(|(secret, _)| secret.clone())
+            .collect()
+    }
+
+    /// Replaces each of `secrets` in `messages` with `[redacted <source>]`.
+    pub fn redact(&self, messages: &mut [ApiMessage], secrets: &[KnownSecret]) {
+        for message in messages {
+            for_each_text_mut(message, &mut |text| redact_text(text, secrets));
+        }
+    }
+
+    /// Strips every known secret from `session` before it is written to
+    /// disk, including ones allowed to reach the provider: the messages are
+    /// redacted, and read_file snapshots holding one keep only their hash.
+    pub fn redact_session(&self, session: &mut SavedSession) {
+        let Some(matcher) = &self.matcher else {
+            return;
+        };
+        self.redact(&mut session.messages, &self.secrets);
+        session
+            .read_file_snapshots
+            .drop_content_matching(|content| matcher.is_match(content));
+    }
+}
+
+/// Input of the [`SEND_SECRETS_APPROVAL`] prompt; carries masked values only.
+pub fn send_secrets_input(secrets: &[KnownSecret]) -> Value {
+    json!({
+        "secrets": secrets
+            .iter()
+            .map(|secret| json!({ "source": secret.source, "value": secret.masked() }))
+            .collect::<Vec<_>>()
+    })
+}
+
+/// Replaces each of `secrets` in `text` with `[redacted <source>]`.
+pub fn redact_text(text: &mut String, secrets: &[KnownSecret]) {
+    for secret in secrets {
+        if text.contains(&secret.value) {
+            *text = text.replace(&secret.value, &format!("[redacted {}]", secret.source));
+        }
+    }
+}
+
+fn looks_like_secret_name(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    upper != SECRET_GUARD_ENV
+        && SECRET_NAME_HINTS.iter().any(|hint| upper.contains(hint))
+        && !NON_SECRET_SUFFIXES
+            .iter()
+            .any(|suffix| upper.ends_with(suffix))
+}
+
+fn for_each_text(message: &ApiMessage, visit: &mut impl FnMut(&str)) {
+    match &message.content {
+        Content::Text(text) => visit(text),
+        Content::Blocks(blocks) => {
+            for block in blocks {
+                match block {
+                    ContentBlock::Text { text } => visit(text),
+                    ContentBlock::ToolResult { content, .. } => visit(content),
+                    ContentBlock::ToolUse { input, .. } => visit_json(input, visit),
+                    // The provider wrote the thinking, and its signature
+                    // covers the text, so it goes back unchanged.
+                    ContentBlock::Thinking { .. }
+                    | ContentBlock::RedactedThinking { .. }
+                    | ContentBlock::Document { .. } => {}
+                }
+            }
+        }
+    }
+}
+
+fn visit_json(value: &Value, visit: &mut impl FnMut(&str)) {
+    match value {
+        Value::String(text) => visit(text),
+        Value::Array(items) => items.iter().for_each(|item| visit_json(item, visit)),
+        Value::Object(fields) => fields.values().for_each(|item| visit_json(item, visit)),
+        _ => {}
+    }
+}
+
+fn for_each_text_mut(message: &mut ApiMessage, visit: &mut impl FnMut(&mut String)) {
+    match &mut message.content {
+        Content::Text(text) => visit(text),
+        Content::Blocks(blocks) => {
+            for block in blocks {
+                match block {
+                    ContentBlock::Text { text } => visit(text),
+                    ContentBlock::ToolResult { content, .. } => visit(content),
+                    ContentBlock::ToolUse { input, .. } => visit_json_mut(input, visit),
+                    ContentBlock::Thinking { .. }
+                    | ContentBlock::RedactedThinking { .. }
+                    | ContentBlock::Document { .. } => {}
+                }
+            }
+        }
+    }
+}
+
+fn visit_json_mut(value: &mut Value, visit: &mut impl FnMut(&mut String)) {
+    match value {
+        Value::String(text) => visit(text),
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| visit_json_mut(item, visit)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|item| visit_json_mut(item, visit)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(content: Content) -> ApiMessage {
+        ApiMessage {
+            role: "user".to_string(),
+            content,
+        }
+    }
+
+    #[test]
+    fn test_secret_names_skip_locations() {
+        assert!(looks_like_secret_name("ANTHROPIC_API_KEY"));
+        assert!(looks_like_secret_name("github_token"));
+        assert!(looks_like_secret_name("DB_PASSWORD"));
+        assert!(!looks_like_secret_name("SSH_AUTH_SOCK"));
+        assert!(!looks_like_secret_name(
+            "GOOGLE_APPLICATION_CREDENTIALS_FILE"
+        ));
+        assert!(!looks_like_secret_name("HOME"));
+        assert!(!looks_like_secret_name(SECRET_GUARD_ENV));
+    }
+
+    #[test]
+    fn test_scan_and_redact_cover_text_tool_results_and_inputs() {
+        let guard = SecretGuard::new([
+            (
+                "ANTHROPIC_API_KEY".to_string(),
+                "sk-ant-0123456789".to_string(),
+            ),
+            ("SHORT_TOKEN".to_string(), "abc".to_string()),
+            ("DB_PASSWORD".to_string(), "hunter2hunter2".to_string()),
+        ]);
+        let mut messages = vec![
+            message(Content::Text("plain question".to_string())),
+            message(Content::Blocks(vec![
+                ContentBlock::ToolUse {
+                    id: "t1".to_string(),
+                    name: "write_file".to_string(),
+                    input: json!({ "path": ".env", "content": "PW=hunter2hunter2" }),
+                },
+                ContentBlock::ToolResult {
+                    tool_use_id: "t1".to_string(),
+                    content: "export ANTHROPIC_API_KEY=sk-ant-0123456789".to_string(),
+                    is_error: false,
+                },
+            ])),
+        ];
+
+        let found = guard.scan(&messages);
+        assert_eq!(
+            found
+                .iter()
+                .map(|secret| secret.source.as_str())
+                .collect::<Vec<_>>(),
+            ["ANTHROPIC_API_KEY", "DB_PASSWORD"]
+        );
+        assert_eq!(found[0].masked(), "sk-a... (17 chars)");
+        assert_eq!(
+            send_secrets_input(&found[..1]),
+            json!({ "secrets": [{ "source": "ANTHROPIC_API_KEY", "value": "sk-a... (17 chars)" }] })
+        );
+
+        guard.redact(&mut messages, &found);
+        assert!(guard.scan(&messages).is_empty());
+        let Content::Blocks(blocks) = &messages[1].content else {
+            panic!("blocks");
+        };
+        let ContentBlock::ToolResult { content, .. } = &blocks[1] else {
+            panic!("tool result");
+        };
+        assert_eq!(
+            content,
+            "export ANTHROPIC_API_KEY=[redacted ANTHROPIC_API_KEY]"
+        );
+        assert!(SecretGuard::default().scan(&messages).is_empty());
+    }
+
+    #[test]
+    fn test_saved_sessions_keep_no_secrets() {
+        let guard = SecretGuard::new([("DB_PASSWORD".to_string(), "hunter2hunter2".to_string())]);
+        let mut session = SavedSession {
+            version: 1,
+            id: "s1".to_string(),
+            saved_at: 0,
+            messages: vec![message(Content::Text("PW=hunter2hunter2".to_string()))],
+            token_usage: Default::default(),
+            turn_count: 1,
+            tool_statuses: Vec::new(),
+            read_file_snapshots: Default::default(),
+            turn_overrides: Vec::new(),
+            turn_changes: Vec::new(),
+            turn_tags: Vec::new(),
+            sampling: Default::default(),
+        };
+        session
+            .read_file_snapshots
+            .summarize(".env", "PW=hunter2hunter2\n");
+        session.read_file_snapshots.summarize("a.rs", "fn a() {}\n");
+
+        guard.redact_session(&mut session);
+        let json = serde_json::to_string(&session).expect("json");
+        assert!(!json.contains("hunter2hunter2"), "{json}");
+        assert!(json.contains("[redacted DB_PASSWORD]"));
+        assert!(json.contains("fn a() {}"));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::util::parse_bool_str;
+
+const SENSITIVE_PATHS_ENV: &str = "VEX_SENSITIVE_PATHS";
+
+/// Paths whose edits always need explicit approval: manifests, lockfiles,
+/// CI configuration, database migrations, and files that run code later
+/// (vex's state, git's hooks and config).
+pub const DEFAULT_SENSITIVE_PATTERNS: [&str; 13] = [
+    "Cargo.toml",
+    "*.lock",
+    "package.json",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "go.mod",
+    "go.sum",
+    ".github/workflows/**",
+    ".gitlab-ci.yml",
+    ".circleci/**",
+    "**/migrations/**",
+    ".aistar/**",
+    ".git/**",
+];
+
+/// Glob list for the sensitive approval tier. Patterns without a `/` match
+/// the file name at any depth; patterns with one match the whole
+/// workspace-relative path, where `*` stays within a segment and `**` spans
+/// segments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SensitivePaths {
+    patterns: Vec<String>,
+}
+
+impl Default for SensitivePaths {
+    fn default() -> Self {
+        Self::new(DEFAULT_SENSITIVE_PATTERNS.iter().map(|p| p.to_string()))
+    }
+}
+
+impl SensitivePaths {
+    pub fn new(patterns: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            patterns: patterns
+                .into_iter()
+                .map(|pattern| pattern.trim().trim_start_matches("./").to_string())
+                .filter(|pattern| !pattern.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Reads `VEX_SENSITIVE_PATHS`, a comma-separated glob list. `default`
+    /// in the list expands to the built-in patterns; `off` disables the tier.
+    pub fn from_env() -> Self {
+        match std::env::var(SENSITIVE_PATHS_ENV) {
+            Ok(value) => Self::parse(&value),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        if parse_bool_str(value) == Some(false) {
+            return Self::new(Vec::new());
+        }
+        Self::new(value.split(',').flat_map(|entry| {
+            if entry.trim() == "default" {
+                DEFAULT_SENSITIVE_PATTERNS
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect()
+            } else {
+                vec![entry.to_string()]
+            }
+        }))
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// The first pattern matching `path`, if any.
+    pub fn matching(&self, path: &str) -> Option<&str> {
+        let path = path.trim().trim_start_matches("./");
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let file_name = segments.last()?;
+        self.patterns
+            .iter()
+            .find(|pattern| {
+                if pattern.contains('/') {
+                    let pattern: Vec<&str> = pattern.split('/').collect();
+                    segments_match(&pattern, &segments)
+                } else {
+                    wildcard_match(pattern.as_bytes(), file_name.as_bytes())
+                }
+            })
+            .map(String::as_str)
+    }
+}
+
+/// Whether workspace-relative `path` matches `pattern` segment by segment:
+/// `*` and `?` stay within one segment and `**` spans any number of them.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    segments_match(&pattern, &segments)
+}
+
+fn segments_match(pattern: &[&str], segments: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => segments.is_empty(),
+        Some((&"**", rest)) => {
+            (0..=segments.len()).any(|skip| segments_match(rest, &segments[skip..]))
+        }
+        Some((first, rest)) => segments.split_first().is_some_and(|(segment, tail)| {
+            wildcard_match(first.as_bytes(), segment.as_bytes()) && segments_match(rest, tail)
+        }),
+    }
+}
+
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| wildcard_match(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && wildcard_match(rest, &text[1..]),
+        Some((ch, rest)) => text.first() == Some(ch) && wildcard_match(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_patterns_cover_manifests_ci_and_migrations() {
+        let paths = SensitivePaths::default();
+        assert_eq!(paths.matching("Cargo.toml"), Some("Cargo.toml"));
+        assert_eq!(
+            paths.matching("./crates/core/Cargo.toml"),
+            Some("Cargo.toml")
+        );
+        assert_eq!(paths.matching("yarn.lock"), Some("*.lock"));
+        assert_eq!(
+            paths.matching(".github/workflows/ci.yml"),
+            Some(".github/workflows/**")
+        );
+        assert_eq!(
+            paths.matching("db/migrations/001_init.sql"),
+            Some("**/migrations/**")
+        );
+        assert_eq!(paths.matching(".git/hooks/pre-commit"), Some(".git/**"));
+        assert_eq!(paths.matching("./.git/config"), Some(".git/**"));
+        assert_eq!(paths.matching("src/main.rs"), None);
+        assert_eq!(paths.matching("docs/github/workflows.md"), None);
+    }
+
+    #[test]
+    fn test_parse_extends_or_disables_defaults() {
+        let paths = SensitivePaths::parse("default, schema/*.sql");
+        assert_eq!(paths.matching("schema/users.sql"), Some("schema/*.sql"));
+        assert_eq!(paths.matching("schema/nested/users.sql"), None);
+        assert!(paths.matching("Cargo.toml").is_some());
+
+        let only_custom = SensitivePaths::parse("Dockerfile");
+        assert!(only_custom.matching("Cargo.toml").is_none());
+        assert!(only_custom.matching("deploy/Dockerfile").is_some());
+
+        assert!(SensitivePaths::parse("off").patterns().is_empty());
+    }
+}
@@ -0,0 +1,491 @@
+use super::stream_block::ToolStatusRecord;
+use crate::change_summary::TurnChangeSummary;
+use crate::config::Sampling;
+use crate::state_dirs::StateDirs;
+use crate::tags::{starts_turn, TurnTag};
+use crate::tool_preview::ReadFileSnapshotCache;
+use crate::turn_overrides::{clip_recorded_prompt, TurnOverrideRecord};
+use crate::types::{ApiMessage, Content, ContentBlock, TokenUsage};
+use crate::util::parse_bool_str;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const SESSIONS_ENV: &str = "VEX_SESSIONS";
+/// Saved sessions kept per workspace; the least recently saved go first.
+const MAX_SAVED_SESSIONS: usize = 20;
+/// Bumped when a saved session can no longer be read by older builds.
+pub const SAVED_SESSION_VERSION: u32 = 1;
+/// Assistant text longer than this is clipped in a resume recap.
+const MAX_RECAP_TEXT_CHARS: usize = 600;
+/// One summary per saved session, with each turn's file changes, so the
+/// sessions can be listed and browsed without reading every conversation.
+const SESSION_INDEX_FILE: &str = "index.json";
+
+/// Sessions are saved unless `VEX_SESSIONS` is off.
+pub fn sessions_enabled_from_env() -> bool {
+    std::env::var(SESSIONS_ENV)
+        .ok()
+        .and_then(|value| parse_bool_str(&value))
+        .unwrap_or(true)
+}
+
+/// Everything needed to continue a conversation in a later process: the
+/// message history, how each tool call ended, and what `read_file` last
+/// returned for each path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub version: u32,
+    pub id: String,
+    pub saved_at: u64,
+    pub messages: Vec<ApiMessage>,
+    #[serde(default)]
+    pub token_usage: TokenUsage,
+    #[serde(default)]
+    pub turn_count: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_statuses: Vec<ToolStatusRecord>,
+    #[serde(default)]
+    pub read_file_snapshots: ReadFileSnapshotCache,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turn_overrides: Vec<TurnOverrideRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turn_changes: Vec<TurnChangeSummary>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turn_tags: Vec<TurnTag>,
+    /// Sampling parameters the session was pinned to, if any.
+    #[serde(default, skip_serializing_if = "Sampling::is_empty")]
+    pub sampling: Sampling,
+}
+
+impl SavedSession {
+    /// The first prompt of the session, on one line.
+    pub fn title(&self) -> String {
+        let prompt = self
+            .messages
+            .iter()
+            .find(|message| starts_turn(message))
+            .map(message_text)
+            .unwrap_or_default();
+        clip_recorded_prompt(&prompt.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    pub fn summary(&self) -> SavedSessionSummary {
+        SavedSessionSummary {
+            id: self.id.clone(),
+            saved_at: self.saved_at,
+            turn_count: self.turn_count,
+            title: self.title(),
+            tags: self.tags(),
+            turn_changes: self.turn_changes.clone(),
+        }
+    }
+
+    /// The distinct tags put on the session's turns, sorted.
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.turn_tags.iter().map(|tag| tag.tag.clone()).collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    /// Each prompt and reply, with tool calls listed by name, for showing a
+    /// resumed conversation in the transcript.
+    pub fn recap_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for message in &self.messages {
+            if message.role == "user" {
+                if starts_turn(message) {
+                    lines.push(format!("> {}", message_text(message).trim()));
+                }
+                continue;
+            }
+            let text = message_text(message);
+            let text = text.trim();
+            if !text.is_empty() {
+                let mut clipped: String = text.chars().take(MAX_RECAP_TEXT_CHARS).collect();
+                if clipped.len() < text.len() {
+                    clipped.push_str("...");
+                }
+                lines.extend(clipped.lines().map(str::to_string));
+            }
+            if let Content::Blocks(blocks) = &message.content {
+                let tools: Vec<&str> = blocks
+                    .iter()
+                    .filter_map(|block| match block {
+                        ContentBlock::ToolUse { name, .. } => Some(name.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                if !tools.is_empty() {
+                    lines.push(format!("[tool calls: {}]", tools.join(", ")));
+                }
+            }
+        }
+        lines
+    }
+}
+
+/// The text blocks of `message`, joined.
+fn message_text(message: &ApiMessage) -> String {
+    match &message.content {
+        Content::Text(text) => text.clone(),
+        Content::Blocks(blocks) => blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedSessionSummary {
+    pub id: String,
+    pub saved_at: u64,
+    pub turn_count: usize,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// What each turn's tools changed, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turn_changes: Vec<TurnChangeSummary>,
+}
+
+/// Saved sessions under `.aistar/conversations/`, one `<id>.json` each,
+/// rewritten after every turn so a restart can pick the conversation up, and
+/// an `index.json` summarizing them.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn for_workspace(working_dir: &Path) -> Self {
+        Self::new(StateDirs::for_workspace(working_dir).conversations_dir())
+    }
+
+    /// The workspace's store unless `VEX_SESSIONS` is off.
+    pub fn from_env(working_dir: &Path) -> Option<Self> {
+        sessions_enabled_from_env().then(|| Self::for_workspace(working_dir))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes `session` over its earlier save and drops the oldest saves
+    /// beyond the retention limit. A session with no messages is not saved.
+    pub fn save(&self, session: &SavedSession) -> Result<Option<PathBuf>> {
+        if session.messages.is_empty() {
+            return Ok(None);
+        }
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        let path = self.path_for(&session.id)?;
+        let staged = path.with_extension("json.tmp");
+        let json = serde_json::to_vec(session).context("failed to serialize session")?;
+        fs::write(&staged, json)
+            .with_context(|| format!("failed to write {}", staged.display()))?;
+        fs::rename(&staged, &path)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        self.prune()?;
+        let mut index = self.index()?;
+        index.retain(|entry| entry.id != session.id);
+        index.push(session.summary());
+        self.write_index(index)?;
+        Ok(Some(path))
+    }
+
+    /// Saved sessions, most recently saved first, from the index.
+    pub fn list(&self) -> Result<Vec<SavedSessionSummary>> {
+        let mut summaries = self.index()?;
+        summaries.sort_by(|left, right| {
+            right
+                .saved_at
+                .cmp(&left.saved_at)
+                .then_with(|| right.id.cmp(&left.id))
+        });
+        Ok(summaries)
+    }
+
+    /// The session saved as `id`, or the only one whose id starts with it.
+    pub fn load(&self, id: &str) -> Result<SavedSession> {
+        let id = id.trim();
+        if id.is_empty() {
+            bail!("no session id given");
+        }
+        let exact = self.path_for(id)?;
+        if exact.is_file() {
+            return read_session(&exact);
+        }
+        let matches: Vec<PathBuf> = self
+            .files()?
+            .into_iter()
+            .filter(|path| {
+                path.file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .is_some_and(|stem| stem.starts_with(id))
+            })
+            .collect();
+        match matches.as_slice() {
+            [path] => read_session(path),
+            [] => bail!("no saved session matches '{id}'"),
+            _ => bail!(
+                "'{id}' matches {} saved sessions; give more of the id",
+                matches.len()
+            ),
+        }
+    }
+
+    /// The index entry for `id`, or the only one whose id starts with it.
+    pub fn find_summary(&self, id: &str) -> Result<SavedSessionSummary> {
+        let id = id.trim();
+        let mut matches: Vec<SavedSessionSummary> = self
+            .index()?
+            .into_iter()
+            .filter(|entry| entry.id.starts_with(id))
+            .collect();
+        if let Some(exact) = matches.iter().position(|entry| entry.id == id) {
+            return Ok(matches.swap_remove(exact));
+        }
+        match matches.len() {
+            1 => Ok(matches.remove(0)),
+            0 => bail!("no saved session matches '{id}'"),
+            count => bail!("'{id}' matches {count} saved sessions; give more of the id"),
+        }
+    }
+
+    /// The index, less entries whose session file is gone. Without an index
+    /// (sessions saved by an older build), it is rebuilt from the files;
+    /// files that cannot be read are skipped.
+    fn index(&self) -> Result<Vec<SavedSessionSummary>> {
+        let path = self.dir.join(SESSION_INDEX_FILE);
+        let indexed = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Vec<SavedSessionSummary>>(&bytes).ok());
+        let Some(mut index) = indexed else {
+            let index: Vec<SavedSessionSummary> = self
+                .files()?
+                .iter()
+                .filter_map(|path| read_session(path).ok())
+                .map(|session| session.summary())
+                .collect();
+            if !index.is_empty() {
+                self.write_index(index.clone())?;
+            }
+            return Ok(index);
+        };
+        index.retain(|entry| {
+            self.path_for(&entry.id)
+                .is_ok_and(|session| session.is_file())
+        });
+        Ok(index)
+    }
+
+    fn write_index(&self, mut index: Vec<SavedSessionSummary>) -> Result<()> {
+        index.retain(|entry| {
+            self.path_for(&entry.id)
+                .is_ok_and(|session| session.is_file())
+        });
+        let path = self.dir.join(SESSION_INDEX_FILE);
+        let staged = path.with_extension("json.tmp");
+        let json = serde_json::to_vec(&index).context("failed to serialize session index")?;
+        fs::write(&staged, json)
+            .with_context(|| format!("failed to write {}", staged.display()))?;
+        fs::rename(&staged, &path).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    fn path_for(&self, id: &str) -> Result<PathBuf> {
+        if id.is_empty()
+            || !id
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_'))
+        {
+            bail!("invalid session id '{id}'");
+        }
+        Ok(self.dir.join(format!("{id}.json")))
+    }
+
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to read {}", self.dir.display()))
+            }
+        };
+        Ok(entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "json")
+                    && !path.ends_with(SESSION_INDEX_FILE)
+            })
+            .collect())
+    }
+
+    fn prune(&self) -> Result<()> {
+        let mut files: Vec<(std::time::SystemTime, PathBuf)> = self
+            .files()?
+            .into_iter()
+            .filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+            .collect();
+        if files.len() <= MAX_SAVED_SESSIONS {
+            return Ok(());
+        }
+        files.sort();
+        for (_, path) in &files[..files.len() - MAX_SAVED_SESSIONS] {
+            let _ = fs::remove_file(path);
+        }
+        Ok(())
+    }
+}
+
+fn read_session(path: &Path) -> Result<SavedSession> {
+    let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let session: SavedSession = serde_json::from_slice(&bytes)
+        .with_context(|| format!("invalid saved session {}", path.display()))?;
+    if session.version > SAVED_SESSION_VERSION {
+        bail!(
+            "{} was saved by a newer vex (format {}); upgrade to resume it",
+            path.display(),
+            session.version
+        );
+    }
+    Ok(session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(id: &str, saved_at: u64, prompt: &str) -> SavedSession {
+        SavedSession {
+            version: SAVED_SESSION_VERSION,
+            id: id.to_string(),
+            saved_at,
+            messages: vec![
+                ApiMessage {
+                    role: "user".to_string(),
+                    content: Content::Text(prompt.to_string()),
+                },
+                ApiMessage {
+                    role: "assistant".to_string(),
+                    content: Content::Blocks(vec![
+                        ContentBlock::Text {
+                            text: "Reading it.".to_string(),
+                        },
+                        ContentBlock::ToolUse {
+                            id: "tool-1".to_string(),
+                            name: "read_file".to_string(),
+                            input: serde_json::json!({"path": "src/main.rs"}),
+                        },
+                    ]),
+                },
+            ],
+            token_usage: TokenUsage::default(),
+            turn_count: 1,
+            tool_statuses: Vec::new(),
+            read_file_snapshots: ReadFileSnapshotCache::default(),
+            turn_overrides: Vec::new(),
+            turn_changes: Vec::new(),
+            turn_tags: Vec::new(),
+            sampling: Sampling::default(),
+        }
+    }
+
+    #[test]
+    fn test_sessions_round_trip_and_load_by_unique_prefix() {
+        let temp = tempfile::tempdir().expect("tempdir");
+        let store = SessionStore::new(temp.path().join("conversations"));
+        assert!(store.list().expect("list").is_empty());
+
+        store
+            .save(&session("abc-1", 10, "explain\n  the parser"))
+            .expect("save");
+        let mut tagged = session("abc-2", 20, "fix the lexer");
+        tagged.turn_tags = ["lexer", "bug-hunt", "lexer"]
+            .iter()
+            .map(|tag| TurnTag {
+                turn: 1,
+                tag: tag.to_string(),
+                prompt: String::new(),
+            })
+            .collect();
+        store.save(&tagged).expect("save");
+        let mut empty = session("empty", 30, "");
+        empty.messages.clear();
+        assert!(store.save(&empty).expect("save").is_none());
+
+        let listed = store.list().expect("list");
+        assert_eq!(
+            listed.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(),
+            ["abc-2", "abc-1"]
+        );
+        assert_eq!(listed[1].title, "explain the parser");
+        assert_eq!(listed[0].tags, ["bug-hunt", "lexer"]);
+        assert!(listed[1].tags.is_empty());
+
+        let loaded = store.load("abc-1").expect("load");
+        assert_eq!(loaded.messages.len(), 2);
+        assert_eq!(
+            loaded.recap_lines(),
+            [
+                "> explain\n  the parser",
+                "Reading it.",
+                "[tool calls: read_file]"
+            ]
+        );
+        assert!(store.load("abc").is_err());
+        assert!(store.load("../abc-1").is_err());
+
+        // A newer build writes its own session file; this one cannot read it.
+        let mut newer = session("future", 40, "later");
+        newer.version = SAVED_SESSION_VERSION + 1;
+        fs::write(
+            store.dir().join("future.json"),
+            serde_json::to_vec(&newer).expect("serialize"),
+        )
+        .expect("write");
+        assert!(store.load("fut").is_err());
+        assert_eq!(store.list().expect("list").len(), 2);
+    }
+
+    #[test]
+    fn test_index_keeps_turn_changes_and_follows_the_session_files() {
+        let temp = tempfile::tempdir().expect("tempdir");
+        let store = SessionStore::new(temp.path().join("conversations"));
+        let mut changed = session("abc-1", 10, "add a parser");
+        let mut summary = TurnChangeSummary::new(1);
+        summary.record_file("src/parser.rs", None, Some("fn parse() {}\n"));
+        summary.record_command("run_tests".to_string());
+        changed.turn_changes = vec![summary.clone()];
+        store.save(&changed).expect("save");
+        store.save(&session("abd-2", 20, "explain")).expect("save");
+
+        let index = store.dir().join(SESSION_INDEX_FILE);
+        let indexed: Vec<SavedSessionSummary> =
+            serde_json::from_slice(&fs::read(&index).expect("index written")).expect("parse");
+        assert_eq!(indexed.len(), 2);
+        let found = store.find_summary("abc").expect("unique prefix");
+        assert_eq!(found.turn_changes, [summary]);
+        assert!(store.find_summary("ab").is_err());
+        assert!(store.find_summary("zzz").is_err());
+
+        fs::remove_file(store.dir().join("abd-2.json")).expect("remove");
+        fs::remove_file(&index).expect("remove index");
+        let listed = store.list().expect("rebuilt");
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].turn_changes.len(), 1);
+        assert!(index.is_file(), "index rewritten from the files");
+    }
+}
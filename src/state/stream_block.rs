@@ -33,6 +33,37 @@ pub enum ToolStatus {
     Cancelled,
 }
 
+impl ToolStatus {
+    /// Short lifecycle label, e.g. `preparing · running · done`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ToolStatus::Pending => "preparing",
+            ToolStatus::WaitingApproval => "awaiting approval",
+            ToolStatus::Executing => "running",
+            ToolStatus::Complete => "done",
+            ToolStatus::Error => "error",
+            ToolStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// True once the call will not change status again.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            ToolStatus::Complete | ToolStatus::Error | ToolStatus::Cancelled
+        )
+    }
+}
+
+/// The status a tool call ended with, kept for the whole session.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ToolStatusRecord {
+    pub turn: usize,
+    pub id: String,
+    pub name: String,
+    pub status: ToolStatus,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,402 @@
+use crate::util::unix_now;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+pub const PROJECT_STATE_DIR: &str = ".aistar";
+/// The layout this build reads and writes. 1 had no version marker and kept
+/// scratch directories in `tmp/`; 2 gives each session its own directory
+/// under `sessions/`.
+pub const STATE_LAYOUT_VERSION: u32 = 2;
+const VERSION_FILE: &str = "layout-version";
+const LAYOUT_LOCK_FILE: &str = "layout.lock";
+const SESSIONS_DIR: &str = "sessions";
+const SESSION_LOCK_FILE: &str = "session.lock";
+const SESSION_SCRATCH_DIR: &str = "tmp";
+/// Where layout 1 kept scratch directories.
+const LEGACY_SCRATCH_DIR: &str = "tmp";
+/// How long to wait for another instance to finish a migration.
+const LAYOUT_LOCK_WAIT: Duration = Duration::from_secs(5);
+const LAYOUT_LOCK_POLL: Duration = Duration::from_millis(50);
+
+static SESSION_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Every path vex keeps under a workspace's `.aistar/`. Files shared by all
+/// sessions (memory, logs, backups, locks) sit at the top; files only one
+/// running session uses live in its own `sessions/<id>/` directory, so
+/// concurrent instances never write the same scratch paths. A
+/// `layout-version` marker records the layout, and [`StateDirs::prepare`]
+/// upgrades older layouts and refuses newer ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDirs {
+    root: PathBuf,
+}
+
+impl StateDirs {
+    pub fn for_workspace(working_dir: &Path) -> Self {
+        Self {
+            root: working_dir.join(PROJECT_STATE_DIR),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn memory_db(&self) -> PathBuf {
+        self.root.join("memory.db")
+    }
+
+    /// The JSONL memory store older versions wrote; imported into
+    /// [`StateDirs::memory_db`] the first time it is opened.
+    pub fn legacy_memory_file(&self) -> PathBuf {
+        self.root.join("memory.jsonl")
+    }
+
+    pub fn usage_log(&self) -> PathBuf {
+        self.root.join("usage.jsonl")
+    }
+
+    pub fn audit_log(&self) -> PathBuf {
+        self.root.join("audit.jsonl")
+    }
+
+    pub fn hooks_file(&self) -> PathBuf {
+        self.root.join("hooks.toml")
+    }
+
+    pub fn conventions_file(&self) -> PathBuf {
+        self.root.join("conventions.md")
+    }
+
+    pub fn presets_dir(&self) -> PathBuf {
+        self.root.join("presets")
+    }
+
+    pub fn tools_dir(&self) -> PathBuf {
+        self.root.join("tools")
+    }
+
+    pub fn backups_dir(&self) -> PathBuf {
+        self.root.join("backups")
+    }
+
+    pub fn shares_dir(&self) -> PathBuf {
+        self.root.join("shares")
+    }
+
+    pub fn pastes_dir(&self) -> PathBuf {
+        self.root.join("pastes")
+    }
+
+    pub fn raw_dir(&self) -> PathBuf {
+        self.root.join("raw")
+    }
+
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.root.join("snapshots")
+    }
+
+    pub fn locks_dir(&self) -> PathBuf {
+        self.root.join("locks")
+    }
+
+    /// Saved conversations that `/resume` can reload.
+    pub fn conversations_dir(&self) -> PathBuf {
+        self.root.join("conversations")
+    }
+
+    pub fn sessions_dir(&self) -> PathBuf {
+        self.root.join(SESSIONS_DIR)
+    }
+
+    /// `path` relative to the workspace, as shown to the user and the model.
+    pub fn display(&self, path: &Path) -> String {
+        self.root
+            .parent()
+            .and_then(|workspace| path.strip_prefix(workspace).ok())
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    /// The layout found on disk; an unmarked directory is layout 1.
+    pub fn layout_version(&self) -> Result<u32> {
+        let path = self.root.join(VERSION_FILE);
+        match fs::read_to_string(&path) {
+            Ok(text) => text
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid layout version in {}", path.display())),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(1),
+            Err(error) => Err(error).with_context(|| format!("failed to read {}", path.display())),
+        }
+    }
+
+    /// Brings an existing state directory up to [`STATE_LAYOUT_VERSION`].
+    /// Migration runs under `layout.lock`, so instances starting together
+    /// migrate once. A layout from a newer vex is an error rather than
+    /// something to overwrite. A workspace without `.aistar/` is left alone.
+    pub fn prepare(&self) -> Result<()> {
+        if !self.root.is_dir() {
+            return Ok(());
+        }
+        let found = self.layout_version()?;
+        if found > STATE_LAYOUT_VERSION {
+            bail!(
+                "{} uses state layout {found}, but this vex only understands layout {STATE_LAYOUT_VERSION}; upgrade vex or move the directory aside",
+                self.root.display()
+            );
+        }
+        if found == STATE_LAYOUT_VERSION {
+            return Ok(());
+        }
+        let _lock = LayoutLock::acquire(&self.root)?;
+        // Another instance may have migrated while this one waited.
+        if self.layout_version()? < 2 {
+            remove_abandoned_sessions(&self.root.join(LEGACY_SCRATCH_DIR));
+            let _ = fs::remove_dir(self.root.join(LEGACY_SCRATCH_DIR));
+        }
+        self.write_version()
+    }
+
+    /// A directory for this process's session-only files. Nothing is
+    /// created until [`SessionDir::ensure`]; directories left by sessions
+    /// whose process is gone are removed now.
+    pub fn session(&self) -> SessionDir {
+        remove_abandoned_sessions(&self.sessions_dir());
+        let id = new_session_id();
+        SessionDir {
+            path: self.sessions_dir().join(&id),
+            id,
+            dirs: self.clone(),
+        }
+    }
+
+    fn write_version(&self) -> Result<()> {
+        fs::create_dir_all(&self.root)
+            .with_context(|| format!("failed to create {}", self.root.display()))?;
+        let path = self.root.join(VERSION_FILE);
+        let staged = self
+            .root
+            .join(format!("{VERSION_FILE}.{}", std::process::id()));
+        fs::write(&staged, format!("{STATE_LAYOUT_VERSION}\n"))
+            .with_context(|| format!("failed to write {}", staged.display()))?;
+        fs::rename(&staged, &path).with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// One session's directory under `.aistar/sessions/`, removed when dropped.
+/// Its `session.lock` names the owning process so later sessions can tell a
+/// live directory from one left by a crash.
+#[derive(Debug)]
+pub struct SessionDir {
+    id: String,
+    path: PathBuf,
+    dirs: StateDirs,
+}
+
+impl SessionDir {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn scratch_dir(&self) -> PathBuf {
+        self.path.join(SESSION_SCRATCH_DIR)
+    }
+
+    pub fn state_dirs(&self) -> &StateDirs {
+        &self.dirs
+    }
+
+    /// Creates the directory and its lock file, upgrading or marking the
+    /// state directory first.
+    pub fn ensure(&self) -> Result<&Path> {
+        if self.path.is_dir() {
+            return Ok(&self.path);
+        }
+        self.dirs.prepare()?;
+        if !self.dirs.root.join(VERSION_FILE).exists() {
+            self.dirs.write_version()?;
+        }
+        fs::create_dir_all(&self.path)
+            .with_context(|| format!("failed to create {}", self.path.display()))?;
+        // Keeps session files out of `git status` and `git add -A`.
+        let ignore = self.dirs.sessions_dir().join(".gitignore");
+        if !ignore.exists() {
+            fs::write(&ignore, "*\n")
+                .with_context(|| format!("failed to write {}", ignore.display()))?;
+        }
+        let lock = self.path.join(SESSION_LOCK_FILE);
+        fs::write(&lock, format!("{}\n", std::process::id()))
+            .with_context(|| format!("failed to write {}", lock.display()))?;
+        Ok(&self.path)
+    }
+}
+
+impl Drop for SessionDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Held while a layout migration runs; removed on drop. A lock left by a
+/// process that is gone is taken over.
+struct LayoutLock(PathBuf);
+
+impl LayoutLock {
+    fn acquire(root: &Path) -> Result<Self> {
+        let path = root.join(LAYOUT_LOCK_FILE);
+        let deadline = SystemTime::now() + LAYOUT_LOCK_WAIT;
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    use std::io::Write;
+                    writeln!(file, "{}", std::process::id())
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    return Ok(Self(path));
+                }
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                    let holder = read_pid(&path);
+                    if holder.is_some_and(|pid| !process_alive(pid)) {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if SystemTime::now() >= deadline {
+                        bail!(
+                            "another vex (pid {}) is upgrading {}; remove {} if it is not running",
+                            holder.map_or_else(|| "unknown".to_string(), |pid| pid.to_string()),
+                            root.display(),
+                            path.display()
+                        );
+                    }
+                    std::thread::sleep(LAYOUT_LOCK_POLL);
+                }
+                Err(error) => {
+                    return Err(error)
+                        .with_context(|| format!("failed to create {}", path.display()))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for LayoutLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Removes session directories in `dir` whose process is gone, judged by
+/// their `session.lock` or, for layout 1, the pid that starts their name.
+fn remove_abandoned_sessions(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let pid = read_pid(&path.join(SESSION_LOCK_FILE)).or_else(|| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.split('-').next())
+                .and_then(|pid| pid.parse().ok())
+        });
+        if let Some(pid) = pid {
+            if pid != std::process::id() && !process_alive(pid) {
+                let _ = fs::remove_dir_all(&path);
+            }
+        }
+    }
+}
+
+/// `<pid>-<unix seconds>-<n>`, unique among the sessions of this machine.
+pub fn new_session_id() -> String {
+    format!(
+        "{}-{}-{}",
+        std::process::id(),
+        unix_now(),
+        SESSION_SEQUENCE.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+pub fn process_alive(pid: u32) -> bool {
+    let proc_root = Path::new("/proc");
+    !proc_root.is_dir() || proc_root.join(pid.to_string()).exists()
+}
+
+/// Without a cheap liveness check, every process is treated as running.
+#[cfg(not(target_os = "linux"))]
+pub fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_layout_is_migrated_once_and_sessions_get_their_own_directories() -> Result<()> {
+        let temp = TempDir::new()?;
+        let dirs = StateDirs::for_workspace(temp.path());
+        dirs.prepare()?;
+        let session = dirs.session();
+        assert!(!dirs.root().exists(), "nothing is created before use");
+
+        // A layout 1 directory with scratch left by a dead session (pid 0
+        // is never live) and shared files that must survive.
+        fs::create_dir_all(dirs.root().join("tmp/0-1-0-turn1"))?;
+        fs::write(dirs.memory_db(), "")?;
+        assert_eq!(dirs.layout_version()?, 1);
+        dirs.prepare()?;
+        assert_eq!(dirs.layout_version()?, STATE_LAYOUT_VERSION);
+        assert!(!dirs.root().join("tmp").exists());
+        assert!(dirs.memory_db().exists());
+        assert!(!dirs.root().join(LAYOUT_LOCK_FILE).exists());
+
+        let abandoned = dirs.sessions_dir().join("0-1-0");
+        fs::create_dir_all(&abandoned)?;
+        fs::write(abandoned.join(SESSION_LOCK_FILE), "0\n")?;
+        let other = dirs.session();
+        assert!(!abandoned.exists());
+        assert_ne!(session.id(), other.id());
+        session.ensure()?;
+        other.ensure()?;
+        assert_eq!(
+            read_pid(&session.path().join(SESSION_LOCK_FILE)),
+            Some(std::process::id())
+        );
+        assert!(dirs
+            .display(&session.scratch_dir())
+            .starts_with(".aistar/sessions/"));
+        let path = session.path().to_path_buf();
+        drop(session);
+        assert!(!path.exists());
+        assert!(other.path().exists());
+
+        fs::write(dirs.root().join(VERSION_FILE), "9\n")?;
+        let error = dirs.prepare().expect_err("newer layout").to_string();
+        assert!(error.contains("state layout 9"), "{error}");
+        Ok(())
+    }
+}
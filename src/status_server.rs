@@ -0,0 +1,325 @@
+use crate::events::{EventBus, EventKind, HandlerId, RuntimeEvent};
+use crate::state::ConversationReader;
+use crate::util::parse_bool_flag;
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+pub const STATUS_ADDR_ENV: &str = "VEX_STATUS_ADDR";
+/// Allows `VEX_STATUS_ADDR` to name a non-loopback address.
+pub const STATUS_ALLOW_REMOTE_ENV: &str = "VEX_STATUS_ALLOW_REMOTE";
+/// Requests larger than this are not read further.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Default)]
+struct ToolSnapshot {
+    name: String,
+    running: bool,
+    duration: Option<Duration>,
+    /// Only whether the call failed: the error text can echo tool input.
+    failed: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct StatusState {
+    running: bool,
+    round: usize,
+    last_tool: Option<ToolSnapshot>,
+    last_turn: Option<(Duration, &'static str)>,
+}
+
+impl StatusState {
+    fn apply(&mut self, event: &RuntimeEvent) {
+        match event {
+            RuntimeEvent::TurnStarted { .. } => {
+                self.running = true;
+                self.round = 0;
+            }
+            RuntimeEvent::RoundStarted { round, .. } => {
+                self.running = true;
+                self.round = *round;
+            }
+            RuntimeEvent::TurnCompleted {
+                elapsed, cancelled, ..
+            } => {
+                self.running = false;
+                let outcome = if *cancelled { "cancelled" } else { "complete" };
+                self.last_turn = Some((*elapsed, outcome));
+            }
+            RuntimeEvent::TurnFailed { elapsed, .. } => {
+                self.running = false;
+                self.last_turn = Some((*elapsed, "error"));
+            }
+            RuntimeEvent::ToolStarted { name, .. } => {
+                self.last_tool = Some(ToolSnapshot {
+                    name: name.clone(),
+                    running: true,
+                    ..ToolSnapshot::default()
+                });
+            }
+            RuntimeEvent::ToolFinished {
+                name,
+                duration,
+                error,
+                ..
+            } => {
+                self.last_tool = Some(ToolSnapshot {
+                    name: name.clone(),
+                    running: false,
+                    duration: Some(*duration),
+                    failed: error.is_some(),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Serves `GET /healthz` and `GET /status` over plain HTTP so monitors and
+/// status-line scripts can follow a session without reading the terminal.
+/// `/status` carries counters only (turn, round, last tool, token usage),
+/// never prompts, replies, or tool input. Nothing a client sends changes
+/// the session.
+pub struct StatusServer {
+    addr: SocketAddr,
+    accept_task: JoinHandle<()>,
+    events: EventBus,
+    handler: HandlerId,
+}
+
+impl StatusServer {
+    /// Binds `addr` and follows the session through `events` and `reader`.
+    /// A bare port binds 127.0.0.1; other hosts must be loopback unless
+    /// `allow_remote`. Must be called inside a Tokio runtime.
+    pub fn bind(
+        addr: &str,
+        allow_remote: bool,
+        events: &EventBus,
+        reader: ConversationReader,
+    ) -> Result<Self> {
+        let addr = parse_addr(addr, allow_remote)?;
+        let listener = std::net::TcpListener::bind(addr)
+            .with_context(|| format!("failed to bind status endpoint {addr}"))?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let listener = TcpListener::from_std(listener)?;
+
+        let state = Arc::new(Mutex::new(StatusState::default()));
+        let handler_state = Arc::clone(&state);
+        let kinds = [EventKind::Turn, EventKind::Error, EventKind::Tool];
+        let handler = events.subscribe_fn(&kinds, move |event| {
+            handler_state
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .apply(event);
+        });
+        let started = Instant::now();
+        let accept_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let body = {
+                    let state = state
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .clone();
+                    status_json(&state, &reader, started.elapsed())
+                };
+                tokio::spawn(async move {
+                    let _ = tokio::time::timeout(REQUEST_TIMEOUT, respond(stream, body)).await;
+                });
+            }
+        });
+        Ok(Self {
+            addr,
+            accept_task,
+            events: events.clone(),
+            handler,
+        })
+    }
+
+    /// Binds the address in `VEX_STATUS_ADDR`, if set.
+    pub fn from_env(events: &EventBus, reader: ConversationReader) -> Option<Result<Self>> {
+        let addr = std::env::var(STATUS_ADDR_ENV).ok()?;
+        let addr = addr.trim();
+        let allow_remote = std::env::var(STATUS_ALLOW_REMOTE_ENV)
+            .ok()
+            .and_then(parse_bool_flag)
+            .unwrap_or(false);
+        (!addr.is_empty()).then(|| Self::bind(addr, allow_remote, events, reader))
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+        self.events.unsubscribe(self.handler);
+    }
+}
+
+/// A bare port means 127.0.0.1. Host names are resolved, and every address
+/// they resolve to must be loopback unless `allow_remote`; the first is bound.
+fn parse_addr(addr: &str, allow_remote: bool) -> Result<SocketAddr> {
+    if let Ok(port) = addr.parse::<u16>() {
+        return Ok(SocketAddr::from(([127, 0, 0, 1], port)));
+    }
+    let resolved: Vec<SocketAddr> = addr
+        .to_socket_addrs()
+        .with_context(|| {
+            format!("invalid {STATUS_ADDR_ENV} '{addr}'; expected host:port or a port")
+        })?
+        .collect();
+    let Some(first) = resolved.first().copied() else {
+        bail!("{STATUS_ADDR_ENV} '{addr}' did not resolve to any address");
+    };
+    if !allow_remote && resolved.iter().any(|addr| !addr.ip().is_loopback()) {
+        bail!(
+            "{STATUS_ADDR_ENV} '{addr}' is not a loopback address; set {STATUS_ALLOW_REMOTE_ENV}=1 to serve the status endpoint to other hosts"
+        );
+    }
+    Ok(first)
+}
+
+fn status_json(state: &StatusState, reader: &ConversationReader, uptime: Duration) -> Value {
+    let usage = reader.token_usage();
+    json!({
+        "state": if state.running { "running" } else { "idle" },
+        "turn": reader.turn_count(),
+        "round": state.round,
+        "tools_in_flight": reader.in_flight_tools(),
+        "last_tool": state.last_tool.as_ref().map(|tool| json!({
+            "name": tool.name,
+            "state": match (tool.running, tool.failed) {
+                (true, _) => "running",
+                (false, false) => "ok",
+                (false, true) => "error",
+            },
+            "duration_ms": tool.duration.map(|duration| duration.as_millis() as u64),
+        })),
+        "last_turn": state.last_turn.map(|(elapsed, outcome)| json!({
+            "outcome": outcome,
+            "elapsed_ms": elapsed.as_millis() as u64,
+        })),
+        "usage": {
+            "input_tokens": usage.input_tokens,
+            "output_tokens": usage.output_tokens,
+        },
+        "uptime_secs": uptime.as_secs(),
+    })
+}
+
+async fn respond(mut stream: TcpStream, status: Value) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n")
+        && request.len() < MAX_REQUEST_BYTES
+    {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+    let (code, content_type, body) = match (method, path) {
+        ("GET", "/healthz") => ("200 OK", "text/plain", "ok\n".to_string()),
+        ("GET", "/status") => ("200 OK", "application/json", format!("{status}\n")),
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "only GET is supported\n".to_string(),
+        ),
+    };
+    let response = format!(
+        "HTTP/1.1 {code}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream.write_all(request.as_bytes()).await.expect("request");
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .await
+            .expect("response");
+        response
+    }
+
+    #[tokio::test]
+    async fn test_status_follows_published_events() {
+        let events = EventBus::default();
+        let server =
+            StatusServer::bind("127.0.0.1:0", false, &events, ConversationReader::default())
+                .expect("bind");
+        let addr = server.addr();
+
+        let health = get(addr, "GET /healthz HTTP/1.1\r\nHost: x\r\n\r\n").await;
+        assert!(health.starts_with("HTTP/1.1 200 OK"), "{health}");
+        assert!(health.ends_with("ok\n"));
+
+        events.publish(RuntimeEvent::RoundStarted { turn: 1, round: 2 });
+        events.publish(RuntimeEvent::ToolFinished {
+            id: "t1".to_string(),
+            name: "run_tests".to_string(),
+            duration: Duration::from_millis(1500),
+            error: Some("failed to read /home/me/.env".to_string()),
+        });
+        let response = get(addr, "GET /status HTTP/1.1\r\n\r\n").await;
+        let body = response.split("\r\n\r\n").nth(1).expect("body");
+        let status: Value = serde_json::from_str(body).expect("json");
+        assert_eq!(status["state"], "running");
+        assert_eq!(status["round"], 2);
+        assert_eq!(status["last_tool"]["name"], "run_tests");
+        assert_eq!(status["last_tool"]["state"], "error");
+        assert_eq!(status["last_tool"]["duration_ms"], 1500);
+        assert!(!body.contains(".env"), "{body}");
+        assert_eq!(status["usage"]["input_tokens"], 0);
+
+        let missing = get(addr, "GET /other HTTP/1.1\r\n\r\n").await;
+        assert!(missing.starts_with("HTTP/1.1 404"), "{missing}");
+        let post = get(addr, "POST /status HTTP/1.1\r\n\r\n").await;
+        assert!(post.starts_with("HTTP/1.1 405"), "{post}");
+        assert_eq!(
+            parse_addr("8080", false).expect("port"),
+            SocketAddr::from(([127, 0, 0, 1], 8080))
+        );
+        assert!(parse_addr("[::1]:8080", false).is_ok());
+        assert!(parse_addr("0.0.0.0:8080", false).is_err());
+        assert!(parse_addr("0.0.0.0:8080", true).is_ok());
+        assert!(parse_addr("localhost:8080", false)
+            .expect("localhost")
+            .ip()
+            .is_loopback());
+        assert!(parse_addr("localhost", false).is_err());
+
+        // Stopping the server takes its handler off the bus.
+        let handlers = events.subscriber_count();
+        drop(server);
+        assert_eq!(events.subscriber_count(), handlers - 1);
+    }
+}
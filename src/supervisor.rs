@@ -0,0 +1,167 @@
+use crate::terminal;
+use anyhow::{Context, Result};
+use std::io::{IsTerminal, Write};
+use std::process::{ExitStatus, Stdio};
+
+pub const SUPERVISED_FLAG: &str = "--supervised";
+/// Set on the child so it runs the UI itself instead of supervising again.
+const SUPERVISED_CHILD_ENV: &str = "VEX_SUPERVISED_CHILD";
+const MAX_RESTARTS: usize = 3;
+/// Exit code of a Rust process whose main thread panicked.
+const PANIC_EXIT_CODE: i32 = 101;
+/// Signals that ask a process to stop (SIGHUP, SIGINT, SIGTERM): a child
+/// ended by one was stopped on purpose and is not restarted.
+const STOP_SIGNALS: [i32; 3] = [1, 2, 15];
+/// Scroll region reset, attribute reset.
+const RESET_SEQUENCES: &str = "\x1b[r\x1b[0m";
+
+/// Removes `--supervised` from `args`. True when this process should
+/// supervise, which a supervised child never does.
+pub fn take_supervised_flag(args: &mut Vec<String>) -> bool {
+    let before = args.len();
+    args.retain(|arg| arg != SUPERVISED_FLAG);
+    args.len() != before && std::env::var_os(SUPERVISED_CHILD_ENV).is_none()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ChildExit {
+    Finished(i32),
+    Crashed(String),
+}
+
+fn classify_exit(status: ExitStatus) -> ChildExit {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            if STOP_SIGNALS.contains(&signal) {
+                // The shell convention for a process ended by a signal.
+                return ChildExit::Finished(128 + signal);
+            }
+            return ChildExit::Crashed(format!("killed by signal {signal}"));
+        }
+    }
+    match status.code() {
+        Some(PANIC_EXIT_CODE) => ChildExit::Crashed("panicked".to_string()),
+        Some(code) => ChildExit::Finished(code),
+        None => ChildExit::Crashed("terminated abnormally".to_string()),
+    }
+}
+
+/// Runs the UI in a child process with `args` and restores the terminal
+/// after it exits, however it exits. A child that crashes is restarted up
+/// to three times with a fresh session. Returns the exit code to use.
+pub async fn run_supervised(args: &[String]) -> Result<i32> {
+    let exe = std::env::current_exe().context("failed to locate the vex executable")?;
+    let saved_mode = save_terminal_mode();
+    // Ctrl+C belongs to the child; the supervisor must outlive it.
+    tokio::spawn(async { while tokio::signal::ctrl_c().await.is_ok() {} });
+
+    let mut restarts = 0;
+    loop {
+        let status = tokio::process::Command::new(&exe)
+            .args(args)
+            .env(SUPERVISED_CHILD_ENV, "1")
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .await
+            .with_context(|| format!("failed to start {}", exe.display()))?;
+        restore_terminal(saved_mode.as_deref());
+        match classify_exit(status) {
+            ChildExit::Finished(code) => return Ok(code),
+            ChildExit::Crashed(reason) if restarts < MAX_RESTARTS => {
+                restarts += 1;
+                eprintln!(
+                    "[vex {reason}; terminal restored, restarting ({restarts}/{MAX_RESTARTS})]"
+                );
+            }
+            ChildExit::Crashed(reason) => {
+                eprintln!(
+                    "[vex {reason}; terminal restored, giving up after {MAX_RESTARTS} restarts]"
+                );
+                return Ok(1);
+            }
+        }
+    }
+}
+
+/// `stty -g` output for the controlling terminal, so the exact settings
+/// can be put back rather than a generic sane mode.
+fn save_terminal_mode() -> Option<String> {
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+    let output = std::process::Command::new("stty")
+        .arg("-g")
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|mode| !mode.is_empty())
+}
+
+fn restore_terminal(saved_mode: Option<&str>) {
+    let _ = terminal::restore();
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(RESET_SEQUENCES.as_bytes());
+    let _ = stdout.flush();
+    if std::io::stdin().is_terminal() {
+        let _ = std::process::Command::new("stty")
+            .arg(saved_mode.unwrap_or("sane"))
+            .stdin(Stdio::inherit())
+            .status();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ENV_LOCK;
+
+    #[test]
+    fn test_flag_is_removed_and_ignored_in_the_child() {
+        let _guard = ENV_LOCK.blocking_lock();
+        std::env::remove_var(SUPERVISED_CHILD_ENV);
+        let mut args = vec!["--supervised".to_string(), "--emit-patches=out".to_string()];
+        assert!(take_supervised_flag(&mut args));
+        assert_eq!(args, vec!["--emit-patches=out"]);
+        assert!(!take_supervised_flag(&mut args));
+
+        std::env::set_var(SUPERVISED_CHILD_ENV, "1");
+        let mut args = vec!["--supervised".to_string()];
+        assert!(!take_supervised_flag(&mut args));
+        assert!(args.is_empty());
+        std::env::remove_var(SUPERVISED_CHILD_ENV);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stop_signals_are_clean_and_other_signals_and_panics_are_crashes() {
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(
+            classify_exit(ExitStatus::from_raw(9)),
+            ChildExit::Crashed("killed by signal 9".to_string())
+        );
+        assert_eq!(
+            classify_exit(ExitStatus::from_raw(15)),
+            ChildExit::Finished(143)
+        );
+        assert_eq!(
+            classify_exit(ExitStatus::from_raw(1)),
+            ChildExit::Finished(129)
+        );
+        assert_eq!(
+            classify_exit(ExitStatus::from_raw(101 << 8)),
+            ChildExit::Crashed("panicked".to_string())
+        );
+        assert_eq!(
+            classify_exit(ExitStatus::from_raw(2 << 8)),
+            ChildExit::Finished(2)
+        );
+    }
+}
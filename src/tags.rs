@@ -0,0 +1,184 @@
+use crate::types::{ApiMessage, Content, ContentBlock};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const MAX_TAG_CHARS: usize = 40;
+
+/// A label put on one turn with `/tag`, kept with the conversation and
+/// written into session backups and bundles.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnTag {
+    /// 1-based turn number within the session.
+    pub turn: usize,
+    pub tag: String,
+    /// Start of the user message of the tagged turn.
+    pub prompt: String,
+}
+
+/// Tags in `/tag` arguments, lowercased and without a leading `#`. Tags are
+/// words of letters, digits, `-`, `_`, and `.`.
+pub fn parse_tags(args: &str) -> Result<Vec<String>> {
+    let mut tags: Vec<String> = Vec::new();
+    for word in args.split_whitespace() {
+        let tag = word.trim_start_matches('#').to_lowercase();
+        if tag.is_empty()
+            || tag.chars().count() > MAX_TAG_CHARS
+            || !tag
+                .chars()
+                .all(|ch| ch.is_alphanumeric() || matches!(ch, '-' | '_' | '.'))
+        {
+            bail!(
+                "invalid tag '{word}'; use up to {MAX_TAG_CHARS} letters, digits, '-', '_', or '.'"
+            );
+        }
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    if tags.is_empty() {
+        bail!("usage: /tag <tag> [tag...]");
+    }
+    Ok(tags)
+}
+
+/// One line per tag with the turns that carry it, in tag order.
+pub fn summarize_tags(tags: &[TurnTag]) -> Vec<String> {
+    let mut turns: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for tag in tags {
+        turns.entry(&tag.tag).or_default().push(tag.turn);
+    }
+    turns
+        .into_iter()
+        .map(|(tag, turns)| {
+            let label = if turns.len() == 1 { "turn" } else { "turns" };
+            let list: Vec<String> = turns.iter().map(ToString::to_string).collect();
+            format!("{tag}: {label} {}", list.join(", "))
+        })
+        .collect()
+}
+
+/// Whether `tags` has a tag starting with `filter`, which may carry a
+/// leading `#`. An empty filter matches everything.
+pub fn matches_tag_filter(tags: &[String], filter: &str) -> bool {
+    let filter = filter.trim().trim_start_matches('#').to_lowercase();
+    filter.is_empty() || tags.iter().any(|tag| tag.starts_with(&filter))
+}
+
+/// Whether `message` is a user message that starts a turn, as opposed to one
+/// carrying tool results back to the model.
+pub fn starts_turn(message: &ApiMessage) -> bool {
+    if message.role != "user" {
+        return false;
+    }
+    match &message.content {
+        Content::Blocks(blocks) => !blocks
+            .iter()
+            .any(|block| matches!(block, ContentBlock::ToolResult { .. })),
+        Content::Text(text) => {
+            !(text.starts_with("tool_result ") || text.starts_with("tool_error "))
+        }
+    }
+}
+
+/// The messages of the turns tagged `tag`. `turn_count` is the number of the
+/// last turn in `messages`; turns pruned from history are not recovered.
+pub fn messages_tagged(
+    messages: &[ApiMessage],
+    turn_count: usize,
+    tags: &[TurnTag],
+    tag: &str,
+) -> Vec<ApiMessage> {
+    let starts: Vec<usize> = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| starts_turn(message))
+        .map(|(index, _)| index)
+        .collect();
+    let first_turn = (turn_count + 1).saturating_sub(starts.len());
+    let mut selected = Vec::new();
+    for (position, start) in starts.iter().enumerate() {
+        let turn = first_turn + position;
+        if !tags
+            .iter()
+            .any(|tagged| tagged.turn == turn && tagged.tag == tag)
+        {
+            continue;
+        }
+        let end = starts.get(position + 1).copied().unwrap_or(messages.len());
+        selected.extend_from_slice(&messages[*start..end]);
+    }
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, text: &str) -> ApiMessage {
+        ApiMessage {
+            role: role.to_string(),
+            content: Content::Text(text.to_string()),
+        }
+    }
+
+    fn tag(turn: usize, tag: &str) -> TurnTag {
+        TurnTag {
+            turn,
+            tag: tag.to_string(),
+            prompt: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_tags_normalizes_and_rejects_punctuation() {
+        assert_eq!(
+            parse_tags("#Bug-Hunt api_design bug-hunt").expect("tags"),
+            ["bug-hunt", "api_design"]
+        );
+        assert!(parse_tags("").is_err());
+        assert!(parse_tags("bug/hunt").is_err());
+
+        let tags = ["api_design".to_string(), "bug-hunt".to_string()];
+        assert!(matches_tag_filter(&tags, "#BUG"));
+        assert!(matches_tag_filter(&tags, ""));
+        assert!(!matches_tag_filter(&tags, "hunt"));
+        assert!(!matches_tag_filter(&[], "bug"));
+    }
+
+    #[test]
+    fn test_tagged_turns_are_cut_from_history() {
+        // Turn 1 was pruned; history starts at turn 2.
+        let messages = vec![
+            message("user", "second"),
+            message("assistant", "<function=git_status>\n</function>"),
+            message("user", "tool_result git_status:\nclean"),
+            message("assistant", "clean"),
+            message("user", "third"),
+            message("assistant", "done"),
+        ];
+        let tags = [tag(1, "bug-hunt"), tag(2, "bug-hunt"), tag(3, "api")];
+
+        let texts = |messages: Vec<ApiMessage>| -> Vec<String> {
+            messages
+                .into_iter()
+                .map(|message| match message.content {
+                    Content::Text(text) => text,
+                    Content::Blocks(_) => String::new(),
+                })
+                .collect()
+        };
+        assert_eq!(
+            texts(messages_tagged(&messages, 3, &tags, "bug-hunt")),
+            texts(messages[..4].to_vec())
+        );
+        assert_eq!(
+            texts(messages_tagged(&messages, 3, &tags, "api")),
+            ["third", "done"]
+        );
+        assert_eq!(
+            summarize_tags(&tags),
+            ["api: turn 3", "bug-hunt: turns 1, 2"]
+        );
+    }
+}
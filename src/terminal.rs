@@ -1,3 +1,6 @@
+pub mod graphics;
+pub mod input;
+
 use crossterm::{
     cursor::Show,
     event::{DisableBracketedPaste, EnableBracketedPaste},
@@ -5,12 +8,92 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
-use std::io::{self, Stdout};
+use std::io::{self, IsTerminal, Stdout};
 use std::sync::Once;
 
 pub type TerminalType = Terminal<CrosstermBackend<Stdout>>;
 static PANIC_HOOK_INSTALLED: Once = Once::new();
 
+const TERMINAL_MODE_ENV: &str = "VEX_TERMINAL";
+/// Terminal families that handle scroll regions and 256 colors even when
+/// `TERM` does not say so.
+const FULL_TUI_TERMS: &[&str] = &[
+    "alacritty",
+    "foot",
+    "tmux",
+    "wezterm",
+    "xterm-ghostty",
+    "xterm-kitty",
+];
+
+/// Whether the full-screen TUI can run, or why output falls back to plain
+/// sequential text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TerminalSupport {
+    Full,
+    Plain(String),
+}
+
+/// Checks stdout and the environment. `VEX_TERMINAL=plain` or `full`
+/// overrides detection.
+pub fn detect_support() -> TerminalSupport {
+    #[cfg(windows)]
+    let ansi_console = Some(crossterm::ansi_support::supports_ansi());
+    #[cfg(not(windows))]
+    let ansi_console = None;
+    classify_terminal(
+        |key| std::env::var(key).ok(),
+        io::stdout().is_terminal(),
+        ansi_console,
+    )
+}
+
+/// `ansi_console` is `Some` on Windows, where `TERM` is usually unset and the
+/// console itself reports ANSI support.
+fn classify_terminal(
+    var: impl Fn(&str) -> Option<String>,
+    stdout_is_tty: bool,
+    ansi_console: Option<bool>,
+) -> TerminalSupport {
+    let var = |key: &str| var(key).map(|value| value.trim().to_string());
+    match var(TERMINAL_MODE_ENV).as_deref() {
+        Some("plain") => return TerminalSupport::Plain(format!("{TERMINAL_MODE_ENV}=plain")),
+        Some("full") => return TerminalSupport::Full,
+        _ => {}
+    }
+    if !stdout_is_tty {
+        return TerminalSupport::Plain("stdout is not a terminal".to_string());
+    }
+    match ansi_console {
+        Some(false) => {
+            return TerminalSupport::Plain("console does not support ANSI sequences".to_string())
+        }
+        Some(true) => return TerminalSupport::Full,
+        None => {}
+    }
+    let term = var("TERM").unwrap_or_default();
+    if term.is_empty() || term == "dumb" || term == "unknown" {
+        return TerminalSupport::Plain(if term.is_empty() {
+            "TERM is not set".to_string()
+        } else {
+            format!("TERM={term}")
+        });
+    }
+    let advertises_color = ["256color", "truecolor", "direct"]
+        .iter()
+        .any(|marker| term.contains(marker))
+        || var("COLORTERM").is_some_and(|value| !value.is_empty())
+        || var("TERM_PROGRAM").is_some_and(|value| !value.is_empty())
+        || FULL_TUI_TERMS
+            .iter()
+            .any(|known| term == *known || term.starts_with(&format!("{known}-")));
+    if advertises_color {
+        TerminalSupport::Full
+    } else {
+        TerminalSupport::Plain(format!("TERM={term} does not advertise 256 colors"))
+    }
+}
+
 pub fn install_panic_hook_once() {
     PANIC_HOOK_INSTALLED.call_once(|| {
         let original_hook = std::panic::take_hook();
@@ -57,4 +140,63 @@ mod tests {
             "panic hook must be installed before raw mode setup"
         );
     }
+
+    #[test]
+    fn test_classify_terminal_falls_back_for_limited_terminals() {
+        let classify = |vars: &[(&str, &str)], tty: bool, ansi: Option<bool>| {
+            classify_terminal(
+                |key| {
+                    vars.iter()
+                        .find(|(name, _)| *name == key)
+                        .map(|(_, value)| value.to_string())
+                },
+                tty,
+                ansi,
+            )
+        };
+        let plain = |reason: &str| TerminalSupport::Plain(reason.to_string());
+
+        assert_eq!(
+            classify(&[("TERM", "xterm-256color")], true, None),
+            TerminalSupport::Full
+        );
+        assert_eq!(
+            classify(&[("TERM", "xterm"), ("COLORTERM", "truecolor")], true, None),
+            TerminalSupport::Full
+        );
+        assert_eq!(
+            classify(&[("TERM", "tmux-direct")], true, None),
+            TerminalSupport::Full
+        );
+        assert_eq!(classify(&[], true, Some(true)), TerminalSupport::Full);
+        assert_eq!(
+            classify(&[("TERM", "dumb")], true, None),
+            plain("TERM=dumb")
+        );
+        assert_eq!(classify(&[], true, None), plain("TERM is not set"));
+        assert_eq!(
+            classify(&[("TERM", "vt100")], true, None),
+            plain("TERM=vt100 does not advertise 256 colors")
+        );
+        assert_eq!(
+            classify(&[("TERM", "xterm-256color")], false, None),
+            plain("stdout is not a terminal")
+        );
+        assert_eq!(
+            classify(&[], true, Some(false)),
+            plain("console does not support ANSI sequences")
+        );
+        assert_eq!(
+            classify(&[("TERM", "dumb"), ("VEX_TERMINAL", "full")], false, None),
+            TerminalSupport::Full
+        );
+        assert_eq!(
+            classify(
+                &[("TERM", "xterm-256color"), ("VEX_TERMINAL", "plain")],
+                true,
+                None
+            ),
+            plain("VEX_TERMINAL=plain")
+        );
+    }
 }
@@ -0,0 +1,351 @@
+use crate::clipboard::base64_encode;
+use crate::ui::inline_images::{ImageFormat, ImagePlacement};
+use crate::util::parse_bool_str;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+pub const INLINE_IMAGES_ENV: &str = "VEX_INLINE_IMAGES";
+/// Files larger than this are shown as placeholders only.
+const MAX_INLINE_IMAGE_BYTES: u64 = 16 * 1024 * 1024;
+/// Base64 bytes per kitty graphics escape; the protocol caps chunks at 4096.
+const KITTY_CHUNK_BYTES: usize = 4096;
+/// `TERM_PROGRAM` values of terminals that show iTerm2 inline images.
+const ITERM2_TERM_PROGRAMS: [&str; 3] = ["iTerm.app", "WezTerm", "WarpTerminal"];
+const KITTY_TERMS: [&str; 2] = ["xterm-kitty", "xterm-ghostty"];
+/// Terminals that draw sixel graphics whatever `TERM` says.
+const SIXEL_TERMS: [&str; 3] = ["foot", "mlterm", "contour"];
+/// Programs that turn an image file into sixel output, tried in order.
+const SIXEL_CONVERTERS: [&str; 2] = ["chafa", "img2sixel"];
+/// Used when the terminal does not report its size in pixels.
+const DEFAULT_CELL_PIXELS: (u16, u16) = (10, 20);
+
+/// How the terminal draws images, when it can.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    /// Kitty's graphics protocol; PNG files are sent as they are.
+    Kitty,
+    /// iTerm2's inline file escape, also understood by WezTerm.
+    Iterm2,
+    /// DEC sixel graphics, converted by an external program.
+    Sixel(PathBuf),
+}
+
+impl GraphicsProtocol {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Kitty => "kitty",
+            Self::Iterm2 => "iTerm2",
+            Self::Sixel(_) => "sixel",
+        }
+    }
+}
+
+/// Probes the environment for an image protocol. `VEX_INLINE_IMAGES=off`
+/// disables images, and `kitty`, `iterm2`, or `sixel` picks a protocol the
+/// probe does not recognize.
+pub fn detect_graphics() -> Option<GraphicsProtocol> {
+    classify_graphics(|key| std::env::var(key).ok(), find_sixel_converter)
+}
+
+fn classify_graphics(
+    var: impl Fn(&str) -> Option<String>,
+    sixel_converter: impl Fn() -> Option<PathBuf>,
+) -> Option<GraphicsProtocol> {
+    let var = |key: &str| var(key).filter(|value| !value.trim().is_empty());
+    let sixel = || sixel_converter().map(GraphicsProtocol::Sixel);
+    if let Some(value) = var(INLINE_IMAGES_ENV) {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "kitty" => return Some(GraphicsProtocol::Kitty),
+            "iterm2" | "iterm" => return Some(GraphicsProtocol::Iterm2),
+            "sixel" => return sixel(),
+            other if parse_bool_str(other) == Some(false) => return None,
+            _ => {}
+        }
+    }
+    // Multiplexers drop graphics escapes unless configured to pass them on.
+    if var("TMUX").is_some() || var("STY").is_some() {
+        return None;
+    }
+    let term = var("TERM").unwrap_or_default();
+    let term_program = var("TERM_PROGRAM").unwrap_or_default();
+    if var("KITTY_WINDOW_ID").is_some()
+        || KITTY_TERMS.contains(&term.as_str())
+        || term_program == "ghostty"
+    {
+        return Some(GraphicsProtocol::Kitty);
+    }
+    if ITERM2_TERM_PROGRAMS.contains(&term_program.as_str()) {
+        return Some(GraphicsProtocol::Iterm2);
+    }
+    if term.contains("sixel")
+        || SIXEL_TERMS
+            .iter()
+            .any(|known| term == *known || term.starts_with(&format!("{known}-")))
+    {
+        return sixel();
+    }
+    None
+}
+
+fn find_sixel_converter() -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    SIXEL_CONVERTERS.iter().find_map(|name| {
+        std::env::split_paths(&path)
+            .map(|dir| dir.join(name))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// What the frontend does after a frame to show the images placed in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageFrame {
+    /// The images on screen are already where they belong.
+    Unchanged,
+    /// Write these escapes, then restore the cursor.
+    Draw(String),
+    /// Images drawn into cells cannot be moved: clear the screen, redraw,
+    /// and the next frame draws them again.
+    Repaint,
+}
+
+/// Tracks the images on screen and encodes each image once per size.
+#[derive(Debug)]
+pub struct InlineImageRenderer {
+    protocol: GraphicsProtocol,
+    cell_pixels: (u16, u16),
+    encoded: HashMap<(PathBuf, u16, u16), Option<String>>,
+    shown: Vec<ImagePlacement>,
+}
+
+impl InlineImageRenderer {
+    pub fn new(protocol: GraphicsProtocol) -> Self {
+        let cell_pixels = crossterm::terminal::window_size()
+            .ok()
+            .filter(|size| size.columns > 0 && size.rows > 0 && size.width > 0)
+            .map_or(DEFAULT_CELL_PIXELS, |size| {
+                (size.width / size.columns, size.height / size.rows)
+            });
+        Self {
+            protocol,
+            cell_pixels,
+            encoded: HashMap::new(),
+            shown: Vec::new(),
+        }
+    }
+
+    pub fn protocol(&self) -> &GraphicsProtocol {
+        &self.protocol
+    }
+
+    /// Escapes that take the screen from the last frame's images to
+    /// `placements`. Images that cannot be read or encoded stay placeholders.
+    pub fn frame(&mut self, placements: Vec<ImagePlacement>) -> ImageFrame {
+        if placements == self.shown {
+            return ImageFrame::Unchanged;
+        }
+        let mut out = String::new();
+        match self.protocol {
+            // Kitty images sit above the text and are deleted by id.
+            GraphicsProtocol::Kitty => out.push_str(KITTY_DELETE_ALL),
+            _ if !self.shown.is_empty() => {
+                self.shown.clear();
+                return ImageFrame::Repaint;
+            }
+            _ => {}
+        }
+        for placement in &placements {
+            if let Some(sequence) = self.sequence(placement) {
+                out.push_str(&format!("\x1b[{};{}H", placement.y + 1, placement.x + 1));
+                out.push_str(&sequence);
+            }
+        }
+        self.shown = placements;
+        ImageFrame::Draw(out)
+    }
+
+    fn sequence(&mut self, placement: &ImagePlacement) -> Option<String> {
+        let key = (placement.path.clone(), placement.cols, placement.rows);
+        if let Some(sequence) = self.encoded.get(&key) {
+            return sequence.clone();
+        }
+        let sequence = self.encode(placement);
+        self.encoded.insert(key, sequence.clone());
+        sequence
+    }
+
+    fn encode(&self, placement: &ImagePlacement) -> Option<String> {
+        let read = |path: &Path| {
+            let size = std::fs::metadata(path).ok()?.len();
+            (size <= MAX_INLINE_IMAGE_BYTES)
+                .then(|| std::fs::read(path).ok())
+                .flatten()
+        };
+        match &self.protocol {
+            GraphicsProtocol::Kitty => (placement.format == ImageFormat::Png)
+                .then(|| read(&placement.path))
+                .flatten()
+                .map(|bytes| kitty_sequence(&bytes, placement.cols, placement.rows)),
+            GraphicsProtocol::Iterm2 => read(&placement.path)
+                .map(|bytes| iterm2_sequence(&bytes, placement.cols, placement.rows)),
+            GraphicsProtocol::Sixel(converter) => sixel_sequence(
+                converter,
+                &placement.path,
+                placement.cols,
+                placement.rows,
+                self.cell_pixels,
+            ),
+        }
+    }
+}
+
+const KITTY_DELETE_ALL: &str = "\x1b_Ga=d,d=A,q=2\x1b\\";
+
+/// Transmits and shows a PNG in `cols` x `rows` cells without moving the
+/// cursor. `q=2` keeps the terminal from answering on the input stream.
+fn kitty_sequence(png: &[u8], cols: u16, rows: u16) -> String {
+    let payload = base64_encode(png);
+    let chunks: Vec<&str> = payload
+        .as_bytes()
+        .chunks(KITTY_CHUNK_BYTES)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+    let mut out = String::with_capacity(payload.len() + chunks.len() * 32);
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(index + 1 < chunks.len());
+        if index == 0 {
+            out.push_str(&format!(
+                "\x1b_Ga=T,f=100,t=d,c={cols},r={rows},C=1,q=2,m={more};{chunk}\x1b\\"
+            ));
+        } else {
+            out.push_str(&format!("\x1b_Gm={more};{chunk}\x1b\\"));
+        }
+    }
+    out
+}
+
+fn iterm2_sequence(bytes: &[u8], cols: u16, rows: u16) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={cols};height={rows};preserveAspectRatio=1:{}\x07",
+        bytes.len(),
+        base64_encode(bytes)
+    )
+}
+
+fn sixel_sequence(
+    converter: &Path,
+    path: &Path,
+    cols: u16,
+    rows: u16,
+    (cell_width, cell_height): (u16, u16),
+) -> Option<String> {
+    let mut command = Command::new(converter);
+    if converter.file_name().is_some_and(|name| name == "chafa") {
+        command.args(["-f", "sixels", "--size", &format!("{cols}x{rows}")]);
+    } else {
+        command.args([
+            "-w",
+            &(u32::from(cols) * u32::from(cell_width)).to_string(),
+            "-h",
+            &(u32::from(rows) * u32::from(cell_height)).to_string(),
+        ]);
+    }
+    let output = command
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8(output.stdout).ok())
+        .flatten()
+        .filter(|sixel| sixel.starts_with("\x1bP"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_picks_protocol_and_renderer_redraws_only_on_change() {
+        let classify = |vars: &[(&str, &str)], converter: Option<&str>| {
+            classify_graphics(
+                |key| {
+                    vars.iter()
+                        .find(|(name, _)| *name == key)
+                        .map(|(_, value)| value.to_string())
+                },
+                || converter.map(PathBuf::from),
+            )
+        };
+        assert_eq!(
+            classify(&[("TERM", "xterm-kitty")], None),
+            Some(GraphicsProtocol::Kitty)
+        );
+        assert_eq!(
+            classify(&[("TERM_PROGRAM", "iTerm.app")], None),
+            Some(GraphicsProtocol::Iterm2)
+        );
+        assert_eq!(
+            classify(&[("TERM", "foot")], Some("/usr/bin/chafa")),
+            Some(GraphicsProtocol::Sixel(PathBuf::from("/usr/bin/chafa")))
+        );
+        assert_eq!(classify(&[("TERM", "foot")], None), None);
+        assert_eq!(classify(&[("TERM", "xterm-256color")], None), None);
+        assert_eq!(
+            classify(&[("TERM", "xterm-kitty"), ("TMUX", "/tmp/tmux")], None),
+            None
+        );
+        assert_eq!(
+            classify(&[("TERM", "xterm-kitty"), (INLINE_IMAGES_ENV, "off")], None),
+            None
+        );
+        assert_eq!(
+            classify(
+                &[("TERM", "xterm-256color"), (INLINE_IMAGES_ENV, "iterm2")],
+                None
+            ),
+            Some(GraphicsProtocol::Iterm2)
+        );
+
+        let kitty = kitty_sequence(&vec![7; 5000], 10, 4);
+        assert!(kitty.starts_with("\x1b_Ga=T,f=100,t=d,c=10,r=4,C=1,q=2,m=1;"));
+        assert_eq!(kitty.matches("\x1b_G").count(), 2);
+        assert!(kitty.contains("\x1b_Gm=0;"));
+        assert_eq!(
+            iterm2_sequence(b"foobar", 3, 2),
+            "\x1b]1337;File=inline=1;size=6;width=3;height=2;preserveAspectRatio=1:Zm9vYmFy\x07"
+        );
+
+        let temp = tempfile::tempdir().expect("tempdir");
+        let path = temp.path().join("shot.gif");
+        std::fs::write(&path, b"GIF89a\x02\0\x02\0").expect("write");
+        let placement = |y| ImagePlacement {
+            path: path.clone(),
+            format: ImageFormat::Gif,
+            x: 4,
+            y,
+            cols: 2,
+            rows: 1,
+        };
+        let mut renderer = InlineImageRenderer::new(GraphicsProtocol::Iterm2);
+        let ImageFrame::Draw(first) = renderer.frame(vec![placement(3)]) else {
+            panic!("first frame draws the image");
+        };
+        assert!(first.starts_with("\x1b[4;5H\x1b]1337;File=inline=1;size=10;"));
+        assert_eq!(renderer.frame(vec![placement(3)]), ImageFrame::Unchanged);
+        assert_eq!(renderer.frame(vec![placement(2)]), ImageFrame::Repaint);
+        assert!(matches!(
+            renderer.frame(vec![placement(2)]),
+            ImageFrame::Draw(_)
+        ));
+
+        let mut kitty = InlineImageRenderer::new(GraphicsProtocol::Kitty);
+        assert_eq!(
+            kitty.frame(vec![placement(3)]),
+            ImageFrame::Draw(KITTY_DELETE_ALL.to_string())
+        );
+    }
+}
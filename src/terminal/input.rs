@@ -0,0 +1,175 @@
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long the event reader waits before checking whether its queue has
+/// been dropped.
+const EVENT_READER_POLL: Duration = Duration::from_millis(50);
+
+/// Input read from the terminal by the background reader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TerminalInput {
+    /// A key, paste, or resize while the TUI holds the terminal in raw mode.
+    Event(Event),
+    /// A line of stdin in plain output mode.
+    Line(String),
+    /// Ctrl+C outside raw mode, where it arrives as a signal.
+    Interrupt,
+    /// Stdin reached its end or could not be read.
+    Closed,
+}
+
+impl TerminalInput {
+    /// Ctrl+C, whether as a raw-mode key or a signal.
+    pub fn is_interrupt(&self) -> bool {
+        match self {
+            Self::Interrupt => true,
+            Self::Event(Event::Key(key)) => {
+                key.kind != KeyEventKind::Release
+                    && key.code == KeyCode::Char('c')
+                    && key.modifiers.contains(KeyModifiers::CONTROL)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The one reader of stdin. A background thread owns the terminal input and
+/// feeds this queue, so frontends never poll or block on stdin themselves
+/// and no two readers race for the same bytes (as they can under tmux
+/// control mode or some SSH setups, which dropped characters).
+pub struct InputQueue {
+    rx: mpsc::UnboundedReceiver<TerminalInput>,
+    held: VecDeque<TerminalInput>,
+    closed: bool,
+}
+
+impl InputQueue {
+    /// Reads crossterm events, for the raw-mode TUI. The reader stops soon
+    /// after the queue is dropped.
+    pub fn events() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            while !tx.is_closed() {
+                match event::poll(EVENT_READER_POLL).and_then(|ready| {
+                    if ready {
+                        event::read().map(Some)
+                    } else {
+                        Ok(None)
+                    }
+                }) {
+                    Ok(Some(event)) => {
+                        if tx.send(TerminalInput::Event(event)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(_) => break,
+                }
+            }
+            let _ = tx.send(TerminalInput::Closed);
+        });
+        Self::from_receiver(rx)
+    }
+
+    /// Reads stdin a line at a time and Ctrl+C signals, for plain output.
+    /// Must be called inside a Tokio runtime.
+    pub fn lines() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let line_tx = tx.clone();
+        // A thread rather than a blocking task: a read waiting on stdin
+        // cannot be cancelled and would hold up runtime shutdown.
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line_tx.send(TerminalInput::Line(line)).is_err() {
+                    return;
+                }
+            }
+            let _ = line_tx.send(TerminalInput::Closed);
+        });
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if tx.send(TerminalInput::Interrupt).is_err() {
+                    break;
+                }
+            }
+        });
+        Self::from_receiver(rx)
+    }
+
+    fn from_receiver(rx: mpsc::UnboundedReceiver<TerminalInput>) -> Self {
+        Self {
+            rx,
+            held: VecDeque::new(),
+            closed: false,
+        }
+    }
+
+    /// The oldest input not yet taken, without waiting. A reader that went
+    /// away reports [`TerminalInput::Closed`] once.
+    pub fn try_next(&mut self) -> Option<TerminalInput> {
+        if let Some(input) = self.held.pop_front() {
+            return Some(input);
+        }
+        match self.rx.try_recv() {
+            Ok(input) => Some(input),
+            Err(mpsc::error::TryRecvError::Empty) => None,
+            Err(mpsc::error::TryRecvError::Disconnected) => {
+                (!std::mem::replace(&mut self.closed, true)).then_some(TerminalInput::Closed)
+            }
+        }
+    }
+
+    /// Takes the oldest input matching `wanted` and keeps the rest, in
+    /// order, for [`Self::try_next`].
+    pub fn take_first(&mut self, wanted: impl Fn(&TerminalInput) -> bool) -> Option<TerminalInput> {
+        while let Ok(input) = self.rx.try_recv() {
+            self.held.push_back(input);
+        }
+        let index = self.held.iter().position(wanted)?;
+        self.held.remove(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyEvent;
+
+    #[test]
+    fn test_take_first_keeps_other_input_in_order() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut queue = InputQueue::from_receiver(rx);
+        let key = |ch, modifiers| {
+            TerminalInput::Event(Event::Key(KeyEvent::new(KeyCode::Char(ch), modifiers)))
+        };
+        for input in [
+            key('a', KeyModifiers::NONE),
+            key('c', KeyModifiers::CONTROL),
+            TerminalInput::Line("next".to_string()),
+        ] {
+            tx.send(input).expect("send");
+        }
+
+        assert_eq!(
+            queue.take_first(TerminalInput::is_interrupt),
+            Some(key('c', KeyModifiers::CONTROL))
+        );
+        assert_eq!(queue.take_first(TerminalInput::is_interrupt), None);
+        assert_eq!(queue.try_next(), Some(key('a', KeyModifiers::NONE)));
+        assert_eq!(
+            queue.try_next(),
+            Some(TerminalInput::Line("next".to_string()))
+        );
+        assert_eq!(queue.try_next(), None);
+
+        drop(tx);
+        assert_eq!(queue.try_next(), Some(TerminalInput::Closed));
+        assert_eq!(queue.try_next(), None);
+    }
+}
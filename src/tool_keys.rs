@@ -0,0 +1,50 @@
+use serde_json::Value;
+
+/// Keys a write/edit input may use for the file it changes.
+pub const PATH_KEYS: [&str; 4] = ["path", "file_path", "file", "filename"];
+/// Keys a rename input may use for the file being moved.
+pub const SOURCE_PATH_KEYS: [&str; 3] = ["old_path", "from", "source_path"];
+/// Keys a rename input may use for where the file ends up.
+pub const TARGET_PATH_KEYS: [&str; 3] = ["new_path", "to", "target_path"];
+/// Keys an edit input may use for the text being replaced.
+pub const OLD_TEXT_KEYS: [&str; 5] = ["old_str", "old_text", "old_string", "find", "search"];
+/// Keys an edit input may use for the replacement text.
+pub const NEW_TEXT_KEYS: [&str; 6] = [
+    "new_str",
+    "new_text",
+    "new_string",
+    "replace",
+    "replace_with",
+    "replacement",
+];
+/// Keys a write input may use for the file's content.
+pub const CONTENT_KEYS: [&str; 2] = ["content", "text"];
+
+/// Every key that names a workspace path, renames included.
+pub fn all_path_keys() -> impl Iterator<Item = &'static str> {
+    PATH_KEYS
+        .into_iter()
+        .chain(SOURCE_PATH_KEYS)
+        .chain(TARGET_PATH_KEYS)
+}
+
+/// The first of `keys` that `input` holds as a string.
+pub fn first_str<'a>(input: &'a Value, keys: &[&str]) -> Option<&'a str> {
+    keys.iter()
+        .find_map(|key| input.get(*key).and_then(|value| value.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_first_str_takes_the_first_alias_present() {
+        let input = json!({"file": 3, "filename": "b.rs", "file_path": "a.rs"});
+        assert_eq!(first_str(&input, &PATH_KEYS), Some("a.rs"));
+        assert_eq!(first_str(&input, &OLD_TEXT_KEYS), None);
+        assert_eq!(all_path_keys().count(), 10);
+        assert!(all_path_keys().any(|key| key == "target_path"));
+    }
+}
@@ -1,4 +1,8 @@
-use crate::edit_diff::format_edit_hunks;
+use crate::edit_diff::{changed_line_span, format_edit_hunks, format_unified_file_diff};
+use crate::state::SEND_SECRETS_APPROVAL;
+use crate::tool_keys::{first_str, CONTENT_KEYS, NEW_TEXT_KEYS, OLD_TEXT_KEYS, PATH_KEYS};
+use crate::ui::input_metrics::{truncate_with_ellipsis, Ellipsis};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 
@@ -23,57 +27,168 @@ pub enum ReadFileSnapshotSummary {
         before_lines: usize,
         after_chars: usize,
         after_lines: usize,
+        /// `None` when the earlier content was too large to keep.
+        delta: Option<LineDelta>,
     },
 }
 
+/// Where a re-read file changed since the previous read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineDelta {
+    pub changed_lines: usize,
+    pub first_line: usize,
+    pub last_line: usize,
+}
+
+impl LineDelta {
+    /// `3 lines changed near lines 120-126`.
+    pub fn describe(self) -> String {
+        let noun = if self.changed_lines == 1 {
+            "line"
+        } else {
+            "lines"
+        };
+        let near = if self.first_line == self.last_line {
+            format!("line {}", self.first_line)
+        } else {
+            format!("lines {}-{}", self.first_line, self.last_line)
+        };
+        format!("{} {noun} changed near {near}", self.changed_lines)
+    }
+}
+
+/// Earlier content above this size is not kept, so changes to larger files
+/// are reported by size only.
+const MAX_SNAPSHOT_DIFF_BYTES: usize = 256 * 1024;
+/// A re-read whose diff is longer than this is sent in full instead.
+pub const MAX_READ_FILE_MINI_DIFF_LINES: usize = 40;
+const READ_FILE_MINI_DIFF_CONTEXT_LINES: usize = 2;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReadFileSummaryMessageStyle {
     History,
     StreamEvent,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ReadFileSnapshot {
+    // DefaultHasher is only stable within one build, so after an upgrade a
+    // resumed session may report an unchanged file as changed once.
+    hash: u64,
+    chars: usize,
+    lines: usize,
+    /// Kept up to `MAX_SNAPSHOT_DIFF_BYTES` so a later read can be diffed.
+    content: Option<String>,
+}
+
+/// What `read_file` last returned for each path; saved with the session so
+/// a resumed session still reports unchanged re-reads.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct ReadFileSnapshotCache {
-    // (content_hash, chars, lines) — hash is u64 from DefaultHasher.
-    // DefaultHasher is non-deterministic across process restarts, which is
-    // acceptable since this cache is per-process in-memory only.
-    entries: HashMap<String, (u64, usize, usize)>,
+    entries: HashMap<String, ReadFileSnapshot>,
 }
 
 impl ReadFileSnapshotCache {
+    /// Drops the snapshot of `path`, so its next read counts as the first.
+    pub fn forget(&mut self, path: &str) {
+        self.entries.remove(path);
+    }
+
+    /// Drops the saved content of snapshots for which `matches` holds. Their
+    /// hash still tells an unchanged re-read; a changed one is reported by
+    /// size only.
+    pub fn drop_content_matching(&mut self, matches: impl Fn(&str) -> bool) {
+        for snapshot in self.entries.values_mut() {
+            if snapshot.content.as_deref().is_some_and(&matches) {
+                snapshot.content = None;
+            }
+        }
+    }
+
     pub fn summarize(&mut self, path: &str, content: &str) -> ReadFileSnapshotSummary {
+        self.summarize_with_diff(path, content).0
+    }
+
+    /// Like [`Self::summarize`], plus the hunks between the previous and the
+    /// current content when a changed file's diff is at most
+    /// `MAX_READ_FILE_MINI_DIFF_LINES` lines.
+    pub fn summarize_with_diff(
+        &mut self,
+        path: &str,
+        content: &str,
+    ) -> (ReadFileSnapshotSummary, Option<String>) {
         let (after_chars, after_lines) = content_stats(content);
-        let after_hash = hash_content(content);
+        let snapshot = ReadFileSnapshot {
+            hash: hash_content(content),
+            chars: after_chars,
+            lines: after_lines,
+            content: (content.len() <= MAX_SNAPSHOT_DIFF_BYTES).then(|| content.to_string()),
+        };
 
-        match self.entries.get(path).copied() {
-            None => {
-                self.entries
-                    .insert(path.to_string(), (after_hash, after_chars, after_lines));
+        let Some(previous) = self.entries.get(path) else {
+            self.entries.insert(path.to_string(), snapshot);
+            return (
                 ReadFileSnapshotSummary::FirstRead {
                     chars: after_chars,
                     lines: after_lines,
-                }
-            }
-            Some((prev_hash, prev_chars, prev_lines)) if prev_hash == after_hash => {
+                },
+                None,
+            );
+        };
+        if previous.hash == snapshot.hash {
+            return (
                 ReadFileSnapshotSummary::Unchanged {
-                    chars: prev_chars,
-                    lines: prev_lines,
-                }
-            }
-            Some((_, before_chars, before_lines)) => {
-                self.entries
-                    .insert(path.to_string(), (after_hash, after_chars, after_lines));
-                ReadFileSnapshotSummary::Changed {
-                    before_chars,
-                    before_lines,
-                    after_chars,
-                    after_lines,
-                }
-            }
+                    chars: previous.chars,
+                    lines: previous.lines,
+                },
+                None,
+            );
         }
+        let (delta, mini_diff) = match previous.content.as_deref() {
+            Some(before) if snapshot.content.is_some() => {
+                let delta = changed_line_span(before, content).map(
+                    |(changed_lines, first_line, last_line)| LineDelta {
+                        changed_lines,
+                        first_line,
+                        last_line,
+                    },
+                );
+                (delta, mini_diff(path, before, content))
+            }
+            _ => (None, None),
+        };
+        let summary = ReadFileSnapshotSummary::Changed {
+            before_chars: previous.chars,
+            before_lines: previous.lines,
+            after_chars,
+            after_lines,
+            delta,
+        };
+        self.entries.insert(path.to_string(), snapshot);
+        (summary, mini_diff)
     }
 }
 
+/// Unified diff hunks without the file header, or `None` when longer than
+/// `MAX_READ_FILE_MINI_DIFF_LINES`.
+fn mini_diff(path: &str, before: &str, after: &str) -> Option<String> {
+    let diff = format_unified_file_diff(
+        path,
+        Some(before),
+        Some(after),
+        READ_FILE_MINI_DIFF_CONTEXT_LINES,
+    );
+    let hunks: Vec<&str> = diff.lines().skip(3).collect();
+    (!hunks.is_empty() && hunks.len() <= MAX_READ_FILE_MINI_DIFF_LINES).then(|| hunks.join("\n"))
+}
+
+fn delta_suffix(delta: Option<LineDelta>) -> String {
+    delta
+        .map(|delta| format!(", {}", delta.describe()))
+        .unwrap_or_default()
+}
+
 fn hash_content(content: &str) -> u64 {
     use std::hash::{Hash, Hasher};
     let mut h = std::collections::hash_map::DefaultHasher::new();
@@ -100,6 +215,21 @@ pub fn read_file_path(input: &Value) -> Option<String> {
         .map(|s| s.to_string())
 }
 
+/// `read_file_path` plus the requested line range, so ranged reads of one
+/// file are cached as separate snapshots.
+pub fn read_file_snapshot_key(input: &Value) -> Option<String> {
+    let path = read_file_path(input)?;
+    let line = |key: &str| input.get(key).and_then(|v| v.as_u64());
+    Some(match (line("start_line"), line("end_line")) {
+        (None, None) => path,
+        (start, end) => format!(
+            "{path}:{}-{}",
+            start.unwrap_or(1),
+            end.map(|end| end.to_string()).unwrap_or_default()
+        ),
+    })
+}
+
 pub fn format_read_file_snapshot_message(
     path: &str,
     summary: ReadFileSnapshotSummary,
@@ -119,9 +249,11 @@ pub fn format_read_file_snapshot_message(
                 before_lines,
                 after_chars,
                 after_lines,
+                delta,
             },
         ) => format!(
-            "Read {path}: content changed ({before_chars} chars/{before_lines} lines -> {after_chars} chars/{after_lines} lines). Full content omitted; use search_files for targeted string matches."
+            "Read {path}: content changed ({before_chars} chars/{before_lines} lines -> {after_chars} chars/{after_lines} lines{}). Full content omitted; use search_files for targeted string matches.",
+            delta_suffix(delta)
         ),
         (
             ReadFileSummaryMessageStyle::StreamEvent,
@@ -138,9 +270,11 @@ pub fn format_read_file_snapshot_message(
                 before_lines,
                 after_chars,
                 after_lines,
+                delta,
             },
         ) => format!(
-            "content changed: {before_chars} chars/{before_lines} lines -> {after_chars} chars/{after_lines} lines"
+            "content changed: {before_chars} chars/{before_lines} lines -> {after_chars} chars/{after_lines} lines{}",
+            delta_suffix(delta)
         ),
     }
 }
@@ -183,25 +317,9 @@ pub fn preview_edit_file_input(
     diff_indent: &str,
     diff_context_lines: usize,
 ) -> String {
-    let path =
-        first_input_str(input, &["path", "file_path", "file", "filename"]).unwrap_or("<missing>");
-    let old_str = first_input_str(
-        input,
-        &["old_str", "old_text", "old_string", "find", "search"],
-    )
-    .unwrap_or("");
-    let new_str = first_input_str(
-        input,
-        &[
-            "new_str",
-            "new_text",
-            "new_string",
-            "replace",
-            "replace_with",
-            "replacement",
-        ],
-    )
-    .unwrap_or("");
+    let path = first_str(input, &PATH_KEYS).unwrap_or("<missing>");
+    let old_str = first_str(input, &OLD_TEXT_KEYS).unwrap_or("");
+    let new_str = first_str(input, &NEW_TEXT_KEYS).unwrap_or("");
 
     let (old_chars, old_lines) = content_stats(old_str);
     let (new_chars, new_lines) = content_stats(new_str);
@@ -226,8 +344,8 @@ pub fn preview_write_file_input(
     marker: Option<char>,
     max_lines: usize,
 ) -> String {
-    let path = first_input_str(input, &["path", "file_path", "file"]).unwrap_or("<missing>");
-    let content = first_input_str(input, &["content", "text"]).unwrap_or("");
+    let path = first_str(input, &PATH_KEYS).unwrap_or("<missing>");
+    let content = first_str(input, &CONTENT_KEYS).unwrap_or("");
     let (chars, lines) = content_stats(content);
 
     let mut out = String::new();
@@ -237,9 +355,250 @@ pub fn preview_write_file_input(
     out
 }
 
-fn first_input_str<'a>(input: &'a Value, keys: &[&str]) -> Option<&'a str> {
-    keys.iter()
-        .find_map(|key| input.get(*key).and_then(|value| value.as_str()))
+/// Top-level string fields of a JSON object that streams in, parsed as each
+/// piece arrives so a long input is read once. A string cut off mid-way
+/// holds what has arrived so far; other values are skipped.
+#[derive(Debug, Default)]
+pub struct PartialJsonStrings {
+    /// Finished fields, with their line counts.
+    fields: HashMap<String, (String, usize)>,
+    /// The field being read, if any.
+    current: Option<(String, String, usize)>,
+    state: PartialJsonState,
+    key: String,
+    /// Characters after a backslash, while an escape is incomplete.
+    escape: Option<String>,
+    high_surrogate: Option<u16>,
+    depth: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum PartialJsonState {
+    #[default]
+    Start,
+    BeforeKey,
+    Key,
+    AfterKey,
+    BeforeValue,
+    StringValue,
+    OtherValue,
+    /// A string inside a skipped value.
+    OtherString,
+    Done,
+}
+
+enum StringStep {
+    Char(char),
+    Pending,
+    Close,
+}
+
+impl PartialJsonStrings {
+    pub fn push(&mut self, delta: &str) {
+        for ch in delta.chars() {
+            self.push_char(ch);
+        }
+    }
+
+    /// The field's text so far and how many lines it has.
+    pub fn get(&self, key: &str) -> Option<(&str, usize)> {
+        match &self.current {
+            Some((current, text, lines)) if current == key => Some((text, *lines)),
+            _ => self
+                .fields
+                .get(key)
+                .map(|(text, lines)| (text.as_str(), *lines)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.current.is_none()
+    }
+
+    fn push_char(&mut self, ch: char) {
+        use PartialJsonState::*;
+        match self.state {
+            Start if ch.is_whitespace() => {}
+            Start if ch == '{' => self.state = BeforeKey,
+            BeforeKey if ch.is_whitespace() || ch == ',' => {}
+            BeforeKey if ch == '"' => {
+                self.key.clear();
+                self.state = Key;
+            }
+            Key => match self.string_step(ch) {
+                StringStep::Char(ch) => self.key.push(ch),
+                StringStep::Pending => {}
+                StringStep::Close => self.state = AfterKey,
+            },
+            AfterKey if ch.is_whitespace() => {}
+            AfterKey if ch == ':' => self.state = BeforeValue,
+            BeforeValue if ch.is_whitespace() => {}
+            BeforeValue if ch == '"' => {
+                self.current = Some((std::mem::take(&mut self.key), String::new(), 1));
+                self.state = StringValue;
+            }
+            BeforeValue => {
+                self.depth = 0;
+                self.state = OtherValue;
+                self.push_char(ch);
+            }
+            StringValue => match self.string_step(ch) {
+                StringStep::Char(ch) => {
+                    if let Some((_, text, lines)) = self.current.as_mut() {
+                        text.push(ch);
+                        *lines += usize::from(ch == '\n');
+                    }
+                }
+                StringStep::Pending => {}
+                StringStep::Close => {
+                    if let Some((key, text, lines)) = self.current.take() {
+                        self.fields.insert(key, (text, lines));
+                    }
+                    self.state = BeforeKey;
+                }
+            },
+            OtherValue => match ch {
+                ',' if self.depth == 0 => self.state = BeforeKey,
+                '}' if self.depth == 0 => self.state = Done,
+                '{' | '[' => self.depth += 1,
+                '}' | ']' => self.depth = self.depth.saturating_sub(1),
+                '"' => self.state = OtherString,
+                _ => {}
+            },
+            OtherString => {
+                if let StringStep::Close = self.string_step(ch) {
+                    self.state = OtherValue;
+                }
+            }
+            // The object closed, or the input is not one.
+            _ => self.state = Done,
+        }
+    }
+
+    /// One character of a string body after its opening quote, decoding
+    /// escapes once they are complete.
+    fn string_step(&mut self, ch: char) -> StringStep {
+        let Some(escape) = self.escape.as_mut() else {
+            return match ch {
+                '\\' => {
+                    self.escape = Some(String::new());
+                    StringStep::Pending
+                }
+                '"' => StringStep::Close,
+                _ => self.decoded(ch),
+            };
+        };
+        escape.push(ch);
+        if escape.starts_with('u') && escape.len() < 5 {
+            return StringStep::Pending;
+        }
+        let escape = self.escape.take().unwrap_or_default();
+        let decoded = match escape.as_str() {
+            "n" => '\n',
+            "t" => '\t',
+            "r" => '\r',
+            "b" => '\u{8}',
+            "f" => '\u{c}',
+            unicode if unicode.starts_with('u') => {
+                let Ok(unit) = u16::from_str_radix(&unicode[1..], 16) else {
+                    return self.decoded(char::REPLACEMENT_CHARACTER);
+                };
+                if (0xD800..0xDC00).contains(&unit) {
+                    self.high_surrogate = Some(unit);
+                    return StringStep::Pending;
+                }
+                if (0xDC00..0xE000).contains(&unit) {
+                    if let Some(high) = self.high_surrogate.take() {
+                        return StringStep::Char(
+                            char::decode_utf16([high, unit])
+                                .next()
+                                .and_then(Result::ok)
+                                .unwrap_or(char::REPLACEMENT_CHARACTER),
+                        );
+                    }
+                }
+                char::from_u32(unit.into()).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            other => other.chars().next().unwrap_or(char::REPLACEMENT_CHARACTER),
+        };
+        self.decoded(decoded)
+    }
+
+    /// `ch`, after a replacement for any high surrogate left unpaired.
+    fn decoded(&mut self, ch: char) -> StringStep {
+        if self.high_surrogate.take().is_some() {
+            match self.state {
+                PartialJsonState::Key => self.key.push(char::REPLACEMENT_CHARACTER),
+                PartialJsonState::StringValue => {
+                    if let Some((_, text, _)) = self.current.as_mut() {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                    }
+                }
+                _ => {}
+            }
+        }
+        StringStep::Char(ch)
+    }
+}
+
+const LIVE_PREVIEW_LINE_WIDTH: usize = 200;
+
+/// An edit_file or write_file input as it streams in, previewed as the
+/// removed and added text so far.
+#[derive(Debug)]
+pub struct LiveToolInput {
+    tool_name: String,
+    input: PartialJsonStrings,
+}
+
+impl LiveToolInput {
+    /// `None` for tools without a live preview.
+    pub fn new(tool_name: &str) -> Option<Self> {
+        matches!(tool_name, "edit_file" | "write_file").then(|| Self {
+            tool_name: tool_name.to_string(),
+            input: PartialJsonStrings::default(),
+        })
+    }
+
+    pub fn push(&mut self, delta: &str) {
+        self.input.push(delta);
+    }
+
+    /// A header and each line so far, marked, capped at `max_lines` body
+    /// lines with a count of the rest. Only the shown lines are visited.
+    pub fn preview_lines(&self, max_lines: usize) -> Vec<String> {
+        let field = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| self.input.get(key))
+                .unwrap_or(("", 0))
+        };
+        let (path, _) = field(&PATH_KEYS);
+        let sections = match self.tool_name.as_str() {
+            "edit_file" => vec![('-', field(&OLD_TEXT_KEYS)), ('+', field(&NEW_TEXT_KEYS))],
+            _ => vec![('+', field(&CONTENT_KEYS))],
+        };
+
+        let mut lines = vec![format!("[streaming {}] {path}", self.tool_name)];
+        let mut hidden = 0;
+        for (marker, (text, line_count)) in sections {
+            if text.is_empty() {
+                continue;
+            }
+            let room = (max_lines + 1).saturating_sub(lines.len());
+            for line in text.split('\n').take(room) {
+                let line = truncate_with_ellipsis(line, LIVE_PREVIEW_LINE_WIDTH, Ellipsis::Unicode);
+                lines.push(format!("  {marker} {line}"));
+            }
+            hidden += line_count.saturating_sub(room);
+        }
+        if hidden > 0 {
+            lines.push(format!(
+                "  ... {hidden} more line{}",
+                if hidden == 1 { "" } else { "s" }
+            ));
+        }
+        lines
+    }
 }
 
 pub fn preview_tool_input(
@@ -287,6 +646,27 @@ pub fn preview_tool_input(
                 .unwrap_or(100);
             format!("path: {path}\nmax_entries: {max_entries}")
         }
+        (ToolPreviewStyle::Structured, "tail_file") => {
+            let path = input.get("path").and_then(|v| v.as_str()).unwrap_or("?");
+            let max_lines = input
+                .get("max_lines")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(200);
+            let reset = input
+                .get("reset")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            if reset {
+                format!("path: {path}\nmax_lines: {max_lines}\nreset: true")
+            } else {
+                format!("path: {path}\nmax_lines: {max_lines}")
+            }
+        }
+        (ToolPreviewStyle::Structured, "tree") => {
+            let path = input.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+            let max_depth = input.get("max_depth").and_then(|v| v.as_u64()).unwrap_or(3);
+            format!("path: {path}\nmax_depth: {max_depth}")
+        }
         (ToolPreviewStyle::Structured, "search_files" | "search") => {
             let query = input
                 .get("query")
@@ -305,6 +685,21 @@ pub fn preview_tool_input(
             out.push_str(&format!("max_results: {max_results}"));
             out
         }
+        (_, SEND_SECRETS_APPROVAL) => {
+            let secrets = input
+                .get("secrets")
+                .and_then(|v| v.as_array())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let mut out = "The next request contains the value of:\n".to_string();
+            for secret in secrets {
+                let source = secret.get("source").and_then(|v| v.as_str()).unwrap_or("?");
+                let value = secret.get("value").and_then(|v| v.as_str()).unwrap_or("?");
+                out.push_str(&format!("  {source}  {value}\n"));
+            }
+            out.push_str("Approve to send it as is; deny to redact it.");
+            out
+        }
         (ToolPreviewStyle::Structured, _) => {
             if input.as_object().map(|obj| obj.is_empty()).unwrap_or(false) {
                 "(no arguments)".to_string()
@@ -322,6 +717,61 @@ pub fn preview_tool_input(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_partial_json_strings_keep_the_unfinished_value() {
+        let mut fields = PartialJsonStrings::default();
+        // Split mid-escape and mid-character, as stream deltas can be.
+        for delta in [
+            r#"{"path": "src/a.rs", "replace_all": false, "old_str": "fn a() {\n    \"#,
+            r#""x\u00"#,
+            r#"e9 \ud83d\ude00"#,
+        ] {
+            fields.push(delta);
+        }
+        assert_eq!(fields.get("path"), Some(("src/a.rs", 1)));
+        assert_eq!(
+            fields.get("old_str"),
+            Some(("fn a() {\n    \"x\u{e9} \u{1f600}", 2))
+        );
+        assert!(fields.get("replace_all").is_none());
+
+        let mut fields = PartialJsonStrings::default();
+        fields.push(r#"{"path": "a.rs", "content": "x\"#);
+        assert_eq!(fields.get("content"), Some(("x", 1)));
+        let mut fields = PartialJsonStrings::default();
+        fields.push("");
+        assert!(fields.is_empty());
+    }
+
+    #[test]
+    fn test_live_preview_grows_with_streamed_edit_input() {
+        let mut edit = LiveToolInput::new("edit_file").expect("edit_file preview");
+        edit.push(r#"{"path":"src/a.rs","old_str":"let x = 1;\nlet y = 2;","#);
+        edit.push(r#""new_str":"let x = 10;\nlet"#);
+        assert_eq!(
+            edit.preview_lines(10),
+            vec![
+                "[streaming edit_file] src/a.rs",
+                "  - let x = 1;",
+                "  - let y = 2;",
+                "  + let x = 10;",
+                "  + let",
+            ]
+        );
+        let mut write = LiveToolInput::new("write_file").expect("write_file preview");
+        write.push(r#"{"path":"b","content":"1\n2\n3\n4"#);
+        assert_eq!(
+            write.preview_lines(2),
+            vec![
+                "[streaming write_file] b",
+                "  + 1",
+                "  + 2",
+                "  ... 2 more lines"
+            ]
+        );
+        assert!(LiveToolInput::new("read_file").is_none());
+    }
+
     #[test]
     fn test_preview_lines_with_and_without_marker() {
         assert_eq!(preview_lines(Some('+'), "", 10, 1, "  "), "  1 + <empty>\n");
@@ -355,6 +805,11 @@ mod tests {
                 before_lines: 1,
                 after_chars: 4,
                 after_lines: 1,
+                delta: Some(LineDelta {
+                    changed_lines: 1,
+                    first_line: 1,
+                    last_line: 1,
+                }),
             }
         );
 
@@ -377,6 +832,10 @@ mod tests {
 
         let missing_path = serde_json::json!({ "query": "needle" });
         assert_eq!(read_file_path(&missing_path), None);
+        assert_eq!(
+            read_file_snapshot_key(&serde_json::json!({ "path": "a.rs", "start_line": 10 })),
+            Some("a.rs:10-".to_string())
+        );
 
         // Non-string value must also return None.
         let non_string = serde_json::json!({ "path": 42 });
@@ -409,13 +868,54 @@ mod tests {
                 before_lines: 2,
                 after_chars: 10,
                 after_lines: 2,
+                delta: Some(LineDelta {
+                    changed_lines: 1,
+                    first_line: 2,
+                    last_line: 2,
+                }),
             },
             ReadFileSummaryMessageStyle::StreamEvent,
         );
         assert_eq!(
             stream,
-            "content changed: 9 chars/2 lines -> 10 chars/2 lines"
+            "content changed: 9 chars/2 lines -> 10 chars/2 lines, 1 line changed near line 2"
+        );
+    }
+
+    #[test]
+    fn test_changed_reread_yields_mini_diff_until_too_large() {
+        let mut cache = ReadFileSnapshotCache::default();
+        let before: String = (1..=200).map(|n| format!("line {n}\n")).collect();
+        cache.summarize_with_diff("big.rs", &before);
+
+        let after = before.replace("line 120\n", "line 120 edited\n");
+        let (summary, diff) = cache.summarize_with_diff("big.rs", &after);
+        let ReadFileSnapshotSummary::Changed { delta, .. } = summary else {
+            panic!("expected Changed, got {summary:?}");
+        };
+        assert_eq!(
+            delta.map(LineDelta::describe).as_deref(),
+            Some("1 line changed near line 120")
+        );
+        assert_eq!(
+            diff.as_deref(),
+            Some("@@ -118,5 +118,5 @@\n line 118\n line 119\n-line 120\n+line 120 edited\n line 121\n line 122")
         );
+
+        let rewritten: String = (1..=200).map(|n| format!("row {n}\n")).collect();
+        let (summary, diff) = cache.summarize_with_diff("big.rs", &rewritten);
+        assert!(matches!(
+            summary,
+            ReadFileSnapshotSummary::Changed {
+                delta: Some(LineDelta {
+                    changed_lines: 200,
+                    first_line: 1,
+                    last_line: 200,
+                }),
+                ..
+            }
+        ));
+        assert_eq!(diff, None);
     }
 
     #[test]
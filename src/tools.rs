@@ -1,3 +1,40 @@
+mod commit_message;
+mod custom;
+mod file_locks;
+mod fuzzy_edit;
+mod memory;
 mod operator;
-
+mod patch_output;
+mod pool;
+mod process_group;
+mod repo_map;
+mod result_refs;
+mod scratch;
+mod snapshots;
+mod symbol_context;
+mod tail;
+mod test_runner;
+mod tree;
+mod vcs;
+mod workspace_stats;
+pub(crate) use custom::{parse_flat_toml, TomlScalar};
+pub use custom::{CustomTool, CustomToolSet};
+pub use file_locks::{file_locks_enabled_from_env, FileLockTurn, FileLocks, FILE_LOCKS_ENV};
+pub use fuzzy_edit::{fuzzy_edit_enabled_from_env, FuzzyEdit, FUZZY_EDIT_ENV};
+pub use memory::{render_memory_entries, MemoryEntry, MemoryStore};
 pub use operator::ToolOperator;
+pub use patch_output::{PatchEntry, PatchKind, PatchOutput};
+pub use pool::{
+    ToolPool, ToolPoolStats, ToolPriority, ToolSlot, DEFAULT_TOOL_CONCURRENCY, TOOL_CONCURRENCY_ENV,
+};
+pub use process_group::{
+    kill_process_group, own_process_group, own_process_group_std, spawn_tracked, wait_with_timeout,
+    CallProcesses, ProcessGroupGuard,
+};
+pub use result_refs::{result_dedup_enabled_from_env, ToolResultStore, DEDUP_TOOL_RESULTS_ENV};
+pub use scratch::ScratchSpace;
+pub use snapshots::{FileSnapshots, Snapshot, SnapshotChange};
+pub use symbol_context::{symbol_context_enabled_from_env, SYMBOL_CONTEXT_ENV};
+pub use test_runner::resolve_test_timeout;
+pub use vcs::{git_repository_found, Vcs};
+pub use workspace_stats::{format_bytes, workspace_files};
@@ -0,0 +1,354 @@
+use anyhow::{bail, Result};
+use std::path::Path;
+
+/// Conventional-commit subjects are kept to this many characters.
+const MAX_SUBJECT_CHARS: usize = 72;
+/// Longer requests are cut off in the message body.
+const MAX_INTENT_CHARS: usize = 400;
+/// Changed files listed in the message body.
+const MAX_LISTED_FILES: usize = 12;
+const DOC_EXTENSIONS: [&str; 4] = ["md", "txt", "rst", "adoc"];
+const BUILD_FILES: [&str; 7] = [
+    "Cargo.toml",
+    "Cargo.lock",
+    "package.json",
+    "package-lock.json",
+    "pyproject.toml",
+    "go.mod",
+    "Makefile",
+];
+/// Words in the request that pick the commit type, checked in order.
+const TYPE_KEYWORDS: [(&str, &[&str]); 6] = [
+    (
+        "fix",
+        &["fix", "bug", "crash", "broken", "error", "regression"],
+    ),
+    ("perf", &["perf", "faster", "slow", "speed", "optimi"]),
+    (
+        "refactor",
+        &["refactor", "rename", "clean", "restructure", "simplif"],
+    ),
+    ("docs", &["docs", "document", "readme"]),
+    ("test", &["test"]),
+    ("feat", &["add", "implement", "support", "introduce", "new"]),
+];
+/// Leading words of a request that do not belong in a subject.
+const FILLER_PREFIXES: [&str; 6] = [
+    "please ",
+    "can you ",
+    "could you ",
+    "let's ",
+    "lets ",
+    "i want to ",
+];
+
+/// Which changes a suggested message describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitSource {
+    /// What `git_commit` would commit now.
+    Staged,
+    /// Every uncommitted change in the working tree, staged or not.
+    Session,
+}
+
+impl CommitSource {
+    pub fn parse(value: Option<&str>) -> Result<Self> {
+        match value.map(str::trim).unwrap_or("staged") {
+            "" | "staged" | "cached" => Ok(Self::Staged),
+            "session" | "all" | "worktree" => Ok(Self::Session),
+            other => bail!("unknown source '{other}'; use \"staged\" or \"session\""),
+        }
+    }
+}
+
+/// One changed path with its `git diff --name-status` letter and line counts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    pub status: char,
+    pub path: String,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Joins `git diff --name-status` and `--numstat` output. Renames list the
+/// new path.
+pub fn parse_changed_files(name_status: &str, numstat: &str) -> Vec<ChangedFile> {
+    let counts: Vec<(String, usize, usize)> = numstat
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let added = fields.next()?.parse().unwrap_or(0);
+            let removed = fields.next()?.parse().unwrap_or(0);
+            let path = renamed_to(fields.next_back()?);
+            Some((path, added, removed))
+        })
+        .collect();
+    name_status
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let status = fields.next()?.chars().next()?;
+            let path = fields.next_back()?.to_string();
+            let (added, removed) = counts
+                .iter()
+                .find(|(counted, _, _)| *counted == path)
+                .map_or((0, 0), |(_, added, removed)| (*added, *removed));
+            Some(ChangedFile {
+                status,
+                path,
+                added,
+                removed,
+            })
+        })
+        .collect()
+}
+
+/// The new path of a `--numstat` rename, written `src/{old.rs => new.rs}`
+/// or `old.rs => new.rs`.
+fn renamed_to(path: &str) -> String {
+    if let (Some(open), Some(close)) = (path.find('{'), path.rfind('}')) {
+        if let Some((_, new)) = path[open + 1..close].split_once(" => ") {
+            return format!("{}{new}{}", &path[..open], &path[close + 1..]).replace("//", "/");
+        }
+    }
+    match path.split_once(" => ") {
+        Some((_, new)) => new.to_string(),
+        None => path.to_string(),
+    }
+}
+
+/// A conventional-commit message for `files`: type and scope from the
+/// paths and the request, a subject from the request, and a body quoting
+/// the request and listing the changes.
+pub fn draft_commit_message(files: &[ChangedFile], intent: Option<&str>) -> String {
+    let intent = intent
+        .map(|intent| intent.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|intent| !intent.is_empty());
+    let kind = commit_type(files, intent.as_deref());
+    let prefix = match commit_scope(files) {
+        Some(scope) => format!("{kind}({scope}): "),
+        None => format!("{kind}: "),
+    };
+    let subject = intent
+        .as_deref()
+        .map(|intent| without_type_word(&subject_from_intent(intent), kind))
+        .filter(|subject| !subject.is_empty())
+        .unwrap_or_else(|| match files {
+            [file] => format!("update {}", file.path),
+            files => format!("update {} files", files.len()),
+        });
+    let mut message = prefix.clone();
+    let room = MAX_SUBJECT_CHARS.saturating_sub(prefix.chars().count());
+    if subject.chars().count() > room {
+        message.extend(subject.chars().take(room.saturating_sub(3)));
+        message.push_str("...");
+    } else {
+        message.push_str(&subject);
+    }
+    message.push_str("\n\n");
+    if let Some(intent) = &intent {
+        let mut quoted: String = intent.chars().take(MAX_INTENT_CHARS).collect();
+        if quoted.len() < intent.len() {
+            quoted.push_str("...");
+        }
+        message.push_str(&format!("Requested: {quoted}\n\n"));
+    }
+    for file in files.iter().take(MAX_LISTED_FILES) {
+        message.push_str(&format!(
+            "- {} {} (+{} -{})\n",
+            file.status, file.path, file.added, file.removed
+        ));
+    }
+    if files.len() > MAX_LISTED_FILES {
+        message.push_str(&format!(
+            "- and {} more files\n",
+            files.len() - MAX_LISTED_FILES
+        ));
+    }
+    message.trim_end().to_string()
+}
+
+fn commit_type(files: &[ChangedFile], intent: Option<&str>) -> &'static str {
+    let all = |predicate: fn(&str) -> bool| {
+        !files.is_empty() && files.iter().all(|file| predicate(&file.path))
+    };
+    if all(is_doc_path) {
+        return "docs";
+    }
+    if all(is_test_path) {
+        return "test";
+    }
+    if all(|path| path.starts_with(".github/") || path.starts_with(".gitlab-ci")) {
+        return "ci";
+    }
+    if all(|path| BUILD_FILES.contains(&file_name(path))) {
+        return "build";
+    }
+    if let Some(intent) = intent.map(str::to_lowercase) {
+        let words: Vec<&str> = intent
+            .split(|ch: char| !ch.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        for (kind, keywords) in TYPE_KEYWORDS {
+            if words
+                .iter()
+                .any(|word| keywords.iter().any(|keyword| word.starts_with(keyword)))
+            {
+                return kind;
+            }
+        }
+    }
+    if files.iter().any(|file| file.status == 'A') {
+        "feat"
+    } else {
+        "chore"
+    }
+}
+
+/// The deepest directory shared by every path, skipping `src`, or the file
+/// stem when one file changed.
+fn commit_scope(files: &[ChangedFile]) -> Option<String> {
+    if let [file] = files {
+        return Path::new(&file.path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .map(str::to_string);
+    }
+    let mut shared: Vec<&str> = files.first()?.path.split('/').collect();
+    shared.pop();
+    for file in &files[1..] {
+        let dirs: Vec<&str> = file.path.split('/').collect();
+        let dirs = &dirs[..dirs.len() - 1];
+        let common = shared
+            .iter()
+            .zip(dirs)
+            .take_while(|(left, right)| left == right)
+            .count();
+        shared.truncate(common);
+    }
+    shared
+        .into_iter()
+        .rev()
+        .find(|dir| *dir != "src")
+        .map(str::to_string)
+}
+
+/// The request's first sentence, without filler, lower-cased and without a
+/// trailing period.
+fn subject_from_intent(intent: &str) -> String {
+    let sentence = intent
+        .split_terminator(['.', '\n', '?', '!'])
+        .next()
+        .unwrap_or(intent)
+        .trim();
+    let mut subject = sentence.to_string();
+    let mut stripped = true;
+    while stripped {
+        stripped = false;
+        for filler in FILLER_PREFIXES {
+            if subject.to_lowercase().starts_with(filler) {
+                subject = subject[filler.len()..].trim_start().to_string();
+                stripped = true;
+            }
+        }
+    }
+    let mut chars = subject.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// `subject` without a leading word that only restates `kind`, so "fix the
+/// crash" under `fix:` becomes "crash" rather than "fix: fix the crash".
+fn without_type_word(subject: &str, kind: &str) -> String {
+    let mut words = subject.split_whitespace();
+    let Some(first) = words.next() else {
+        return String::new();
+    };
+    if !first.to_lowercase().starts_with(kind) {
+        return subject.to_string();
+    }
+    let mut rest: Vec<&str> = words.collect();
+    if rest
+        .first()
+        .is_some_and(|word| ["the", "a", "an"].contains(&word.to_lowercase().as_str()))
+    {
+        rest.remove(0);
+    }
+    if rest.is_empty() {
+        subject.to_string()
+    } else {
+        rest.join(" ")
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn is_doc_path(path: &str) -> bool {
+    path.starts_with("docs/")
+        || Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| DOC_EXTENSIONS.contains(&extension))
+}
+
+fn is_test_path(path: &str) -> bool {
+    let name = file_name(path);
+    path.starts_with("tests/")
+        || path.contains("/tests/")
+        || name.starts_with("test_")
+        || name.contains("_test.")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_takes_type_scope_and_subject_from_changes_and_request() {
+        let files = parse_changed_files(
+            "A\tsrc/tools/scratch.rs\nM\tsrc/tools/operator.rs\nR100\tsrc/tools/old.rs\tsrc/tools/new.rs\n",
+            "120\t0\tsrc/tools/scratch.rs\n3\t1\tsrc/tools/operator.rs\n0\t0\tsrc/tools/{old.rs => new.rs}\n",
+        );
+        assert_eq!(files.len(), 3);
+        assert_eq!((files[0].added, files[1].removed), (120, 1));
+        assert_eq!(files[2].path, "src/tools/new.rs");
+
+        let message = draft_commit_message(
+            &files,
+            Some("Please add per-turn scratch directories. Keep them out of git."),
+        );
+        let mut lines = message.lines();
+        assert_eq!(
+            lines.next(),
+            Some("feat(tools): add per-turn scratch directories")
+        );
+        assert!(message
+            .contains("Requested: Please add per-turn scratch directories. Keep them out of git."));
+        assert!(message.contains("- A src/tools/scratch.rs (+120 -0)"));
+
+        let readme = parse_changed_files("M\tREADME.md\n", "4\t2\tREADME.md\n");
+        assert_eq!(
+            draft_commit_message(&readme, None).lines().next(),
+            Some("docs(README): update README.md")
+        );
+        let mixed = parse_changed_files("M\tsrc/a.rs\nM\tlib/b.rs\n", "");
+        assert!(
+            draft_commit_message(&mixed, Some("Fixed the crash on empty input"))
+                .starts_with("fix: crash on empty input\n")
+        );
+        assert!(draft_commit_message(&mixed, Some("fix")).starts_with("fix: fix\n"));
+        assert!(draft_commit_message(&mixed, Some("refactor the lexer"))
+            .starts_with("refactor: lexer\n"));
+        assert_eq!(
+            CommitSource::parse(None).expect("source"),
+            CommitSource::Staged
+        );
+        assert!(CommitSource::parse(Some("branch")).is_err());
+    }
+}
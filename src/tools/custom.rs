@@ -0,0 +1,375 @@
+use super::process_group::spawn_tracked;
+use crate::state_dirs::StateDirs;
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// A project-defined tool loaded from `.aistar/tools/<name>.toml`:
+///
+/// ```toml
+/// name = "lint"
+/// description = "Run clippy for one package."
+/// command = "cargo clippy -p {{package}} -- -D warnings"
+/// input_schema = '''{"type":"object","properties":{"package":{"type":"string"}},"required":["package"]}'''
+/// requires_approval = true
+/// ```
+///
+/// `{{arg}}` placeholders are replaced with shell-quoted tool arguments and the
+/// command runs under `sh -c` in the workspace root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomTool {
+    pub name: String,
+    pub description: String,
+    pub command: String,
+    pub input_schema: Value,
+    /// What the file asks for. Not honored: the file comes with the
+    /// repository, so it cannot waive confirmation for its own command.
+    pub requires_approval: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CustomToolSet {
+    pub tools: Vec<CustomTool>,
+    pub errors: Vec<String>,
+}
+
+impl CustomTool {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut fields = parse_flat_toml(source)?;
+        let mut take_string = |key: &str| match fields.remove(key) {
+            Some(TomlScalar::String(value)) => Ok(Some(value)),
+            Some(_) => bail!("'{key}' must be a string"),
+            None => Ok(None),
+        };
+
+        let name = take_string("name")?.context("missing 'name'")?;
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+        {
+            bail!("tool name '{name}' must be non-empty and use only [A-Za-z0-9_-]");
+        }
+        let command = take_string("command")?.context("missing 'command'")?;
+        let description =
+            take_string("description")?.unwrap_or_else(|| format!("Project tool `{name}`."));
+        let input_schema = match take_string("input_schema")? {
+            Some(schema) => serde_json::from_str::<Value>(&schema)
+                .context("'input_schema' must be a JSON object string")?,
+            None => json!({ "type": "object", "properties": {} }),
+        };
+        if !input_schema.is_object() {
+            bail!("'input_schema' must be a JSON object");
+        }
+        let requires_approval = match fields.remove("requires_approval") {
+            Some(TomlScalar::Bool(value)) => value,
+            Some(_) => bail!("'requires_approval' must be a boolean"),
+            None => true,
+        };
+        if let Some(unknown) = fields.keys().next() {
+            bail!("unknown key '{unknown}'");
+        }
+
+        Ok(Self {
+            name,
+            description,
+            command,
+            input_schema,
+            requires_approval,
+        })
+    }
+
+    pub fn definition(&self) -> Value {
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "input_schema": self.input_schema,
+        })
+    }
+
+    /// Expands `{{arg}}` placeholders with shell-quoted argument values.
+    pub fn render_command(&self, input: &Value) -> Result<String> {
+        let mut rendered = String::new();
+        let mut rest = self.command.as_str();
+        while let Some(start) = rest.find("{{") {
+            rendered.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let end = after
+                .find("}}")
+                .with_context(|| format!("unterminated placeholder in {} command", self.name))?;
+            let key = after[..end].trim();
+            let value = match input.get(key) {
+                Some(Value::String(text)) => text.clone(),
+                Some(Value::Null) | None => {
+                    bail!("{} requires a '{key}' argument", self.name)
+                }
+                Some(other) => other.to_string(),
+            };
+            rendered.push_str(&shell_quote(&value));
+            rest = &after[end + 2..];
+        }
+        rendered.push_str(rest);
+        Ok(rendered)
+    }
+
+    /// Runs the tool; stdout becomes the tool result and a non-zero exit
+    /// becomes a tool error carrying stderr.
+    pub fn execute(&self, working_dir: &Path, input: &Value) -> Result<String> {
+        let command = self.render_command(input)?;
+        let output = spawn_tracked(
+            Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .current_dir(working_dir)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
+        .and_then(|child| child.wait_with_output())
+        .with_context(|| format!("Failed to execute custom tool {}", self.name))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if !output.status.success() {
+            let details = if stderr.is_empty() { stdout } else { stderr };
+            let code = output
+                .status
+                .code()
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "signal".to_string());
+            bail!("{} failed (exit {code}): {details}", self.name);
+        }
+        if stdout.is_empty() {
+            Ok("OK".to_string())
+        } else {
+            Ok(stdout)
+        }
+    }
+}
+
+impl CustomToolSet {
+    /// Loads every `*.toml` under `.aistar/tools/`, sorted by file name.
+    /// Invalid files are reported in `errors` and skipped.
+    pub fn load(working_dir: &Path) -> Self {
+        let dir = StateDirs::for_workspace(working_dir).tools_dir();
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return Self::default();
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        paths.sort();
+
+        let mut set = Self::default();
+        for path in paths {
+            let display = path
+                .strip_prefix(working_dir)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            let parsed = fs::read_to_string(&path)
+                .context("unreadable")
+                .and_then(|source| CustomTool::parse(&source));
+            match parsed {
+                Ok(tool) if set.get(&tool.name).is_some() => set
+                    .errors
+                    .push(format!("{display}: duplicate tool name '{}'", tool.name)),
+                Ok(tool) => set.tools.push(tool),
+                Err(error) => set.errors.push(format!("{display}: {error:#}")),
+            }
+        }
+        set
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CustomTool> {
+        self.tools.iter().find(|tool| tool.name == name)
+    }
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TomlScalar {
+    String(String),
+    Bool(bool),
+}
+
+/// Parses the flat `key = value` subset of TOML used by tool and profile
+/// files: basic, literal, and multi-line strings, and booleans. Tables are
+/// not supported.
+pub(crate) fn parse_flat_toml(source: &str) -> Result<BTreeMap<String, TomlScalar>> {
+    let mut fields = BTreeMap::new();
+    let mut lines = source.lines().enumerate();
+    while let Some((line_idx, line)) = lines.next() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed.starts_with('[') {
+            bail!("line {}: tables are not supported", line_idx + 1);
+        }
+        let (key, value) = trimmed
+            .split_once('=')
+            .with_context(|| format!("line {}: expected key = value", line_idx + 1))?;
+        let key = key.trim().trim_matches('"').to_string();
+        let value = value.trim();
+
+        let scalar = if let Some(delimiter) = ["\"\"\"", "'''"]
+            .into_iter()
+            .find(|delimiter| value.starts_with(delimiter))
+        {
+            let mut body = value[3..].to_string();
+            while !body.contains(delimiter) {
+                let (_, next) = lines
+                    .next()
+                    .with_context(|| format!("line {}: unterminated string", line_idx + 1))?;
+                body.push('\n');
+                body.push_str(next);
+            }
+            let end = body.find(delimiter).unwrap_or(body.len());
+            let body = body[..end].strip_prefix('\n').unwrap_or(&body[..end]);
+            if delimiter == "'''" {
+                TomlScalar::String(body.to_string())
+            } else {
+                TomlScalar::String(unescape_basic(body, line_idx)?)
+            }
+        } else if let Some(rest) = value.strip_prefix('"') {
+            let end = find_basic_string_end(rest)
+                .with_context(|| format!("line {}: unterminated string", line_idx + 1))?;
+            TomlScalar::String(unescape_basic(&rest[..end], line_idx)?)
+        } else if let Some(rest) = value.strip_prefix('\'') {
+            let end = rest
+                .find('\'')
+                .with_context(|| format!("line {}: unterminated string", line_idx + 1))?;
+            TomlScalar::String(rest[..end].to_string())
+        } else {
+            let bare = value.split('#').next().unwrap_or_default().trim();
+            match bare {
+                "true" => TomlScalar::Bool(true),
+                "false" => TomlScalar::Bool(false),
+                _ => bail!("line {}: unsupported value '{bare}'", line_idx + 1),
+            }
+        };
+
+        if fields.insert(key.clone(), scalar).is_some() {
+            bail!("line {}: duplicate key '{key}'", line_idx + 1);
+        }
+    }
+    Ok(fields)
+}
+
+fn find_basic_string_end(rest: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (idx, ch) in rest.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(idx),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn unescape_basic(text: &str, line_idx: usize) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            other => bail!(
+                "line {}: unsupported escape '\\{}'",
+                line_idx + 1,
+                other.map(String::from).unwrap_or_default()
+            ),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const LINT_TOOL: &str = r#"
+# Project lint helper
+name = "lint"
+description = "Run clippy for one package."
+command = "echo linting {{package}}"
+input_schema = '''
+{"type":"object","properties":{"package":{"type":"string"}},"required":["package"]}
+'''
+requires_approval = false
+"#;
+
+    #[test]
+    fn test_parse_tool_definition() {
+        let tool = CustomTool::parse(LINT_TOOL).expect("parse");
+        assert_eq!(tool.name, "lint");
+        assert!(!tool.requires_approval);
+        assert_eq!(tool.input_schema["required"][0], "package");
+        assert_eq!(tool.definition()["name"], "lint");
+
+        assert!(CustomTool::parse("name = \"x\"").is_err());
+        assert!(CustomTool::parse("name = \"bad name\"\ncommand = \"true\"").is_err());
+        assert!(CustomTool::parse("[tool]\nname = \"x\"").is_err());
+    }
+
+    #[test]
+    fn test_render_command_quotes_arguments() {
+        let tool = CustomTool::parse(LINT_TOOL).expect("parse");
+        let rendered = tool
+            .render_command(&json!({ "package": "core'; rm -rf /" }))
+            .expect("render");
+        assert_eq!(rendered, "echo linting 'core'\\''; rm -rf /'");
+        assert!(tool.render_command(&json!({})).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_load_and_execute_maps_exit_status() {
+        let temp = TempDir::new().expect("temp dir");
+        let dir = temp.path().join(".aistar/tools");
+        fs::create_dir_all(&dir).expect("tools dir");
+        fs::write(dir.join("lint.toml"), LINT_TOOL).expect("write tool");
+        fs::write(
+            dir.join("fail.toml"),
+            "name = \"fail\"\ncommand = \"echo boom >&2; exit 3\"\n",
+        )
+        .expect("write tool");
+        fs::write(dir.join("broken.toml"), "name = ").expect("write tool");
+
+        let set = CustomToolSet::load(temp.path());
+        assert_eq!(set.tools.len(), 2);
+        assert_eq!(set.errors.len(), 1);
+        assert!(set.errors[0].starts_with(".aistar/tools/broken.toml"));
+
+        let lint = set.get("lint").expect("lint tool");
+        assert_eq!(
+            lint.execute(temp.path(), &json!({ "package": "core" }))
+                .expect("lint runs"),
+            "linting core"
+        );
+        let error = set
+            .get("fail")
+            .expect("fail tool")
+            .execute(temp.path(), &json!({}))
+            .expect_err("non-zero exit is a tool error");
+        assert_eq!(error.to_string(), "fail failed (exit 3): boom");
+    }
+}
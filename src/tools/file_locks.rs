@@ -0,0 +1,264 @@
+use crate::state_dirs::{new_session_id, process_alive, StateDirs};
+use crate::util::{parse_bool_str, unix_now};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+pub const FILE_LOCKS_ENV: &str = "VEX_FILE_LOCKS";
+/// A lock not refreshed for this long is treated as left behind by a
+/// session that exited without cleaning up.
+const LOCK_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Lock markers are written unless `VEX_FILE_LOCKS` is off.
+pub fn file_locks_enabled_from_env() -> bool {
+    std::env::var(FILE_LOCKS_ENV)
+        .ok()
+        .and_then(|value| parse_bool_str(&value))
+        .unwrap_or(true)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Contention {
+    session: String,
+    pid: u32,
+    at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct LockRecord {
+    path: String,
+    session: String,
+    pid: u32,
+    acquired_at: u64,
+    refreshed_at: u64,
+    /// Other sessions that changed the file while this lock was held.
+    #[serde(default)]
+    contested_by: Vec<Contention>,
+}
+
+impl LockRecord {
+    fn is_live(&self, now: u64) -> bool {
+        now.saturating_sub(self.refreshed_at) < LOCK_TTL.as_secs() && process_alive(self.pid)
+    }
+}
+
+/// Advisory markers under `.aistar/locks/` naming the files a session is
+/// changing during a turn, so two vex sessions in one workspace notice when
+/// they edit the same file. Nothing is blocked: the session that writes
+/// through another's lock gets a warning in its tool result, and the lock
+/// owner is told the next time it checks for contentions.
+#[derive(Debug)]
+pub struct FileLocks {
+    dir: PathBuf,
+    session: String,
+    pid: u32,
+    held: Mutex<BTreeSet<String>>,
+}
+
+impl FileLocks {
+    pub fn for_workspace(root: &Path) -> Self {
+        Self {
+            dir: StateDirs::for_workspace(root).locks_dir(),
+            session: new_session_id(),
+            pid: std::process::id(),
+            held: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Marks workspace-relative `path` as being changed by this session.
+    /// When another live session holds it, its lock is kept, marked as
+    /// contested, and a warning is returned.
+    pub fn claim(&self, path: &str) -> Result<Option<String>> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        let lock_path = self.lock_path(path);
+        let now = unix_now();
+        if let Some(mut record) = read_record(&lock_path) {
+            if record.session != self.session && record.is_live(now) {
+                record.contested_by.push(Contention {
+                    session: self.session.clone(),
+                    pid: self.pid,
+                    at: now,
+                });
+                write_record(&lock_path, &record)?;
+                return Ok(Some(format!(
+                    "[file lock] {path} is being changed by another vex session (pid {}, since {} UTC). This change may conflict with theirs; re-read the file before editing it again and tell the user about the overlap.",
+                    record.pid,
+                    crate::audit::format_utc(record.acquired_at)
+                )));
+            }
+            if record.session == self.session {
+                record.refreshed_at = now;
+                write_record(&lock_path, &record)?;
+                return Ok(None);
+            }
+        }
+        write_record(
+            &lock_path,
+            &LockRecord {
+                path: path.to_string(),
+                session: self.session.clone(),
+                pid: self.pid,
+                acquired_at: now,
+                refreshed_at: now,
+                contested_by: Vec::new(),
+            },
+        )?;
+        self.held().insert(path.to_string());
+        Ok(None)
+    }
+
+    /// One warning per file this session holds that another session changed
+    /// since the last call.
+    pub fn take_contentions(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for path in self.held().iter() {
+            let lock_path = self.lock_path(path);
+            let Some(mut record) = read_record(&lock_path) else {
+                continue;
+            };
+            if record.session != self.session || record.contested_by.is_empty() {
+                continue;
+            }
+            let pids: BTreeSet<u32> = record.contested_by.iter().map(|other| other.pid).collect();
+            let pids: Vec<String> = pids.iter().map(u32::to_string).collect();
+            warnings.push(format!(
+                "[file lock] another vex session (pid {}) changed {path} while this session was editing it. Re-read the file before editing it again and tell the user about the overlap.",
+                pids.join(", ")
+            ));
+            record.contested_by.clear();
+            let _ = write_record(&lock_path, &record);
+        }
+        warnings
+    }
+
+    /// Removes every lock this session holds.
+    pub fn release_all(&self) {
+        let held = std::mem::take(&mut *self.held());
+        for path in held {
+            let lock_path = self.lock_path(&path);
+            if read_record(&lock_path).is_some_and(|record| record.session == self.session) {
+                let _ = fs::remove_file(&lock_path);
+            }
+        }
+    }
+
+    fn held(&self) -> MutexGuard<'_, BTreeSet<String>> {
+        self.held
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_path(&self, path: &str) -> PathBuf {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        path.hash(&mut hasher);
+        self.dir.join(format!("{:016x}.json", hasher.finish()))
+    }
+}
+
+impl Drop for FileLocks {
+    fn drop(&mut self) {
+        self.release_all();
+    }
+}
+
+/// Releases a session's locks when the turn that took them ends, however it
+/// ends.
+#[derive(Debug)]
+pub struct FileLockTurn(Option<Arc<FileLocks>>);
+
+impl FileLockTurn {
+    pub fn new(locks: Option<Arc<FileLocks>>) -> Self {
+        Self(locks)
+    }
+}
+
+impl Drop for FileLockTurn {
+    fn drop(&mut self) {
+        if let Some(locks) = &self.0 {
+            locks.release_all();
+        }
+    }
+}
+
+fn read_record(path: &Path) -> Option<LockRecord> {
+    let raw = fs::read(path).ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+/// Writes through a temporary file so a concurrent reader never sees half a
+/// record.
+fn write_record(path: &Path, record: &LockRecord) -> Result<()> {
+    let temp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    fs::write(&temp, serde_json::to_vec(record)?)
+        .with_context(|| format!("failed to write {}", temp.display()))?;
+    fs::rename(&temp, path).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_overlapping_sessions_are_warned_on_both_sides() -> Result<()> {
+        let temp = TempDir::new()?;
+        let first = FileLocks::for_workspace(temp.path());
+        let second = FileLocks::for_workspace(temp.path());
+
+        assert_eq!(first.claim("src/lib.rs")?, None);
+        assert_eq!(first.claim("src/lib.rs")?, None);
+        assert_eq!(second.claim("src/main.rs")?, None);
+        let warning = second
+            .claim("src/lib.rs")?
+            .expect("held by the first session");
+        assert!(
+            warning.starts_with("[file lock] src/lib.rs is being changed by another vex session"),
+            "{warning}"
+        );
+
+        let contentions = first.take_contentions();
+        assert_eq!(contentions.len(), 1);
+        assert!(
+            contentions[0].contains("changed src/lib.rs"),
+            "{contentions:?}"
+        );
+        assert!(first.take_contentions().is_empty());
+
+        first.release_all();
+        assert_eq!(second.claim("src/lib.rs")?, None);
+        drop(second);
+        let leftover = fs::read_dir(first.dir())?.count();
+        assert_eq!(leftover, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expired_lock_is_taken_over() -> Result<()> {
+        let temp = TempDir::new()?;
+        let locks = FileLocks::for_workspace(temp.path());
+        fs::create_dir_all(locks.dir())?;
+        write_record(
+            &locks.lock_path("a.txt"),
+            &LockRecord {
+                path: "a.txt".to_string(),
+                session: "gone".to_string(),
+                pid: std::process::id(),
+                acquired_at: 1,
+                refreshed_at: 1,
+                contested_by: Vec::new(),
+            },
+        )?;
+        assert_eq!(locks.claim("a.txt")?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,303 @@
+use crate::util::parse_bool_str;
+use anyhow::{bail, Result};
+
+pub const FUZZY_EDIT_ENV: &str = "VEX_FUZZY_EDIT";
+
+/// edit_file falls back to whitespace-tolerant matching only when
+/// `VEX_FUZZY_EDIT` is on, since a loose match can land on the wrong lines.
+pub fn fuzzy_edit_enabled_from_env() -> bool {
+    std::env::var(FUZZY_EDIT_ENV)
+        .ok()
+        .and_then(|value| parse_bool_str(&value))
+        .unwrap_or(false)
+}
+
+/// How far old_str had to be normalized to match, from strictest to loosest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Normalization {
+    TrailingWhitespace,
+    Indentation,
+    InnerWhitespace,
+}
+
+impl Normalization {
+    const ALL: [Self; 3] = [
+        Self::TrailingWhitespace,
+        Self::Indentation,
+        Self::InnerWhitespace,
+    ];
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::TrailingWhitespace => "ignoring trailing whitespace",
+            Self::Indentation => "ignoring indentation",
+            Self::InnerWhitespace => "ignoring all whitespace differences",
+        }
+    }
+
+    fn line_key(self, line: &str) -> String {
+        match self {
+            Self::TrailingWhitespace => line.trim_end().to_string(),
+            Self::Indentation => line.trim().to_string(),
+            Self::InnerWhitespace => line.split_whitespace().collect::<Vec<_>>().join(" "),
+        }
+    }
+}
+
+/// What a tolerant edit changed, so the tool result shows the edit as
+/// applied rather than as requested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyEdit {
+    /// The file text old_str matched.
+    pub matched: String,
+    /// What replaced it: new_str, re-indented when the indentation differed.
+    pub replacement: String,
+    /// What was normalized to find the match.
+    pub report: String,
+}
+
+/// Where a tolerant match of old_str landed and the text to put there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct TolerantMatch {
+    /// Byte range of the matched lines in the file.
+    pub(super) start: usize,
+    pub(super) end: usize,
+    /// new_str, re-indented to the file's indentation when that differed.
+    pub(super) replacement: String,
+    /// What was normalized, for the tool result.
+    pub(super) report: String,
+}
+
+/// Finds the one run of whole lines in `content` that equals `old_str` once
+/// whitespace is normalized, trying the strictest normalization first, and
+/// failing that the one place `old_str` occurs as a substring with
+/// whitespace runs collapsed. `None` when nothing matches; an error when the
+/// best level matches more than one place, since picking either would hide
+/// the mismatch.
+pub(super) fn find_tolerant(
+    content: &str,
+    old_str: &str,
+    new_str: &str,
+) -> Result<Option<TolerantMatch>> {
+    let wanted: Vec<&str> = old_str.trim_end_matches('\n').split('\n').collect();
+    let mut offsets = Vec::new();
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        offsets.push(offset);
+        lines.push(line.trim_end_matches('\n'));
+        offset += line.len();
+    }
+    if wanted.len() > lines.len() {
+        return find_anchored(content, old_str, new_str);
+    }
+
+    for normalization in Normalization::ALL {
+        let keys: Vec<String> = wanted
+            .iter()
+            .map(|line| normalization.line_key(line))
+            .collect();
+        let starts: Vec<usize> = (0..=lines.len() - wanted.len())
+            .filter(|&first| {
+                keys.iter()
+                    .zip(&lines[first..])
+                    .all(|(key, line)| *key == normalization.line_key(line))
+            })
+            .collect();
+        match starts.as_slice() {
+            [] => continue,
+            [first] => {
+                let first = *first;
+                let last = first + wanted.len() - 1;
+                let mut end = offsets[last] + lines[last].len();
+                if old_str.ends_with('\n') && end < content.len() {
+                    end += 1;
+                }
+                let (replacement, reindented) = if normalization == Normalization::TrailingWhitespace
+                {
+                    (new_str.to_string(), false)
+                } else {
+                    reindent(new_str, &wanted, &lines[first..=last])
+                };
+                let lines = if first == last {
+                    format!("line {}", first + 1)
+                } else {
+                    format!("lines {}-{}", first + 1, last + 1)
+                };
+                let mut report = format!(
+                    "[fuzzy match: old_str matched {lines} only after {}",
+                    normalization.describe()
+                );
+                if reindented {
+                    report.push_str("; new_str re-indented to match the file");
+                }
+                report.push(']');
+                return Ok(Some(TolerantMatch {
+                    start: offsets[first],
+                    end,
+                    replacement,
+                    report,
+                }));
+            }
+            many => bail!(
+                "String '{old_str}' not found exactly, and matches {} places after {}; include more surrounding lines",
+                many.len(),
+                normalization.describe()
+            ),
+        }
+    }
+    find_anchored(content, old_str, new_str)
+}
+
+/// Matches `old_str` anywhere in `content`, not just on whole lines, with
+/// every whitespace run in both read as one space. Only a unique occurrence
+/// counts; `new_str` goes in as written.
+fn find_anchored(content: &str, old_str: &str, new_str: &str) -> Result<Option<TolerantMatch>> {
+    let (wanted, _) = collapse_whitespace(old_str.trim());
+    if wanted.is_empty() {
+        return Ok(None);
+    }
+    let (collapsed, spans) = collapse_whitespace(content);
+    let starts: Vec<usize> = collapsed
+        .match_indices(&wanted)
+        .map(|(start, _)| start)
+        .collect();
+    match starts.as_slice() {
+        [] => Ok(None),
+        [first] => {
+            let start = spans[*first].0;
+            let end = spans[first + wanted.len() - 1].1;
+            let first_line = content[..start].matches('\n').count() + 1;
+            let last_line = first_line + content[start..end].matches('\n').count();
+            let lines = if first_line == last_line {
+                format!("line {first_line}")
+            } else {
+                format!("lines {first_line}-{last_line}")
+            };
+            Ok(Some(TolerantMatch {
+                start,
+                end,
+                replacement: new_str.to_string(),
+                report: format!(
+                    "[fuzzy match: old_str matched part of {lines} as its only occurrence after collapsing whitespace]"
+                ),
+            }))
+        }
+        many => bail!(
+            "String '{old_str}' not found exactly, and occurs {} times after collapsing whitespace; include more surrounding text",
+            many.len()
+        ),
+    }
+}
+
+/// `text` with each whitespace run replaced by one space, and for each byte
+/// of the result the byte range of `text` it came from.
+fn collapse_whitespace(text: &str) -> (String, Vec<(usize, usize)>) {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut spans: Vec<(usize, usize)> = Vec::with_capacity(text.len());
+    for (offset, c) in text.char_indices() {
+        let end = offset + c.len_utf8();
+        if c.is_whitespace() {
+            match spans.last_mut() {
+                Some(span) if collapsed.ends_with(' ') => span.1 = end,
+                _ => {
+                    collapsed.push(' ');
+                    spans.push((offset, end));
+                }
+            }
+        } else {
+            collapsed.push(c);
+            spans.extend((offset..end).map(|_| (offset, end)));
+        }
+    }
+    (collapsed, spans)
+}
+
+/// `new_str` with old_str's indentation swapped for the file's, taken from
+/// the first non-blank line of each.
+fn reindent(new_str: &str, wanted: &[&str], matched: &[&str]) -> (String, bool) {
+    let Some((from, to)) = wanted
+        .iter()
+        .zip(matched)
+        .find(|(line, _)| !line.trim().is_empty())
+        .map(|(wanted, matched)| (indentation(wanted), indentation(matched)))
+    else {
+        return (new_str.to_string(), false);
+    };
+    if from == to {
+        return (new_str.to_string(), false);
+    }
+    let replacement = new_str
+        .split('\n')
+        .map(|line| match line.strip_prefix(from) {
+            Some(rest) if !line.trim().is_empty() => format!("{to}{rest}"),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    (replacement, true)
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tolerant_match_reindents_and_reports() {
+        let content = "fn main() {\n    let x = 1;   \n    println!(\"{x}\");\n}\n";
+
+        let partial = find_tolerant(content, "let x = 1;\n  println", "let x = 2;\n    println")
+            .expect("match")
+            .expect("anchored");
+        assert_eq!(
+            &content[partial.start..partial.end],
+            "let x = 1;   \n    println"
+        );
+        assert_eq!(partial.replacement, "let x = 2;\n    println");
+        assert_eq!(
+            partial.report,
+            "[fuzzy match: old_str matched part of lines 2-3 as its only occurrence after collapsing whitespace]"
+        );
+
+        let found = find_tolerant(
+            content,
+            "  let x = 1;\n  println!(\"{x}\");",
+            "  let x = 2;\n  println!(\"{x}\");",
+        )
+        .expect("match")
+        .expect("found");
+        assert_eq!(
+            &content[found.start..found.end],
+            "    let x = 1;   \n    println!(\"{x}\");"
+        );
+        assert_eq!(found.replacement, "    let x = 2;\n    println!(\"{x}\");");
+        assert_eq!(
+            found.report,
+            "[fuzzy match: old_str matched lines 2-3 only after ignoring indentation; new_str re-indented to match the file]"
+        );
+
+        let found = find_tolerant(content, "    let x = 1;\n", "    let x = 3;\n")
+            .expect("match")
+            .expect("found");
+        assert_eq!(&content[found.start..found.end], "    let x = 1;   \n");
+        assert_eq!(found.replacement, "    let x = 3;\n");
+        assert!(found.report.contains("ignoring trailing whitespace"));
+
+        let found = find_tolerant(content, "println!( \"{x}\");", "").expect("match");
+        assert!(found.is_none(), "whitespace is not inserted between tokens");
+        let found = find_tolerant(content, "let  x = 1;", "")
+            .expect("match")
+            .expect("found");
+        assert!(found.report.contains("ignoring all whitespace differences"));
+
+        let error = find_tolerant("  a\n  b\n  a\n", "a", "c").expect_err("ambiguous");
+        assert!(error.to_string().contains("matches 2 places"));
+        let error = find_tolerant("x = f(a,  b); y = f(a, b);\n", "f(a,\tb)", "g()")
+            .expect_err("ambiguous substring");
+        assert!(error.to_string().contains("occurs 2 times"));
+    }
+}
@@ -0,0 +1,323 @@
+use crate::state_dirs::StateDirs;
+use crate::util::unix_now;
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const MAX_MEMORY_CHARS: usize = 2_000;
+/// How long a write waits for another vex instance holding the database.
+const MEMORY_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    pub id: u64,
+    pub text: String,
+    pub created_at: u64,
+}
+
+impl MemoryEntry {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get::<_, i64>(0)? as u64,
+            text: row.get(1)?,
+            created_at: row.get::<_, i64>(2)? as u64,
+        })
+    }
+}
+
+/// Per-project memory store, a SQLite database at `.aistar/memory.db`.
+/// Forgotten ids are never handed out again.
+#[derive(Debug, Clone)]
+pub struct MemoryStore {
+    path: PathBuf,
+    legacy_path: PathBuf,
+}
+
+impl MemoryStore {
+    pub fn for_workspace(working_dir: &Path) -> Self {
+        let dirs = StateDirs::for_workspace(working_dir);
+        Self {
+            path: dirs.memory_db(),
+            legacy_path: dirs.legacy_memory_file(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn load(&self) -> Result<Vec<MemoryEntry>> {
+        let Some(connection) = self.open_existing()? else {
+            return Ok(Vec::new());
+        };
+        let mut statement =
+            connection.prepare("SELECT id, text, created_at FROM memories ORDER BY id")?;
+        let entries = statement
+            .query_map([], MemoryEntry::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read memories")?;
+        Ok(entries)
+    }
+
+    pub fn remember(&self, text: &str) -> Result<MemoryEntry> {
+        let text = text.trim();
+        if text.is_empty() {
+            bail!("remember requires a non-empty 'text' string argument");
+        }
+        if text.chars().count() > MAX_MEMORY_CHARS {
+            bail!("remember text is too long (max {MAX_MEMORY_CHARS} chars); store a short fact");
+        }
+
+        let connection = self.open()?;
+        let find = |connection: &Connection| {
+            connection
+                .query_row(
+                    "SELECT id, text, created_at FROM memories WHERE text = ?1",
+                    params![text],
+                    MemoryEntry::from_row,
+                )
+                .optional()
+                .context("Failed to read memory")
+        };
+        // A fact stored twice keeps its first id.
+        if let Some(duplicate) = find(&connection)? {
+            return Ok(duplicate);
+        }
+        connection
+            .execute(
+                "INSERT INTO memories (text, created_at) VALUES (?1, ?2)",
+                params![text, unix_now() as i64],
+            )
+            .context("Failed to write memory")?;
+        find(&connection)?.context("Failed to read memory back")
+    }
+
+    /// Deletes memory `id`, returning it, or `None` when no such memory exists.
+    pub fn forget(&self, id: u64) -> Result<Option<MemoryEntry>> {
+        let Some(connection) = self.open_existing()? else {
+            return Ok(None);
+        };
+        let entry = connection
+            .query_row(
+                "SELECT id, text, created_at FROM memories WHERE id = ?1",
+                params![id as i64],
+                MemoryEntry::from_row,
+            )
+            .optional()
+            .context("Failed to read memory")?;
+        if entry.is_some() {
+            connection
+                .execute("DELETE FROM memories WHERE id = ?1", params![id as i64])
+                .context("Failed to delete memory")?;
+        }
+        Ok(entry)
+    }
+
+    /// Returns up to `limit` memories sharing the most terms with `query`,
+    /// newest first on ties. Memories with no overlapping terms are skipped.
+    pub fn recall(&self, query: &str, limit: usize) -> Result<Vec<MemoryEntry>> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let query_terms = memory_terms(query);
+        if query_terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut scored: Vec<(usize, MemoryEntry)> = self
+            .load()?
+            .into_iter()
+            .filter_map(|entry| {
+                let score = memory_terms(&entry.text).intersection(&query_terms).count();
+                (score > 0).then_some((score, entry))
+            })
+            .collect();
+        scored.sort_by(|(left_score, left), (right_score, right)| {
+            right_score
+                .cmp(left_score)
+                .then_with(|| right.id.cmp(&left.id))
+        });
+        Ok(scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, entry)| entry)
+            .collect())
+    }
+
+    /// Opens the database, or `None` when nothing was ever remembered, so
+    /// reads do not create `.aistar/`.
+    fn open_existing(&self) -> Result<Option<Connection>> {
+        if self.path.exists() || self.legacy_path.exists() {
+            self.open().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn open(&self) -> Result<Connection> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let connection = Connection::open(&self.path)
+            .with_context(|| format!("Failed to open memory store {}", self.path.display()))?;
+        connection.busy_timeout(MEMORY_BUSY_TIMEOUT)?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS memories (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    text TEXT NOT NULL UNIQUE,
+                    created_at INTEGER NOT NULL
+                )",
+            )
+            .with_context(|| format!("Failed to set up memory store {}", self.path.display()))?;
+        self.import_legacy(&connection)?;
+        Ok(connection)
+    }
+
+    /// Moves memories from the old `memory.jsonl` into the database, keeping
+    /// their ids, then removes the file. Malformed lines are dropped.
+    fn import_legacy(&self, connection: &Connection) -> Result<()> {
+        if !self.legacy_path.exists() {
+            return Ok(());
+        }
+        let content = fs::read_to_string(&self.legacy_path).with_context(|| {
+            format!("Failed to read memory store {}", self.legacy_path.display())
+        })?;
+        let entries = content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<MemoryEntry>(line).ok());
+        let transaction = connection.unchecked_transaction()?;
+        for entry in entries {
+            transaction.execute(
+                "INSERT OR IGNORE INTO memories (id, text, created_at) VALUES (?1, ?2, ?3)",
+                params![entry.id as i64, entry.text, entry.created_at as i64],
+            )?;
+        }
+        transaction
+            .commit()
+            .context("Failed to import legacy memories")?;
+        fs::remove_file(&self.legacy_path)
+            .with_context(|| format!("Failed to remove {}", self.legacy_path.display()))
+    }
+}
+
+pub fn render_memory_entries(entries: &[MemoryEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| format!("- [#{}] {}", entry.id, entry.text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn memory_terms(text: &str) -> BTreeSet<String> {
+    text.split(|ch: char| !ch.is_alphanumeric() && ch != '_')
+        .filter(|term| term.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_remember_appends_and_deduplicates() {
+        let temp = TempDir::new().expect("temp dir");
+        let store = MemoryStore::for_workspace(temp.path());
+
+        let first = store
+            .remember("Run cargo fmt before commits")
+            .expect("remember");
+        let again = store
+            .remember("Run cargo fmt before commits")
+            .expect("remember");
+        let second = store.remember("Migrations live in db/").expect("remember");
+
+        assert_eq!(first.id, 1);
+        assert_eq!(again, first);
+        assert_eq!(second.id, 2);
+        assert_eq!(store.load().expect("load").len(), 2);
+        assert!(store.path().ends_with(".aistar/memory.db"));
+    }
+
+    #[test]
+    fn test_recall_ranks_by_term_overlap() {
+        let temp = TempDir::new().expect("temp dir");
+        let store = MemoryStore::for_workspace(temp.path());
+        store
+            .remember("Integration tests need the postgres container")
+            .expect("remember");
+        store
+            .remember("Prefer anyhow for errors")
+            .expect("remember");
+        store
+            .remember("Postgres migrations are append-only")
+            .expect("remember");
+
+        let recalled = store
+            .recall("why do postgres migrations fail?", 2)
+            .expect("recall");
+        assert_eq!(recalled.len(), 2);
+        assert_eq!(recalled[0].text, "Postgres migrations are append-only");
+        assert!(store
+            .recall("unrelated words", 3)
+            .expect("recall")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_forget_deletes_and_never_reuses_the_id() {
+        let temp = TempDir::new().expect("temp dir");
+        let store = MemoryStore::for_workspace(temp.path());
+        assert_eq!(store.forget(1).expect("forget"), None);
+
+        let first = store.remember("keep me").expect("remember");
+        let second = store.remember("drop me").expect("remember");
+        assert_eq!(
+            store.forget(second.id).expect("forget"),
+            Some(second.clone())
+        );
+        assert_eq!(store.forget(second.id).expect("forget again"), None);
+
+        let third = store.remember("added later").expect("remember");
+        assert_eq!(third.id, 3);
+        let ids: Vec<u64> = store
+            .load()
+            .expect("load")
+            .iter()
+            .map(|entry| entry.id)
+            .collect();
+        assert_eq!(ids, vec![first.id, third.id]);
+    }
+
+    #[test]
+    fn test_legacy_jsonl_memories_are_imported_once() {
+        let temp = TempDir::new().expect("temp dir");
+        let dirs = StateDirs::for_workspace(temp.path());
+        fs::create_dir_all(dirs.root()).expect("state dir");
+        fs::write(
+            dirs.legacy_memory_file(),
+            "{\"id\":4,\"text\":\"keep me\",\"created_at\":7}\nnot json\n",
+        )
+        .expect("legacy store");
+        let store = MemoryStore::for_workspace(temp.path());
+
+        let entries = store.load().expect("load");
+        assert_eq!(
+            entries,
+            vec![MemoryEntry {
+                id: 4,
+                text: "keep me".to_string(),
+                created_at: 7,
+            }]
+        );
+        assert!(!dirs.legacy_memory_file().exists());
+        assert_eq!(store.remember("next").expect("remember").id, 5);
+    }
+}
@@ -1,28 +1,230 @@
+use super::commit_message::{draft_commit_message, parse_changed_files, ChangedFile, CommitSource};
+use super::custom::CustomToolSet;
+use super::file_locks::{FileLockTurn, FileLocks};
+use super::fuzzy_edit::{find_tolerant, FuzzyEdit};
+use super::memory::{render_memory_entries, MemoryStore};
+use super::patch_output::PatchOutput;
+use super::process_group::spawn_tracked;
+use super::repo_map::RepoMap;
+use super::result_refs::ToolResultStore;
+use super::scratch::ScratchSpace;
+use super::snapshots::FileSnapshots;
+use super::symbol_context::edit_symbol_context;
+use super::tail::{TailCursors, MAX_TAIL_BYTES, MAX_TAIL_LINES};
+use super::test_runner::{
+    detect_test_command, resolve_test_timeout, run_test_command, TestCommand, TEST_COMMAND_ENV,
+};
+use super::tree::{render_tree, MAX_TREE_DEPTH, MAX_TREE_ENTRIES};
+use super::workspace_stats::render_workspace_stats;
+use crate::edit_diff::DiffContext;
 use aho_corasick::AhoCorasickBuilder;
 use anyhow::{bail, Context, Result};
 use std::fs;
 use std::path::{Component, Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 const MAX_EDIT_SNIPPET_CHARS: usize = 2_000;
 const MAX_EDIT_SNIPPET_LINES: usize = 80;
+/// Cached workspace_stats output is reused for this long unless a file tool
+/// changes the tree first.
+const WORKSPACE_STATS_CACHE_TTL: Duration = Duration::from_secs(60);
 
 #[derive(Clone)]
 pub struct ToolOperator {
     working_dir: PathBuf,
     canonical_working_dir: PathBuf,
+    custom_tools: Arc<CustomToolSet>,
+    patch_output: Option<Arc<PatchOutput>>,
+    symbol_context: bool,
+    fuzzy_edit: bool,
+    snapshots: Option<Arc<FileSnapshots>>,
+    file_locks: Option<Arc<FileLocks>>,
+    diff_context: DiffContext,
+    workspace_stats_cache: Arc<Mutex<Option<(Instant, String)>>>,
+    /// Built by the first `repo_map` call, then refreshed per file as file
+    /// tools change the tree.
+    repo_map: Arc<Mutex<Option<RepoMap>>>,
+    result_store: Option<Arc<Mutex<ToolResultStore>>>,
+    tail_cursors: Arc<TailCursors>,
+    scratch: Arc<ScratchSpace>,
+    /// The last `suggest_commit_message` draft, which a `git_commit` call
+    /// without a message commits.
+    commit_suggestion: Arc<Mutex<Option<String>>>,
 }
 
 impl ToolOperator {
     pub fn new(working_dir: PathBuf) -> Self {
         let canonical_working_dir =
             fs::canonicalize(&working_dir).unwrap_or_else(|_| working_dir.clone());
+        let custom_tools = Arc::new(CustomToolSet::load(&working_dir));
+        let scratch = Arc::new(ScratchSpace::for_workspace(&working_dir));
         Self {
             working_dir,
             canonical_working_dir,
+            custom_tools,
+            patch_output: None,
+            symbol_context: false,
+            fuzzy_edit: false,
+            snapshots: None,
+            file_locks: None,
+            diff_context: DiffContext::default(),
+            workspace_stats_cache: Arc::new(Mutex::new(None)),
+            repo_map: Arc::default(),
+            result_store: None,
+            tail_cursors: Arc::default(),
+            scratch,
+            commit_suggestion: Arc::default(),
         }
     }
 
+    /// Routes write_file/edit_file/rename_file into `.patch` files under
+    /// `dir` instead of modifying the working tree.
+    pub fn with_patch_output(mut self, dir: Option<PathBuf>) -> Self {
+        self.patch_output = dir.map(|dir| Arc::new(PatchOutput::new(dir)));
+        self
+    }
+
+    pub fn patch_output(&self) -> Option<&PatchOutput> {
+        self.patch_output.as_deref()
+    }
+
+    /// Appends callers and callees of edited functions to edit_file results.
+    pub fn with_symbol_context(mut self, enabled: bool) -> Self {
+        self.symbol_context = enabled;
+        self
+    }
+
+    /// Lets edit_file match old_str with whitespace differences when no
+    /// exact match exists.
+    pub fn with_fuzzy_edit(mut self, enabled: bool) -> Self {
+        self.fuzzy_edit = enabled;
+        self
+    }
+
+    /// Records an undo snapshot before every file change, for workspaces
+    /// without version control.
+    pub fn with_undo_snapshots(mut self, enabled: bool) -> Self {
+        self.snapshots = enabled.then(|| Arc::new(FileSnapshots::for_workspace(&self.working_dir)));
+        self
+    }
+
+    fn snapshot_write(&self, tool: &str, resolved: &Path) -> Result<()> {
+        match &self.snapshots {
+            Some(snapshots) => snapshots
+                .record_write(tool, &self.patch_key(resolved))
+                .context("Failed to record undo snapshot"),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes advisory lock markers for files this session changes, so
+    /// another vex session editing the same file is warned.
+    pub fn with_file_locks(mut self, enabled: bool) -> Self {
+        self.file_locks = enabled.then(|| Arc::new(FileLocks::for_workspace(&self.working_dir)));
+        self
+    }
+
+    /// Claims `path` before a write, edit, or rename. Returns a warning for
+    /// the tool result when another session holds it or the lock could not
+    /// be written.
+    pub fn claim_file_lock(&self, path: &str) -> Option<String> {
+        let locks = self.file_locks.as_ref()?;
+        if self.patch_output.is_some() {
+            return None;
+        }
+        let key = self.patch_key(&self.resolve_path(path).ok()?);
+        locks
+            .claim(&key)
+            .unwrap_or_else(|error| Some(format!("[file lock] failed to lock {key}: {error:#}")))
+    }
+
+    /// Warnings for files this session holds that another session changed.
+    pub fn take_lock_contentions(&self) -> Vec<String> {
+        self.file_locks
+            .as_ref()
+            .map(|locks| locks.take_contentions())
+            .unwrap_or_default()
+    }
+
+    /// Keeps locks claimed during a turn until the returned guard drops.
+    pub fn file_lock_turn(&self) -> FileLockTurn {
+        FileLockTurn::new(self.file_locks.clone())
+    }
+
+    /// Replaces tool results identical to an earlier one with a reference
+    /// the model can expand with expand_tool_result.
+    pub fn with_result_dedup(mut self, enabled: bool) -> Self {
+        self.result_store = enabled.then(|| Arc::new(Mutex::new(ToolResultStore::default())));
+        self
+    }
+
+    /// History text for a successful `tool` result: a short reference when
+    /// the same content was already recorded, otherwise `None`.
+    pub fn reference_duplicate_result(
+        &self,
+        tool: &str,
+        tool_use_id: &str,
+        content: &str,
+    ) -> Option<String> {
+        self.result_store
+            .as_ref()?
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .reference_duplicate(tool, tool_use_id, content)
+    }
+
+    /// Forgets stored results of `tool_use_ids`, whose content left the
+    /// history.
+    pub fn forget_results(&self, tool_use_ids: &[String]) {
+        if tool_use_ids.is_empty() {
+            return;
+        }
+        if let Some(store) = &self.result_store {
+            store
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .forget(tool_use_ids);
+        }
+    }
+
+    pub fn expand_tool_result(&self, key: &str) -> Result<String> {
+        let key = non_empty_trimmed(key)
+            .context("expand_tool_result requires a non-empty 'hash' field")?;
+        let Some(store) = &self.result_store else {
+            bail!("Duplicate tool result references are disabled");
+        };
+        store
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .expand(key)
+    }
+
+    /// Context lines for edit diffs; clones of `context` share overrides.
+    pub fn with_diff_context(mut self, context: DiffContext) -> Self {
+        self.diff_context = context;
+        self
+    }
+
+    pub fn diff_context(&self) -> &DiffContext {
+        &self.diff_context
+    }
+
+    /// Caller/callee context for a completed edit that inserted `new_str`
+    /// into `path`; `None` when disabled or no function was touched.
+    pub fn edit_symbol_context(&self, path: &str, new_str: &str) -> Option<String> {
+        if !self.symbol_context || new_str.trim().is_empty() {
+            return None;
+        }
+        let resolved = self.resolve_path(path).ok()?;
+        let content = self.read_current(&resolved, path).ok()??;
+        let offset = content.find(new_str)?;
+        let start_line = content[..offset].matches('\n').count() + 1;
+        let end_line = start_line + new_str.trim_end_matches('\n').matches('\n').count();
+        edit_symbol_context(&self.working_dir, &resolved, &content, start_line, end_line)
+    }
+
     fn resolve_path(&self, path: &str) -> Result<PathBuf> {
         let path = path.trim();
         if path.is_empty() {
@@ -47,6 +249,33 @@ impl ToolOperator {
         Ok(normalized)
     }
 
+    /// Absolute, symlink-resolved form of a tool path argument. Paths that do
+    /// not exist yet resolve through their nearest existing ancestor; `None`
+    /// when the path is rejected by the workspace guard.
+    pub fn canonical_path(&self, path: &str) -> Option<PathBuf> {
+        let resolved = self.resolve_path(path).ok()?;
+        let ancestor = self.nearest_existing_ancestor(&resolved)?;
+        let canonical = fs::canonicalize(ancestor).ok()?;
+        match resolved.strip_prefix(ancestor) {
+            Ok(rest) if !rest.as_os_str().is_empty() => Some(canonical.join(rest)),
+            _ => Some(canonical),
+        }
+    }
+
+    /// [`Self::canonical_path`] relative to the workspace root, with `/`
+    /// separators, so a symlink is named by what it points at.
+    pub fn canonical_relative_path(&self, path: &str) -> Option<String> {
+        let canonical = self.canonical_path(path)?;
+        let relative = canonical.strip_prefix(&self.canonical_working_dir).ok()?;
+        Some(
+            relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+        )
+    }
+
     fn ensure_path_is_within_workspace(&self, path: &Path) -> Result<()> {
         let guard_path = if path.exists() {
             path.to_path_buf()
@@ -98,7 +327,69 @@ impl ToolOperator {
         if resolved.is_dir() {
             bail!("read_file expected a file path, got a directory: {path}");
         }
-        fs::read_to_string(resolved).context("Failed to read file")
+        self.read_current(&resolved, path)
+            .and_then(|content| content.context("File does not exist"))
+            .context("Failed to read file")
+    }
+
+    /// Lines `start_line..=end_line` (1-based) of `path`, headed by the range
+    /// and total line count. `end_line` defaults to, and is clamped at, the
+    /// last line.
+    pub fn read_file_lines(
+        &self,
+        path: &str,
+        start_line: usize,
+        end_line: Option<usize>,
+    ) -> Result<String> {
+        let content = self.read_file(path)?;
+        let total = content.lines().count();
+        if start_line == 0 || start_line > total {
+            bail!("start_line {start_line} is out of range: {path} has {total} lines");
+        }
+        let end_line = end_line.unwrap_or(total).min(total);
+        if end_line < start_line {
+            bail!("end_line {end_line} is before start_line {start_line}");
+        }
+        let body = content
+            .lines()
+            .skip(start_line - 1)
+            .take(end_line + 1 - start_line)
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(format!(
+            "[lines {start_line}-{end_line} of {total}]\n{body}"
+        ))
+    }
+
+    /// Reads `resolved`, seeing through pending patches in patch mode.
+    /// Returns `None` when the file does not exist.
+    fn read_current(&self, resolved: &Path, path: &str) -> Result<Option<String>> {
+        if let Some(pending) = self
+            .patch_output
+            .as_ref()
+            .and_then(|output| output.pending_content(&self.patch_key(resolved)))
+        {
+            return Ok(pending);
+        }
+        match fs::read_to_string(resolved) {
+            Ok(content) => Ok(Some(content)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error).with_context(|| format!("Failed to read {path}")),
+        }
+    }
+
+    /// The workspace-relative key of `path` and its current content,
+    /// `None` content when the file does not exist. `None` when `path`
+    /// falls outside the workspace or cannot be read.
+    pub fn file_state(&self, path: &str) -> Option<(String, Option<String>)> {
+        let resolved = self.resolve_path(path).ok()?;
+        let content = self.read_current(&resolved, path).ok()?;
+        Some((self.patch_key(&resolved), content))
+    }
+
+    fn patch_key(&self, resolved: &Path) -> String {
+        self.to_workspace_relative_display(resolved)
+            .replace(std::path::MAIN_SEPARATOR, "/")
     }
 
     pub fn write_file(&self, path: &str, content: &str) -> Result<()> {
@@ -106,18 +397,39 @@ impl ToolOperator {
         if resolved.is_dir() {
             bail!("write_file expected a file path, got a directory: {path}");
         }
+        if let Some(output) = &self.patch_output {
+            let old = self.read_current(&resolved, path)?;
+            output.emit_change(
+                "write_file",
+                &self.patch_key(&resolved),
+                old.as_deref(),
+                content,
+            )?;
+            return Ok(());
+        }
+        self.snapshot_write("write_file", &resolved)?;
         if let Some(parent) = resolved.parent() {
             fs::create_dir_all(parent)?;
         }
-        fs::write(resolved, content).context("Failed to write file")
+        self.invalidate_workspace_stats();
+        fs::write(&resolved, content).context("Failed to write file")?;
+        self.refresh_repo_map(&resolved);
+        Ok(())
     }
 
-    pub fn edit_file(&self, path: &str, old_str: &str, new_str: &str) -> Result<()> {
+    /// Replaces the one occurrence of `old_str`. With fuzzy edits on, a
+    /// snippet that only matches once whitespace is normalized is replaced
+    /// too, and the returned edit says what was matched, what replaced it,
+    /// and what was normalized.
+    pub fn edit_file(&self, path: &str, old_str: &str, new_str: &str) -> Result<Option<FuzzyEdit>> {
         let resolved = self.resolve_path(path)?;
         if resolved.is_dir() {
             bail!("edit_file expected a file path, got a directory: {path}");
         }
-        let content = fs::read_to_string(&resolved).context("Failed to read file for edit")?;
+        let content = self
+            .read_current(&resolved, path)
+            .and_then(|content| content.context("File does not exist"))
+            .context("Failed to read file for edit")?;
 
         if old_str.trim().is_empty() {
             bail!("edit_file requires a non-empty old_str");
@@ -140,25 +452,80 @@ impl ToolOperator {
         }
 
         let occurrences = content.matches(old_str).count();
-        if occurrences == 0 {
-            bail!("String '{}' not found in file", old_str);
-        }
-        if occurrences > 1 {
-            bail!(
+        let mut fuzzy = None;
+        let new_content = match occurrences {
+            1 => content.replacen(old_str, new_str, 1),
+            0 => {
+                let tolerant = if self.fuzzy_edit {
+                    find_tolerant(&content, old_str, new_str)?
+                } else {
+                    None
+                };
+                let Some(tolerant) = tolerant else {
+                    bail!("String '{}' not found in file", old_str);
+                };
+                let new_content = format!(
+                    "{}{}{}",
+                    &content[..tolerant.start],
+                    tolerant.replacement,
+                    &content[tolerant.end..]
+                );
+                fuzzy = Some(FuzzyEdit {
+                    matched: content[tolerant.start..tolerant.end].to_string(),
+                    replacement: tolerant.replacement,
+                    report: tolerant.report,
+                });
+                new_content
+            }
+            _ => bail!(
                 "String '{}' appears {} times; must be unique",
                 old_str,
                 occurrences
-            );
+            ),
+        };
+        if let Some(output) = &self.patch_output {
+            output.emit_change(
+                "edit_file",
+                &self.patch_key(&resolved),
+                Some(&content),
+                &new_content,
+            )?;
+            return Ok(fuzzy);
         }
-
-        let new_content = content.replacen(old_str, new_str, 1);
-        fs::write(resolved, new_content).context("Failed to edit file")
+        self.snapshot_write("edit_file", &resolved)?;
+        self.invalidate_workspace_stats();
+        fs::write(&resolved, new_content).context("Failed to edit file")?;
+        self.refresh_repo_map(&resolved);
+        Ok(fuzzy)
     }
 
     pub fn rename_file(&self, old_path: &str, new_path: &str) -> Result<String> {
         let from = self.resolve_path(old_path)?;
         let to = self.resolve_path(new_path)?;
 
+        if let Some(output) = &self.patch_output {
+            if from.is_dir() {
+                bail!("rename_file cannot rename directories in patch output mode");
+            }
+            let Some(content) = self.read_current(&from, old_path)? else {
+                bail!(
+                    "Failed to rename file: source '{}' does not exist",
+                    old_path
+                );
+            };
+            if from == to {
+                return Ok(format!("Source and target are the same: {old_path}"));
+            }
+            if self.read_current(&to, new_path)?.is_some() {
+                bail!("Failed to rename file: target '{new_path}' already exists");
+            }
+            let entry =
+                output.emit_rename(&self.patch_key(&from), &self.patch_key(&to), &content)?;
+            return Ok(format!(
+                "Wrote {} (rename {} -> {})",
+                entry.file, old_path, new_path
+            ));
+        }
         if !from.exists() {
             bail!(
                 "Failed to rename file: source '{}' does not exist",
@@ -169,10 +536,18 @@ impl ToolOperator {
             return Ok(format!("Source and target are the same: {old_path}"));
         }
 
+        if let Some(snapshots) = &self.snapshots {
+            snapshots
+                .record_rename(&self.patch_key(&from), &self.patch_key(&to))
+                .context("Failed to record undo snapshot")?;
+        }
         if let Some(parent) = to.parent() {
             fs::create_dir_all(parent).context("Failed to create destination directory")?;
         }
+        self.invalidate_workspace_stats();
         fs::rename(&from, &to).context("Failed to rename file")?;
+        self.refresh_repo_map(&from);
+        self.refresh_repo_map(&to);
         Ok(format!("Renamed {} -> {}", old_path, new_path))
     }
 
@@ -220,6 +595,50 @@ impl ToolOperator {
         }
     }
 
+    /// Box-drawn tree of `path` with file sizes and per-directory counts,
+    /// expanded `max_depth` levels and capped at `max_entries` lines.
+    pub fn tree(&self, path: Option<&str>, max_depth: usize, max_entries: usize) -> Result<String> {
+        let root = self.resolve_optional_path(path)?;
+        if root.is_file() {
+            return self.list_files(path, 1);
+        }
+        if !root.is_dir() {
+            bail!("Directory not found: {}", root.display());
+        }
+        let display = match self.to_workspace_relative_display(&root) {
+            relative if relative.is_empty() => ".".to_string(),
+            relative => relative,
+        };
+        Ok(render_tree(
+            &root,
+            &display,
+            max_depth.clamp(1, MAX_TREE_DEPTH),
+            max_entries.clamp(1, MAX_TREE_ENTRIES),
+        ))
+    }
+
+    /// Lines appended to `path` since tail_file last read it this session,
+    /// starting with its last lines on the first call or with `reset`.
+    pub fn tail_file(
+        &self,
+        path: &str,
+        max_lines: usize,
+        max_bytes: usize,
+        reset: bool,
+    ) -> Result<String> {
+        let resolved = self.resolve_path(path)?;
+        if resolved.is_dir() {
+            bail!("tail_file expected a file path, got a directory: {path}");
+        }
+        self.tail_cursors.read_new(
+            &resolved,
+            &self.to_workspace_relative_display(&resolved),
+            reset,
+            max_lines.clamp(1, MAX_TAIL_LINES),
+            max_bytes.clamp(1, MAX_TAIL_BYTES),
+        )
+    }
+
     pub fn search_files(
         &self,
         query: &str,
@@ -233,6 +652,69 @@ impl ToolOperator {
         self.search_literal(query, &root, max_results)
     }
 
+    /// Language breakdown, largest files, and test/bench locations, cached
+    /// until a file tool changes the workspace or the cache expires.
+    pub fn workspace_stats(&self) -> Result<String> {
+        if let Ok(cache) = self.workspace_stats_cache.lock() {
+            if let Some((computed_at, stats)) = cache.as_ref() {
+                if computed_at.elapsed() < WORKSPACE_STATS_CACHE_TTL {
+                    return Ok(stats.clone());
+                }
+            }
+        }
+        let stats = render_workspace_stats(&self.working_dir)?;
+        if let Ok(mut cache) = self.workspace_stats_cache.lock() {
+            *cache = Some((Instant::now(), stats.clone()));
+        }
+        Ok(stats)
+    }
+
+    fn invalidate_workspace_stats(&self) {
+        if let Ok(mut cache) = self.workspace_stats_cache.lock() {
+            *cache = None;
+        }
+    }
+
+    /// Top-level symbols of each source file under `path`. The first call
+    /// scans the workspace; later calls reuse the map, which file tools keep
+    /// current, unless `refresh` asks for a new scan after other changes.
+    pub fn repo_map(&self, path: Option<&str>, refresh: bool) -> Result<String> {
+        let prefix = match path.filter(|path| !path.trim().is_empty()) {
+            Some(path) => Some(self.patch_key(&self.resolve_path(path)?)),
+            None => None,
+        };
+        let mut map = self
+            .repo_map
+            .lock()
+            .map_err(|_| anyhow::anyhow!("repo map lock poisoned"))?;
+        if refresh || map.is_none() {
+            *map = Some(RepoMap::scan(&self.working_dir));
+        }
+        Ok(map
+            .as_ref()
+            .map(|map| map.render(prefix.as_deref()))
+            .unwrap_or_default())
+    }
+
+    /// Drops the repo map and workspace stats after changes no file tool
+    /// made (custom tools, `/undo`, hooks), so the next call rescans.
+    pub fn invalidate_workspace_caches(&self) {
+        if let Ok(mut map) = self.repo_map.lock() {
+            *map = None;
+        }
+        self.invalidate_workspace_stats();
+    }
+
+    /// Re-outlines just the file (or directory) a tool changed, once a map
+    /// has been built.
+    fn refresh_repo_map(&self, resolved: &Path) {
+        if let Ok(mut map) = self.repo_map.lock() {
+            if let Some(map) = map.as_mut() {
+                map.refresh(&self.working_dir, &self.patch_key(resolved));
+            }
+        }
+    }
+
     pub fn git_status(&self, short: bool, path: Option<&str>) -> Result<String> {
         let mut args = vec!["status".to_string()];
         if short {
@@ -278,20 +760,178 @@ impl ToolOperator {
     }
 
     pub fn git_add(&self, path: &str) -> Result<String> {
+        self.ensure_tree_writes_allowed("git_add")?;
         let pathspec = self.sanitize_git_pathspec(path)?;
         self.run_git(vec!["add".to_string(), "--".to_string(), pathspec])?;
         Ok(format!("Staged {path}"))
     }
 
     pub fn git_commit(&self, message: &str) -> Result<String> {
+        self.ensure_tree_writes_allowed("git_commit")?;
         let message = non_empty_trimmed(message)
             .context("git_commit requires a non-empty 'message' field")?;
-        self.run_git(vec![
+        let output = self.run_git(vec![
             "commit".to_string(),
             "-m".to_string(),
             message.to_string(),
             "--no-gpg-sign".to_string(),
-        ])
+        ])?;
+        if let Ok(mut suggestion) = self.commit_suggestion.lock() {
+            *suggestion = None;
+        }
+        Ok(output)
+    }
+
+    /// The draft from the last `suggest_commit_message` call not yet
+    /// committed.
+    pub fn suggested_commit_message(&self) -> Option<String> {
+        self.commit_suggestion
+            .lock()
+            .ok()
+            .and_then(|suggestion| suggestion.clone())
+    }
+
+    /// Drafts a conventional-commit message for the staged changes, or for
+    /// every uncommitted change when `source` is "session". Nothing is
+    /// committed; a later `git_commit` without a message commits the draft
+    /// once the user approves it.
+    pub fn suggest_commit_message(
+        &self,
+        source: Option<&str>,
+        intent: Option<&str>,
+    ) -> Result<String> {
+        let source = CommitSource::parse(source)?;
+        // Before the first commit there is no HEAD to diff against, and
+        // everything tracked is staged.
+        let range = match source {
+            CommitSource::Session if self.head_exists() => "HEAD",
+            _ => "--cached",
+        };
+        let diff = |format: &str| {
+            self.git_stdout(&[
+                "diff".to_string(),
+                range.to_string(),
+                format.to_string(),
+                "-M".to_string(),
+            ])
+        };
+        let mut files = parse_changed_files(&diff("--name-status")?, &diff("--numstat")?);
+        if source == CommitSource::Session {
+            let untracked = self.git_stdout(&[
+                "ls-files".to_string(),
+                "--others".to_string(),
+                "--exclude-standard".to_string(),
+            ])?;
+            for path in untracked.lines() {
+                let added = fs::read_to_string(self.working_dir.join(path))
+                    .map(|content| content.lines().count())
+                    .unwrap_or(0);
+                files.push(ChangedFile {
+                    status: 'A',
+                    path: path.to_string(),
+                    added,
+                    removed: 0,
+                });
+            }
+        }
+        if files.is_empty() {
+            match source {
+                CommitSource::Staged => bail!(
+                    "nothing is staged; stage changes with git_add, or use source \"session\" to describe every uncommitted change"
+                ),
+                CommitSource::Session => bail!("the working tree has no uncommitted changes"),
+            }
+        }
+        let message = draft_commit_message(&files, intent);
+        if let Ok(mut suggestion) = self.commit_suggestion.lock() {
+            *suggestion = Some(message.clone());
+        }
+        let next = match source {
+            CommitSource::Staged => "Call git_commit without a message to commit this draft; the user approves it in the commit prompt. Pass a message instead if the subject misses the point of the change.",
+            CommitSource::Session => "git_commit only commits staged changes, so stage these files with git_add first, then call git_commit without a message to commit this draft once the user approves.",
+        };
+        Ok(format!("Suggested commit message:\n\n{message}\n\n{next}"))
+    }
+
+    pub fn memory_store(&self) -> MemoryStore {
+        MemoryStore::for_workspace(&self.working_dir)
+    }
+
+    /// Id of this process's session directory under `.aistar/sessions/`.
+    pub fn session_id(&self) -> &str {
+        self.scratch.session_id()
+    }
+
+    /// Gives the next turn its own scratch directory.
+    pub fn begin_scratch_turn(&self) {
+        self.scratch.begin_turn();
+    }
+
+    pub fn scratch_write(&self, name: &str, content: &str) -> Result<String> {
+        self.scratch.write(name, content)
+    }
+
+    pub fn scratch_read(&self, name: &str) -> Result<String> {
+        self.scratch.read(name)
+    }
+
+    pub fn remember(&self, text: &str) -> Result<String> {
+        let entry = self.memory_store().remember(text)?;
+        Ok(format!("Remembered memory #{}: {}", entry.id, entry.text))
+    }
+
+    pub fn recall(&self, query: &str, limit: usize) -> Result<String> {
+        let query =
+            non_empty_trimmed(query).context("recall requires a non-empty 'query' field")?;
+        let entries = self.memory_store().recall(query, limit.clamp(1, 50))?;
+        if entries.is_empty() {
+            Ok("No matching memories.".to_string())
+        } else {
+            Ok(render_memory_entries(&entries))
+        }
+    }
+
+    /// Runs the configured (`VEX_TEST_COMMAND`) or auto-detected test command.
+    pub fn run_tests(&self, filter: Option<&str>) -> Result<String> {
+        let configured = std::env::var(TEST_COMMAND_ENV)
+            .ok()
+            .and_then(|command| TestCommand::from_configured(&command));
+        let command = configured
+            .or_else(|| detect_test_command(&self.working_dir))
+            .with_context(|| {
+                format!(
+                    "run_tests could not detect a test command (no Cargo.toml, package.json test script, or pytest config); set {TEST_COMMAND_ENV}"
+                )
+            })?;
+        run_test_command(
+            &command.with_filter(filter),
+            &self.working_dir,
+            resolve_test_timeout(),
+        )
+    }
+
+    pub fn custom_tools(&self) -> &CustomToolSet {
+        &self.custom_tools
+    }
+
+    /// Runs a project tool from `.aistar/tools/`, or returns `None` when no
+    /// custom tool has that name.
+    pub fn run_custom_tool(&self, name: &str, input: &serde_json::Value) -> Option<Result<String>> {
+        let tool = self.custom_tools.get(name)?;
+        let result = tool.execute(&self.working_dir, input);
+        // The command may have changed any file.
+        self.invalidate_workspace_caches();
+        Some(result)
+    }
+
+    fn ensure_tree_writes_allowed(&self, tool: &str) -> Result<()> {
+        if let Some(output) = &self.patch_output {
+            bail!(
+                "{tool} is disabled in patch output mode; changes are written as patches to {}",
+                output.dir().display()
+            );
+        }
+        Ok(())
     }
 
     fn sanitize_git_pathspec(&self, path: &str) -> Result<String> {
@@ -307,11 +947,38 @@ impl ToolOperator {
     }
 
     fn run_git(&self, args: Vec<String>) -> Result<String> {
-        let output = Command::new("git")
-            .current_dir(&self.working_dir)
-            .args(&args)
-            .output()
-            .context("Failed to execute git command")?;
+        let stdout = self.git_stdout(&args)?;
+        if stdout.is_empty() {
+            Ok("OK".to_string())
+        } else {
+            Ok(stdout)
+        }
+    }
+
+    /// Whether HEAD names a commit; false in a repository with no commits.
+    fn head_exists(&self) -> bool {
+        self.git_stdout(&[
+            "rev-parse".to_string(),
+            "--verify".to_string(),
+            "--quiet".to_string(),
+            "HEAD".to_string(),
+        ])
+        .is_ok()
+    }
+
+    /// Trimmed stdout of a successful git command, empty when it printed
+    /// nothing.
+    fn git_stdout(&self, args: &[String]) -> Result<String> {
+        let output = spawn_tracked(
+            Command::new("git")
+                .current_dir(&self.working_dir)
+                .args(args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
+        .and_then(|child| child.wait_with_output())
+        .context("Failed to execute git command")?;
 
         let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
         let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
@@ -320,12 +987,7 @@ impl ToolOperator {
             let details = if stderr.is_empty() { stdout } else { stderr };
             bail!("git {} failed: {}", args.join(" "), details);
         }
-
-        if stdout.is_empty() {
-            Ok("OK".to_string())
-        } else {
-            Ok(stdout)
-        }
+        Ok(stdout)
     }
 
     fn resolve_optional_path(&self, path: Option<&str>) -> Result<PathBuf> {
@@ -440,6 +1102,39 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_commit_suggestion_works_before_the_first_commit_and_feeds_git_commit() {
+        let temp = TempDir::new().expect("temp dir");
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .current_dir(temp.path())
+                .args(args)
+                .output()
+                .is_ok_and(|output| output.status.success())
+        };
+        if !git(&["init", "-q"]) {
+            return;
+        }
+        git(&["config", "user.email", "dev@example.com"]);
+        git(&["config", "user.name", "Dev"]);
+        fs::write(temp.path().join("notes.md"), "one\ntwo\n").expect("write");
+        let executor = ToolOperator::new(temp.path().to_path_buf());
+
+        let suggested = executor
+            .suggest_commit_message(Some("session"), Some("document the notes"))
+            .expect("session diff without HEAD");
+        assert!(
+            suggested.contains("docs(notes): document the notes"),
+            "{suggested}"
+        );
+        let draft = executor.suggested_commit_message().expect("draft kept");
+        assert!(draft.contains("- A notes.md (+2 -0)"), "{draft}");
+
+        executor.git_add("notes.md").expect("stage");
+        executor.git_commit(&draft).expect("commit");
+        assert_eq!(executor.suggested_commit_message(), None);
+    }
+
     #[test]
     fn test_empty_path_rejected() {
         let temp = TempDir::new().expect("temp dir");
@@ -451,6 +1146,24 @@ mod tests {
         assert!(err.to_string().contains("Path cannot be empty"));
     }
 
+    #[test]
+    fn test_read_file_lines_returns_clamped_range() {
+        let temp = TempDir::new().expect("temp dir");
+        fs::write(temp.path().join("notes.txt"), "one\ntwo\nthree\nfour\n").expect("seed");
+        let executor = ToolOperator::new(temp.path().to_path_buf());
+
+        assert_eq!(
+            executor.read_file_lines("notes.txt", 2, Some(3)).unwrap(),
+            "[lines 2-3 of 4]\ntwo\nthree"
+        );
+        assert_eq!(
+            executor.read_file_lines("notes.txt", 4, Some(99)).unwrap(),
+            "[lines 4-4 of 4]\nfour"
+        );
+        assert!(executor.read_file_lines("notes.txt", 5, None).is_err());
+        assert!(executor.read_file_lines("notes.txt", 3, Some(2)).is_err());
+    }
+
     #[test]
     fn test_edit_file_rejects_directory_target() {
         let temp = TempDir::new().expect("temp dir");
@@ -464,6 +1177,108 @@ mod tests {
             .contains("edit_file expected a file path, got a directory"));
     }
 
+    #[test]
+    fn test_workspace_stats_cache_refreshes_after_writes() {
+        let temp = TempDir::new().expect("temp dir");
+        fs::write(temp.path().join("main.py"), "print(1)\n").expect("write");
+        let executor = ToolOperator::new(temp.path().to_path_buf());
+        let before = executor.workspace_stats().expect("stats");
+        assert!(before.starts_with("Workspace: 1 files"), "{before}");
+
+        fs::write(temp.path().join("other.py"), "print(2)\n").expect("write");
+        assert_eq!(executor.workspace_stats().expect("cached stats"), before);
+
+        executor
+            .write_file("tests/test_main.py", "def test_x(): pass\n")
+            .expect("write via tool");
+        let after = executor.workspace_stats().expect("stats");
+        assert!(after.starts_with("Workspace: 3 files"), "{after}");
+        assert!(after.contains("Test locations:\n  tests/"), "{after}");
+    }
+
+    #[test]
+    fn test_custom_tool_runs_invalidate_the_repo_map() {
+        let temp = TempDir::new().expect("temp dir");
+        fs::write(temp.path().join("lib.rs"), "fn old() {}\n").expect("write");
+        let tools_dir = crate::state_dirs::StateDirs::for_workspace(temp.path()).tools_dir();
+        fs::create_dir_all(&tools_dir).expect("tools dir");
+        fs::write(
+            tools_dir.join("gen.toml"),
+            "name = \"gen\"\ncommand = \"printf 'fn generated() {}\\\\n' > gen.rs\"\n",
+        )
+        .expect("tool");
+        let executor = ToolOperator::new(temp.path().to_path_buf());
+        assert!(!executor
+            .repo_map(None, false)
+            .expect("map")
+            .contains("gen.rs"));
+
+        executor
+            .run_custom_tool("gen", &serde_json::json!({}))
+            .expect("custom tool")
+            .expect("ran");
+        let map = executor.repo_map(None, false).expect("map");
+        assert!(map.contains("gen.rs"), "{map}");
+        assert!(map.contains("fn generated()"), "{map}");
+    }
+
+    #[test]
+    fn test_fuzzy_edit_is_opt_in() {
+        let temp = TempDir::new().expect("temp dir");
+        fs::write(
+            temp.path().join("lib.py"),
+            "def total(items):\n    return sum(items)\n",
+        )
+        .expect("write");
+        let executor = ToolOperator::new(temp.path().to_path_buf());
+        let err = executor
+            .edit_file("lib.py", "\treturn sum(items)", "\treturn sum(items) + 1")
+            .expect_err("exact match required");
+        assert!(err.to_string().contains("not found in file"));
+
+        let executor = executor.with_fuzzy_edit(true);
+        let fuzzy = executor
+            .edit_file("lib.py", "\treturn sum(items)", "\treturn sum(items) + 1")
+            .expect("fuzzy edit")
+            .expect("fuzzy match");
+        assert_eq!(
+            fuzzy.report,
+            "[fuzzy match: old_str matched line 2 only after ignoring indentation; new_str re-indented to match the file]"
+        );
+        assert_eq!(fuzzy.matched, "    return sum(items)");
+        assert_eq!(fuzzy.replacement, "    return sum(items) + 1");
+        assert_eq!(
+            fs::read_to_string(temp.path().join("lib.py")).expect("read"),
+            "def total(items):\n    return sum(items) + 1\n"
+        );
+    }
+
+    #[test]
+    fn test_edit_symbol_context_is_opt_in() {
+        let temp = TempDir::new().expect("temp dir");
+        fs::write(
+            temp.path().join("lib.py"),
+            "def total(items):\n    return sum(items)\n\nprint(total([1]))\n",
+        )
+        .expect("write");
+        let executor = ToolOperator::new(temp.path().to_path_buf());
+        executor
+            .edit_file("lib.py", "return sum(items)", "return sum(items) + 1")
+            .expect("edit");
+        assert_eq!(
+            executor.edit_symbol_context("lib.py", "return sum(items) + 1"),
+            None
+        );
+
+        let executor = executor.with_symbol_context(true);
+        assert_eq!(
+            executor
+                .edit_symbol_context("lib.py", "return sum(items) + 1")
+                .as_deref(),
+            Some("Symbol context (VEX_EDIT_SYMBOL_CONTEXT):\n`total` callers:\n  lib.py:4: print(total([1]))")
+        );
+    }
+
     #[cfg(unix)]
     #[test]
     fn test_search_literal_skips_symlink_escape_paths() {
@@ -483,4 +1298,57 @@ mod tests {
             .expect("literal search should succeed");
         assert_eq!(result, "No matches found.");
     }
+
+    #[test]
+    fn test_patch_output_mode_leaves_tree_untouched_and_applies() {
+        let workspace = TempDir::new().expect("workspace");
+        let patches = TempDir::new().expect("patches");
+        fs::write(workspace.path().join("lib.rs"), "fn a() {}\nfn b() {}\n").expect("seed");
+        let executor = ToolOperator::new(workspace.path().to_path_buf())
+            .with_patch_output(Some(patches.path().to_path_buf()));
+
+        executor
+            .edit_file("lib.rs", "fn b() {}", "fn b() { a() }")
+            .expect("edit emits patch");
+        executor
+            .edit_file("lib.rs", "fn a() {}", "fn a() { todo!() }")
+            .expect("second edit builds on pending content");
+        executor
+            .write_file("src/new.rs", "pub fn new() {}")
+            .expect("write emits patch");
+        executor
+            .rename_file("src/new.rs", "src/made.rs")
+            .expect("rename emits patch");
+
+        assert_eq!(
+            fs::read_to_string(workspace.path().join("lib.rs")).unwrap(),
+            "fn a() {}\nfn b() {}\n"
+        );
+        assert!(!workspace.path().join("src").exists());
+        assert_eq!(
+            executor.read_file("lib.rs").unwrap(),
+            "fn a() { todo!() }\nfn b() { a() }\n"
+        );
+        assert!(executor.read_file("src/new.rs").is_err());
+        assert!(executor.git_commit("nope").is_err());
+
+        let entries = executor.patch_output().expect("patch mode").entries();
+        assert_eq!(entries.len(), 4);
+        let applies = Command::new("git")
+            .current_dir(workspace.path())
+            .arg("apply")
+            .args(entries.iter().map(|entry| patches.path().join(&entry.file)))
+            .status();
+        if let Ok(status) = applies {
+            assert!(status.success(), "patches should apply with git apply");
+            assert_eq!(
+                fs::read_to_string(workspace.path().join("lib.rs")).unwrap(),
+                "fn a() { todo!() }\nfn b() { a() }\n"
+            );
+            assert_eq!(
+                fs::read_to_string(workspace.path().join("src/made.rs")).unwrap(),
+                "pub fn new() {}"
+            );
+        }
+    }
 }
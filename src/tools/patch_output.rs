@@ -0,0 +1,224 @@
+use crate::edit_diff::format_unified_file_diff;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+const PATCH_CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchKind {
+    Create,
+    Modify,
+    Rename,
+}
+
+/// One emitted patch, as listed in `manifest.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PatchEntry {
+    pub seq: usize,
+    pub file: String,
+    pub tool: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub renamed_from: Option<String>,
+    pub kind: PatchKind,
+    pub added: usize,
+    pub removed: usize,
+}
+
+#[derive(Default)]
+struct PatchState {
+    /// Workspace-relative path -> content after all emitted patches; `None`
+    /// marks a path that was renamed away.
+    pending: HashMap<String, Option<String>>,
+    entries: Vec<PatchEntry>,
+}
+
+/// Collects write/edit/rename tool calls as numbered `.patch` files instead
+/// of touching the working tree. Later patches build on earlier ones, so the
+/// set applies cleanly with `git apply` in manifest order.
+pub struct PatchOutput {
+    dir: PathBuf,
+    state: Mutex<PatchState>,
+}
+
+impl PatchOutput {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            state: Mutex::new(PatchState::default()),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn entries(&self) -> Vec<PatchEntry> {
+        self.lock().entries.clone()
+    }
+
+    /// Content of `path` as the emitted patches leave it: `Some(Some(_))` for
+    /// a patched file, `Some(None)` for one renamed away, `None` when no
+    /// patch touched it.
+    pub fn pending_content(&self, path: &str) -> Option<Option<String>> {
+        self.lock().pending.get(path).cloned()
+    }
+
+    /// Records a content change. `old` is the file before the change, `None`
+    /// when the file is being created.
+    pub fn emit_change(
+        &self,
+        tool: &str,
+        path: &str,
+        old: Option<&str>,
+        new: &str,
+    ) -> Result<PatchEntry> {
+        let diff = format_unified_file_diff(path, old, Some(new), PATCH_CONTEXT_LINES);
+        let (added, removed) = count_changed_lines(&diff);
+        let kind = if old.is_some() {
+            PatchKind::Modify
+        } else {
+            PatchKind::Create
+        };
+        let mut state = self.lock();
+        let entry = self.write_patch(&mut state, tool, path, None, kind, &diff, added, removed)?;
+        state
+            .pending
+            .insert(path.to_string(), Some(new.to_string()));
+        Ok(entry)
+    }
+
+    pub fn emit_rename(&self, from: &str, to: &str, content: &str) -> Result<PatchEntry> {
+        let diff = format!(
+            "diff --git a/{from} b/{to}\nsimilarity index 100%\nrename from {from}\nrename to {to}\n"
+        );
+        let mut state = self.lock();
+        let entry = self.write_patch(
+            &mut state,
+            "rename_file",
+            to,
+            Some(from),
+            PatchKind::Rename,
+            &diff,
+            0,
+            0,
+        )?;
+        state.pending.insert(from.to_string(), None);
+        state
+            .pending
+            .insert(to.to_string(), Some(content.to_string()));
+        Ok(entry)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_patch(
+        &self,
+        state: &mut PatchState,
+        tool: &str,
+        path: &str,
+        renamed_from: Option<&str>,
+        kind: PatchKind,
+        diff: &str,
+        added: usize,
+        removed: usize,
+    ) -> Result<PatchEntry> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        let seq = state.entries.len() + 1;
+        let file = format!("{seq:04}-{tool}-{}.patch", sanitize_file_stem(path));
+        let patch_path = self.dir.join(&file);
+        fs::write(&patch_path, diff)
+            .with_context(|| format!("failed to write {}", patch_path.display()))?;
+
+        let entry = PatchEntry {
+            seq,
+            file,
+            tool: tool.to_string(),
+            path: path.to_string(),
+            renamed_from: renamed_from.map(str::to_string),
+            kind,
+            added,
+            removed,
+        };
+        state.entries.push(entry.clone());
+        let manifest_path = self.dir.join(MANIFEST_FILE_NAME);
+        fs::write(
+            &manifest_path,
+            serde_json::to_vec_pretty(&serde_json::json!({ "patches": state.entries }))?,
+        )
+        .with_context(|| format!("failed to write {}", manifest_path.display()))?;
+        Ok(entry)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PatchState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn sanitize_file_stem(path: &str) -> String {
+    let stem: String = path
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '.' || ch == '-' || ch == '_' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .take(80)
+        .collect();
+    stem.trim_matches('.').to_string()
+}
+
+fn count_changed_lines(diff: &str) -> (usize, usize) {
+    let body = diff.lines().skip_while(|line| !line.starts_with("@@"));
+    body.fold((0, 0), |(added, removed), line| {
+        match line.as_bytes().first() {
+            Some(b'+') => (added + 1, removed),
+            Some(b'-') => (added, removed + 1),
+            _ => (added, removed),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_patches_are_numbered_and_listed_in_manifest() -> Result<()> {
+        let temp = TempDir::new()?;
+        let output = PatchOutput::new(temp.path().join("patches"));
+        output.emit_change("write_file", "src/new.rs", None, "fn main() {}\n")?;
+        output.emit_change(
+            "edit_file",
+            "src/new.rs",
+            Some("fn main() {}\n"),
+            "fn main() {\n    run();\n}\n",
+        )?;
+        output.emit_rename("src/new.rs", "src/bin.rs", "fn main() {\n    run();\n}\n")?;
+
+        let entries = output.entries();
+        assert_eq!(entries[0].file, "0001-write_file-src_new.rs.patch");
+        assert_eq!(entries[1].kind, PatchKind::Modify);
+        assert_eq!((entries[1].added, entries[1].removed), (3, 1));
+        assert_eq!(entries[2].renamed_from.as_deref(), Some("src/new.rs"));
+        assert_eq!(output.pending_content("src/new.rs"), Some(None));
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(output.dir().join(MANIFEST_FILE_NAME))?)?;
+        assert_eq!(manifest["patches"].as_array().map(Vec::len), Some(3));
+        assert_eq!(manifest["patches"][2]["kind"], "rename");
+        assert!(output.dir().join(&entries[2].file).exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+pub const TOOL_CONCURRENCY_ENV: &str = "VEX_TOOL_CONCURRENCY";
+pub const DEFAULT_TOOL_CONCURRENCY: usize = 4;
+const MAX_TOOL_CONCURRENCY: usize = 64;
+
+/// Queue order for tool calls waiting on a slot; higher runs first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ToolPriority {
+    /// Workspace-wide scans and test runs.
+    Bulk,
+    Normal,
+    /// Cheap lookups the model is usually blocked on.
+    Interactive,
+}
+
+/// Cumulative counters for a [`ToolPool`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolPoolStats {
+    pub capacity: usize,
+    pub running: usize,
+    pub waiting: usize,
+    /// Tool calls that got a slot, queued or not.
+    pub started: u64,
+    /// Tool calls that had to wait for a slot.
+    pub queued: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl ToolPoolStats {
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "tool pool: {}/{} running, {} queued; {} started",
+            self.running, self.capacity, self.waiting, self.started
+        );
+        if self.queued > 0 {
+            let average = self.total_wait / self.queued as u32;
+            summary.push_str(&format!(
+                ", {} waited (avg {}ms, max {}ms)",
+                self.queued,
+                average.as_millis(),
+                self.max_wait.as_millis()
+            ));
+        }
+        summary
+    }
+}
+
+#[derive(Debug)]
+struct Waiter {
+    priority: ToolPriority,
+    sequence: u64,
+    slot_tx: oneshot::Sender<()>,
+}
+
+impl Ord for Waiter {
+    /// Highest priority first, then first come first served.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    waiters: BinaryHeap<Waiter>,
+    next_sequence: u64,
+    stats: ToolPoolStats,
+}
+
+/// Bounds how many tool calls run on the blocking thread pool at once.
+/// Calls past the limit queue by [`ToolPriority`]. A call that times out
+/// gives its slot back once the processes it started are killed.
+#[derive(Debug)]
+pub struct ToolPool {
+    state: Mutex<PoolState>,
+}
+
+impl ToolPool {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.clamp(1, MAX_TOOL_CONCURRENCY);
+        Self {
+            state: Mutex::new(PoolState {
+                stats: ToolPoolStats {
+                    capacity,
+                    ..ToolPoolStats::default()
+                },
+                ..PoolState::default()
+            }),
+        }
+    }
+
+    /// Capacity from `VEX_TOOL_CONCURRENCY`, default 4.
+    pub fn from_env() -> Self {
+        let capacity = std::env::var(TOOL_CONCURRENCY_ENV)
+            .ok()
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .unwrap_or(DEFAULT_TOOL_CONCURRENCY);
+        Self::new(capacity)
+    }
+
+    pub fn stats(&self) -> ToolPoolStats {
+        let state = self.lock();
+        ToolPoolStats {
+            waiting: state.waiters.len(),
+            ..state.stats
+        }
+    }
+
+    /// Waits for a free slot. Dropping the future gives up its place in
+    /// the queue.
+    pub async fn acquire(self: &Arc<Self>, priority: ToolPriority) -> ToolSlot {
+        let queued_at = Instant::now();
+        let slot_rx = {
+            let mut state = self.lock();
+            if state.stats.running < state.stats.capacity && state.waiters.is_empty() {
+                state.stats.running += 1;
+                state.stats.started += 1;
+                return ToolSlot {
+                    pool: Arc::clone(self),
+                    waited: Duration::ZERO,
+                };
+            }
+            let (slot_tx, slot_rx) = oneshot::channel();
+            let sequence = state.next_sequence;
+            state.next_sequence += 1;
+            state.waiters.push(Waiter {
+                priority,
+                sequence,
+                slot_tx,
+            });
+            slot_rx
+        };
+
+        let mut pending = PendingSlot {
+            pool: Arc::clone(self),
+            slot_rx: Some(slot_rx),
+        };
+        if let Some(slot_rx) = pending.slot_rx.as_mut() {
+            // The sender is only dropped after a failed hand-off, which
+            // needs this receiver to be gone.
+            let _ = slot_rx.await;
+        }
+        pending.slot_rx = None;
+
+        let waited = queued_at.elapsed();
+        let mut state = self.lock();
+        state.stats.started += 1;
+        state.stats.queued += 1;
+        state.stats.total_wait += waited;
+        state.stats.max_wait = state.stats.max_wait.max(waited);
+        ToolSlot {
+            pool: Arc::clone(self),
+            waited,
+        }
+    }
+
+    /// Hands the slot to the next waiter, or frees it.
+    fn release(&self) {
+        let mut state = self.lock();
+        while let Some(waiter) = state.waiters.pop() {
+            if waiter.slot_tx.send(()).is_ok() {
+                return;
+            }
+        }
+        state.stats.running -= 1;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for ToolPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_TOOL_CONCURRENCY)
+    }
+}
+
+/// A queued acquire; returns a slot handed over after it was dropped.
+struct PendingSlot {
+    pool: Arc<ToolPool>,
+    slot_rx: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for PendingSlot {
+    fn drop(&mut self) {
+        if let Some(mut slot_rx) = self.slot_rx.take() {
+            slot_rx.close();
+            if slot_rx.try_recv().is_ok() {
+                self.pool.release();
+            }
+        }
+    }
+}
+
+/// One running tool call's slot, released when dropped.
+#[derive(Debug)]
+pub struct ToolSlot {
+    pool: Arc<ToolPool>,
+    waited: Duration,
+}
+
+impl ToolSlot {
+    /// Time spent queued before the slot was granted.
+    pub fn waited(&self) -> Duration {
+        self.waited
+    }
+}
+
+impl Drop for ToolSlot {
+    fn drop(&mut self) {
+        self.pool.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queued_calls_run_by_priority_then_arrival() {
+        let pool = Arc::new(ToolPool::new(1));
+        let held = pool.acquire(ToolPriority::Normal).await;
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        for (label, priority) in [
+            ("search", ToolPriority::Bulk),
+            ("edit", ToolPriority::Normal),
+            ("read", ToolPriority::Interactive),
+            ("list", ToolPriority::Interactive),
+        ] {
+            let pool = Arc::clone(&pool);
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let _slot = pool.acquire(priority).await;
+                let _ = order_tx.send(label);
+            });
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(pool.stats().waiting, 4);
+
+        drop(held);
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            order.push(order_rx.recv().await.expect("every call runs"));
+        }
+        assert_eq!(order, vec!["read", "list", "edit", "search"]);
+        let stats = pool.stats();
+        assert_eq!((stats.running, stats.waiting), (0, 0));
+        assert_eq!((stats.started, stats.queued), (5, 4));
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_waiter_does_not_leak_its_slot() {
+        let pool = Arc::new(ToolPool::new(1));
+        let held = pool.acquire(ToolPriority::Normal).await;
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(20),
+            pool.acquire(ToolPriority::Interactive),
+        )
+        .await;
+        assert!(waiting.is_err());
+        assert_eq!(pool.stats().waiting, 1);
+
+        drop(held);
+        assert_eq!(pool.stats().running, 0);
+        let slot = pool.acquire(ToolPriority::Bulk).await;
+        assert_eq!(slot.waited(), Duration::ZERO);
+        assert_eq!(pool.stats().waiting, 0);
+    }
+}
@@ -0,0 +1,220 @@
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Makes `command` start as the leader of its own process group, so
+/// [`kill_process_group`] reaches everything it spawns, not just the shell.
+pub fn own_process_group(command: &mut tokio::process::Command) -> &mut tokio::process::Command {
+    #[cfg(unix)]
+    command.process_group(0);
+    command
+}
+
+/// [`own_process_group`] for a blocking `std` command.
+pub fn own_process_group_std(command: &mut std::process::Command) -> &mut std::process::Command {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    command
+}
+
+/// Sends SIGKILL to the process group led by `pid`. Errors are ignored: the
+/// group is usually gone already.
+#[cfg(unix)]
+pub fn kill_process_group(pid: u32) {
+    if let Some(pid) = i32::try_from(pid)
+        .ok()
+        .and_then(rustix::process::Pid::from_raw)
+    {
+        let _ = rustix::process::kill_process_group(pid, rustix::process::Signal::KILL);
+    }
+}
+
+/// Without process groups only the child itself can be killed, which its
+/// `kill_on_drop` already does.
+#[cfg(not(unix))]
+pub fn kill_process_group(_pid: u32) {}
+
+/// Kills a child's process group when dropped unless [`Self::disarm`] ran
+/// first, so a timed-out or cancelled run takes its descendants with it.
+#[derive(Debug)]
+pub struct ProcessGroupGuard {
+    pid: Option<u32>,
+}
+
+impl ProcessGroupGuard {
+    pub fn new(pid: Option<u32>) -> Self {
+        Self { pid }
+    }
+
+    /// Called once the child exited on its own; anything it left running in
+    /// the background is its business.
+    pub fn disarm(&mut self) {
+        self.pid = None;
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        if let Some(pid) = self.pid.take() {
+            kill_process_group(pid);
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT_CALL: RefCell<Option<CallProcesses>> = const { RefCell::new(None) };
+}
+
+/// The process groups one blocking tool call started, so the call can be
+/// killed from another thread when it times out or is cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CallProcesses {
+    state: Arc<Mutex<CallProcessState>>,
+}
+
+#[derive(Debug, Default)]
+struct CallProcessState {
+    groups: Vec<u32>,
+    killed: bool,
+}
+
+impl CallProcesses {
+    /// Runs `call` with [`spawn_tracked`] recording into `self`.
+    pub fn scope<T>(&self, call: impl FnOnce() -> T) -> T {
+        let previous = CURRENT_CALL.with(|current| current.replace(Some(self.clone())));
+        let result = call();
+        CURRENT_CALL.with(|current| *current.borrow_mut() = previous);
+        result
+    }
+
+    /// Kills every group started so far and any started later.
+    pub fn kill_all(&self) {
+        let groups = {
+            let mut state = self.lock();
+            state.killed = true;
+            std::mem::take(&mut state.groups)
+        };
+        for pid in groups {
+            kill_process_group(pid);
+        }
+    }
+
+    fn track(&self, pid: u32) {
+        let mut state = self.lock();
+        if state.killed {
+            drop(state);
+            kill_process_group(pid);
+        } else {
+            state.groups.push(pid);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CallProcessState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Spawns `command` in its own process group, recorded in the current
+/// [`CallProcesses::scope`] if there is one.
+pub fn spawn_tracked(command: &mut std::process::Command) -> std::io::Result<std::process::Child> {
+    let child = own_process_group_std(command).spawn()?;
+    CURRENT_CALL.with(|current| {
+        if let Some(call) = current.borrow().as_ref() {
+            call.track(child.id());
+        }
+    });
+    Ok(child)
+}
+
+/// Waits for `child` for at most `timeout`, killing its whole process group
+/// when it runs over. `None` means it timed out.
+pub async fn wait_with_timeout(
+    child: &mut tokio::process::Child,
+    timeout: Duration,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    let mut guard = ProcessGroupGuard::new(child.id());
+    match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(status) => {
+            guard.disarm();
+            status.map(Some)
+        }
+        Err(_) => {
+            drop(guard);
+            let _ = child.kill().await;
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::state_dirs::process_alive;
+    use std::time::Instant;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_timeout_kills_background_grandchildren() {
+        let temp = TempDir::new().expect("temp");
+        let mut command = tokio::process::Command::new("sh");
+        command
+            .arg("-c")
+            .arg("sleep 30 & echo $! > grandchild.pid; wait")
+            .current_dir(temp.path())
+            .kill_on_drop(true);
+        let mut child = own_process_group(&mut command).spawn().expect("spawn");
+        let pid_file = temp.path().join("grandchild.pid");
+        let started = Instant::now();
+        while !pid_file.exists() && started.elapsed() < Duration::from_secs(5) {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let status = wait_with_timeout(&mut child, Duration::from_millis(200))
+            .await
+            .expect("wait");
+        assert!(status.is_none());
+        let grandchild: u32 = std::fs::read_to_string(&pid_file)
+            .expect("pid")
+            .trim()
+            .parse()
+            .expect("pid number");
+        let started = Instant::now();
+        while process_alive(grandchild) && started.elapsed() < Duration::from_secs(5) {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(
+            !process_alive(grandchild),
+            "grandchild {grandchild} survived"
+        );
+    }
+
+    #[test]
+    fn test_killing_a_call_ends_the_processes_it_started() {
+        let call = CallProcesses::default();
+        let worker = {
+            let call = call.clone();
+            std::thread::spawn(move || {
+                call.scope(|| {
+                    let mut command = std::process::Command::new("sleep");
+                    command.arg("30");
+                    let mut child = spawn_tracked(&mut command).expect("spawn");
+                    child.wait().expect("wait")
+                })
+            })
+        };
+        let started = Instant::now();
+        while call.lock().groups.is_empty() && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        call.kill_all();
+        let status = worker.join().expect("worker");
+        assert!(!status.success());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,168 @@
+use super::workspace_stats::workspace_files;
+use crate::attachments::symbol_label;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "mjs", "ts", "tsx", "go", "java", "kt", "c", "h", "cc", "cpp", "hpp",
+    "cs", "rb", "php", "swift", "scala", "sh",
+];
+const MAX_MAPPED_FILE_BYTES: u64 = 512 * 1024;
+const MAX_SYMBOL_CHARS: usize = 120;
+const MAX_RENDERED_LINES: usize = 400;
+
+/// Top-level symbols of the workspace's source files, keyed by
+/// workspace-relative path. Built by one full scan, then kept current one
+/// file at a time as file tools change the tree.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct RepoMap {
+    files: BTreeMap<String, Vec<(usize, String)>>,
+}
+
+impl RepoMap {
+    pub(super) fn scan(root: &Path) -> Self {
+        let mut map = Self::default();
+        for file in workspace_files(root) {
+            if let Ok(relative) = file.strip_prefix(root) {
+                map.update(root, &relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        map
+    }
+
+    /// Re-reads `path` (workspace-relative, `/`-separated) from disk. A path
+    /// that is now a directory refreshes every file under it; one that no
+    /// longer exists is dropped along with anything mapped under it.
+    pub(super) fn refresh(&mut self, root: &Path, path: &str) {
+        let path = path.trim_end_matches('/');
+        let nested = format!("{path}/");
+        self.files
+            .retain(|mapped, _| mapped != path && !mapped.starts_with(&nested));
+        let absolute = root.join(path);
+        if absolute.is_dir() {
+            for file in workspace_files(&absolute) {
+                if let Ok(relative) = file.strip_prefix(root) {
+                    self.update(root, &relative.to_string_lossy().replace('\\', "/"));
+                }
+            }
+        } else {
+            self.update(root, path);
+        }
+    }
+
+    fn update(&mut self, root: &Path, path: &str) {
+        let is_source = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| SOURCE_EXTENSIONS.contains(&extension));
+        let absolute = root.join(path);
+        let small = fs::metadata(&absolute)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.len() <= MAX_MAPPED_FILE_BYTES);
+        let content = (is_source && small)
+            .then(|| fs::read_to_string(&absolute).ok())
+            .flatten();
+        match content {
+            Some(content) => {
+                let symbols = content
+                    .lines()
+                    .enumerate()
+                    .filter_map(|(index, line)| {
+                        let label = symbol_label(line)?;
+                        Some((index + 1, label.chars().take(MAX_SYMBOL_CHARS).collect()))
+                    })
+                    .collect();
+                self.files.insert(path.to_string(), symbols);
+            }
+            None => {
+                self.files.remove(path);
+            }
+        }
+    }
+
+    /// Files under `prefix` (all files when `None`) with each symbol's line
+    /// number, cut off after a few hundred lines.
+    pub(super) fn render(&self, prefix: Option<&str>) -> String {
+        let prefix = prefix
+            .map(|prefix| prefix.trim_end_matches('/'))
+            .filter(|prefix| !prefix.is_empty() && *prefix != ".");
+        let files: Vec<(&String, &Vec<(usize, String)>)> = self
+            .files
+            .iter()
+            .filter(|(path, _)| {
+                prefix
+                    .is_none_or(|prefix| *path == prefix || path.starts_with(&format!("{prefix}/")))
+            })
+            .collect();
+        let symbols: usize = files.iter().map(|(_, symbols)| symbols.len()).sum();
+        let mut lines = vec![format!(
+            "Repo map: {} source files, {symbols} symbols",
+            files.len()
+        )];
+        let mut shown_files = 0;
+        for (path, file_symbols) in &files {
+            if lines.len() + 1 + file_symbols.len() > MAX_RENDERED_LINES && shown_files > 0 {
+                break;
+            }
+            shown_files += 1;
+            lines.push(path.to_string());
+            for (line, label) in file_symbols.iter() {
+                lines.push(format!("  {line}: {label}"));
+            }
+        }
+        if shown_files < files.len() {
+            lines.push(format!(
+                "... {} more files; pass a path to narrow the map",
+                files.len() - shown_files
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_refresh_updates_only_the_touched_files() {
+        let temp = TempDir::new().expect("temp dir");
+        let root = temp.path();
+        fs::create_dir_all(root.join("src/parser")).expect("dirs");
+        fs::write(root.join("src/lib.rs"), "pub fn run() {}\n").expect("write");
+        fs::write(
+            root.join("src/parser/mod.rs"),
+            "pub struct Parser;\n\nimpl Parser {\n    pub fn parse(&self) {}\n}\n",
+        )
+        .expect("write");
+        fs::write(root.join("README.md"), "# Title\n").expect("write");
+
+        let mut map = RepoMap::scan(root);
+        let rendered = map.render(None);
+        assert!(rendered.starts_with("Repo map: 2 source files, 4 symbols"));
+        assert!(rendered.contains("src/lib.rs\n  1: pub fn run() {}"));
+        assert!(rendered.contains("  4: pub fn parse(&self) {}"));
+        assert!(!rendered.contains("README"));
+
+        // Edited on disk but not refreshed: the map still shows the old
+        // outline until the tool that wrote it refreshes that file.
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub fn run() {}\npub fn stop() {}\n",
+        )
+        .expect("write");
+        fs::write(root.join("src/parser/mod.rs"), "").expect("write");
+        map.refresh(root, "src/lib.rs");
+        let rendered = map.render(Some("src/"));
+        assert!(rendered.contains("  2: pub fn stop() {}"));
+        assert!(rendered.contains("  4: pub fn parse(&self) {}"));
+
+        fs::rename(root.join("src/parser"), root.join("src/syntax")).expect("rename");
+        map.refresh(root, "src/parser");
+        map.refresh(root, "src/syntax");
+        let rendered = map.render(Some("src/syntax"));
+        assert!(rendered.starts_with("Repo map: 1 source files, 0 symbols"));
+        assert!(!map.render(None).contains("src/parser"));
+    }
+}
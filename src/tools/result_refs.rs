@@ -0,0 +1,144 @@
+use crate::util::parse_bool_str;
+use anyhow::{bail, Result};
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+pub const DEDUP_TOOL_RESULTS_ENV: &str = "VEX_DEDUP_TOOL_RESULTS";
+
+/// Results shorter than this are cheaper to repeat than to reference.
+const MIN_DEDUP_CHARS: usize = 400;
+const MAX_STORED_RESULTS: usize = 200;
+
+/// Repeated tool results are replaced by references unless
+/// `VEX_DEDUP_TOOL_RESULTS` is off.
+pub fn result_dedup_enabled_from_env() -> bool {
+    std::env::var(DEDUP_TOOL_RESULTS_ENV)
+        .ok()
+        .and_then(|value| parse_bool_str(&value))
+        .unwrap_or(true)
+}
+
+#[derive(Debug)]
+struct StoredResult {
+    tool: String,
+    tool_use_id: String,
+    content: String,
+}
+
+/// Tool results already placed in history this session, keyed by content
+/// hash, so a later identical result can be sent as a short reference and
+/// expanded again on request.
+#[derive(Debug, Default)]
+pub struct ToolResultStore {
+    entries: HashMap<String, StoredResult>,
+    order: VecDeque<String>,
+}
+
+impl ToolResultStore {
+    /// Returns a reference to the earlier copy when `content` was already
+    /// recorded, otherwise records it and returns `None`.
+    pub fn reference_duplicate(
+        &mut self,
+        tool: &str,
+        tool_use_id: &str,
+        content: &str,
+    ) -> Option<String> {
+        let chars = content.chars().count();
+        if chars < MIN_DEDUP_CHARS {
+            return None;
+        }
+        let hash = content_hash(content);
+        if let Some(earlier) = self.entries.get(&hash) {
+            return Some(format!(
+                "[identical to the earlier {} result {} (hash {hash}, {chars} chars); call expand_tool_result with this hash to see it again]",
+                earlier.tool, earlier.tool_use_id
+            ));
+        }
+        if self.order.len() == MAX_STORED_RESULTS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.order.push_back(hash.clone());
+        self.entries.insert(
+            hash,
+            StoredResult {
+                tool: tool.to_string(),
+                tool_use_id: tool_use_id.to_string(),
+                content: content.to_string(),
+            },
+        );
+        None
+    }
+
+    /// Drops the results first seen as `tool_use_ids`, so the next identical
+    /// result is sent in full rather than pointing at a copy the history no
+    /// longer holds.
+    pub fn forget(&mut self, tool_use_ids: &[String]) {
+        self.entries
+            .retain(|_, stored| !tool_use_ids.contains(&stored.tool_use_id));
+        let entries = &self.entries;
+        self.order.retain(|hash| entries.contains_key(hash));
+    }
+
+    /// Full content of a stored result, looked up by hash or by the
+    /// tool_use id of its first occurrence.
+    pub fn expand(&self, key: &str) -> Result<String> {
+        let key = key.trim();
+        let found = self.entries.get(key).or_else(|| {
+            self.entries
+                .values()
+                .find(|stored| stored.tool_use_id == key)
+        });
+        match found {
+            Some(stored) => Ok(stored.content.clone()),
+            None => bail!("No stored tool result matches `{key}`; it may have been evicted"),
+        }
+    }
+}
+
+/// 16 hex digits. `DefaultHasher::new` uses fixed keys, so hashes are
+/// stable within a build.
+fn content_hash(content: &str) -> String {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_results_become_expandable_references() -> Result<()> {
+        let mut store = ToolResultStore::default();
+        let output = "src/lib.rs:1: match\n".repeat(40);
+        assert_eq!(
+            store.reference_duplicate("search_files", "toolu_1", &output),
+            None
+        );
+        let reference = store
+            .reference_duplicate("search", "toolu_2", &output)
+            .expect("second copy is referenced");
+        let hash = content_hash(&output);
+        assert!(
+            reference.starts_with(&format!(
+                "[identical to the earlier search_files result toolu_1 (hash {hash}, 800 chars)"
+            )),
+            "{reference}"
+        );
+        assert_eq!(store.expand(&hash)?, output);
+        assert_eq!(store.expand("toolu_1")?, output);
+        assert!(store.expand("toolu_2").is_err());
+
+        assert_eq!(
+            store.reference_duplicate("search", "toolu_3", "short"),
+            None
+        );
+        assert_eq!(
+            store.reference_duplicate("search", "toolu_4", "short"),
+            None
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use crate::state_dirs::{SessionDir, StateDirs};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// scratch_read returns at most this much of a file.
+const MAX_SCRATCH_READ_BYTES: usize = 256 * 1024;
+
+/// Per-turn directories in the session's `.aistar/sessions/<id>/tmp/` where
+/// the model keeps generated scripts, downloaded data, and other
+/// intermediate files out of the working tree. Each turn writes to
+/// `turn<n>/`; files from earlier turns of the session stay readable. The
+/// directories are removed when the session ends, and ones left by a session
+/// that did not exit cleanly are removed when the next session starts.
+#[derive(Debug)]
+pub struct ScratchSpace {
+    session: SessionDir,
+    root: PathBuf,
+    turn: AtomicUsize,
+}
+
+impl ScratchSpace {
+    pub fn for_workspace(working_dir: &Path) -> Self {
+        let session = StateDirs::for_workspace(working_dir).session();
+        Self {
+            root: session.scratch_dir(),
+            session,
+            turn: AtomicUsize::new(0),
+        }
+    }
+
+    /// The id of the session directory the scratch files live in.
+    pub fn session_id(&self) -> &str {
+        self.session.id()
+    }
+
+    /// Moves writes to a fresh directory for the next turn.
+    pub fn begin_turn(&self) {
+        self.turn.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// The current turn's directory; it exists once something is written.
+    pub fn turn_dir(&self) -> PathBuf {
+        self.dir_for(self.turn.load(Ordering::SeqCst))
+    }
+
+    fn dir_for(&self, turn: usize) -> PathBuf {
+        self.root.join(format!("turn{turn}"))
+    }
+
+    pub fn write(&self, name: &str, content: &str) -> Result<String> {
+        let relative = scratch_name(name)?;
+        let dir = self.turn_dir();
+        if !dir.exists() {
+            self.session.ensure()?;
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let path = dir.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        fs::write(&path, content).with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(format!(
+            "Wrote {} bytes to scratch file {name} ({})",
+            content.len(),
+            self.session.state_dirs().display(&path)
+        ))
+    }
+
+    /// Reads `name` from the current turn's directory, or else from the
+    /// latest earlier turn of this session that wrote it.
+    pub fn read(&self, name: &str) -> Result<String> {
+        let relative = scratch_name(name)?;
+        let Some(path) = (0..=self.turn.load(Ordering::SeqCst))
+            .rev()
+            .map(|turn| self.dir_for(turn).join(relative))
+            .find(|path| path.is_file())
+        else {
+            bail!("No scratch file named {name} in this session");
+        };
+        let bytes =
+            fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        let shown = bytes.len().min(MAX_SCRATCH_READ_BYTES);
+        let mut content = String::from_utf8_lossy(&bytes[..shown]).into_owned();
+        if shown < bytes.len() {
+            content.push_str(&format!(
+                "\n[scratch file truncated: showing {shown} of {} bytes]",
+                bytes.len()
+            ));
+        }
+        Ok(content)
+    }
+}
+
+/// `name` as a relative path that stays inside the scratch directory.
+fn scratch_name(name: &str) -> Result<&Path> {
+    let path = Path::new(name.trim());
+    if path.as_os_str().is_empty()
+        || !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        bail!("scratch file names must be relative paths without '..', got '{name}'");
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scratch_files_are_per_turn_and_removed_with_the_session() {
+        let temp = TempDir::new().expect("temp");
+        let root = StateDirs::for_workspace(temp.path()).sessions_dir();
+        // pid 0 is never a live session.
+        let abandoned = root.join("0-1-0/tmp/turn1");
+        fs::create_dir_all(&abandoned).expect("abandoned dir");
+
+        let scratch = ScratchSpace::for_workspace(temp.path());
+        assert!(!root.join("0-1-0").exists());
+        scratch.begin_turn();
+        let written = scratch.write("gen/script.py", "print(1)\n").expect("write");
+        assert!(
+            written.contains(&format!(
+                ".aistar/sessions/{}/tmp/turn1/",
+                scratch.session_id()
+            )),
+            "{written}"
+        );
+        let first_turn = scratch.turn_dir();
+
+        scratch.begin_turn();
+        scratch.write("data.json", "{}").expect("write");
+        assert_eq!(scratch.read("gen/script.py").expect("read"), "print(1)\n");
+        assert_eq!(scratch.read("data.json").expect("read"), "{}");
+        assert!(scratch.read("missing.txt").is_err());
+        assert!(scratch.write("../escape.txt", "x").is_err());
+        assert!(scratch.write("/etc/passwd", "x").is_err());
+        assert_eq!(
+            fs::read_to_string(root.join(".gitignore")).expect("ignore"),
+            "*\n"
+        );
+
+        drop(scratch);
+        assert!(!first_turn.exists());
+        assert_eq!(fs::read_dir(&root).expect("root").count(), 1);
+    }
+}
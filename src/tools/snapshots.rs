@@ -0,0 +1,243 @@
+use crate::state_dirs::StateDirs;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAX_SNAPSHOTS: usize = 100;
+
+/// What a file tool is about to change, recorded so it can be reversed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SnapshotChange {
+    /// `path` is about to be written; `previous` is `None` when it did not
+    /// exist yet.
+    Write {
+        path: String,
+        previous: Option<String>,
+    },
+    Rename {
+        from: String,
+        to: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub tool: String,
+    pub created_at: u64,
+    pub change: SnapshotChange,
+}
+
+impl Snapshot {
+    pub fn summary(&self) -> String {
+        match &self.change {
+            SnapshotChange::Write {
+                path,
+                previous: Some(_),
+            } => format!("restored {path} ({})", self.tool),
+            SnapshotChange::Write {
+                path,
+                previous: None,
+            } => format!("removed {path} ({})", self.tool),
+            SnapshotChange::Rename { from, to } => {
+                format!("renamed {to} back to {from} ({})", self.tool)
+            }
+        }
+    }
+}
+
+/// Pre-change copies of files touched by write_file, edit_file, and
+/// rename_file, kept under `.aistar/snapshots/` in workspaces without
+/// version control so `/undo` can reverse the newest change. Paths are
+/// workspace-relative.
+#[derive(Debug)]
+pub struct FileSnapshots {
+    root: PathBuf,
+    dir: PathBuf,
+    sequence: AtomicU64,
+}
+
+impl FileSnapshots {
+    pub fn for_workspace(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            dir: StateDirs::for_workspace(root).snapshots_dir(),
+            sequence: AtomicU64::new(0),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Records the current state of `path` before `tool` writes it.
+    /// Files that are not UTF-8 text are skipped.
+    pub fn record_write(&self, tool: &str, path: &str) -> Result<()> {
+        let previous = match fs::read_to_string(self.root.join(path)) {
+            Ok(content) => Some(content),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(_) => return Ok(()),
+        };
+        self.record(
+            tool,
+            SnapshotChange::Write {
+                path: path.to_string(),
+                previous,
+            },
+        )
+    }
+
+    pub fn record_rename(&self, from: &str, to: &str) -> Result<()> {
+        self.record(
+            "rename_file",
+            SnapshotChange::Rename {
+                from: from.to_string(),
+                to: to.to_string(),
+            },
+        )
+    }
+
+    fn record(&self, tool: &str, change: SnapshotChange) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        let created_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        let snapshot = Snapshot {
+            tool: tool.to_string(),
+            created_at: created_at_ms / 1000,
+            change,
+        };
+        let sequence = self.sequence.fetch_add(1, Ordering::SeqCst);
+        let path = self
+            .dir
+            .join(format!("{created_at_ms:013}-{sequence:06}.json"));
+        fs::write(&path, serde_json::to_vec(&snapshot)?)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        self.prune()
+    }
+
+    /// Snapshot files, oldest first.
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to read {}", self.dir.display()))
+            }
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    fn prune(&self) -> Result<()> {
+        let files = self.files()?;
+        let excess = files.len().saturating_sub(MAX_SNAPSHOTS);
+        for path in &files[..excess] {
+            fs::remove_file(path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Every recorded change, oldest first.
+    pub fn journal(&self) -> Result<Vec<Snapshot>> {
+        self.files()?
+            .iter()
+            .map(|path| {
+                let raw =
+                    fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+                serde_json::from_slice(&raw)
+                    .with_context(|| format!("failed to parse {}", path.display()))
+            })
+            .collect()
+    }
+
+    /// Reverses the newest recorded change and drops its snapshot. Returns
+    /// `None` when there is nothing to undo.
+    pub fn undo_last(&self) -> Result<Option<Snapshot>> {
+        let Some(path) = self.files()?.pop() else {
+            return Ok(None);
+        };
+        let raw = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        let snapshot: Snapshot = serde_json::from_slice(&raw)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        match &snapshot.change {
+            SnapshotChange::Write {
+                path: file,
+                previous: Some(previous),
+            } => {
+                let target = self.root.join(file);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&target, previous)
+                    .with_context(|| format!("failed to restore {file}"))?;
+            }
+            SnapshotChange::Write {
+                path: file,
+                previous: None,
+            } => match fs::remove_file(self.root.join(file)) {
+                Ok(()) => {}
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+                Err(error) => {
+                    return Err(error).with_context(|| format!("failed to remove {file}"));
+                }
+            },
+            SnapshotChange::Rename { from, to } => {
+                if self.root.join(from).exists() {
+                    bail!("cannot undo rename: {from} exists again");
+                }
+                fs::rename(self.root.join(to), self.root.join(from))
+                    .with_context(|| format!("failed to rename {to} back to {from}"))?;
+            }
+        }
+        fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+        Ok(Some(snapshot))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_undo_reverses_changes_newest_first() -> Result<()> {
+        let temp = TempDir::new()?;
+        let snapshots = FileSnapshots::for_workspace(temp.path());
+        fs::write(temp.path().join("a.txt"), "original")?;
+
+        snapshots.record_write("edit_file", "a.txt")?;
+        fs::write(temp.path().join("a.txt"), "edited")?;
+        snapshots.record_write("write_file", "new.txt")?;
+        fs::write(temp.path().join("new.txt"), "created")?;
+        snapshots.record_rename("a.txt", "b.txt")?;
+        fs::rename(temp.path().join("a.txt"), temp.path().join("b.txt"))?;
+
+        let summaries: Vec<String> = std::iter::from_fn(|| snapshots.undo_last().transpose())
+            .map(|snapshot| snapshot.map(|snapshot| snapshot.summary()))
+            .collect::<Result<_>>()?;
+        assert_eq!(
+            summaries,
+            vec![
+                "renamed b.txt back to a.txt (rename_file)",
+                "removed new.txt (write_file)",
+                "restored a.txt (edit_file)",
+            ]
+        );
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt"))?, "original");
+        assert!(!temp.path().join("new.txt").exists());
+        assert!(!temp.path().join("b.txt").exists());
+        assert_eq!(snapshots.undo_last()?, None);
+        Ok(())
+    }
+}
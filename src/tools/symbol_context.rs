@@ -0,0 +1,288 @@
+use super::workspace_stats::workspace_files;
+use crate::attachments::strip_symbol_prefixes;
+use crate::util::parse_bool_str;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+pub const SYMBOL_CONTEXT_ENV: &str = "VEX_EDIT_SYMBOL_CONTEXT";
+
+const DEFINITION_KEYWORDS: &[&str] = &["fn ", "def ", "function ", "func "];
+const MAX_SCANNED_FILE_BYTES: u64 = 512 * 1024;
+const MAX_ENTRIES_PER_LIST: usize = 8;
+const MAX_LINE_CHARS: usize = 160;
+
+/// Edit results only carry caller/callee context when
+/// `VEX_EDIT_SYMBOL_CONTEXT` is on, since every scan adds tokens.
+pub fn symbol_context_enabled_from_env() -> bool {
+    std::env::var(SYMBOL_CONTEXT_ENV)
+        .ok()
+        .and_then(|value| parse_bool_str(&value))
+        .unwrap_or(false)
+}
+
+struct Definition {
+    line: usize,
+    indent: usize,
+    name: String,
+}
+
+/// Callers and callees of the functions an edit touched. `start_line` and
+/// `end_line` are 1-based lines of the replacement text in `content`, the
+/// file's new contents. Functions are found by definition keywords and
+/// references by `name(` text matches across the workspace, so the result
+/// is a hint, not a resolved call graph. `None` when the edit touched no
+/// function.
+pub fn edit_symbol_context(
+    root: &Path,
+    edited_file: &Path,
+    content: &str,
+    start_line: usize,
+    end_line: usize,
+) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let definitions: Vec<Definition> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            definition_name(line).map(|name| Definition {
+                line: index + 1,
+                indent: indent_of(line),
+                name: name.to_string(),
+            })
+        })
+        .collect();
+
+    // The function enclosing the edit start, plus any the edit defines.
+    let enclosing = definitions
+        .iter()
+        .rposition(|definition| definition.line <= start_line)
+        .filter(|&index| start_line <= body_end(&lines, &definitions, index));
+    let edited: Vec<usize> = (0..definitions.len())
+        .filter(|&index| {
+            Some(index) == enclosing || (start_line..=end_line).contains(&definitions[index].line)
+        })
+        .collect();
+    if edited.is_empty() {
+        return None;
+    }
+
+    let edited_names: BTreeSet<&str> = edited
+        .iter()
+        .map(|&index| definitions[index].name.as_str())
+        .collect();
+    let mut callees = BTreeSet::new();
+    for &index in &edited {
+        let body_start = definitions[index].line;
+        let body_end = body_end(&lines, &definitions, index);
+        for line in &lines[body_start - 1..body_end] {
+            for name in called_names(line) {
+                if !edited_names.contains(name) {
+                    callees.insert(name.to_string());
+                }
+            }
+        }
+    }
+
+    let mut callers: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut callee_definitions: BTreeMap<String, String> = BTreeMap::new();
+    for file in workspace_files(root) {
+        let too_large = fs::metadata(&file)
+            .map(|metadata| metadata.len() > MAX_SCANNED_FILE_BYTES)
+            .unwrap_or(true);
+        if too_large {
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(&file) else {
+            continue;
+        };
+        let display = file
+            .strip_prefix(root)
+            .unwrap_or(&file)
+            .to_string_lossy()
+            .replace(std::path::MAIN_SEPARATOR, "/");
+        for (index, line) in text.lines().enumerate() {
+            let defined = definition_name(line);
+            if let Some(name) = defined {
+                if callees.contains(name) && !callee_definitions.contains_key(name) {
+                    callee_definitions.insert(
+                        name.to_string(),
+                        format!("{display}:{}: {}", index + 1, clip(line.trim())),
+                    );
+                }
+            }
+            for name in called_names(line) {
+                let Some(&name) = edited_names.get(name) else {
+                    continue;
+                };
+                if defined == Some(name) {
+                    continue;
+                }
+                // Recursive calls inside the edited function are not callers.
+                let recursive = file == edited_file
+                    && edited.iter().any(|&edited_index| {
+                        definitions[edited_index].name == name
+                            && (definitions[edited_index].line
+                                ..=body_end(&lines, &definitions, edited_index))
+                                .contains(&(index + 1))
+                    });
+                if recursive {
+                    continue;
+                }
+                let entries = callers.entry(name).or_default();
+                if entries.len() < MAX_ENTRIES_PER_LIST {
+                    entries.push(format!("{display}:{}: {}", index + 1, clip(line.trim())));
+                }
+            }
+        }
+    }
+
+    let mut out = format!("Symbol context ({SYMBOL_CONTEXT_ENV}):");
+    for name in &edited_names {
+        match callers.get(name).filter(|entries| !entries.is_empty()) {
+            Some(entries) => {
+                out.push_str(&format!("\n`{name}` callers:"));
+                for entry in entries {
+                    out.push_str(&format!("\n  {entry}"));
+                }
+            }
+            None => out.push_str(&format!("\n`{name}` callers: none found")),
+        }
+    }
+    if !callee_definitions.is_empty() {
+        out.push_str("\nCalls into:");
+        for entry in callee_definitions.values().take(MAX_ENTRIES_PER_LIST) {
+            out.push_str(&format!("\n  {entry}"));
+        }
+    }
+    Some(out)
+}
+
+/// 1-based last line of the body of `definitions[index]`: up to the first
+/// later line indented no deeper than the definition (a closing bracket
+/// included), and never past the next definition.
+fn body_end(lines: &[&str], definitions: &[Definition], index: usize) -> usize {
+    let definition = &definitions[index];
+    let limit = definitions
+        .get(index + 1)
+        .map(|next| next.line - 1)
+        .unwrap_or(lines.len());
+    for line_number in definition.line + 1..=limit {
+        let line = lines[line_number - 1];
+        if line.trim().is_empty() || indent_of(line) > definition.indent {
+            continue;
+        }
+        return if line.trim_start().starts_with(['}', ')', ']']) {
+            line_number
+        } else {
+            line_number - 1
+        };
+    }
+    limit
+}
+
+fn indent_of(line: &str) -> usize {
+    line.chars()
+        .take_while(|ch| ch.is_whitespace())
+        .map(|ch| if ch == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+fn definition_name(line: &str) -> Option<&str> {
+    let rest = strip_symbol_prefixes(line.trim_start());
+    let after = DEFINITION_KEYWORDS
+        .iter()
+        .find_map(|keyword| rest.strip_prefix(keyword))?;
+    // Go methods: `func (r *Receiver) Name(`.
+    let after = match after.strip_prefix('(') {
+        Some(receiver) => receiver.split_once(')')?.1.trim_start(),
+        None => after,
+    };
+    let end = after
+        .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+        .unwrap_or(after.len());
+    (end > 0).then(|| &after[..end])
+}
+
+/// Identifiers immediately followed by `(`.
+fn called_names(line: &str) -> impl Iterator<Item = &str> {
+    let bytes = line.as_bytes();
+    line.match_indices('(').filter_map(move |(paren, _)| {
+        let start = line[..paren]
+            .rfind(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+            .map(|index| index + 1)
+            .unwrap_or(0);
+        let name = &line[start..paren];
+        let starts_with_letter = name
+            .chars()
+            .next()
+            .is_some_and(|ch| ch.is_alphabetic() || ch == '_');
+        // `foo!(` is a macro, not a call.
+        (starts_with_letter && !(start > 0 && bytes[start - 1] == b'!')).then_some(name)
+    })
+}
+
+fn clip(line: &str) -> String {
+    if line.chars().count() > MAX_LINE_CHARS {
+        let mut clipped: String = line.chars().take(MAX_LINE_CHARS).collect();
+        clipped.push_str("...");
+        clipped
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_definition_and_call_names() {
+        assert_eq!(
+            definition_name("    pub async fn load(&self) {"),
+            Some("load")
+        );
+        assert_eq!(definition_name("def parse_args(argv):"), Some("parse_args"));
+        assert_eq!(
+            definition_name("func (s *Server) Serve(addr string) error {"),
+            Some("Serve")
+        );
+        assert_eq!(definition_name("let fn_ptr = load;"), None);
+        assert_eq!(
+            called_names("let x = store.load(parse(a), vec![1]); println!(\"{x}\");")
+                .collect::<Vec<_>>(),
+            vec!["load", "parse"]
+        );
+    }
+
+    #[test]
+    fn test_edit_context_lists_callers_and_callees() -> anyhow::Result<()> {
+        let temp = TempDir::new()?;
+        fs::create_dir_all(temp.path().join("src"))?;
+        fs::create_dir_all(temp.path().join("target"))?;
+        let lib = "pub fn parse(input: &str) -> usize {\n    normalize(input).len()\n}\n\nfn normalize(input: &str) -> String {\n    input.trim().to_string()\n}\n";
+        fs::write(temp.path().join("src/lib.rs"), lib)?;
+        fs::write(
+            temp.path().join("src/main.rs"),
+            "fn main() {\n    let n = parse(\"x\");\n}\n",
+        )?;
+        fs::write(temp.path().join("target/gen.rs"), "parse(\"ignored\");\n")?;
+
+        let context = edit_symbol_context(temp.path(), &temp.path().join("src/lib.rs"), lib, 2, 2)
+            .expect("edit inside parse");
+        assert_eq!(
+            context,
+            "Symbol context (VEX_EDIT_SYMBOL_CONTEXT):\n`parse` callers:\n  src/main.rs:2: let n = parse(\"x\");\nCalls into:\n  src/lib.rs:5: fn normalize(input: &str) -> String {"
+        );
+
+        let context = edit_symbol_context(temp.path(), &temp.path().join("src/lib.rs"), lib, 6, 6)
+            .expect("edit inside normalize");
+        assert!(context.contains("`normalize` callers:\n  src/lib.rs:2: normalize(input).len()"));
+        assert_eq!(
+            edit_symbol_context(temp.path(), &temp.path().join("a.txt"), "no code", 1, 1),
+            None
+        );
+        Ok(())
+    }
+}
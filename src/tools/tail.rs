@@ -0,0 +1,217 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub(super) const MAX_TAIL_LINES: usize = 2_000;
+pub(super) const MAX_TAIL_BYTES: usize = 256 * 1024;
+
+/// Byte offset of the next unread line of each file tail_file has read this
+/// session, keyed by resolved path.
+#[derive(Debug, Default)]
+pub(super) struct TailCursors {
+    offsets: Mutex<HashMap<PathBuf, u64>>,
+}
+
+impl TailCursors {
+    /// Lines appended to `path` since the last call, up to `max_lines` and
+    /// `max_bytes`. The first read of a path (or one with `reset`) returns
+    /// its last lines instead of the whole file. Only complete lines are
+    /// returned, so a line still being written is picked up next time.
+    pub(super) fn read_new(
+        &self,
+        path: &Path,
+        display: &str,
+        reset: bool,
+        max_lines: usize,
+        max_bytes: usize,
+    ) -> Result<String> {
+        let mut file =
+            File::open(path).with_context(|| format!("Failed to open {display} for tail_file"))?;
+        let len = file
+            .metadata()
+            .with_context(|| format!("Failed to stat {display}"))?
+            .len();
+        let mut offsets = self
+            .offsets
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        let previous = if reset {
+            None
+        } else {
+            offsets.get(path).copied()
+        };
+
+        let mut note = None;
+        let (start, chunk) = match previous {
+            Some(offset) if offset == len => {
+                return Ok(format!(
+                    "[{display}: no new lines since the last read; {len} bytes]"
+                ));
+            }
+            Some(offset) if offset < len => {
+                let chunk = read_range(&mut file, offset, max_bytes)?;
+                (offset, leading_lines(chunk, max_lines, max_bytes))
+            }
+            // Shorter than at the last read: truncated or rotated.
+            Some(_) => {
+                note = Some("file was truncated since the last read; reading from the start");
+                let chunk = read_range(&mut file, 0, max_bytes)?;
+                (0, leading_lines(chunk, max_lines, max_bytes))
+            }
+            None => {
+                let from = len.saturating_sub(max_bytes as u64);
+                let chunk = read_range(&mut file, from, max_bytes)?;
+                let (skipped, lines) = trailing_lines(&chunk, from > 0, max_lines);
+                (from + skipped as u64, lines.to_vec())
+            }
+        };
+        let end = start + chunk.len() as u64;
+        offsets.insert(path.to_path_buf(), end);
+
+        let pending = len - end;
+        if chunk.is_empty() {
+            return Ok(format!(
+                "[{display}: no complete new lines; {pending} bytes of a line still being written]"
+            ));
+        }
+        let mut header = format!("[{display}: bytes {start}-{end} of {len}");
+        if pending > 0 {
+            header.push_str(&format!(
+                "; {pending} bytes not yet read, call again for more"
+            ));
+        }
+        if let Some(note) = note {
+            header.push_str(&format!("; {note}"));
+        }
+        header.push(']');
+        let body = String::from_utf8_lossy(&chunk);
+        Ok(format!("{header}\n{}", body.trim_end_matches('\n')))
+    }
+}
+
+fn read_range(file: &mut File, offset: u64, max_bytes: usize) -> Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))
+        .context("Failed to seek for tail_file")?;
+    let mut buffer = Vec::new();
+    file.take(max_bytes as u64)
+        .read_to_end(&mut buffer)
+        .context("Failed to read for tail_file")?;
+    Ok(buffer)
+}
+
+/// The first `max_lines` complete lines of `chunk`. A single line longer
+/// than `max_bytes` is returned cut, so the cursor still moves past it.
+fn leading_lines(mut chunk: Vec<u8>, max_lines: usize, max_bytes: usize) -> Vec<u8> {
+    let end = chunk
+        .iter()
+        .enumerate()
+        .filter(|(_, byte)| **byte == b'\n')
+        .take(max_lines)
+        .last()
+        .map(|(index, _)| index + 1);
+    match end {
+        Some(end) => chunk.truncate(end),
+        None if chunk.len() >= max_bytes => {}
+        None => chunk.clear(),
+    }
+    chunk
+}
+
+/// The last `max_lines` complete lines of `chunk` and how many bytes precede
+/// them. When `mid_line` the chunk starts inside a line, which is skipped.
+fn trailing_lines(chunk: &[u8], mid_line: bool, max_lines: usize) -> (usize, &[u8]) {
+    let complete = match chunk.iter().rposition(|byte| *byte == b'\n') {
+        Some(index) => &chunk[..=index],
+        None => return (0, &[]),
+    };
+    let mut starts: Vec<usize> = std::iter::once(0)
+        .chain(
+            complete
+                .iter()
+                .enumerate()
+                .filter(|(_, byte)| **byte == b'\n')
+                .map(|(index, _)| index + 1),
+        )
+        .filter(|start| *start < complete.len())
+        .collect();
+    if mid_line && !starts.is_empty() {
+        starts.remove(0);
+    }
+    let first = starts
+        .len()
+        .checked_sub(max_lines)
+        .and_then(|skip| starts.get(skip))
+        .or(starts.first())
+        .copied()
+        .unwrap_or(complete.len());
+    (first, &complete[first..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_reads_only_lines_appended_since_the_last_call() {
+        let temp = TempDir::new().expect("temp");
+        let path = temp.path().join("build.log");
+        fs::write(&path, "one\ntwo\nthree\n").expect("seed");
+        let cursors = TailCursors::default();
+        let read = |reset, max_lines| {
+            cursors
+                .read_new(&path, "build.log", reset, max_lines, MAX_TAIL_BYTES)
+                .expect("tail")
+        };
+
+        assert_eq!(read(false, 2), "[build.log: bytes 4-14 of 14]\ntwo\nthree");
+        assert_eq!(
+            read(false, 2),
+            "[build.log: no new lines since the last read; 14 bytes]"
+        );
+
+        let mut log = OpenOptions::new().append(true).open(&path).expect("open");
+        write!(log, "four\nfive\nsix\npart").expect("append");
+        assert_eq!(
+            read(false, 2),
+            "[build.log: bytes 14-24 of 32; 8 bytes not yet read, call again for more]\nfour\nfive"
+        );
+        assert_eq!(
+            read(false, 2),
+            "[build.log: bytes 24-28 of 32; 4 bytes not yet read, call again for more]\nsix"
+        );
+        assert_eq!(
+            read(false, 2),
+            "[build.log: no complete new lines; 4 bytes of a line still being written]"
+        );
+        writeln!(log, "ial").expect("finish line");
+        assert_eq!(read(false, 2), "[build.log: bytes 28-36 of 36]\npartial");
+
+        fs::write(&path, "fresh\n").expect("rotate");
+        assert_eq!(
+            read(false, 2),
+            "[build.log: bytes 0-6 of 6; file was truncated since the last read; reading from the start]\nfresh"
+        );
+        assert_eq!(read(true, 5), "[build.log: bytes 0-6 of 6]\nfresh");
+    }
+
+    #[test]
+    fn test_first_read_of_a_large_file_starts_at_a_line_boundary() {
+        let temp = TempDir::new().expect("temp");
+        let path = temp.path().join("big.log");
+        fs::write(&path, "aaaa\nbbbb\ncccc\n").expect("seed");
+        let cursors = TailCursors::default();
+        // Eight bytes from the end starts inside "bbbb".
+        assert_eq!(
+            cursors
+                .read_new(&path, "big.log", false, 10, 8)
+                .expect("tail"),
+            "[big.log: bytes 10-15 of 15]\ncccc"
+        );
+    }
+}
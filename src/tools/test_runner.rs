@@ -0,0 +1,585 @@
+use super::process_group::{kill_process_group, spawn_tracked};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const TEST_COMMAND_ENV: &str = "VEX_TEST_COMMAND";
+pub const TEST_TIMEOUT_ENV: &str = "VEX_TEST_TIMEOUT_SECS";
+const DEFAULT_TEST_TIMEOUT_SECS: u64 = 600;
+const MAX_REPORTED_FAILURES: usize = 20;
+const MAX_FAILURE_MESSAGE_CHARS: usize = 400;
+const OUTPUT_HEAD_LINES: usize = 40;
+const OUTPUT_TAIL_LINES: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestFramework {
+    Cargo,
+    Npm,
+    Pytest,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    pub framework: TestFramework,
+}
+
+impl TestCommand {
+    fn new(program: &str, args: &[&str], framework: TestFramework) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            framework,
+        }
+    }
+
+    /// Parses an explicitly configured command line (`VEX_TEST_COMMAND`).
+    /// Arguments are split on whitespace; the framework is inferred from the
+    /// program name so failures can still be parsed.
+    pub fn from_configured(command: &str) -> Option<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next()?.to_string();
+        let args: Vec<String> = parts.map(ToOwned::to_owned).collect();
+        let framework = match program.as_str() {
+            "cargo" => TestFramework::Cargo,
+            "npm" | "npx" | "yarn" | "pnpm" | "jest" => TestFramework::Npm,
+            "pytest" => TestFramework::Pytest,
+            "python" | "python3" if args.iter().any(|arg| arg == "pytest") => TestFramework::Pytest,
+            _ => TestFramework::Other,
+        };
+        Some(Self {
+            program,
+            args,
+            framework,
+        })
+    }
+
+    pub fn with_filter(mut self, filter: Option<&str>) -> Self {
+        let Some(filter) = filter.map(str::trim).filter(|value| !value.is_empty()) else {
+            return self;
+        };
+        match self.framework {
+            TestFramework::Cargo | TestFramework::Other => self.args.push(filter.to_string()),
+            TestFramework::Npm => {
+                if !self.args.iter().any(|arg| arg == "--") {
+                    self.args.push("--".to_string());
+                }
+                self.args.push("-t".to_string());
+                self.args.push(filter.to_string());
+            }
+            TestFramework::Pytest => {
+                self.args.push("-k".to_string());
+                self.args.push(filter.to_string());
+            }
+        }
+        self
+    }
+
+    pub fn display(&self) -> String {
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestFailure {
+    pub name: String,
+    pub file: Option<String>,
+    pub message: String,
+}
+
+/// Picks the project's test command from well-known manifests, in the order
+/// Cargo, npm, pytest.
+pub fn detect_test_command(working_dir: &Path) -> Option<TestCommand> {
+    if working_dir.join("Cargo.toml").is_file() {
+        return Some(TestCommand::new("cargo", &["test"], TestFramework::Cargo));
+    }
+
+    if let Ok(manifest) = fs::read_to_string(working_dir.join("package.json")) {
+        let has_test_script = serde_json::from_str::<serde_json::Value>(&manifest)
+            .ok()
+            .and_then(|value| value.pointer("/scripts/test").cloned())
+            .is_some_and(|script| script.is_string());
+        if has_test_script {
+            return Some(TestCommand::new("npm", &["test"], TestFramework::Npm));
+        }
+    }
+
+    let has_pytest_config = working_dir.join("pytest.ini").is_file()
+        || working_dir.join("conftest.py").is_file()
+        || file_contains(&working_dir.join("pyproject.toml"), "[tool.pytest")
+        || file_contains(&working_dir.join("setup.cfg"), "[tool:pytest]")
+        || file_contains(&working_dir.join("tox.ini"), "[pytest]");
+    if has_pytest_config {
+        return Some(TestCommand::new("pytest", &["-q"], TestFramework::Pytest));
+    }
+
+    None
+}
+
+fn file_contains(path: &Path, needle: &str) -> bool {
+    fs::read_to_string(path).is_ok_and(|content| content.contains(needle))
+}
+
+pub fn resolve_test_timeout() -> Duration {
+    let secs = std::env::var(TEST_TIMEOUT_ENV)
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_TEST_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Runs `command` in `working_dir` and returns a failure summary followed by
+/// the raw output trimmed to its head and tail.
+pub fn run_test_command(
+    command: &TestCommand,
+    working_dir: &Path,
+    timeout: Duration,
+) -> Result<String> {
+    let mut child = spawn_tracked(
+        Command::new(&command.program)
+            .args(&command.args)
+            .current_dir(working_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    )
+    .with_context(|| format!("Failed to start test command `{}`", command.display()))?;
+
+    let stdout_reader = spawn_pipe_reader(child.stdout.take());
+    let stderr_reader = spawn_pipe_reader(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .context("Failed to wait for test command")?
+        {
+            break Some(status);
+        }
+        if started.elapsed() >= timeout {
+            // The group, so test binaries the runner spawned die too and
+            // let go of the output pipes.
+            kill_process_group(child.id());
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+
+    let stdout = stdout_reader.join().unwrap_or_default();
+    let stderr = stderr_reader.join().unwrap_or_default();
+    let combined = match (stdout.trim().is_empty(), stderr.trim().is_empty()) {
+        (false, false) => format!("{}\n{}", stderr.trim_end(), stdout.trim_end()),
+        (true, false) => stderr,
+        _ => stdout,
+    };
+
+    let Some(status) = status else {
+        bail!(
+            "test command `{}` timed out after {}s ({TEST_TIMEOUT_ENV} to adjust)\n{}",
+            command.display(),
+            timeout.as_secs(),
+            truncate_output(&combined)
+        );
+    };
+
+    let failures = parse_test_failures(command.framework, &combined);
+    Ok(render_test_report(
+        command,
+        status.code(),
+        status.success(),
+        &failures,
+        &combined,
+    ))
+}
+
+fn spawn_pipe_reader<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        String::from_utf8_lossy(&bytes).to_string()
+    })
+}
+
+fn render_test_report(
+    command: &TestCommand,
+    exit_code: Option<i32>,
+    success: bool,
+    failures: &[TestFailure],
+    output: &str,
+) -> String {
+    let exit = exit_code
+        .map(|code| code.to_string())
+        .unwrap_or_else(|| "signal".to_string());
+    let verdict = if success {
+        "passed".to_string()
+    } else if failures.is_empty() {
+        "failed".to_string()
+    } else {
+        format!("{} failed", failures.len())
+    };
+
+    let mut report = format!("run_tests: `{}` {verdict} (exit {exit})", command.display());
+    if !failures.is_empty() {
+        report.push_str("\nFailures:");
+        for failure in failures.iter().take(MAX_REPORTED_FAILURES) {
+            let location = failure
+                .file
+                .as_deref()
+                .map(|file| format!(" ({file})"))
+                .unwrap_or_default();
+            report.push_str(&format!("\n- {}{location}", failure.name));
+            if !failure.message.is_empty() {
+                report.push_str(&format!(": {}", failure.message));
+            }
+        }
+        if failures.len() > MAX_REPORTED_FAILURES {
+            report.push_str(&format!(
+                "\n- ... {} more failures omitted",
+                failures.len() - MAX_REPORTED_FAILURES
+            ));
+        }
+    }
+    let output = truncate_output(output);
+    if !output.is_empty() {
+        report.push_str("\nOutput:\n");
+        report.push_str(&output);
+    }
+    report
+}
+
+/// Keeps the first and last lines of long output; test runners print the
+/// interesting summary at the end and the build/setup context at the start.
+pub fn truncate_output(output: &str) -> String {
+    let lines: Vec<&str> = output.trim_end().lines().collect();
+    if lines.len() <= OUTPUT_HEAD_LINES + OUTPUT_TAIL_LINES {
+        return lines.join("\n");
+    }
+    let omitted = lines.len() - OUTPUT_HEAD_LINES - OUTPUT_TAIL_LINES;
+    format!(
+        "{}\n[... {omitted} lines omitted ...]\n{}",
+        lines[..OUTPUT_HEAD_LINES].join("\n"),
+        lines[lines.len() - OUTPUT_TAIL_LINES..].join("\n")
+    )
+}
+
+pub fn parse_test_failures(framework: TestFramework, output: &str) -> Vec<TestFailure> {
+    match framework {
+        TestFramework::Cargo => parse_cargo_failures(output),
+        TestFramework::Npm => parse_jest_failures(output),
+        TestFramework::Pytest => parse_pytest_failures(output),
+        TestFramework::Other => {
+            let mut failures = parse_cargo_failures(output);
+            if failures.is_empty() {
+                failures = parse_pytest_failures(output);
+            }
+            failures
+        }
+    }
+}
+
+fn parse_cargo_failures(output: &str) -> Vec<TestFailure> {
+    let mut failures: Vec<TestFailure> = Vec::new();
+    let mut lines = output.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        else {
+            continue;
+        };
+
+        let mut file = None;
+        let mut message_lines: Vec<&str> = Vec::new();
+        while let Some(next) = lines.peek() {
+            let next = *next;
+            if next.starts_with("---- ") || next.trim() == "failures:" {
+                break;
+            }
+            lines.next();
+            if let Some(location) = next
+                .split_once("panicked at ")
+                .map(|(_, location)| location)
+            {
+                if let Some(location) = location.strip_suffix(':') {
+                    // Rust 1.73+: `panicked at src/lib.rs:10:5:` then the message.
+                    file = Some(location.to_string());
+                } else if let Some((message, location)) = location.rsplit_once("', ") {
+                    // Older format: `panicked at 'message', src/lib.rs:10:5`.
+                    file = Some(location.to_string());
+                    message_lines.push(message.trim_start_matches('\''));
+                }
+                continue;
+            }
+            if next.starts_with("note: ") {
+                continue;
+            }
+            if file.is_some() && !next.trim().is_empty() {
+                message_lines.push(next.trim());
+            }
+        }
+
+        failures.push(TestFailure {
+            name: name.to_string(),
+            file,
+            message: clip_message(&message_lines.join(" ")),
+        });
+    }
+
+    for line in output.lines() {
+        let Some(name) = line
+            .strip_prefix("test ")
+            .and_then(|rest| rest.strip_suffix(" ... FAILED"))
+        else {
+            continue;
+        };
+        if !failures.iter().any(|failure| failure.name == name) {
+            failures.push(TestFailure {
+                name: name.to_string(),
+                file: None,
+                message: String::new(),
+            });
+        }
+    }
+    failures
+}
+
+fn parse_pytest_failures(output: &str) -> Vec<TestFailure> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("FAILED "))
+        .map(|rest| {
+            let (node, message) = rest.split_once(" - ").unwrap_or((rest, ""));
+            let (file, name) = match node.split_once("::") {
+                Some((file, name)) => (Some(file.to_string()), name.to_string()),
+                None => (None, node.to_string()),
+            };
+            TestFailure {
+                name,
+                file,
+                message: clip_message(message),
+            }
+        })
+        .collect()
+}
+
+fn parse_jest_failures(output: &str) -> Vec<TestFailure> {
+    let mut failures = Vec::new();
+    let mut current_file: Option<String> = None;
+    let mut lines = output.lines().peekable();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        if let Some(file) = trimmed.strip_prefix("FAIL ") {
+            current_file = Some(file.trim().to_string());
+            continue;
+        }
+        let Some(name) = trimmed.strip_prefix("● ") else {
+            continue;
+        };
+        if name.starts_with("Test suite failed to run") && current_file.is_none() {
+            continue;
+        }
+        let mut message = String::new();
+        while let Some(next) = lines.peek() {
+            let next = next.trim();
+            if next.starts_with("● ") || next.starts_with("FAIL ") {
+                break;
+            }
+            lines.next();
+            if !next.is_empty() {
+                message = next.to_string();
+                break;
+            }
+        }
+        failures.push(TestFailure {
+            name: name.to_string(),
+            file: current_file.clone(),
+            message: clip_message(&message),
+        });
+    }
+    failures
+}
+
+fn clip_message(message: &str) -> String {
+    let message = message.trim();
+    if message.chars().count() <= MAX_FAILURE_MESSAGE_CHARS {
+        return message.to_string();
+    }
+    let clipped: String = message.chars().take(MAX_FAILURE_MESSAGE_CHARS).collect();
+    format!("{clipped}...")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect_prefers_cargo_then_npm_then_pytest() {
+        let temp = TempDir::new().expect("temp dir");
+        assert_eq!(detect_test_command(temp.path()), None);
+
+        fs::write(temp.path().join("pytest.ini"), "[pytest]\n").expect("write");
+        assert_eq!(
+            detect_test_command(temp.path()).map(|command| command.framework),
+            Some(TestFramework::Pytest)
+        );
+
+        fs::write(
+            temp.path().join("package.json"),
+            r#"{"scripts":{"test":"jest"}}"#,
+        )
+        .expect("write");
+        assert_eq!(
+            detect_test_command(temp.path()).map(|command| command.display()),
+            Some("npm test".to_string())
+        );
+
+        fs::write(temp.path().join("Cargo.toml"), "[package]\n").expect("write");
+        assert_eq!(
+            detect_test_command(temp.path()).map(|command| command.display()),
+            Some("cargo test".to_string())
+        );
+    }
+
+    #[test]
+    fn test_filter_is_applied_per_framework() {
+        let cargo = TestCommand::new("cargo", &["test"], TestFramework::Cargo);
+        assert_eq!(
+            cargo.with_filter(Some("parser")).display(),
+            "cargo test parser"
+        );
+
+        let pytest = TestCommand::from_configured("python -m pytest").expect("command");
+        assert_eq!(
+            pytest.with_filter(Some("slow")).display(),
+            "python -m pytest -k slow"
+        );
+
+        let npm = TestCommand::new("npm", &["test"], TestFramework::Npm);
+        assert_eq!(
+            npm.with_filter(Some("login")).display(),
+            "npm test -- -t login"
+        );
+    }
+
+    #[test]
+    fn test_parse_cargo_failures_extracts_location_and_message() {
+        let output = "running 2 tests\n\
+test tests::ok ... ok\n\
+test tests::broken ... FAILED\n\
+\n\
+failures:\n\
+\n\
+---- tests::broken stdout ----\n\
+\n\
+thread 'tests::broken' panicked at src/lib.rs:12:9:\n\
+assertion `left == right` failed\n\
+  left: 1\n\
+ right: 2\n\
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n\
+\n\
+failures:\n\
+    tests::broken\n\
+\n\
+test result: FAILED. 1 passed; 1 failed; 0 ignored";
+
+        let failures = parse_test_failures(TestFramework::Cargo, output);
+        assert_eq!(
+            failures,
+            vec![TestFailure {
+                name: "tests::broken".to_string(),
+                file: Some("src/lib.rs:12:9".to_string()),
+                message: "assertion `left == right` failed left: 1 right: 2".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_pytest_and_jest_failures() {
+        let pytest = "=== short test summary info ===\n\
+FAILED tests/test_api.py::test_login - AssertionError: expected 200\n\
+1 failed, 3 passed";
+        let failures = parse_test_failures(TestFramework::Pytest, pytest);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name, "test_login");
+        assert_eq!(failures[0].file.as_deref(), Some("tests/test_api.py"));
+        assert_eq!(failures[0].message, "AssertionError: expected 200");
+
+        let jest =
+            "FAIL src/sum.test.js\n  ● sum › adds numbers\n\n    expect(received).toBe(expected)\n";
+        let failures = parse_test_failures(TestFramework::Npm, jest);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name, "sum › adds numbers");
+        assert_eq!(failures[0].file.as_deref(), Some("src/sum.test.js"));
+        assert_eq!(failures[0].message, "expect(received).toBe(expected)");
+    }
+
+    #[test]
+    fn test_truncate_output_keeps_head_and_tail() {
+        let output = (1..=500)
+            .map(|idx| format!("line {idx}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let truncated = truncate_output(&output);
+        assert!(truncated.starts_with("line 1\n"));
+        assert!(truncated.ends_with("line 500"));
+        assert!(truncated.contains("[... 380 lines omitted ...]"));
+        assert!(!truncated.contains("line 41\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_test_command_reports_exit_status() {
+        let temp = TempDir::new().expect("temp dir");
+        let command = TestCommand::from_configured("sh -c exit").expect("command");
+        let report = run_test_command(&command, temp.path(), Duration::from_secs(10)).expect("run");
+        assert!(report.starts_with("run_tests: `sh -c exit` passed (exit 0)"));
+
+        let command = TestCommand::from_configured("sleep 5").expect("command");
+        let err = run_test_command(&command, temp.path(), Duration::from_millis(100))
+            .expect_err("timeout");
+        assert!(err.to_string().contains("timed out"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_timeout_kills_processes_the_test_command_started() {
+        let temp = TempDir::new().expect("temp dir");
+        fs::write(
+            temp.path().join("slow.sh"),
+            "sleep 30 & echo $! > slow.pid; wait\n",
+        )
+        .expect("script");
+        let command = TestCommand::from_configured("sh slow.sh").expect("command");
+        let started = Instant::now();
+        let err = run_test_command(&command, temp.path(), Duration::from_millis(300))
+            .expect_err("timeout");
+        assert!(err.to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let sleeper: u32 = fs::read_to_string(temp.path().join("slow.pid"))
+            .expect("pid")
+            .trim()
+            .parse()
+            .expect("pid number");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while crate::state_dirs::process_alive(sleeper) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!crate::state_dirs::process_alive(sleeper));
+    }
+}
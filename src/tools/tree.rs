@@ -0,0 +1,182 @@
+use super::workspace_stats::{format_bytes, workspace_files};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub(super) const MAX_TREE_DEPTH: usize = 12;
+pub(super) const MAX_TREE_ENTRIES: usize = 2_000;
+
+#[derive(Debug, Default)]
+struct TreeNode {
+    children: BTreeMap<String, TreeNode>,
+    is_file: bool,
+    files: usize,
+    bytes: u64,
+}
+
+impl TreeNode {
+    fn insert(&mut self, components: &[String], bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+        let Some((first, rest)) = components.split_first() else {
+            return;
+        };
+        let child = self.children.entry(first.clone()).or_default();
+        if rest.is_empty() {
+            child.is_file = true;
+            child.files = 1;
+            child.bytes = bytes;
+        } else {
+            child.insert(rest, bytes);
+        }
+    }
+
+    /// Directories first, then files, each alphabetical.
+    fn sorted_children(&self) -> Vec<(&String, &TreeNode)> {
+        let mut children: Vec<_> = self.children.iter().collect();
+        children.sort_by_key(|(name, node)| (node.is_file, name.as_str()));
+        children
+    }
+
+    fn annotation(&self) -> String {
+        if self.is_file {
+            format!("({})", format_bytes(self.bytes))
+        } else {
+            let noun = if self.files == 1 { "file" } else { "files" };
+            format!("({} {noun}, {})", self.files, format_bytes(self.bytes))
+        }
+    }
+
+    /// Entries shown within `depth` levels below this node.
+    fn visible_entries(&self, depth: usize) -> usize {
+        if depth == 0 {
+            return 0;
+        }
+        self.children
+            .values()
+            .map(|child| 1 + child.visible_entries(depth - 1))
+            .sum()
+    }
+}
+
+struct TreeWriter {
+    lines: Vec<String>,
+    max_depth: usize,
+    max_entries: usize,
+    shown: usize,
+}
+
+impl TreeWriter {
+    fn write_children(&mut self, node: &TreeNode, prefix: &str, depth: usize) {
+        let children = node.sorted_children();
+        let last_index = children.len().saturating_sub(1);
+        for (index, (name, child)) in children.into_iter().enumerate() {
+            if self.shown >= self.max_entries {
+                return;
+            }
+            let last = index == last_index;
+            let branch = if last { "└── " } else { "├── " };
+            let slash = if child.is_file { "" } else { "/" };
+            self.lines.push(format!(
+                "{prefix}{branch}{name}{slash} {}",
+                child.annotation()
+            ));
+            self.shown += 1;
+            if !child.is_file && depth < self.max_depth {
+                let nested = format!("{prefix}{}", if last { "    " } else { "│   " });
+                self.write_children(child, &nested, depth + 1);
+            }
+        }
+    }
+}
+
+/// An indented tree of the workspace files under `root`, honoring
+/// `.gitignore` like `workspace_stats`. Each entry carries its size, and
+/// each directory its file count and total size, including levels below
+/// `max_depth` that are not expanded. Empty directories are not listed.
+pub(super) fn render_tree(
+    root: &Path,
+    root_display: &str,
+    max_depth: usize,
+    max_entries: usize,
+) -> String {
+    let mut tree = TreeNode::default();
+    for path in workspace_files(root) {
+        let Ok(relative) = path.strip_prefix(root) else {
+            continue;
+        };
+        let components: Vec<String> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let bytes = fs::metadata(&path)
+            .map(|meta| meta.len())
+            .unwrap_or_default();
+        tree.insert(&components, bytes);
+    }
+    if tree.files == 0 {
+        return "(no files found)".to_string();
+    }
+
+    let mut writer = TreeWriter {
+        lines: vec![format!(
+            "{}/ {}",
+            root_display.trim_end_matches('/'),
+            tree.annotation()
+        )],
+        max_depth,
+        max_entries,
+        shown: 0,
+    };
+    writer.write_children(&tree, "", 1);
+    let hidden = tree.visible_entries(max_depth).saturating_sub(writer.shown);
+    if hidden > 0 {
+        writer.lines.push(format!(
+            "... {hidden} more entries; raise max_entries or pass a narrower path"
+        ));
+    }
+    writer.lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_tree_annotates_sizes_and_bounds_depth_and_entries() -> Result<()> {
+        let temp = TempDir::new()?;
+        let root = temp.path();
+        for (path, content) in [
+            ("src/lib.rs", "a".repeat(2_048)),
+            ("src/ui/render.rs", "b".repeat(100)),
+            ("src/ui/deep/more.rs", "c".repeat(10)),
+            ("README.md", "# x\n".to_string()),
+            ("target/debug/out", "ignored".to_string()),
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().expect("parent"))?;
+            fs::write(path, content)?;
+        }
+
+        let tree = render_tree(root, ".", 2, 100);
+        assert_eq!(
+            tree,
+            [
+                "./ (4 files, 2.1 KiB)",
+                "├── src/ (3 files, 2.1 KiB)",
+                "│   ├── ui/ (2 files, 110 B)",
+                "│   └── lib.rs (2.0 KiB)",
+                "└── README.md (4 B)",
+            ]
+            .join("\n")
+        );
+
+        let bounded = render_tree(root, ".", 3, 2);
+        assert!(
+            bounded.ends_with("\n... 4 more entries; raise max_entries or pass a narrower path")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::Path;
+
+/// Version control system managing a workspace, found by walking up from
+/// the workspace root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vcs {
+    Git,
+    Jujutsu,
+    Mercurial,
+    None,
+}
+
+impl Vcs {
+    /// The nearest repository marker wins. A colocated jj repository also
+    /// has `.git` but is reported as jj, the tool its owner drives.
+    pub fn detect(root: &Path) -> Self {
+        for dir in root.ancestors() {
+            if dir.join(".jj").is_dir() {
+                return Self::Jujutsu;
+            }
+            if dir.join(".hg").is_dir() {
+                return Self::Mercurial;
+            }
+            // Worktrees and submodules use a `.git` file.
+            if dir.join(".git").exists() {
+                return Self::Git;
+            }
+        }
+        Self::None
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Git => "git",
+            Self::Jujutsu => "jj",
+            Self::Mercurial => "hg",
+            Self::None => "none",
+        }
+    }
+}
+
+/// True when the git_* tools can work in `root`: some ancestor has `.git`,
+/// which includes colocated jj repositories.
+pub fn git_repository_found(root: &Path) -> bool {
+    root.ancestors().any(|dir| dir.join(".git").exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detects_nearest_repository_kind() -> anyhow::Result<()> {
+        let temp = TempDir::new()?;
+        let nested = temp.path().join("a/b");
+        fs::create_dir_all(&nested)?;
+        // The temp dir itself may sit inside a checkout, so compare against
+        // what lies above it.
+        let outside = Vcs::detect(temp.path());
+
+        fs::create_dir_all(temp.path().join("a/.hg"))?;
+        assert_eq!(Vcs::detect(&nested), Vcs::Mercurial);
+        assert_eq!(Vcs::detect(temp.path()), outside);
+
+        fs::create_dir_all(nested.join(".git"))?;
+        assert_eq!(Vcs::detect(&nested), Vcs::Git);
+        assert!(git_repository_found(&nested));
+
+        fs::create_dir_all(nested.join(".jj"))?;
+        assert_eq!(Vcs::detect(&nested), Vcs::Jujutsu);
+        assert_eq!(Vcs::Jujutsu.label(), "jj");
+        Ok(())
+    }
+}
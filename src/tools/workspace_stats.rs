@@ -0,0 +1,238 @@
+use anyhow::Result;
+use ignore::WalkBuilder;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SKIPPED_DIRS: &[&str] = &[
+    "target",
+    "node_modules",
+    "__pycache__",
+    "venv",
+    "build",
+    "dist",
+];
+const TEST_DIR_NAMES: &[&str] = &["tests", "test", "__tests__", "spec", "specs", "testdata"];
+const BENCH_DIR_NAMES: &[&str] = &["benches", "bench", "benchmarks"];
+const MAX_LARGEST_FILES: usize = 10;
+const MAX_LISTED_DIRS: usize = 20;
+const MAX_LANGUAGES: usize = 15;
+
+/// Workspace files, honoring `.gitignore`, `.ignore`, and git's exclude
+/// files (in git checkouts or not), skipping hidden and common build
+/// directories without following symlinks.
+pub fn workspace_files(root: &Path) -> Vec<PathBuf> {
+    let walker = WalkBuilder::new(root)
+        .require_git(false)
+        .follow_links(false)
+        .filter_entry(|entry| {
+            !(entry.depth() > 0
+                && entry.file_type().is_some_and(|kind| kind.is_dir())
+                && SKIPPED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref()))
+        })
+        .build();
+    let mut files: Vec<PathBuf> = walker
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+        .map(|entry| entry.into_path())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Language name for a file extension, or `None` for unrecognized files.
+fn language_for(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "rs" => "Rust",
+        "py" => "Python",
+        "js" | "mjs" | "cjs" | "jsx" => "JavaScript",
+        "ts" | "tsx" => "TypeScript",
+        "go" => "Go",
+        "java" => "Java",
+        "kt" | "kts" => "Kotlin",
+        "c" | "h" => "C",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "C++",
+        "cs" => "C#",
+        "rb" => "Ruby",
+        "php" => "PHP",
+        "swift" => "Swift",
+        "sh" | "bash" | "zsh" => "Shell",
+        "md" | "markdown" => "Markdown",
+        "toml" => "TOML",
+        "json" => "JSON",
+        "yaml" | "yml" => "YAML",
+        "html" | "htm" => "HTML",
+        "css" | "scss" => "CSS",
+        "sql" => "SQL",
+        _ => return None,
+    })
+}
+
+fn is_test_file(name: &str) -> bool {
+    name.ends_with("_test.go")
+        || name.ends_with("_test.py")
+        || (name.starts_with("test_") && name.ends_with(".py"))
+        || name.contains(".test.")
+        || name.contains(".spec.")
+}
+
+#[derive(Debug, Default)]
+struct LanguageTotals {
+    files: usize,
+    lines: usize,
+}
+
+/// Renders a one-call overview of the workspace: file counts by language,
+/// the largest files, and where tests and benchmarks live.
+pub(super) fn render_workspace_stats(root: &Path) -> Result<String> {
+    let files = workspace_files(root);
+    let mut languages: BTreeMap<&str, LanguageTotals> = BTreeMap::new();
+    let mut sizes: Vec<(u64, String)> = Vec::new();
+    let mut total_bytes = 0u64;
+    let mut test_dirs = BTreeSet::new();
+    let mut bench_dirs = BTreeSet::new();
+
+    for path in &files {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let display = relative
+            .to_string_lossy()
+            .replace(std::path::MAIN_SEPARATOR, "/");
+        let size = fs::metadata(path)
+            .map(|meta| meta.len())
+            .unwrap_or_default();
+        total_bytes += size;
+        sizes.push((size, display.clone()));
+
+        let language = language_for(path).unwrap_or("Other");
+        let totals = languages.entry(language).or_default();
+        totals.files += 1;
+        if language != "Other" {
+            totals.lines += fs::read_to_string(path)
+                .map(|text| text.lines().count())
+                .unwrap_or_default();
+        }
+
+        let parent = match display.rsplit_once('/') {
+            Some((parent, _)) => parent.to_string(),
+            None => ".".to_string(),
+        };
+        let components: Vec<&str> = display.split('/').collect();
+        let directories = &components[..components.len() - 1];
+        if let Some(position) = directories
+            .iter()
+            .position(|name| TEST_DIR_NAMES.contains(name))
+        {
+            test_dirs.insert(directories[..=position].join("/"));
+        } else if is_test_file(components[components.len() - 1]) {
+            test_dirs.insert(parent);
+        } else if let Some(position) = directories
+            .iter()
+            .position(|name| BENCH_DIR_NAMES.contains(name))
+        {
+            bench_dirs.insert(directories[..=position].join("/"));
+        }
+    }
+
+    let mut out = format!(
+        "Workspace: {} files, {}\n",
+        files.len(),
+        format_bytes(total_bytes)
+    );
+    if files.is_empty() {
+        return Ok(out.trim_end().to_string());
+    }
+
+    let mut by_files: Vec<(&str, LanguageTotals)> = languages.into_iter().collect();
+    by_files.sort_by(|a, b| b.1.files.cmp(&a.1.files).then(a.0.cmp(b.0)));
+    out.push_str("\nLanguages (files, lines):\n");
+    for (language, totals) in by_files.iter().take(MAX_LANGUAGES) {
+        if *language == "Other" {
+            out.push_str(&format!("  {language}: {} files\n", totals.files));
+        } else {
+            out.push_str(&format!(
+                "  {language}: {} files, {} lines\n",
+                totals.files, totals.lines
+            ));
+        }
+    }
+
+    sizes.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    out.push_str("\nLargest files:\n");
+    for (size, path) in sizes.iter().take(MAX_LARGEST_FILES) {
+        out.push_str(&format!("  {path} ({})\n", format_bytes(*size)));
+    }
+
+    for (label, dirs) in [
+        ("Test locations", &test_dirs),
+        ("Bench locations", &bench_dirs),
+    ] {
+        out.push_str(&format!("\n{label}:"));
+        if dirs.is_empty() {
+            out.push_str(" none found\n");
+            continue;
+        }
+        out.push('\n');
+        for dir in dirs.iter().take(MAX_LISTED_DIRS) {
+            out.push_str(&format!("  {dir}/\n"));
+        }
+        if dirs.len() > MAX_LISTED_DIRS {
+            out.push_str(&format!("  ... {} more\n", dirs.len() - MAX_LISTED_DIRS));
+        }
+    }
+    Ok(out.trim_end().to_string())
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1_023 => format!("{bytes} B"),
+        1_024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1_024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_stats_group_languages_tests_and_benches() -> Result<()> {
+        let temp = TempDir::new()?;
+        let root = temp.path();
+        for (path, content) in [
+            ("src/lib.rs", "pub fn a() {}\npub fn b() {}\n"),
+            ("src/main.rs", "fn main() {}\n"),
+            ("tests/api.rs", "#[test]\nfn t() {}\n"),
+            ("benches/speed.rs", "fn bench() {}\n"),
+            ("web/app.test.ts", "test('x', () => {});\n"),
+            ("README.md", "# Title\n"),
+            ("target/debug/out.rs", "ignored\n"),
+            (".hidden/secret.rs", "ignored\n"),
+            (".gitignore", "generated/\n"),
+            ("generated/schema.rs", "ignored\n"),
+            ("LICENSE", &"x".repeat(2_048)),
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().expect("parent"))?;
+            fs::write(path, content)?;
+        }
+
+        let stats = render_workspace_stats(root)?;
+        assert!(stats.starts_with("Workspace: 7 files, "), "{stats}");
+        assert!(stats.contains("\n  Rust: 4 files, 6 lines\n"), "{stats}");
+        assert!(stats.contains("\n  Other: 1 files\n"), "{stats}");
+        assert!(
+            stats.contains("Largest files:\n  LICENSE (2.0 KiB)\n"),
+            "{stats}"
+        );
+        assert!(
+            stats.contains("Test locations:\n  tests/\n  web/\n"),
+            "{stats}"
+        );
+        assert!(stats.ends_with("Bench locations:\n  benches/"), "{stats}");
+        assert!(!stats.contains("target/") && !stats.contains(".hidden"));
+        assert!(!stats.contains("generated/"), "{stats}");
+        Ok(())
+    }
+}
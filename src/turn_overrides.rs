@@ -0,0 +1,232 @@
+use crate::config::{resolve_model_alias, MAX_TEMPERATURE, MODEL_ALIASES};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+const MIN_MAX_TOKENS: u32 = 128;
+const MAX_MAX_TOKENS: u32 = 64_000;
+/// The smallest extended-thinking budget the Messages API accepts.
+const MIN_THINKING_BUDGET: u32 = 1_024;
+const MAX_RECORDED_PROMPT_CHARS: usize = 120;
+
+/// Request parameters changed for a single turn. Set with directives at the
+/// start of a message (`/temp 0.2`, `/max_tokens 8000`, `/think 4000`,
+/// `/model opus`, or an `@opus:` prefix); later turns use the defaults again.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TurnOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_budget: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+}
+
+impl TurnOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `model claude-opus-4-1-20250805, temperature 0.2`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(model) = &self.model {
+            parts.push(format!("model {model}"));
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            parts.push(format!("max_tokens {max_tokens}"));
+        }
+        if let Some(budget) = self.thinking_budget {
+            parts.push(format!("thinking budget {budget}"));
+        }
+        if let Some(temperature) = self.temperature {
+            if self.thinking_budget.is_some() {
+                parts.push(format!("temperature {temperature} (dropped: thinking)"));
+            } else {
+                parts.push(format!("temperature {temperature}"));
+            }
+        }
+        parts.join(", ")
+    }
+
+    /// Splits leading directives off `input`. Returns `None` when the input
+    /// starts with no directive, so ordinary messages pass through untouched.
+    pub fn parse_prefix(input: &str) -> Result<Option<(Self, String)>> {
+        let mut overrides = Self::default();
+        let mut rest = input.trim_start();
+        let mut matched = false;
+        loop {
+            if let Some(model) = model_prefix(rest) {
+                overrides.model = Some(resolve_model_alias(model));
+                rest = rest[model.len() + 2..].trim_start();
+                matched = true;
+                continue;
+            }
+            let Some((directive, after)) = rest.split_once(char::is_whitespace) else {
+                break;
+            };
+            if !DIRECTIVES.contains(&directive) {
+                break;
+            }
+            let after = after.trim_start();
+            let (value, after) = after.split_once(char::is_whitespace).unwrap_or((after, ""));
+            overrides.apply(directive, value)?;
+            rest = after.trim_start();
+            matched = true;
+        }
+        if !matched {
+            return Ok(None);
+        }
+        if rest.is_empty() {
+            bail!(
+                "turn overrides ({}) need a message after them",
+                overrides.describe()
+            );
+        }
+        Ok(Some((overrides, rest.to_string())))
+    }
+
+    fn apply(&mut self, directive: &str, value: &str) -> Result<()> {
+        if value.is_empty() {
+            bail!("{directive} needs a value");
+        }
+        match directive {
+            "/model" => self.model = Some(resolve_model_alias(value)),
+            "/temp" | "/temperature" => {
+                let temperature: f64 = value
+                    .parse()
+                    .with_context(|| format!("{directive} expects a number, got `{value}`"))?;
+                if !(0.0..=MAX_TEMPERATURE).contains(&temperature) {
+                    bail!("{directive} must be between 0 and {MAX_TEMPERATURE}");
+                }
+                self.temperature = Some(temperature);
+            }
+            "/max_tokens" | "/max-tokens" => {
+                let max_tokens: u32 = value
+                    .parse()
+                    .with_context(|| format!("{directive} expects a token count, got `{value}`"))?;
+                self.max_tokens = Some(max_tokens.clamp(MIN_MAX_TOKENS, MAX_MAX_TOKENS));
+            }
+            "/think" => {
+                let budget: u32 = value
+                    .parse()
+                    .with_context(|| format!("/think expects a token budget, got `{value}`"))?;
+                if budget < MIN_THINKING_BUDGET {
+                    bail!("/think budget must be at least {MIN_THINKING_BUDGET} tokens");
+                }
+                self.thinking_budget = Some(budget);
+            }
+            _ => bail!("unknown turn override {directive}"),
+        }
+        Ok(())
+    }
+}
+
+const DIRECTIVES: &[&str] = &[
+    "/model",
+    "/temp",
+    "/temperature",
+    "/max_tokens",
+    "/max-tokens",
+    "/think",
+];
+
+/// The model named by an `@model:` prefix. Only aliases and Claude or
+/// `local/` model ids count, so a message like `@main.rs: why?` is left
+/// alone.
+fn model_prefix(text: &str) -> Option<&str> {
+    let (name, _) = text.strip_prefix('@')?.split_once(':')?;
+    let is_model = MODEL_ALIASES.iter().any(|(alias, _)| *alias == name)
+        || ((name.starts_with("claude-") || name.starts_with("local/"))
+            && !name.contains(char::is_whitespace));
+    is_model.then_some(name)
+}
+
+/// Overrides that applied to one turn, kept with the conversation and
+/// written into session backups.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnOverrideRecord {
+    /// 1-based turn number within the session.
+    pub turn: usize,
+    /// Start of the user message the overrides applied to.
+    pub prompt: String,
+    pub overrides: TurnOverrides,
+}
+
+impl TurnOverrideRecord {
+    pub fn new(turn: usize, prompt: &str, overrides: TurnOverrides) -> Self {
+        Self {
+            turn,
+            prompt: clip_recorded_prompt(prompt),
+            overrides,
+        }
+    }
+}
+
+/// The start of `prompt`, as recorded with per-turn session data.
+pub fn clip_recorded_prompt(prompt: &str) -> String {
+    let mut clipped: String = prompt.chars().take(MAX_RECORDED_PROMPT_CHARS).collect();
+    if clipped.len() < prompt.len() {
+        clipped.push_str("...");
+    }
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leading_directives_are_split_from_the_message() -> Result<()> {
+        let (overrides, message) =
+            TurnOverrides::parse_prefix("@opus: /temp 0.2 /think 2048 review the parser")?
+                .expect("directives");
+        assert_eq!(message, "review the parser");
+        assert_eq!(
+            overrides,
+            TurnOverrides {
+                model: Some("claude-opus-4-1-20250805".to_string()),
+                thinking_budget: Some(2048),
+                temperature: Some(0.2),
+                ..TurnOverrides::default()
+            }
+        );
+        assert_eq!(
+            overrides.describe(),
+            "model claude-opus-4-1-20250805, thinking budget 2048, temperature 0.2 (dropped: thinking)"
+        );
+
+        let (overrides, _) =
+            TurnOverrides::parse_prefix("/max_tokens 999999 go")?.expect("directive");
+        assert_eq!(overrides.max_tokens, Some(MAX_MAX_TOKENS));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ordinary_messages_and_bad_values() {
+        for message in [
+            "explain /temp 0.2",
+            "@main.rs: why does this fail?",
+            "/thinking expand",
+            "/temperatures are fine",
+        ] {
+            assert_eq!(
+                TurnOverrides::parse_prefix(message).expect("no error"),
+                None,
+                "{message}"
+            );
+        }
+        for (input, error) in [
+            ("/temp 3 hi", "between 0 and 2"),
+            ("/temp warm hi", "expects a number"),
+            ("/think 10 hi", "at least 1024"),
+            ("@haiku:", "need a message"),
+        ] {
+            let message = TurnOverrides::parse_prefix(input)
+                .expect_err(input)
+                .to_string();
+            assert!(message.contains(error), "{input}: {message}");
+        }
+    }
+}
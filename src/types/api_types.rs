@@ -31,6 +31,29 @@ pub enum ContentBlock {
         #[serde(default)]
         is_error: bool,
     },
+    /// Extended thinking. Sent back unchanged with the rest of the turn:
+    /// the API checks `signature` before it continues after a tool call.
+    Thinking {
+        thinking: String,
+        #[serde(default)]
+        signature: String,
+    },
+    /// Thinking the API flagged and returns encrypted in `data`.
+    RedactedThinking {
+        data: String,
+    },
+    /// A file uploaded through the provider's Files API, referenced by id.
+    Document {
+        source: DocumentSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DocumentSource {
+    File { file_id: String },
 }
 
 fn default_json_object() -> serde_json::Value {
@@ -56,12 +79,29 @@ pub enum StreamEvent {
     },
     MessageDelta {
         delta: MessageDelta,
+        #[serde(default)]
+        usage: Option<TokenUsage>,
     },
     MessageStop,
+    Ping,
+    Error {
+        error: StreamErrorPayload,
+    },
     #[serde(other)]
     Unknown,
 }
 
+/// Body of a provider `error` event, e.g.
+/// `{"type":"overloaded_error","message":"Overloaded"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct StreamErrorPayload {
+    #[serde(rename = "type")]
+    #[serde(default)]
+    pub error_type: String,
+    #[serde(default)]
+    pub message: String,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Delta {
     #[serde(rename = "type")]
@@ -71,6 +111,10 @@ pub struct Delta {
     pub text: Option<String>,
     #[serde(default)]
     pub partial_json: Option<String>,
+    #[serde(default)]
+    pub thinking: Option<String>,
+    #[serde(default)]
+    pub signature: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -78,6 +122,29 @@ pub struct MessageStartData {
     pub id: String,
     pub role: String,
     pub model: String,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -1,4 +1,14 @@
+pub mod context_view;
+pub mod diff_style;
 pub mod editor;
+pub mod help;
+pub mod hyperlinks;
+pub mod inline_images;
 pub mod input_metrics;
 pub mod layout;
+pub mod plain;
+pub mod prompt_history;
 pub mod render;
+pub mod thinking;
+pub mod transcript_spill;
+pub mod typewriter;
@@ -0,0 +1,158 @@
+use crate::state::ContextBreakdown;
+use crate::util::group_thousands;
+
+const BAR_WIDTH: usize = 20;
+
+fn bar(tokens: usize, total: usize) -> String {
+    let filled = if total == 0 {
+        0
+    } else {
+        (tokens * BAR_WIDTH).div_ceil(total).min(BAR_WIDTH)
+    };
+    format!("{}{}", "█".repeat(filled), "░".repeat(BAR_WIDTH - filled))
+}
+
+fn tokens(count: usize) -> String {
+    format!("~{}", group_thousands(count))
+}
+
+fn percent(tokens: usize, total: usize) -> usize {
+    (tokens * 100).checked_div(total).unwrap_or(0)
+}
+
+/// `/context` output: each part of the next request with a bar of its share,
+/// the history message by message, and what pruning removes next.
+/// `attachments` are the queued attachments with their tokens.
+pub fn render_context_breakdown(
+    breakdown: &ContextBreakdown,
+    attachments: &[(String, usize)],
+) -> Vec<String> {
+    let history_tokens = breakdown.history_tokens();
+    let attachment_tokens: usize = attachments.iter().map(|(_, tokens)| tokens).sum();
+    let total = breakdown.overhead.system_tokens
+        + breakdown.overhead.tool_schema_tokens
+        + history_tokens
+        + attachment_tokens;
+
+    let mut lines = vec![format!(
+        "[context: {} tokens in the next request, counted as history budgeting counts them]",
+        tokens(total)
+    )];
+    let parts = [
+        (
+            "system prompt".to_string(),
+            breakdown.overhead.system_tokens,
+        ),
+        (
+            "tool schemas".to_string(),
+            breakdown.overhead.tool_schema_tokens,
+        ),
+        (
+            format!("history ({} messages)", breakdown.messages.len()),
+            history_tokens,
+        ),
+        (
+            format!("attachments ({})", attachments.len()),
+            attachment_tokens,
+        ),
+    ];
+    for (label, count) in parts {
+        lines.push(format!(
+            "  {label:<24} {:>9}  {} {:>3}%",
+            tokens(count),
+            bar(count, total),
+            percent(count, total)
+        ));
+    }
+
+    if !breakdown.messages.is_empty() {
+        lines.push("  history by message:".to_string());
+        for (index, message) in breakdown.messages.iter().enumerate() {
+            let marker = if breakdown.next_pruned.contains(&index) {
+                " (pruned next)"
+            } else {
+                ""
+            };
+            lines.push(format!(
+                "    #{index:<3} {:<9} {:>9}  {}  {}{marker}",
+                message.role,
+                tokens(message.tokens),
+                bar(message.tokens, total),
+                message.label
+            ));
+        }
+    }
+    for (path, count) in attachments {
+        lines.push(format!("  attached {path}: {}", tokens(*count)));
+    }
+
+    let limit = breakdown.overhead.max_api_messages;
+    let pruned = &breakdown.next_pruned;
+    lines.push(if pruned.is_empty() {
+        format!("  pruning: nothing to prune yet; history is kept up to {limit} messages")
+    } else {
+        let range = if pruned.len() == 1 {
+            format!("#{}", pruned.start)
+        } else {
+            format!("#{}-#{}", pruned.start, pruned.end - 1)
+        };
+        let when = if breakdown.prunes_next_request {
+            "the next request drops".to_string()
+        } else {
+            format!(
+                "once history passes {limit} messages ({} now), the first to go are",
+                breakdown.messages.len()
+            )
+        };
+        format!(
+            "  pruning: {when} {range} ({} tokens)",
+            tokens(breakdown.pruned_tokens())
+        )
+    });
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{MessageSize, RequestOverhead};
+
+    #[test]
+    fn test_breakdown_lines_show_shares_and_the_next_pruning() {
+        let breakdown = ContextBreakdown {
+            overhead: RequestOverhead {
+                system_tokens: 100,
+                tool_schema_tokens: 500,
+                max_api_messages: 32,
+            },
+            messages: vec![
+                MessageSize {
+                    role: "user".to_string(),
+                    label: "text".to_string(),
+                    tokens: 200,
+                },
+                MessageSize {
+                    role: "assistant".to_string(),
+                    label: "text".to_string(),
+                    tokens: 100,
+                },
+            ],
+            next_pruned: 0..2,
+            prunes_next_request: false,
+        };
+        let lines = render_context_breakdown(&breakdown, &[("notes.md".to_string(), 100)]);
+
+        assert_eq!(
+            lines[0],
+            "[context: ~1,000 tokens in the next request, counted as history budgeting counts them]"
+        );
+        assert!(lines[2].contains("tool schemas"));
+        assert!(lines[2].contains("~500"));
+        assert!(lines[2].ends_with("██████████░░░░░░░░░░  50%"));
+        assert!(lines[6].contains("#0") && lines[6].ends_with("text (pruned next)"));
+        assert_eq!(
+            lines.last().map(String::as_str),
+            Some("  pruning: once history passes 32 messages (2 now), the first to go are #0-#1 (~300 tokens)")
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use crate::util::parse_bool_str;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+
+pub const DIFF_PALETTE_ENV: &str = "VEX_DIFF_PALETTE";
+pub const DIFF_GLYPHS_ENV: &str = "VEX_DIFF_GLYPHS";
+pub const DIFF_TINT_ENV: &str = "VEX_DIFF_TINT";
+
+/// Colors for added and removed diff lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffPalette {
+    #[default]
+    RedGreen,
+    /// Blue for additions and orange for removals, distinguishable with
+    /// red-green color blindness.
+    Colorblind,
+    /// No color; additions are bold and removals dim.
+    Mono,
+}
+
+impl DiffPalette {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::RedGreen => "default",
+            Self::Colorblind => "colorblind",
+            Self::Mono => "mono",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "default" | "red-green" | "redgreen" => Some(Self::RedGreen),
+            "colorblind" | "cb" | "blue-orange" => Some(Self::Colorblind),
+            "mono" | "none" | "off" => Some(Self::Mono),
+            _ => None,
+        }
+    }
+
+    fn added(self) -> Color {
+        match self {
+            Self::RedGreen => Color::Green,
+            Self::Colorblind => Color::Indexed(39),
+            Self::Mono => Color::White,
+        }
+    }
+
+    fn removed(self) -> Color {
+        match self {
+            Self::RedGreen => Color::Red,
+            Self::Colorblind => Color::Indexed(208),
+            Self::Mono => Color::Gray,
+        }
+    }
+
+    fn added_tint(self) -> Color {
+        match self {
+            Self::RedGreen => Color::Indexed(22),
+            Self::Colorblind => Color::Indexed(17),
+            Self::Mono => Color::Indexed(236),
+        }
+    }
+
+    fn removed_tint(self) -> Color {
+        match self {
+            Self::RedGreen => Color::Indexed(52),
+            Self::Colorblind => Color::Indexed(94),
+            Self::Mono => Color::Indexed(233),
+        }
+    }
+}
+
+/// What a rendered row is, as far as diff styling is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffRowKind {
+    Added,
+    Removed,
+    Hunk,
+}
+
+/// How diff rows are drawn in the transcript, patch approvals, and tool
+/// approval previews.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffStyle {
+    pub palette: DiffPalette,
+    /// Draws the `+`/`-` marker bold and reversed, so the row kind reads
+    /// without color.
+    pub glyphs: bool,
+    /// Tints the background of added and removed rows.
+    pub tint: bool,
+}
+
+impl DiffStyle {
+    /// `VEX_DIFF_PALETTE` (default, colorblind, or mono), `VEX_DIFF_GLYPHS`,
+    /// and `VEX_DIFF_TINT`.
+    pub fn from_env() -> Self {
+        let flag = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| parse_bool_str(&value))
+                .unwrap_or(false)
+        };
+        Self {
+            palette: std::env::var(DIFF_PALETTE_ENV)
+                .ok()
+                .and_then(|value| DiffPalette::parse(&value))
+                .unwrap_or_default(),
+            glyphs: flag(DIFF_GLYPHS_ENV),
+            tint: flag(DIFF_TINT_ENV),
+        }
+    }
+
+    /// `colorblind, glyphs on, tint off`.
+    pub fn describe(&self) -> String {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+        format!(
+            "{}, glyphs {}, tint {}",
+            self.palette.name(),
+            on_off(self.glyphs),
+            on_off(self.tint)
+        )
+    }
+
+    pub fn style(&self, kind: DiffRowKind) -> Style {
+        match kind {
+            DiffRowKind::Added => {
+                let mut style = Style::default().fg(self.palette.added());
+                if self.palette == DiffPalette::Mono {
+                    style = style.add_modifier(Modifier::BOLD);
+                }
+                if self.tint {
+                    style = style.bg(self.palette.added_tint());
+                }
+                style
+            }
+            DiffRowKind::Removed => {
+                let mut style = Style::default().fg(self.palette.removed());
+                if self.palette == DiffPalette::Mono {
+                    style = style.add_modifier(Modifier::DIM);
+                }
+                if self.tint {
+                    style = style.bg(self.palette.removed_tint());
+                }
+                style
+            }
+            DiffRowKind::Hunk => Style::default().fg(Color::Cyan),
+        }
+    }
+
+    /// `row` as spans in `style`, with the diff marker emphasized when
+    /// glyph emphasis is on.
+    pub fn spans(&self, row: &str, kind: Option<DiffRowKind>, style: Style) -> Vec<Span<'static>> {
+        let marker = match kind {
+            Some(DiffRowKind::Added) => '+',
+            Some(DiffRowKind::Removed) => '-',
+            _ => return vec![Span::styled(row.to_string(), style)],
+        };
+        match row.find(marker).filter(|_| self.glyphs) {
+            Some(at) => {
+                let after = at + marker.len_utf8();
+                vec![
+                    Span::styled(row[..at].to_string(), style),
+                    Span::styled(
+                        row[at..after].to_string(),
+                        style.add_modifier(Modifier::BOLD | Modifier::REVERSED),
+                    ),
+                    Span::styled(row[after..].to_string(), style),
+                ]
+            }
+            None => vec![Span::styled(row.to_string(), style)],
+        }
+    }
+
+    pub fn line(&self, row: &str, kind: Option<DiffRowKind>, style: Style) -> Line<'static> {
+        Line::from(self.spans(row, kind, style)).style(style)
+    }
+}
+
+/// Classifies unified diff rows (`+x`, `-x`, `@@`) and the numbered rows of
+/// edit hunks (`  12 + x`).
+pub fn diff_row_kind(row: &str) -> Option<DiffRowKind> {
+    if row.starts_with("@@") {
+        return Some(DiffRowKind::Hunk);
+    }
+    if row.starts_with('+') && !row.starts_with("+++") {
+        return Some(DiffRowKind::Added);
+    }
+    if row.starts_with('-') && !row.starts_with("---") {
+        return Some(DiffRowKind::Removed);
+    }
+    let trimmed = row.trim_start();
+    if trimmed.starts_with("@@") && trimmed.ends_with("@@") {
+        return Some(DiffRowKind::Hunk);
+    }
+    let digits = trimmed.len()
+        - trimmed
+            .trim_start_matches(|ch: char| ch.is_ascii_digit())
+            .len();
+    if digits == 0 {
+        return None;
+    }
+    match trimmed[digits..].get(..3) {
+        Some(" + ") => Some(DiffRowKind::Added),
+        Some(" - ") => Some(DiffRowKind::Removed),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_are_classified_in_both_diff_formats() {
+        assert_eq!(diff_row_kind("+added"), Some(DiffRowKind::Added));
+        assert_eq!(diff_row_kind("-removed"), Some(DiffRowKind::Removed));
+        assert_eq!(diff_row_kind("+++ b/src/lib.rs"), None);
+        assert_eq!(diff_row_kind("@@ -1 +1 @@"), Some(DiffRowKind::Hunk));
+        assert_eq!(diff_row_kind("  @@ -3,2 +3,2 @@"), Some(DiffRowKind::Hunk));
+        assert_eq!(diff_row_kind("  12 + let x = 1;"), Some(DiffRowKind::Added));
+        assert_eq!(
+            diff_row_kind("  12 - let x = 0;"),
+            Some(DiffRowKind::Removed)
+        );
+        assert_eq!(diff_row_kind("  12   unchanged"), None);
+        assert_eq!(diff_row_kind("2024 was a year"), None);
+    }
+
+    #[test]
+    fn test_colorblind_palette_with_glyphs_and_tint() {
+        let style = DiffStyle {
+            palette: DiffPalette::Colorblind,
+            glyphs: true,
+            tint: true,
+        };
+        let added = style.style(DiffRowKind::Added);
+        assert_eq!(added.fg, Some(Color::Indexed(39)));
+        assert_eq!(added.bg, Some(Color::Indexed(17)));
+        assert_eq!(
+            style.style(DiffRowKind::Removed).fg,
+            Some(Color::Indexed(208))
+        );
+
+        let spans = style.spans("  3 - old", Some(DiffRowKind::Removed), Style::default());
+        let text: Vec<&str> = spans.iter().map(|span| span.content.as_ref()).collect();
+        assert_eq!(text, vec!["  3 ", "-", " old"]);
+        assert!(spans[1].style.add_modifier.contains(Modifier::REVERSED));
+        assert_eq!(style.describe(), "colorblind, glyphs on, tint on");
+        assert_eq!(
+            DiffPalette::parse("Blue-Orange"),
+            Some(DiffPalette::Colorblind)
+        );
+    }
+}
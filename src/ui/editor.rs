@@ -104,6 +104,15 @@ impl InputEditor {
         self.input_state.cursor = cursor + value.len();
     }
 
+    /// Replaces the whole draft (undoable), leaving the cursor at the end.
+    pub fn replace_buffer(&mut self, value: &str) {
+        self.input_state.history_index = None;
+        self.input_state.history_stash = None;
+        self.push_undo();
+        self.input_state.buffer = value.to_string();
+        self.input_state.cursor = value.len();
+    }
+
     pub fn backspace(&mut self) {
         let end = self.clamp_cursor_to_boundary_left(self.input_state.cursor);
         if end == 0 {
@@ -215,6 +224,9 @@ impl InputEditor {
         }
     }
 
+    // Collapsing the buffer checks into guards would let Ctrl-D and Esc fall
+    // through to the later arms.
+    #[allow(clippy::collapsible_match)]
     pub fn apply_key(&mut self, key: KeyEvent) -> InputAction {
         match key.code {
             KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
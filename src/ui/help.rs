@@ -0,0 +1,389 @@
+/// A slash command handled locally by the TUI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub args: &'static str,
+    pub description: &'static str,
+}
+
+/// A key binding, grouped by where it applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub category: &'static str,
+    pub keys: &'static str,
+    pub action: &'static str,
+}
+
+/// Every local slash command. The help screen is generated from this list,
+/// and a test checks that each entry is actually handled.
+pub const SLASH_COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "/help",
+        args: "",
+        description: "open this help screen (also /commands)",
+    },
+    CommandSpec {
+        name: "/history",
+        args: "[filter]",
+        description: "pick a previous prompt to re-run or edit",
+    },
+    CommandSpec {
+        name: "/cancel",
+        args: "[reason]",
+        description: "cancel the running turn; the reason is passed to the model",
+    },
+    CommandSpec {
+        name: "/attach",
+        args: "<path> | clear",
+        description: "attach a file or image to the next message (large files as outlines)",
+    },
+    CommandSpec {
+        name: "/add",
+        args: "<glob>... [--max-kb <n>]",
+        description: "attach every workspace file matching a glob, up to a total size",
+    },
+    CommandSpec {
+        name: "/share",
+        args: "[--tag <tag>] [path]",
+        description: "write a redacted session bundle, optionally of tagged turns only",
+    },
+    CommandSpec {
+        name: "/followup",
+        args: "<n>",
+        description: "insert suggested follow-up n into the input",
+    },
+    CommandSpec {
+        name: "/tag",
+        args: "[tag...]",
+        description: "tag the current turn for later search; no tags lists them",
+    },
+    CommandSpec {
+        name: "/diffcontext",
+        args: "[preview|history|transcript] [lines]",
+        description:
+            "show or set diff context lines for approvals, edit results, and the transcript",
+    },
+    CommandSpec {
+        name: "/diffstyle",
+        args: "[default|colorblind|mono] | [glyphs|tint] [on|off]",
+        description: "show or set the diff palette, marker emphasis, and background tints",
+    },
+    CommandSpec {
+        name: "/undo",
+        args: "",
+        description: "reverse the newest file change (workspaces without version control)",
+    },
+    CommandSpec {
+        name: "/profile",
+        args: "",
+        description: "show the active profile's settings",
+    },
+    CommandSpec {
+        name: "/memories",
+        args: "[forget <id>]",
+        description: "list facts saved with the remember tool, or delete one",
+    },
+    CommandSpec {
+        name: "/toolpool",
+        args: "",
+        description: "show running and queued tool calls and queue wait times",
+    },
+    CommandSpec {
+        name: "/presets",
+        args: "",
+        description: "open the prompt preset picker",
+    },
+    CommandSpec {
+        name: "/resume",
+        args: "[n|id|#tag]",
+        description: "list saved sessions or those tagged #tag, or reload one by number or id",
+    },
+    CommandSpec {
+        name: "/changes",
+        args: "[n|id]",
+        description: "list what each turn changed, here or in a saved session",
+    },
+    CommandSpec {
+        name: "/sessions",
+        args: "[tag]",
+        description: "pick a saved session to resume, filtered by tag",
+    },
+    CommandSpec {
+        name: "/rebase-assist",
+        args: "[upstream|continue|abort]",
+        description: "resolve rebase conflicts with the model, one stop at a time",
+    },
+    CommandSpec {
+        name: "/audit",
+        args: "[count]",
+        description: "show the newest tool audit records",
+    },
+    CommandSpec {
+        name: "/copy",
+        args: "[result|diff] [N|last]",
+        description: "copy a recent tool result or diff to the clipboard; no argument lists them",
+    },
+    CommandSpec {
+        name: "/context",
+        args: "",
+        description: "show what the next request holds and what pruning removes next",
+    },
+    CommandSpec {
+        name: "/release-notes",
+        args: "",
+        description: "show the latest release's changelog",
+    },
+    CommandSpec {
+        name: "/thinking",
+        args: "<expand|collapse|hide|toggle> [last|all]",
+        description: "re-render thinking blocks",
+    },
+];
+
+/// Key bindings of the full-screen TUI, by category.
+pub const KEY_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        category: "Input",
+        keys: "Enter",
+        action: "send the message",
+    },
+    KeyBinding {
+        category: "Input",
+        keys: "Shift+Enter / Ctrl+J",
+        action: "insert a newline",
+    },
+    KeyBinding {
+        category: "Input",
+        keys: "Up / Down",
+        action: "recall previous inputs",
+    },
+    KeyBinding {
+        category: "Input",
+        keys: "Alt+Up / Alt+Down",
+        action: "scroll a draft taller than the input box",
+    },
+    KeyBinding {
+        category: "Input",
+        keys: "Alt+1 / Alt+2 / Alt+3",
+        action: "insert a suggested follow-up (VEX_FOLLOW_UPS=on)",
+    },
+    KeyBinding {
+        category: "Input",
+        keys: "Ctrl+Z / Ctrl+Y",
+        action: "undo / redo",
+    },
+    KeyBinding {
+        category: "Input",
+        keys: "Ctrl+C",
+        action: "cancel the turn; twice when idle to exit",
+    },
+    KeyBinding {
+        category: "Input",
+        keys: "Ctrl+D",
+        action: "exit when the input is empty",
+    },
+    KeyBinding {
+        category: "Transcript",
+        keys: "PgUp / PgDn",
+        action: "scroll by a page",
+    },
+    KeyBinding {
+        category: "Transcript",
+        keys: "Ctrl+Up / Ctrl+Down",
+        action: "scroll by a line",
+    },
+    KeyBinding {
+        category: "Transcript",
+        keys: "Ctrl+Home / Ctrl+End",
+        action: "jump to top / follow the bottom",
+    },
+    KeyBinding {
+        category: "Transcript",
+        keys: "Ctrl+T",
+        action: "expand or collapse the last thinking block",
+    },
+    KeyBinding {
+        category: "Transcript",
+        keys: "F1 / ? (empty input)",
+        action: "open this help screen",
+    },
+    KeyBinding {
+        category: "Approvals",
+        keys: "1 / y",
+        action: "approve once",
+    },
+    KeyBinding {
+        category: "Approvals",
+        keys: "2 / a",
+        action: "approve for the rest of the session",
+    },
+    KeyBinding {
+        category: "Approvals",
+        keys: "3 / n / Esc",
+        action: "deny",
+    },
+    KeyBinding {
+        category: "Approvals",
+        keys: "4 / r",
+        action: "deny with a reason",
+    },
+    KeyBinding {
+        category: "Pickers",
+        keys: "Up / Down / Enter",
+        action: "select and confirm",
+    },
+    KeyBinding {
+        category: "Pickers",
+        keys: "/ (history)",
+        action: "fuzzy-filter prompts",
+    },
+    KeyBinding {
+        category: "Pickers",
+        keys: "e (history)",
+        action: "load the prompt into the input for editing",
+    },
+    KeyBinding {
+        category: "Pickers",
+        keys: "Esc / q",
+        action: "close",
+    },
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HelpLine {
+    Heading(String),
+    Entry { key: String, description: String },
+    Blank,
+}
+
+/// Help screen content: keybindings by category, slash commands, then
+/// `highlights` (current config). A non-empty `query` keeps only entries
+/// whose key or description contains it, case-insensitively, and drops
+/// headings left with no entries.
+pub fn help_lines(query: &str, highlights: &[(String, String)]) -> Vec<HelpLine> {
+    let query = query.trim().to_lowercase();
+    let matches = |key: &str, description: &str| {
+        query.is_empty()
+            || key.to_lowercase().contains(&query)
+            || description.to_lowercase().contains(&query)
+    };
+
+    let mut sections: Vec<(String, Vec<(String, String)>)> = Vec::new();
+    for binding in KEY_BINDINGS {
+        let heading = format!("Keys: {}", binding.category);
+        if sections.last().map(|(name, _)| name) != Some(&heading) {
+            sections.push((heading, Vec::new()));
+        }
+        if matches(binding.keys, binding.action) {
+            if let Some((_, entries)) = sections.last_mut() {
+                entries.push((binding.keys.to_string(), binding.action.to_string()));
+            }
+        }
+    }
+    sections.push((
+        "Commands".to_string(),
+        SLASH_COMMANDS
+            .iter()
+            .map(|command| {
+                let usage = if command.args.is_empty() {
+                    command.name.to_string()
+                } else {
+                    format!("{} {}", command.name, command.args)
+                };
+                (usage, command.description.to_string())
+            })
+            .filter(|(usage, description)| matches(usage, description))
+            .collect(),
+    ));
+    sections.push((
+        "Current config".to_string(),
+        highlights
+            .iter()
+            .filter(|(key, value)| matches(key, value))
+            .cloned()
+            .collect(),
+    ));
+
+    let mut lines = Vec::new();
+    for (heading, entries) in sections {
+        if entries.is_empty() {
+            continue;
+        }
+        if !lines.is_empty() {
+            lines.push(HelpLine::Blank);
+        }
+        lines.push(HelpLine::Heading(heading));
+        lines.extend(
+            entries
+                .into_iter()
+                .map(|(key, description)| HelpLine::Entry { key, description }),
+        );
+    }
+    lines
+}
+
+/// Plain-text help for surfaces without the TUI.
+pub fn render_help_text(lines: &[HelpLine]) -> String {
+    let key_width = lines
+        .iter()
+        .filter_map(|line| match line {
+            HelpLine::Entry { key, .. } => Some(key.chars().count()),
+            _ => None,
+        })
+        .max()
+        .unwrap_or_default();
+    lines
+        .iter()
+        .map(|line| match line {
+            HelpLine::Heading(heading) => format!("{heading}:"),
+            HelpLine::Entry { key, description } => {
+                format!("  {key:<key_width$}  {description}")
+            }
+            HelpLine::Blank => String::new(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_help_lines_filter_across_sections() {
+        let highlights = vec![("model".to_string(), "claude-test".to_string())];
+        let all = help_lines("", &highlights);
+        assert_eq!(all[0], HelpLine::Heading("Keys: Input".to_string()));
+        assert!(all.contains(&HelpLine::Heading("Commands".to_string())));
+        assert_eq!(
+            all.last(),
+            Some(&HelpLine::Entry {
+                key: "model".to_string(),
+                description: "claude-test".to_string()
+            })
+        );
+
+        let filtered = help_lines("THINKING", &highlights);
+        assert_eq!(
+            filtered,
+            vec![
+                HelpLine::Heading("Keys: Transcript".to_string()),
+                HelpLine::Entry {
+                    key: "Ctrl+T".to_string(),
+                    description: "expand or collapse the last thinking block".to_string()
+                },
+                HelpLine::Blank,
+                HelpLine::Heading("Commands".to_string()),
+                HelpLine::Entry {
+                    key: "/thinking <expand|collapse|hide|toggle> [last|all]".to_string(),
+                    description: "re-render thinking blocks".to_string()
+                },
+            ]
+        );
+        assert!(help_lines("no such binding", &highlights).is_empty());
+        assert_eq!(
+            render_help_text(&help_lines("claude", &highlights)),
+            "Current config:\n  model  claude-test"
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use crate::util::parse_bool_str;
+use ratatui::buffer::{Buffer, Cell};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub const HYPERLINKS_ENV: &str = "VEX_HYPERLINKS";
+pub const OSC8_CLOSE: &str = "\x1b]8;;\x1b\\";
+/// Path lookups are cached this long, so files created meanwhile get links.
+const PATH_CACHE_TTL: Duration = Duration::from_secs(5);
+const MAX_CACHED_PATHS: usize = 4_096;
+/// `TERM_PROGRAM` values of terminals that open OSC 8 links.
+const LINKING_TERM_PROGRAMS: [&str; 6] = [
+    "iTerm.app",
+    "WezTerm",
+    "vscode",
+    "ghostty",
+    "WarpTerminal",
+    "Hyper",
+];
+const LINKING_TERMS: [&str; 5] = [
+    "xterm-kitty",
+    "xterm-ghostty",
+    "wezterm",
+    "foot",
+    "alacritty",
+];
+/// Characters that end a link candidate besides whitespace.
+const LINK_DELIMITERS: [char; 12] = ['"', '\'', '`', '<', '>', '(', ')', '[', ']', '{', '}', '|'];
+
+/// Whether to draw paths and URLs as OSC 8 hyperlinks. `VEX_HYPERLINKS=on`
+/// or `off` overrides detection of terminals known to support them.
+pub fn hyperlinks_enabled() -> bool {
+    classify_hyperlinks(|key| std::env::var(key).ok())
+}
+
+fn classify_hyperlinks(var: impl Fn(&str) -> Option<String>) -> bool {
+    let var = |key: &str| var(key).filter(|value| !value.trim().is_empty());
+    if let Some(enabled) = var(HYPERLINKS_ENV).and_then(|value| parse_bool_str(&value)) {
+        return enabled;
+    }
+    // Multiplexers drop or mangle OSC 8 unless configured to pass it on.
+    if var("TMUX").is_some() || var("STY").is_some() {
+        return false;
+    }
+    if var("TERM_PROGRAM").is_some_and(|program| LINKING_TERM_PROGRAMS.contains(&program.trim())) {
+        return true;
+    }
+    if var("WT_SESSION").is_some() || var("KONSOLE_VERSION").is_some() {
+        return true;
+    }
+    if var("VTE_VERSION")
+        .and_then(|version| version.trim().parse::<u32>().ok())
+        .is_some_and(|version| version >= 5_000)
+    {
+        return true;
+    }
+    var("TERM").is_some_and(|term| {
+        LINKING_TERMS
+            .iter()
+            .any(|known| term == *known || term.starts_with(&format!("{known}-")))
+    })
+}
+
+pub fn osc8_open(url: &str) -> String {
+    format!("\x1b]8;;{url}\x1b\\")
+}
+
+/// A link target found in a line of text; `start..end` are byte offsets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub start: usize,
+    pub end: usize,
+    pub url: String,
+}
+
+/// A link found on screen with copies of the cells that show it.
+#[derive(Debug, Clone)]
+pub struct DrawnLink {
+    pub url: String,
+    pub cells: Vec<(u16, u16, Cell)>,
+}
+
+/// Finds URLs and paths of existing files in text, resolving relative paths
+/// against the workspace.
+#[derive(Debug)]
+pub struct LinkFinder {
+    workspace: PathBuf,
+    files: HashMap<String, Option<String>>,
+    cached_at: Instant,
+}
+
+impl LinkFinder {
+    pub fn new(workspace: PathBuf) -> Self {
+        Self {
+            workspace,
+            files: HashMap::new(),
+            cached_at: Instant::now(),
+        }
+    }
+
+    pub fn find(&mut self, text: &str) -> Vec<Link> {
+        if self.cached_at.elapsed() >= PATH_CACHE_TTL || self.files.len() >= MAX_CACHED_PATHS {
+            self.files.clear();
+            self.cached_at = Instant::now();
+        }
+        let mut links = Vec::new();
+        let mut start = None;
+        for (index, ch) in text.char_indices().chain([(text.len(), ' ')]) {
+            let ends = ch.is_whitespace() || LINK_DELIMITERS.contains(&ch);
+            match (start, ends) {
+                (None, false) => start = Some(index),
+                (Some(from), true) => {
+                    start = None;
+                    if let Some(link) = self.candidate(&text[from..index], from) {
+                        links.push(link);
+                    }
+                }
+                _ => {}
+            }
+        }
+        links
+    }
+
+    fn candidate(&mut self, token: &str, offset: usize) -> Option<Link> {
+        let token = token.trim_end_matches(['.', ',', ':', ';', '!', '?']);
+        if token.starts_with("https://") || token.starts_with("http://") {
+            return (token.len() > "https://".len()).then(|| Link {
+                start: offset,
+                end: offset + token.len(),
+                url: token.to_string(),
+            });
+        }
+        // `src/lib.rs:12:5` links the file; the location stays plain text.
+        let path = token.split(':').next().unwrap_or_default();
+        if path.len() < 3
+            || !(path.contains('/') || path.contains('.'))
+            || !path
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || "_./-+@".contains(ch))
+        {
+            return None;
+        }
+        let workspace = &self.workspace;
+        let url = self
+            .files
+            .entry(path.to_string())
+            .or_insert_with(|| file_url(workspace, path))
+            .clone()?;
+        Some(Link {
+            start: offset,
+            end: offset + path.len(),
+            url,
+        })
+    }
+
+    /// `text` with each link wrapped in OSC 8 escapes.
+    pub fn linkify(&mut self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut copied = 0;
+        for link in self.find(text) {
+            out.push_str(&text[copied..link.start]);
+            out.push_str(&osc8_open(&link.url));
+            out.push_str(&text[link.start..link.end]);
+            out.push_str(OSC8_CLOSE);
+            copied = link.end;
+        }
+        out.push_str(&text[copied..]);
+        out
+    }
+
+    /// Links drawn in `buffer`.
+    pub fn buffer_links(&mut self, buffer: &Buffer) -> Vec<DrawnLink> {
+        let area = buffer.area;
+        let mut links = Vec::new();
+        for y in area.top()..area.bottom() {
+            // One byte per column; cells that cannot be part of a link
+            // (wide or non-ASCII symbols) become spaces.
+            let row: String = (area.left()..area.right())
+                .map(|x| {
+                    let symbol = buffer[(x, y)].symbol();
+                    match symbol.as_bytes() {
+                        [byte] if byte.is_ascii_graphic() => *byte as char,
+                        _ => ' ',
+                    }
+                })
+                .collect();
+            for link in self.find(&row) {
+                let cells = (link.start..link.end)
+                    .map(|column| {
+                        let x = area.left() + column as u16;
+                        (x, y, buffer[(x, y)].clone())
+                    })
+                    .collect();
+                links.push(DrawnLink {
+                    url: link.url,
+                    cells,
+                });
+            }
+        }
+        links
+    }
+}
+
+fn file_url(workspace: &Path, path: &str) -> Option<String> {
+    let resolved = workspace.join(path);
+    if !resolved.is_file() {
+        return None;
+    }
+    let absolute = resolved.canonicalize().unwrap_or(resolved);
+    let mut url = "file://".to_string();
+    for byte in absolute.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    Some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detection_prefers_the_toggle_and_skips_multiplexers() {
+        let detect = |vars: &[(&str, &str)]| {
+            classify_hyperlinks(|key| {
+                vars.iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+        assert!(detect(&[("TERM_PROGRAM", "WezTerm")]));
+        assert!(detect(&[("TERM", "xterm-kitty")]));
+        assert!(detect(&[("VTE_VERSION", "7600")]));
+        assert!(!detect(&[("VTE_VERSION", "4800")]));
+        assert!(!detect(&[("TERM", "xterm-256color")]));
+        assert!(!detect(&[
+            ("TERM_PROGRAM", "iTerm.app"),
+            ("TMUX", "/tmp/t,1,0")
+        ]));
+        assert!(detect(&[("TMUX", "/tmp/t,1,0"), (HYPERLINKS_ENV, "on")]));
+        assert!(!detect(&[
+            ("TERM_PROGRAM", "vscode"),
+            (HYPERLINKS_ENV, "off")
+        ]));
+    }
+
+    #[test]
+    fn test_links_cover_urls_and_existing_files() {
+        let temp = TempDir::new().expect("temp");
+        fs::create_dir_all(temp.path().join("src/app")).expect("dirs");
+        fs::write(temp.path().join("src/app/mod.rs"), "").expect("file");
+        let root = temp.path().canonicalize().expect("canonical");
+        let mut finder = LinkFinder::new(temp.path().to_path_buf());
+
+        let text = "edited src/app/mod.rs:12, see (https://example.com/docs). missing.rs";
+        let links = finder.find(text);
+        assert_eq!(links.len(), 2);
+        assert_eq!(&text[links[0].start..links[0].end], "src/app/mod.rs");
+        assert_eq!(
+            links[0].url,
+            format!("file://{}/src/app/mod.rs", root.display())
+        );
+        assert_eq!(links[1].url, "https://example.com/docs");
+        assert_eq!(
+            finder.linkify("open https://example.com now"),
+            format!(
+                "open {}https://example.com{OSC8_CLOSE} now",
+                osc8_open("https://example.com")
+            )
+        );
+
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 24, 2));
+        buffer.set_string(0, 1, "│ src/app/mod.rs", ratatui::style::Style::default());
+        let drawn = finder.buffer_links(&buffer);
+        assert_eq!(drawn.len(), 1);
+        let cells = &drawn[0].cells;
+        assert_eq!((cells[0].0, cells[0].1, cells.len()), (2, 1, 14));
+        assert_eq!(cells[0].2.symbol(), "s");
+    }
+}
@@ -0,0 +1,293 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use std::path::PathBuf;
+
+/// Inline images are scaled down to fit this many columns and rows.
+const MAX_IMAGE_COLS: u32 = 48;
+const MAX_IMAGE_ROWS: u32 = 16;
+/// Assumed pixel size of a terminal cell when sizing an image in cells.
+const CELL_WIDTH_PX: u32 = 10;
+const CELL_HEIGHT_PX: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    WebP,
+    Bmp,
+}
+
+impl ImageFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Png => "PNG",
+            Self::Jpeg => "JPEG",
+            Self::Gif => "GIF",
+            Self::WebP => "WebP",
+            Self::Bmp => "BMP",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Format and pixel size from the file header, or `None` when `bytes` is
+/// not an image this reader knows.
+pub fn image_info(bytes: &[u8]) -> Option<ImageInfo> {
+    let be16 = |at: usize| {
+        Some(u32::from(u16::from_be_bytes([
+            *bytes.get(at)?,
+            *bytes.get(at + 1)?,
+        ])))
+    };
+    let le16 = |at: usize| {
+        Some(u32::from(u16::from_le_bytes([
+            *bytes.get(at)?,
+            *bytes.get(at + 1)?,
+        ])))
+    };
+    let be32 = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let le24 = |at: usize| {
+        Some(u32::from_le_bytes([
+            *bytes.get(at)?,
+            *bytes.get(at + 1)?,
+            *bytes.get(at + 2)?,
+            0,
+        ]))
+    };
+    let (format, width, height) = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        (ImageFormat::Png, be32(16)?, be32(20)?)
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        (ImageFormat::Gif, le16(6)?, le16(8)?)
+    } else if bytes.starts_with(b"BM") {
+        let width = i32::from_le_bytes(bytes.get(18..22)?.try_into().ok()?);
+        let height = i32::from_le_bytes(bytes.get(22..26)?.try_into().ok()?);
+        (
+            ImageFormat::Bmp,
+            width.unsigned_abs(),
+            height.unsigned_abs(),
+        )
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        match bytes.get(12..16)? {
+            b"VP8 " => (ImageFormat::WebP, le16(26)? & 0x3fff, le16(28)? & 0x3fff),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+                (
+                    ImageFormat::WebP,
+                    (bits & 0x3fff) + 1,
+                    ((bits >> 14) & 0x3fff) + 1,
+                )
+            }
+            b"VP8X" => (ImageFormat::WebP, le24(24)? + 1, le24(27)? + 1),
+            _ => return None,
+        }
+    } else if bytes.starts_with(&[0xff, 0xd8]) {
+        // Walk the segments to the first start-of-frame marker.
+        let mut at = 2;
+        loop {
+            while bytes.get(at) == Some(&0xff) && bytes.get(at + 1) == Some(&0xff) {
+                at += 1;
+            }
+            if *bytes.get(at)? != 0xff {
+                return None;
+            }
+            let marker = *bytes.get(at + 1)?;
+            if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+                break (ImageFormat::Jpeg, be16(at + 7)?, be16(at + 5)?);
+            }
+            at += 2 + be16(at + 2)? as usize;
+        }
+    } else {
+        return None;
+    };
+    (width > 0 && height > 0).then_some(ImageInfo {
+        format,
+        width,
+        height,
+    })
+}
+
+/// An image shown in the transcript: a placeholder line naming it, followed
+/// by blank rows the frontend draws the image over when the terminal can.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlineImage {
+    pub path: PathBuf,
+    pub label: String,
+    pub info: ImageInfo,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+impl InlineImage {
+    /// `label` is how the transcript names the image, usually its
+    /// workspace-relative path. Images are never scaled up.
+    pub fn new(path: PathBuf, label: String, info: ImageInfo) -> Self {
+        let scale = f64::min(
+            1.0,
+            f64::min(
+                f64::from(MAX_IMAGE_COLS * CELL_WIDTH_PX) / f64::from(info.width),
+                f64::from(MAX_IMAGE_ROWS * CELL_HEIGHT_PX) / f64::from(info.height),
+            ),
+        );
+        let cells = |pixels: u32, cell: u32, max: u32| {
+            ((f64::from(pixels) * scale / f64::from(cell)).ceil() as u32).clamp(1, max) as u16
+        };
+        Self {
+            path,
+            label,
+            cols: cells(info.width, CELL_WIDTH_PX, MAX_IMAGE_COLS),
+            rows: cells(info.height, CELL_HEIGHT_PX, MAX_IMAGE_ROWS),
+            info,
+        }
+    }
+
+    /// The placeholder line: path, format, and pixel size.
+    pub fn marker(&self) -> String {
+        format!(
+            "[image: {} ({}, {}x{})]",
+            self.label,
+            self.info.format.name(),
+            self.info.width,
+            self.info.height
+        )
+    }
+
+    /// The transcript entry; `reserve_rows` adds the blank rows the image is
+    /// drawn over.
+    pub fn history_entry(&self, reserve_rows: bool) -> String {
+        let mut entry = self.marker();
+        if reserve_rows {
+            entry.push_str(&"\n".repeat(self.rows.into()));
+        }
+        entry
+    }
+}
+
+/// Where an image goes on screen, in cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImagePlacement {
+    pub path: PathBuf,
+    pub format: ImageFormat,
+    pub x: u16,
+    pub y: u16,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+/// Finds the placeholder of each image drawn in `area` of `buffer` and
+/// places the image on the rows below it. Images whose rows do not all fit
+/// in `area` are left as placeholders.
+pub fn find_placements(buffer: &Buffer, area: Rect, images: &[InlineImage]) -> Vec<ImagePlacement> {
+    let area = area.intersection(buffer.area);
+    if images.is_empty() || area.is_empty() {
+        return Vec::new();
+    }
+    let markers: Vec<(String, &InlineImage)> =
+        images.iter().map(|image| (image.marker(), image)).collect();
+    let mut placements = Vec::new();
+    for y in area.top()..area.bottom() {
+        // One byte per column, as in hyperlink detection; labels with wide
+        // or non-ASCII characters are never matched.
+        let row: String = (area.left()..area.right())
+            .map(|x| match buffer[(x, y)].symbol().as_bytes() {
+                [byte] if byte.is_ascii_graphic() => *byte as char,
+                _ => ' ',
+            })
+            .collect();
+        let Some((column, image)) = markers
+            .iter()
+            .find_map(|(marker, image)| row.find(marker.as_str()).map(|column| (column, *image)))
+        else {
+            continue;
+        };
+        let x = area.left() + column as u16;
+        if y + image.rows >= area.bottom() || x + image.cols > area.right() {
+            continue;
+        }
+        placements.push(ImagePlacement {
+            path: image.path.clone(),
+            format: image.info.format,
+            x,
+            y: y + 1,
+            cols: image.cols,
+            rows: image.rows,
+        });
+    }
+    placements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::text::Line;
+    use ratatui::widgets::{Paragraph, Widget};
+
+    #[test]
+    fn test_reads_image_sizes_and_places_images_below_their_placeholder() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend(1280u32.to_be_bytes());
+        png.extend(720u32.to_be_bytes());
+        let info = image_info(&png).expect("png");
+        assert_eq!(
+            (info.format, info.width, info.height),
+            (ImageFormat::Png, 1280, 720)
+        );
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend([32, 0, 16, 0]);
+        assert_eq!(
+            image_info(&gif).map(|info| (info.width, info.height)),
+            Some((32, 16))
+        );
+        let jpeg = [
+            0xff, 0xd8, 0xff, 0xe0, 0, 4, 0, 0, 0xff, 0xc0, 0, 17, 8, 0, 50, 0, 100,
+        ];
+        assert_eq!(
+            image_info(&jpeg).map(|info| (info.width, info.height)),
+            Some((100, 50))
+        );
+        assert_eq!(image_info(b"not an image"), None);
+
+        let image = InlineImage::new(PathBuf::from("/w/shot.png"), "shot.png".to_string(), info);
+        assert_eq!((image.cols, image.rows), (48, 14));
+        let small = InlineImage::new(
+            PathBuf::from("/w/icon.gif"),
+            "icon.gif".to_string(),
+            image_info(&gif).expect("gif"),
+        );
+        assert_eq!((small.cols, small.rows), (4, 1));
+        assert_eq!(image.marker(), "[image: shot.png (PNG, 1280x720)]");
+        assert_eq!(image.history_entry(true).split('\n').count(), 15);
+
+        let area = Rect::new(0, 0, 60, 20);
+        let mut buffer = Buffer::empty(area);
+        let mut lines = vec![
+            Line::from("earlier"),
+            Line::from(format!("1 | {}", image.marker())),
+        ];
+        lines.extend((0..14).map(|_| Line::from("")));
+        Paragraph::new(lines.clone()).render(area, &mut buffer);
+        let placements = find_placements(&buffer, area, &[image.clone(), small]);
+        assert_eq!(
+            placements,
+            vec![ImagePlacement {
+                path: PathBuf::from("/w/shot.png"),
+                format: ImageFormat::Png,
+                x: 4,
+                y: 2,
+                cols: 48,
+                rows: 14,
+            }]
+        );
+        // Cut off at the bottom of the pane: left as a placeholder.
+        let short = Rect::new(0, 0, 60, 10);
+        let mut buffer = Buffer::empty(short);
+        Paragraph::new(lines).render(short, &mut buffer);
+        assert!(find_placements(&buffer, short, &[image]).is_empty());
+    }
+}
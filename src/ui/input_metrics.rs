@@ -1,5 +1,7 @@
 use unicode_width::UnicodeWidthChar;
 
+const ZERO_WIDTH_JOINER: char = '\u{200d}';
+
 pub fn wrap_input_lines(input: &str, width: usize) -> Vec<String> {
     let width = width.max(1);
     let mut lines = vec![String::new()];
@@ -32,10 +34,15 @@ pub fn wrap_input_lines(input: &str, width: usize) -> Vec<String> {
     lines
 }
 
+/// Returns the cursor's wrapped row and on-screen column. Columns follow
+/// display width, so double-width glyphs count as two cells, and rows that
+/// contain right-to-left text are measured in visual order (see
+/// [`visual_line`]) so the caret lands beside the glyph it precedes.
 pub fn cursor_row_col(input: &str, cursor_byte: usize, width: usize) -> (usize, usize) {
     let width = width.max(1);
     let mut row = 0usize;
     let mut col = 0usize;
+    let mut chars_in_row = 0usize;
     let cursor_byte = clamp_to_char_boundary_left(input, cursor_byte);
 
     for (idx, ch) in input.char_indices() {
@@ -48,35 +55,178 @@ pub fn cursor_row_col(input: &str, cursor_byte: usize, width: usize) -> (usize,
         if ch == '\n' {
             row += 1;
             col = 0;
+            chars_in_row = 0;
             continue;
         }
         let ch_width = char_display_width(ch);
         if col + ch_width > width && col > 0 {
             row += 1;
             col = 0;
+            chars_in_row = 0;
         }
         col += ch_width;
+        chars_in_row += 1;
     }
 
     if col >= width {
-        row += 1;
-        col = 0;
+        return (row + 1, 0);
+    }
+
+    if let Some(line) = wrap_input_lines(input, width).get(row) {
+        if line.chars().any(is_rtl_char) {
+            col = visual_caret_col(line, chars_in_row);
+        }
     }
 
     (row, col)
 }
 
+/// True for strong right-to-left characters (Hebrew, Arabic, Syriac, Thaana,
+/// N'Ko and their presentation forms).
+pub fn is_rtl_char(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF | 0x10800..=0x10FFF | 0x1E800..=0x1EFFF
+    )
+}
+
+/// Reorders one wrapped row for display. Right-to-left runs are reversed as
+/// a unit while digit groups inside them keep their left-to-right order; this
+/// is a simplified form of the Unicode bidi algorithm that is sufficient for
+/// single-paragraph prompt input.
+pub fn visual_line(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    visual_order(&chars).into_iter().map(|i| chars[i]).collect()
+}
+
+fn visual_caret_col(line: &str, char_index: usize) -> usize {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.is_empty() {
+        return 0;
+    }
+    let rtl = rtl_run_mask(&chars);
+    let mut starts = vec![0usize; chars.len()];
+    let mut used = 0usize;
+    for logical in visual_order(&chars) {
+        starts[logical] = used;
+        used += char_display_width(chars[logical]);
+    }
+
+    let reads_rtl = |i: usize| rtl[i] && !chars[i].is_ascii_digit();
+    match chars.get(char_index) {
+        Some(&ch) if reads_rtl(char_index) => starts[char_index] + char_display_width(ch),
+        Some(_) => starts[char_index],
+        None if reads_rtl(chars.len() - 1) => starts[chars.len() - 1],
+        None => used,
+    }
+}
+
+fn visual_order(chars: &[char]) -> Vec<usize> {
+    let rtl = rtl_run_mask(chars);
+    let mut order = Vec::with_capacity(chars.len());
+    let mut i = 0usize;
+    while i < chars.len() {
+        if !rtl[i] {
+            order.push(i);
+            i += 1;
+            continue;
+        }
+        let run_end = (i..chars.len()).find(|&j| !rtl[j]).unwrap_or(chars.len());
+        let mut units: Vec<Vec<usize>> = Vec::new();
+        for j in i..run_end {
+            let extends_digits = chars[j].is_ascii_digit()
+                && j > i
+                && chars[j - 1].is_ascii_digit()
+                && units.last().is_some();
+            match units.last_mut() {
+                Some(unit) if extends_digits => unit.push(j),
+                _ => units.push(vec![j]),
+            }
+        }
+        order.extend(units.into_iter().rev().flatten());
+        i = run_end;
+    }
+    order
+}
+
+/// Marks characters that belong to a right-to-left run: everything from a
+/// strong RTL character up to the last strong RTL character before the next
+/// strong left-to-right one.
+fn rtl_run_mask(chars: &[char]) -> Vec<bool> {
+    let mut mask = vec![false; chars.len()];
+    let mut run_start: Option<usize> = None;
+    let mut last_rtl = 0usize;
+    for (i, &ch) in chars.iter().enumerate() {
+        if is_rtl_char(ch) {
+            run_start.get_or_insert(i);
+            last_rtl = i;
+        } else if ch.is_alphabetic() {
+            if let Some(start) = run_start.take() {
+                mask[start..=last_rtl].iter_mut().for_each(|m| *m = true);
+            }
+        }
+    }
+    if let Some(start) = run_start {
+        mask[start..=last_rtl].iter_mut().for_each(|m| *m = true);
+    }
+    mask
+}
+
+/// Marker appended by [`truncate_with_ellipsis`] when text was cut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ellipsis {
+    None,
+    /// `...`, for surfaces that must stay ASCII, like the status line.
+    Ascii,
+    /// `…`, one cell wide.
+    Unicode,
+}
+
+impl Ellipsis {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Ascii => "...",
+            Self::Unicode => "\u{2026}",
+        }
+    }
+}
+
+/// The longest prefix of `text` that fits in `max_width` cells. A
+/// double-width glyph that would straddle the limit is dropped rather than
+/// split, combining marks stay with their base character, and a trailing
+/// zero-width joiner is removed so no half emoji sequence is left behind.
 pub fn truncate_to_display_width(text: &str, max_width: usize) -> String {
     let mut out = String::new();
     let mut used = 0usize;
     for ch in text.chars() {
         let ch_width = char_display_width(ch);
-        if used + ch_width > max_width && used > 0 {
+        if used + ch_width > max_width {
             break;
         }
         out.push(ch);
         used += ch_width;
     }
+    while out.ends_with(ZERO_WIDTH_JOINER) {
+        out.pop();
+    }
+    out
+}
+
+/// `text` unchanged when it fits in `max_width` cells; otherwise cut so that
+/// it plus `ellipsis` fits. When even the ellipsis does not fit, the text is
+/// cut without one.
+pub fn truncate_with_ellipsis(text: &str, max_width: usize, ellipsis: Ellipsis) -> String {
+    if display_width(text) <= max_width {
+        return text.to_string();
+    }
+    let marker = ellipsis.as_str();
+    let marker_width = display_width(marker);
+    if marker_width == 0 || marker_width >= max_width {
+        return truncate_to_display_width(text, max_width);
+    }
+    let mut out = truncate_to_display_width(text, max_width - marker_width);
+    out.push_str(marker);
     out
 }
 
@@ -95,3 +245,95 @@ pub fn clamp_to_char_boundary_left(input: &str, cursor: usize) -> usize {
     }
     cursor
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_width_cursor_columns_count_two_cells() {
+        let input = "a世界b";
+        let fixtures = [(0, 0), (1, 1), (4, 3), (7, 5), (8, 6)];
+        for (byte, expected_col) in fixtures {
+            assert_eq!(cursor_row_col(input, byte, 20), (0, expected_col));
+        }
+    }
+
+    #[test]
+    fn double_width_glyph_wraps_instead_of_splitting_at_edge() {
+        let input = "abc世";
+        assert_eq!(wrap_input_lines(input, 4), vec!["abc", "世"]);
+        assert_eq!(cursor_row_col(input, 3, 4), (0, 3));
+        assert_eq!(cursor_row_col(input, input.len(), 4), (1, 2));
+    }
+
+    #[test]
+    fn truncation_never_splits_wide_glyphs_or_emoji_sequences() {
+        assert_eq!(truncate_to_display_width("ab世界", 3), "ab");
+        assert_eq!(truncate_to_display_width("世界", 1), "");
+        assert_eq!(truncate_to_display_width("e\u{301}x", 1), "e\u{301}");
+        let family = "\u{1f468}\u{200d}\u{1f469}";
+        assert_eq!(truncate_to_display_width(family, 3), "\u{1f468}");
+
+        assert_eq!(
+            truncate_with_ellipsis("short", 5, Ellipsis::Unicode),
+            "short"
+        );
+        assert_eq!(
+            truncate_with_ellipsis("漢字テキスト", 6, Ellipsis::Unicode),
+            "漢字\u{2026}"
+        );
+        assert_eq!(
+            truncate_with_ellipsis("abcdefgh", 6, Ellipsis::Ascii),
+            "abc..."
+        );
+        assert_eq!(
+            truncate_with_ellipsis("abcdefgh", 3, Ellipsis::Ascii),
+            "abc"
+        );
+        assert_eq!(
+            truncate_with_ellipsis("abcdefgh", 4, Ellipsis::None),
+            "abcd"
+        );
+        for width in 0..12 {
+            let cut = truncate_with_ellipsis("a漢b字c🙂d", width, Ellipsis::Unicode);
+            assert!(display_width(&cut) <= width, "{cut:?} at {width}");
+        }
+    }
+
+    #[test]
+    fn visual_line_reverses_rtl_runs_and_keeps_digits_ltr() {
+        let fixtures = [
+            ("abc", "abc"),
+            ("שלום", "םולש"),
+            ("hi שלום!", "hi םולש!"),
+            ("שלום עולם", "םלוע םולש"),
+            ("מחיר 120 שקל", "לקש 120 ריחמ"),
+            ("say مرحبا now", "say ابحرم now"),
+        ];
+        for (logical, visual) in fixtures {
+            assert_eq!(visual_line(logical), visual, "fixture {logical:?}");
+        }
+    }
+
+    #[test]
+    fn rtl_cursor_moves_leftward_through_the_run() {
+        let input = "ab שלום";
+        let starts: Vec<usize> = input.char_indices().map(|(i, _)| i).collect();
+        let cols: Vec<usize> = starts
+            .iter()
+            .map(|&byte| cursor_row_col(input, byte, 40).1)
+            .collect();
+        assert_eq!(cols, vec![0, 1, 2, 7, 6, 5, 4]);
+        assert_eq!(cursor_row_col(input, input.len(), 40), (0, 3));
+    }
+
+    #[test]
+    fn mixed_direction_cursor_returns_to_ltr_after_run() {
+        let input = "שלום x";
+        let x_byte = input.find('x').expect("fixture has x");
+        assert_eq!(cursor_row_col(input, x_byte, 40), (0, 5));
+        assert_eq!(cursor_row_col(input, input.len(), 40), (0, 6));
+        assert_eq!(cursor_row_col(input, 0, 40), (0, 4));
+    }
+}
@@ -24,6 +24,47 @@ pub fn split_three_pane_layout(area: Rect, input_rows: u16) -> ThreePaneLayout {
     }
 }
 
+/// Narrowest input the footer widgets may leave; below it they are hidden.
+pub const MIN_FOOTER_INPUT_WIDTH: u16 = 24;
+/// Columns kept for the footer widgets whether or not anything is showing,
+/// so the input does not change width when a turn starts or ends. Longer
+/// widget text is truncated.
+pub const FOOTER_WIDGET_COLUMNS: u16 = 36;
+
+/// Columns for the right-aligned footer widgets, or 0 when the pane is too
+/// narrow to spare them.
+pub fn footer_widget_columns(pane_width: u16) -> u16 {
+    if pane_width < FOOTER_WIDGET_COLUMNS + MIN_FOOTER_INPUT_WIDTH {
+        return 0;
+    }
+    FOOTER_WIDGET_COLUMNS
+}
+
+/// Splits the input pane into the input and a footer widget column.
+pub fn split_input_footer(input: Rect, widget_columns: u16) -> (Rect, Option<Rect>) {
+    if widget_columns == 0 || widget_columns >= input.width {
+        return (input, None);
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(1), Constraint::Length(widget_columns)])
+        .split(input);
+    (chunks[0], Some(chunks[1]))
+}
+
+/// Splits a one-row strip for follow-up suggestions off the top of the
+/// input pane; the input keeps at least one row.
+pub fn split_follow_ups(input: Rect, shown: bool) -> (Option<Rect>, Rect) {
+    if !shown || input.height < 2 {
+        return (None, input);
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(input);
+    (Some(chunks[0]), chunks[1])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -50,4 +91,16 @@ mod tests {
         assert_eq!(panes.header.height, 1);
         assert_eq!(panes.history.height, 5);
     }
+
+    #[test]
+    fn footer_widgets_take_the_right_columns_when_they_fit() {
+        assert_eq!(footer_widget_columns(80), FOOTER_WIDGET_COLUMNS);
+        assert_eq!(footer_widget_columns(59), 0);
+
+        let input = Rect::new(0, 16, 80, 4);
+        let (text, widgets) = split_input_footer(input, FOOTER_WIDGET_COLUMNS);
+        assert_eq!(text, Rect::new(0, 16, 44, 4));
+        assert_eq!(widgets, Some(Rect::new(44, 16, 36, 4)));
+        assert_eq!(split_input_footer(input, 0), (input, None));
+    }
 }
@@ -0,0 +1,204 @@
+use crate::audit::format_utc;
+use crate::util::unix_now;
+
+/// What a plain sequential frontend has already written for the history
+/// pane, so each render only emits new text and never moves the cursor.
+#[derive(Debug, Default)]
+pub struct PlainTranscript {
+    /// Printed text of history lines, starting at global line `base`.
+    printed: Vec<String>,
+    base: usize,
+    /// Index into `printed` of the line whose text ends at the cursor; a
+    /// streamed line that only grows is continued in place.
+    open: Option<usize>,
+    mid_line: bool,
+}
+
+impl PlainTranscript {
+    /// Text to write for `lines`, where `dropped` counts lines the history
+    /// cap has removed from their front. A line that changes other than by
+    /// growing at the cursor is written again in full.
+    pub fn update(&mut self, dropped: usize, lines: &[String]) -> String {
+        if dropped > self.base {
+            let shift = (dropped - self.base).min(self.printed.len());
+            self.printed.drain(..shift);
+            self.open = self.open.and_then(|index| index.checked_sub(shift));
+            self.base = dropped;
+        }
+
+        let mut out = String::new();
+        for (index, line) in lines.iter().enumerate() {
+            match self.printed.get(index) {
+                Some(printed) if printed == line => continue,
+                Some(printed) if self.open == Some(index) && line.starts_with(printed.as_str()) => {
+                    out.push_str(&line[printed.len()..]);
+                }
+                _ => {
+                    out.push_str(self.line_break());
+                    out.push_str(line);
+                }
+            }
+            self.mid_line = true;
+            self.open = Some(index);
+            if index < self.printed.len() {
+                self.printed[index] = line.clone();
+            } else {
+                self.printed.push(line.clone());
+            }
+        }
+        if lines.len() < self.printed.len() {
+            self.printed.truncate(lines.len());
+            self.open = self.open.filter(|index| *index < lines.len());
+        }
+        out
+    }
+
+    /// Ends the current line so a prompt starts at the left margin.
+    pub fn line_break(&mut self) -> &'static str {
+        let started = std::mem::take(&mut self.mid_line);
+        self.open = None;
+        if started {
+            "\n"
+        } else {
+            ""
+        }
+    }
+}
+
+pub const TURN_SEPARATORS_ENV: &str = "VEX_PLAIN_SEPARATORS";
+
+/// How plain output marks the start of each prompt and response, so a long
+/// session can be navigated in the terminal's scrollback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeparatorStyle {
+    Rule,
+    Ascii,
+    #[default]
+    Off,
+}
+
+impl SeparatorStyle {
+    /// `VEX_PLAIN_SEPARATORS=rule|ascii|off`, default `off` so piped output
+    /// is unchanged unless asked for.
+    pub fn from_env() -> Self {
+        match std::env::var(TURN_SEPARATORS_ENV)
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "rule" | "on" | "1" | "true" => Self::Rule,
+            "ascii" => Self::Ascii,
+            _ => Self::Off,
+        }
+    }
+
+    /// The separator for `role` in turn `turn`, stamped with the current time.
+    pub fn line(self, turn: usize, role: &str) -> Option<String> {
+        self.render(turn, role, unix_now())
+    }
+
+    fn render(self, turn: usize, role: &str, timestamp: u64) -> Option<String> {
+        let (rule, dot) = match self {
+            Self::Rule => ("────", "·"),
+            Self::Ascii => ("----", "|"),
+            Self::Off => return None,
+        };
+        Some(format!(
+            "{rule} turn {turn} {dot} {role} {dot} {} UTC {rule}",
+            format_utc(timestamp)
+        ))
+    }
+}
+
+/// The approval prompt for a pending tool call, with the same choices as the
+/// TUI overlay.
+pub fn render_tool_approval_prompt(tool_name: &str, input_preview: &str) -> String {
+    format!(
+        "[approval required: {tool_name}]\n{}\n  1 approve once | 2 approve for session | 3 deny | 4 deny with reason\n  (or `n <reason>`)\n> ",
+        input_preview.trim_end()
+    )
+}
+
+pub fn render_patch_approval_prompt(patch_preview: &str) -> String {
+    format!(
+        "[patch approval required]\n{}\n  1 apply | 3 reject\n> ",
+        patch_preview.trim_end()
+    )
+}
+
+/// Numbered picker rows; a plain surface selects by typing the number.
+pub fn render_picker_prompt(title: &str, rows: &[String]) -> String {
+    let mut out = format!("[{title}]\n");
+    for (index, row) in rows.iter().enumerate() {
+        out.push_str(&format!("{:>3}. {row}\n", index + 1));
+    }
+    out.push_str("  type a number to choose, or press Enter to close\n> ");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn test_streamed_line_is_continued_and_edits_are_reprinted() {
+        let mut transcript = PlainTranscript::default();
+        assert_eq!(transcript.update(0, &lines(&["> hi", "Hel"])), "> hi\nHel");
+        assert_eq!(transcript.update(0, &lines(&["> hi", "Hello"])), "lo");
+        assert_eq!(
+            transcript.update(0, &lines(&["> hi", "Hello", "[tool] read_file"])),
+            "\n[tool] read_file"
+        );
+        assert_eq!(
+            transcript.update(0, &lines(&["> hi", "Hello!", "[tool] read_file"])),
+            "\nHello!"
+        );
+        assert_eq!(transcript.line_break(), "\n");
+        assert_eq!(transcript.line_break(), "");
+        assert_eq!(
+            transcript.update(0, &lines(&["> hi", "Hello!", "[tool] read_file", "done"])),
+            "done"
+        );
+    }
+
+    #[test]
+    fn test_capped_history_does_not_reprint() {
+        let mut transcript = PlainTranscript::default();
+        assert_eq!(transcript.update(0, &lines(&["a", "b", "c"])), "a\nb\nc");
+        assert_eq!(transcript.update(2, &lines(&["c", "d"])), "\nd");
+        assert_eq!(transcript.update(2, &lines(&["c", "d!"])), "!");
+    }
+
+    #[test]
+    fn test_separator_styles() {
+        assert_eq!(
+            SeparatorStyle::Rule.render(3, "you", 86_400 + 3_661),
+            Some("──── turn 3 · you · 1970-01-02 01:01:01 UTC ────".to_string())
+        );
+        assert_eq!(
+            SeparatorStyle::Ascii.render(3, "vex", 0),
+            Some("---- turn 3 | vex | 1970-01-01 00:00:00 UTC ----".to_string())
+        );
+        assert_eq!(SeparatorStyle::Off.render(3, "you", 0), None);
+
+        let _env_lock = crate::test_support::ENV_LOCK.blocking_lock();
+        std::env::remove_var(TURN_SEPARATORS_ENV);
+        assert_eq!(SeparatorStyle::from_env(), SeparatorStyle::Off);
+        std::env::set_var(TURN_SEPARATORS_ENV, "rule");
+        assert_eq!(SeparatorStyle::from_env(), SeparatorStyle::Rule);
+        std::env::remove_var(TURN_SEPARATORS_ENV);
+    }
+
+    #[test]
+    fn test_picker_prompt_numbers_rows() {
+        assert_eq!(
+            render_picker_prompt("presets", &lines(&["bugfix  Fix a bug"])),
+            "[presets]\n  1. bugfix  Fix a bug\n  type a number to choose, or press Enter to close\n> "
+        );
+    }
+}
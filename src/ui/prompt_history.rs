@@ -0,0 +1,215 @@
+use crate::ui::input_metrics::{truncate_with_ellipsis, Ellipsis};
+use crate::util::unix_now;
+
+const MAX_PROMPT_HISTORY: usize = 500;
+/// Display cells of prompt text shown per row.
+const ROW_TEXT_MAX_WIDTH: usize = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptOutcome {
+    Running,
+    Ok,
+    Error,
+    Cancelled,
+}
+
+impl PromptOutcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PromptOutcome::Running => "running",
+            PromptOutcome::Ok => "ok",
+            PromptOutcome::Error => "error",
+            PromptOutcome::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptHistoryEntry {
+    pub text: String,
+    pub submitted_at: u64,
+    pub outcome: PromptOutcome,
+}
+
+/// Prompts submitted this session with their outcome, oldest first.
+#[derive(Debug, Default)]
+pub struct PromptHistory {
+    entries: Vec<PromptHistoryEntry>,
+}
+
+impl PromptHistory {
+    pub fn push(&mut self, text: &str) {
+        if self.entries.len() >= MAX_PROMPT_HISTORY {
+            self.entries.remove(0);
+        }
+        self.entries.push(PromptHistoryEntry {
+            text: text.to_string(),
+            submitted_at: unix_now(),
+            outcome: PromptOutcome::Running,
+        });
+    }
+
+    /// Records the outcome of the most recent prompt if it is still running.
+    pub fn finish_latest(&mut self, outcome: PromptOutcome) {
+        if let Some(entry) = self
+            .entries
+            .last_mut()
+            .filter(|entry| entry.outcome == PromptOutcome::Running)
+        {
+            entry.outcome = outcome;
+        }
+    }
+
+    pub fn entries(&self) -> &[PromptHistoryEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Indices of entries matching `query`, best fuzzy match first and newest
+    /// first among equal scores. An empty query lists everything newest first.
+    pub fn filter(&self, query: &str) -> Vec<usize> {
+        let mut matches: Vec<(i64, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                fuzzy_match_score(query, &entry.text).map(|score| (score, index))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.cmp(a));
+        matches.into_iter().map(|(_, index)| index).collect()
+    }
+}
+
+/// One aligned picker row; the TUI styles the columns, plain surfaces use
+/// [`render_prompt_history_rows`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptHistoryRow {
+    pub number: usize,
+    pub age: String,
+    pub outcome: PromptOutcome,
+    pub text: String,
+}
+
+pub fn prompt_history_rows(history: &PromptHistory, indices: &[usize]) -> Vec<PromptHistoryRow> {
+    let now = unix_now();
+    indices
+        .iter()
+        .filter_map(|&index| {
+            let entry = history.entries.get(index)?;
+            Some(PromptHistoryRow {
+                number: index + 1,
+                age: format_age(now.saturating_sub(entry.submitted_at)),
+                outcome: entry.outcome,
+                text: single_line_preview(&entry.text),
+            })
+        })
+        .collect()
+}
+
+pub fn render_prompt_history_rows(rows: &[PromptHistoryRow]) -> String {
+    rows.iter()
+        .map(|row| {
+            format!(
+                "{:>4}  {:>4}  {:<9}  {}",
+                format!("#{}", row.number),
+                row.age,
+                row.outcome.label(),
+                row.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Case-insensitive subsequence match. Consecutive characters and matches at
+/// word starts score higher; `None` when `query` is not a subsequence.
+pub fn fuzzy_match_score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let mut score = 0i64;
+    let mut query_index = 0;
+    let mut previous_match: Option<usize> = None;
+    let mut previous_char = ' ';
+    for (position, ch) in text.chars().enumerate() {
+        if query_index < query.len() && ch.to_lowercase().eq(query[query_index].to_lowercase()) {
+            score += 1;
+            if previous_match == Some(position.wrapping_sub(1)) {
+                score += 3;
+            }
+            if !previous_char.is_alphanumeric() {
+                score += 2;
+            }
+            previous_match = Some(position);
+            query_index += 1;
+        }
+        previous_char = ch;
+    }
+    (query_index == query.len()).then_some(score)
+}
+
+fn single_line_preview(text: &str) -> String {
+    let first_line = text.lines().next().unwrap_or_default().trim();
+    let mut preview = truncate_with_ellipsis(first_line, ROW_TEXT_MAX_WIDTH, Ellipsis::Unicode);
+    if preview == first_line && text.trim().lines().count() > 1 {
+        preview.push_str(" ...");
+    }
+    preview
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{seconds}s"),
+        60..=3_599 => format!("{}m", seconds / 60),
+        3_600..=86_399 => format!("{}h", seconds / 3_600),
+        _ => format!("{}d", seconds / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_filter_ranks_word_start_and_consecutive_matches() {
+        let mut history = PromptHistory::default();
+        history.push("fix the parser bug");
+        history.push("run cargo test");
+        history.push("cat notes");
+        assert_eq!(history.filter("ct"), vec![1, 2]);
+        assert_eq!(history.filter("parser"), vec![0]);
+        assert_eq!(history.filter(""), vec![2, 1, 0]);
+        assert!(history.filter("zzz").is_empty());
+    }
+
+    #[test]
+    fn test_outcome_only_updates_running_prompt() {
+        let mut history = PromptHistory::default();
+        history.push("first");
+        history.finish_latest(PromptOutcome::Error);
+        history.finish_latest(PromptOutcome::Ok);
+        assert_eq!(history.entries()[0].outcome, PromptOutcome::Error);
+    }
+
+    #[test]
+    fn test_rows_are_aligned_and_single_line() {
+        let mut history = PromptHistory::default();
+        history.push("explain\nthis file");
+        history.finish_latest(PromptOutcome::Ok);
+        history.push("run tests");
+        let rows = prompt_history_rows(&history, &history.filter(""));
+        assert_eq!(
+            render_prompt_history_rows(&rows),
+            "  #2    0s  running    run tests\n  #1    0s  ok         explain ..."
+        );
+    }
+}
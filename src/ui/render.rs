@@ -1,6 +1,13 @@
+use crate::presets::Preset;
+use crate::state::{ApprovalTier, ToolClass};
+use crate::ui::diff_style::{diff_row_kind, DiffRowKind, DiffStyle};
+use crate::ui::help::HelpLine;
 use crate::ui::input_metrics::{
-    char_display_width, cursor_row_col, truncate_to_display_width, wrap_input_lines,
+    char_display_width, cursor_row_col, display_width, truncate_with_ellipsis, visual_line,
+    wrap_input_lines, Ellipsis,
 };
+use crate::ui::prompt_history::{PromptHistoryRow, PromptOutcome};
+use crate::util::group_thousands;
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
@@ -8,25 +15,111 @@ use ratatui::{
     widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 pub enum OverlayModal<'a> {
     PatchApprove {
         patch_preview: &'a str,
         scroll_offset: usize,
         viewport_rows: usize,
+        diff_style: DiffStyle,
     },
     ToolPermission {
         tool_name: &'a str,
         input_preview: &'a str,
+        class: ToolClass,
+        paths: &'a [String],
         auto_approve_enabled: bool,
+        reason_entry: bool,
+        prompt_focused: bool,
+        tier: &'a ApprovalTier,
+        queued: &'a [String],
+        diff_style: DiffStyle,
+    },
+    HistoryPicker {
+        query: &'a str,
+        filtering: bool,
+        rows: &'a [PromptHistoryRow],
+        selected: usize,
+    },
+    PresetPicker {
+        presets: &'a [Preset],
+        selected: usize,
+    },
+    SessionPicker {
+        tag: &'a str,
+        filtering: bool,
+        rows: &'a [String],
+        selected: usize,
+    },
+    PasteConfirm {
+        lines: usize,
+        chars: usize,
+        text: &'a str,
     },
 }
 
+/// The input pane grows with the draft up to this many rows, then scrolls.
+pub const MAX_INPUT_PANE_ROWS: usize = 8;
+
 pub fn input_visual_rows(input: &str, width: usize) -> usize {
     wrap_input_lines(input, width).len().max(1)
 }
 
-pub fn render_input(frame: &mut Frame<'_>, area: Rect, input: &str, cursor_byte: usize) {
+/// Rows given to the input pane: the draft's visual rows, capped at
+/// [`MAX_INPUT_PANE_ROWS`] and at half the screen so the output region
+/// stays put while a long draft is edited.
+pub fn input_pane_rows(input: &str, width: usize, screen_rows: u16) -> u16 {
+    let cap = MAX_INPUT_PANE_ROWS.min((screen_rows / 2).max(1) as usize);
+    input_visual_rows(input, width).clamp(1, cap) as u16
+}
+
+/// Scroll position inside the input pane. The window follows the cursor
+/// until Alt+Up/Down pins it; editing the draft unpins it again.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InputViewport {
+    pinned_start: Option<usize>,
+    last_start: usize,
+}
+
+impl InputViewport {
+    pub fn scroll_up(&mut self) {
+        let start = self.pinned_start.unwrap_or(self.last_start);
+        self.pinned_start = Some(start.saturating_sub(1));
+    }
+
+    pub fn scroll_down(&mut self) {
+        let start = self.pinned_start.unwrap_or(self.last_start);
+        self.pinned_start = Some(start.saturating_add(1));
+    }
+
+    pub fn follow_cursor(&mut self) {
+        self.pinned_start = None;
+    }
+
+    fn window_start(&mut self, cursor_row: usize, total_rows: usize, visible_rows: usize) -> usize {
+        let max_start = total_rows.saturating_sub(visible_rows);
+        let start = match self.pinned_start {
+            Some(start) => {
+                let start = start.min(max_start);
+                self.pinned_start = Some(start);
+                start
+            }
+            None => input_window_start(cursor_row, visible_rows),
+        };
+        self.last_start = start;
+        start
+    }
+}
+
+pub fn render_input(
+    frame: &mut Frame<'_>,
+    area: Rect,
+    input: &str,
+    cursor_byte: usize,
+    viewport: &mut InputViewport,
+) {
     if area.height == 0 || area.width <= 2 {
         return;
     }
@@ -36,13 +129,17 @@ pub fn render_input(frame: &mut Frame<'_>, area: Rect, input: &str, cursor_byte:
     let lines = wrap_input_lines(input, input_width);
     let (cursor_row, cursor_col) = cursor_row_col(input, cursor_byte, input_width);
     let visible_rows = inner.height as usize;
-    let window_start = input_window_start(cursor_row, visible_rows);
+    let total_rows = lines.len().max(cursor_row + 1);
+    let window_start = viewport.window_start(cursor_row, total_rows, visible_rows);
 
     let mut rendered = Vec::with_capacity(visible_rows);
     for offset in 0..visible_rows {
         let row_index = window_start + offset;
         let prefix = if row_index == 0 { "> " } else { "  " };
-        let line = lines.get(row_index).cloned().unwrap_or_default();
+        let line = lines
+            .get(row_index)
+            .map(|line| visual_line(line))
+            .unwrap_or_default();
         rendered.push(Line::from(format!("{prefix}{line}")));
     }
 
@@ -58,6 +155,36 @@ pub fn render_input(frame: &mut Frame<'_>, area: Rect, input: &str, cursor_byte:
         inner,
     );
 
+    let hidden_below = total_rows.saturating_sub(window_start + visible_rows);
+    for (row, hidden, direction) in [
+        (0, window_start, "above"),
+        (visible_rows - 1, hidden_below, "below"),
+    ] {
+        if hidden == 0 {
+            continue;
+        }
+        let label = input_overflow_label(hidden, direction);
+        let width = (display_width(&label) as u16).min(inner.width);
+        let label_area = Rect::new(
+            inner.x + inner.width - width,
+            inner.y + row as u16,
+            width,
+            1,
+        );
+        frame.render_widget(
+            Paragraph::new(label).style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .bg(Color::Rgb(24, 24, 24)),
+            ),
+            label_area,
+        );
+    }
+
+    // A cursor scrolled out of the window is hidden rather than clamped.
+    if !(window_start..window_start + visible_rows).contains(&cursor_row) {
+        return;
+    }
     let cursor_y = inner
         .y
         .saturating_add(cursor_row.saturating_sub(window_start) as u16);
@@ -68,39 +195,89 @@ pub fn render_input(frame: &mut Frame<'_>, area: Rect, input: &str, cursor_byte:
     frame.set_cursor_position((cursor_x, cursor_y));
 }
 
+fn input_overflow_label(hidden: usize, direction: &str) -> String {
+    let unit = if hidden == 1 { "line" } else { "lines" };
+    format!(" (+{hidden} {unit} {direction}) ")
+}
+
 fn input_window_start(cursor_row: usize, visible_rows: usize) -> usize {
     cursor_row
         .saturating_add(1)
         .saturating_sub(visible_rows.max(1))
 }
 
-pub fn render_messages(frame: &mut Frame<'_>, area: Rect, messages: &[String], scroll: usize) {
+pub fn render_messages(
+    frame: &mut Frame<'_>,
+    area: Rect,
+    messages: &[String],
+    scroll: usize,
+    diff_style: &DiffStyle,
+) {
+    let mut cache = HistoryRenderCache::default();
+    render_messages_cached(frame, area, messages, scroll, diff_style, &mut cache);
+}
+
+/// Styled history rows from the previous draw, reused while the history,
+/// pane width, and diff style stay the same, so a keystroke or spinner frame
+/// does not re-wrap the whole transcript.
+#[derive(Default)]
+pub struct HistoryRenderCache {
+    key: Option<(u64, u16, DiffStyle)>,
+    body: Vec<Line<'static>>,
+}
+
+/// [`render_messages`] reusing the rows in `cache` when they are current.
+/// Only the rows inside `area` are handed to the frame.
+pub fn render_messages_cached(
+    frame: &mut Frame<'_>,
+    area: Rect,
+    messages: &[String],
+    scroll: usize,
+    diff_style: &DiffStyle,
+    cache: &mut HistoryRenderCache,
+) {
     if area.height == 0 || area.width == 0 {
         return;
     }
-    let inner = area;
+    let mut hasher = DefaultHasher::new();
+    messages.hash(&mut hasher);
+    let key = (hasher.finish(), area.width, *diff_style);
+    if cache.key != Some(key) {
+        cache.body = history_body(messages, area.width, diff_style);
+        cache.key = Some(key);
+    }
+    let start = scroll.min(cache.body.len());
+    let end = (start + area.height as usize).min(cache.body.len());
+    frame.render_widget(
+        Paragraph::new(Text::from(cache.body[start..end].to_vec())),
+        area,
+    );
+}
 
+fn history_body(messages: &[String], width: u16, diff_style: &DiffStyle) -> Vec<Line<'static>> {
     let logical_rows = expand_history_rows(messages);
     let line_number_width = logical_rows.len().max(1).to_string().len();
-    let content_width = history_content_width(inner.width, line_number_width);
+    let content_width = history_content_width(width, line_number_width);
     let mut body: Vec<Line<'static>> = Vec::new();
     for (index, row) in logical_rows.iter().enumerate() {
-        let row_style = history_row_style(row);
-        let wrapped_segments = wrap_input_lines(row, content_width);
+        let (kind, row_style) = history_row_style(row, diff_style);
+        let wrapped_segments = wrap_history_row(row, content_width);
         for (segment_index, segment) in wrapped_segments.iter().enumerate() {
+            // Only the first segment holds the diff marker.
+            let content = if segment_index == 0 {
+                diff_style.spans(segment, kind, row_style)
+            } else {
+                vec![Span::styled(segment.to_string(), row_style)]
+            };
             body.push(format_history_row_segment(
                 index + 1,
                 line_number_width,
-                segment,
-                row_style,
+                content,
                 segment_index == 0,
             ));
         }
     }
-
-    let paragraph =
-        Paragraph::new(Text::from(body)).scroll((scroll.min(u16::MAX as usize) as u16, 0));
-    frame.render_widget(paragraph, inner);
+    body
 }
 
 pub fn history_visual_line_count(messages: &[String], content_width: usize) -> usize {
@@ -111,7 +288,7 @@ pub fn history_visual_line_count(messages: &[String], content_width: usize) -> u
     let content_width = content_width.max(1);
     expand_history_rows(messages)
         .iter()
-        .map(|row| wrap_input_lines(row, content_width).len().max(1))
+        .map(|row| wrap_history_row(row, content_width).len())
         .sum()
 }
 
@@ -143,11 +320,86 @@ fn expand_history_rows(messages: &[String]) -> Vec<String> {
     rows
 }
 
+/// Word-wraps one transcript row to `width` columns. Continuation segments
+/// carry a hanging indent so list items, diff lines, prompts and bracketed
+/// tool/status lines stay visually grouped; words wider than the line are
+/// split by display width.
+pub fn wrap_history_row(row: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    if display_width(row) <= width {
+        return vec![row.to_string()];
+    }
+
+    let indent = hanging_indent(row);
+    let indent = if indent * 2 > width { 0 } else { indent };
+    let continuation = " ".repeat(indent);
+
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0usize;
+    let mut line_start = 0usize;
+    for chunk in row.split_inclusive(' ') {
+        let word = chunk.trim_end_matches(' ');
+        let word_width = display_width(word);
+        if current_width > line_start && current_width + word_width > width {
+            segments.push(current.trim_end_matches(' ').to_string());
+            current = continuation.clone();
+            current_width = indent;
+            line_start = indent;
+        }
+        if current_width == line_start && !segments.is_empty() && word.is_empty() {
+            continue;
+        }
+        for ch in chunk.chars() {
+            let ch_width = char_display_width(ch);
+            if ch != ' ' && current_width + ch_width > width && current_width > line_start {
+                segments.push(std::mem::take(&mut current));
+                current = continuation.clone();
+                current_width = indent;
+                line_start = indent;
+            }
+            current.push(ch);
+            current_width += ch_width;
+        }
+    }
+    if current_width > line_start || segments.is_empty() {
+        segments.push(current.trim_end_matches(' ').to_string());
+    }
+    segments
+}
+
+fn hanging_indent(row: &str) -> usize {
+    if row.starts_with("> ") {
+        return 2;
+    }
+    if is_diff_row(row) {
+        return 1;
+    }
+
+    let leading = row.len() - row.trim_start_matches(' ').len();
+    let rest = &row[leading..];
+    if rest.starts_with("- ") || rest.starts_with("* ") || rest.starts_with("• ") {
+        return leading + 2;
+    }
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && (rest[digits..].starts_with(". ") || rest[digits..].starts_with(") ")) {
+        return leading + digits + 2;
+    }
+    if rest.starts_with('[') {
+        return leading + 1;
+    }
+    leading
+}
+
+fn is_diff_row(row: &str) -> bool {
+    (row.starts_with('+') && !row.starts_with("+++"))
+        || (row.starts_with('-') && !row.starts_with("---") && !row.starts_with("- "))
+}
+
 fn format_history_row_segment(
     line_number: usize,
     line_number_width: usize,
-    row: &str,
-    style: Style,
+    content: Vec<Span<'static>>,
     show_line_number: bool,
 ) -> Line<'static> {
     let line_prefix = if show_line_number {
@@ -155,41 +407,169 @@ fn format_history_row_segment(
     } else {
         format!("{:>line_number_width$} | ", "")
     };
-    Line::from(vec![
-        Span::styled(
-            line_prefix,
-            Style::default()
-                .fg(Color::DarkGray)
-                .add_modifier(Modifier::DIM),
-        ),
-        Span::styled(row.to_string(), style),
-    ])
+    let mut spans = vec![Span::styled(
+        line_prefix,
+        Style::default()
+            .fg(Color::DarkGray)
+            .add_modifier(Modifier::DIM),
+    )];
+    spans.extend(content);
+    Line::from(spans)
 }
 
-fn history_row_style(row: &str) -> Style {
-    if row.starts_with('+') && !row.starts_with("+++") {
-        Style::default().fg(Color::Green)
-    } else if row.starts_with('-') && !row.starts_with("---") {
-        Style::default().fg(Color::Red)
-    } else if row.starts_with("@@") || row.starts_with("diff --git") || row.starts_with("index ") {
-        Style::default().fg(Color::Cyan)
-    } else {
-        Style::default().fg(Color::White)
+fn history_row_style(row: &str, diff_style: &DiffStyle) -> (Option<DiffRowKind>, Style) {
+    let kind = diff_row_kind(row);
+    let style = match kind {
+        Some(kind) => diff_style.style(kind),
+        None if row.starts_with("diff --git") || row.starts_with("index ") => {
+            diff_style.style(DiffRowKind::Hunk)
+        }
+        None => Style::default().fg(Color::White),
+    };
+    (kind, style)
+}
+
+/// Footer widgets, right-aligned on the bottom row of `area`.
+pub fn render_footer_widgets(frame: &mut Frame<'_>, area: Rect, widgets: &str) {
+    if area.height == 0 || area.width == 0 {
+        return;
+    }
+    let row = Rect::new(area.x, area.y + area.height - 1, area.width, 1);
+    let widgets = truncate_with_ellipsis(
+        widgets,
+        usize::from(area.width).saturating_sub(2),
+        Ellipsis::Unicode,
+    );
+    frame.render_widget(
+        Paragraph::new(Line::from(format!(" {widgets} ")).alignment(Alignment::Right))
+            .style(Style::default().fg(Color::Cyan).bg(Color::Rgb(24, 24, 24))),
+        row,
+    );
+    if area.height > 1 {
+        let above = Rect::new(area.x, area.y, area.width, area.height - 1);
+        frame.render_widget(
+            Paragraph::new("").style(Style::default().bg(Color::Rgb(24, 24, 24))),
+            above,
+        );
     }
 }
 
+/// Follow-up suggestions as `Alt+1 run the tests  Alt+2 ...` on one row.
+pub fn render_follow_ups(frame: &mut Frame<'_>, area: Rect, suggestions: &[String]) {
+    if area.height == 0 || area.width == 0 {
+        return;
+    }
+    let mut spans = Vec::new();
+    for (index, suggestion) in suggestions.iter().enumerate() {
+        if index > 0 {
+            spans.push(Span::raw("  "));
+        }
+        spans.push(Span::styled(
+            format!("Alt+{}", index + 1),
+            Style::default().fg(Color::Cyan),
+        ));
+        spans.push(Span::styled(
+            format!(" {suggestion}"),
+            Style::default().fg(Color::Gray),
+        ));
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
 pub fn render_status_line(frame: &mut Frame<'_>, area: Rect, status: &str) {
     if area.height == 0 || area.width == 0 {
         return;
     }
 
-    let text = truncate_line(status, area.width as usize);
+    let text = truncate_with_ellipsis(status, area.width as usize, Ellipsis::Ascii);
     frame.render_widget(
         Paragraph::new(text).style(Style::default().fg(Color::DarkGray)),
         area,
     );
 }
 
+/// The help screen takes the whole frame rather than a centered modal.
+pub fn render_help_overlay(frame: &mut Frame<'_>, query: &str, lines: &[HelpLine], scroll: usize) {
+    let area = frame.area();
+    if area.width == 0 || area.height == 0 {
+        return;
+    }
+    frame.render_widget(Clear, area);
+    let outer = Block::default()
+        .borders(Borders::ALL)
+        .title("Help")
+        .style(Style::default().fg(Color::Cyan));
+    let inner = outer.inner(area);
+    frame.render_widget(outer, area);
+
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled("filter: ", Style::default().fg(Color::DarkGray)),
+            Span::raw(format!("{query}_")),
+        ])),
+        vertical[0],
+    );
+    let body = if lines.is_empty() {
+        vec![Line::styled(
+            "no matching keys, commands, or settings",
+            Style::default().fg(Color::DarkGray),
+        )]
+    } else {
+        help_content(lines)
+    };
+    frame.render_widget(
+        Paragraph::new(Text::from(body)).scroll((scroll.min(u16::MAX as usize) as u16, 0)),
+        vertical[1],
+    );
+    frame.render_widget(
+        Paragraph::new("type to filter   up/down/pgup/pgdn scroll   enter/esc close")
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::DarkGray)),
+        vertical[2],
+    );
+}
+
+fn help_content(lines: &[HelpLine]) -> Vec<Line<'static>> {
+    let key_width = lines
+        .iter()
+        .filter_map(|line| match line {
+            HelpLine::Entry { key, .. } => Some(display_width(key)),
+            _ => None,
+        })
+        .max()
+        .unwrap_or_default();
+    lines
+        .iter()
+        .map(|line| match line {
+            HelpLine::Heading(heading) => Line::styled(
+                heading.clone(),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            HelpLine::Entry { key, description } => Line::from(vec![
+                Span::styled(
+                    format!(
+                        "  {key}{}  ",
+                        " ".repeat(key_width.saturating_sub(display_width(key)))
+                    ),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(description.clone(), Style::default().fg(Color::Gray)),
+            ]),
+            HelpLine::Blank => Line::from(""),
+        })
+        .collect()
+}
+
 pub fn render_overlay_modal(frame: &mut Frame<'_>, modal: OverlayModal<'_>) {
     if frame.area().width == 0 || frame.area().height == 0 {
         return;
@@ -241,6 +621,7 @@ fn modal_content(
             patch_preview,
             scroll_offset,
             viewport_rows,
+            diff_style,
         } => {
             let lines: Vec<&str> = patch_preview.lines().collect();
             let start = scroll_offset.min(lines.len().saturating_sub(1));
@@ -261,7 +642,7 @@ fn modal_content(
                 Style::default().add_modifier(Modifier::BOLD),
             ));
             for line in lines.iter().skip(start).take(visible) {
-                body.push(styled_diff_line(line));
+                body.push(styled_diff_line(line, &diff_style));
             }
 
             (
@@ -274,13 +655,36 @@ fn modal_content(
         OverlayModal::ToolPermission {
             tool_name,
             input_preview,
+            class,
+            paths,
             auto_approve_enabled,
+            reason_entry,
+            prompt_focused,
+            tier,
+            queued,
+            diff_style,
         } => {
             let mut body = Vec::new();
             body.push(Line::styled(
-                format!("Tool: {tool_name}"),
-                Style::default().add_modifier(Modifier::BOLD),
+                format!("Tool: {tool_name} ({})", class.label()),
+                match class {
+                    ToolClass::Destructive => {
+                        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+                    }
+                    _ => Style::default().add_modifier(Modifier::BOLD),
+                },
             ));
+            if !paths.is_empty() {
+                body.push(Line::from(format!("Paths: {}", paths.join(", "))));
+            }
+            if let Some(note) = tier.note() {
+                body.push(Line::styled(
+                    format!("Tier: {note}"),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
             if auto_approve_enabled {
                 body.push(Line::styled(
                     "session auto-approve is ON",
@@ -297,7 +701,10 @@ fn modal_content(
             let preview_lines: Vec<&str> = input_preview.lines().collect();
             let max_preview_lines = 6;
             for line in preview_lines.iter().take(max_preview_lines) {
-                body.push(Line::from(line.to_string()));
+                body.push(match diff_row_kind(line) {
+                    Some(_) => styled_diff_line(line, &diff_style),
+                    None => Line::from(line.to_string()),
+                });
             }
             if preview_lines.len() > max_preview_lines {
                 body.push(Line::styled(
@@ -310,26 +717,256 @@ fn modal_content(
                         .add_modifier(Modifier::DIM),
                 ));
             }
+            if !queued.is_empty() {
+                let queued_style = Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::DIM);
+                body.push(Line::from(""));
+                body.push(Line::styled(
+                    format!("Queued ({}) - waiting for previous approval", queued.len()),
+                    queued_style.add_modifier(Modifier::BOLD),
+                ));
+                let max_queued_lines = 4;
+                for summary in queued.iter().take(max_queued_lines) {
+                    body.push(Line::styled(format!("  {summary}"), queued_style));
+                }
+                if queued.len() > max_queued_lines {
+                    body.push(Line::styled(
+                        format!("  ... ({} more)", queued.len() - max_queued_lines),
+                        queued_style,
+                    ));
+                }
+            }
+            if reason_entry {
+                body.push(Line::from(""));
+                body.push(Line::styled(
+                    "Type a denial reason in the input box and press Enter.",
+                    Style::default().add_modifier(Modifier::BOLD),
+                ));
+                return (
+                    "Tool Permission",
+                    Color::Yellow,
+                    body,
+                    "enter deny with reason   esc deny without reason",
+                );
+            }
             (
                 "Tool Permission",
                 Color::Yellow,
                 body,
-                "1 yes   2 allow this session   3/esc cancel",
+                if prompt_focused {
+                    "1 yes   2 allow this session   3/esc cancel   4 deny with reason   tab back to input"
+                } else {
+                    "alt+1 yes   alt+2 allow this session   esc cancel   alt+4 deny with reason   tab focus"
+                },
+            )
+        }
+        OverlayModal::HistoryPicker {
+            query,
+            filtering,
+            rows,
+            selected,
+        } => (
+            "Prompt History",
+            Color::Cyan,
+            history_picker_content(query, filtering, rows, selected),
+            if filtering {
+                "type to filter   enter/esc done"
+            } else {
+                "up/down select   enter re-run   e edit   / filter   esc close"
+            },
+        ),
+        OverlayModal::PresetPicker { presets, selected } => (
+            "Start From a Preset",
+            Color::Cyan,
+            preset_picker_content(presets, selected),
+            "up/down select   enter load into input   esc blank session",
+        ),
+        OverlayModal::SessionPicker {
+            tag,
+            filtering,
+            rows,
+            selected,
+        } => (
+            "Saved Sessions",
+            Color::Cyan,
+            session_picker_content(tag, filtering, rows, selected),
+            if filtering {
+                "type a tag to filter   enter/esc done"
+            } else {
+                "up/down select   enter resume   / filter by tag   esc close"
+            },
+        ),
+        OverlayModal::PasteConfirm { lines, chars, text } => {
+            let dim = Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::DIM);
+            let mut body = vec![
+                Line::styled(
+                    format!(
+                        "Paste {} lines ({} characters)?",
+                        group_thousands(lines),
+                        group_thousands(chars)
+                    ),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Line::from(
+                    "Attaching saves it as a file the model reads on demand instead of sending it inline.",
+                ),
+                Line::from(""),
+            ];
+            let max_preview_lines = 6;
+            for line in text.lines().take(max_preview_lines) {
+                body.push(Line::styled(line.to_string(), dim));
+            }
+            if lines > max_preview_lines {
+                body.push(Line::styled(
+                    format!(
+                        "... ({} more lines)",
+                        group_thousands(lines - max_preview_lines)
+                    ),
+                    dim,
+                ));
+            }
+            (
+                "Large Paste",
+                Color::Magenta,
+                body,
+                "1/y paste inline   2/a attach as file   3/n/esc discard",
             )
         }
     }
 }
 
-fn styled_diff_line(line: &str) -> Line<'static> {
-    if line.starts_with('+') && !line.starts_with("+++") {
-        Line::styled(line.to_string(), Style::default().fg(Color::Green))
-    } else if line.starts_with('-') && !line.starts_with("---") {
-        Line::styled(line.to_string(), Style::default().fg(Color::Red))
-    } else if line.starts_with("@@") {
-        Line::styled(line.to_string(), Style::default().fg(Color::Cyan))
-    } else {
-        Line::styled(line.to_string(), Style::default().fg(Color::Gray))
+fn preset_picker_content(presets: &[Preset], selected: usize) -> Vec<Line<'static>> {
+    let dim = Style::default()
+        .fg(Color::DarkGray)
+        .add_modifier(Modifier::DIM);
+    let name_width = presets
+        .iter()
+        .map(|preset| preset.name.chars().count())
+        .max()
+        .unwrap_or_default();
+    presets
+        .iter()
+        .enumerate()
+        .map(|(index, preset)| {
+            let line = Line::from(vec![
+                Span::styled(
+                    format!("{:<name_width$}  ", preset.name),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(preset.title.clone(), dim),
+            ]);
+            if index == selected {
+                line.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                line
+            }
+        })
+        .collect()
+}
+
+fn history_picker_content(
+    query: &str,
+    filtering: bool,
+    rows: &[PromptHistoryRow],
+    selected: usize,
+) -> Vec<Line<'static>> {
+    let dim = Style::default()
+        .fg(Color::DarkGray)
+        .add_modifier(Modifier::DIM);
+    let mut body = Vec::new();
+    let cursor = if filtering { "_" } else { "" };
+    body.push(Line::from(vec![
+        Span::styled("Filter: ", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(format!("{query}{cursor}")),
+    ]));
+    body.push(Line::from(""));
+    if rows.is_empty() {
+        body.push(Line::styled("No matching prompts.", dim));
+        return body;
+    }
+
+    let max_rows = 12;
+    let start = selected.saturating_sub(max_rows - 1);
+    for (offset, row) in rows.iter().enumerate().skip(start).take(max_rows) {
+        let outcome_color = match row.outcome {
+            PromptOutcome::Ok => Color::Green,
+            PromptOutcome::Error => Color::Red,
+            PromptOutcome::Cancelled => Color::Yellow,
+            PromptOutcome::Running => Color::Cyan,
+        };
+        let mut line = Line::from(vec![
+            Span::styled(format!("{:>4}  ", format!("#{}", row.number)), dim),
+            Span::styled(format!("{:>4}  ", row.age), dim),
+            Span::styled(
+                format!("{:<9}  ", row.outcome.label()),
+                Style::default().fg(outcome_color),
+            ),
+            Span::raw(row.text.clone()),
+        ]);
+        if offset == selected {
+            line = line.style(Style::default().add_modifier(Modifier::REVERSED));
+        }
+        body.push(line);
     }
+    if rows.len() > start + max_rows {
+        body.push(Line::styled(
+            format!("... ({} more)", rows.len() - start - max_rows),
+            dim,
+        ));
+    }
+    body
+}
+
+fn session_picker_content(
+    tag: &str,
+    filtering: bool,
+    rows: &[String],
+    selected: usize,
+) -> Vec<Line<'static>> {
+    let dim = Style::default()
+        .fg(Color::DarkGray)
+        .add_modifier(Modifier::DIM);
+    let cursor = if filtering { "_" } else { "" };
+    let mut body = vec![
+        Line::from(vec![
+            Span::styled("Tag: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!("{tag}{cursor}")),
+        ]),
+        Line::from(""),
+    ];
+    if rows.is_empty() {
+        body.push(Line::styled("No sessions with a matching tag.", dim));
+        return body;
+    }
+    let max_rows = 12;
+    let start = selected.saturating_sub(max_rows - 1);
+    for (offset, row) in rows.iter().enumerate().skip(start).take(max_rows) {
+        let line = Line::from(row.clone());
+        body.push(if offset == selected {
+            line.style(Style::default().add_modifier(Modifier::REVERSED))
+        } else {
+            line
+        });
+    }
+    if rows.len() > start + max_rows {
+        body.push(Line::styled(
+            format!("... ({} more)", rows.len() - start - max_rows),
+            dim,
+        ));
+    }
+    body
+}
+
+fn styled_diff_line(line: &str, diff_style: &DiffStyle) -> Line<'static> {
+    let kind = diff_row_kind(line);
+    let style = match kind {
+        Some(kind) => diff_style.style(kind),
+        None => Style::default().fg(Color::Gray),
+    };
+    diff_style.line(line, kind, style)
 }
 
 fn centered_modal_area(size: Rect, preferred_height: u16) -> Rect {
@@ -341,29 +978,6 @@ fn centered_modal_area(size: Rect, preferred_height: u16) -> Rect {
     Rect::new(x, y, width, height)
 }
 
-fn truncate_line(input: &str, width: usize) -> String {
-    let width = width.max(1);
-    let mut out = String::new();
-    let mut used = 0usize;
-    let mut truncated = false;
-
-    for ch in input.chars() {
-        let ch_width = char_display_width(ch);
-        if used + ch_width > width {
-            truncated = true;
-            break;
-        }
-        out.push(ch);
-        used += ch_width;
-    }
-
-    if truncated && width >= 4 {
-        out = truncate_to_display_width(&out, width - 3);
-        out.push_str("...");
-    }
-    out
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -373,17 +987,74 @@ mod tests {
     fn all_modals_use_unified_renderer() {
         let backend = TestBackend::new(100, 30);
         let mut terminal = Terminal::new(backend).expect("test terminal");
+        let queued = vec!["write_file path: b.txt".to_string()];
+        let paths = vec!["/work/Cargo.toml".to_string()];
+        let sensitive = ApprovalTier::Sensitive {
+            path: "Cargo.toml".to_string(),
+            pattern: "Cargo.toml".to_string(),
+        };
 
+        let history_rows = vec![PromptHistoryRow {
+            number: 1,
+            age: "2m".to_string(),
+            outcome: PromptOutcome::Error,
+            text: "fix the parser".to_string(),
+        }];
+        let presets: Vec<Preset> = ["bugfix", "refactor"]
+            .into_iter()
+            .map(|name| Preset {
+                name: name.to_string(),
+                title: format!("{name} template"),
+                template: String::new(),
+            })
+            .collect();
+        let session_rows = vec!["abc-1  1970-01-01 00:00:00  2 turns  fix it  #bug".to_string()];
         let modals = [
             OverlayModal::PatchApprove {
                 patch_preview: "diff --git a/src/app/mod.rs b/src/app/mod.rs",
                 scroll_offset: 0,
                 viewport_rows: 8,
+                diff_style: DiffStyle::default(),
             },
             OverlayModal::ToolPermission {
                 tool_name: "exec_command",
                 input_preview: "echo hi",
+                class: ToolClass::Mutating,
+                paths: &[],
                 auto_approve_enabled: false,
+                reason_entry: false,
+                prompt_focused: false,
+                tier: &ApprovalTier::Standard,
+                queued: &[],
+                diff_style: DiffStyle::default(),
+            },
+            OverlayModal::ToolPermission {
+                tool_name: "write_file",
+                input_preview: "path: Cargo.toml",
+                class: ToolClass::Destructive,
+                paths: &paths,
+                auto_approve_enabled: false,
+                reason_entry: true,
+                prompt_focused: false,
+                tier: &sensitive,
+                queued: &queued,
+                diff_style: DiffStyle::default(),
+            },
+            OverlayModal::HistoryPicker {
+                query: "pars",
+                filtering: true,
+                rows: &history_rows,
+                selected: 0,
+            },
+            OverlayModal::PresetPicker {
+                presets: &presets,
+                selected: 1,
+            },
+            OverlayModal::SessionPicker {
+                tag: "bug",
+                filtering: false,
+                rows: &session_rows,
+                selected: 0,
             },
         ];
 
@@ -394,6 +1065,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn tool_permission_modal_lists_queued_calls_dimmed() {
+        let queued: Vec<String> = (1..=6).map(|n| format!("read_file path: {n}.rs")).collect();
+        let (_, _, body, _) = modal_content(OverlayModal::ToolPermission {
+            tool_name: "write_file",
+            input_preview: "path: a.txt",
+            class: ToolClass::Destructive,
+            paths: &["/work/a.txt".to_string()],
+            auto_approve_enabled: false,
+            reason_entry: false,
+            prompt_focused: false,
+            tier: &ApprovalTier::Standard,
+            queued: &queued,
+            diff_style: DiffStyle::default(),
+        });
+        let text: Vec<String> = body.iter().map(|line| line.to_string()).collect();
+        assert_eq!(text[0], "Tool: write_file (destructive)");
+        assert_eq!(body[0].style.fg, Some(Color::Red));
+        assert_eq!(text[1], "Paths: /work/a.txt");
+        let header = text
+            .iter()
+            .position(|line| line.starts_with("Queued (6)"))
+            .expect("queued header");
+        assert_eq!(text[header + 1], "  read_file path: 1.rs");
+        assert_eq!(text[header + 5], "  ... (2 more)");
+        assert_eq!(body[header + 1].style.fg, Some(Color::DarkGray));
+    }
+
     #[test]
     fn input_window_start_scrolls_once_cursor_exceeds_visible_rows() {
         assert_eq!(input_window_start(0, 4), 0);
@@ -402,12 +1101,38 @@ mod tests {
         assert_eq!(input_window_start(7, 4), 4);
     }
 
+    #[test]
+    fn input_viewport_pins_on_scroll_and_clamps_to_draft() {
+        let mut viewport = InputViewport::default();
+        assert_eq!(viewport.window_start(9, 10, 4), 6);
+
+        viewport.scroll_up();
+        viewport.scroll_up();
+        assert_eq!(viewport.window_start(9, 10, 4), 4);
+        for _ in 0..10 {
+            viewport.scroll_down();
+        }
+        assert_eq!(viewport.window_start(9, 10, 4), 6);
+
+        viewport.follow_cursor();
+        assert_eq!(viewport.window_start(2, 10, 4), 0);
+        assert_eq!(input_overflow_label(1, "below"), " (+1 line below) ");
+    }
+
+    #[test]
+    fn input_pane_rows_cap_at_half_the_screen() {
+        let draft = "x\n".repeat(20);
+        assert_eq!(input_pane_rows("", 80, 40), 1);
+        assert_eq!(input_pane_rows(&draft, 80, 40), MAX_INPUT_PANE_ROWS as u16);
+        assert_eq!(input_pane_rows(&draft, 80, 10), 5);
+    }
+
     #[test]
     fn diff_line_semantics_are_styled_by_prefix() {
-        let add = styled_diff_line("+added");
-        let del = styled_diff_line("-removed");
-        let hunk = styled_diff_line("@@ -1 +1 @@");
-        let ctx = styled_diff_line(" context");
+        let add = styled_diff_line("+added", &DiffStyle::default());
+        let del = styled_diff_line("-removed", &DiffStyle::default());
+        let hunk = styled_diff_line("@@ -1 +1 @@", &DiffStyle::default());
+        let ctx = styled_diff_line(" context", &DiffStyle::default());
 
         assert_eq!(add.style.fg, Some(Color::Green));
         assert_eq!(del.style.fg, Some(Color::Red));
@@ -415,6 +1140,31 @@ mod tests {
         assert_eq!(ctx.style.fg, Some(Color::Gray));
     }
 
+    #[test]
+    fn history_cache_rewraps_only_after_history_or_width_changes() {
+        let mut terminal = Terminal::new(TestBackend::new(40, 3)).expect("terminal");
+        let mut cache = HistoryRenderCache::default();
+        let mut messages = vec!["alpha".to_string(), "beta".to_string()];
+        let mut draw = |messages: &[String], cache: &mut HistoryRenderCache, width: u16| {
+            terminal
+                .draw(|frame| {
+                    let area = Rect::new(0, 0, width, 3);
+                    render_messages_cached(frame, area, messages, 1, &DiffStyle::default(), cache);
+                })
+                .expect("draw");
+            cache.key
+        };
+
+        let first = draw(&messages, &mut cache, 40);
+        assert_eq!(cache.body.len(), 2);
+        assert_eq!(draw(&messages, &mut cache, 40), first);
+        messages.push("gamma".to_string());
+        let grown = draw(&messages, &mut cache, 40);
+        assert_ne!(grown, first);
+        assert_eq!(cache.body.len(), 3);
+        assert_ne!(draw(&messages, &mut cache, 30), grown);
+    }
+
     #[test]
     fn history_visual_line_count_tracks_embedded_newlines() {
         let messages = vec![
@@ -431,11 +1181,57 @@ mod tests {
         assert_eq!(history_visual_line_count(&messages, 3), 2);
     }
 
+    #[test]
+    fn wrap_history_row_breaks_on_words_with_hanging_list_indent() {
+        let rows = wrap_history_row("- alpha beta gamma delta", 12);
+        assert_eq!(rows, vec!["- alpha beta", "  gamma", "  delta"]);
+        assert!(rows.iter().all(|row| display_width(row) <= 12));
+    }
+
+    #[test]
+    fn wrap_history_row_indents_tool_diff_and_numbered_lines() {
+        assert_eq!(
+            wrap_history_row("[tool approval requested: write_file]", 20),
+            vec!["[tool approval", " requested:", " write_file]"]
+        );
+        assert_eq!(wrap_history_row("+let x = 1;", 8), vec!["+let x =", " 1;"]);
+        assert_eq!(
+            wrap_history_row("12. first second", 10),
+            vec!["12. first", "    second"]
+        );
+    }
+
+    #[test]
+    fn wrap_history_row_splits_overlong_words_and_keeps_short_rows() {
+        assert_eq!(wrap_history_row("short", 10), vec!["short"]);
+        assert_eq!(wrap_history_row("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert_eq!(wrap_history_row("世界世界", 5), vec!["世界", "世界"]);
+    }
+
+    #[test]
+    fn history_visual_line_count_follows_word_wrap_on_resize() {
+        let messages = vec!["- alpha beta gamma delta".to_string()];
+        assert_eq!(history_visual_line_count(&messages, 80), 1);
+        assert_eq!(history_visual_line_count(&messages, 12), 3);
+    }
+
     #[test]
     fn history_row_style_marks_diff_rows() {
-        assert_eq!(history_row_style("+add").fg, Some(Color::Green));
-        assert_eq!(history_row_style("-del").fg, Some(Color::Red));
-        assert_eq!(history_row_style("@@ -1 +1 @@").fg, Some(Color::Cyan));
-        assert_eq!(history_row_style("plain text").fg, Some(Color::White));
+        assert_eq!(
+            history_row_style("+add", &DiffStyle::default()).1.fg,
+            Some(Color::Green)
+        );
+        assert_eq!(
+            history_row_style("-del", &DiffStyle::default()).1.fg,
+            Some(Color::Red)
+        );
+        assert_eq!(
+            history_row_style("@@ -1 +1 @@", &DiffStyle::default()).1.fg,
+            Some(Color::Cyan)
+        );
+        assert_eq!(
+            history_row_style("plain text", &DiffStyle::default()).1.fg,
+            Some(Color::White)
+        );
     }
 }
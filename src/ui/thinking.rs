@@ -0,0 +1,131 @@
+const THINKING_DISPLAY_ENV: &str = "VEX_THINKING_DISPLAY";
+const COLLAPSED_THINKING_LINES: usize = 4;
+
+/// How a thinking block is shown in the transcript. The block always keeps
+/// its full content, so any block can be re-rendered after the fact.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThinkingDisplay {
+    #[default]
+    Collapsed,
+    Expanded,
+    Hidden,
+}
+
+impl ThinkingDisplay {
+    /// Reads `VEX_THINKING_DISPLAY` (`collapsed`, `expanded`, or `hidden`).
+    pub fn from_env() -> Self {
+        std::env::var(THINKING_DISPLAY_ENV)
+            .ok()
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "collapsed" | "collapse" => Some(Self::Collapsed),
+            "expanded" | "expand" => Some(Self::Expanded),
+            "hidden" | "hide" => Some(Self::Hidden),
+            _ => None,
+        }
+    }
+}
+
+/// Thinking text streamed before a round's tool calls, tied to the history
+/// line that displays it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThinkingBlock {
+    pub line: usize,
+    pub content: String,
+    pub display: ThinkingDisplay,
+}
+
+impl ThinkingBlock {
+    pub fn new(line: usize, display: ThinkingDisplay) -> Self {
+        Self {
+            line,
+            content: String::new(),
+            display,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let line_count = self.content.trim_end().lines().count();
+        match self.display {
+            ThinkingDisplay::Expanded => self.content.clone(),
+            ThinkingDisplay::Collapsed if line_count <= COLLAPSED_THINKING_LINES => {
+                self.content.clone()
+            }
+            ThinkingDisplay::Collapsed => {
+                let shown = self
+                    .content
+                    .lines()
+                    .take(COLLAPSED_THINKING_LINES)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!(
+                    "{shown}\n  (+{} more lines, Ctrl+T to expand)",
+                    line_count - COLLAPSED_THINKING_LINES
+                )
+            }
+            ThinkingDisplay::Hidden => {
+                format!("[thinking hidden: {line_count} lines, Ctrl+T to show]")
+            }
+        }
+    }
+
+    /// Flips between expanded and `fallback`, or collapsed when the
+    /// fallback is itself expanded.
+    pub fn toggle(&mut self, fallback: ThinkingDisplay) {
+        self.display = match (self.display, fallback) {
+            (ThinkingDisplay::Expanded, ThinkingDisplay::Expanded) => ThinkingDisplay::Collapsed,
+            (ThinkingDisplay::Expanded, fallback) => fallback,
+            _ => ThinkingDisplay::Expanded,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(lines: usize, display: ThinkingDisplay) -> ThinkingBlock {
+        let mut block = ThinkingBlock::new(0, display);
+        block.content = (1..=lines)
+            .map(|n| format!("step {n}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        block
+    }
+
+    #[test]
+    fn test_render_modes_keep_full_content() {
+        let mut thinking = block(6, ThinkingDisplay::Collapsed);
+        assert_eq!(
+            thinking.render(),
+            "step 1\nstep 2\nstep 3\nstep 4\n  (+2 more lines, Ctrl+T to expand)"
+        );
+        thinking.toggle(ThinkingDisplay::Collapsed);
+        assert_eq!(thinking.render(), thinking.content);
+        thinking.toggle(ThinkingDisplay::Hidden);
+        assert_eq!(
+            thinking.render(),
+            "[thinking hidden: 6 lines, Ctrl+T to show]"
+        );
+        assert_eq!(
+            block(3, ThinkingDisplay::Collapsed).render(),
+            "step 1\nstep 2\nstep 3"
+        );
+    }
+
+    #[test]
+    fn test_toggle_with_expanded_default_collapses() {
+        let mut thinking = block(6, ThinkingDisplay::Expanded);
+        thinking.toggle(ThinkingDisplay::Expanded);
+        assert_eq!(thinking.display, ThinkingDisplay::Collapsed);
+        assert_eq!(
+            ThinkingDisplay::parse(" Hidden "),
+            Some(ThinkingDisplay::Hidden)
+        );
+        assert_eq!(ThinkingDisplay::parse("loud"), None);
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+pub const TRANSCRIPT_SPILL_LINES_ENV: &str = "VEX_TRANSCRIPT_SPILL_LINES";
+const DEFAULT_SPILL_LINES: usize = 200_000;
+const SEGMENT_LINES: usize = 1_000;
+
+#[derive(Debug)]
+struct Segment {
+    path: PathBuf,
+    lines: usize,
+}
+
+/// Transcript lines pushed out of memory by the history cap, kept in
+/// segment files under a temporary directory so scrolling up can page them
+/// back in. Holds at most `max_lines`; older segments are deleted.
+#[derive(Debug)]
+pub struct TranscriptSpill {
+    max_lines: usize,
+    dir: Option<TempDir>,
+    segments: VecDeque<Segment>,
+    next_segment: u64,
+    spilled: usize,
+    evicted: usize,
+}
+
+impl TranscriptSpill {
+    /// `max_lines` of 0 keeps nothing, which turns spilling off.
+    pub fn new(max_lines: usize) -> Self {
+        Self {
+            max_lines,
+            dir: None,
+            segments: VecDeque::new(),
+            next_segment: 0,
+            spilled: 0,
+            evicted: 0,
+        }
+    }
+
+    /// Size from `VEX_TRANSCRIPT_SPILL_LINES`, default 200000.
+    pub fn from_env() -> Self {
+        let max_lines = std::env::var(TRANSCRIPT_SPILL_LINES_ENV)
+            .ok()
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .unwrap_or(DEFAULT_SPILL_LINES);
+        Self::new(max_lines)
+    }
+
+    /// Stops spilling, e.g. after a write failed; spilled lines are lost.
+    pub fn disable(&mut self) {
+        *self = Self::new(0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.spilled
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spilled == 0
+    }
+
+    /// Lines deleted from the oldest end to stay within the limit.
+    pub fn evicted(&self) -> usize {
+        self.evicted
+    }
+
+    /// Appends `lines`, oldest first, after everything already spilled.
+    pub fn push(&mut self, lines: &[String]) -> Result<()> {
+        if self.max_lines == 0 {
+            self.evicted += lines.len();
+            return Ok(());
+        }
+        let dir = match &self.dir {
+            Some(dir) => dir.path().to_path_buf(),
+            None => {
+                let dir = tempfile::Builder::new()
+                    .prefix("vex-transcript-")
+                    .tempdir()
+                    .context("failed to create a transcript spill directory")?;
+                let path = dir.path().to_path_buf();
+                self.dir = Some(dir);
+                path
+            }
+        };
+        let mut rest = lines;
+        while !rest.is_empty() {
+            if self
+                .segments
+                .back()
+                .is_none_or(|segment| segment.lines >= SEGMENT_LINES)
+            {
+                let path = dir.join(format!("{:08}.jsonl", self.next_segment));
+                self.next_segment += 1;
+                self.segments.push_back(Segment { path, lines: 0 });
+            }
+            let Some(segment) = self.segments.back_mut() else {
+                break;
+            };
+            let take = rest.len().min(SEGMENT_LINES - segment.lines);
+            append_lines(&segment.path, &rest[..take])?;
+            segment.lines += take;
+            self.spilled += take;
+            rest = &rest[take..];
+        }
+
+        while self.spilled > self.max_lines {
+            let Some(oldest) = self.segments.pop_front() else {
+                break;
+            };
+            let _ = fs::remove_file(&oldest.path);
+            self.spilled -= oldest.lines;
+            self.evicted += oldest.lines;
+        }
+        Ok(())
+    }
+
+    /// Removes and returns up to `count` of the most recently spilled
+    /// lines, oldest first.
+    pub fn pop_newest(&mut self, count: usize) -> Result<Vec<String>> {
+        let mut taken = VecDeque::new();
+        while taken.len() < count {
+            let Some(segment) = self.segments.back_mut() else {
+                break;
+            };
+            let mut lines = read_lines(&segment.path)?;
+            let keep = lines.len().saturating_sub(count - taken.len());
+            let tail = lines.split_off(keep);
+            for line in tail.into_iter().rev() {
+                taken.push_front(line);
+            }
+            self.spilled -= segment.lines - keep;
+            if keep == 0 {
+                let _ = fs::remove_file(&segment.path);
+                self.segments.pop_back();
+            } else {
+                fs::write(&segment.path, "")
+                    .with_context(|| format!("failed to rewrite {}", segment.path.display()))?;
+                append_lines(&segment.path, &lines)?;
+                segment.lines = keep;
+            }
+        }
+        Ok(taken.into())
+    }
+}
+
+/// One JSON string per line, so lines with embedded newlines round-trip.
+fn append_lines(path: &Path, lines: &[String]) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let mut buffer = Vec::new();
+    for line in lines {
+        serde_json::to_writer(&mut buffer, line)?;
+        buffer.push(b'\n');
+    }
+    file.write_all(&buffer)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn read_lines(path: &Path) -> Result<Vec<String>> {
+    let raw =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    raw.lines()
+        .map(|line| serde_json::from_str(line).context("corrupt transcript spill segment"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(range: std::ops::Range<usize>) -> Vec<String> {
+        range.map(|index| format!("line {index}")).collect()
+    }
+
+    #[test]
+    fn test_lines_page_back_newest_first_and_the_ring_evicts_oldest() -> Result<()> {
+        let mut spill = TranscriptSpill::new(2_500);
+        spill.push(&numbered(0..1_500))?;
+        spill.push(&["multi\nline".to_string()])?;
+        spill.push(&numbered(1_500..2_000))?;
+        assert_eq!(spill.len(), 2_001);
+
+        let newest = spill.pop_newest(3)?;
+        assert_eq!(newest, vec!["line 1997", "line 1998", "line 1999"]);
+        let across_segments = spill.pop_newest(600)?;
+        assert_eq!(across_segments[0], "line 1398");
+        assert!(across_segments.contains(&"multi\nline".to_string()));
+        assert_eq!(
+            across_segments.last().map(String::as_str),
+            Some("line 1996")
+        );
+        assert_eq!(spill.len(), 1_398);
+
+        spill.push(&numbered(5_000..7_000))?;
+        assert_eq!(spill.evicted(), 1_000);
+        assert_eq!(spill.len(), 2_398);
+        let all = spill.pop_newest(usize::MAX)?;
+        assert_eq!(all[0], "line 1000");
+        assert!(spill.is_empty());
+        Ok(())
+    }
+}
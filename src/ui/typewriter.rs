@@ -0,0 +1,146 @@
+use std::time::{Duration, Instant};
+
+pub const TYPEWRITER_ENV: &str = "VEX_TYPEWRITER";
+const DEFAULT_CHARS_PER_SEC: u32 = 240;
+const MIN_CHARS_PER_SEC: u32 = 10;
+const MAX_CHARS_PER_SEC: u32 = 10_000;
+/// Text is released at most once per frame.
+const RELEASE_INTERVAL: Duration = Duration::from_millis(16);
+/// Text queued beyond this much at the set rate is shown at once, so the
+/// display never falls further behind the stream.
+const MAX_LAG: Duration = Duration::from_millis(500);
+
+/// Smooths streamed text into a steady per-character flow. Deltas are
+/// queued as they arrive and released a few characters per frame at the
+/// configured rate, catching up at once when the backlog grows too long.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Typewriter {
+    chars_per_sec: f64,
+    pending: String,
+    pending_chars: usize,
+    last_release: Option<Instant>,
+    /// Fractional characters earned but not yet released.
+    credit: f64,
+}
+
+impl Typewriter {
+    pub fn new(chars_per_sec: u32) -> Self {
+        Self {
+            chars_per_sec: f64::from(chars_per_sec.clamp(MIN_CHARS_PER_SEC, MAX_CHARS_PER_SEC)),
+            pending: String::new(),
+            pending_chars: 0,
+            last_release: None,
+            credit: 0.0,
+        }
+    }
+
+    /// `VEX_TYPEWRITER=on` smooths at 240 characters per second, and a
+    /// number sets the rate. Off by default.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(TYPEWRITER_ENV).ok()?;
+        let value = value.trim();
+        if let Ok(rate) = value.parse::<u32>() {
+            return (rate > 0).then(|| Self::new(rate));
+        }
+        crate::util::parse_bool_str(value)
+            .filter(|enabled| *enabled)
+            .map(|_| Self::new(DEFAULT_CHARS_PER_SEC))
+    }
+
+    pub fn push(&mut self, text: &str) {
+        self.pending.push_str(text);
+        self.pending_chars += text.chars().count();
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// The text due by `now`, or `None` when nothing is due this frame.
+    pub fn release(&mut self, now: Instant) -> Option<String> {
+        if self.pending.is_empty() {
+            self.last_release = None;
+            self.credit = 0.0;
+            return None;
+        }
+        let Some(last) = self.last_release else {
+            // The first character of a burst shows at once.
+            self.last_release = Some(now);
+            return Some(self.take_chars(1));
+        };
+        let elapsed = now.saturating_duration_since(last);
+        if elapsed < RELEASE_INTERVAL {
+            return None;
+        }
+        self.credit += elapsed.as_secs_f64() * self.chars_per_sec;
+        self.last_release = Some(now);
+        let max_backlog = (MAX_LAG.as_secs_f64() * self.chars_per_sec) as usize;
+        let behind = self.pending_chars.saturating_sub(max_backlog);
+        let count = (self.credit.floor() as usize + behind).min(self.pending_chars);
+        if count == 0 {
+            return None;
+        }
+        self.credit -= count.saturating_sub(behind) as f64;
+        Some(self.take_chars(count))
+    }
+
+    /// Everything still queued, for when other output must not overtake it.
+    pub fn flush(&mut self) -> Option<String> {
+        self.last_release = None;
+        self.credit = 0.0;
+        self.pending_chars = 0;
+        (!self.pending.is_empty()).then(|| std::mem::take(&mut self.pending))
+    }
+
+    fn take_chars(&mut self, count: usize) -> String {
+        let end = self
+            .pending
+            .char_indices()
+            .nth(count)
+            .map_or(self.pending.len(), |(index, _)| index);
+        self.pending_chars -= count.min(self.pending_chars);
+        self.pending.drain(..end).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_releases_at_the_rate_and_catches_up_on_a_backlog() {
+        let start = Instant::now();
+        let mut typewriter = Typewriter::new(100);
+        assert_eq!(typewriter.release(start), None);
+        typewriter.push("héllo world");
+        assert_eq!(typewriter.release(start).as_deref(), Some("h"));
+        assert_eq!(
+            typewriter.release(start + Duration::from_millis(5)),
+            None,
+            "at most one release per frame"
+        );
+        // 100 chars/s over 50ms is five characters.
+        assert_eq!(
+            typewriter
+                .release(start + Duration::from_millis(50))
+                .as_deref(),
+            Some("éllo ")
+        );
+        assert_eq!(typewriter.flush().as_deref(), Some("world"));
+        assert!(typewriter.is_idle());
+
+        // 500 queued characters at 100/s would take 5s; all but the last
+        // half second's worth show at once.
+        typewriter.push(&"x".repeat(500));
+        let mut released = typewriter.release(start).map_or(0, |text| text.len());
+        let mut now = start + Duration::from_millis(16);
+        released += typewriter.release(now).map_or(0, |text| text.len());
+        assert!(released > 440, "caught up to half a second behind");
+        for _ in 0..40 {
+            now += Duration::from_millis(16);
+            released += typewriter.release(now).map_or(0, |text| text.len());
+        }
+        assert_eq!(released, 500, "backlog drained within 640ms");
+        assert_eq!(typewriter.release(now + Duration::from_secs(1)), None);
+    }
+}
@@ -0,0 +1,307 @@
+use crate::config::Config;
+use crate::state_dirs::StateDirs;
+use crate::types::TokenUsage;
+use crate::util::{parse_bool_str, unix_now};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const DEFAULT_REPORT_SINCE: &str = "7d";
+
+pub const USAGE_USAGE: &str =
+    "usage: vex usage report [--since <7d|24h|30m>] [--log <usage.jsonl>]";
+
+/// One completed turn. Records carry counts and timings only, never prompt
+/// or response content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub timestamp: u64,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub duration_ms: u64,
+    pub outcome: String,
+}
+
+impl UsageRecord {
+    pub fn new(model: &str, usage: TokenUsage, duration: Duration, succeeded: bool) -> Self {
+        Self {
+            timestamp: unix_now(),
+            model: model.to_string(),
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            duration_ms: duration.as_millis() as u64,
+            outcome: if succeeded { "complete" } else { "error" }.to_string(),
+        }
+    }
+}
+
+/// Opt-in, local-only JSONL log of per-turn usage.
+#[derive(Debug, Clone)]
+pub struct UsageLog {
+    path: PathBuf,
+}
+
+impl UsageLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn for_workspace(working_dir: &Path) -> Self {
+        Self::new(StateDirs::for_workspace(working_dir).usage_log())
+    }
+
+    /// `VEX_USAGE_LOG=on` logs to `.aistar/usage.jsonl`; any other non-boolean
+    /// value is used as the log path. Unset or `off` disables logging.
+    pub fn from_env(working_dir: &Path) -> Option<Self> {
+        let value = std::env::var("VEX_USAGE_LOG").ok()?;
+        let value = value.trim();
+        match parse_bool_str(value) {
+            Some(true) => Some(Self::for_workspace(working_dir)),
+            Some(false) => None,
+            None if value.is_empty() => None,
+            None => Some(Self::new(working_dir.join(value))),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, record: &UsageRecord) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(record)?)
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Reads all records, skipping lines that do not parse.
+    pub fn read(&self) -> Result<Vec<UsageRecord>> {
+        let raw = match fs::read_to_string(&self.path) {
+            Ok(raw) => raw,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed to read {}", self.path.display()))
+            }
+        };
+        Ok(raw
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageSummary {
+    pub turns: u64,
+    pub errors: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub duration_ms: u64,
+}
+
+/// Totals per model for records at or after `cutoff` (unix seconds).
+pub fn summarize_usage(records: &[UsageRecord], cutoff: u64) -> BTreeMap<String, UsageSummary> {
+    let mut summary = BTreeMap::<String, UsageSummary>::new();
+    for record in records.iter().filter(|record| record.timestamp >= cutoff) {
+        let entry = summary.entry(record.model.clone()).or_default();
+        entry.turns += 1;
+        entry.errors += u64::from(record.outcome != "complete");
+        entry.input_tokens += record.input_tokens;
+        entry.output_tokens += record.output_tokens;
+        entry.duration_ms += record.duration_ms;
+    }
+    summary
+}
+
+pub fn render_usage_report(since: &str, summary: &BTreeMap<String, UsageSummary>) -> String {
+    let mut report = format!("# Usage since {since} ago\n\n");
+    if summary.is_empty() {
+        report.push_str("No turns recorded.\n");
+        return report;
+    }
+    report.push_str(
+        "| Model | Turns | Errors | Input tokens | Output tokens | Time (s) |\n\
+         |---|---:|---:|---:|---:|---:|\n",
+    );
+    let mut total = UsageSummary::default();
+    for (model, row) in summary {
+        report.push_str(&render_usage_row(model, row));
+        total.turns += row.turns;
+        total.errors += row.errors;
+        total.input_tokens += row.input_tokens;
+        total.output_tokens += row.output_tokens;
+        total.duration_ms += row.duration_ms;
+    }
+    if summary.len() > 1 {
+        report.push_str(&render_usage_row("**total**", &total));
+    }
+    report
+}
+
+fn render_usage_row(label: &str, row: &UsageSummary) -> String {
+    format!(
+        "| {label} | {} | {} | {} | {} | {:.1} |\n",
+        row.turns,
+        row.errors,
+        row.input_tokens,
+        row.output_tokens,
+        row.duration_ms as f64 / 1000.0
+    )
+}
+
+/// Parses `7d`, `24h`, `30m`, `90s`, or a bare number of days.
+pub fn parse_since(spec: &str) -> Result<Duration> {
+    let spec = spec.trim();
+    let (digits, unit) = match spec.find(|ch: char| !ch.is_ascii_digit()) {
+        Some(split) => spec.split_at(split),
+        None => (spec, "d"),
+    };
+    let amount: u64 = digits
+        .parse()
+        .with_context(|| format!("invalid --since value '{spec}'"))?;
+    let seconds = match unit {
+        "d" => 86_400,
+        "h" => 3_600,
+        "m" => 60,
+        "s" => 1,
+        _ => bail!("invalid --since unit in '{spec}' (expected d, h, m, or s)"),
+    };
+    Ok(Duration::from_secs(amount.saturating_mul(seconds)))
+}
+
+pub fn run_usage_cli(config: &Config, args: &[String]) -> Result<()> {
+    let mut iter = args.iter();
+    match iter.next().map(String::as_str) {
+        Some("report") => {}
+        Some(other) => bail!("Unknown usage command '{other}'\n{USAGE_USAGE}"),
+        None => bail!("{USAGE_USAGE}"),
+    }
+
+    let mut since = DEFAULT_REPORT_SINCE.to_string();
+    let mut log = None;
+    while let Some(arg) = iter.next() {
+        let mut value = |flag: &str| {
+            iter.next()
+                .cloned()
+                .with_context(|| format!("{flag} requires a value\n{USAGE_USAGE}"))
+        };
+        match arg.as_str() {
+            "--since" => since = value("--since")?,
+            "--log" => log = Some(UsageLog::new(PathBuf::from(value("--log")?))),
+            other => bail!("Unknown usage argument '{other}'\n{USAGE_USAGE}"),
+        }
+    }
+
+    let window = parse_since(&since)?;
+    let log = log
+        .or_else(|| UsageLog::from_env(&config.working_dir))
+        .unwrap_or_else(|| UsageLog::for_workspace(&config.working_dir));
+    let cutoff = unix_now().saturating_sub(window.as_secs());
+    let summary = summarize_usage(&log.read()?, cutoff);
+    print!("{}", render_usage_report(&since, &summary));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(timestamp: u64, model: &str, input: u64, output: u64, outcome: &str) -> UsageRecord {
+        UsageRecord {
+            timestamp,
+            model: model.to_string(),
+            input_tokens: input,
+            output_tokens: output,
+            duration_ms: 1500,
+            outcome: outcome.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_since_units() {
+        assert_eq!(parse_since("7d").unwrap(), Duration::from_secs(7 * 86_400));
+        assert_eq!(parse_since("24h").unwrap(), Duration::from_secs(86_400));
+        assert_eq!(parse_since("30m").unwrap(), Duration::from_secs(1_800));
+        assert_eq!(parse_since("3").unwrap(), Duration::from_secs(3 * 86_400));
+        assert!(parse_since("7w").is_err());
+        assert!(parse_since("d").is_err());
+    }
+
+    #[test]
+    fn test_log_round_trip_skips_corrupt_lines() -> Result<()> {
+        let temp = TempDir::new()?;
+        let log = UsageLog::for_workspace(temp.path());
+        log.append(&record(10, "model-a", 100, 20, "complete"))?;
+        fs::OpenOptions::new()
+            .append(true)
+            .open(log.path())?
+            .write_all(b"{not json\n")?;
+        log.append(&record(20, "model-b", 5, 1, "error"))?;
+
+        let records = log.read()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].model, "model-b");
+        Ok(())
+    }
+
+    #[test]
+    fn test_summary_filters_by_cutoff_and_groups_by_model() {
+        let records = vec![
+            record(5, "model-a", 1_000, 1_000, "complete"),
+            record(100, "model-a", 100, 20, "complete"),
+            record(200, "model-a", 50, 10, "error"),
+            record(300, "model-b", 7, 3, "complete"),
+        ];
+        let summary = summarize_usage(&records, 50);
+        assert_eq!(
+            summary["model-a"],
+            UsageSummary {
+                turns: 2,
+                errors: 1,
+                input_tokens: 150,
+                output_tokens: 30,
+                duration_ms: 3000,
+            }
+        );
+        let report = render_usage_report("7d", &summary);
+        assert!(report.contains("| model-b | 1 | 0 | 7 | 3 | 1.5 |"));
+        assert!(report.contains("| **total** | 3 | 1 | 157 | 33 | 4.5 |"));
+    }
+
+    #[test]
+    fn test_from_env_is_opt_in() {
+        let _env_lock = crate::test_support::ENV_LOCK.blocking_lock();
+        let temp = TempDir::new().expect("tempdir");
+
+        std::env::remove_var("VEX_USAGE_LOG");
+        assert!(UsageLog::from_env(temp.path()).is_none());
+        std::env::set_var("VEX_USAGE_LOG", "off");
+        assert!(UsageLog::from_env(temp.path()).is_none());
+        std::env::set_var("VEX_USAGE_LOG", "on");
+        assert_eq!(
+            UsageLog::from_env(temp.path()).unwrap().path(),
+            temp.path().join(".aistar/usage.jsonl")
+        );
+        std::env::set_var("VEX_USAGE_LOG", "logs/vex.jsonl");
+        assert_eq!(
+            UsageLog::from_env(temp.path()).unwrap().path(),
+            temp.path().join("logs/vex.jsonl")
+        );
+        std::env::remove_var("VEX_USAGE_LOG");
+    }
+}
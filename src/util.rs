@@ -1,4 +1,5 @@
 use reqwest::Url;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Parse "true"/"false"/"1"/"0" from an owned String.
 pub fn parse_bool_flag(s: String) -> Option<bool> {
@@ -33,6 +34,54 @@ pub fn is_local_endpoint_url(url: &str) -> bool {
     }
 }
 
+/// True for `anthropic.com` and its subdomains.
+pub fn is_anthropic_host(url: &str) -> bool {
+    Url::parse(url.trim())
+        .ok()
+        .and_then(|parsed| parsed.host_str().map(str::to_ascii_lowercase))
+        .is_some_and(|host| host == "anthropic.com" || host.ends_with(".anthropic.com"))
+}
+
+/// `4200` as `4,200`.
+pub fn group_thousands(value: usize) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// `125s` as `2m 05s`; hours drop the seconds.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3_599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3_600, secs % 3_600 / 60),
+    }
+}
+
+/// Lowercase hex SHA-256 of `bytes`.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Seconds since the Unix epoch, or 0 when the clock is set before it.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -46,6 +95,14 @@ mod tests {
         assert_eq!(parse_bool_str("maybe"), None);
     }
 
+    #[test]
+    fn test_group_thousands() {
+        assert_eq!(group_thousands(0), "0");
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(4_200), "4,200");
+        assert_eq!(group_thousands(1_234_567), "1,234,567");
+    }
+
     #[test]
     fn test_is_local_endpoint_url_normalizes_case_and_space() {
         assert!(is_local_endpoint_url(" HTTP://LOCALHOST:8000/v1/messages "));
@@ -1,4 +1,4 @@
-use vexcoder::config::Config;
+use vexcoder::config::{Config, Sampling};
 
 #[test]
 fn test_config_validation_rejects_invalid_models_for_remote_api() {
@@ -7,7 +7,11 @@ fn test_config_validation_rejects_invalid_models_for_remote_api() {
         model: "local/mock-model".to_string(),
         api_url: "https://api.anthropic.com/v1/messages".to_string(),
         anthropic_version: "2023-06-01".to_string(),
+        api_protocol: None,
         working_dir: std::env::current_dir().expect("cwd"),
+        emit_patches_dir: None,
+        check_api_key: false,
+        sampling: Sampling::default(),
     };
 
     assert!(config.validate().is_err());
@@ -20,7 +24,11 @@ fn test_config_validation_allows_local_endpoint_without_api_key() {
         model: "local/llama3.3".to_string(),
         api_url: "http://localhost:8000/v1/messages".to_string(),
         anthropic_version: "2023-06-01".to_string(),
+        api_protocol: None,
         working_dir: std::env::current_dir().expect("cwd"),
+        emit_patches_dir: None,
+        check_api_key: false,
+        sampling: Sampling::default(),
     };
 
     assert!(config.validate().is_ok());
@@ -1,4 +1,4 @@
-use vexcoder::api::stream::StreamParser;
+use vexcoder::api::stream::{StreamError, StreamParser};
 use vexcoder::types::{ContentBlock, StreamEvent};
 
 #[test]
@@ -131,3 +131,99 @@ fn test_openai_tool_call_stream_maps_to_unified_events() {
         other => panic!("unexpected event: {other:?}"),
     }
 }
+
+#[test]
+fn test_error_event_is_typed() {
+    let mut parser = StreamParser::new();
+
+    let chunk = b"event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n";
+    let events = parser.process(chunk).expect("error event should parse");
+    assert_eq!(events.len(), 1);
+
+    match &events[0] {
+        StreamEvent::Error { error } => {
+            let typed = StreamError::from(error.clone());
+            assert_eq!(
+                typed,
+                StreamError::Provider {
+                    kind: "overloaded_error".to_string(),
+                    message: "Overloaded".to_string(),
+                }
+            );
+            assert!(typed.is_retryable());
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+}
+
+#[test]
+fn test_openai_error_chunk_is_typed() {
+    let mut parser = StreamParser::new();
+
+    let chunk = br#"data: {"error":{"message":"context too long","type":"invalid_request_error"}}
+
+"#;
+    let events = parser.process(chunk).expect("openai error should parse");
+    match &events[..] {
+        [StreamEvent::Error { error }] => {
+            assert_eq!(error.error_type, "invalid_request_error");
+            assert!(!StreamError::from(error.clone()).is_retryable());
+        }
+        other => panic!("unexpected events: {other:?}"),
+    }
+}
+
+#[test]
+fn test_ping_between_deltas_is_surfaced() {
+    let mut parser = StreamParser::new();
+
+    let chunk = b"event: ping\ndata: {\"type\": \"ping\"}\n\nevent: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n";
+    let events = parser.process(chunk).expect("ping should not fail parser");
+    assert_eq!(events.len(), 2);
+    assert!(matches!(events[0], StreamEvent::Ping));
+    assert!(matches!(events[1], StreamEvent::ContentBlockDelta { .. }));
+}
+
+#[test]
+fn test_corrupted_frame_is_skipped_and_stream_continues() {
+    let mut parser = StreamParser::new();
+
+    let mut chunk = b"event: content_block_delta\ndata: \xff\xfe broken\n\n".to_vec();
+    chunk.extend_from_slice(b"event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"ok\"}}\n\n");
+    let events = parser
+        .process(&chunk)
+        .expect("a single corrupted frame should be skipped");
+    assert_eq!(events.len(), 1);
+}
+
+#[test]
+fn test_run_of_malformed_frames_fails_with_typed_error() {
+    let mut parser = StreamParser::new();
+
+    let chunk = b"data: {not json}\n\n".repeat(8);
+    let error = parser
+        .process(&chunk)
+        .expect_err("a run of malformed frames should fail the stream");
+    assert!(matches!(
+        error.downcast_ref::<StreamError>(),
+        Some(StreamError::MalformedStream { .. })
+    ));
+}
+
+#[test]
+fn test_openai_length_finish_maps_to_max_tokens_stop() {
+    let mut parser = StreamParser::new();
+    let chunk = br#"data: {"id":"chatcmpl-2","object":"chat.completion.chunk","choices":[{"index":0,"delta":{"content":"Cut off"},"finish_reason":"length"}]}
+
+"#;
+    let events = parser
+        .process(chunk)
+        .expect("openai length chunk should parse");
+    assert_eq!(events.len(), 2);
+    match &events[1] {
+        StreamEvent::MessageDelta { delta, .. } => {
+            assert_eq!(delta.stop_reason.as_deref(), Some("max_tokens"));
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+}